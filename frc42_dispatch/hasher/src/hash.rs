@@ -1,5 +1,8 @@
 use thiserror::Error;
 
+use crate::blake2b::blake2b_512;
+use crate::keccak::keccak256;
+
 /// Minimal interface for a hashing function
 ///
 /// Hasher::hash() must return a digest that is at least 4 bytes long so that it can be cast to a
@@ -23,6 +26,19 @@ impl Hasher for Blake2bSyscall {
     }
 }
 
+/// Hasher that uses the keccak-256 syscall provided by the FVM, for resolving EVM selectors
+#[cfg(feature = "use_sdk")]
+#[derive(Default)]
+pub struct Keccak256Syscall {}
+
+#[cfg(feature = "use_sdk")]
+impl Hasher for Keccak256Syscall {
+    fn hash(&self, bytes: &[u8]) -> Vec<u8> {
+        use fvm_shared::crypto::hash::SupportedHashes;
+        fvm_sdk::crypto::hash_owned(SupportedHashes::Keccak256, bytes)
+    }
+}
+
 /// Uses an underlying hashing function (blake2b by convention) to generate method numbers from
 /// method names
 #[derive(Default)]
@@ -38,6 +54,8 @@ pub enum MethodNameErr {
     IllegalName(#[from] IllegalNameErr),
     #[error("unable to calculate method id, choose a another method name")]
     IndeterminableId,
+    #[error("function signature must be of the form `name(type1,type2)` without spaces")]
+    IllegalSignature,
 }
 
 #[derive(Error, PartialEq, Eq, Debug)]
@@ -89,6 +107,118 @@ impl<T: Hasher> MethodResolver<T> {
 
         Err(MethodNameErr::IndeterminableId)
     }
+
+    /// Generates a Solidity-compatible 4 byte function selector from a canonical signature such
+    /// as `transfer(address,uint256)`
+    ///
+    /// The selector is the first four bytes of `hash(signature)`, so the resolver must be created
+    /// with a keccak-256 hasher. Unlike method numbers, selectors may take any value.
+    pub fn evm_selector(&self, signature: &str) -> Result<u32, MethodNameErr> {
+        if !is_valid_signature(signature.as_bytes()) {
+            return Err(MethodNameErr::IllegalSignature);
+        }
+        Ok(as_u32(&self.hasher.hash(signature.as_bytes())))
+    }
+}
+
+/// Generates a standard FRC-0042 compliant method number using blake2b, in a const context
+///
+/// Produces the same numbers as `MethodResolver::method_number` with a blake2b hasher, but can be
+/// used for consts, associated consts and match arms without depending on the proc macro crate.
+/// Panics if the name is not a valid method name, which is a compile error when evaluated in a
+/// const context.
+pub const fn method_number(method_name: &str) -> u64 {
+    let name = method_name.as_bytes();
+    if name.is_empty() {
+        panic!("empty method name provided");
+    }
+    if !(name[0].is_ascii_uppercase() || name[0] == b'_') {
+        panic!("method name doesn't start with capital letter or _");
+    }
+    let mut i = 0;
+    while i < name.len() {
+        if !(name[i].is_ascii_alphanumeric() || name[i] == b'_') {
+            panic!("method name contains letters outside [a-zA-Z0-9_]");
+        }
+        i += 1;
+    }
+    if is_constructor(name) {
+        return 1;
+    }
+
+    let digest = blake2b_512(b"1|", name);
+    // each word of the digest holds two chunks of four bytes
+    let mut chunk = 0;
+    while chunk < 16 {
+        let bytes = digest[chunk / 2].to_le_bytes();
+        let start = (chunk % 2) * 4;
+        let method_id = u32::from_be_bytes([
+            bytes[start],
+            bytes[start + 1],
+            bytes[start + 2],
+            bytes[start + 3],
+        ]) as u64;
+        // Method numbers below 2^24 are reserved for other use
+        if method_id >= 1 << 24 {
+            return method_id;
+        }
+        chunk += 1;
+    }
+    panic!("unable to calculate method id, choose a another method name");
+}
+
+/// Generates a Solidity-compatible 4 byte function selector using keccak-256, in a const context
+///
+/// Produces the same selectors as `MethodResolver::evm_selector` with a keccak-256 hasher, so that
+/// an actor can match both FRC-0042 method numbers and EVM selectors against consts. Panics if the
+/// signature is malformed.
+pub const fn evm_selector(signature: &str) -> u32 {
+    let signature = signature.as_bytes();
+    if !is_valid_signature(signature) {
+        panic!("function signature must be of the form `name(type1,type2)` without spaces");
+    }
+    let digest = keccak256(signature);
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+/// Checks a function signature is a name followed by a parenthesised list of types
+///
+/// The types themselves aren't validated, but may only contain characters that can appear in a
+/// canonical Solidity type: `[a-zA-Z0-9_]`, brackets for arrays and parentheses for tuples.
+const fn is_valid_signature(signature: &[u8]) -> bool {
+    if signature.is_empty() || !(signature[0].is_ascii_alphabetic() || signature[0] == b'_') {
+        return false;
+    }
+    let mut i = 0;
+    while i < signature.len() && (signature[i].is_ascii_alphanumeric() || signature[i] == b'_') {
+        i += 1;
+    }
+    if i == signature.len() || signature[i] != b'(' || signature[signature.len() - 1] != b')' {
+        return false;
+    }
+    while i < signature.len() {
+        let c = signature[i];
+        if !(c.is_ascii_alphanumeric() || matches!(c, b'_' | b',' | b'(' | b')' | b'[' | b']')) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn is_constructor(name: &[u8]) -> bool {
+    let constructor = b"Constructor";
+    if name.len() != constructor.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < name.len() {
+        if name[i] != constructor[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
 }
 
 /// Checks that a method name is valid and compliant with the FRC-0042 standard recommendations
@@ -125,7 +255,9 @@ fn as_u32(bytes: &[u8]) -> u32 {
 #[cfg(test)]
 mod tests {
 
-    use super::{Hasher, IllegalNameErr, MethodNameErr, MethodResolver};
+    use super::{
+        evm_selector, method_number, Hasher, IllegalNameErr, MethodNameErr, MethodResolver,
+    };
 
     #[derive(Clone, Copy)]
     struct FakeHasher {}
@@ -216,4 +348,64 @@ mod tests {
         // But the method number is not a collision
         assert_ne!(method_hasher_1.method_number(contrived_1).unwrap(), 1);
     }
+
+    #[test]
+    fn const_method_numbers_match_blake2b() {
+        const TRANSFER: u64 = method_number("Transfer");
+        assert_eq!(TRANSFER, 0x04cbf732);
+        assert_eq!(method_number("Constructor"), 1);
+        assert_eq!(method_number("Method"), 0xa20642fc);
+        assert_eq!(method_number("_Method"), 0xeb9575aa);
+        assert_eq!(method_number("BalanceOf"), 0x8710e1ac);
+        assert_eq!(method_number("TransferFrom"), 0xd7d4deed);
+
+        const BURN: u64 = method_number("Burn");
+        const MINT: u64 = method_number("Mint");
+        match 0x06f84ab2 {
+            BURN => panic!("unexpected method"),
+            MINT => {}
+            _ => panic!("unexpected method"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't start with capital letter")]
+    fn const_method_numbers_reject_invalid_names() {
+        method_number("invalidMethod");
+    }
+
+    #[test]
+    fn evm_selectors_match_solidity() {
+        const TRANSFER: u32 = evm_selector("transfer(address,uint256)");
+        assert_eq!(TRANSFER, 0xa9059cbb);
+        assert_eq!(evm_selector("balanceOf(address)"), 0x70a08231);
+        assert_eq!(evm_selector("approve(address,uint256)"), 0x095ea7b3);
+
+        // FRC-0042 method numbers and selectors can be matched in one table
+        const FRC42_TRANSFER: u64 = method_number("Transfer");
+        let dispatch = |method: u64| match method {
+            FRC42_TRANSFER => "native",
+            m if m == TRANSFER as u64 => "evm",
+            _ => "unknown",
+        };
+        assert_eq!(dispatch(0x04cbf732), "native");
+        assert_eq!(dispatch(0xa9059cbb), "evm");
+    }
+
+    #[test]
+    fn evm_selectors_use_the_resolver_hasher() {
+        let resolver = MethodResolver::new(FakeHasher {});
+        assert_eq!(
+            resolver.evm_selector("f(uint256[],(bool,bytes32))").unwrap(),
+            super::as_u32(b"f(ui")
+        );
+
+        for signature in ["", "transfer", "transfer(address, uint256)", "1f()", "f(x)y", "f("] {
+            assert_eq!(
+                resolver.evm_selector(signature).unwrap_err(),
+                MethodNameErr::IllegalSignature,
+                "{signature}"
+            );
+        }
+    }
 }
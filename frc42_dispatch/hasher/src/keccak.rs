@@ -0,0 +1,161 @@
+//! A minimal keccak-256 implementation that can be evaluated in const contexts
+//!
+//! This is the original Keccak padding used by Ethereum and Solidity, not the SHA3-256 variant
+//! standardised by NIST. Besides deriving EVM function selectors, it is used off-chain and in tests
+//! to build the digests Ethereum wallets sign, where no hashing syscall is available.
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Rotation offsets of the lanes visited by the rho and pi steps, in the order given by `PI`
+const RHO: [u32; 24] =
+    [1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44];
+
+const PI: [usize; 24] =
+    [10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1];
+
+/// Bytes absorbed per permutation for a 256 bit digest
+const RATE: usize = 136;
+
+/// Hashes `input`, returning the 32 byte digest
+pub const fn keccak256(input: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+    let mut offset = 0;
+    // the final block is always absorbed last as it holds the padding, even if the input is a
+    // whole number of blocks long
+    while input.len() - offset >= RATE {
+        state = permute(absorb(state, input, offset, RATE));
+        offset += RATE;
+    }
+    state = absorb(state, input, offset, input.len() - offset);
+    // pad with 0x01 after the input and 0x80 at the end of the block
+    let last = input.len() - offset;
+    state[last / 8] ^= 0x01 << (8 * (last % 8));
+    state[(RATE - 1) / 8] ^= 0x80 << (8 * ((RATE - 1) % 8));
+    state = permute(state);
+
+    let mut digest = [0u8; 32];
+    let mut i = 0;
+    while i < 32 {
+        digest[i] = (state[i / 8] >> (8 * (i % 8))) as u8;
+        i += 1;
+    }
+    digest
+}
+
+/// XORs `length` bytes of the input starting at `offset` into the state as little-endian lanes
+const fn absorb(mut state: [u64; 25], input: &[u8], offset: usize, length: usize) -> [u64; 25] {
+    let mut i = 0;
+    while i < length {
+        state[i / 8] ^= (input[offset + i] as u64) << (8 * (i % 8));
+        i += 1;
+    }
+    state
+}
+
+/// The keccak-f[1600] permutation
+const fn permute(mut a: [u64; 25]) -> [u64; 25] {
+    let mut round = 0;
+    while round < 24 {
+        // theta
+        let mut c = [0u64; 5];
+        let mut x = 0;
+        while x < 5 {
+            c[x] = a[x] ^ a[x + 5] ^ a[x + 10] ^ a[x + 15] ^ a[x + 20];
+            x += 1;
+        }
+        let mut x = 0;
+        while x < 5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            let mut y = 0;
+            while y < 25 {
+                a[y + x] ^= d;
+                y += 5;
+            }
+            x += 1;
+        }
+
+        // rho and pi
+        let mut last = a[1];
+        let mut i = 0;
+        while i < 24 {
+            let lane = a[PI[i]];
+            a[PI[i]] = last.rotate_left(RHO[i]);
+            last = lane;
+            i += 1;
+        }
+
+        // chi
+        let mut y = 0;
+        while y < 25 {
+            let row = [a[y], a[y + 1], a[y + 2], a[y + 3], a[y + 4]];
+            let mut x = 0;
+            while x < 5 {
+                a[y + x] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+                x += 1;
+            }
+            y += 5;
+        }
+
+        // iota
+        a[0] ^= ROUND_CONSTANTS[round];
+        round += 1;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::keccak256;
+
+    fn to_hex(bytes: [u8; 32]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn it_matches_reference_digests() {
+        assert_eq!(
+            to_hex(keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(
+            to_hex(keccak256(b"abc")),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+    }
+
+    #[test]
+    fn it_hashes_across_block_boundaries() {
+        // a whole block of input is followed by a block holding only padding
+        assert_eq!(
+            to_hex(keccak256(&[0u8; 136])),
+            "3a5912a7c5faa06ee4fe906253e339467a9ce87d533c65be3c15cb231cdb25f9"
+        );
+        assert_ne!(keccak256(&[0u8; 135]), keccak256(&[0u8; 136]));
+        assert_ne!(keccak256(&[0u8; 136]), keccak256(&[0u8; 137]));
+    }
+}
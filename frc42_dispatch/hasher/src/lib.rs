@@ -1 +1,3 @@
+mod blake2b;
 pub mod hash;
+pub mod keccak;
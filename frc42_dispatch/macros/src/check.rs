@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use frc42_hasher::hash::MethodResolver;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Error, LitStr, Result, Token};
+
+use crate::hash::Blake2bHasher;
+
+/// Method numbers below this are reserved, other than the constructor
+const FIRST_METHOD_NUMBER: u64 = 1 << 24;
+const CONSTRUCTOR_METHOD_NUMBER: u64 = 1;
+
+/// A comma separated list of method names
+pub struct MethodNames(pub Vec<LitStr>);
+
+impl Parse for MethodNames {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let names = Punctuated::<LitStr, Token![,]>::parse_terminated(input)?;
+        Ok(Self(names.into_iter().collect()))
+    }
+}
+
+/// Resolves the method names used by one actor, checking that no two share a method number
+#[derive(Default)]
+pub struct MethodNumbers {
+    numbers: BTreeMap<u64, String>,
+}
+
+impl MethodNumbers {
+    /// Resolves a method name, failing if it is invalid, reserved or collides with a name already
+    /// inserted
+    pub fn insert(&mut self, name: &LitStr) -> Result<u64> {
+        let number = MethodResolver::new(Blake2bHasher {})
+            .method_number(&name.value())
+            .map_err(|e| Error::new(name.span(), e))?;
+        if number != CONSTRUCTOR_METHOD_NUMBER && number < FIRST_METHOD_NUMBER {
+            return Err(Error::new(
+                name.span(),
+                format!("method {:?} hashes to the reserved method number {number}", name.value()),
+            ));
+        }
+        match self.numbers.insert(number, name.value()) {
+            Some(existing) if existing == name.value() => {
+                Err(Error::new(name.span(), format!("method {existing:?} is used more than once")))
+            }
+            Some(existing) => Err(Error::new(
+                name.span(),
+                format!("method {:?} hashes to the same number as {existing:?}", name.value()),
+            )),
+            None => Ok(number),
+        }
+    }
+}
+
+/// Checks that a set of method names can be used together in one actor
+pub fn check_methods(names: &MethodNames) -> Result<()> {
+    let mut numbers = MethodNumbers::default();
+    for name in &names.0 {
+        numbers.insert(name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::Span;
+    use syn::LitStr;
+
+    use super::{check_methods, MethodNames};
+
+    fn names(names: &[&str]) -> MethodNames {
+        MethodNames(names.iter().map(|name| LitStr::new(name, Span::call_site())).collect())
+    }
+
+    fn check(methods: &[&str]) -> Result<(), String> {
+        check_methods(&names(methods)).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn it_accepts_distinct_methods() {
+        assert_eq!(check(&["Constructor", "Transfer", "TransferFrom", "BalanceOf"]), Ok(()));
+        assert_eq!(check(&[]), Ok(()));
+    }
+
+    #[test]
+    fn it_rejects_duplicates_and_collisions() {
+        assert_eq!(
+            check(&["Transfer", "Burn", "Transfer"]),
+            Err("method \"Transfer\" is used more than once".into())
+        );
+        // these names hash to the same FRC-0042 method number
+        assert_eq!(
+            check(&["Method46776", "Method85157"]),
+            Err("method \"Method85157\" hashes to the same number as \"Method46776\"".into())
+        );
+        assert!(check(&["Transfer", "transfer"]).is_err());
+    }
+}
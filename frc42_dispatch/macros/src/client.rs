@@ -0,0 +1,125 @@
+use frc42_hasher::hash::MethodResolver;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Error, FnArg, Ident, ItemTrait, LitStr, Result, ReturnType, TraitItem, Type};
+
+use crate::export::{method_name, take_method_attrs};
+use crate::hash::Blake2bHasher;
+
+/// A method of the trait to be called through the generated client
+struct ClientMethod {
+    ident: Ident,
+    name: LitStr,
+    param: Option<(Ident, Type)>,
+    ret: Option<Type>,
+}
+
+/// Expands a trait describing an actor's interface, adding a client struct that calls its methods
+pub fn expand(client: Ident, mut item: ItemTrait) -> Result<TokenStream> {
+    let mut methods = Vec::new();
+    for trait_item in item.items.iter_mut() {
+        if let TraitItem::Method(method) = trait_item {
+            let attrs = take_method_attrs(&mut method.attrs)?;
+            if attrs.skip {
+                continue;
+            }
+            let name = match attrs.name {
+                Some(name) => name,
+                None => LitStr::new(
+                    &method_name(&method.sig.ident.to_string()),
+                    method.sig.ident.span(),
+                ),
+            };
+            MethodResolver::new(Blake2bHasher {})
+                .method_number(&name.value())
+                .map_err(|e| Error::new(name.span(), e))?;
+
+            let sig = &method.sig;
+            let mut inputs = sig.inputs.iter();
+            match inputs.next() {
+                Some(FnArg::Receiver(receiver))
+                    if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+                _ => return Err(Error::new(sig.span(), "client methods must take `&self`")),
+            }
+            let param = match inputs.next() {
+                Some(FnArg::Typed(arg)) => {
+                    Some((Ident::new("params", arg.span()), (*arg.ty).clone()))
+                }
+                _ => None,
+            };
+            if let Some(arg) = inputs.next() {
+                return Err(Error::new(
+                    arg.span(),
+                    "client methods may take at most one parameter",
+                ));
+            }
+            let ret = match &sig.output {
+                ReturnType::Type(_, ty) if !is_unit(ty) => Some((**ty).clone()),
+                _ => None,
+            };
+            methods.push(ClientMethod { ident: sig.ident.clone(), name, param, ret });
+        }
+    }
+
+    let vis = &item.vis;
+    let calls = methods.iter().map(|method| {
+        let ClientMethod { ident, name, param, ret } = method;
+        let (arg, params) = match param {
+            Some((param, ty)) => (
+                quote! { , #param: &#ty },
+                quote! { ::frc42_dispatch::client::encode_params(#param)? },
+            ),
+            None => (quote! {}, quote! { None }),
+        };
+        let (ret_ty, ret) = match ret {
+            Some(ty) => {
+                (quote! { #ty }, quote! { ::frc42_dispatch::client::decode_return(response) })
+            }
+            None => (
+                quote! { () },
+                quote! { ::frc42_dispatch::client::check_response(response).map(|_| ()) },
+            ),
+        };
+        let doc = format!(" Calls the `{}` method of the actor at `to`", name.value());
+        quote! {
+            #[doc = #doc]
+            pub fn #ident(
+                &self,
+                to: &::frc42_dispatch::client::Address #arg
+            ) -> ::core::result::Result<#ret_ty, ::frc42_dispatch::client::ClientError> {
+                let params = #params;
+                let response = self.messenger.call_method(
+                    to,
+                    #name,
+                    params,
+                    ::frc42_dispatch::client::TokenAmount::default(),
+                )?;
+                #ret
+            }
+        }
+    });
+
+    Ok(quote! {
+        #item
+
+        /// Typed client for calling the methods of an actor from another actor
+        #[derive(Default)]
+        #vis struct #client<T: ::frc42_dispatch::hash::Hasher> {
+            messenger: ::frc42_dispatch::message::MethodMessenger<T>,
+        }
+
+        impl<T: ::frc42_dispatch::hash::Hasher> #client<T> {
+            /// Creates a client that resolves method numbers with the given hasher
+            pub fn new(hasher: T) -> Self {
+                Self { messenger: ::frc42_dispatch::message::MethodMessenger::new(hasher) }
+            }
+
+            #(#calls)*
+        }
+    })
+}
+
+fn is_unit(ty: &Type) -> bool {
+    matches!(ty, Type::Tuple(tuple) if tuple.elems.is_empty())
+}
@@ -0,0 +1,473 @@
+use frc42_hasher::hash::MethodResolver;
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Error, FnArg, GenericArgument, Ident, ImplItem, ImplItemMethod, ItemImpl, Lit,
+    LitStr, Meta, NestedMeta, PathArguments, Result, ReturnType, Token, Type, Visibility,
+};
+
+use crate::check::MethodNumbers;
+use crate::hash::Blake2bHasher;
+
+/// The name of the method added by `#[frc42_export(interface)]`
+const INTERFACE_METHOD: &str = "Interface";
+
+/// Arguments to the `frc42_export` attribute itself
+#[derive(Default)]
+pub struct ExportArgs {
+    /// Error type of the generated invoke function, inferred from the exported methods if absent
+    error: Option<Type>,
+    /// Whether to export an `Interface` method returning the interface descriptor
+    interface: bool,
+}
+
+enum ExportArg {
+    Error(Type),
+    Interface,
+}
+
+impl Parse for ExportArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let key: Ident = input.parse()?;
+        if key == "error" {
+            input.parse::<Token![=]>()?;
+            Ok(Self::Error(input.parse()?))
+        } else if key == "interface" {
+            Ok(Self::Interface)
+        } else {
+            Err(Error::new(key.span(), "expected `error = <type>` or `interface`"))
+        }
+    }
+}
+
+impl Parse for ExportArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = Self::default();
+        for arg in Punctuated::<ExportArg, Token![,]>::parse_terminated(input)? {
+            match arg {
+                ExportArg::Error(error) => args.error = Some(error),
+                ExportArg::Interface => args.interface = true,
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// A method to be dispatched by the generated invoke function
+struct ExportedMethod {
+    ident: Ident,
+    name: LitStr,
+    group: Option<LitStr>,
+    mutable: bool,
+    param: Option<Type>,
+    /// The return value, or None if the method returns `()`
+    ret: Option<Type>,
+    error: Option<Type>,
+}
+
+/// How a method is annotated with `#[frc42(...)]`
+#[derive(Default)]
+pub(crate) struct MethodAttrs {
+    /// The method is not exported
+    pub skip: bool,
+    /// Overrides the method name derived from the function name
+    pub name: Option<LitStr>,
+    /// The group the method belongs to, for middleware applying to a set of methods
+    pub group: Option<LitStr>,
+}
+
+/// Expands an impl block, adding an `frc42_invoke` function that dispatches to its public methods
+pub fn expand(args: ExportArgs, mut item: ItemImpl) -> Result<TokenStream> {
+    let mut methods = Vec::new();
+    let mut numbers = MethodNumbers::default();
+    let interface_number = MethodResolver::new(Blake2bHasher {})
+        .method_number(INTERFACE_METHOD)
+        .expect("interface method name is valid");
+    if args.interface {
+        numbers.insert(&LitStr::new(INTERFACE_METHOD, item.self_ty.span()))?;
+    }
+    for impl_item in item.items.iter_mut() {
+        if let ImplItem::Method(method) = impl_item {
+            let attrs = take_method_attrs(&mut method.attrs)?;
+            if attrs.skip || !matches!(method.vis, Visibility::Public(_)) {
+                continue;
+            }
+            let name = match attrs.name {
+                Some(name) => name,
+                None => LitStr::new(
+                    &method_name(&method.sig.ident.to_string()),
+                    method.sig.ident.span(),
+                ),
+            };
+            let number = numbers.insert(&name)?;
+            methods.push((number, exported_method(method, name, attrs.group)?));
+        }
+    }
+
+    let error = match args.error.or_else(|| methods.iter().find_map(|(_, m)| m.error.clone())) {
+        Some(error) => error,
+        None => {
+            return Err(Error::new(
+                item.self_ty.span(),
+                "cannot infer the error type, use #[frc42_export(error = <type>)]",
+            ))
+        }
+    };
+
+    let mut descriptors: Vec<_> = methods
+        .iter()
+        .map(|(number, method)| {
+            let name = &method.name;
+            let params = type_hint(method.param.as_ref());
+            let returns = type_hint(method.ret.as_ref());
+            let mutating = method.mutable;
+            let group = option_string(method.group.as_ref().map(LitStr::value));
+            quote! {
+                ::frc42_dispatch::interface::MethodDescriptor {
+                    name: ::std::string::String::from(#name),
+                    method_num: #number,
+                    params: #params,
+                    returns: #returns,
+                    mutating: #mutating,
+                    group: #group,
+                }
+            }
+        })
+        .collect();
+    let interface_arm = if args.interface {
+        let returns = option_string(Some("Interface".to_string()));
+        descriptors.push(quote! {
+            ::frc42_dispatch::interface::MethodDescriptor {
+                name: ::std::string::String::from(#INTERFACE_METHOD),
+                method_num: #interface_number,
+                params: ::core::option::Option::None,
+                returns: #returns,
+                mutating: false,
+                group: ::core::option::Option::None,
+            }
+        });
+        quote! {
+            #interface_number => {
+                let call = ::frc42_dispatch::export::MethodCall {
+                    method_num,
+                    name: #INTERFACE_METHOD,
+                    group: ::core::option::Option::None,
+                    mutating: false,
+                };
+                middleware.before(self, &call)?;
+                let ret = Self::frc42_interface();
+                middleware.after(self, &call)?;
+                Ok(Some(::frc42_dispatch::export::serialize_return::<_, #error>(&ret)?))
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let arms = methods.iter().map(|(number, method)| {
+        let ident = &method.ident;
+        let name = &method.name;
+        let group = match &method.group {
+            Some(group) => quote! { ::core::option::Option::Some(#group) },
+            None => quote! { ::core::option::Option::None },
+        };
+        let mutating = method.mutable;
+        let bind = if method.ret.is_none() { quote! {} } else { quote! { let ret = } };
+        let call = match &method.param {
+            Some(param) => quote! {
+                let params = ::frc42_dispatch::export::deserialize_params::<#param, #error>(params)?;
+                #bind self.#ident(params)
+            },
+            None => quote! { #bind self.#ident() },
+        };
+        let flush = if method.mutable {
+            quote! { flush_state(self).map_err(::frc42_dispatch::export::DispatchError::Method)?; }
+        } else {
+            quote! {}
+        };
+        let ret = if method.ret.is_none() {
+            quote! { Ok(Some(None)) }
+        } else {
+            quote! { Ok(Some(::frc42_dispatch::export::serialize_return::<_, #error>(&ret)?)) }
+        };
+        quote! {
+            #number => {
+                let call = ::frc42_dispatch::export::MethodCall {
+                    method_num,
+                    name: #name,
+                    group: #group,
+                    mutating: #mutating,
+                };
+                middleware.before(self, &call)?;
+                #call.map_err(::frc42_dispatch::export::DispatchError::Method)?;
+                #flush
+                middleware.after(self, &call)?;
+                #ret
+            }
+        }
+    });
+
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+    Ok(quote! {
+        #item
+
+        impl #impl_generics #self_ty #where_clause {
+            /// Invokes the exported method matching an FRC-0042 method number
+            ///
+            /// `flush_state` is called after each method taking `&mut self` succeeds and must save
+            /// the state and update the root cid.
+            ///
+            /// Possible returns:
+            /// - Ok(None) - method not found
+            /// - Ok(Some(block)) - the serialized return value (or None if the method returns `()`)
+            /// - Err(error) - any error encountered during dispatch or returned by the method
+            pub fn frc42_invoke<F>(
+                &mut self,
+                method_num: u64,
+                params: ::core::option::Option<::frc42_dispatch::export::IpldBlock>,
+                flush_state: F,
+            ) -> ::core::result::Result<
+                ::core::option::Option<::core::option::Option<::frc42_dispatch::export::IpldBlock>>,
+                ::frc42_dispatch::export::DispatchError<#error>,
+            >
+            where
+                F: FnOnce(&mut Self) -> ::core::result::Result<(), #error>,
+            {
+                self.frc42_invoke_with(method_num, params, &(), flush_state)
+            }
+
+            /// Invokes the exported method matching an FRC-0042 method number, running
+            /// `middleware` before and after the method
+            ///
+            /// The `after` hook runs once the method has succeeded and state has been flushed.
+            #[allow(unused_variables)]
+            pub fn frc42_invoke_with<M, F>(
+                &mut self,
+                method_num: u64,
+                params: ::core::option::Option<::frc42_dispatch::export::IpldBlock>,
+                middleware: &M,
+                flush_state: F,
+            ) -> ::core::result::Result<
+                ::core::option::Option<::core::option::Option<::frc42_dispatch::export::IpldBlock>>,
+                ::frc42_dispatch::export::DispatchError<#error>,
+            >
+            where
+                M: ::frc42_dispatch::export::Middleware<Self, #error> + ?Sized,
+                F: FnOnce(&mut Self) -> ::core::result::Result<(), #error>,
+            {
+                match method_num {
+                    #(#arms)*
+                    #interface_arm
+                    _ => Ok(None),
+                }
+            }
+
+            /// Describes the methods dispatched by `frc42_invoke`, for tools discovering the
+            /// interface of the actor
+            pub fn frc42_interface() -> ::frc42_dispatch::interface::Interface {
+                ::frc42_dispatch::interface::Interface {
+                    methods: ::std::vec![#(#descriptors),*],
+                }
+            }
+        }
+    })
+}
+
+/// Removes the `#[frc42(...)]` attributes from a method, returning their contents
+pub(crate) fn take_method_attrs(attrs: &mut Vec<Attribute>) -> Result<MethodAttrs> {
+    let mut method_attrs = MethodAttrs::default();
+    let mut i = 0;
+    while i < attrs.len() {
+        if !attrs[i].path.is_ident("frc42") {
+            i += 1;
+            continue;
+        }
+        let list = match attrs.remove(i).parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new(meta.span(), "expected #[frc42(...)]")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    method_attrs.skip = true
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => {
+                    method_attrs.name = Some(lit_str(nv.lit)?)
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("group") => {
+                    method_attrs.group = Some(lit_str(nv.lit)?)
+                }
+                nested => {
+                    return Err(Error::new(
+                        nested.span(),
+                        "expected `skip`, `name = \"...\"` or `group = \"...\"`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(method_attrs)
+}
+
+fn lit_str(lit: Lit) -> Result<LitStr> {
+    match lit {
+        Lit::Str(lit) => Ok(lit),
+        lit => Err(Error::new(lit.span(), "expected a string")),
+    }
+}
+
+/// Checks the signature of a method is one that can be dispatched
+fn exported_method(
+    method: &ImplItemMethod,
+    name: LitStr,
+    group: Option<LitStr>,
+) -> Result<ExportedMethod> {
+    let sig = &method.sig;
+    let mut inputs = sig.inputs.iter();
+    let mutable = match inputs.next() {
+        Some(FnArg::Receiver(receiver)) if receiver.reference.is_some() => {
+            receiver.mutability.is_some()
+        }
+        _ => {
+            return Err(Error::new(sig.span(), "exported methods must take `&self` or `&mut self`"))
+        }
+    };
+    let param = match inputs.next() {
+        Some(FnArg::Typed(arg)) => Some((*arg.ty).clone()),
+        _ => None,
+    };
+    if let Some(arg) = inputs.next() {
+        return Err(Error::new(arg.span(), "exported methods may take at most one parameter"));
+    }
+
+    let (ok, error) = result_types(&sig.output)
+        .ok_or_else(|| Error::new(sig.output.span(), "exported methods must return a `Result`"))?;
+    let ret = match ok {
+        Type::Tuple(tuple) if tuple.elems.is_empty() => None,
+        ok => Some(ok),
+    };
+    Ok(ExportedMethod { ident: sig.ident.clone(), name, group, mutable, param, ret, error })
+}
+
+/// Splits a `Result<T, E>` (or `Result<T>` alias) return type into `T` and `E` if present
+fn result_types(output: &ReturnType) -> Option<(Type, Option<Type>)> {
+    let ty = match output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => return None,
+    };
+    let segment = match &**ty {
+        Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Result" {
+        return None;
+    }
+    let args = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => &args.args,
+        _ => return None,
+    };
+    let mut types = args.iter().filter_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(ty.clone()),
+        _ => None,
+    });
+    let ok = types.next()?;
+    Some((ok, types.next()))
+}
+
+/// Describes a parameter or return type as an `Option<String>` expression for the interface
+fn type_hint(ty: Option<&Type>) -> TokenStream {
+    option_string(ty.map(|ty| type_string(quote!(#ty))))
+}
+
+fn option_string(value: Option<String>) -> TokenStream {
+    match value {
+        Some(value) => {
+            quote! { ::core::option::Option::Some(::std::string::String::from(#value)) }
+        }
+        None => quote! { ::core::option::Option::None },
+    }
+}
+
+/// Formats the tokens of a type as it would be written in source, e.g. `Vec<(Address, u64)>`
+fn type_string(tokens: TokenStream) -> String {
+    let mut out = String::new();
+    // whether the last token was an identifier or literal, which must be separated from a
+    // following one by a space
+    let mut word = false;
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+                out.push_str(open);
+                out.push_str(type_string(group.stream()).trim_end());
+                out.push_str(close);
+                word = false;
+            }
+            TokenTree::Punct(punct) => {
+                out.push(punct.as_char());
+                if matches!(punct.as_char(), ',' | ';') {
+                    out.push(' ');
+                }
+                word = false;
+            }
+            TokenTree::Ident(_) | TokenTree::Literal(_) => {
+                if word {
+                    out.push(' ');
+                }
+                out.push_str(&token.to_string());
+                word = true;
+            }
+        }
+    }
+    out
+}
+
+/// Converts a snake_case function name to the UpperCamelCase FRC-0042 method name
+pub(crate) fn method_name(ident: &str) -> String {
+    ident
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::{method_name, type_string};
+
+    #[test]
+    fn it_formats_type_hints() {
+        assert_eq!(type_string(quote!(u64)), "u64");
+        assert_eq!(type_string(quote!(Vec<(Address, u64)>)), "Vec<(Address, u64)>");
+        assert_eq!(type_string(quote!(&'static str)), "&'static str");
+        assert_eq!(type_string(quote!([u8; 32])), "[u8; 32]");
+        assert_eq!(
+            type_string(quote!(fvm_shared::econ::TokenAmount)),
+            "fvm_shared::econ::TokenAmount"
+        );
+    }
+
+    #[test]
+    fn it_converts_function_names() {
+        assert_eq!(method_name("transfer"), "Transfer");
+        assert_eq!(method_name("transfer_from"), "TransferFrom");
+        assert_eq!(method_name("balance_of_2"), "BalanceOf2");
+    }
+}
@@ -2,9 +2,14 @@ use frc42_hasher::hash::MethodResolver;
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::parse::{Parse, ParseStream};
-use syn::{parse_macro_input, LitStr, Result};
+use syn::{parse_macro_input, Ident, ItemImpl, ItemTrait, LitStr, Result};
 
+mod check;
+mod client;
+mod export;
 mod hash;
+use crate::check::MethodNames;
+use crate::export::ExportArgs;
 use crate::hash::Blake2bHasher;
 
 struct MethodName(LitStr);
@@ -36,6 +41,49 @@ pub fn method_hash(input: TokenStream) -> TokenStream {
     quote!(#hash).into()
 }
 
+/// Checks that a list of method names can be used by the same actor, failing compilation if any
+/// name is invalid, repeated or hashes to the same method number as another
+///
+/// `match_method!` runs this check on the names it matches.
+#[proc_macro]
+pub fn check_methods(input: TokenStream) -> TokenStream {
+    let names: MethodNames = parse_macro_input!(input);
+    check::check_methods(&names).map(|_| quote!()).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// Generates an `frc42_invoke` function dispatching FRC-0042 method numbers to the public methods
+/// of an impl block
+///
+/// Each public method taking `&self` or `&mut self` is exported under the UpperCamelCase form of its
+/// name, unless renamed with `#[frc42(name = "...")]` or excluded with `#[frc42(skip)]`. Exported
+/// methods take at most one parameter, which is deserialized from the message parameters, and
+/// return a `Result` whose value is serialized as the return block. The error type of the generated
+/// function is taken from the first method returning `Result<T, E>` or may be given explicitly with
+/// `#[frc42_export(error = E)]`.
+///
+/// An `frc42_interface` function describing the exported methods is also generated. With
+/// `#[frc42_export(interface)]` it is additionally exported as the `Interface` method.
+#[proc_macro_attribute]
+pub fn frc42_export(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args: ExportArgs = parse_macro_input!(attr);
+    let item: ItemImpl = parse_macro_input!(item);
+    export::expand(args, item).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// Generates a typed client struct, named by the attribute argument, from a trait describing the
+/// methods of an actor
+///
+/// Each trait method takes `&self` and at most one parameter. The client gains a method of the same
+/// name taking the address of the actor to call and a reference to the parameter, which encodes the
+/// parameter, calls the FRC-0042 method (named as for `#[frc42_export]`) and decodes its return
+/// value.
+#[proc_macro_attribute]
+pub fn client(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let name: Ident = parse_macro_input!(attr);
+    let item: ItemTrait = parse_macro_input!(item);
+    client::expand(name, item).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
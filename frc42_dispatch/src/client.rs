@@ -0,0 +1,118 @@
+//! Support for the typed clients generated by `#[client]`
+//!
+//! The generated clients call into the helpers here to encode parameters and decode the response
+//! of the called actor.
+use fvm_ipld_encoding::de::DeserializeOwned;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::ser::Serialize;
+pub use fvm_shared::address::Address;
+pub use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::Response;
+use thiserror::Error;
+
+use crate::message::MethodMessengerError;
+
+/// Errors from calling a method through a typed client
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("error calling method: {0}")]
+    Messenger(#[from] MethodMessengerError),
+    #[error("failed to serialize params: {0}")]
+    Params(fvm_ipld_encoding::Error),
+    #[error("method aborted with exit_code={0:?}")]
+    Aborted(ExitCode),
+    #[error("method returned no data")]
+    MissingReturn,
+    #[error("failed to deserialize return data: {0}")]
+    Return(fvm_ipld_encoding::Error),
+}
+
+/// Encodes the parameters of a method call
+pub fn encode_params<P>(params: &P) -> Result<Option<IpldBlock>, ClientError>
+where
+    P: Serialize + ?Sized,
+{
+    IpldBlock::serialize_cbor(params).map_err(ClientError::Params)
+}
+
+/// Checks that a method call succeeded, returning its return data
+pub fn check_response(response: Response) -> Result<Option<IpldBlock>, ClientError> {
+    if !response.exit_code.is_success() {
+        return Err(ClientError::Aborted(response.exit_code));
+    }
+    Ok(response.return_data)
+}
+
+/// Checks that a method call succeeded and decodes its return value
+pub fn decode_return<R>(response: Response) -> Result<R, ClientError>
+where
+    R: DeserializeOwned,
+{
+    let ret = check_response(response)?.ok_or(ClientError::MissingReturn)?;
+    ret.deserialize().map_err(ClientError::Return)
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_shared::{error::ExitCode, Response};
+
+    use self::counter::CounterClient;
+    use super::{decode_return, encode_params, ClientError};
+    use crate::hash::Hasher;
+
+    #[derive(Default)]
+    struct FakeHasher {}
+
+    impl Hasher for FakeHasher {
+        fn hash(&self, bytes: &[u8]) -> Vec<u8> {
+            bytes.to_vec()
+        }
+    }
+
+    // the client methods can only be called inside the FVM
+    #[allow(dead_code)]
+    mod counter {
+        use crate::client;
+
+        #[client(CounterClient)]
+        pub trait Counter {
+            fn count(&self) -> u8;
+            fn increment_by(&self, amount: u8) -> u8;
+            #[frc42(name = "Clear")]
+            fn reset(&self);
+        }
+    }
+
+    #[test]
+    fn it_generates_clients() {
+        let _client = CounterClient::new(FakeHasher {});
+        let _client = CounterClient::<FakeHasher>::default();
+    }
+
+    #[test]
+    fn it_decodes_responses() {
+        let params = encode_params(&5u8).unwrap();
+        let ret: u8 =
+            decode_return(Response { exit_code: ExitCode::OK, return_data: params.clone() })
+                .unwrap();
+        assert_eq!(ret, 5);
+
+        let err = decode_return::<u8>(Response {
+            exit_code: ExitCode::USR_FORBIDDEN,
+            return_data: params,
+        })
+        .unwrap_err();
+        assert!(matches!(err, ClientError::Aborted(ExitCode::USR_FORBIDDEN)));
+
+        let err = decode_return::<u8>(Response { exit_code: ExitCode::OK, return_data: None })
+            .unwrap_err();
+        assert!(matches!(err, ClientError::MissingReturn));
+
+        let data = IpldBlock::serialize_cbor(&"five").unwrap();
+        let err = decode_return::<u8>(Response { exit_code: ExitCode::OK, return_data: data })
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Return(_)));
+    }
+}
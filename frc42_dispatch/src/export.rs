@@ -0,0 +1,411 @@
+//! Support for the code generated by `#[frc42_export]`
+//!
+//! The attribute generates an `frc42_invoke` function which calls into the helpers here to decode
+//! parameters and encode return values. With the `use_sdk` feature, `params_from_block` and
+//! `return_block` convert between those values and the block ids passed to an actor's `invoke`.
+//!
+//! `frc42_invoke_with` additionally runs a [`Middleware`] around each method, so that policies
+//! such as caller allowlists or pause checks can be applied to a whole group of methods (tagged
+//! with `#[frc42(group = "...")]`) without repeating them in every method body.
+use fvm_ipld_encoding::de::DeserializeOwned;
+pub use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::ser::Serialize;
+use fvm_shared::error::ExitCode;
+use fvm_shared::ActorID;
+use thiserror::Error;
+
+/// Errors from invoking a method exported with `#[frc42_export]`
+#[derive(Error, Debug)]
+pub enum DispatchError<E> {
+    #[error("missing parameters")]
+    MissingParams,
+    #[error("failed to deserialize params: {0}")]
+    Params(fvm_ipld_encoding::Error),
+    #[error("failed to serialize return data: {0}")]
+    Return(fvm_ipld_encoding::Error),
+    #[error("call to {method} rejected: {reason}")]
+    Rejected { method: &'static str, reason: String },
+    #[error("{0}")]
+    Method(E),
+}
+
+impl<E> From<&DispatchError<E>> for ExitCode
+where
+    for<'a> &'a E: Into<ExitCode>,
+{
+    fn from(error: &DispatchError<E>) -> Self {
+        match error {
+            DispatchError::MissingParams => ExitCode::USR_ILLEGAL_ARGUMENT,
+            DispatchError::Params(_) | DispatchError::Return(_) => ExitCode::USR_SERIALIZATION,
+            DispatchError::Rejected { .. } => ExitCode::USR_FORBIDDEN,
+            DispatchError::Method(e) => e.into(),
+        }
+    }
+}
+
+/// Describes the exported method being invoked, for use by [`Middleware`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MethodCall {
+    pub method_num: u64,
+    pub name: &'static str,
+    /// The group set with `#[frc42(group = "...")]`, if any
+    pub group: Option<&'static str>,
+    /// Whether the method takes `&mut self`
+    pub mutating: bool,
+}
+
+impl MethodCall {
+    /// Creates an error rejecting this call
+    pub fn reject<E>(&self, reason: impl Into<String>) -> DispatchError<E> {
+        DispatchError::Rejected { method: self.name, reason: reason.into() }
+    }
+}
+
+/// Hooks run around each method dispatched by `frc42_invoke_with`
+///
+/// `before` runs after the method has been matched but before its parameters are decoded, and
+/// may reject the call. `after` runs once the method has succeeded and state has been flushed;
+/// an error from it fails the whole call, so the actor should abort to roll back the state change.
+pub trait Middleware<A: ?Sized, E> {
+    fn before(&self, _actor: &A, _call: &MethodCall) -> Result<(), DispatchError<E>> {
+        Ok(())
+    }
+
+    fn after(&self, _actor: &A, _call: &MethodCall) -> Result<(), DispatchError<E>> {
+        Ok(())
+    }
+}
+
+/// No middleware
+impl<A: ?Sized, E> Middleware<A, E> for () {}
+
+impl<A, E, M> Middleware<A, E> for &M
+where
+    A: ?Sized,
+    M: Middleware<A, E> + ?Sized,
+{
+    fn before(&self, actor: &A, call: &MethodCall) -> Result<(), DispatchError<E>> {
+        (**self).before(actor, call)
+    }
+
+    fn after(&self, actor: &A, call: &MethodCall) -> Result<(), DispatchError<E>> {
+        (**self).after(actor, call)
+    }
+}
+
+/// Runs both middleware, with `after` hooks running in the reverse order of `before` hooks
+impl<A, E, M1, M2> Middleware<A, E> for (M1, M2)
+where
+    A: ?Sized,
+    M1: Middleware<A, E>,
+    M2: Middleware<A, E>,
+{
+    fn before(&self, actor: &A, call: &MethodCall) -> Result<(), DispatchError<E>> {
+        self.0.before(actor, call)?;
+        self.1.before(actor, call)
+    }
+
+    fn after(&self, actor: &A, call: &MethodCall) -> Result<(), DispatchError<E>> {
+        self.1.after(actor, call)?;
+        self.0.after(actor, call)
+    }
+}
+
+/// Applies middleware only to the methods of a group
+pub struct Group<'a, M> {
+    pub group: &'a str,
+    pub middleware: M,
+}
+
+impl<'a, M> Group<'a, M> {
+    pub fn new(group: &'a str, middleware: M) -> Self {
+        Self { group, middleware }
+    }
+
+    fn applies_to(&self, call: &MethodCall) -> bool {
+        call.group == Some(self.group)
+    }
+}
+
+impl<'a, A, E, M> Middleware<A, E> for Group<'a, M>
+where
+    A: ?Sized,
+    M: Middleware<A, E>,
+{
+    fn before(&self, actor: &A, call: &MethodCall) -> Result<(), DispatchError<E>> {
+        if self.applies_to(call) {
+            self.middleware.before(actor, call)?;
+        }
+        Ok(())
+    }
+
+    fn after(&self, actor: &A, call: &MethodCall) -> Result<(), DispatchError<E>> {
+        if self.applies_to(call) {
+            self.middleware.after(actor, call)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rejects calls to methods that take `&mut self`
+pub struct ReadOnly;
+
+impl<A: ?Sized, E> Middleware<A, E> for ReadOnly {
+    fn before(&self, _actor: &A, call: &MethodCall) -> Result<(), DispatchError<E>> {
+        if call.mutating {
+            return Err(call.reject("actor is read-only"));
+        }
+        Ok(())
+    }
+}
+
+/// Rejects calls unless the caller is in the allowlist
+pub struct CallerAllowlist<'a> {
+    pub caller: ActorID,
+    pub allowed: &'a [ActorID],
+}
+
+impl<'a, A: ?Sized, E> Middleware<A, E> for CallerAllowlist<'a> {
+    fn before(&self, _actor: &A, call: &MethodCall) -> Result<(), DispatchError<E>> {
+        if !self.allowed.contains(&self.caller) {
+            return Err(call.reject(format!("caller {} is not allowed", self.caller)));
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes the parameters of an exported method
+pub fn deserialize_params<P, E>(params: Option<IpldBlock>) -> Result<P, DispatchError<E>>
+where
+    P: DeserializeOwned,
+{
+    let params = params.ok_or(DispatchError::MissingParams)?;
+    params.deserialize().map_err(DispatchError::Params)
+}
+
+/// Serializes the return value of an exported method
+pub fn serialize_return<R, E>(ret: &R) -> Result<Option<IpldBlock>, DispatchError<E>>
+where
+    R: Serialize + ?Sized,
+{
+    IpldBlock::serialize_cbor(ret).map_err(DispatchError::Return)
+}
+
+/// Reads the parameters passed to an actor's `invoke`
+#[cfg(feature = "use_sdk")]
+pub fn params_from_block<E>(params: u32) -> Result<Option<IpldBlock>, DispatchError<E>> {
+    fvm_sdk::message::params_raw(params).map_err(|_| DispatchError::MissingParams)
+}
+
+/// Saves the return value of an exported method, returning the block id to return from `invoke`
+#[cfg(feature = "use_sdk")]
+pub fn return_block(ret: Option<IpldBlock>) -> u32 {
+    match ret {
+        Some(block) => fvm_sdk::ipld::put_block(block.codec, &block.data).unwrap_or_else(|e| {
+            fvm_sdk::vm::abort(
+                ExitCode::USR_SERIALIZATION.value(),
+                Some(format!("failed to store return data {e}").as_str()),
+            )
+        }),
+        None => fvm_sdk::NO_DATA_BLOCK_ID,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::ActorID;
+    use thiserror::Error;
+
+    use super::{CallerAllowlist, DispatchError, Group, MethodCall, Middleware, ReadOnly};
+    use crate::{frc42_export, method_hash};
+
+    #[derive(Error, Debug, PartialEq, Eq)]
+    enum CounterError {
+        #[error("counter overflowed")]
+        Overflow,
+    }
+
+    impl From<&CounterError> for ExitCode {
+        fn from(_: &CounterError) -> Self {
+            ExitCode::USR_ILLEGAL_STATE
+        }
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        count: u8,
+        flushes: u32,
+        paused: bool,
+    }
+
+    #[frc42_export]
+    impl Counter {
+        pub fn count(&self) -> Result<u8, CounterError> {
+            Ok(self.count)
+        }
+
+        pub fn increment_by(&mut self, amount: u8) -> Result<u8, CounterError> {
+            self.count = self.count.checked_add(amount).ok_or(CounterError::Overflow)?;
+            Ok(self.count)
+        }
+
+        #[frc42(name = "Clear", group = "admin")]
+        pub fn reset(&mut self) -> Result<(), CounterError> {
+            self.count = 0;
+            Ok(())
+        }
+
+        #[frc42(skip)]
+        pub fn flush(&mut self) -> Result<(), CounterError> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    fn invoke(
+        counter: &mut Counter,
+        method_num: u64,
+        params: Option<IpldBlock>,
+    ) -> Result<Option<Option<IpldBlock>>, DispatchError<CounterError>> {
+        counter.frc42_invoke(method_num, params, |counter| counter.flush())
+    }
+
+    #[test]
+    fn it_dispatches_exported_methods() {
+        let mut counter = Counter::default();
+
+        let params = IpldBlock::serialize_cbor(&5u8).unwrap();
+        let ret = invoke(&mut counter, method_hash!("IncrementBy"), params).unwrap().unwrap();
+        assert_eq!(ret.unwrap().deserialize::<u8>().unwrap(), 5);
+        assert_eq!(counter.flushes, 1);
+
+        // read-only methods don't flush state
+        let ret = invoke(&mut counter, method_hash!("Count"), None).unwrap().unwrap();
+        assert_eq!(ret.unwrap().deserialize::<u8>().unwrap(), 5);
+        assert_eq!(counter.flushes, 1);
+
+        // methods returning () have no return block
+        let ret = invoke(&mut counter, method_hash!("Clear"), None).unwrap().unwrap();
+        assert!(ret.is_none());
+        assert_eq!(counter.count, 0);
+        assert_eq!(counter.flushes, 2);
+
+        // skipped and renamed methods are not exported under their own names
+        assert!(invoke(&mut counter, method_hash!("Flush"), None).unwrap().is_none());
+        assert!(invoke(&mut counter, method_hash!("Reset"), None).unwrap().is_none());
+    }
+
+    #[test]
+    fn it_reports_dispatch_errors() {
+        let mut counter = Counter { count: 255, ..Default::default() };
+
+        let err = invoke(&mut counter, method_hash!("IncrementBy"), None).unwrap_err();
+        assert!(matches!(err, DispatchError::MissingParams));
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        let params = IpldBlock::serialize_cbor(&"one").unwrap();
+        let err = invoke(&mut counter, method_hash!("IncrementBy"), params).unwrap_err();
+        assert!(matches!(err, DispatchError::Params(_)));
+
+        // failed methods don't flush state
+        let params = IpldBlock::serialize_cbor(&1u8).unwrap();
+        let err = invoke(&mut counter, method_hash!("IncrementBy"), params).unwrap_err();
+        assert!(matches!(err, DispatchError::Method(CounterError::Overflow)));
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_ILLEGAL_STATE);
+        assert_eq!(counter.flushes, 0);
+    }
+
+    /// Rejects mutating calls while the counter is paused
+    struct PauseCheck;
+
+    impl Middleware<Counter, CounterError> for PauseCheck {
+        fn before(
+            &self,
+            counter: &Counter,
+            call: &MethodCall,
+        ) -> Result<(), DispatchError<CounterError>> {
+            if counter.paused && call.mutating {
+                return Err(call.reject("counter is paused"));
+            }
+            Ok(())
+        }
+    }
+
+    /// Records the methods that completed and the count after each
+    #[derive(Default)]
+    struct CallLog {
+        calls: RefCell<Vec<(&'static str, u8)>>,
+    }
+
+    impl Middleware<Counter, CounterError> for CallLog {
+        fn after(
+            &self,
+            counter: &Counter,
+            call: &MethodCall,
+        ) -> Result<(), DispatchError<CounterError>> {
+            self.calls.borrow_mut().push((call.name, counter.count));
+            Ok(())
+        }
+    }
+
+    fn invoke_with<M: Middleware<Counter, CounterError>>(
+        counter: &mut Counter,
+        method_num: u64,
+        params: Option<IpldBlock>,
+        middleware: &M,
+    ) -> Result<Option<Option<IpldBlock>>, DispatchError<CounterError>> {
+        counter.frc42_invoke_with(method_num, params, middleware, |counter| counter.flush())
+    }
+
+    #[test]
+    fn it_runs_middleware() {
+        let mut counter = Counter::default();
+        let log = CallLog::default();
+        let middleware = (PauseCheck, &log);
+
+        let params = IpldBlock::serialize_cbor(&5u8).unwrap();
+        invoke_with(&mut counter, method_hash!("IncrementBy"), params.clone(), &middleware)
+            .unwrap();
+        invoke_with(&mut counter, method_hash!("Count"), None, &middleware).unwrap();
+
+        // rejected calls don't reach the method or the after hooks
+        counter.paused = true;
+        let err = invoke_with(&mut counter, method_hash!("IncrementBy"), params, &middleware)
+            .unwrap_err();
+        assert!(matches!(err, DispatchError::Rejected { method: "IncrementBy", .. }));
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+        assert_eq!(counter.count, 5);
+        assert_eq!(counter.flushes, 1);
+
+        // read-only methods are still allowed while paused
+        invoke_with(&mut counter, method_hash!("Count"), None, &middleware).unwrap();
+        assert_eq!(*log.calls.borrow(), vec![("IncrementBy", 5), ("Count", 5), ("Count", 5)]);
+
+        let err = invoke_with(&mut counter, method_hash!("Clear"), None, &ReadOnly).unwrap_err();
+        assert!(matches!(err, DispatchError::Rejected { method: "Clear", .. }));
+    }
+
+    #[test]
+    fn it_applies_middleware_to_groups() {
+        let mut counter = Counter::default();
+        let admins: &[ActorID] = &[1, 2];
+        let admin_only =
+            move |caller| Group::new("admin", CallerAllowlist { caller, allowed: admins });
+
+        // methods outside the group are open to any caller
+        let params = IpldBlock::serialize_cbor(&5u8).unwrap();
+        invoke_with(&mut counter, method_hash!("IncrementBy"), params, &admin_only(3)).unwrap();
+
+        let err =
+            invoke_with(&mut counter, method_hash!("Clear"), None, &admin_only(3)).unwrap_err();
+        assert!(matches!(err, DispatchError::Rejected { method: "Clear", .. }));
+        assert_eq!(counter.count, 5);
+
+        invoke_with(&mut counter, method_hash!("Clear"), None, &admin_only(2)).unwrap();
+        assert_eq!(counter.count, 0);
+    }
+}
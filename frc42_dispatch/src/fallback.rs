@@ -0,0 +1,99 @@
+//! Chaining of invoke handlers which pass through methods they don't handle
+//!
+//! Generic handlers such as `frc46_invoke` and `frc53_invoke` return `Ok(None)` for method numbers
+//! they don't recognise, so an actor can combine the standard methods with its own extensions and
+//! app-specific methods by trying each handler in turn. [`InvokeFallback`] provides the
+//! combinators for this, so the chain reads as a list of handlers ending with the standard abort
+//! for unhandled methods:
+//!
+//! ```ignore
+//! let ret = roles_invoke(method_num, params, &mut token, save_state)
+//!     .or_invoke(|| frc46_invoke(method_num, params, &mut token, save_state))
+//!     .or_abort_unhandled(method_num)?;
+//! ```
+//!
+//! A handler is only called if none before it handled the method, and the chain stops at the
+//! first error.
+
+/// The result of an invoke handler, which is `Ok(None)` if the handler doesn't handle the method
+pub type InvokeResult<R, E> = Result<Option<R>, E>;
+
+/// Combinators for falling back to further handlers when a method isn't handled
+pub trait InvokeFallback<R, E> {
+    /// Calls `handler` if no handler has handled the method yet
+    fn or_invoke<F>(self, handler: F) -> InvokeResult<R, E>
+    where
+        F: FnOnce() -> InvokeResult<R, E>;
+
+    /// Returns the result of the handler that handled the method, or the error from `unhandled`
+    /// if none did
+    fn or_unhandled<F>(self, unhandled: F) -> Result<R, E>
+    where
+        F: FnOnce() -> E;
+
+    /// Returns the result of the handler that handled the method, aborting with
+    /// `USR_UNHANDLED_MESSAGE` if none did
+    #[cfg(feature = "use_sdk")]
+    fn or_abort_unhandled(self, method_num: u64) -> Result<R, E>;
+}
+
+impl<R, E> InvokeFallback<R, E> for InvokeResult<R, E> {
+    fn or_invoke<F>(self, handler: F) -> InvokeResult<R, E>
+    where
+        F: FnOnce() -> InvokeResult<R, E>,
+    {
+        match self {
+            Ok(None) => handler(),
+            handled => handled,
+        }
+    }
+
+    fn or_unhandled<F>(self, unhandled: F) -> Result<R, E>
+    where
+        F: FnOnce() -> E,
+    {
+        self?.ok_or_else(unhandled)
+    }
+
+    #[cfg(feature = "use_sdk")]
+    fn or_abort_unhandled(self, method_num: u64) -> Result<R, E> {
+        match self? {
+            Some(ret) => Ok(ret),
+            None => crate::match_method::abort_unhandled(method_num),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InvokeFallback, InvokeResult};
+
+    fn handles(method: u64, method_num: u64) -> InvokeResult<u64, String> {
+        Ok((method == method_num).then_some(method))
+    }
+
+    #[test]
+    fn it_tries_handlers_in_turn() {
+        let chain = |method_num| {
+            handles(1, method_num)
+                .or_invoke(|| handles(2, method_num))
+                .or_invoke(|| handles(3, method_num))
+                .or_unhandled(|| format!("unhandled {method_num}"))
+        };
+        assert_eq!(chain(1), Ok(1));
+        assert_eq!(chain(3), Ok(3));
+        assert_eq!(chain(4), Err(String::from("unhandled 4")));
+    }
+
+    #[test]
+    fn it_stops_at_handled_methods_and_errors() {
+        let ret = handles(1, 1).or_invoke(|| panic!("method was already handled"));
+        assert_eq!(ret, Ok(Some(1)));
+
+        let failed: InvokeResult<u64, String> = Err(String::from("failed"));
+        let ret = failed
+            .or_invoke(|| panic!("handler called after an error"))
+            .or_unhandled(|| String::from("unhandled"));
+        assert_eq!(ret, Err(String::from("failed")));
+    }
+}
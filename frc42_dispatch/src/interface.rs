@@ -0,0 +1,121 @@
+//! Machine-readable descriptions of the methods an actor exports
+//!
+//! `#[frc42_export]` generates an `frc42_interface` function returning an [`Interface`], which can
+//! be called from build scripts or tests to produce a descriptor for wallets, explorers and code
+//! generators. With `#[frc42_export(interface)]` the descriptor is also returned by a reserved
+//! `Interface` method so that it can be discovered on-chain.
+use fvm_ipld_encoding::tuple::*;
+
+/// Describes a method exported by an actor
+///
+/// Parameter and return types are hints giving the Rust type as written in the actor's source,
+/// which is serialized as CBOR.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct MethodDescriptor {
+    pub name: String,
+    pub method_num: u64,
+    /// The parameter type, or None if the method takes no parameters
+    pub params: Option<String>,
+    /// The return type, or None if the method returns nothing
+    pub returns: Option<String>,
+    /// Whether the method may change the actor's state
+    pub mutating: bool,
+    /// The group set with `#[frc42(group = "...")]`, if any
+    pub group: Option<String>,
+}
+
+/// The methods exported by an actor
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Interface {
+    pub methods: Vec<MethodDescriptor>,
+}
+
+impl Interface {
+    /// Looks up a method by name
+    pub fn method(&self, name: &str) -> Option<&MethodDescriptor> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    /// Looks up a method by number
+    pub fn method_by_number(&self, method_num: u64) -> Option<&MethodDescriptor> {
+        self.methods.iter().find(|method| method.method_num == method_num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::error::ExitCode;
+    use thiserror::Error;
+
+    use super::{Interface, MethodDescriptor};
+    use crate::{frc42_export, method_hash};
+
+    #[derive(Error, Debug)]
+    #[error("registry error")]
+    struct RegistryError;
+
+    impl From<&RegistryError> for ExitCode {
+        fn from(_: &RegistryError) -> Self {
+            ExitCode::USR_ILLEGAL_STATE
+        }
+    }
+
+    #[derive(Default)]
+    struct Registry {
+        names: Vec<(String, u64)>,
+    }
+
+    #[frc42_export(interface)]
+    impl Registry {
+        pub fn lookup(&self, name: String) -> Result<Option<u64>, RegistryError> {
+            Ok(self.names.iter().find(|(n, _)| *n == name).map(|(_, id)| *id))
+        }
+
+        #[frc42(group = "admin")]
+        pub fn register(&mut self, entry: (String, u64)) -> Result<(), RegistryError> {
+            self.names.push(entry);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_describes_exported_methods() {
+        let interface = Registry::frc42_interface();
+        assert_eq!(
+            interface.method("Lookup"),
+            Some(&MethodDescriptor {
+                name: "Lookup".into(),
+                method_num: method_hash!("Lookup"),
+                params: Some("String".into()),
+                returns: Some("Option<u64>".into()),
+                mutating: false,
+                group: None,
+            })
+        );
+        assert_eq!(
+            interface.method_by_number(method_hash!("Register")),
+            Some(&MethodDescriptor {
+                name: "Register".into(),
+                method_num: method_hash!("Register"),
+                params: Some("(String, u64)".into()),
+                returns: None,
+                mutating: true,
+                group: Some("admin".into()),
+            })
+        );
+        assert_eq!(interface.methods.len(), 3);
+    }
+
+    #[test]
+    fn it_exports_the_interface_method() {
+        let mut registry = Registry::default();
+        let ret = registry
+            .frc42_invoke(method_hash!("Interface"), None, |_| Ok(()))
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let interface: Interface = ret.deserialize().unwrap();
+        assert_eq!(interface, Registry::frc42_interface());
+        assert!(!interface.method("Interface").unwrap().mutating);
+    }
+}
@@ -1,7 +1,15 @@
+// allows the code generated by `#[frc42_export]` and `#[client]` to refer to this crate by name
+// in its own tests
+extern crate self as frc42_dispatch;
+
 pub use frc42_hasher as hasher;
 pub use frc42_hasher::hash;
-pub use frc42_macros::method_hash;
+pub use frc42_macros::{check_methods, client, frc42_export, method_hash};
 
+pub mod client;
+pub mod export;
+pub mod fallback;
+pub mod interface;
 pub mod match_method;
 pub mod message;
 
@@ -1,18 +1,64 @@
+#[cfg(feature = "use_sdk")]
+use fvm_shared::error::ExitCode;
+
+/// Method numbers below this are reserved and never produced by hashing a method name, apart from
+/// the `Constructor` (1)
+pub const FIRST_METHOD_NUMBER: u64 = 1 << 24;
+
+/// Whether a method number is in the range reserved by FRC-0042, which includes bare value
+/// transfers (method 0) but not the constructor
+pub const fn is_reserved(method_num: u64) -> bool {
+    method_num != 1 && method_num < FIRST_METHOD_NUMBER
+}
+
+/// Aborts with the standard exit code for a method number not handled by the actor
+#[cfg(feature = "use_sdk")]
+pub fn abort_unhandled(method_num: u64) -> ! {
+    fvm_sdk::vm::abort(
+        ExitCode::USR_UNHANDLED_MESSAGE.value(),
+        Some(&format!("unknown method number {method_num}")),
+    )
+}
+
+/// Aborts with the standard exit code for a method number in the reserved range
+#[cfg(feature = "use_sdk")]
+pub fn abort_reserved(method_num: u64) -> ! {
+    fvm_sdk::vm::abort(
+        ExitCode::USR_UNHANDLED_MESSAGE.value(),
+        Some(&format!("reserved method number {method_num}")),
+    )
+}
+
+/// Matches a method number against FRC-0042 method names
+///
+/// Besides arms for method names, the match may contain:
+/// - `reserved => ...` which handles method numbers in the reserved range (see [`is_reserved`])
+/// - `_ => ...` or `other => ...` to handle any other method number, the latter binding it to
+///   `other`
+///
+/// The parameters of an unhandled method can be read with `export::params_from_block` for
+/// forwarding to a fallback handler.
+///
+/// The method names are checked at compile time with [`check_methods!`](crate::check_methods), so
+/// a name that is repeated or hashes to the same number as another fails to compile rather than
+/// being shadowed at runtime.
 #[macro_export]
 macro_rules! match_method {
     ($method:expr, {$($body:tt)*}) => {
-        match_method!{@match $method, {}, $($body)*}
+        match_method!{@match $method, [], {}, $($body)*}
     };
-    (@match $method:expr, {$($body:tt)*}, $(,)*) => {
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, $(,)*) => {{
+        $crate::check_methods!($($names)*);
         match $method {
             $($body)*
         }
-    };
+    }};
     // matches block with comma
-    (@match $method:expr, {$($body:tt)*}, $p:literal => $e:expr, $($tail:tt)*) => {
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, $p:literal => $e:expr, $($tail:tt)*) => {
         match_method! {
             @match
             $method,
+            [$($names)* $p,],
             {
                 $($body)*
                 $crate::method_hash!($p) => $e,
@@ -21,10 +67,11 @@ macro_rules! match_method {
         }
     };
     // matches block without comma
-    (@match $method:expr, {$($body:tt)*}, $p:literal => $e:block $($tail:tt)*) => {
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, $p:literal => $e:block $($tail:tt)*) => {
         match_method! {
             @match
             $method,
+            [$($names)* $p,],
             {
                 $($body)*
                 $crate::method_hash!($p) => $e,
@@ -32,11 +79,38 @@ macro_rules! match_method {
             $($tail)*
         }
     };
+    // matches the reserved range with a trailing comma
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, reserved => $e:expr, $($tail:tt)*) => {
+        match_method! {
+            @match
+            $method,
+            [$($names)*],
+            {
+                $($body)*
+                method if $crate::match_method::is_reserved(method) => $e,
+            },
+            $($tail)*
+        }
+    };
+    // matches the reserved range with a block and no comma
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, reserved => $e:block $($tail:tt)*) => {
+        match_method! {
+            @match
+            $method,
+            [$($names)*],
+            {
+                $($body)*
+                method if $crate::match_method::is_reserved(method) => $e,
+            },
+            $($tail)*
+        }
+    };
     // matches _ with a trailing comma
-    (@match $method:expr, {$($body:tt)*}, _ => $e:expr, $($tail:tt)*) => {
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, _ => $e:expr, $($tail:tt)*) => {
         match_method! {
             @match
             $method,
+            [$($names)*],
             {
                 $($body)*
                 _ => $e,
@@ -45,16 +119,42 @@ macro_rules! match_method {
         }
     };
     // matches _ without a trailing comma (common if it's the last item)
-    (@match $method:expr, {$($body:tt)*}, _ => $e:expr) => {
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, _ => $e:expr) => {
         match_method! {
             @match
             $method,
+            [$($names)*],
             {
                 $($body)*
                 _ => $e,
             },
         }
     };
+    // matches a fallback binding the method number with a trailing comma
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, $other:ident => $e:expr, $($tail:tt)*) => {
+        match_method! {
+            @match
+            $method,
+            [$($names)*],
+            {
+                $($body)*
+                $other => $e,
+            },
+            $($tail)*
+        }
+    };
+    // matches a fallback binding the method number without a trailing comma
+    (@match $method:expr, [$($names:tt)*], {$($body:tt)*}, $other:ident => $e:expr) => {
+        match_method! {
+            @match
+            $method,
+            [$($names)*],
+            {
+                $($body)*
+                $other => $e,
+            },
+        }
+    };
 }
 
 #[cfg(test)]
@@ -108,4 +208,47 @@ mod tests {
 
         assert_eq!(ret, Some(2));
     }
+
+    #[test]
+    fn handle_reserved_methods() {
+        let dispatch = |method_num: u64| {
+            match_method!(method_num, {
+                "Constructor" => "constructor",
+                reserved => "reserved",
+                "TokensReceived" => {
+                    "tokens received"
+                }
+                _ => "unknown",
+            })
+        };
+
+        assert_eq!(dispatch(1), "constructor");
+        assert_eq!(dispatch(0), "reserved");
+        assert_eq!(dispatch(2), "reserved");
+        assert_eq!(dispatch(super::FIRST_METHOD_NUMBER - 1), "reserved");
+        assert_eq!(dispatch(crate::method_hash!("TokensReceived")), "tokens received");
+        assert_eq!(dispatch(super::FIRST_METHOD_NUMBER), "unknown");
+    }
+
+    #[test]
+    fn handle_fallback_binding() {
+        let method_num = 12345u64;
+        let ret = match_method!(method_num, {
+            "Constructor" => None,
+            reserved => {
+                None
+            }
+            other => Some(other),
+        });
+
+        assert_eq!(ret, None);
+
+        let method_num = crate::method_hash!("TokensReceived");
+        let ret = match_method!(method_num, {
+            "Constructor" => None,
+            other => Some(other)
+        });
+
+        assert_eq!(ret, Some(method_num));
+    }
 }
@@ -1,9 +1,12 @@
+use fvm_ipld_encoding::de::DeserializeOwned;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::ser::Serialize;
 #[cfg(feature = "use_sdk")]
 use fvm_sdk::send;
 use fvm_shared::{address::Address, econ::TokenAmount, error::ErrorNumber, Response};
 use thiserror::Error;
 
+use crate::client::{decode_return, encode_params, ClientError};
 use crate::hash::{Hasher, MethodNameErr, MethodResolver};
 
 /// Utility to invoke standard methods on deployed actors
@@ -12,6 +15,18 @@ pub struct MethodMessenger<T: Hasher> {
     method_resolver: MethodResolver<T>,
 }
 
+/// A call made by `MethodMessenger::call_many`: the actor, method name, params and value to send
+pub type BatchCall<'a> = (Address, &'a str, Option<IpldBlock>, TokenAmount);
+
+/// Controls whether `MethodMessenger::call_many` continues after a call fails
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BatchMode {
+    /// Stop after the first call that fails to send or exits with a non-zero code
+    StopOnError,
+    /// Make every call regardless of earlier failures
+    CollectAll,
+}
+
 #[derive(Error, PartialEq, Eq, Debug)]
 pub enum MethodMessengerError {
     #[error("error when calculating method name: `{0}`")]
@@ -53,4 +68,159 @@ impl<T: Hasher> MethodMessenger<T> {
         let _method = self.method_resolver.method_number(method)?;
         unimplemented!()
     }
+
+    /// Calls a method (by name) with typed parameters, decoding its return value
+    ///
+    /// Fails if the called method aborts or returns no data, so methods that return nothing
+    /// should be called with `call_method` instead.
+    pub fn call_typed<P, R>(
+        &self,
+        to: &Address,
+        method: &str,
+        params: &P,
+        value: TokenAmount,
+    ) -> Result<R, ClientError>
+    where
+        P: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        let params = encode_params(params)?;
+        let response = self.call_method(to, method, params, value)?;
+        decode_return(response)
+    }
+
+    /// Calls several methods in turn, returning the result of each call
+    ///
+    /// All method names are resolved before any message is sent, so an invalid name fails the
+    /// whole batch without side effects. With [`BatchMode::StopOnError`] the returned results end
+    /// at the first call that failed.
+    #[cfg(feature = "use_sdk")]
+    pub fn call_many(
+        &self,
+        calls: &[BatchCall],
+        mode: BatchMode,
+    ) -> Result<Vec<Result<Response, MethodMessengerError>>, MethodMessengerError> {
+        self.call_many_with(calls, mode, |to, method, params, value| {
+            send::send(to, method, params, value, None, fvm_shared::sys::SendFlags::empty())
+        })
+    }
+
+    #[cfg_attr(not(feature = "use_sdk"), allow(dead_code))]
+    fn call_many_with<F>(
+        &self,
+        calls: &[BatchCall],
+        mode: BatchMode,
+        mut send: F,
+    ) -> Result<Vec<Result<Response, MethodMessengerError>>, MethodMessengerError>
+    where
+        F: FnMut(&Address, u64, Option<IpldBlock>, TokenAmount) -> Result<Response, ErrorNumber>,
+    {
+        let methods = calls
+            .iter()
+            .map(|(_, method, _, _)| self.method_resolver.method_number(method))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut results = Vec::with_capacity(calls.len());
+        for ((to, _, params, value), method) in calls.iter().zip(methods) {
+            let res =
+                send(to, method, params.clone(), value.clone()).map_err(MethodMessengerError::from);
+            let failed = !matches!(&res, Ok(response) if response.exit_code.is_success());
+            results.push(res);
+            if failed && mode == BatchMode::StopOnError {
+                break;
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::{ErrorNumber, ExitCode};
+    use fvm_shared::Response;
+
+    use super::{BatchMode, MethodMessenger, MethodMessengerError};
+    use crate::hash::{Hasher, MethodNameErr, MethodResolver};
+
+    #[derive(Default)]
+    struct FakeHasher {}
+
+    impl Hasher for FakeHasher {
+        fn hash(&self, bytes: &[u8]) -> Vec<u8> {
+            bytes.to_vec()
+        }
+    }
+
+    fn method_number(name: &str) -> u64 {
+        MethodResolver::new(FakeHasher {}).method_number(name).unwrap()
+    }
+
+    fn response(exit_code: ExitCode) -> Result<Response, ErrorNumber> {
+        Ok(Response { exit_code, return_data: None })
+    }
+
+    #[test]
+    fn it_calls_many_methods() {
+        let messenger = MethodMessenger::new(FakeHasher {});
+        let calls = [
+            (Address::new_id(1), "Transfer", None, TokenAmount::from_atto(1)),
+            (
+                Address::new_id(2),
+                "Burn",
+                IpldBlock::serialize_cbor(&1u8).unwrap(),
+                TokenAmount::default(),
+            ),
+            (Address::new_id(3), "Transfer", None, TokenAmount::default()),
+        ];
+
+        let mut sent = vec![];
+        let results = messenger
+            .call_many_with(&calls, BatchMode::CollectAll, |to, method, _, value| {
+                sent.push((*to, method, value));
+                match to.id().unwrap() {
+                    2 => response(ExitCode::USR_FORBIDDEN),
+                    _ => response(ExitCode::OK),
+                }
+            })
+            .unwrap();
+        assert_eq!(
+            sent,
+            vec![
+                (Address::new_id(1), method_number("Transfer"), TokenAmount::from_atto(1)),
+                (Address::new_id(2), method_number("Burn"), TokenAmount::default()),
+                (Address::new_id(3), method_number("Transfer"), TokenAmount::default()),
+            ]
+        );
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[1].as_ref().unwrap().exit_code, ExitCode::USR_FORBIDDEN);
+
+        // stops after the first failure, whether the call aborted or couldn't be sent
+        let results = messenger
+            .call_many_with(&calls, BatchMode::StopOnError, |to, _, _, _| match to.id().unwrap() {
+                1 => Err(ErrorNumber::NotFound),
+                _ => response(ExitCode::OK),
+            })
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(MethodMessengerError::Syscall(ErrorNumber::NotFound))));
+    }
+
+    #[test]
+    fn it_resolves_all_methods_before_sending() {
+        let messenger = MethodMessenger::new(FakeHasher {});
+        let calls = [
+            (Address::new_id(1), "Transfer", None, TokenAmount::default()),
+            (Address::new_id(2), "burn", None, TokenAmount::default()),
+        ];
+
+        let err = messenger
+            .call_many_with(&calls, BatchMode::CollectAll, |_, _, _, _| {
+                panic!("no calls should be made")
+            })
+            .unwrap_err();
+        assert!(matches!(err, MethodMessengerError::MethodName(MethodNameErr::IllegalName(_))));
+    }
 }
@@ -0,0 +1,152 @@
+//! Benchmarks of token operations across HAMT bit widths
+//!
+//! Each operation runs against a token with many holders and allowances, once per bit width.
+//! Criterion measures the time taken, while the blocks read and written by a single run are printed
+//! before each benchmark, as they dominate the gas cost of an operation on chain. A change to
+//! `DEFAULT_HAMT_BIT_WIDTH` should be backed by these results.
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use frc46_token::token::state::TokenState;
+use frc46_token::token::Token;
+use fvm_actor_utils::instrumented_blockstore::InstrumentedBlockstore;
+use fvm_actor_utils::syscalls::fake_syscalls::FakeSyscalls;
+use fvm_actor_utils::util::ActorRuntime;
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::{address::Address, econ::TokenAmount, ActorID};
+
+type Runtime = ActorRuntime<FakeSyscalls, InstrumentedBlockstore<MemoryBlockstore>>;
+
+const BIT_WIDTHS: [u32; 5] = [2, 3, 4, 5, 6];
+/// Number of accounts holding tokens before each operation
+const HOLDERS: u64 = 1_000;
+/// Number of accounts minted to by a batch mint
+const BATCH_SIZE: u64 = 100;
+const MINTER: ActorID = 10;
+const FIRST_HOLDER: ActorID = 1_000;
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Transfer,
+    TransferFrom,
+    BatchMint,
+}
+
+impl Operation {
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Transfer => "transfer",
+            Operation::TransferFrom => "transfer_from",
+            Operation::BatchMint => "batch_mint",
+        }
+    }
+}
+
+fn id(actor: ActorID) -> Address {
+    Address::new_id(actor)
+}
+
+fn mint(token: &mut Token<FakeSyscalls, InstrumentedBlockstore<MemoryBlockstore>>, to: ActorID) {
+    let mut hook = token
+        .mint(
+            &id(MINTER),
+            &id(to),
+            &TokenAmount::from_atto(1_000),
+            RawBytes::default(),
+            RawBytes::default(),
+        )
+        .unwrap();
+    hook.call(token.runtime()).unwrap();
+}
+
+/// Creates a token where every holder has a balance and has approved the next holder as operator
+fn populated_state(runtime: &Runtime, bit_width: u32) -> TokenState {
+    let mut state = TokenState::new_with_bit_width(runtime.bs(), bit_width).unwrap();
+    let mut token = Token::wrap(runtime, 1, &mut state);
+    for holder in FIRST_HOLDER..FIRST_HOLDER + HOLDERS {
+        mint(&mut token, holder);
+        token
+            .increase_allowance(&id(holder), &id(holder + 1), &TokenAmount::from_atto(100))
+            .unwrap();
+    }
+    token.flush().unwrap();
+    state
+}
+
+/// Runs an operation and flushes the state, so that the blocks it changed are written
+fn run(runtime: &Runtime, state: &mut TokenState, operation: Operation) {
+    let mut token = Token::wrap(runtime, 1, state);
+    let amount = TokenAmount::from_atto(10);
+    match operation {
+        Operation::Transfer => {
+            let mut hook = token
+                .transfer(
+                    &id(FIRST_HOLDER),
+                    &id(FIRST_HOLDER + 1),
+                    &amount,
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap();
+            hook.call(token.runtime()).unwrap();
+        }
+        Operation::TransferFrom => {
+            let mut hook = token
+                .transfer_from(
+                    &id(FIRST_HOLDER + 1),
+                    &id(FIRST_HOLDER),
+                    &id(FIRST_HOLDER + 2),
+                    &amount,
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap();
+            hook.call(token.runtime()).unwrap();
+        }
+        Operation::BatchMint => {
+            let first_recipient = FIRST_HOLDER + HOLDERS;
+            for to in first_recipient..first_recipient + BATCH_SIZE {
+                mint(&mut token, to);
+            }
+        }
+    }
+    token.flush().unwrap();
+}
+
+fn state_layout(c: &mut Criterion) {
+    let tokens: Vec<_> = BIT_WIDTHS
+        .iter()
+        .map(|&bit_width| {
+            let runtime = Runtime::new(
+                FakeSyscalls::default(),
+                InstrumentedBlockstore::new(MemoryBlockstore::default()),
+            );
+            let state = populated_state(&runtime, bit_width);
+            (bit_width, runtime, state)
+        })
+        .collect();
+
+    for operation in [Operation::Transfer, Operation::TransferFrom, Operation::BatchMint] {
+        let mut group = c.benchmark_group(operation.name());
+        for (bit_width, runtime, state) in &tokens {
+            let (_, stats) = runtime.bs().measure(|| run(runtime, &mut state.clone(), operation));
+            println!(
+                "{}/{bit_width}: {} reads, {} writes, {} bytes written",
+                operation.name(),
+                stats.reads,
+                stats.writes,
+                stats.bytes_written
+            );
+            group.bench_with_input(BenchmarkId::from_parameter(bit_width), state, |b, state| {
+                b.iter_batched_ref(
+                    || state.clone(),
+                    |state| run(runtime, state, operation),
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, state_layout);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+//! Scaffolding shared by extensions that keep their own state alongside a token
+//!
+//! Extensions such as [`proposals`](crate::proposals) and [`sponsored`](crate::sponsored)
+//! transfers keep a state tree of Hamts which an actor stores next to its
+//! [`TokenState`](crate::token::state::TokenState). The state implements [`ExtensionState`] to be
+//! created, loaded and saved, and the extension's library functions are methods of an
+//! [`Extension`] wrapping that state with the runtime.
+use cid::multihash::Code;
+use cid::Cid;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_actor_utils::transaction::Transactional;
+use fvm_actor_utils::util::ActorRuntime;
+use fvm_ipld_blockstore::{Block, Blockstore};
+use fvm_ipld_encoding::de::DeserializeOwned;
+use fvm_ipld_encoding::ser::Serialize;
+use fvm_ipld_encoding::{CborStore, DAG_CBOR};
+use fvm_ipld_hamt::{BytesKey, Error as HamtError, Hamt};
+use fvm_shared::error::ExitCode;
+use thiserror::Error;
+
+use crate::token::state::DEFAULT_HAMT_BIT_WIDTH;
+
+#[derive(Error, Debug)]
+pub enum ExtensionError {
+    #[error("ipld hamt error: {0}")]
+    IpldHamt(#[from] HamtError),
+    #[error("missing state at cid: {0}")]
+    MissingState(Cid),
+    #[error("underlying serialization error: {0}")]
+    Serialization(String),
+}
+
+impl From<&ExtensionError> for ExitCode {
+    fn from(error: &ExtensionError) -> Self {
+        match error {
+            ExtensionError::IpldHamt(_) | ExtensionError::Serialization(_) => {
+                ExitCode::USR_SERIALIZATION
+            }
+            ExtensionError::MissingState(_) => ExitCode::USR_ILLEGAL_STATE,
+        }
+    }
+}
+
+pub type ExtensionResult<T> = std::result::Result<T, ExtensionError>;
+
+/// A Hamt in the state of an extension, keyed by bytes
+pub type Map<'bs, BS, V> = Hamt<&'bs BS, V, BytesKey>;
+
+/// The state tree of an extension, which an actor stores alongside its token state
+pub trait ExtensionState: Serialize + DeserializeOwned + Transactional {
+    /// Create a new state-tree, without committing it (the root cid) to a blockstore
+    fn new<BS: Blockstore>(store: &BS) -> ExtensionResult<Self>;
+
+    /// Bit-width to use when loading the state's Hamts
+    fn hamt_bit_width(&self) -> u32;
+
+    /// Loads a fresh copy of the state from a blockstore from a given cid
+    fn load<BS: Blockstore>(bs: &BS, cid: &Cid) -> ExtensionResult<Self> {
+        match bs.get_cbor::<Self>(cid) {
+            Ok(Some(state)) => Ok(state),
+            Ok(None) => Err(ExtensionError::MissingState(*cid)),
+            Err(err) => Err(ExtensionError::Serialization(err.to_string())),
+        }
+    }
+
+    /// Saves the current state to the blockstore, returning the cid
+    fn save<BS: Blockstore>(&self, bs: &BS) -> ExtensionResult<Cid> {
+        let data = fvm_ipld_encoding::to_vec(self)
+            .map_err(|err| ExtensionError::Serialization(err.to_string()))?;
+        bs.put(Code::Blake2b256, &Block { codec: DAG_CBOR, data })
+            .map_err(|err| ExtensionError::Serialization(err.to_string()))
+    }
+
+    /// Loads one of the state's Hamts from its root
+    fn load_map<'bs, BS, V>(&self, root: &Cid, bs: &'bs BS) -> ExtensionResult<Map<'bs, BS, V>>
+    where
+        BS: Blockstore,
+        V: Serialize + DeserializeOwned,
+    {
+        Ok(Map::load_with_bit_width(root, bs, self.hamt_bit_width())?)
+    }
+}
+
+/// Returns the root of an empty Hamt with the default bit-width, for the state of a new extension
+pub fn empty_map<BS, V>(store: &BS) -> ExtensionResult<Cid>
+where
+    BS: Blockstore,
+    V: Serialize + DeserializeOwned,
+{
+    Ok(Map::<BS, V>::new_with_bit_width(store, DEFAULT_HAMT_BIT_WIDTH).flush()?)
+}
+
+/// Library functions of an extension, operating on its wrapped state
+pub struct Extension<'st, S, BS, T>
+where
+    S: Syscalls,
+    BS: Blockstore,
+    T: ExtensionState,
+{
+    pub(crate) runtime: &'st ActorRuntime<S, BS>,
+    pub(crate) state: &'st mut T,
+}
+
+impl<'st, S, BS, T> Extension<'st, S, BS, T>
+where
+    S: Syscalls,
+    BS: Blockstore,
+    T: ExtensionState,
+{
+    /// Creates a new clean state instance
+    ///
+    /// Must be flushed to the blockstore explicitly to persist changes
+    pub fn create_state(bs: &BS) -> ExtensionResult<T> {
+        T::new(bs)
+    }
+
+    /// Wrap an existing state
+    pub fn wrap(runtime: &'st ActorRuntime<S, BS>, state: &'st mut T) -> Self {
+        Self { runtime, state }
+    }
+
+    /// Flush state and return Cid for root
+    pub fn flush(&mut self) -> ExtensionResult<Cid> {
+        self.state.save(self.runtime)
+    }
+
+    /// Get a reference to the wrapped state tree
+    pub fn state(&self) -> &T {
+        self.state
+    }
+}
@@ -0,0 +1,461 @@
+//! An ERC-20 facade, so that one token actor can serve both native FRC-0046 and Solidity callers
+//!
+//! EVM contracts call other actors through their `InvokeEVM` method (see [`INVOKE_EVM_METHOD_NUM`])
+//! with Solidity calldata. A token which exports that method alongside its FRC-0046 methods can
+//! decode the calldata as an [`Erc20Call`] and [`invoke`](Erc20Call::invoke) it through its
+//! [`FRC46Token`] implementation, returning the ABI-encoded return data to the contract.
+//!
+//! ERC-20 values are integers with `decimals()` implied decimal places, while FRC-0046 amounts are
+//! always in atto units (18 decimals) and must be multiples of the token's granularity.
+//! [`Erc20Scale`] maps between the two, so a token with a granularity of 10^12 appears to Solidity
+//! callers as an ERC-20 token with 6 decimals, where a value of 1 is 10^12 atto units.
+use std::cmp::Ordering;
+
+use frc42_dispatch::hash::method_number;
+use fvm_actor_utils::abi::{
+    encode, encode_bool, encode_uint256, encode_uint64, AbiError, AbiValue, Calldata,
+};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::bigint::BigInt;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::MethodNum;
+use num_traits::Zero;
+
+use crate::token::types::{
+    DecreaseAllowanceParams, FRC46Token, GetAllowanceParams, IncreaseAllowanceParams,
+    RevokeAllowanceParams, TransferFromParams, TransferParams,
+};
+use crate::token::TOKEN_PRECISION;
+
+/// The method through which EVM contracts call other actors, passing calldata as a CBOR byte string
+pub const INVOKE_EVM_METHOD_NUM: MethodNum = method_number("InvokeEVM");
+
+/// Selectors of the ERC-20 functions served by the facade
+pub mod selectors {
+    use frc42_dispatch::hash::evm_selector;
+    use fvm_actor_utils::abi::Selector;
+
+    pub const NAME: Selector = evm_selector("name()");
+    pub const SYMBOL: Selector = evm_selector("symbol()");
+    pub const DECIMALS: Selector = evm_selector("decimals()");
+    pub const TOTAL_SUPPLY: Selector = evm_selector("totalSupply()");
+    pub const BALANCE_OF: Selector = evm_selector("balanceOf(address)");
+    pub const ALLOWANCE: Selector = evm_selector("allowance(address,address)");
+    pub const TRANSFER: Selector = evm_selector("transfer(address,uint256)");
+    pub const APPROVE: Selector = evm_selector("approve(address,uint256)");
+    pub const TRANSFER_FROM: Selector = evm_selector("transferFrom(address,address,uint256)");
+}
+
+/// The scaling between ERC-20 values and FRC-0046 amounts
+///
+/// Values are scaled by the largest power of ten (up to 10^18) that divides the granularity, so
+/// every amount the token allows has an exact ERC-20 value. Amounts which aren't multiples of that
+/// power, such as some allowances, are rounded down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Erc20Scale {
+    factor: u64,
+}
+
+impl Erc20Scale {
+    /// Returns the scaling for a token of the given granularity
+    pub fn for_granularity(granularity: u64) -> Self {
+        let mut factor = 1;
+        while factor < TOKEN_PRECISION && granularity % (factor * 10) == 0 {
+            factor *= 10;
+        }
+        Self { factor }
+    }
+
+    /// Returns the number of atto units in an ERC-20 value of 1
+    pub fn factor(&self) -> u64 {
+        self.factor
+    }
+
+    /// Returns the number of decimals reported to ERC-20 callers
+    pub fn decimals(&self) -> u8 {
+        18 - self.factor.ilog10() as u8
+    }
+
+    /// Converts an ERC-20 value to an FRC-0046 amount
+    pub fn to_amount(&self, value: BigInt) -> TokenAmount {
+        TokenAmount::from_atto(value * self.factor)
+    }
+
+    /// Converts an FRC-0046 amount to an ERC-20 value, rounding down
+    pub fn to_value(&self, amount: &TokenAmount) -> BigInt {
+        amount.atto() / self.factor
+    }
+}
+
+/// An ERC-20 function call decoded from calldata
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Erc20Call {
+    Name,
+    Symbol,
+    Decimals,
+    TotalSupply,
+    BalanceOf { owner: Address },
+    Allowance { owner: Address, spender: Address },
+    Transfer { to: Address, value: BigInt },
+    Approve { spender: Address, value: BigInt },
+    TransferFrom { from: Address, to: Address, value: BigInt },
+}
+
+impl Erc20Call {
+    /// Decodes a call from Solidity calldata
+    pub fn decode(calldata: &[u8]) -> Result<Self, AbiError> {
+        let args = Calldata::parse(calldata)?;
+        Ok(match args.selector() {
+            selectors::NAME => Self::Name,
+            selectors::SYMBOL => Self::Symbol,
+            selectors::DECIMALS => Self::Decimals,
+            selectors::TOTAL_SUPPLY => Self::TotalSupply,
+            selectors::BALANCE_OF => Self::BalanceOf { owner: args.address(0)? },
+            selectors::ALLOWANCE => {
+                Self::Allowance { owner: args.address(0)?, spender: args.address(1)? }
+            }
+            selectors::TRANSFER => Self::Transfer { to: args.address(0)?, value: args.uint256(1)? },
+            selectors::APPROVE => {
+                Self::Approve { spender: args.address(0)?, value: args.uint256(1)? }
+            }
+            selectors::TRANSFER_FROM => Self::TransferFrom {
+                from: args.address(0)?,
+                to: args.address(1)?,
+                value: args.uint256(2)?,
+            },
+            selector => return Err(AbiError::UnknownSelector(selector)),
+        })
+    }
+
+    /// Returns true if the call doesn't change the token's state
+    ///
+    /// Read-only calls may be made by static calls from EVM contracts, in which the token must not
+    /// save its state.
+    pub fn is_read_only(&self) -> bool {
+        !matches!(self, Self::Transfer { .. } | Self::Approve { .. } | Self::TransferFrom { .. })
+    }
+
+    /// Performs the call through the token's FRC-0046 methods, returning the ABI-encoded return
+    /// data
+    ///
+    /// `caller` must be the address the token's methods act on behalf of. ERC-20 `approve` sets an
+    /// allowance outright, so it reads the caller's current allowance and increases, decreases or
+    /// revokes it as needed. Transfers call the recipient's receiver hook as native transfers do,
+    /// while a `transferFrom` of the caller's own balance is rejected as FRC-0046 requires.
+    pub fn invoke<T>(self, token: &mut T, caller: &Address) -> Result<Vec<u8>, T::TokenError>
+    where
+        T: FRC46Token,
+        T::TokenError: From<AbiError>,
+    {
+        let scale = Erc20Scale::for_granularity(token.granularity());
+        let encode_value = |amount: &TokenAmount| encode_uint256(&scale.to_value(amount));
+        let ret = match self {
+            Self::Name => encode(&[AbiValue::Bytes(token.name().as_bytes())]),
+            Self::Symbol => encode(&[AbiValue::Bytes(token.symbol().as_bytes())]),
+            Self::Decimals => encode(&[AbiValue::Word(encode_uint64(scale.decimals().into()))]),
+            Self::TotalSupply => encode(&[AbiValue::Word(encode_value(&token.total_supply())?)]),
+            Self::BalanceOf { owner } => {
+                encode(&[AbiValue::Word(encode_value(&token.balance_of(owner)?)?)])
+            }
+            Self::Allowance { owner, spender } => {
+                let allowance = token.allowance(GetAllowanceParams { owner, operator: spender })?;
+                encode(&[AbiValue::Word(encode_value(&allowance)?)])
+            }
+            Self::Transfer { to, value } => {
+                token.transfer(TransferParams {
+                    to,
+                    amount: scale.to_amount(value),
+                    operator_data: RawBytes::default(),
+                })?;
+                encode(&[AbiValue::Word(encode_bool(true))])
+            }
+            Self::Approve { spender, value } => {
+                let current =
+                    token.allowance(GetAllowanceParams { owner: *caller, operator: spender })?;
+                let target = scale.to_amount(value);
+                match target.cmp(&current) {
+                    Ordering::Greater => {
+                        let increase = target - current;
+                        token.increase_allowance(IncreaseAllowanceParams {
+                            operator: spender,
+                            increase,
+                        })?;
+                    }
+                    Ordering::Less if target.is_zero() => {
+                        token.revoke_allowance(RevokeAllowanceParams { operator: spender })?;
+                    }
+                    Ordering::Less => {
+                        let decrease = current - target;
+                        token.decrease_allowance(DecreaseAllowanceParams {
+                            operator: spender,
+                            decrease,
+                        })?;
+                    }
+                    Ordering::Equal => {}
+                }
+                encode(&[AbiValue::Word(encode_bool(true))])
+            }
+            Self::TransferFrom { from, to, value } => {
+                token.transfer_from(TransferFromParams {
+                    from,
+                    to,
+                    amount: scale.to_amount(value),
+                    operator_data: RawBytes::default(),
+                })?;
+                encode(&[AbiValue::Word(encode_bool(true))])
+            }
+        };
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use fvm_actor_utils::abi::{encode_address, encode_bool, encode_uint64, AbiError, Calldata};
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::bigint::BigInt;
+    use fvm_shared::econ::TokenAmount;
+    use num_traits::Zero;
+
+    use super::{selectors, Erc20Call, Erc20Scale};
+    use crate::token::types::{
+        AllowanceReturn, BalanceReturn, BurnFromParams, BurnFromReturn, BurnParams, BurnReturn,
+        DecreaseAllowanceParams, DecreaseAllowanceReturn, FRC46Token, GetAllowanceParams,
+        GranularityReturn, IncreaseAllowanceParams, IncreaseAllowanceReturn, RevokeAllowanceParams,
+        RevokeAllowanceReturn, TotalSupplyReturn, TransferFromParams, TransferFromReturn,
+        TransferParams, TransferReturn,
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum MockError {
+        Abi(AbiError),
+        InsufficientFunds,
+        /// The mock doesn't support the method
+        Unsupported,
+    }
+
+    impl From<AbiError> for MockError {
+        fn from(error: AbiError) -> Self {
+            MockError::Abi(error)
+        }
+    }
+
+    /// A token keeping balances and allowances in memory, acting on behalf of a fixed caller
+    struct MockToken {
+        caller: Address,
+        granularity: u64,
+        balances: HashMap<Address, TokenAmount>,
+        allowances: HashMap<(Address, Address), TokenAmount>,
+    }
+
+    impl MockToken {
+        fn debit(&mut self, owner: Address, amount: &TokenAmount) -> Result<(), MockError> {
+            let balance = self.balances.entry(owner).or_default();
+            if &*balance < amount {
+                return Err(MockError::InsufficientFunds);
+            }
+            *balance -= amount.clone();
+            Ok(())
+        }
+    }
+
+    impl FRC46Token for MockToken {
+        type TokenError = MockError;
+
+        fn name(&self) -> String {
+            "Mock Token".into()
+        }
+
+        fn symbol(&self) -> String {
+            "MOCK".into()
+        }
+
+        fn granularity(&self) -> GranularityReturn {
+            self.granularity
+        }
+
+        fn total_supply(&mut self) -> TotalSupplyReturn {
+            self.balances.values().fold(TokenAmount::zero(), |supply, balance| supply + balance)
+        }
+
+        fn balance_of(&mut self, params: Address) -> Result<BalanceReturn, MockError> {
+            Ok(self.balances.get(&params).cloned().unwrap_or_default())
+        }
+
+        fn allowance(&mut self, params: GetAllowanceParams) -> Result<AllowanceReturn, MockError> {
+            let key = (params.owner, params.operator);
+            Ok(self.allowances.get(&key).cloned().unwrap_or_default())
+        }
+
+        fn transfer(&mut self, params: TransferParams) -> Result<TransferReturn, MockError> {
+            self.debit(self.caller, &params.amount)?;
+            *self.balances.entry(params.to).or_default() += params.amount.clone();
+            Ok(TransferReturn {
+                from_balance: self.balances[&self.caller].clone(),
+                to_balance: self.balances[&params.to].clone(),
+                recipient_data: RawBytes::default(),
+            })
+        }
+
+        fn transfer_from(
+            &mut self,
+            params: TransferFromParams,
+        ) -> Result<TransferFromReturn, MockError> {
+            let allowance = self.allowances.entry((params.from, self.caller)).or_default();
+            if *allowance < params.amount {
+                return Err(MockError::InsufficientFunds);
+            }
+            *allowance -= params.amount.clone();
+            let allowance = allowance.clone();
+            self.debit(params.from, &params.amount)?;
+            *self.balances.entry(params.to).or_default() += params.amount.clone();
+            Ok(TransferFromReturn {
+                from_balance: self.balances[&params.from].clone(),
+                to_balance: self.balances[&params.to].clone(),
+                allowance,
+                recipient_data: RawBytes::default(),
+            })
+        }
+
+        fn increase_allowance(
+            &mut self,
+            params: IncreaseAllowanceParams,
+        ) -> Result<IncreaseAllowanceReturn, MockError> {
+            let allowance = self.allowances.entry((self.caller, params.operator)).or_default();
+            *allowance += params.increase;
+            Ok(allowance.clone())
+        }
+
+        fn decrease_allowance(
+            &mut self,
+            params: DecreaseAllowanceParams,
+        ) -> Result<DecreaseAllowanceReturn, MockError> {
+            let allowance = self.allowances.entry((self.caller, params.operator)).or_default();
+            *allowance -= params.decrease;
+            Ok(allowance.clone())
+        }
+
+        fn revoke_allowance(
+            &mut self,
+            params: RevokeAllowanceParams,
+        ) -> Result<RevokeAllowanceReturn, MockError> {
+            self.allowances.remove(&(self.caller, params.operator));
+            Ok(())
+        }
+
+        fn burn(&mut self, _: BurnParams) -> Result<BurnReturn, MockError> {
+            Err(MockError::Unsupported)
+        }
+
+        fn burn_from(&mut self, _: BurnFromParams) -> Result<BurnFromReturn, MockError> {
+            Err(MockError::Unsupported)
+        }
+    }
+
+    const ALICE: Address = Address::new_id(1);
+    const BOB: Address = Address::new_id(2);
+
+    fn calldata(selector: u32, args: &[[u8; 32]]) -> Vec<u8> {
+        [&selector.to_be_bytes()[..], &args.concat()].concat()
+    }
+
+    fn word(n: u64) -> [u8; 32] {
+        encode_uint64(n)
+    }
+
+    fn address(address: Address) -> [u8; 32] {
+        encode_address(&address).unwrap()
+    }
+
+    fn call(token: &mut MockToken, calldata: &[u8]) -> Result<Vec<u8>, MockError> {
+        let caller = token.caller;
+        Erc20Call::decode(calldata)?.invoke(token, &caller)
+    }
+
+    #[test]
+    fn it_scales_values_by_granularity() {
+        let scale = Erc20Scale::for_granularity(1);
+        assert_eq!((scale.factor(), scale.decimals()), (1, 18));
+        let scale = Erc20Scale::for_granularity(1_000_000_000_000);
+        assert_eq!((scale.factor(), scale.decimals()), (1_000_000_000_000, 6));
+        // only the power of ten dividing the granularity is scaled away
+        let scale = Erc20Scale::for_granularity(5_000_000_000_000);
+        assert_eq!((scale.factor(), scale.decimals()), (1_000_000_000_000, 6));
+        let scale = Erc20Scale::for_granularity(10_000_000_000_000_000_000);
+        assert_eq!((scale.factor(), scale.decimals()), (1_000_000_000_000_000_000, 0));
+
+        let scale = Erc20Scale::for_granularity(1_000_000_000_000);
+        assert_eq!(scale.to_amount(BigInt::from(3)), TokenAmount::from_atto(3_000_000_000_000u64));
+        assert_eq!(scale.to_value(&TokenAmount::from_atto(3_500_000_000_000u64)), BigInt::from(3));
+    }
+
+    #[test]
+    fn it_decodes_calls() {
+        let data = calldata(selectors::TRANSFER_FROM, &[address(ALICE), address(BOB), word(5)]);
+        assert_eq!(
+            Erc20Call::decode(&data).unwrap(),
+            Erc20Call::TransferFrom { from: ALICE, to: BOB, value: BigInt::from(5) }
+        );
+        assert_eq!(Erc20Call::decode(&calldata(selectors::NAME, &[])).unwrap(), Erc20Call::Name);
+        assert!(!Erc20Call::decode(&data).unwrap().is_read_only());
+        assert!(Erc20Call::Name.is_read_only());
+
+        let data = calldata(selectors::TRANSFER, &[address(ALICE)]);
+        assert_eq!(Erc20Call::decode(&data), Err(AbiError::MissingArgument(1)));
+        let data = calldata(0x12345678, &[]);
+        assert_eq!(Erc20Call::decode(&data), Err(AbiError::UnknownSelector(0x12345678)));
+    }
+
+    #[test]
+    fn it_serves_erc20_calls() {
+        let mut token = MockToken {
+            caller: ALICE,
+            granularity: 1_000_000_000_000,
+            balances: HashMap::from([(ALICE, TokenAmount::from_atto(100_000_000_000_000u64))]),
+            allowances: HashMap::new(),
+        };
+
+        let ret = call(&mut token, &calldata(selectors::SYMBOL, &[])).unwrap();
+        let ret_calldata = [&[0; 4][..], &ret].concat();
+        assert_eq!(Calldata::parse(&ret_calldata).unwrap().bytes(0).unwrap(), b"MOCK");
+        let ret = call(&mut token, &calldata(selectors::DECIMALS, &[])).unwrap();
+        assert_eq!(ret, word(6));
+
+        // values are scaled by the granularity in both directions
+        let ret = call(&mut token, &calldata(selectors::BALANCE_OF, &[address(ALICE)])).unwrap();
+        assert_eq!(ret, word(100));
+        let ret = call(&mut token, &calldata(selectors::TRANSFER, &[address(BOB), word(40)]));
+        assert_eq!(ret.unwrap(), encode_bool(true));
+        assert_eq!(token.balances[&BOB], TokenAmount::from_atto(40_000_000_000_000u64));
+        let ret = call(&mut token, &calldata(selectors::TOTAL_SUPPLY, &[])).unwrap();
+        assert_eq!(ret, word(100));
+
+        // approve sets the allowance outright, whatever it was before
+        let approve = |value| calldata(selectors::APPROVE, &[address(BOB), word(value)]);
+        let allowance = calldata(selectors::ALLOWANCE, &[address(ALICE), address(BOB)]);
+        for value in [30, 50, 20, 20, 0] {
+            call(&mut token, &approve(value)).unwrap();
+            assert_eq!(call(&mut token, &allowance).unwrap(), word(value));
+        }
+        assert!(token.allowances.get(&(ALICE, BOB)).is_none());
+
+        // transferFrom spends the allowance of the caller
+        token.caller = BOB;
+        let transfer_from =
+            calldata(selectors::TRANSFER_FROM, &[address(ALICE), address(BOB), word(10)]);
+        assert_eq!(call(&mut token, &transfer_from), Err(MockError::InsufficientFunds));
+        token.allowances.insert((ALICE, BOB), TokenAmount::from_atto(10_000_000_000_000u64));
+        call(&mut token, &transfer_from).unwrap();
+        assert_eq!(token.balances[&BOB], TokenAmount::from_atto(50_000_000_000_000u64));
+        assert!(token.allowances[&(ALICE, BOB)].is_zero());
+
+        // balances too large for a uint256 can't be returned
+        token.balances.insert(BOB, TokenAmount::from_atto(BigInt::from(1) << 300));
+        let ret = call(&mut token, &calldata(selectors::BALANCE_OF, &[address(BOB)]));
+        assert!(matches!(ret, Err(MockError::Abi(AbiError::Overflow(_)))));
+    }
+}
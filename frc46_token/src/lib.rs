@@ -1,3 +1,7 @@
 // https://github.com/helix-onchain/filecoin/issues/165
+pub mod extension;
+pub mod interop;
+pub mod proposals;
 pub mod receiver;
+pub mod sponsored;
 pub mod token;
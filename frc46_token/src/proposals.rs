@@ -0,0 +1,595 @@
+//! Transfers proposed by one signer of an account and made once enough signers confirm them
+//!
+//! Treasuries are often held by multisig actors, whose signers would otherwise have to approve
+//! every token transfer through the multisig, or have the multisig grant them allowances to spend
+//! from it. Instead, an account can name a set of signers and a threshold once with
+//! [`Proposals::set_signers`]. Any of those signers may then [`propose`](Proposals::propose) a
+//! transfer out of the account, and the transfer is made when the threshold number of signers have
+//! [`confirm`](Proposals::confirm)ed it. The call making the last confirmation returns the
+//! recipient's receiver hook, which must be called as for [`Token::transfer`].
+//!
+//! Proposals expire at an epoch chosen by their proposer, after which they can no longer be
+//! confirmed and anyone may [`cancel`](Proposals::cancel) them. Confirmations only count while
+//! their signer remains in the account's signer set, so changes to the signers or threshold apply
+//! to pending proposals too.
+//!
+//! Proposals are kept in a [`ProposalState`] which an actor stores alongside its
+//! [`TokenState`](crate::token::state::TokenState).
+use cid::Cid;
+use fvm_actor_utils::messaging::MessagingError;
+use fvm_actor_utils::receiver::ReceiverHook;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_actor_utils::transaction::Transactional;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_encoding::RawBytes;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::ActorID;
+use integer_encoding::VarInt;
+use thiserror::Error;
+
+use crate::extension::{
+    empty_map, Extension, ExtensionError, ExtensionResult, ExtensionState, Map,
+};
+use crate::token::state::{actor_id_key, DEFAULT_HAMT_BIT_WIDTH};
+use crate::token::types::TransferIntermediate;
+use crate::token::{validate_amount_with_granularity, Token, TokenError};
+
+/// Identifies a proposal. Proposals are numbered from zero in the order they are made
+pub type ProposalID = u64;
+
+#[derive(Error, Debug)]
+pub enum ProposalError {
+    #[error("error in proposal state: {0}")]
+    State(#[from] ExtensionError),
+    #[error("error in token: {0}")]
+    Token(#[from] TokenError),
+    #[error("error calling other actor: {0}")]
+    Messaging(#[from] MessagingError),
+    #[error("threshold {threshold} must be between 1 and the number of signers {signers}")]
+    InvalidThreshold { threshold: u64, signers: usize },
+    #[error("{0} is listed as a signer more than once")]
+    DuplicateSigner(ActorID),
+    #[error("{signer} is not a signer for {holder}")]
+    NotSigner { signer: ActorID, holder: ActorID },
+    #[error("proposal {0} not found")]
+    NotFound(ProposalID),
+    #[error("expiration {expiration} must be after the current epoch {epoch}")]
+    InvalidExpiration { expiration: ChainEpoch, epoch: ChainEpoch },
+    #[error("proposal {id} expired at epoch {expiration}")]
+    Expired { id: ProposalID, expiration: ChainEpoch },
+    #[error("{signer} has already confirmed proposal {id}")]
+    AlreadyConfirmed { id: ProposalID, signer: ActorID },
+    #[error("{caller} cannot cancel proposal {id} before it expires")]
+    CannotCancel { id: ProposalID, caller: ActorID },
+}
+
+impl From<&ProposalError> for ExitCode {
+    fn from(error: &ProposalError) -> Self {
+        match error {
+            ProposalError::State(e) => e.into(),
+            ProposalError::Token(e) => e.into(),
+            ProposalError::Messaging(e) => e.into(),
+            ProposalError::InvalidThreshold { threshold: _, signers: _ }
+            | ProposalError::DuplicateSigner(_)
+            | ProposalError::InvalidExpiration { expiration: _, epoch: _ }
+            | ProposalError::AlreadyConfirmed { id: _, signer: _ } => {
+                ExitCode::USR_ILLEGAL_ARGUMENT
+            }
+            ProposalError::NotSigner { signer: _, holder: _ }
+            | ProposalError::Expired { id: _, expiration: _ }
+            | ProposalError::CannotCancel { id: _, caller: _ } => ExitCode::USR_FORBIDDEN,
+            ProposalError::NotFound(_) => ExitCode::USR_NOT_FOUND,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, ProposalError>;
+
+type SignerMap<'bs, BS> = Map<'bs, BS, SignerSet>;
+type ProposalMap<'bs, BS> = Map<'bs, BS, Proposal>;
+
+/// The signers who may propose and confirm transfers out of an account
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct SignerSet {
+    pub signers: Vec<ActorID>,
+    /// Number of signers who must confirm a proposal before its transfer is made
+    pub threshold: u64,
+}
+
+/// A transfer awaiting confirmation
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct Proposal {
+    /// The account the tokens are transferred from
+    pub holder: ActorID,
+    pub proposer: ActorID,
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub operator_data: RawBytes,
+    /// The first epoch at which the proposal can no longer be confirmed
+    pub expiration: ChainEpoch,
+    /// Signers who have confirmed the proposal, starting with its proposer
+    pub confirmations: Vec<ActorID>,
+}
+
+/// Parameters of a proposed transfer
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct ProposeParams {
+    pub holder: Address,
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub operator_data: RawBytes,
+    pub expiration: ChainEpoch,
+}
+
+/// The outcome of proposing or confirming a transfer
+#[derive(Debug)]
+pub enum Confirmation {
+    /// The proposal needs more confirmations, having the given number from current signers
+    Pending { id: ProposalID, confirmations: u64 },
+    /// The proposal reached its threshold and the transfer was made
+    ///
+    /// The hook must be called or it will panic and abort the transaction. Its return data can be
+    /// passed to [`Token::transfer_return`].
+    Executed { id: ProposalID, hook: ReceiverHook<TransferIntermediate> },
+}
+
+/// Proposal state IPLD structure
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct ProposalState {
+    /// Map<ActorID, SignerSet> of the accounts which have configured signers, as a Hamt
+    pub signers: Cid,
+    /// Map<ProposalID, Proposal> of pending proposals, as a Hamt
+    pub proposals: Cid,
+    /// The ID of the next proposal to be made
+    pub next_proposal: ProposalID,
+    /// Bit-width to use when loading Hamts
+    hamt_bit_width: u32,
+}
+
+impl Transactional for ProposalState {}
+
+impl ExtensionState for ProposalState {
+    fn new<BS: Blockstore>(store: &BS) -> ExtensionResult<Self> {
+        Ok(Self {
+            signers: empty_map::<_, SignerSet>(store)?,
+            proposals: empty_map::<_, Proposal>(store)?,
+            next_proposal: 0,
+            hamt_bit_width: DEFAULT_HAMT_BIT_WIDTH,
+        })
+    }
+
+    fn hamt_bit_width(&self) -> u32 {
+        self.hamt_bit_width
+    }
+}
+
+impl ProposalState {
+    /// Get the signers configured for an account
+    pub fn get_signers<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        holder: ActorID,
+    ) -> ExtensionResult<Option<SignerSet>> {
+        let map: SignerMap<BS> = self.load_map(&self.signers, bs)?;
+        Ok(map.get(&actor_id_key(holder))?.cloned())
+    }
+
+    /// Set or clear the signers configured for an account
+    pub fn set_signers<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        holder: ActorID,
+        signers: Option<SignerSet>,
+    ) -> ExtensionResult<()> {
+        let mut map: SignerMap<BS> = self.load_map(&self.signers, bs)?;
+        match signers {
+            Some(signers) => {
+                map.set(actor_id_key(holder), signers)?;
+            }
+            None => {
+                map.delete(&actor_id_key(holder))?;
+            }
+        }
+        self.signers = map.flush()?;
+        Ok(())
+    }
+
+    /// Get a pending proposal
+    pub fn get_proposal<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        id: ProposalID,
+    ) -> ExtensionResult<Option<Proposal>> {
+        let map: ProposalMap<BS> = self.load_map(&self.proposals, bs)?;
+        Ok(map.get(&proposal_key(id))?.cloned())
+    }
+
+    /// Store or remove a pending proposal
+    pub fn set_proposal<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        id: ProposalID,
+        proposal: Option<Proposal>,
+    ) -> ExtensionResult<()> {
+        let mut map: ProposalMap<BS> = self.load_map(&self.proposals, bs)?;
+        match proposal {
+            Some(proposal) => {
+                map.set(proposal_key(id), proposal)?;
+            }
+            None => {
+                map.delete(&proposal_key(id))?;
+            }
+        }
+        self.proposals = map.flush()?;
+        Ok(())
+    }
+}
+
+fn proposal_key(id: ProposalID) -> BytesKey {
+    id.encode_var_vec().into()
+}
+
+/// Library functions managing transfer proposals for a token
+pub type Proposals<'st, S, BS> = Extension<'st, S, BS, ProposalState>;
+
+impl<S, BS> Proposals<'_, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Returns the signers configured for an account, if any
+    pub fn signers(&self, holder: &Address) -> Result<Option<SignerSet>> {
+        match self.runtime.resolve_id(holder) {
+            Ok(holder) => Ok(self.state.get_signers(self.runtime, holder)?),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Returns a pending proposal
+    pub fn proposal(&self, id: ProposalID) -> Result<Proposal> {
+        self.state.get_proposal(self.runtime, id)?.ok_or(ProposalError::NotFound(id))
+    }
+
+    /// Sets the signers who may propose transfers out of the holder's account, and how many of them
+    /// must confirm each transfer
+    ///
+    /// The holder is implicitly the caller of the actor. Passing no signers and a threshold of zero
+    /// clears the account's signers, after which its pending proposals can't be confirmed.
+    pub fn set_signers(
+        &mut self,
+        holder: &Address,
+        signers: &[Address],
+        threshold: u64,
+    ) -> Result<()> {
+        let holder = self.runtime.resolve_id(holder)?;
+        let mut ids = Vec::with_capacity(signers.len());
+        for signer in signers {
+            let id = self.runtime.resolve_or_init(signer)?;
+            if ids.contains(&id) {
+                return Err(ProposalError::DuplicateSigner(id));
+            }
+            ids.push(id);
+        }
+
+        let signers = if ids.is_empty() && threshold == 0 {
+            None
+        } else if threshold == 0 || threshold > ids.len() as u64 {
+            return Err(ProposalError::InvalidThreshold { threshold, signers: ids.len() });
+        } else {
+            Some(SignerSet { signers: ids, threshold })
+        };
+        Ok(self.state.set_signers(self.runtime, holder, signers)?)
+    }
+
+    /// Proposes a transfer out of the holder's account, confirming it on behalf of the proposer
+    ///
+    /// The proposer must be one of the holder's signers and the amount must be a valid transfer
+    /// amount for the token. If the holder's threshold is one, the transfer is made immediately.
+    pub fn propose(
+        &mut self,
+        token: &mut Token<'_, S, BS>,
+        proposer: &Address,
+        params: ProposeParams,
+    ) -> Result<Confirmation> {
+        validate_amount_with_granularity(&params.amount, "proposal", token.granularity())?;
+        let epoch = self.runtime.curr_epoch();
+        if params.expiration <= epoch {
+            return Err(ProposalError::InvalidExpiration { expiration: params.expiration, epoch });
+        }
+        let proposer = self.runtime.resolve_id(proposer)?;
+        let holder = self.runtime.resolve_id(&params.holder)?;
+        let signers = self.signer_set(holder, proposer)?;
+
+        let id = self.state.next_proposal;
+        let proposal = Proposal {
+            holder,
+            proposer,
+            to: params.to,
+            amount: params.amount,
+            operator_data: params.operator_data,
+            expiration: params.expiration,
+            confirmations: vec![proposer],
+        };
+        let confirmation = self.apply(token, id, proposal, &signers)?;
+        self.state.next_proposal += 1;
+        Ok(confirmation)
+    }
+
+    /// Confirms a proposal on behalf of one of its holder's signers
+    ///
+    /// Once the holder's threshold of current signers have confirmed the proposal, the transfer is
+    /// made and the proposal removed.
+    pub fn confirm(
+        &mut self,
+        token: &mut Token<'_, S, BS>,
+        signer: &Address,
+        id: ProposalID,
+    ) -> Result<Confirmation> {
+        let signer = self.runtime.resolve_id(signer)?;
+        let mut proposal = self.proposal(id)?;
+        if self.runtime.curr_epoch() >= proposal.expiration {
+            return Err(ProposalError::Expired { id, expiration: proposal.expiration });
+        }
+        let signers = self.signer_set(proposal.holder, signer)?;
+        if proposal.confirmations.contains(&signer) {
+            return Err(ProposalError::AlreadyConfirmed { id, signer });
+        }
+
+        proposal.confirmations.push(signer);
+        self.apply(token, id, proposal, &signers)
+    }
+
+    /// Removes a proposal without making its transfer, returning it
+    ///
+    /// The proposer or holder may cancel a proposal at any time, and anyone may once it expires.
+    pub fn cancel(&mut self, caller: &Address, id: ProposalID) -> Result<Proposal> {
+        let proposal = self.proposal(id)?;
+        if self.runtime.curr_epoch() < proposal.expiration {
+            let caller = self.runtime.resolve_id(caller)?;
+            if caller != proposal.proposer && caller != proposal.holder {
+                return Err(ProposalError::CannotCancel { id, caller });
+            }
+        }
+        self.state.set_proposal(self.runtime, id, None)?;
+        Ok(proposal)
+    }
+
+    /// Returns the holder's signers, checking the signer is one of them
+    fn signer_set(&self, holder: ActorID, signer: ActorID) -> Result<SignerSet> {
+        match self.state.get_signers(self.runtime, holder)? {
+            Some(signers) if signers.signers.contains(&signer) => Ok(signers),
+            _ => Err(ProposalError::NotSigner { signer, holder }),
+        }
+    }
+
+    /// Stores the proposal, or makes its transfer if enough current signers have confirmed it
+    fn apply(
+        &mut self,
+        token: &mut Token<'_, S, BS>,
+        id: ProposalID,
+        proposal: Proposal,
+        signers: &SignerSet,
+    ) -> Result<Confirmation> {
+        let runtime = self.runtime;
+        let confirmations =
+            proposal.confirmations.iter().filter(|s| signers.signers.contains(s)).count() as u64;
+        self.state.transaction(|state| {
+            if confirmations < signers.threshold {
+                state.set_proposal(runtime, id, Some(proposal))?;
+                return Ok(Confirmation::Pending { id, confirmations });
+            }
+            state.set_proposal(runtime, id, None)?;
+            let hook = token.transfer(
+                &Address::new_id(proposal.holder),
+                &proposal.to,
+                &proposal.amount,
+                proposal.operator_data,
+                RawBytes::default(),
+            )?;
+            Ok(Confirmation::Executed { id, hook })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::syscalls::fake_syscalls::FakeSyscalls;
+    use fvm_actor_utils::util::ActorRuntime;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::clock::ChainEpoch;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{Confirmation, ProposalError, ProposalState, Proposals, ProposeParams};
+    use crate::extension::ExtensionState;
+    use crate::token::state::TokenState;
+    use crate::token::Token;
+
+    const TOKEN_ACTOR: &Address = &Address::new_id(1);
+    const TREASURY: &Address = &Address::new_id(2);
+    const ALICE: &Address = &Address::new_id(3);
+    const BOB: &Address = &Address::new_id(4);
+    const CAROL: &Address = &Address::new_id(5);
+    const RECIPIENT: &Address = &Address::new_id(6);
+
+    fn setup(
+        runtime: &ActorRuntime<FakeSyscalls, MemoryBlockstore>,
+    ) -> (TokenState, ProposalState) {
+        let mut token_state = TokenState::new(runtime.bs()).unwrap();
+        let mut token = Token::wrap(runtime, 1, &mut token_state);
+        let mut hook = token
+            .mint(
+                TOKEN_ACTOR,
+                TREASURY,
+                &TokenAmount::from_atto(100),
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap();
+        token.flush().unwrap();
+        hook.call(runtime).unwrap();
+        (token_state, ProposalState::new(runtime.bs()).unwrap())
+    }
+
+    fn params(amount: u64, expiration: ChainEpoch) -> ProposeParams {
+        ProposeParams {
+            holder: *TREASURY,
+            to: *RECIPIENT,
+            amount: TokenAmount::from_atto(amount),
+            operator_data: RawBytes::default(),
+            expiration,
+        }
+    }
+
+    fn assert_pending(confirmation: Confirmation, expected: u64) -> u64 {
+        match confirmation {
+            Confirmation::Pending { id, confirmations } => {
+                assert_eq!(confirmations, expected);
+                id
+            }
+            Confirmation::Executed { hook, .. } => {
+                std::mem::forget(hook);
+                panic!("expected the proposal to be pending");
+            }
+        }
+    }
+
+    #[test]
+    fn it_makes_transfers_confirmed_by_enough_signers() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let (mut token_state, mut proposal_state) = setup(&runtime);
+        let mut token = Token::wrap(&runtime, 1, &mut token_state);
+        let mut proposals = Proposals::wrap(&runtime, &mut proposal_state);
+
+        // only configured signers may propose
+        let err = proposals.propose(&mut token, ALICE, params(60, 10)).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+        proposals.set_signers(TREASURY, &[*ALICE, *BOB, *CAROL], 2).unwrap();
+        assert_eq!(proposals.signers(TREASURY).unwrap().unwrap().signers, [3, 4, 5]);
+
+        let id = assert_pending(proposals.propose(&mut token, ALICE, params(60, 10)).unwrap(), 1);
+        assert_eq!(id, 0);
+        let proposal = proposals.proposal(id).unwrap();
+        assert_eq!(proposal.proposer, ALICE.id().unwrap());
+        assert_eq!(proposal.confirmations, [ALICE.id().unwrap()]);
+        // nothing is transferred until the threshold is reached
+        assert_eq!(token.balance_of(TREASURY).unwrap(), TokenAmount::from_atto(100));
+
+        // signers can't confirm twice, and others can't confirm at all
+        let err = proposals.confirm(&mut token, ALICE, id).unwrap_err();
+        assert!(matches!(err, ProposalError::AlreadyConfirmed { .. }));
+        let err = proposals.confirm(&mut token, RECIPIENT, id).unwrap_err();
+        assert!(matches!(err, ProposalError::NotSigner { signer: 6, holder: 2 }));
+
+        match proposals.confirm(&mut token, BOB, id).unwrap() {
+            Confirmation::Executed { id: executed, mut hook } => {
+                assert_eq!(executed, id);
+                token.flush().unwrap();
+                proposals.flush().unwrap();
+                let ret = token.transfer_return(hook.call(&runtime).unwrap()).unwrap();
+                assert_eq!(ret.from_balance, TokenAmount::from_atto(40));
+                assert_eq!(ret.to_balance, TokenAmount::from_atto(60));
+            }
+            Confirmation::Pending { .. } => panic!("expected the transfer to be made"),
+        }
+        // the proposal is removed once executed
+        let err = proposals.confirm(&mut token, CAROL, id).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_NOT_FOUND);
+
+        // a threshold of one makes transfers as they are proposed
+        proposals.set_signers(TREASURY, &[*ALICE], 1).unwrap();
+        match proposals.propose(&mut token, ALICE, params(40, 10)).unwrap() {
+            Confirmation::Executed { id, mut hook } => {
+                assert_eq!(id, 1);
+                hook.call(&runtime).unwrap();
+            }
+            Confirmation::Pending { .. } => panic!("expected the transfer to be made"),
+        }
+        assert_eq!(token.balance_of(TREASURY).unwrap(), TokenAmount::from_atto(0));
+        assert_eq!(token.balance_of(RECIPIENT).unwrap(), TokenAmount::from_atto(100));
+    }
+
+    #[test]
+    fn it_validates_signer_sets() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let (mut token_state, mut proposal_state) = setup(&runtime);
+        let mut token = Token::wrap(&runtime, 1, &mut token_state);
+        let mut proposals = Proposals::wrap(&runtime, &mut proposal_state);
+
+        for threshold in [0, 3] {
+            let err = proposals.set_signers(TREASURY, &[*ALICE, *BOB], threshold).unwrap_err();
+            assert!(matches!(err, ProposalError::InvalidThreshold { signers: 2, .. }));
+        }
+        let err = proposals.set_signers(TREASURY, &[*ALICE, *ALICE], 1).unwrap_err();
+        assert!(matches!(err, ProposalError::DuplicateSigner(3)));
+        assert_eq!(proposals.signers(TREASURY).unwrap(), None);
+
+        // confirmations only count while their signer remains a signer
+        proposals.set_signers(TREASURY, &[*ALICE, *BOB, *CAROL], 2).unwrap();
+        let id = assert_pending(proposals.propose(&mut token, ALICE, params(60, 10)).unwrap(), 1);
+        proposals.set_signers(TREASURY, &[*BOB, *CAROL], 2).unwrap();
+        assert_pending(proposals.confirm(&mut token, BOB, id).unwrap(), 1);
+        let err = proposals.confirm(&mut token, ALICE, id).unwrap_err();
+        assert!(matches!(err, ProposalError::NotSigner { .. }));
+
+        // a failed transfer leaves the proposal pending
+        let big = assert_pending(proposals.propose(&mut token, BOB, params(200, 10)).unwrap(), 1);
+        let err = proposals.confirm(&mut token, CAROL, big).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_INSUFFICIENT_FUNDS);
+        assert_eq!(proposals.proposal(big).unwrap().confirmations, [BOB.id().unwrap()]);
+
+        // once the signers are cleared, nobody can confirm
+        proposals.set_signers(TREASURY, &[], 0).unwrap();
+        assert_eq!(proposals.signers(TREASURY).unwrap(), None);
+        let err = proposals.confirm(&mut token, CAROL, id).unwrap_err();
+        assert!(matches!(err, ProposalError::NotSigner { .. }));
+
+        // amounts are checked when proposed
+        proposals.set_signers(TREASURY, &[*ALICE], 1).unwrap();
+        let mut negative = params(0, 10);
+        negative.amount = TokenAmount::from_atto(-1);
+        let err = proposals.propose(&mut token, ALICE, negative).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_ILLEGAL_ARGUMENT);
+        assert_eq!(token.balance_of(TREASURY).unwrap(), TokenAmount::from_atto(100));
+    }
+
+    #[test]
+    fn it_expires_and_cancels_proposals() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let (mut token_state, mut proposal_state) = setup(&runtime);
+        let mut token = Token::wrap(&runtime, 1, &mut token_state);
+        let mut proposals = Proposals::wrap(&runtime, &mut proposal_state);
+        proposals.set_signers(TREASURY, &[*ALICE, *BOB, *CAROL], 2).unwrap();
+        runtime.syscalls.set_curr_epoch(5);
+
+        let err = proposals.propose(&mut token, ALICE, params(60, 5)).unwrap_err();
+        assert!(matches!(err, ProposalError::InvalidExpiration { expiration: 5, epoch: 5 }));
+        let id = assert_pending(proposals.propose(&mut token, ALICE, params(60, 10)).unwrap(), 1);
+
+        // only the proposer or holder can cancel a live proposal
+        let err = proposals.cancel(BOB, id).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+        assert_eq!(proposals.cancel(TREASURY, id).unwrap().amount, TokenAmount::from_atto(60));
+        assert!(matches!(proposals.proposal(id).unwrap_err(), ProposalError::NotFound(0)));
+
+        // expired proposals can't be confirmed, and anyone can remove them
+        let id = assert_pending(proposals.propose(&mut token, ALICE, params(60, 10)).unwrap(), 1);
+        runtime.syscalls.set_curr_epoch(10);
+        let err = proposals.confirm(&mut token, BOB, id).unwrap_err();
+        assert!(matches!(err, ProposalError::Expired { id: 1, expiration: 10 }));
+        proposals.cancel(RECIPIENT, id).unwrap();
+        assert_eq!(token.balance_of(TREASURY).unwrap(), TokenAmount::from_atto(100));
+
+        // the state round-trips through the blockstore
+        let cid = proposals.flush().unwrap();
+        assert_eq!(&ProposalState::load(runtime.bs(), &cid).unwrap(), proposals.state());
+        assert_eq!(proposals.state().next_proposal, 2);
+    }
+}
@@ -0,0 +1,468 @@
+//! Transfers authorized off-chain by the owner and submitted by a relayer
+//!
+//! An owner without FIL to pay for gas can still move their tokens by signing a transfer
+//! authorization and handing it to a relayer, who submits it with
+//! [`Sponsored::transfer`] and pays for the message. The authorization may pay the relayer a fee
+//! out of the transferred amount, so the recipient receives the amount less the fee. It may also
+//! name the only relayer allowed to submit it, so a fee can't be claimed by whoever copies the
+//! authorization first.
+//!
+//! The owner signs the EIP-712 digest of a [`SponsoredTransferMessage`] in the
+//! [`domain`](Sponsored::domain) of this token, so an authorization can't be replayed against
+//! another token or on another network. The message includes the owner's current nonce so it can't
+//! be replayed against this token either, and the nonce is incremented each time an authorization
+//! is used. The owner must be given by the address of the key that signed it: a secp256k1 (f1), BLS
+//! (f3) or Ethereum-style (f410) address.
+//!
+//! Nonces are kept in a [`SponsoredState`] which an actor stores alongside its
+//! [`TokenState`](crate::token::state::TokenState).
+use cid::Cid;
+use fvm_actor_utils::abi::{encode_uint256, encode_uint64, AbiError};
+use fvm_actor_utils::crypto::{eip712_digest, keccak256, struct_hash, Digest, Eip712Domain};
+use fvm_actor_utils::messaging::MessagingError;
+use fvm_actor_utils::receiver::ReceiverHookBatch;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_actor_utils::transaction::Transactional;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::bigint::BigInt;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::ActorID;
+use num_traits::Zero;
+use thiserror::Error;
+
+use crate::extension::{
+    empty_map, Extension, ExtensionError, ExtensionResult, ExtensionState, Map,
+};
+use crate::token::state::{actor_id_key, DEFAULT_HAMT_BIT_WIDTH};
+use crate::token::types::TransferIntermediate;
+use crate::token::{validate_amount_with_granularity, Token, TokenError};
+
+/// Name of the EIP-712 domain in which sponsored transfers are signed
+pub const SPONSORED_TRANSFER_DOMAIN: &str = "frc46_token/sponsored_transfer";
+
+/// Version of the EIP-712 domain in which sponsored transfers are signed
+pub const SPONSORED_TRANSFER_VERSION: &str = "1";
+
+/// The EIP-712 type of a [`SponsoredTransferMessage`]
+pub const SPONSORED_TRANSFER_TYPE: &str = concat!(
+    "SponsoredTransfer(bytes owner,bytes to,uint256 amount,uint256 fee,bytes relayer,",
+    "uint256 nonce,uint256 deadline,bytes operatorData)"
+);
+
+#[derive(Error, Debug)]
+pub enum SponsoredError {
+    #[error("error in sponsored transfer state: {0}")]
+    State(#[from] ExtensionError),
+    #[error("error in token: {0}")]
+    Token(#[from] TokenError),
+    #[error("error calling other actor: {0}")]
+    Messaging(#[from] MessagingError),
+    #[error("transfer can't be encoded for signing: {0}")]
+    Abi(#[from] AbiError),
+    #[error("signature is not valid for this transfer")]
+    InvalidSignature,
+    #[error("authorization expired at epoch {deadline}, current epoch is {epoch}")]
+    Expired { deadline: ChainEpoch, epoch: ChainEpoch },
+    #[error("fee {fee} exceeds the transferred amount {amount}")]
+    FeeExceedsAmount { fee: TokenAmount, amount: TokenAmount },
+    #[error("{relayer} may not submit a transfer authorized for relayer {expected}")]
+    WrongRelayer { relayer: Address, expected: Address },
+}
+
+impl From<&SponsoredError> for ExitCode {
+    fn from(error: &SponsoredError) -> Self {
+        match error {
+            SponsoredError::State(e) => e.into(),
+            SponsoredError::Token(e) => e.into(),
+            SponsoredError::Messaging(e) => e.into(),
+            SponsoredError::Abi(_) | SponsoredError::FeeExceedsAmount { fee: _, amount: _ } => {
+                ExitCode::USR_ILLEGAL_ARGUMENT
+            }
+            SponsoredError::InvalidSignature
+            | SponsoredError::Expired { deadline: _, epoch: _ }
+            | SponsoredError::WrongRelayer { relayer: _, expected: _ } => ExitCode::USR_FORBIDDEN,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, SponsoredError>;
+
+/// A transfer authorized by the owner's signature
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct SponsoredTransferParams {
+    /// Signer of the authorization, which must be an f1, f3 or f410 address
+    pub owner: Address,
+    pub to: Address,
+    /// The amount taken from the owner, including the fee
+    pub amount: TokenAmount,
+    /// The part of the amount paid to the relayer, which may be zero
+    pub fee: TokenAmount,
+    /// The only relayer allowed to submit the transfer, or None to allow anyone
+    pub relayer: Option<Address>,
+    /// Last epoch at which the authorization may be used
+    pub deadline: ChainEpoch,
+    pub operator_data: RawBytes,
+    /// Signature by the owner over the digest of the transfer, see [`Sponsored::digest`]
+    pub signature: RawBytes,
+}
+
+/// The typed message signed by the owner to authorize a sponsored transfer
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct SponsoredTransferMessage {
+    pub owner: Address,
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub fee: TokenAmount,
+    pub relayer: Option<Address>,
+    pub nonce: u64,
+    pub deadline: ChainEpoch,
+    pub operator_data: RawBytes,
+}
+
+impl SponsoredTransferMessage {
+    /// Returns the EIP-712 hash of the message, of type [`SPONSORED_TRANSFER_TYPE`]
+    ///
+    /// Addresses are encoded as `bytes` holding their Filecoin encoding, since f1 and f3 owners
+    /// have no Ethereum address, and an absent relayer as empty bytes.
+    pub fn struct_hash(&self) -> std::result::Result<Digest, AbiError> {
+        let relayer = self.relayer.map(|relayer| relayer.to_bytes()).unwrap_or_default();
+        Ok(struct_hash(
+            SPONSORED_TRANSFER_TYPE,
+            &[
+                keccak256(&self.owner.to_bytes()),
+                keccak256(&self.to.to_bytes()),
+                encode_uint256(self.amount.atto())?,
+                encode_uint256(self.fee.atto())?,
+                keccak256(&relayer),
+                encode_uint64(self.nonce),
+                encode_uint256(&BigInt::from(self.deadline))?,
+                keccak256(self.operator_data.bytes()),
+            ],
+        ))
+    }
+}
+
+/// Sponsored transfer state IPLD structure
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct SponsoredState {
+    /// Map<ActorID, u64> of the next nonce of each owner that has made a sponsored transfer, as a
+    /// Hamt
+    pub nonces: Cid,
+    /// Bit-width to use when loading Hamts
+    hamt_bit_width: u32,
+}
+
+impl Transactional for SponsoredState {}
+
+impl ExtensionState for SponsoredState {
+    fn new<BS: Blockstore>(store: &BS) -> ExtensionResult<Self> {
+        Ok(Self { nonces: empty_map::<_, u64>(store)?, hamt_bit_width: DEFAULT_HAMT_BIT_WIDTH })
+    }
+
+    fn hamt_bit_width(&self) -> u32 {
+        self.hamt_bit_width
+    }
+}
+
+impl SponsoredState {
+    /// Get the nonce the next transfer authorized by an owner must use
+    pub fn get_nonce<BS: Blockstore>(&self, bs: &BS, owner: ActorID) -> ExtensionResult<u64> {
+        let map: Map<BS, u64> = self.load_map(&self.nonces, bs)?;
+        Ok(map.get(&actor_id_key(owner))?.copied().unwrap_or_default())
+    }
+
+    /// Set the nonce the next transfer authorized by an owner must use
+    pub fn set_nonce<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        owner: ActorID,
+        nonce: u64,
+    ) -> ExtensionResult<()> {
+        let mut map: Map<BS, u64> = self.load_map(&self.nonces, bs)?;
+        map.set(actor_id_key(owner), nonce)?;
+        self.nonces = map.flush()?;
+        Ok(())
+    }
+}
+
+/// Library functions managing sponsored transfers for a token
+pub type Sponsored<'st, S, BS> = Extension<'st, S, BS, SponsoredState>;
+
+impl<S, BS> Sponsored<'_, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Returns the nonce the next transfer authorized by an owner must use
+    pub fn nonce(&self, owner: &Address) -> Result<u64> {
+        match self.runtime.resolve_id(owner) {
+            Ok(owner) => Ok(self.state.get_nonce(self.runtime, owner)?),
+            Err(MessagingError::AddressNotResolved(_)) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the EIP-712 domain in which owners sign transfers of this token
+    pub fn domain(&self) -> Eip712Domain {
+        Eip712Domain {
+            name: SPONSORED_TRANSFER_DOMAIN.into(),
+            version: SPONSORED_TRANSFER_VERSION.into(),
+            chain_id: self.runtime.chain_id(),
+            verifying_contract: Address::new_id(self.runtime.actor_id()),
+        }
+    }
+
+    /// Returns the digest an owner signs to authorize the transfer described by `params`
+    ///
+    /// The signature in `params` is ignored.
+    pub fn digest(&self, params: &SponsoredTransferParams) -> Result<Digest> {
+        let message = self.message(params)?;
+        Ok(eip712_digest(&self.domain().separator()?, &message.struct_hash()?))
+    }
+
+    /// Returns the typed message whose digest an owner signs to authorize the transfer described
+    /// by `params`
+    ///
+    /// The signature in `params` is ignored.
+    pub fn message(&self, params: &SponsoredTransferParams) -> Result<SponsoredTransferMessage> {
+        Ok(SponsoredTransferMessage {
+            owner: params.owner,
+            to: params.to,
+            amount: params.amount.clone(),
+            fee: params.fee.clone(),
+            relayer: params.relayer,
+            nonce: self.nonce(&params.owner)?,
+            deadline: params.deadline,
+            operator_data: params.operator_data.clone(),
+        })
+    }
+
+    /// Makes a transfer authorized by the owner's signature, on behalf of the relayer submitting it
+    ///
+    /// The recipient receives the amount less the fee, and the relayer receives the fee. Both the
+    /// amount received and the fee must be valid transfer amounts for the token. Returns the
+    /// recipient's receiver hook, followed by the relayer's if the fee is non-zero. The hooks must
+    /// be called as for [`Token::transfer`], and the first one's return data can be passed to
+    /// [`Token::transfer_return`].
+    pub fn transfer(
+        &mut self,
+        token: &mut Token<'_, S, BS>,
+        relayer: &Address,
+        params: SponsoredTransferParams,
+    ) -> Result<ReceiverHookBatch<TransferIntermediate>> {
+        let epoch = self.runtime.curr_epoch();
+        if epoch > params.deadline {
+            return Err(SponsoredError::Expired { deadline: params.deadline, epoch });
+        }
+        if let Some(expected) = params.relayer {
+            if !self.runtime.same_address(relayer, &expected) {
+                return Err(SponsoredError::WrongRelayer { relayer: *relayer, expected });
+            }
+        }
+        if params.fee > params.amount {
+            return Err(SponsoredError::FeeExceedsAmount {
+                fee: params.fee,
+                amount: params.amount,
+            });
+        }
+        let granularity = token.granularity();
+        validate_amount_with_granularity(&params.fee, "fee", granularity)?;
+        let received = params.amount.clone() - params.fee.clone();
+        validate_amount_with_granularity(&received, "transfer", granularity)?;
+
+        let message = self.message(&params)?;
+        let digest = eip712_digest(&self.domain().separator()?, &message.struct_hash()?);
+        if !self.runtime.verify_digest(&params.owner, params.signature.bytes(), &digest)? {
+            return Err(SponsoredError::InvalidSignature);
+        }
+        // a valid signature means the owner's key exists, but its account may not yet
+        let owner = self.runtime.resolve_or_init(&params.owner)?;
+
+        let runtime = self.runtime;
+        // roll back the transfer to the recipient too if the fee can't be paid
+        let before = token.state().clone();
+        let mut hooks = ReceiverHookBatch::new();
+        let res: Result<()> = self.state.transaction(|state| {
+            state.set_nonce(runtime, owner, message.nonce + 1)?;
+            hooks.push(token.transfer(
+                &params.owner,
+                &params.to,
+                &received,
+                params.operator_data,
+                RawBytes::default(),
+            )?);
+            if !params.fee.is_zero() {
+                hooks.push(token.transfer(
+                    &params.owner,
+                    relayer,
+                    &params.fee,
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )?);
+            }
+            Ok(())
+        });
+        match res {
+            Ok(()) => Ok(hooks),
+            Err(e) => {
+                hooks.discard();
+                token.replace(before);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::syscalls::fake_syscalls::{FakeKey, FakeSyscalls};
+    use fvm_actor_utils::util::ActorRuntime;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::clock::ChainEpoch;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{Sponsored, SponsoredError, SponsoredState, SponsoredTransferParams};
+    use crate::extension::ExtensionState;
+    use crate::token::state::TokenState;
+    use crate::token::Token;
+
+    const TOKEN_ACTOR: &Address = &Address::new_id(1);
+    const RELAYER: &Address = &Address::new_id(2);
+    const RECIPIENT: &Address = &Address::new_id(3);
+    const OTHER_RELAYER: &Address = &Address::new_id(4);
+
+    fn owner() -> Address {
+        FakeKey::new(b"owner").secp_address()
+    }
+
+    fn setup(
+        runtime: &ActorRuntime<FakeSyscalls, MemoryBlockstore>,
+    ) -> (TokenState, SponsoredState) {
+        let mut token_state = TokenState::new(runtime.bs()).unwrap();
+        let mut token = Token::wrap(runtime, 1, &mut token_state);
+        let mut hook = token
+            .mint(
+                TOKEN_ACTOR,
+                &owner(),
+                &TokenAmount::from_atto(100),
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap();
+        token.flush().unwrap();
+        hook.call(runtime).unwrap();
+        (token_state, SponsoredState::new(runtime.bs()).unwrap())
+    }
+
+    fn params(amount: u64, fee: u64, deadline: ChainEpoch) -> SponsoredTransferParams {
+        SponsoredTransferParams {
+            owner: owner(),
+            to: *RECIPIENT,
+            amount: TokenAmount::from_atto(amount),
+            fee: TokenAmount::from_atto(fee),
+            relayer: None,
+            deadline,
+            operator_data: RawBytes::default(),
+            signature: RawBytes::default(),
+        }
+    }
+
+    fn sign(
+        sponsored: &Sponsored<FakeSyscalls, MemoryBlockstore>,
+        mut params: SponsoredTransferParams,
+    ) -> SponsoredTransferParams {
+        let digest = sponsored.digest(&params).unwrap();
+        params.signature = RawBytes::new(FakeKey::new(b"owner").sign_secp(&digest).to_vec());
+        params
+    }
+
+    #[test]
+    fn it_makes_transfers_authorized_by_the_owner() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let (mut token_state, mut sponsored_state) = setup(&runtime);
+        let mut token = Token::wrap(&runtime, 1, &mut token_state);
+        let mut sponsored = Sponsored::wrap(&runtime, &mut sponsored_state);
+        assert_eq!(sponsored.nonce(&owner()).unwrap(), 0);
+
+        let authorized = sign(&sponsored, params(60, 5, 10));
+        let mut hooks = sponsored.transfer(&mut token, RELAYER, authorized.clone()).unwrap();
+        assert_eq!(hooks.len(), 2);
+        token.flush().unwrap();
+        sponsored.flush().unwrap();
+        let mut results = hooks.call_all(&runtime).unwrap();
+        let ret = token.transfer_return(results.remove(0)).unwrap();
+        assert_eq!(ret.from_balance, TokenAmount::from_atto(40));
+        assert_eq!(ret.to_balance, TokenAmount::from_atto(55));
+        assert_eq!(token.balance_of(RELAYER).unwrap(), TokenAmount::from_atto(5));
+        assert_eq!(sponsored.nonce(&owner()).unwrap(), 1);
+
+        // the authorization can't be replayed
+        let err = sponsored.transfer(&mut token, RELAYER, authorized).unwrap_err();
+        assert!(matches!(err, SponsoredError::InvalidSignature));
+
+        // without a fee, only the recipient is notified
+        let authorized = sign(&sponsored, params(40, 0, 10));
+        let mut hooks = sponsored.transfer(&mut token, RELAYER, authorized).unwrap();
+        assert_eq!(hooks.len(), 1);
+        hooks.call_all(&runtime).unwrap();
+        assert_eq!(token.balance_of(&owner()).unwrap(), TokenAmount::from_atto(0));
+        assert_eq!(token.balance_of(RECIPIENT).unwrap(), TokenAmount::from_atto(95));
+        assert_eq!(sponsored.nonce(&owner()).unwrap(), 2);
+
+        // the state round-trips through the blockstore
+        let cid = sponsored.flush().unwrap();
+        assert_eq!(&SponsoredState::load(runtime.bs(), &cid).unwrap(), sponsored.state());
+    }
+
+    #[test]
+    fn it_rejects_invalid_authorizations() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let (mut token_state, mut sponsored_state) = setup(&runtime);
+        let mut token = Token::wrap(&runtime, 1, &mut token_state);
+        let mut sponsored = Sponsored::wrap(&runtime, &mut sponsored_state);
+        runtime.syscalls.set_curr_epoch(5);
+
+        // tampering with a signed authorization invalidates it
+        let mut tampered = sign(&sponsored, params(60, 5, 10));
+        tampered.fee = TokenAmount::from_atto(50);
+        let err = sponsored.transfer(&mut token, RELAYER, tampered).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+
+        let expired = sign(&sponsored, params(60, 5, 4));
+        let err = sponsored.transfer(&mut token, RELAYER, expired).unwrap_err();
+        assert!(matches!(err, SponsoredError::Expired { deadline: 4, epoch: 5 }));
+
+        let excessive = sign(&sponsored, params(60, 61, 10));
+        let err = sponsored.transfer(&mut token, RELAYER, excessive).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        // an authorization naming a relayer can only be submitted by that relayer
+        let mut bound = params(60, 5, 10);
+        bound.relayer = Some(*RELAYER);
+        let bound = sign(&sponsored, bound);
+        let err = sponsored.transfer(&mut token, OTHER_RELAYER, bound.clone()).unwrap_err();
+        assert!(matches!(err, SponsoredError::WrongRelayer { .. }));
+
+        // if the fee can't be paid, the transfer to the recipient is rolled back too and the
+        // nonce is left unused
+        let unaffordable = sign(&sponsored, params(105, 10, 10));
+        let err = sponsored.transfer(&mut token, RELAYER, unaffordable).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_INSUFFICIENT_FUNDS);
+        assert_eq!(sponsored.nonce(&owner()).unwrap(), 0);
+        assert_eq!(token.balance_of(&owner()).unwrap(), TokenAmount::from_atto(100));
+        assert_eq!(token.balance_of(RECIPIENT).unwrap(), TokenAmount::from_atto(0));
+
+        let mut hooks = sponsored.transfer(&mut token, RELAYER, bound).unwrap();
+        hooks.call_all(&runtime).unwrap();
+        assert_eq!(token.balance_of(&owner()).unwrap(), TokenAmount::from_atto(40));
+        assert_eq!(token.balance_of(RELAYER).unwrap(), TokenAmount::from_atto(5));
+    }
+}
@@ -1,5 +1,5 @@
 use fvm_actor_utils::messaging::MessagingError;
-use fvm_actor_utils::receiver::ReceiverHookError;
+use fvm_actor_utils::receiver::{ReceiverAbort, ReceiverHookError};
 use fvm_ipld_encoding::Error as SerializationError;
 use fvm_shared::address::{Address, Error as AddressError};
 use fvm_shared::econ::TokenAmount;
@@ -35,6 +35,16 @@ pub enum TokenError {
     StateInvariant(#[from] StateInvariantError),
 }
 
+impl TokenError {
+    /// Returns the details of the abort if a receiver hook rejected the tokens
+    pub fn receiver_abort(&self) -> Option<&ReceiverAbort> {
+        match self {
+            TokenError::ReceiverHook(e) => e.abort(),
+            _ => None,
+        }
+    }
+}
+
 impl From<&TokenError> for ExitCode {
     fn from(error: &TokenError) -> Self {
         match error {
@@ -54,6 +64,7 @@ impl From<&TokenError> for ExitCode {
 #[cfg(test)]
 mod test {
     use fvm_actor_utils::{messaging::MessagingError, receiver::ReceiverHookError};
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
     use fvm_ipld_encoding::{CodecProtocol, Error as SerializationError};
     use fvm_shared::{
         address::{Address, Error as AddressError},
@@ -142,5 +153,17 @@ mod test {
             err.to_string(),
             String::from("receiver hook error: receiver hook was not called")
         );
+        assert!(err.receiver_abort().is_none());
+
+        let err = TokenError::ReceiverHook(ReceiverHookError::new_receiver_error(
+            Address::new_id(1),
+            ExitCode::USR_FORBIDDEN,
+            IpldBlock::serialize_cbor(&"rejected").unwrap(),
+        ));
+        // error code comes from the receiver
+        assert_eq!(ExitCode::USR_FORBIDDEN, ExitCode::from(&err));
+        let abort = err.receiver_abort().unwrap();
+        assert_eq!(abort.address, Address::new_id(1));
+        assert_eq!(abort.message.as_deref(), Some("rejected"));
     }
 }
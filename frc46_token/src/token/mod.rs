@@ -2,9 +2,11 @@ use std::ops::Neg;
 
 use cid::Cid;
 pub use error::TokenError;
+use fvm_actor_utils::blockstore::BufferedBlockstore;
 use fvm_actor_utils::messaging::{MessagingError, RECEIVER_HOOK_METHOD_NUM};
 use fvm_actor_utils::receiver::{ReceiverHook, ReceiverHookError};
 use fvm_actor_utils::syscalls::Syscalls;
+use fvm_actor_utils::transaction::Transactional;
 use fvm_actor_utils::util::ActorRuntime;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
@@ -26,6 +28,10 @@ use crate::token::TokenError::InvalidGranularity;
 
 mod error;
 pub mod state;
+#[cfg(test)]
+mod state_fuzz;
+#[cfg(test)]
+mod state_golden;
 pub mod types;
 
 /// Ratio of integral units to interpretation as standard token units, as given by FRC-0046.
@@ -119,16 +125,19 @@ where
     /// modifications to the state tree.
     ///
     /// If the closure returns an error, the transaction is dropped atomically and no change is
-    /// observed on token state.
+    /// observed on token state. Blocks written by the closure are buffered and only those reachable
+    /// from the final state are written to the blockstore.
     fn transaction<F, Res>(&mut self, f: F) -> Result<Res>
     where
-        F: FnOnce(&mut TokenState, &ActorRuntime<S, BS>) -> Result<Res>,
+        F: FnOnce(&mut TokenState, &BufferedBlockstore<&ActorRuntime<S, BS>>) -> Result<Res>,
     {
-        let mut mutable_state = self.state.clone();
-        let res = f(&mut mutable_state, self.runtime)?;
-        // if closure didn't error, save state
-        *self.state = mutable_state;
-        Ok(res)
+        let bs = BufferedBlockstore::new(self.runtime);
+        self.state.transaction(|state| {
+            let res = f(state, &bs)?;
+            // if closure didn't error, write out the blocks the new state refers to
+            bs.flush_links(state).map_err(|err| TokenStateError::Serialization(err.to_string()))?;
+            Ok(res)
+        })
     }
 }
 
@@ -702,15 +711,16 @@ pub fn validate_allowance<'a>(a: &'a TokenAmount, name: &'static str) -> Result<
 mod test {
     use std::ops::Neg;
 
+    use fvm_actor_utils::instrumented_blockstore::InstrumentedBlockstore;
     use fvm_actor_utils::messaging::{MessagingError, RECEIVER_HOOK_METHOD_NUM};
     use fvm_actor_utils::receiver::{ReceiverHookError, UniversalReceiverParams};
     use fvm_actor_utils::syscalls::fake_syscalls::FakeSyscalls;
     use fvm_actor_utils::util::ActorRuntime;
     use fvm_ipld_blockstore::MemoryBlockstore;
     use fvm_ipld_encoding::RawBytes;
-    use fvm_sdk::sys::ErrorNumber;
     use fvm_shared::address::{Address, BLS_PUB_LEN};
     use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ErrorNumber;
     use num_traits::Zero;
 
     use crate::receiver::{FRC46TokenReceived, FRC46_TOKEN_TYPE};
@@ -731,6 +741,11 @@ mod test {
         Address::new_bls(key.as_slice()).unwrap()
     }
 
+    /// Returns a static delegated address in the EAM namespace, as used by EVM accounts
+    fn evm_address() -> Address {
+        Address::new_delegated(10, &[0xab; 20]).unwrap()
+    }
+
     // Returns a new Actor address, that is uninitializable by the FakeMessenger
     fn actor_address() -> Address {
         Address::new_actor(Default::default())
@@ -1313,6 +1328,49 @@ mod test {
         token.assert_invariants().unwrap();
     }
 
+    #[test]
+    fn it_bounds_blocks_touched_by_transfers() {
+        let helper = ActorRuntime::new(
+            FakeSyscalls::default(),
+            InstrumentedBlockstore::new(MemoryBlockstore::default()),
+        );
+        let mut token_state =
+            Token::<FakeSyscalls, InstrumentedBlockstore<MemoryBlockstore>>::create_state(
+                helper.bs(),
+            )
+            .unwrap();
+        let mut token = Token::wrap(&helper, 1, &mut token_state);
+
+        let mut hook = token
+            .mint(
+                TOKEN_ACTOR,
+                ALICE,
+                &TokenAmount::from_atto(100),
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap();
+        token.flush().unwrap();
+        hook.call(token.runtime).unwrap();
+
+        // the balance map and the state root are rewritten, and nothing else
+        let (_, stats) = helper.blockstore.measure(|| {
+            let mut hook = token
+                .transfer(
+                    ALICE,
+                    BOB,
+                    &TokenAmount::from_atto(60),
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap();
+            token.flush().unwrap();
+            hook.call(token.runtime).unwrap();
+        });
+        assert!(stats.writes <= 2, "{stats:?}");
+        assert!(stats.unique_reads <= 2, "{stats:?}");
+    }
+
     #[test]
     fn it_transfers() {
         let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
@@ -1538,6 +1596,24 @@ mod test {
                 token_data: Default::default(),
             },
         );
+
+        // transfer to an uninitialized EVM address creates a placeholder for it
+        let evm_address = &evm_address();
+        let mut hook = token
+            .transfer(
+                ALICE,
+                evm_address,
+                &TokenAmount::from_atto(10),
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap();
+        token.flush().unwrap();
+        hook.call(token.runtime).unwrap();
+        assert_eq!(token.balance_of(ALICE).unwrap(), TokenAmount::from_atto(80));
+        assert_eq!(token.balance_of(evm_address).unwrap(), TokenAmount::from_atto(10));
+        let evm_id = token.runtime.resolve_id(evm_address).unwrap();
+        assert_eq!(token.runtime.display_address(evm_id), *evm_address);
     }
 
     #[test]
@@ -3,6 +3,8 @@ use std::ops::Neg;
 
 use cid::multihash::Code;
 use cid::Cid;
+use fvm_actor_utils::inspect::{KeyFormat, Layout};
+use fvm_actor_utils::transaction::Transactional;
 use fvm_ipld_blockstore::Block;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::tuple::*;
@@ -19,7 +21,8 @@ use integer_encoding::VarInt;
 use thiserror::Error;
 
 /// This value has been chosen to optimise to reduce gas-costs when accessing the balances map. Non-
-/// standard use cases of the token library might find a different value to be more efficient.
+/// standard use cases of the token library might find a different value to be more efficient. The
+/// `state_layout` benchmarks compare the blocks touched by transfers and mints across bit widths.
 pub const DEFAULT_HAMT_BIT_WIDTH: u32 = 3;
 
 #[derive(Error, Debug)]
@@ -119,6 +122,8 @@ pub struct TokenState {
     hamt_bit_width: u32,
 }
 
+impl Transactional for TokenState {}
+
 /// An abstraction over the IPLD layer to get and modify token state without dealing with HAMTs etc.
 ///
 /// This is a simple wrapper of state and in general does not account for token protocol level
@@ -175,6 +180,17 @@ impl TokenState {
         Ok(cid)
     }
 
+    /// Describes the encoding of the state for rendering with [`fvm_actor_utils::inspect`]
+    pub fn layout() -> Layout {
+        let amount_by_actor = || Layout::hamt(KeyFormat::ActorId, Layout::BigInt);
+        Layout::fields([
+            ("supply", Layout::BigInt),
+            ("balances", amount_by_actor()),
+            ("allowances", Layout::hamt(KeyFormat::ActorId, amount_by_actor())),
+            ("hamt_bit_width", Layout::Cbor),
+        ])
+    }
+
     /// Get the balance of an ActorID from the currently stored state
     pub fn get_balance<BS: Blockstore>(&self, bs: &BS, owner: ActorID) -> Result<TokenAmount> {
         let balances = self.get_balance_map(bs)?;
@@ -786,10 +802,12 @@ pub struct StateSummary {
 mod test {
     use cid::multihash::Code;
     use cid::Cid;
+    use fvm_actor_utils::inspect::inspect;
     use fvm_ipld_blockstore::{Block, Blockstore, MemoryBlockstore};
     use fvm_ipld_encoding::DAG_CBOR;
     use fvm_shared::econ::TokenAmount;
     use fvm_shared::{bigint::Zero, ActorID};
+    use serde_json::json;
 
     use super::TokenState;
     use crate::token::state::{actor_id_key, OwnerAllowanceMap, StateError, StateInvariantError};
@@ -803,6 +821,26 @@ mod test {
         assert_eq!(state, saved_state);
     }
 
+    #[test]
+    fn it_renders_state_with_layout() {
+        let bs = &MemoryBlockstore::new();
+        let mut state = TokenState::new(bs).unwrap();
+        state.change_balance_by(bs, 1, &TokenAmount::from_atto(100)).unwrap();
+        state.change_supply_by(&TokenAmount::from_atto(100)).unwrap();
+        state.set_allowance(bs, 1, 2, &TokenAmount::from_atto(40)).unwrap();
+        let cid = state.save(bs).unwrap();
+
+        assert_eq!(
+            inspect(bs, &cid, &TokenState::layout()).unwrap(),
+            json!({
+                "supply": "100",
+                "balances": { "1": "100" },
+                "allowances": { "1": { "2": "40" } },
+                "hamt_bit_width": 3,
+            })
+        );
+    }
+
     #[test]
     fn it_handles_missing_data_load() {
         // try to load from an empty blockstore (and default Cid)
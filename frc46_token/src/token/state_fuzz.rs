@@ -0,0 +1,212 @@
+//! Property-based tests of `TokenState` against a model implementation
+//!
+//! Random sequences of operations are applied to both a `TokenState` and a simple in-memory model
+//! of balances and allowances. After every step, the operation must have succeeded or failed in
+//! both, the state must pass `check_invariants`, and its balances, allowances and supply must match
+//! the model.
+use std::collections::{BTreeMap, HashMap};
+
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_shared::{econ::TokenAmount, ActorID};
+use proptest::prelude::*;
+
+use super::state::{StateError, TokenState};
+
+/// Accounts are drawn from a small range so that operations often touch the same accounts
+const ACCOUNTS: ActorID = 4;
+/// Amounts are drawn from a small range so that balances and allowances are often exhausted
+const MAX_AMOUNT: i64 = 100;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Mint { to: ActorID, amount: i64 },
+    Burn { owner: ActorID, amount: i64 },
+    Transfer { from: ActorID, to: ActorID, amount: i64 },
+    TransferFrom { operator: ActorID, from: ActorID, to: ActorID, amount: i64 },
+    ChangeAllowance { owner: ActorID, operator: ActorID, delta: i64 },
+    SetAllowance { owner: ActorID, operator: ActorID, amount: i64 },
+    RevokeAllowance { owner: ActorID, operator: ActorID },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let account = || 0..ACCOUNTS;
+    let amount = || 0..=MAX_AMOUNT;
+    // allowances are never stored for an owner approving itself
+    let owner_operator = || {
+        (account(), account())
+            .prop_filter("owner is its own operator", |(owner, operator)| owner != operator)
+    };
+    prop_oneof![
+        (account(), amount()).prop_map(|(to, amount)| Op::Mint { to, amount }),
+        (account(), amount()).prop_map(|(owner, amount)| Op::Burn { owner, amount }),
+        (account(), account(), amount()).prop_map(|(from, to, amount)| Op::Transfer {
+            from,
+            to,
+            amount
+        }),
+        (account(), account(), account(), amount()).prop_map(|(operator, from, to, amount)| {
+            Op::TransferFrom { operator, from, to, amount }
+        }),
+        (owner_operator(), -MAX_AMOUNT..=MAX_AMOUNT).prop_map(|((owner, operator), delta)| {
+            Op::ChangeAllowance { owner, operator, delta }
+        }),
+        (owner_operator(), amount()).prop_map(|((owner, operator), amount)| {
+            Op::SetAllowance { owner, operator, amount }
+        }),
+        owner_operator().prop_map(|(owner, operator)| Op::RevokeAllowance { owner, operator }),
+    ]
+}
+
+/// Reference implementation of the token state, omitting zero balances and allowances
+#[derive(Default)]
+struct Model {
+    supply: i64,
+    balances: BTreeMap<ActorID, i64>,
+    allowances: BTreeMap<(ActorID, ActorID), i64>,
+}
+
+impl Model {
+    fn balance(&self, owner: ActorID) -> i64 {
+        self.balances.get(&owner).copied().unwrap_or_default()
+    }
+
+    fn allowance(&self, owner: ActorID, operator: ActorID) -> i64 {
+        self.allowances.get(&(owner, operator)).copied().unwrap_or_default()
+    }
+
+    fn set_balance(&mut self, owner: ActorID, balance: i64) {
+        match balance {
+            0 => self.balances.remove(&owner),
+            _ => self.balances.insert(owner, balance),
+        };
+    }
+
+    fn set_allowance(&mut self, owner: ActorID, operator: ActorID, allowance: i64) {
+        match allowance {
+            0 => self.allowances.remove(&(owner, operator)),
+            _ => self.allowances.insert((owner, operator), allowance),
+        };
+    }
+
+    fn transfer(&mut self, from: ActorID, to: ActorID, amount: i64) -> bool {
+        if self.balance(from) < amount {
+            return false;
+        }
+        self.set_balance(from, self.balance(from) - amount);
+        self.set_balance(to, self.balance(to) + amount);
+        true
+    }
+
+    /// Applies an operation, returning whether it succeeded
+    fn apply(&mut self, op: &Op) -> bool {
+        match *op {
+            Op::Mint { to, amount } => {
+                self.set_balance(to, self.balance(to) + amount);
+                self.supply += amount;
+                true
+            }
+            Op::Burn { owner, amount } => {
+                if self.balance(owner) < amount {
+                    return false;
+                }
+                self.set_balance(owner, self.balance(owner) - amount);
+                self.supply -= amount;
+                true
+            }
+            Op::Transfer { from, to, amount } => self.transfer(from, to, amount),
+            Op::TransferFrom { operator, from, to, amount } => {
+                let allowance = self.allowance(from, operator);
+                if (allowance == 0 && operator != from) || allowance < amount {
+                    return false;
+                }
+                if self.balance(from) < amount {
+                    return false;
+                }
+                self.set_allowance(from, operator, allowance - amount);
+                self.transfer(from, to, amount)
+            }
+            Op::ChangeAllowance { owner, operator, delta } => {
+                let allowance = (self.allowance(owner, operator) + delta).max(0);
+                self.set_allowance(owner, operator, allowance);
+                true
+            }
+            Op::SetAllowance { owner, operator, amount } => {
+                self.set_allowance(owner, operator, amount);
+                true
+            }
+            Op::RevokeAllowance { owner, operator } => {
+                self.set_allowance(owner, operator, 0);
+                true
+            }
+        }
+    }
+}
+
+/// Applies an operation the way `Token` does, returning whether it succeeded
+///
+/// Operations that fail leave the state unchanged, as `Token` runs each one in a transaction.
+fn apply(state: &mut TokenState, bs: &MemoryBlockstore, op: &Op) -> bool {
+    let atto = |amount: i64| TokenAmount::from_atto(amount);
+    let mut new_state = state.clone();
+    let res: Result<(), StateError> = match *op {
+        Op::Mint { to, amount } => new_state
+            .change_balance_by(bs, to, &atto(amount))
+            .and_then(|_| new_state.change_supply_by(&atto(amount)).map(|_| ())),
+        Op::Burn { owner, amount } => new_state
+            .change_balance_by(bs, owner, &atto(-amount))
+            .and_then(|_| new_state.change_supply_by(&atto(-amount)).map(|_| ())),
+        Op::Transfer { from, to, amount } => new_state.make_transfer(bs, from, to, &atto(amount)),
+        Op::TransferFrom { operator, from, to, amount } => new_state
+            .attempt_use_allowance(bs, operator, from, &atto(amount))
+            .and_then(|_| new_state.make_transfer(bs, from, to, &atto(amount))),
+        Op::ChangeAllowance { owner, operator, delta } => {
+            new_state.change_allowance_by(bs, owner, operator, &atto(delta)).map(|_| ())
+        }
+        Op::SetAllowance { owner, operator, amount } => {
+            new_state.set_allowance(bs, owner, operator, &atto(amount)).map(|_| ())
+        }
+        Op::RevokeAllowance { owner, operator } => {
+            new_state.revoke_allowance(bs, owner, operator).map(|_| ())
+        }
+    };
+    if res.is_ok() {
+        *state = new_state;
+    }
+    res.is_ok()
+}
+
+/// Checks the state's invariants and compares it with the model
+fn check_state(state: &TokenState, bs: &MemoryBlockstore, model: &Model) {
+    let (summary, errors) = state.check_invariants(bs, 1);
+    assert!(errors.is_empty(), "invariants violated: {errors:?}");
+    assert_eq!(summary.total_supply, TokenAmount::from_atto(model.supply));
+
+    let balances: HashMap<_, _> =
+        model.balances.iter().map(|(&owner, &b)| (owner, TokenAmount::from_atto(b))).collect();
+    assert_eq!(summary.balance_map.unwrap(), balances);
+
+    let mut allowances: HashMap<ActorID, HashMap<ActorID, TokenAmount>> = HashMap::new();
+    for (&(owner, operator), &allowance) in &model.allowances {
+        allowances.entry(owner).or_default().insert(operator, TokenAmount::from_atto(allowance));
+    }
+    assert_eq!(summary.allowance_map.unwrap(), allowances);
+
+    // the state survives a round trip through the blockstore
+    let cid = state.save(bs).unwrap();
+    assert_eq!(&TokenState::load(bs, &cid).unwrap(), state);
+}
+
+proptest! {
+    #[test]
+    fn it_matches_the_model(ops in prop::collection::vec(op(), 1..64), bit_width in 1u32..=5) {
+        let bs = MemoryBlockstore::default();
+        let mut state = TokenState::new_with_bit_width(&bs, bit_width).unwrap();
+        let mut model = Model::default();
+
+        for op in &ops {
+            let succeeded = apply(&mut state, &bs, op);
+            prop_assert_eq!(succeeded, model.apply(op), "outcome of {:?} differs", op);
+            check_state(&state, &bs, &model);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Golden tests of the serialized layout of `TokenState`
+//!
+//! Deployed token actors store their state in this layout, so a change to the CBOR bytes or root
+//! CID of these fixtures means existing state can no longer be read. If the change is intended, it
+//! needs a state migration before the golden values here are updated.
+use cid::Cid;
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_shared::{econ::TokenAmount, ActorID};
+
+use super::state::TokenState;
+
+const ALICE: ActorID = 1;
+const BOB: ActorID = 2;
+const CAROL: ActorID = 3;
+
+const EMPTY_CBOR: &str = concat!(
+    "8440d82a5827000171a0e4022018fe6acc61a3a36b0c373c4a3a8ea64b812bf2ca9b528050909c78",
+    "d408558a0cd82a5827000171a0e4022018fe6acc61a3a36b0c373c4a3a8ea64b812bf2ca9b528050",
+    "909c78d408558a0c03",
+);
+const EMPTY_CID: &str = "bafy2bzacec3xvj4crjry6mv3yuwst3zyfzwxroq4tdqwnxud7ql27i4radusu";
+
+const POPULATED_CBOR: &str = concat!(
+    "84430005dcd82a5827000171a0e4022090e0ed7a25dfd4f51056be42ba2caa0852f70885c0fb1a9c",
+    "01b3e17f563353f6d82a5827000171a0e4022005aa1703bc882af66e29f3301fcf376db99c138800",
+    "645d015287847a75cc01e103",
+);
+const POPULATED_CID: &str = "bafy2bzacedwycdxpe67j7siwwn55vapbf3h36xy654ixffy5amajshoy2ph72";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Checks the state serializes to the golden bytes and root CID, and deserializes from them
+fn assert_golden(state: &TokenState, bs: &MemoryBlockstore, cbor: &str, cid: &str) {
+    let bytes = fvm_ipld_encoding::to_vec(state).unwrap();
+    assert_eq!(to_hex(&bytes), cbor, "serialized state layout changed");
+    assert_eq!(state.save(bs).unwrap(), Cid::try_from(cid).unwrap(), "state root changed");
+    assert_eq!(&fvm_ipld_encoding::from_slice::<TokenState>(&bytes).unwrap(), state);
+}
+
+#[test]
+fn empty_state_matches_golden() {
+    let bs = MemoryBlockstore::default();
+    let state = TokenState::new(&bs).unwrap();
+    assert_golden(&state, &bs, EMPTY_CBOR, EMPTY_CID);
+}
+
+#[test]
+fn populated_state_matches_golden() {
+    let bs = MemoryBlockstore::default();
+    let mut state = TokenState::new(&bs).unwrap();
+    state.change_balance_by(&bs, ALICE, &TokenAmount::from_atto(1_000)).unwrap();
+    state.change_balance_by(&bs, BOB, &TokenAmount::from_atto(500)).unwrap();
+    state.change_supply_by(&TokenAmount::from_atto(1_500)).unwrap();
+    state.set_allowance(&bs, ALICE, BOB, &TokenAmount::from_atto(250)).unwrap();
+    state.set_allowance(&bs, BOB, CAROL, &TokenAmount::from_atto(50)).unwrap();
+
+    assert_golden(&state, &bs, POPULATED_CBOR, POPULATED_CID);
+}
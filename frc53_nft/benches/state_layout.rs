@@ -0,0 +1,127 @@
+//! Benchmarks of NFT operations across AMT bit widths
+//!
+//! Each operation runs against a collection with many tokens spread across several owners, once per
+//! bit width of the token data AMT. Criterion measures the time taken, while the blocks read and
+//! written by a single run are printed before each benchmark, as they dominate the gas cost of an
+//! operation on chain. A change to `DEFAULT_AMT_BIT_WIDTH` should be backed by these results.
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use frc53_nft::state::NFTState;
+use frc53_nft::types::TokenID;
+use frc53_nft::NFT;
+use fvm_actor_utils::instrumented_blockstore::InstrumentedBlockstore;
+use fvm_actor_utils::syscalls::fake_syscalls::FakeSyscalls;
+use fvm_actor_utils::util::ActorRuntime;
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::{address::Address, ActorID};
+
+type Runtime<'bs> = ActorRuntime<FakeSyscalls, &'bs InstrumentedBlockstore<MemoryBlockstore>>;
+
+const BIT_WIDTHS: [u32; 5] = [3, 4, 5, 6, 7];
+/// Number of accounts holding tokens before each operation
+const OWNERS: u64 = 10;
+/// Number of tokens minted or transferred at once, and held by each owner
+const BATCH_SIZE: u64 = 100;
+const MINTER: ActorID = 10;
+const FIRST_OWNER: ActorID = 1_000;
+
+#[derive(Clone, Copy)]
+enum Operation {
+    BatchMint,
+    BatchTransfer,
+}
+
+impl Operation {
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::BatchMint => "batch_mint",
+            Operation::BatchTransfer => "batch_transfer",
+        }
+    }
+}
+
+fn id(actor: ActorID) -> Address {
+    Address::new_id(actor)
+}
+
+fn mint(runtime: &Runtime, state: &mut NFTState, to: ActorID) {
+    let mut nft = NFT::wrap(runtime.clone(), state);
+    let metadata = vec![String::from("ipfs://"); BATCH_SIZE as usize];
+    let mut hook =
+        nft.mint(&id(MINTER), &id(to), metadata, RawBytes::default(), RawBytes::default()).unwrap();
+    hook.call(runtime).unwrap();
+    nft.flush().unwrap();
+}
+
+/// Creates a collection where each owner holds a batch of tokens
+fn populated_state(runtime: &Runtime, bit_width: u32) -> NFTState {
+    let mut state = NFTState::new_with_bit_width(runtime.bs(), bit_width).unwrap();
+    for owner in FIRST_OWNER..FIRST_OWNER + OWNERS {
+        mint(runtime, &mut state, owner);
+    }
+    state
+}
+
+/// Runs an operation and flushes the state, so that the blocks it changed are written
+fn run(runtime: &Runtime, state: &mut NFTState, operation: Operation) {
+    match operation {
+        Operation::BatchMint => mint(runtime, state, FIRST_OWNER + OWNERS),
+        Operation::BatchTransfer => {
+            // the first owner holds the first batch of tokens
+            let token_ids: Vec<TokenID> = (0..BATCH_SIZE).collect();
+            let mut nft = NFT::wrap(runtime.clone(), state);
+            let mut hook = nft
+                .transfer(
+                    &id(FIRST_OWNER),
+                    &id(FIRST_OWNER + 1),
+                    &token_ids,
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap();
+            hook.call(runtime).unwrap();
+            nft.flush().unwrap();
+        }
+    }
+}
+
+fn state_layout(c: &mut Criterion) {
+    let stores: Vec<_> = BIT_WIDTHS
+        .iter()
+        .map(|_| InstrumentedBlockstore::new(MemoryBlockstore::default()))
+        .collect();
+    let collections: Vec<_> = BIT_WIDTHS
+        .iter()
+        .zip(&stores)
+        .map(|(&bit_width, bs)| {
+            let runtime = Runtime::new(FakeSyscalls::default(), bs);
+            let state = populated_state(&runtime, bit_width);
+            (bit_width, runtime, state)
+        })
+        .collect();
+
+    for operation in [Operation::BatchMint, Operation::BatchTransfer] {
+        let mut group = c.benchmark_group(operation.name());
+        for (bit_width, runtime, state) in &collections {
+            let (_, stats) = runtime.bs().measure(|| run(runtime, &mut state.clone(), operation));
+            println!(
+                "{}/{bit_width}: {} reads, {} writes, {} bytes written",
+                operation.name(),
+                stats.reads,
+                stats.writes,
+                stats.bytes_written
+            );
+            group.bench_with_input(BenchmarkId::from_parameter(bit_width), state, |b, state| {
+                b.iter_batched_ref(
+                    || state.clone(),
+                    |state| run(runtime, state, operation),
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, state_layout);
+criterion_main!(benches);
@@ -0,0 +1,205 @@
+//! Generic dispatch of the standard FRC-0053 methods
+//!
+//! Actors built on the `FRC53NFT` trait can forward any method they don't handle themselves to
+//! `frc53_invoke`, leaving only construction, minting and other non-standard methods to implement.
+use frc42_dispatch::match_method;
+use fvm_ipld_encoding::DAG_CBOR;
+use fvm_sdk::{self as sdk, NO_DATA_BLOCK_ID};
+use fvm_shared::error::ExitCode;
+use serde::{de::DeserializeOwned, ser::Serialize};
+
+use crate::types::FRC53NFT;
+
+/// Generic invoke for FRC53 NFT methods
+/// Given a method number and parameter block id, invokes the appropriate method on the FRC53NFT interface
+///
+/// The flush_state function passed into this must flush current state to the blockstore and update the root cid
+/// This is called after operations which mutate the state, such as approving an operator or burning NFTs.
+///
+/// Transfer and TransferFrom operations invoke the receiver hook which will require flushing state before calling the hook
+/// This must be done inside the FRC53NFT::transfer/transfer_from functions
+///
+/// Possible returns:
+/// - Ok(None) - method not found
+/// - Ok(Some(u32)) - block id of results saved to blockstore (or NO_DATA_BLOCK_ID if there is no result to return)
+/// - Err(error) - any error encountered during operation
+///
+pub fn frc53_invoke<T, F, E>(
+    method_num: u64,
+    params: u32,
+    nft: &mut T,
+    flush_state: F,
+) -> Result<Option<u32>, E>
+where
+    T: FRC53NFT<NFTError = E>,
+    F: FnOnce(&mut T) -> Result<(), E>,
+{
+    match_method!(method_num, {
+        "Name" => {
+            Ok(frc53_return_block(&nft.name()))
+        }
+        "Symbol" => {
+            Ok(frc53_return_block(&nft.symbol()))
+        }
+        "CollectionMetadata" => {
+            Ok(frc53_return_block(&nft.collection_metadata()))
+        }
+        "TotalSupply" => {
+            Ok(frc53_return_block(&nft.total_supply()))
+        }
+        "BalanceOf" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.balance_of(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "OwnerOf" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.owner_of(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "Metadata" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.metadata(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "GetApproved" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.get_approved(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "IsApprovedForAll" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.is_approved_for_all(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "Approve" => {
+            let params = frc53_unpack_params(params);
+            nft.approve(params)?;
+            flush_state(nft)?;
+            Ok(Some(NO_DATA_BLOCK_ID))
+        }
+        "Revoke" => {
+            let params = frc53_unpack_params(params);
+            nft.revoke(params)?;
+            flush_state(nft)?;
+            Ok(Some(NO_DATA_BLOCK_ID))
+        }
+        "ApproveForAll" => {
+            let params = frc53_unpack_params(params);
+            nft.approve_for_all(params)?;
+            flush_state(nft)?;
+            Ok(Some(NO_DATA_BLOCK_ID))
+        }
+        "RevokeForAll" => {
+            let params = frc53_unpack_params(params);
+            nft.revoke_for_all(params)?;
+            flush_state(nft)?;
+            Ok(Some(NO_DATA_BLOCK_ID))
+        }
+        "Burn" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.burn(params)?;
+            flush_state(nft)?;
+            Ok(frc53_return_block(&res))
+        }
+        "BurnFrom" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.burn_from(params)?;
+            flush_state(nft)?;
+            Ok(frc53_return_block(&res))
+        }
+        "TransferFrom" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.transfer_from(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "Transfer" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.transfer(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "ListTokens" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.list_tokens(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "ListOwnedTokens" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.list_owned_tokens(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "ListTokenOperators" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.list_token_operators(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "ListOperatorTokens" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.list_operator_tokens(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        "ListAccountOperators" => {
+            let params = frc53_unpack_params(params);
+            let res = nft.list_account_operators(params)?;
+            Ok(frc53_return_block(&res))
+        }
+        _ => {
+            // no method found - it's not considered an error here, but an upstream caller may choose to treat it as one
+            Ok(None)
+        }
+    })
+}
+
+// deserialise params for passing to NFT methods
+// this aborts on errors and is intended for frc53_invoke to use
+pub fn frc53_unpack_params<O: DeserializeOwned>(params: u32) -> O {
+    let params = match sdk::message::params_raw(params) {
+        Ok(Some(params)) => params,
+        Ok(None) => {
+            fvm_sdk::vm::abort(
+                ExitCode::USR_ILLEGAL_ARGUMENT.value(),
+                Some(String::from("missing parameters").as_str()),
+            );
+        }
+        Err(e) => {
+            fvm_sdk::vm::abort(
+                ExitCode::USR_SERIALIZATION.value(),
+                Some(format!("failed to get raw params {e}").as_str()),
+            );
+        }
+    };
+
+    match params.deserialize() {
+        Ok(p) => p,
+        Err(e) => {
+            fvm_sdk::vm::abort(
+                ExitCode::USR_SERIALIZATION.value(),
+                Some(format!("failed to deserialize params {e}").as_str()),
+            );
+        }
+    }
+}
+
+// serialise and save return data to the blockstore
+// this also aborts on error and is intended for frc53_invoke to use
+pub fn frc53_return_block<T>(value: &T) -> Option<u32>
+where
+    T: Serialize + ?Sized,
+{
+    let bytes = match fvm_ipld_encoding::to_vec(value) {
+        Ok(b) => b,
+        Err(e) => {
+            fvm_sdk::vm::abort(
+                ExitCode::USR_SERIALIZATION.value(),
+                Some(format!("failed to serialise return data {e}").as_str()),
+            );
+        }
+    };
+
+    Some(sdk::ipld::put_block(DAG_CBOR, bytes.as_slice()).unwrap_or_else(|e| {
+        fvm_sdk::vm::abort(
+            ExitCode::USR_SERIALIZATION.value(),
+            Some(format!("failed to serialise return data {e}").as_str()),
+        )
+    }))
+}
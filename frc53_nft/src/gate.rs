@@ -0,0 +1,109 @@
+//! Guards for token-gated functionality
+//!
+//! Actors built on this library can call these helpers at the top of a method to restrict it to
+//! holders of the collection, failing the call with a consistent error and exit code otherwise.
+use fvm_actor_utils::messaging::MessagingError;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::address::Address;
+use thiserror::Error;
+
+use crate::types::TokenID;
+use crate::{Result, NFT};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GateError {
+    #[error("{holder} holds {balance} tokens but at least {required} are required")]
+    InsufficientBalance { holder: Address, balance: u64, required: u64 },
+    #[error("{holder} is not the owner of token {token_id}")]
+    NotOwner { holder: Address, token_id: TokenID },
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Fails with `GateError::InsufficientBalance` unless `address` holds at least `min_balance`
+    /// tokens of the collection
+    pub fn require_holder(&self, address: &Address, min_balance: u64) -> Result<()> {
+        let balance = self.balance_of(address)?;
+        if balance < min_balance {
+            return Err(GateError::InsufficientBalance {
+                holder: *address,
+                balance,
+                required: min_balance,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Fails with `GateError::NotOwner` unless `address` is the owner of `token_id`
+    ///
+    /// A token that does not exist fails with `StateError::TokenNotFound`
+    pub fn require_owner_of(&self, address: &Address, token_id: TokenID) -> Result<()> {
+        let owner = self.owner_of(token_id)?;
+        let is_owner = match self.runtime.resolve_id(address) {
+            Ok(id) => id == owner,
+            Err(MessagingError::AddressNotResolved(_)) => false,
+            Err(e) => return Err(e.into()),
+        };
+        if !is_owner {
+            return Err(GateError::NotOwner { holder: *address, token_id }.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, error::ExitCode, ActorID};
+
+    use super::GateError;
+    use crate::{state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+
+    #[test]
+    fn it_gates_on_holdings() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1]
+
+        nft.require_holder(&ALICE, 2).unwrap();
+        nft.require_holder(&BOB, 0).unwrap();
+        let err = nft.require_holder(&ALICE, 3).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+        if let NFTError::Gate(e) = err {
+            assert_eq!(
+                e,
+                GateError::InsufficientBalance { holder: ALICE, balance: 2, required: 3 }
+            );
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+
+        nft.require_owner_of(&ALICE, 1).unwrap();
+        let err = nft.require_owner_of(&BOB, 1).unwrap_err();
+        if let NFTError::Gate(e) = err {
+            assert_eq!(e, GateError::NotOwner { holder: BOB, token_id: 1 });
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+        let err = nft.require_owner_of(&ALICE, 2).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::TokenNotFound(2))));
+    }
+}
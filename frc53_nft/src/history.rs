@@ -0,0 +1,262 @@
+//! Epoch-stamped ownership checkpoints for NFT collections
+//!
+//! Once enabled, every change of a token's owner (mint, transfer or burn) is recorded against the
+//! epoch at which it happened. This allows the owner of a token at a past epoch to be looked up
+//! on-chain, e.g. to decide eligibility for a raffle or airdrop based on historical holdings. Only
+//! epochs from the point the history was enabled onwards can be queried.
+use std::collections::BTreeMap;
+
+use cid::Cid;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_amt::Amt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::ActorID;
+
+use crate::state::{NFTState, StateError, TokenData, DEFAULT_AMT_BIT_WIDTH};
+use crate::types::TokenID;
+use crate::{Result, NFT};
+
+/// A change of a token's owner, None meaning the token did not exist (not yet minted or burned)
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct OwnerCheckpoint {
+    /// The epoch at which the change happened
+    pub epoch: ChainEpoch,
+    /// The owner before the change
+    pub previous_owner: Option<ActorID>,
+    /// The owner from `epoch` onwards
+    pub owner: Option<ActorID>,
+}
+
+/// The ownership changes recorded since the history was enabled
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct OwnershipHistory {
+    /// The first epoch for which ownership can be queried
+    pub enabled_at: ChainEpoch,
+    /// Amt<TokenID, Vec<OwnerCheckpoint>> of the changes of each token, ordered by epoch
+    pub checkpoints: Cid,
+}
+
+/// The owners of a set of tokens captured before an operation that may change them
+pub struct PendingCheckpoint {
+    owners: BTreeMap<TokenID, Option<ActorID>>,
+}
+
+impl NFTState {
+    /// Starts recording ownership changes from the current epoch
+    ///
+    /// Has no effect if the history is already enabled
+    pub fn enable_ownership_history<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        current_epoch: ChainEpoch,
+    ) -> std::result::Result<(), StateError> {
+        if self.ownership_history.is_none() {
+            let checkpoints =
+                Amt::<Vec<OwnerCheckpoint>, &BS>::new_with_bit_width(bs, DEFAULT_AMT_BIT_WIDTH)
+                    .flush()?;
+            self.ownership_history =
+                Some(OwnershipHistory { enabled_at: current_epoch, checkpoints });
+        }
+        Ok(())
+    }
+
+    /// Captures the current owners of the tokens, and of any tokens nested beneath them, ahead of
+    /// an operation that may change them
+    ///
+    /// Returns None if the ownership history is not enabled. Tokens that do not exist are captured
+    /// as having no owner.
+    pub fn capture_owners<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_ids: &[TokenID],
+    ) -> std::result::Result<Option<PendingCheckpoint>, StateError> {
+        if self.ownership_history.is_none() {
+            return Ok(None);
+        }
+        let token_array = self.get_token_data_amt(bs)?;
+        let mut owners = BTreeMap::new();
+        let mut pending: Vec<TokenID> = token_ids.to_vec();
+        while let Some(token_id) = pending.pop() {
+            let token = token_array.get(token_id)?;
+            if let Some(token) = token {
+                pending.extend(token.children.iter());
+            }
+            owners.insert(token_id, token.map(|t| t.owner));
+        }
+        Ok(Some(PendingCheckpoint { owners }))
+    }
+
+    /// Records the owners of captured tokens that changed at the current epoch
+    ///
+    /// Several changes within the same epoch collapse into a single checkpoint holding the final
+    /// owner.
+    pub fn record_owners<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        pending: Option<PendingCheckpoint>,
+        current_epoch: ChainEpoch,
+    ) -> std::result::Result<(), StateError> {
+        let (pending, history) = match (pending, self.ownership_history.as_mut()) {
+            (Some(pending), Some(history)) => (pending, history),
+            _ => return Ok(()),
+        };
+        let token_array = Amt::<TokenData, &BS>::load(&self.token_data, bs)?;
+        let mut checkpoints = Amt::<Vec<OwnerCheckpoint>, &BS>::load(&history.checkpoints, bs)?;
+
+        for (token_id, previous_owner) in pending.owners {
+            let owner = token_array.get(token_id)?.map(|t| t.owner);
+            if owner == previous_owner {
+                continue;
+            }
+            let mut log = checkpoints.get(token_id)?.cloned().unwrap_or_default();
+            match log.last_mut() {
+                Some(last) if last.epoch == current_epoch => {
+                    last.owner = owner;
+                    if last.previous_owner == owner {
+                        log.pop();
+                    }
+                }
+                _ => log.push(OwnerCheckpoint { epoch: current_epoch, previous_owner, owner }),
+            }
+            if log.is_empty() {
+                checkpoints.delete(token_id)?;
+            } else {
+                checkpoints.set(token_id, log)?;
+            }
+        }
+
+        history.checkpoints = checkpoints.flush()?;
+        Ok(())
+    }
+
+    /// Returns the owner of a token at the end of the given epoch, or None if the token did not
+    /// exist at that epoch
+    ///
+    /// Fails if the ownership history is not enabled or `epoch` predates it
+    pub fn get_owner_at<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+        epoch: ChainEpoch,
+    ) -> std::result::Result<Option<ActorID>, StateError> {
+        let history = match &self.ownership_history {
+            Some(history) if epoch >= history.enabled_at => history,
+            _ => return Err(StateError::OwnershipHistoryUnavailable(epoch)),
+        };
+        let checkpoints = Amt::<Vec<OwnerCheckpoint>, &BS>::load(&history.checkpoints, bs)?;
+        match checkpoints.get(token_id)? {
+            Some(log) => {
+                let owner = match log.iter().rposition(|c| c.epoch <= epoch) {
+                    Some(pos) => log[pos].owner,
+                    // the owner before the first recorded change is unchanged since enabling
+                    None => log.first().and_then(|c| c.previous_owner),
+                };
+                Ok(owner)
+            }
+            // the token has not changed hands since the history was enabled
+            None => {
+                let token_array = self.get_token_data_amt(bs)?;
+                Ok(token_array.get(token_id)?.map(|t| t.owner))
+            }
+        }
+    }
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Starts recording ownership changes from the current epoch so that `owner_of_at` can be
+    /// queried for this and later epochs
+    pub fn enable_ownership_history(&mut self) -> Result<()> {
+        let current_epoch = self.runtime.curr_epoch();
+        self.transaction(|state, bs| Ok(state.enable_ownership_history(bs, current_epoch)?))
+    }
+
+    /// Returns the owner of a token at the end of a past (or the current) epoch, or None if the
+    /// token had not been minted or had been burned by then
+    pub fn owner_of_at(&self, token_id: TokenID, epoch: ChainEpoch) -> Result<Option<ActorID>> {
+        Ok(self.state.get_owner_at(&self.runtime, token_id, epoch)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, ActorID};
+
+    use crate::{state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+    const CHARLIE_ID: ActorID = 111;
+    const CHARLIE: Address = Address::new_id(CHARLIE_ID);
+
+    #[test]
+    fn it_records_owners_over_time() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        nft.runtime.syscalls.set_curr_epoch(5);
+
+        // token 0 is minted before the history is enabled
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new()], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        let err = nft.owner_of_at(0, 5).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::OwnershipHistoryUnavailable(5))));
+
+        nft.runtime.syscalls.set_curr_epoch(10);
+        nft.enable_ownership_history().unwrap();
+
+        // token 1 is minted at epoch 11, token 0 moves to bob at epoch 12
+        nft.runtime.syscalls.set_curr_epoch(11);
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new()], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        nft.runtime.syscalls.set_curr_epoch(12);
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        // token 1 passes through bob to charlie within epoch 13, then is burned at epoch 14
+        nft.runtime.syscalls.set_curr_epoch(13);
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[1], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        let mut hook =
+            nft.transfer(&BOB, &CHARLIE, &[1], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        nft.runtime.syscalls.set_curr_epoch(14);
+        nft.burn(&CHARLIE, &[1]).unwrap();
+
+        // epochs before the history was enabled cannot be queried
+        nft.owner_of_at(0, 9).unwrap_err();
+
+        assert_eq!(nft.owner_of_at(0, 10).unwrap(), Some(ALICE_ID));
+        assert_eq!(nft.owner_of_at(0, 11).unwrap(), Some(ALICE_ID));
+        assert_eq!(nft.owner_of_at(0, 12).unwrap(), Some(BOB_ID));
+        assert_eq!(nft.owner_of_at(0, 100).unwrap(), Some(BOB_ID));
+
+        assert_eq!(nft.owner_of_at(1, 10).unwrap(), None);
+        assert_eq!(nft.owner_of_at(1, 11).unwrap(), Some(ALICE_ID));
+        assert_eq!(nft.owner_of_at(1, 12).unwrap(), Some(ALICE_ID));
+        assert_eq!(nft.owner_of_at(1, 13).unwrap(), Some(CHARLIE_ID));
+        assert_eq!(nft.owner_of_at(1, 14).unwrap(), None);
+
+        // tokens that never existed have no owner
+        assert_eq!(nft.owner_of_at(2, 14).unwrap(), None);
+
+        nft.check_invariants().unwrap();
+    }
+}
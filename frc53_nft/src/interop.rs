@@ -0,0 +1,389 @@
+//! An ERC-721 facade, so that one NFT actor can serve both native FRC-0053 and Solidity callers
+//!
+//! EVM contracts call other actors through their `InvokeEVM` method (see [`INVOKE_EVM_METHOD_NUM`])
+//! with Solidity calldata. An NFT actor which exports that method alongside its FRC-0053 methods
+//! can decode the calldata as an [`Erc721Call`] and [`invoke`](Erc721Call::invoke) it on its
+//! [`NFT`] handle, returning the ABI-encoded return data to the contract.
+//!
+//! ERC-721 `safeTransferFrom` calls `onERC721Received` on a contract recipient and reverts unless
+//! the recipient accepts the token. Native recipients don't implement that function, so the facade
+//! emulates it with the FRC-0053 receiver hook: a transfer returns the hook in
+//! [`Erc721Return::Transfer`] for the actor to call as it does for native transfers, the `data`
+//! argument reaches the recipient as `operator_data`, and a recipient that aborts the hook reverts
+//! the call. FRC-0053 calls the hook on every transfer, so `transferFrom` behaves as
+//! `safeTransferFrom` with empty data.
+use frc42_dispatch::hash::method_number;
+use fvm_actor_utils::abi::{
+    encode, encode_address, encode_bool, encode_uint64, eth_address_of, AbiError, AbiValue,
+    Calldata, Word,
+};
+use fvm_actor_utils::receiver::ReceiverHook;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::{ActorID, MethodNum};
+
+use crate::types::{TokenID, TransferIntermediate};
+use crate::{Result, NFT};
+
+/// The method through which EVM contracts call other actors, passing calldata as a CBOR byte string
+pub const INVOKE_EVM_METHOD_NUM: MethodNum = method_number("InvokeEVM");
+
+/// Selectors of the ERC-721 functions served by the facade
+pub mod selectors {
+    use frc42_dispatch::hash::evm_selector;
+    use fvm_actor_utils::abi::Selector;
+
+    pub const NAME: Selector = evm_selector("name()");
+    pub const SYMBOL: Selector = evm_selector("symbol()");
+    pub const TOKEN_URI: Selector = evm_selector("tokenURI(uint256)");
+    pub const TOTAL_SUPPLY: Selector = evm_selector("totalSupply()");
+    pub const BALANCE_OF: Selector = evm_selector("balanceOf(address)");
+    pub const OWNER_OF: Selector = evm_selector("ownerOf(uint256)");
+    pub const GET_APPROVED: Selector = evm_selector("getApproved(uint256)");
+    pub const IS_APPROVED_FOR_ALL: Selector = evm_selector("isApprovedForAll(address,address)");
+    pub const APPROVE: Selector = evm_selector("approve(address,uint256)");
+    pub const SET_APPROVAL_FOR_ALL: Selector = evm_selector("setApprovalForAll(address,bool)");
+    pub const TRANSFER_FROM: Selector = evm_selector("transferFrom(address,address,uint256)");
+    pub const SAFE_TRANSFER_FROM: Selector =
+        evm_selector("safeTransferFrom(address,address,uint256)");
+    pub const SAFE_TRANSFER_FROM_WITH_DATA: Selector =
+        evm_selector("safeTransferFrom(address,address,uint256,bytes)");
+}
+
+/// An ERC-721 function call decoded from calldata
+///
+/// `transferFrom` and both forms of `safeTransferFrom` decode to [`Erc721Call::TransferFrom`], with
+/// empty `data` unless it was given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Erc721Call {
+    Name,
+    Symbol,
+    TokenURI { token_id: TokenID },
+    TotalSupply,
+    BalanceOf { owner: Address },
+    OwnerOf { token_id: TokenID },
+    GetApproved { token_id: TokenID },
+    IsApprovedForAll { owner: Address, operator: Address },
+    Approve { to: Address, token_id: TokenID },
+    SetApprovalForAll { operator: Address, approved: bool },
+    TransferFrom { from: Address, to: Address, token_id: TokenID, data: Vec<u8> },
+}
+
+/// The outcome of an ERC-721 call
+pub enum Erc721Return {
+    /// The call is complete, with the given ABI-encoded return data
+    Data(Vec<u8>),
+    /// A token was transferred and the recipient's receiver hook must be called, after saving the
+    /// state, before the call completes with empty return data
+    Transfer(ReceiverHook<TransferIntermediate>),
+}
+
+impl Erc721Call {
+    /// Decodes a call from Solidity calldata
+    pub fn decode(calldata: &[u8]) -> std::result::Result<Self, AbiError> {
+        let args = Calldata::parse(calldata)?;
+        Ok(match args.selector() {
+            selectors::NAME => Self::Name,
+            selectors::SYMBOL => Self::Symbol,
+            selectors::TOKEN_URI => Self::TokenURI { token_id: args.uint64(0)? },
+            selectors::TOTAL_SUPPLY => Self::TotalSupply,
+            selectors::BALANCE_OF => Self::BalanceOf { owner: args.address(0)? },
+            selectors::OWNER_OF => Self::OwnerOf { token_id: args.uint64(0)? },
+            selectors::GET_APPROVED => Self::GetApproved { token_id: args.uint64(0)? },
+            selectors::IS_APPROVED_FOR_ALL => {
+                Self::IsApprovedForAll { owner: args.address(0)?, operator: args.address(1)? }
+            }
+            selectors::APPROVE => Self::Approve { to: args.address(0)?, token_id: args.uint64(1)? },
+            selectors::SET_APPROVAL_FOR_ALL => {
+                Self::SetApprovalForAll { operator: args.address(0)?, approved: args.bool(1)? }
+            }
+            selectors::TRANSFER_FROM | selectors::SAFE_TRANSFER_FROM => Self::TransferFrom {
+                from: args.address(0)?,
+                to: args.address(1)?,
+                token_id: args.uint64(2)?,
+                data: Vec::new(),
+            },
+            selectors::SAFE_TRANSFER_FROM_WITH_DATA => Self::TransferFrom {
+                from: args.address(0)?,
+                to: args.address(1)?,
+                token_id: args.uint64(2)?,
+                data: args.bytes(3)?.to_vec(),
+            },
+            selector => return Err(AbiError::UnknownSelector(selector)),
+        })
+    }
+
+    /// Returns true if the call doesn't change the collection's state
+    ///
+    /// Read-only calls may be made by static calls from EVM contracts, in which the actor must not
+    /// save its state.
+    pub fn is_read_only(&self) -> bool {
+        !matches!(
+            self,
+            Self::Approve { .. } | Self::SetApprovalForAll { .. } | Self::TransferFrom { .. }
+        )
+    }
+
+    /// Performs the call on the NFT handle
+    ///
+    /// `caller` must be the address of the calling contract. Owners are returned as the Ethereum
+    /// address of their delegated address where they have one, so that they compare equal to
+    /// `msg.sender` in the calling contract. ERC-721 allows a single approved address per token
+    /// while FRC-0053 allows several, so `approve` adds an operator, `approve` of the zero address
+    /// revokes every operator of the token, and `getApproved` returns the lowest operator ID.
+    ///
+    /// A `transferFrom` where `from` is the caller transfers the caller's own token, and any other
+    /// is made as an operator of `from`.
+    pub fn invoke<S, BS>(self, nft: &mut NFT<S, BS>, caller: &Address) -> Result<Erc721Return>
+    where
+        S: Syscalls,
+        BS: Blockstore,
+    {
+        let ret = match self {
+            Self::Name => encode(&[AbiValue::Bytes(nft.name().as_bytes())]),
+            Self::Symbol => encode(&[AbiValue::Bytes(nft.symbol().as_bytes())]),
+            Self::TokenURI { token_id } => {
+                encode(&[AbiValue::Bytes(nft.metadata(token_id)?.as_bytes())])
+            }
+            Self::TotalSupply => encode(&[AbiValue::Word(encode_uint64(nft.total_supply()))]),
+            Self::BalanceOf { owner } => {
+                encode(&[AbiValue::Word(encode_uint64(nft.balance_of(&owner)?))])
+            }
+            Self::OwnerOf { token_id } => {
+                encode(&[AbiValue::Word(encode_actor(nft, nft.owner_of(token_id)?)?)])
+            }
+            Self::GetApproved { token_id } => {
+                let operator = nft.approved_operators(token_id)?.iter().next();
+                let word = match operator {
+                    Some(operator) => encode_actor(nft, operator)?,
+                    None => [0; 32],
+                };
+                encode(&[AbiValue::Word(word)])
+            }
+            Self::IsApprovedForAll { owner, operator } => {
+                encode(&[AbiValue::Word(encode_bool(nft.is_account_operator(&owner, &operator)?))])
+            }
+            Self::Approve { to, token_id } if eth_address_of(&to) == Some([0; 20]) => {
+                for operator in nft.approved_operators(token_id)?.iter() {
+                    nft.revoke(caller, &Address::new_id(operator), &[token_id])?;
+                }
+                Vec::new()
+            }
+            Self::Approve { to, token_id } => {
+                nft.approve(caller, &to, &[token_id])?;
+                Vec::new()
+            }
+            Self::SetApprovalForAll { operator, approved: true } => {
+                nft.approve_for_owner(caller, &operator)?;
+                Vec::new()
+            }
+            Self::SetApprovalForAll { operator, approved: false } => {
+                nft.revoke_for_all(caller, &operator)?;
+                Vec::new()
+            }
+            Self::TransferFrom { from, to, token_id, data } => {
+                let operator_data = RawBytes::new(data);
+                let hook = if nft.runtime.same_address(&from, caller) {
+                    nft.transfer(caller, &to, &[token_id], operator_data, RawBytes::default())?
+                } else {
+                    nft.transfer_from(
+                        &from,
+                        caller,
+                        &to,
+                        &[token_id],
+                        operator_data,
+                        RawBytes::default(),
+                    )?
+                };
+                return Ok(Erc721Return::Transfer(hook));
+            }
+        };
+        Ok(Erc721Return::Data(ret))
+    }
+}
+
+/// Encodes the address an actor is known by to EVM callers
+fn encode_actor<S, BS>(nft: &NFT<S, BS>, actor: ActorID) -> Result<Word>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    let address = Some(nft.runtime.display_address(actor))
+        .filter(|address| eth_address_of(address).is_some())
+        .unwrap_or_else(|| Address::new_id(actor));
+    Ok(encode_address(&address)?)
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::abi::{
+        encode_address, encode_bool, encode_uint64, AbiError, Calldata, EAM_ACTOR_ID,
+    };
+    use fvm_actor_utils::messaging::RECEIVER_HOOK_METHOD_NUM;
+    use fvm_actor_utils::receiver::UniversalReceiverParams;
+    use fvm_actor_utils::syscalls::fake_syscalls::FakeSyscalls;
+    use fvm_actor_utils::util::ActorRuntime;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
+
+    use super::{selectors, Erc721Call, Erc721Return};
+    use crate::receiver::{FRC53TokenReceived, FRC53_TOKEN_TYPE};
+    use crate::state::{NFTState, StateError};
+    use crate::{NFTError, NFT};
+
+    type TestNFT<'st> = NFT<'st, FakeSyscalls, MemoryBlockstore>;
+
+    const ALICE: Address = Address::new_id(1);
+    const BOB: Address = Address::new_id(2);
+    const CAROL: Address = Address::new_id(3);
+
+    fn calldata(selector: u32, args: &[[u8; 32]]) -> Vec<u8> {
+        [&selector.to_be_bytes()[..], &args.concat()].concat()
+    }
+
+    fn word(n: u64) -> [u8; 32] {
+        encode_uint64(n)
+    }
+
+    fn address(address: Address) -> [u8; 32] {
+        encode_address(&address).unwrap()
+    }
+
+    /// Mints tokens to Alice with the given metadata
+    fn mint(nft: &mut TestNFT, metadata: &[&str]) {
+        let metadata = metadata.iter().map(|uri| uri.to_string()).collect();
+        let mut hook =
+            nft.mint(&ALICE, &ALICE, metadata, RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+    }
+
+    /// Invokes a call as an actor would, saving the state before calling the receiver hook of a
+    /// transfer
+    fn call(nft: &mut TestNFT, caller: Address, calldata: &[u8]) -> Result<Vec<u8>, NFTError> {
+        match Erc721Call::decode(calldata)?.invoke(nft, &caller)? {
+            Erc721Return::Data(data) => Ok(data),
+            Erc721Return::Transfer(mut hook) => {
+                let cid = nft.flush()?;
+                let intermediate = hook.call(&nft.runtime).map_err(StateError::from)?;
+                nft.transfer_return(intermediate, cid)?;
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    #[test]
+    fn it_decodes_calls() {
+        let data =
+            calldata(selectors::SAFE_TRANSFER_FROM, &[address(ALICE), address(BOB), word(5)]);
+        let transfer = Erc721Call::TransferFrom { from: ALICE, to: BOB, token_id: 5, data: vec![] };
+        assert_eq!(Erc721Call::decode(&data).unwrap(), transfer);
+        let data = calldata(selectors::TRANSFER_FROM, &[address(ALICE), address(BOB), word(5)]);
+        assert_eq!(Erc721Call::decode(&data).unwrap(), transfer);
+        assert!(!transfer.is_read_only());
+
+        // the data of safeTransferFrom follows the static arguments
+        let args = [address(ALICE), address(BOB), word(5), word(4 * 32), word(3), [0xab; 32]];
+        let data = calldata(selectors::SAFE_TRANSFER_FROM_WITH_DATA, &args);
+        assert_eq!(
+            Erc721Call::decode(&data).unwrap(),
+            Erc721Call::TransferFrom { from: ALICE, to: BOB, token_id: 5, data: vec![0xab; 3] }
+        );
+
+        let data = calldata(selectors::OWNER_OF, &[word(7)]);
+        assert_eq!(Erc721Call::decode(&data).unwrap(), Erc721Call::OwnerOf { token_id: 7 });
+        assert!(Erc721Call::OwnerOf { token_id: 7 }.is_read_only());
+
+        let data = calldata(selectors::SET_APPROVAL_FOR_ALL, &[address(BOB), word(2)]);
+        assert_eq!(Erc721Call::decode(&data), Err(AbiError::InvalidArgument(1, "bool")));
+        let data = calldata(0x12345678, &[]);
+        assert_eq!(Erc721Call::decode(&data), Err(AbiError::UnknownSelector(0x12345678)));
+    }
+
+    #[test]
+    fn it_serves_erc721_calls() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        // Carol is an EVM account, known to contracts by her Ethereum address
+        let carol_eth = Address::new_delegated(EAM_ACTOR_ID, &[0xcc; 20]).unwrap();
+        runtime.syscalls.addresses.borrow_mut().insert(carol_eth, 3);
+        let mut state = NFTState::new(&runtime).unwrap();
+        let mut nft = NFT::wrap(runtime, &mut state);
+        mint(&mut nft, &["ipfs://a", "ipfs://b"]);
+
+        let ret = call(&mut nft, BOB, &calldata(selectors::TOKEN_URI, &[word(1)])).unwrap();
+        let ret_calldata = [&[0; 4][..], &ret].concat();
+        assert_eq!(Calldata::parse(&ret_calldata).unwrap().bytes(0).unwrap(), b"ipfs://b");
+        let ret = call(&mut nft, BOB, &calldata(selectors::BALANCE_OF, &[address(ALICE)]));
+        assert_eq!(ret.unwrap(), word(2));
+
+        // Alice transfers her own token, and its owner is reported by Ethereum address
+        let transfer = [address(ALICE), address(carol_eth), word(0)];
+        call(&mut nft, ALICE, &calldata(selectors::SAFE_TRANSFER_FROM, &transfer)).unwrap();
+        let ret = call(&mut nft, BOB, &calldata(selectors::OWNER_OF, &[word(0)])).unwrap();
+        assert_eq!(ret, address(carol_eth));
+        let ret = call(&mut nft, BOB, &calldata(selectors::OWNER_OF, &[word(1)])).unwrap();
+        assert_eq!(ret, address(ALICE));
+
+        // Bob may only transfer once approved for the token
+        let transfer = calldata(selectors::TRANSFER_FROM, &[address(ALICE), address(BOB), word(1)]);
+        call(&mut nft, BOB, &transfer).unwrap_err();
+        call(&mut nft, ALICE, &calldata(selectors::APPROVE, &[address(BOB), word(1)])).unwrap();
+        let ret = call(&mut nft, BOB, &calldata(selectors::GET_APPROVED, &[word(1)])).unwrap();
+        assert_eq!(ret, address(BOB));
+
+        // approving the zero address clears the approval
+        call(&mut nft, ALICE, &calldata(selectors::APPROVE, &[[0; 32], word(1)])).unwrap();
+        let ret = call(&mut nft, BOB, &calldata(selectors::GET_APPROVED, &[word(1)])).unwrap();
+        assert_eq!(ret, [0; 32]);
+        call(&mut nft, BOB, &transfer).unwrap_err();
+
+        // an operator for all of Alice's tokens may transfer them
+        let approve_all = |approved| {
+            calldata(selectors::SET_APPROVAL_FOR_ALL, &[address(BOB), encode_bool(approved)])
+        };
+        let is_approved = calldata(selectors::IS_APPROVED_FOR_ALL, &[address(ALICE), address(BOB)]);
+        call(&mut nft, ALICE, &approve_all(true)).unwrap();
+        assert_eq!(call(&mut nft, BOB, &is_approved).unwrap(), encode_bool(true));
+        call(&mut nft, BOB, &transfer).unwrap();
+        assert_eq!(nft.owner_of(1).unwrap(), 2);
+        call(&mut nft, ALICE, &approve_all(false)).unwrap();
+        assert_eq!(call(&mut nft, BOB, &is_approved).unwrap(), encode_bool(false));
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_emulates_erc721_received_with_receiver_hooks() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&runtime).unwrap();
+        let mut nft = NFT::wrap(runtime, &mut state);
+        mint(&mut nft, &["a", "b"]);
+
+        // the data of safeTransferFrom reaches the recipient's hook as operator data
+        let args = [address(ALICE), address(CAROL), word(0), word(4 * 32), word(3), [0xab; 32]];
+        call(&mut nft, ALICE, &calldata(selectors::SAFE_TRANSFER_FROM_WITH_DATA, &args)).unwrap();
+        {
+            let msg = nft.runtime.syscalls.last_message.borrow().clone().unwrap();
+            assert_eq!(msg.method, RECEIVER_HOOK_METHOD_NUM);
+            let params: UniversalReceiverParams = msg.params.unwrap().deserialize().unwrap();
+            assert_eq!(params.type_, FRC53_TOKEN_TYPE);
+            let received: FRC53TokenReceived = params.payload.deserialize().unwrap();
+            assert_eq!(received.to, 3);
+            assert_eq!(received.token_ids, vec![0]);
+            assert_eq!(received.operator_data, RawBytes::new(vec![0xab; 3]));
+        }
+
+        // a recipient rejecting the token fails the call, which the actor then aborts
+        let root = nft.flush().unwrap();
+        nft.runtime.syscalls.set_on_send(|_, _, _| ExitCode::USR_FORBIDDEN);
+        let transfer =
+            calldata(selectors::TRANSFER_FROM, &[address(ALICE), address(CAROL), word(1)]);
+        let err = call(&mut nft, ALICE, &transfer).unwrap_err();
+        assert_eq!(err.receiver_abort().unwrap().exit_code, ExitCode::USR_FORBIDDEN);
+        nft.load_replace(&root).unwrap();
+        assert_eq!(nft.owner_of(1).unwrap(), 1);
+    }
+}
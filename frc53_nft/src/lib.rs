@@ -6,30 +6,63 @@
 //! on-chain state can be read by direct inspection (rather than via an actor call)
 //! in many cases.
 
+use std::cell::Cell;
+
 use cid::Cid;
 use fvm_actor_utils::{
+    abi::AbiError,
+    blockstore::BufferedBlockstore,
     messaging::MessagingError,
-    receiver::ReceiverHook,
+    receiver::{ReceiverAbort, ReceiverHook, RecipientData},
     syscalls::Syscalls,
+    transaction::Transactional,
     util::{ActorError, ActorRuntime},
 };
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::{Error as EncodingError, RawBytes};
-use fvm_shared::{address::Address, ActorID};
-use receiver::{FRC53ReceiverHook, FRC53TokenReceived};
+use fvm_shared::{address::Address, clock::ChainEpoch, error::ExitCode, ActorID};
+use receiver::{FRC53ReceiverHook, FRC53TokenReceived, FRC53TokensRedeemed};
 use state::{Cursor, StateError, StateInvariantError, StateSummary};
 use thiserror::Error;
 use types::{
+    ActorIDSet, BurnReturn, CollectionMetadata, ListAccountApprovalsReturn,
     ListAccountOperatorsReturn, ListOperatorTokensReturn, ListTokenOperatorsReturn,
-    ListTokensReturn, MintIntermediate, MintReturn, TokenID, TransferIntermediate, TransferReturn,
+    ListTokensReturn, MintIntermediate, MintReturn, OperatorApproval, RedeemReturn, ReturnBuilder,
+    TokenID, TransferIntermediate, TransferReturn,
 };
+use util::ExpiringOperatorSet;
 
+use self::gate::GateError;
+use self::payout::PayoutError;
+use self::policy::{PolicyResult, PolicyViolation, TransferPolicy};
+use self::roles::TOKEN_DATA_ADMIN_ROLE;
 use self::state::NFTState;
-
+use self::view::NFTStateView;
+
+#[cfg(feature = "use_sdk")]
+pub mod dispatch;
+pub mod gate;
+pub mod history;
+pub mod interop;
+pub mod migration;
+pub mod nesting;
+pub mod operator_index;
+pub mod payout;
+pub mod policy;
 pub mod receiver;
+pub mod rental;
+pub mod reveal;
+pub mod roles;
+pub mod staking;
 pub mod state;
+#[cfg(test)]
+mod state_fuzz;
+#[cfg(test)]
+mod state_golden;
+pub mod testing;
 pub mod types;
 pub mod util;
+pub mod view;
 
 #[derive(Error, Debug)]
 pub enum NFTError {
@@ -41,6 +74,38 @@ pub enum NFTError {
     Actor(#[from] ActorError),
     #[error("error encoding ipld value: {0}")]
     Encoding(#[from] EncodingError),
+    #[error("{0}")]
+    Policy(#[from] PolicyViolation),
+    #[error("{0}")]
+    Gate(#[from] GateError),
+    #[error("{0}")]
+    Payout(#[from] PayoutError),
+    #[error("error in EVM call: {0}")]
+    Abi(#[from] AbiError),
+}
+
+impl NFTError {
+    /// Returns the details of the abort if a receiver hook rejected the tokens
+    pub fn receiver_abort(&self) -> Option<&ReceiverAbort> {
+        match self {
+            NFTError::NFTState(StateError::ReceiverHook(e)) => e.abort(),
+            _ => None,
+        }
+    }
+}
+
+impl From<&NFTError> for ExitCode {
+    fn from(error: &NFTError) -> Self {
+        match error {
+            NFTError::NFTState(e) => e.into(),
+            NFTError::Messaging(e) => e.into(),
+            NFTError::Actor(e) => e.into(),
+            NFTError::Encoding(_) => ExitCode::USR_SERIALIZATION,
+            NFTError::Policy(_) | NFTError::Gate(_) => ExitCode::USR_FORBIDDEN,
+            NFTError::Payout(e) => e.into(),
+            NFTError::Abi(e) => e.into(),
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, NFTError>;
@@ -53,6 +118,7 @@ where
 {
     runtime: ActorRuntime<S, BS>,
     state: &'st mut NFTState,
+    policy: Option<&'st dyn TransferPolicy>,
 }
 
 impl<'st, S, BS> NFT<'st, S, BS>
@@ -62,7 +128,25 @@ where
 {
     /// Wrap an instance of the state-tree in a handle for higher-level operations
     pub fn wrap(runtime: ActorRuntime<S, BS>, state: &'st mut NFTState) -> Self {
-        Self { runtime, state }
+        Self { runtime, state, policy: None }
+    }
+
+    /// Attach a TransferPolicy which is consulted before NFTs are transferred, burned or minted
+    /// through this handle
+    pub fn with_policy(mut self, policy: &'st dyn TransferPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Run a check against the attached TransferPolicy, if any
+    fn check_policy<F>(&self, check: F) -> Result<()>
+    where
+        F: FnOnce(&dyn TransferPolicy) -> PolicyResult,
+    {
+        match self.policy {
+            Some(policy) => Ok(check(policy)?),
+            None => Ok(()),
+        }
     }
 
     /// Flush state and return Cid for root
@@ -86,26 +170,29 @@ where
     /// partial writes are dropped.
     ///
     /// If the closure returns an error, the transaction is dropped atomically and no change is
-    /// observed on token state.
+    /// observed on token state. Blocks written by the closure are buffered and only those reachable
+    /// from the final state are written to the blockstore.
     pub fn transaction<F, Res>(&mut self, f: F) -> Result<Res>
     where
-        F: FnOnce(&mut NFTState, &ActorRuntime<S, BS>) -> Result<Res>,
+        F: FnOnce(&mut NFTState, &BufferedBlockstore<&ActorRuntime<S, BS>>) -> Result<Res>,
     {
-        let mut mutable_state = self.state.clone();
-        let res = f(&mut mutable_state, &self.runtime)?;
-        // if closure didn't error save state
-        *self.state = mutable_state;
-        Ok(res)
+        let bs = BufferedBlockstore::new(&self.runtime);
+        self.state.transaction(|state| {
+            let res = f(state, &bs)?;
+            // if closure didn't error, write out the blocks the new state refers to
+            bs.flush_links(state).map_err(|err| StateError::InvariantFailed(err.to_string()))?;
+            Ok(res)
+        })
+    }
+
+    /// Returns a read-only view of the underlying state
+    pub fn view(&self) -> NFTStateView<'_, ActorRuntime<S, BS>> {
+        NFTStateView::new(self.state, &self.runtime)
     }
 
     /// Check the underlying state for consistency errors
     pub fn check_invariants(&self) -> std::result::Result<StateSummary, Vec<StateInvariantError>> {
-        let (summary, errors) = self.state.check_invariants(&self.runtime);
-        if errors.is_empty() {
-            Ok(summary)
-        } else {
-            Err(errors)
-        }
+        self.state.check_invariants(&self.runtime).into_result()
     }
 }
 
@@ -114,6 +201,100 @@ where
     S: Syscalls,
     BS: Blockstore,
 {
+    /// Return the descriptive name of the collection
+    pub fn name(&self) -> String {
+        self.state.collection_metadata.name.clone()
+    }
+
+    /// Return the abbreviated symbol of the collection
+    pub fn symbol(&self) -> String {
+        self.state.collection_metadata.symbol.clone()
+    }
+
+    /// Return the descriptive metadata of the collection
+    pub fn collection_metadata(&self) -> CollectionMetadata {
+        self.state.collection_metadata.clone()
+    }
+
+    /// Replace the descriptive metadata of the collection, returning the previous value
+    ///
+    /// The caller is responsible for checking that the actor calling this method is permitted to
+    /// change the collection metadata
+    pub fn set_collection_metadata(
+        &mut self,
+        collection_metadata: CollectionMetadata,
+    ) -> CollectionMetadata {
+        self.state.set_collection_metadata(collection_metadata)
+    }
+
+    /// Replace the base URI from which the metadata of tokens minted without their own is derived,
+    /// returning the previous value
+    ///
+    /// The caller is responsible for checking that the actor calling this method is permitted to
+    /// change the collection metadata
+    pub fn set_base_uri(&mut self, base_uri: String) -> String {
+        self.state.set_base_uri(base_uri)
+    }
+
+    /// Return whether minting, transferring and burning are suspended across the collection
+    pub fn is_paused(&self) -> bool {
+        self.state.paused
+    }
+
+    /// Suspend minting, transferring and burning across the collection
+    ///
+    /// While paused, these operations fail with `StateError::Paused`. Approvals and queries are
+    /// unaffected. The caller is responsible for checking that the actor calling this method is
+    /// permitted to pause the collection
+    pub fn pause(&mut self) {
+        self.state.set_paused(true);
+    }
+
+    /// Resume minting, transferring and burning across the collection
+    ///
+    /// The caller is responsible for checking that the actor calling this method is permitted to
+    /// unpause the collection
+    pub fn unpause(&mut self) {
+        self.state.set_paused(false);
+    }
+
+    /// Return the maximum number of NFTs that may ever be minted, or None if unlimited
+    pub fn max_supply(&self) -> Option<u64> {
+        self.state.max_supply
+    }
+
+    /// Return the number of NFTs that may still be minted, or None if unlimited
+    pub fn remaining_supply(&self) -> Option<u64> {
+        self.state.remaining_supply()
+    }
+
+    /// Return the maximum number of NFTs that may be minted per epoch, or None if unlimited
+    pub fn mint_limit_per_epoch(&self) -> Option<u64> {
+        self.state.mint_rate_limit.as_ref().map(|limit| limit.max_per_epoch)
+    }
+
+    /// Return the number of NFTs that may still be minted in the current epoch, or None if unlimited
+    pub fn remaining_mints_this_epoch(&self) -> Option<u64> {
+        let current_epoch = self.runtime.curr_epoch();
+        self.state.mint_rate_limit.as_ref().map(|limit| limit.remaining(current_epoch))
+    }
+
+    /// Set the maximum number of NFTs that may ever be minted, burned NFTs included
+    ///
+    /// The max supply cannot be set below the number of NFTs already minted. The caller is
+    /// responsible for checking that the actor calling this method is permitted to do so
+    pub fn set_max_supply(&mut self, max_supply: Option<u64>) -> Result<()> {
+        Ok(self.state.set_max_supply(max_supply)?)
+    }
+
+    /// Set the maximum number of NFTs that may be minted per epoch
+    ///
+    /// The caller is responsible for checking that the actor calling this method is permitted to
+    /// do so
+    pub fn set_mint_limit_per_epoch(&mut self, max_per_epoch: Option<u64>) {
+        self.state.set_mint_limit_per_epoch(max_per_epoch)
+    }
+
     /// Return the total number of NFTs in circulation from this collection
     pub fn total_supply(&self) -> u64 {
         self.state.total_supply
@@ -139,6 +320,92 @@ where
         Ok(self.state.get_metadata(&self.runtime, token_id)?)
     }
 
+    /// Return the extra CBOR payload attached to an NFT, empty if none has been set
+    pub fn get_token_data(&self, token_id: TokenID) -> Result<RawBytes> {
+        Ok(self.state.get_token_data(&self.runtime, token_id)?)
+    }
+
+    /// Replace the extra CBOR payload attached to an NFT
+    ///
+    /// `caller` may be the owner of the NFT or a member of the `TOKEN_DATA_ADMIN_ROLE`. Operators
+    /// approved to transfer the NFT may not change its data. Actors with other notions of authority
+    /// can call `NFTState::set_token_data` directly with their own predicate.
+    pub fn set_token_data(
+        &mut self,
+        caller: &Address,
+        token_id: TokenID,
+        data: RawBytes,
+    ) -> Result<()> {
+        let caller = self.runtime.resolve_id(caller)?;
+
+        self.transaction(|state, bs| {
+            let admin = state.has_role(TOKEN_DATA_ADMIN_ROLE, caller);
+            state.set_token_data(bs, token_id, data, |token_data, token_id| {
+                if token_data.owner == caller || admin {
+                    Ok(())
+                } else {
+                    Err(StateError::NotAuthorized { actor: caller, token_id })
+                }
+            })?;
+            Ok(())
+        })
+    }
+
+    /// Return the operators currently approved at token-level for an NFT
+    ///
+    /// Account-level operators of the owner are not included. Use `list_token_operators` to
+    /// enumerate the operators of a token in pages.
+    pub fn approved_operators(&self, token_id: TokenID) -> Result<ActorIDSet> {
+        Ok(self.state.get_token_operators(&self.runtime, token_id, self.runtime.curr_epoch())?)
+    }
+
+    /// Return whether an address is currently approved at token-level for an NFT
+    pub fn is_approved_operator(&self, token_id: TokenID, operator: &Address) -> Result<bool> {
+        let operator = match self.runtime.resolve_id(operator) {
+            Ok(id) => id,
+            Err(MessagingError::AddressNotResolved(_)) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(self.state.is_token_operator(
+            &self.runtime,
+            token_id,
+            operator,
+            self.runtime.curr_epoch(),
+        )?)
+    }
+
+    /// Return the only recipient a token-level operator may transfer an NFT to, or None if its
+    /// approval is not restricted to a recipient
+    pub fn approved_recipient(
+        &self,
+        token_id: TokenID,
+        operator: &Address,
+    ) -> Result<Option<ActorID>> {
+        let operator = match self.runtime.resolve_id(operator) {
+            Ok(id) => id,
+            Err(MessagingError::AddressNotResolved(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(self.state.get_operator_recipient(&self.runtime, token_id, operator)?)
+    }
+
+    /// Return whether an address is currently an account-level operator for an owner
+    pub fn is_account_operator(&self, owner: &Address, operator: &Address) -> Result<bool> {
+        let (owner, operator) =
+            match (self.runtime.resolve_id(owner), self.runtime.resolve_id(operator)) {
+                (Ok(owner), Ok(operator)) => (owner, operator),
+                (Err(MessagingError::AddressNotResolved(_)), _)
+                | (_, Err(MessagingError::AddressNotResolved(_))) => return Ok(false),
+                (Err(e), _) | (_, Err(e)) => return Err(e.into()),
+            };
+        Ok(self.state.is_owner_operator(
+            &self.runtime,
+            owner,
+            operator,
+            self.runtime.curr_epoch(),
+        )?)
+    }
+
     /// Create new NFTs belonging to the initial_owner. The mint method is not standardised
     /// as part of the actor's interface but this is a usefuly method at the library level to
     /// generate new tokens that will maintain the necessary state invariants.
@@ -156,9 +423,18 @@ where
     ) -> Result<ReceiverHook<MintIntermediate>> {
         let operator = self.runtime.resolve_id(operator)?;
         let initial_owner_id = self.runtime.resolve_or_init(initial_owner)?;
+        let current_epoch = self.runtime.curr_epoch();
+
+        let first_token_id = self.state.next_token;
+        let token_ids: Vec<TokenID> =
+            (first_token_id..first_token_id + metadata_array.len() as u64).collect();
+        self.check_policy(|policy| policy.before_mint(operator, initial_owner_id, &token_ids))?;
 
         let mint_intermediate = self.transaction(|state, bs| {
-            Ok(state.mint_tokens(&bs, initial_owner_id, metadata_array)?)
+            let pending = state.capture_owners(bs, &token_ids)?;
+            let res = state.mint_tokens(&bs, initial_owner_id, metadata_array, current_epoch)?;
+            state.record_owners(bs, pending, current_epoch)?;
+            Ok(res)
         })?;
 
         // params we'll send to the receiver hook
@@ -168,6 +444,7 @@ where
             operator_data,
             token_data,
             token_ids: mint_intermediate.token_ids.clone(),
+            per_token_data: vec![],
         };
 
         Ok(ReceiverHook::new_frc53(*initial_owner, params, mint_intermediate)
@@ -183,56 +460,129 @@ where
         intermediate: MintIntermediate,
         prior_state_cid: Cid,
     ) -> Result<MintReturn> {
-        self.reload_if_changed(prior_state_cid)?;
-        Ok(self.state.mint_return(&self.runtime, intermediate)?)
+        self.complete(intermediate, prior_state_cid)
     }
 
-    /// Burn a set of NFTs as the owner and returns the resulting balance
+    /// Burn a set of NFTs as the owner
     ///
-    /// A burnt TokenID can never be minted again
-    pub fn burn(&mut self, owner: &Address, token_ids: &[TokenID]) -> Result<u64> {
+    /// Returns the resulting balance along with the previous owner and cleared approvals of each
+    /// burnt token. A burnt TokenID can never be minted again
+    pub fn burn(&mut self, owner: &Address, token_ids: &[TokenID]) -> Result<BurnReturn> {
         let owner = self.runtime.resolve_id(owner)?;
+        self.check_policy(|policy| policy.before_burn(owner, owner, token_ids))?;
+
+        let current_epoch = self.runtime.curr_epoch();
 
-        let balance = self.transaction(|state, helper| {
-            Ok(state.burn_tokens(helper, owner, token_ids, |token_data, token_id| {
+        let res = self.transaction(|state, helper| {
+            let pending = state.capture_owners(helper, token_ids)?;
+            let res = state.burn_tokens(helper, owner, token_ids, |token_data, token_id| {
                 NFTState::assert_owns_token(token_data, token_id, owner)
-            })?)
+            })?;
+            state.record_owners(helper, pending, current_epoch)?;
+            Ok(res)
         })?;
 
-        Ok(balance)
+        Ok(res)
     }
 
-    /// Burn a set of NFTs as an operator and returns the resulting balance
+    /// Burn a set of NFTs as an operator
     ///
-    /// A burnt TokenID can never be minted again
+    /// The operator must either be an account-level operator for the owner or be approved for
+    /// every token, otherwise a `StateError::NotOperator` error is returned for the first token
+    /// the operator has no authority over.
+    ///
+    /// Returns the resulting balance along with the previous owner and cleared approvals of each
+    /// burnt token. A burnt TokenID can never be minted again
     pub fn burn_from(
         &mut self,
         owner: &Address,
         operator: &Address,
         token_ids: &[TokenID],
-    ) -> Result<u64> {
+    ) -> Result<BurnReturn> {
         let operator = self.runtime.resolve_id(operator)?;
         let owner = self.runtime.resolve_or_init(owner)?;
+        self.check_policy(|policy| policy.before_burn(operator, owner, token_ids))?;
 
-        let balance = self.transaction(|state, bs| {
-            let owner_map = state.get_owner_data_hamt(bs)?;
-            let account_operator = NFTState::is_account_operator(&owner_map, owner, operator)?;
+        let current_epoch = self.runtime.curr_epoch();
+
+        let res = self.transaction(|state, bs| {
+            let account_operator = state.is_owner_operator(bs, owner, operator, current_epoch)?;
+            // tokens burned solely on the strength of the account-level approval
+            let budgeted = Cell::new(0u64);
+            let pending = state.capture_owners(bs, token_ids)?;
 
             let res = state.burn_tokens(bs, owner, token_ids, |token_data, token_id| {
                 // check the token is owned by the expected account
                 NFTState::assert_owns_token(token_data, token_id, owner)?;
                 // check that the operator has permission to burn the token
-                if !account_operator {
-                    NFTState::assert_token_level_approval(token_data, token_id, operator)
-                } else {
+                if token_data.is_unrestricted_operator(&operator, current_epoch) {
+                    Ok(())
+                } else if account_operator {
+                    budgeted.set(budgeted.get() + 1);
                     Ok(())
+                } else {
+                    Err(StateError::NotOperator { operator, owner, token_id })
                 }
             })?;
+            state.use_operator_budget(bs, owner, operator, budgeted.get())?;
+            state.record_owners(bs, pending, current_epoch)?;
 
             Ok(res)
         })?;
 
-        Ok(balance)
+        Ok(res)
+    }
+
+    /// Burn a set of NFTs owned by the caller and notify a redeemer actor of the burn
+    ///
+    /// The burn is persisted and the root of the actor updated before the redeemer's receiver hook
+    /// is called with `FRC53TokensRedeemed` parameters, so the redeemer can verify the burn. If the
+    /// hook aborts, the state and root are restored to their values before the burn and the hook
+    /// error is returned. The redeemer can use this to implement physical redemption or
+    /// upgrade-by-burn mechanics.
+    ///
+    /// `owner` must be the address that called this method
+    pub fn burn_and_notify(
+        &mut self,
+        owner: &Address,
+        token_ids: &[TokenID],
+        redeemer: &Address,
+        operator_data: RawBytes,
+    ) -> Result<RedeemReturn> {
+        let owner_id = self.runtime.resolve_id(owner)?;
+        let redeemer_id = self.runtime.resolve_id(redeemer)?;
+        let prior_state = self.state.clone();
+        let prior_root = self.runtime.root_cid()?;
+
+        let burn = self.burn(owner, token_ids)?;
+        let burnt_root = self.flush()?;
+        self.runtime.set_root(&burnt_root)?;
+
+        let params = FRC53TokensRedeemed {
+            from: owner_id,
+            redeemer: redeemer_id,
+            token_ids: token_ids.into(),
+            operator_data,
+            token_data: RawBytes::default(),
+        };
+        let mut hook = ReceiverHook::new_frc53_redeem(
+            *redeemer,
+            params,
+            RedeemReturn { burn, recipient_data: RawBytes::default() },
+        )
+        .map_err(StateError::from)?;
+
+        match hook.call(&self.runtime) {
+            Ok(ret) => {
+                self.reload_if_changed(burnt_root)?;
+                Ok(ret)
+            }
+            Err(e) => {
+                *self.state = prior_state;
+                self.runtime.set_root(&prior_root)?;
+                Err(StateError::from(e).into())
+            }
+        }
     }
 
     /// Approve an operator to transfer or burn a single NFT
@@ -244,15 +594,71 @@ where
         caller: &Address,
         operator: &Address,
         token_ids: &[TokenID],
+    ) -> Result<()> {
+        self.approve_until(caller, operator, token_ids, None)
+    }
+
+    /// Approve an operator to transfer or burn a single NFT until the `expiry` epoch (inclusive)
+    ///
+    /// `caller` may be an account-level operator or owner of the NFT
+    /// `operator` is the new address to become an approved operator
+    /// `expiry` of None grants the approval indefinitely
+    pub fn approve_until(
+        &mut self,
+        caller: &Address,
+        operator: &Address,
+        token_ids: &[TokenID],
+        expiry: Option<ChainEpoch>,
+    ) -> Result<()> {
+        self.approve_tokens(caller, operator, token_ids, None, expiry)
+    }
+
+    /// Approve an operator to transfer a set of NFTs, but only to the given recipient
+    ///
+    /// This lets a marketplace list tokens without holding them in escrow, as it can complete a
+    /// sale by transferring the tokens to the agreed buyer but can't divert them elsewhere. A
+    /// restricted operator also can't burn the tokens or act on them in other ways. Approving the
+    /// operator again, with or without a recipient, replaces the restriction.
+    ///
+    /// `caller` may be an account-level operator or owner of the NFT
+    /// `operator` is the new address to become an approved operator
+    /// `recipient` is the only address the operator may transfer the tokens to
+    /// `expiry` of None grants the approval indefinitely
+    pub fn approve_for_recipient(
+        &mut self,
+        caller: &Address,
+        operator: &Address,
+        token_ids: &[TokenID],
+        recipient: &Address,
+        expiry: Option<ChainEpoch>,
+    ) -> Result<()> {
+        self.approve_tokens(caller, operator, token_ids, Some(recipient), expiry)
+    }
+
+    fn approve_tokens(
+        &mut self,
+        caller: &Address,
+        operator: &Address,
+        token_ids: &[TokenID],
+        recipient: Option<&Address>,
+        expiry: Option<ChainEpoch>,
     ) -> Result<()> {
         // Attempt to instantiate the accounts if they don't exist
         let caller = self.runtime.resolve_id(caller)?;
         let operator = self.runtime.resolve_or_init(operator)?;
+        let recipient = recipient.map(|r| self.runtime.resolve_or_init(r)).transpose()?;
+        let current_epoch = self.runtime.curr_epoch();
 
         self.transaction(|state, bs| {
-            Ok(state.approve_for_tokens(bs, operator, token_ids, |token_data, token_id| {
-                NFTState::assert_owns_token(token_data, token_id, caller)
-            })?)
+            Ok(state.approve_for_tokens_to(
+                bs,
+                operator,
+                token_ids,
+                recipient,
+                expiry,
+                current_epoch,
+                |token_data, token_id| NFTState::assert_owns_token(token_data, token_id, caller),
+            )?)
         })?;
 
         Ok(())
@@ -289,15 +695,76 @@ where
     /// `owner` must be the address that called this method
     /// `operator` is the new address to become an approved operator
     pub fn approve_for_owner(&mut self, owner: &Address, operator: &Address) -> Result<()> {
+        self.approve_for_owner_until(owner, operator, None)
+    }
+
+    /// Approve an operator to transfer or burn on behalf of the account until the `expiry` epoch
+    /// (inclusive)
+    ///
+    /// `owner` must be the address that called this method
+    /// `operator` is the new address to become an approved operator
+    /// `expiry` of None grants the approval indefinitely
+    pub fn approve_for_owner_until(
+        &mut self,
+        owner: &Address,
+        operator: &Address,
+        expiry: Option<ChainEpoch>,
+    ) -> Result<()> {
+        let owner = self.runtime.resolve_id(owner)?;
+        // Attempt to instantiate the accounts if they don't exist
+        let operator = self.runtime.resolve_or_init(operator)?;
+        let current_epoch = self.runtime.curr_epoch();
+
+        self.transaction(|state, bs| {
+            Ok(state.approve_for_owner(bs, owner, operator, expiry, current_epoch)?)
+        })?;
+
+        Ok(())
+    }
+
+    /// Approve an operator to transfer or burn up to `budget` tokens on behalf of the account
+    ///
+    /// Each token the operator transfers or burns on the strength of this approval is deducted
+    /// from the budget and the approval is revoked once the budget is spent. Tokens the operator is
+    /// also approved for individually do not count against the budget.
+    ///
+    /// `owner` must be the address that called this method
+    /// `operator` is the new address to become an approved operator
+    /// `expiry` of None grants the approval until the budget is spent or it is revoked
+    pub fn approve_for_owner_with_budget(
+        &mut self,
+        owner: &Address,
+        operator: &Address,
+        budget: u64,
+        expiry: Option<ChainEpoch>,
+    ) -> Result<()> {
         let owner = self.runtime.resolve_id(owner)?;
         // Attempt to instantiate the accounts if they don't exist
         let operator = self.runtime.resolve_or_init(operator)?;
+        let current_epoch = self.runtime.curr_epoch();
 
-        self.transaction(|state, bs| Ok(state.approve_for_owner(bs, owner, operator)?))?;
+        self.transaction(|state, bs| {
+            Ok(state.approve_for_owner_with_budget(
+                bs,
+                owner,
+                operator,
+                expiry,
+                Some(budget),
+                current_epoch,
+            )?)
+        })?;
 
         Ok(())
     }
 
+    /// Returns the number of tokens an account-level operator may still transfer or burn on
+    /// behalf of the owner, or None if its approval is not limited by a budget
+    pub fn operator_budget(&self, owner: &Address, operator: &Address) -> Result<Option<u64>> {
+        let owner = self.runtime.resolve_id(owner)?;
+        let operator = self.runtime.resolve_id(operator)?;
+        Ok(self.state.get_operator_budget(&self.runtime, owner, operator)?)
+    }
+
     /// Revoke the approval of an operator to transfer on behalf of the caller
     ///
     /// `owner` must be the address that called this method
@@ -323,27 +790,126 @@ where
         operator_data: RawBytes,
         token_data: RawBytes,
     ) -> Result<ReceiverHook<TransferIntermediate>> {
+        self.transfer_with_token_data(
+            owner,
+            recipient,
+            token_ids,
+            operator_data,
+            token_data,
+            vec![],
+        )
+    }
+
+    /// Transfers a token owned by the caller, attaching a separate payload to each token
+    ///
+    /// `per_token_data` must be empty or hold exactly one entry per token in `token_ids`. It is
+    /// forwarded to the receiver hook alongside `token_data`, which applies to the whole batch.
+    /// Tokens nested inside `token_ids` move with them and follow them in the token IDs given to
+    /// the transfer policy and receiver hook, with empty per-token data.
+    pub fn transfer_with_token_data(
+        &mut self,
+        owner: &Address,
+        recipient: &Address,
+        token_ids: &[TokenID],
+        operator_data: RawBytes,
+        token_data: RawBytes,
+        per_token_data: Vec<RawBytes>,
+    ) -> Result<ReceiverHook<TransferIntermediate>> {
+        check_per_token_data(token_ids, &per_token_data)?;
         // Attempt to instantiate the accounts if they don't exist
         let owner_id = self.runtime.resolve_or_init(owner)?;
         let recipient_id = self.runtime.resolve_or_init(recipient)?;
+        let moved = self.state.with_descendants(&self.runtime, token_ids)?;
+        self.check_policy(|policy| {
+            policy.before_transfer(owner_id, owner_id, recipient_id, &moved)
+        })?;
+
+        let current_epoch = self.runtime.curr_epoch();
 
         let intermediate = self.transaction(|state, bs| {
-            Ok(state.transfer(bs, token_ids, owner_id, recipient_id, &|token_data, token_id| {
-                NFTState::assert_owns_token(token_data, token_id, owner_id)
-            })?)
+            let pending = state.capture_owners(bs, token_ids)?;
+            let res = state.transfer(
+                bs,
+                token_ids,
+                owner_id,
+                recipient_id,
+                &|token_data, token_id| NFTState::assert_owns_token(token_data, token_id, owner_id),
+            )?;
+            state.record_owners(bs, pending, current_epoch)?;
+            Ok(res)
         })?;
 
         let params = FRC53TokenReceived {
             to: recipient_id,
             operator: owner_id,
-            token_ids: token_ids.into(),
+            token_ids: intermediate.token_ids.clone(),
             operator_data,
             token_data,
+            per_token_data: pad_per_token_data(per_token_data, intermediate.token_ids.len()),
         };
 
         Ok(ReceiverHook::new_frc53(*recipient, params, intermediate).map_err(StateError::from)?)
     }
 
+    /// Transfers different sets of tokens owned by the caller to several recipients at once
+    ///
+    /// All assignments are applied in a single state transaction. Returns one ReceiverHook per
+    /// recipient, in the order of `assignments`; each must be called and its result passed to
+    /// `transfer_return` as for a single transfer.
+    pub fn transfer_multi(
+        &mut self,
+        owner: &Address,
+        assignments: &[(Address, Vec<TokenID>)],
+        operator_data: RawBytes,
+        token_data: RawBytes,
+    ) -> Result<Vec<ReceiverHook<TransferIntermediate>>> {
+        // Attempt to instantiate the accounts if they don't exist
+        let owner_id = self.runtime.resolve_or_init(owner)?;
+        let resolved = assignments
+            .iter()
+            .map(|(recipient, token_ids)| {
+                Ok((self.runtime.resolve_or_init(recipient)?, token_ids.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (recipient_id, token_ids) in &resolved {
+            let moved = self.state.with_descendants(&self.runtime, token_ids)?;
+            self.check_policy(|policy| {
+                policy.before_transfer(owner_id, owner_id, *recipient_id, &moved)
+            })?;
+        }
+
+        let current_epoch = self.runtime.curr_epoch();
+        let all_token_ids: Vec<TokenID> =
+            resolved.iter().flat_map(|(_, token_ids)| token_ids.iter().copied()).collect();
+
+        let intermediates = self.transaction(|state, bs| {
+            let pending = state.capture_owners(bs, &all_token_ids)?;
+            let res = state.transfer_multi(bs, &resolved, owner_id, &|token_data, token_id| {
+                NFTState::assert_owns_token(token_data, token_id, owner_id)
+            })?;
+            state.record_owners(bs, pending, current_epoch)?;
+            Ok(res)
+        })?;
+
+        let mut hooks = Vec::with_capacity(intermediates.len());
+        for ((recipient, _), intermediate) in assignments.iter().zip(intermediates) {
+            let params = FRC53TokenReceived {
+                to: intermediate.to,
+                operator: owner_id,
+                token_ids: intermediate.token_ids.clone(),
+                operator_data: operator_data.clone(),
+                token_data: token_data.clone(),
+                per_token_data: vec![],
+            };
+            hooks.push(
+                ReceiverHook::new_frc53(*recipient, params, intermediate)
+                    .map_err(StateError::from)?,
+            );
+        }
+
+        Ok(hooks)
+    }
+
     /// Constructs TransferReturn data from a TransferIntermediate
     ///
     /// Creates an up-to-date view of the actor state where necessary to generate the values
@@ -353,8 +919,7 @@ where
         intermediate: TransferIntermediate,
         prior_state_cid: Cid,
     ) -> Result<TransferReturn> {
-        self.reload_if_changed(prior_state_cid)?;
-        Ok(self.state.transfer_return(&self.runtime, intermediate)?)
+        self.complete(intermediate, prior_state_cid)
     }
 
     /// Transfers a token that the caller is an operator for
@@ -367,15 +932,51 @@ where
         operator_data: RawBytes,
         token_data: RawBytes,
     ) -> Result<ReceiverHook<TransferIntermediate>> {
+        self.transfer_from_with_token_data(
+            owner,
+            operator,
+            recipient,
+            token_ids,
+            operator_data,
+            token_data,
+            vec![],
+        )
+    }
+
+    /// Transfers a token that the caller is an operator for, attaching a separate payload to each
+    /// token
+    ///
+    /// `per_token_data` must be empty or hold exactly one entry per token in `token_ids`. Tokens
+    /// nested inside `token_ids` are handled as by [`NFT::transfer_with_token_data`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn transfer_from_with_token_data(
+        &mut self,
+        owner: &Address,
+        operator: &Address,
+        recipient: &Address,
+        token_ids: &[TokenID],
+        operator_data: RawBytes,
+        token_data: RawBytes,
+        per_token_data: Vec<RawBytes>,
+    ) -> Result<ReceiverHook<TransferIntermediate>> {
+        check_per_token_data(token_ids, &per_token_data)?;
         // Attempt to instantiate the accounts if they don't exist
         let owner_id = self.runtime.resolve_id(owner)?;
         let operator_id = self.runtime.resolve_id(operator)?;
         let recipient_id = self.runtime.resolve_or_init(recipient)?;
+        let moved = self.state.with_descendants(&self.runtime, token_ids)?;
+        self.check_policy(|policy| {
+            policy.before_transfer(operator_id, owner_id, recipient_id, &moved)
+        })?;
+        let current_epoch = self.runtime.curr_epoch();
 
         let intermediate = self.transaction(|state, bs| {
             let owner_map = state.get_owner_data_hamt(bs)?;
             let account_operator =
-                NFTState::is_account_operator(&owner_map, owner_id, operator_id)?;
+                NFTState::is_account_operator(&owner_map, owner_id, operator_id, current_epoch)?;
+            // tokens transferred solely on the strength of the account-level approval
+            let budgeted = Cell::new(0u64);
+            let pending = state.capture_owners(bs, token_ids)?;
             let intermediate = state.transfer(
                 bs,
                 token_ids,
@@ -384,21 +985,39 @@ where
                 &|token_data, token_id| {
                     NFTState::assert_owns_token(token_data, token_id, owner_id)?;
                     if !account_operator {
-                        NFTState::assert_token_level_approval(token_data, token_id, operator_id)
+                        NFTState::assert_token_level_approval(
+                            token_data,
+                            token_id,
+                            operator_id,
+                            current_epoch,
+                        )?;
+                        NFTState::assert_recipient_allowed(
+                            token_data,
+                            token_id,
+                            operator_id,
+                            recipient_id,
+                        )
                     } else {
+                        // a token-level approval for this transfer spares the operator's budget
+                        if !token_data.may_transfer_to(&operator_id, recipient_id, current_epoch) {
+                            budgeted.set(budgeted.get() + 1);
+                        }
                         Ok(())
                     }
                 },
             )?;
+            state.use_operator_budget(bs, owner_id, operator_id, budgeted.get())?;
+            state.record_owners(bs, pending, current_epoch)?;
             Ok(intermediate)
         })?;
 
         let params = FRC53TokenReceived {
             to: recipient_id,
             operator: owner_id,
-            token_ids: token_ids.into(),
+            token_ids: intermediate.token_ids.clone(),
             operator_data,
             token_data,
+            per_token_data: pad_per_token_data(per_token_data, intermediate.token_ids.len()),
         };
 
         Ok(ReceiverHook::new_frc53(*recipient, params, intermediate).map_err(StateError::from)?)
@@ -413,8 +1032,7 @@ where
         intermediate: TransferIntermediate,
         prior_state_cid: Cid,
     ) -> Result<TransferReturn> {
-        self.reload_if_changed(prior_state_cid)?;
-        Ok(self.state.transfer_return(&self.runtime, intermediate)?)
+        self.complete(intermediate, prior_state_cid)
     }
 
     /// Enumerates a page of TokenIDs
@@ -448,8 +1066,13 @@ where
         limit: u64,
     ) -> Result<ListTokenOperatorsReturn> {
         let cursor = Cursor::from_bytes(cursor)?;
-        let (operators, next_cursor) =
-            self.state.list_token_operators(&self.runtime, token_id, cursor, limit)?;
+        let (operators, next_cursor) = self.state.list_token_operators(
+            &self.runtime,
+            token_id,
+            cursor,
+            limit,
+            self.runtime.curr_epoch(),
+        )?;
         let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
         Ok(ListTokenOperatorsReturn { operators, next_cursor })
     }
@@ -463,8 +1086,13 @@ where
     ) -> Result<ListOperatorTokensReturn> {
         let operator_id = self.runtime.resolve_id(operator)?;
         let cursor = Cursor::from_bytes(cursor)?;
-        let (tokens, next_cursor) =
-            self.state.list_operator_tokens(&self.runtime, operator_id, cursor, limit)?;
+        let (tokens, next_cursor) = self.state.list_operator_tokens(
+            &self.runtime,
+            operator_id,
+            cursor,
+            limit,
+            self.runtime.curr_epoch(),
+        )?;
         let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
         Ok(ListOperatorTokensReturn { tokens, next_cursor })
     }
@@ -478,12 +1106,59 @@ where
     ) -> Result<ListAccountOperatorsReturn> {
         let owner_id = self.runtime.resolve_id(owner)?;
         let cursor = Cursor::from_bytes(cursor)?;
-        let (operators, next_cursor) =
-            self.state.list_account_operators(&self.runtime, owner_id, cursor, limit)?;
+        let (operators, next_cursor) = self.state.list_account_operators(
+            &self.runtime,
+            owner_id,
+            cursor,
+            limit,
+            self.runtime.curr_epoch(),
+        )?;
         let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
         Ok(ListAccountOperatorsReturn { operators, next_cursor })
     }
 
+    /// Returns the outstanding account-level approvals of an owner with their expiry epochs and
+    /// remaining budgets
+    pub fn list_account_approvals(
+        &self,
+        owner: &Address,
+        cursor: RawBytes,
+        limit: u64,
+    ) -> Result<ListAccountApprovalsReturn> {
+        let owner_id = self.runtime.resolve_id(owner)?;
+        let cursor = Cursor::from_bytes(cursor)?;
+        let (approvals, next_cursor) = self.state.list_account_approvals(
+            &self.runtime,
+            owner_id,
+            cursor,
+            limit,
+            self.runtime.curr_epoch(),
+        )?;
+        let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
+        Ok(ListAccountApprovalsReturn { approvals, next_cursor })
+    }
+
+    /// Returns the outstanding token-level approvals of a token with their expiry epochs
+    ///
+    /// Account-level operators of the owner are not included. Use `list_account_approvals` to
+    /// audit those.
+    pub fn list_token_approvals(&self, token_id: TokenID) -> Result<Vec<OperatorApproval>> {
+        Ok(self.state.list_token_approvals(&self.runtime, token_id, self.runtime.curr_epoch())?)
+    }
+
+    /// Constructs the return data of an operation from its intermediate once the receiver hook has
+    /// been called
+    ///
+    /// Creates an up-to-date view of the actor state where necessary to generate the values
+    /// `prior_state_cid` is the CID of the state prior to hook call
+    pub fn complete<I>(&mut self, intermediate: I, prior_state_cid: Cid) -> Result<I::Return>
+    where
+        I: RecipientData + ReturnBuilder,
+    {
+        self.reload_if_changed(prior_state_cid)?;
+        Ok(intermediate.build_return(self.state, &self.runtime)?)
+    }
+
     /// Reloads the state if the current root cid has diverged (i.e. during re-entrant receiver hooks)
     /// from the last known expected cid
     ///
@@ -515,16 +1190,54 @@ impl Cursor {
     }
 }
 
+/// Checks that per-token data is either absent or supplied for every token in the batch
+fn check_per_token_data(
+    token_ids: &[TokenID],
+    per_token_data: &[RawBytes],
+) -> std::result::Result<(), StateError> {
+    if !per_token_data.is_empty() && per_token_data.len() != token_ids.len() {
+        return Err(StateError::TokenDataMismatch {
+            token_count: token_ids.len(),
+            data_count: per_token_data.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Extends the per-token data given for the requested tokens with empty entries for the tokens
+/// nested inside them, which follow them in a transfer
+fn pad_per_token_data(mut per_token_data: Vec<RawBytes>, token_count: usize) -> Vec<RawBytes> {
+    if !per_token_data.is_empty() {
+        per_token_data.resize(token_count, RawBytes::default());
+    }
+    per_token_data
+}
+
 #[cfg(test)]
 mod test {
 
-    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
-    use fvm_ipld_bitfield::bitfield;
-    use fvm_ipld_blockstore::MemoryBlockstore;
-    use fvm_ipld_encoding::RawBytes;
-    use fvm_shared::{address::Address, ActorID};
-
-    use crate::{state::StateError, types::TokenID, NFTError, NFTState, NFT};
+    use fvm_actor_utils::{
+        inspect::inspect,
+        instrumented_blockstore::InstrumentedBlockstore,
+        messaging::RECEIVER_HOOK_METHOD_NUM,
+        receiver::{ReceiverHookError, RecipientData, UniversalReceiverParams},
+        syscalls::fake_syscalls::FakeSyscalls,
+        util::ActorRuntime,
+    };
+    use fvm_ipld_bitfield::{bitfield, BitField};
+    use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+    use fvm_ipld_encoding::{ipld_block::IpldBlock, RawBytes};
+    use fvm_shared::{address::Address, error::ExitCode, ActorID};
+    use serde_json::json;
+
+    use crate::{
+        migration::STATE_VERSION,
+        receiver::{FRC53TokenReceived, FRC53TokensRedeemed, FRC53_REDEEM_TYPE},
+        roles::TOKEN_DATA_ADMIN_ROLE,
+        state::{actor_id_key, StateError, StateInvariantError, OWNER_TOKENS_INLINE_LIMIT},
+        types::{CollectionMetadata, OperatorApproval, ReturnBuilder, TokenID},
+        NFTError, NFTState, NFT,
+    };
 
     const ALICE_ID: ActorID = 1;
     const ALICE: Address = Address::new_id(ALICE_ID);
@@ -533,6 +1246,254 @@ mod test {
     const CHARLIE_ID: ActorID = 111;
     const CHARLIE: Address = Address::new_id(CHARLIE_ID);
 
+    #[test]
+    fn it_exposes_receiver_aborts() {
+        let err = NFTError::from(StateError::from(ReceiverHookError::new_receiver_error(
+            ALICE,
+            ExitCode::USR_FORBIDDEN,
+            IpldBlock::serialize_cbor(&"rejected").unwrap(),
+        )));
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+        let abort = err.receiver_abort().unwrap();
+        assert_eq!(abort.address, ALICE);
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        assert_eq!(abort.message.as_deref(), Some("rejected"));
+
+        assert!(NFTError::from(StateError::Paused).receiver_abort().is_none());
+    }
+
+    #[test]
+    fn it_renders_state_with_layout() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        let mut hook = nft
+            .mint(
+                &ALICE,
+                &ALICE,
+                vec!["a".into(), "b".into()],
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        let cid = nft.flush().unwrap();
+
+        let value = inspect(&nft.runtime, &cid, &NFTState::layout()).unwrap();
+        assert_eq!(value["version"], json!(STATE_VERSION));
+        assert_eq!(value["total_supply"], json!(2));
+        assert_eq!(value["token_data"]["0"]["owner"], json!(ALICE_ID));
+        assert_eq!(value["token_data"]["1"]["metadata"], json!("b"));
+        assert_eq!(value["owner_data"][ALICE_ID.to_string()]["balance"], json!(2));
+        assert_eq!(value["ownership_history"], json!(null));
+    }
+
+    #[test]
+    fn it_stores_collection_metadata() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let metadata = CollectionMetadata {
+            name: "Test Collection".into(),
+            symbol: "TEST".into(),
+            ..Default::default()
+        };
+        let mut state = NFTState::new_with_metadata(&helper, metadata.clone()).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        assert_eq!(nft.name(), "Test Collection");
+        assert_eq!(nft.symbol(), "TEST");
+        assert_eq!(nft.collection_metadata(), metadata);
+
+        let updated = CollectionMetadata {
+            description: "A collection used for testing".into(),
+            external_url: "https://example.com".into(),
+            ..metadata.clone()
+        };
+        let previous = nft.set_collection_metadata(updated.clone());
+        assert_eq!(previous, metadata);
+        assert_eq!(nft.collection_metadata(), updated);
+
+        // metadata survives a round trip through the blockstore
+        let cid = nft.flush().unwrap();
+        nft.load_replace(&cid).unwrap();
+        assert_eq!(nft.collection_metadata(), updated);
+    }
+
+    #[test]
+    fn it_derives_metadata_from_base_uri() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(
+                &ALICE,
+                &ALICE,
+                vec![String::new(), "ipfs://override".into()],
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        // without a base uri the stored metadata is returned as-is
+        assert_eq!(nft.metadata(0).unwrap(), "");
+
+        assert_eq!(nft.set_base_uri("ipfs://collection/".into()), "");
+        assert_eq!(nft.metadata(0).unwrap(), "ipfs://collection/0");
+        // per-token metadata takes precedence
+        assert_eq!(nft.metadata(1).unwrap(), "ipfs://override");
+    }
+
+    #[test]
+    fn it_blocks_mutations_while_paused() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1]
+
+        nft.pause();
+        assert!(nft.is_paused());
+
+        let err = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 1], RawBytes::default(), RawBytes::default())
+            .unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::Paused)), "{err:?}");
+        let err =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::Paused)), "{err:?}");
+        let err = nft.burn(&ALICE, &[1]).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::Paused)), "{err:?}");
+
+        // approvals are still permitted
+        nft.approve_for_owner(&ALICE, &BOB).unwrap();
+        let err = nft
+            .transfer_from(&ALICE, &BOB, &BOB, &[0], RawBytes::default(), RawBytes::default())
+            .unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::Paused)), "{err:?}");
+
+        // state unchanged
+        assert_eq!(nft.total_supply(), 2);
+        assert_eq!(nft.balance_of(&ALICE).unwrap(), 2);
+
+        nft.unpause();
+        assert!(!nft.is_paused());
+        let mut hook = nft
+            .transfer_from(&ALICE, &BOB, &BOB, &[0], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.owner_of(0).unwrap(), BOB_ID);
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_enforces_supply_limits() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        nft.runtime.syscalls.set_curr_epoch(1);
+
+        nft.set_max_supply(Some(5)).unwrap();
+        nft.set_mint_limit_per_epoch(Some(3));
+        assert_eq!(nft.max_supply(), Some(5));
+        assert_eq!(nft.mint_limit_per_epoch(), Some(3));
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.remaining_supply(), Some(3));
+        assert_eq!(nft.remaining_mints_this_epoch(), Some(1));
+
+        {
+            // cannot exceed the per-epoch limit
+            let err = nft
+                .mint(
+                    &ALICE,
+                    &ALICE,
+                    vec![String::new(); 2],
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap_err();
+            if let NFTError::NFTState(StateError::MintRateExceeded { remaining, requested }) = err {
+                assert_eq!(remaining, 1);
+                assert_eq!(requested, 2);
+            } else {
+                panic!("unexpected error {err:?}");
+            }
+        }
+
+        // the limit resets in the next epoch
+        nft.runtime.syscalls.set_curr_epoch(2);
+        assert_eq!(nft.remaining_mints_this_epoch(), Some(3));
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        // burned tokens still count towards the max supply
+        nft.burn(&ALICE, &[0]).unwrap();
+        nft.runtime.syscalls.set_curr_epoch(3);
+        assert_eq!(nft.remaining_supply(), Some(0));
+        let err = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 1], RawBytes::default(), RawBytes::default())
+            .unwrap_err();
+        if let NFTError::NFTState(StateError::MaxSupplyExceeded { max_supply, requested }) = err {
+            assert_eq!(max_supply, 5);
+            assert_eq!(requested, 1);
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+
+        // max supply cannot be lowered below the number of minted tokens
+        let err = nft.set_max_supply(Some(4)).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::InvalidMaxSupply { .. })), "{err:?}");
+
+        // lifting the limits allows minting again
+        nft.set_max_supply(None).unwrap();
+        nft.set_mint_limit_per_epoch(None);
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 10], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.total_supply(), 14);
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_reports_invariant_violations() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        state.mint_tokens(&helper, ALICE_ID, vec![String::new(); 2], 0).unwrap();
+        assert!(state.check_invariants(&helper).is_ok());
+
+        // corrupt the supply and drop alice from the owner map
+        let mut corrupted = state.clone();
+        corrupted.total_supply = 3;
+        let mut owner_map = corrupted.get_owner_data_hamt(&helper).unwrap();
+        owner_map.delete(&actor_id_key(ALICE_ID)).unwrap();
+        corrupted.owner_data = owner_map.flush().unwrap();
+
+        let report = corrupted.check_invariants(&helper);
+        assert!(!report.is_ok());
+        assert_eq!(report.summary.total_supply, 3);
+        assert!(report.errors.iter().any(|e| matches!(
+            e,
+            StateInvariantError::TotalSupplyMismatch { total_supply: 3, token_count: 2 }
+        )));
+        assert!(report
+            .errors
+            .iter()
+            .any(|e| matches!(e, StateInvariantError::MissingOwner(ALICE_ID))));
+    }
+
     #[test]
     fn it_mints_tokens_incrementally() {
         let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
@@ -602,6 +1563,30 @@ mod test {
         nft.check_invariants().unwrap();
     }
 
+    #[test]
+    fn it_bounds_blocks_touched_by_transfers() {
+        let helper = ActorRuntime::new(
+            FakeSyscalls::default(),
+            InstrumentedBlockstore::new(MemoryBlockstore::default()),
+        );
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        // only the roots of the token AMT and owner HAMT are rewritten
+        nft.runtime.blockstore.reset();
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[0, 1], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        let stats = nft.runtime.blockstore.take_stats();
+        assert!(stats.writes <= 2, "{stats:?}");
+        assert!(stats.unique_reads <= 2, "{stats:?}");
+    }
+
     #[test]
     fn it_transfers_tokens() {
         let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
@@ -688,31 +1673,135 @@ mod test {
             assert_eq!(bob_balance, 3);
         }
 
-        /*
-        TODO: either explicitly check for duplicates or use a bitfield representation to prevent duplicates being specified
+        /*
+        TODO: either explicitly check for duplicates or use a bitfield representation to prevent duplicates being specified
+        {
+            // doesn't transfer if there are duplicates
+            let err = nft
+                .transfer(&BOB, &ALICE, &[0, 1, 0], RawBytes::default(), RawBytes::default())
+                .unwrap_err();
+            if let NFTError::NFTState(StateError::NotOwner { actor, token_id }) = err {
+                assert_eq!(actor, BOB_ID);
+                assert_eq!(token_id, 0);
+            } else {
+                panic!("Unexpected error: {:?}", err);
+            }
+            // alice: []
+            // bob: [0, 1, 2]
+        }
+
+        {
+            // state didn't change
+            let alice_balance = nft.balance_of(&ALICE).unwrap();
+            assert_eq!(alice_balance, 0);
+            let bob_balance = nft.balance_of(&BOB).unwrap();
+            assert_eq!(bob_balance, 3);
+        }
+         */
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_transfers_to_multiple_recipients() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 4], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        {
+            // the whole batch fails if any token isn't owned by the caller
+            let err = nft
+                .transfer_multi(
+                    &ALICE,
+                    &[(BOB, vec![0]), (CHARLIE, vec![1, 4])],
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap_err();
+            if let NFTError::NFTState(StateError::TokenNotFound(token_id)) = err {
+                assert_eq!(token_id, 4);
+            } else {
+                panic!("Unexpected error: {err:?}");
+            }
+            assert_eq!(nft.balance_of(&ALICE).unwrap(), 4);
+            assert_eq!(nft.balance_of(&BOB).unwrap(), 0);
+        }
+
+        {
+            // alice sends different tokens to bob and charlie
+            let hooks = nft
+                .transfer_multi(
+                    &ALICE,
+                    &[(BOB, vec![0, 1]), (CHARLIE, vec![3])],
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap();
+            assert_eq!(hooks.len(), 2);
+            for mut hook in hooks {
+                hook.call(&nft.runtime).unwrap();
+            }
+            // alice: [2]
+            // bob: [0, 1]
+            // charlie: [3]
+        }
+
+        assert_eq!(nft.balance_of(&ALICE).unwrap(), 1);
+        assert_eq!(nft.balance_of(&BOB).unwrap(), 2);
+        assert_eq!(nft.balance_of(&CHARLIE).unwrap(), 1);
+        assert_eq!(nft.owner_of(0).unwrap(), BOB_ID);
+        assert_eq!(nft.owner_of(3).unwrap(), CHARLIE_ID);
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_stores_per_token_data() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert!(nft.get_token_data(0).unwrap().is_empty());
+
+        let level = RawBytes::serialize(5u64).unwrap();
         {
-            // doesn't transfer if there are duplicates
-            let err = nft
-                .transfer(&BOB, &ALICE, &[0, 1, 0], RawBytes::default(), RawBytes::default())
-                .unwrap_err();
-            if let NFTError::NFTState(StateError::NotOwner { actor, token_id }) = err {
+            // bob is neither owner nor operator
+            let err = nft.set_token_data(&BOB, 0, level.clone()).unwrap_err();
+            if let NFTError::NFTState(StateError::NotAuthorized { actor, token_id }) = err {
                 assert_eq!(actor, BOB_ID);
                 assert_eq!(token_id, 0);
             } else {
-                panic!("Unexpected error: {:?}", err);
+                panic!("Unexpected error: {err:?}");
             }
-            // alice: []
-            // bob: [0, 1, 2]
         }
 
-        {
-            // state didn't change
-            let alice_balance = nft.balance_of(&ALICE).unwrap();
-            assert_eq!(alice_balance, 0);
-            let bob_balance = nft.balance_of(&BOB).unwrap();
-            assert_eq!(bob_balance, 3);
-        }
-         */
+        nft.set_token_data(&ALICE, 0, level.clone()).unwrap();
+        assert_eq!(nft.get_token_data(0).unwrap(), level);
+        assert!(nft.get_token_data(1).unwrap().is_empty());
+
+        // operators approved to transfer the token may not update its data
+        nft.approve(&ALICE, &BOB, &[0]).unwrap();
+        nft.approve_for_owner(&ALICE, &BOB).unwrap();
+        let level = RawBytes::serialize(6u64).unwrap();
+        let err = nft.set_token_data(&BOB, 0, level.clone()).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::NotAuthorized { .. })));
+
+        // a token data admin may update the data, which survives transfer
+        nft.state.grant_role(TOKEN_DATA_ADMIN_ROLE, CHARLIE_ID);
+        nft.set_token_data(&CHARLIE, 0, level.clone()).unwrap();
+        let mut hook =
+            nft.transfer(&ALICE, &CHARLIE, &[0], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.get_token_data(0).unwrap(), level);
 
         nft.check_invariants().unwrap();
     }
@@ -779,8 +1868,10 @@ mod test {
 
         {
             // burn some tokens
-            let new_balance = nft.burn(&ALICE, &[0, 1, 2]).unwrap();
-            assert_eq!(new_balance, 2);
+            let res = nft.burn(&ALICE, &[0, 1, 2]).unwrap();
+            assert_eq!(res.balance, 2);
+            assert_eq!(res.supply, 2);
+            assert_eq!(res.burned.iter().map(|b| b.token_id).collect::<Vec<_>>(), vec![0, 1, 2]);
             // alice: [3, 4]
         }
 
@@ -813,6 +1904,463 @@ mod test {
         nft.check_invariants().unwrap();
     }
 
+    #[test]
+    fn it_reports_burn_details() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1, 2]
+        nft.approve(&ALICE, &BOB, &[0]).unwrap();
+        nft.approve(&ALICE, &CHARLIE, &[0]).unwrap();
+
+        {
+            // bob has token-level approval for token 0 only
+            let err = nft.burn_from(&ALICE, &BOB, &[0, 1]).unwrap_err();
+            if let NFTError::NFTState(StateError::NotOperator { operator, owner, token_id }) = err {
+                assert_eq!(operator, BOB_ID);
+                assert_eq!(owner, ALICE_ID);
+                assert_eq!(token_id, 1);
+            } else {
+                panic!("unexpected error {err:?}");
+            }
+            assert_eq!(nft.total_supply(), 3);
+        }
+
+        {
+            // burning reports the previous owner and the approvals that were cleared
+            let res = nft.burn_from(&ALICE, &BOB, &[0]).unwrap();
+            assert_eq!(res.balance, 2);
+            assert_eq!(res.supply, 2);
+            assert_eq!(res.burned.len(), 1);
+            assert_eq!(res.burned[0].token_id, 0);
+            assert_eq!(res.burned[0].previous_owner, ALICE_ID);
+            assert_eq!(
+                res.burned[0].cleared_operators,
+                BitField::try_from_bits([BOB_ID, CHARLIE_ID]).unwrap()
+            );
+        }
+
+        {
+            // an account-level operator can burn any of the owner's tokens
+            nft.approve_for_owner(&ALICE, &CHARLIE).unwrap();
+            let res = nft.burn_from(&ALICE, &CHARLIE, &[1, 2]).unwrap();
+            assert_eq!(res.balance, 0);
+            assert!(res.burned.iter().all(|b| b.cleared_operators.is_empty()));
+        }
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_burns_and_notifies_redeemers() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        let root = nft.flush().unwrap();
+        nft.runtime.set_root(&root).unwrap();
+        // alice: [0, 1, 2]
+
+        let res = nft.burn_and_notify(&ALICE, &[0, 1], &BOB, RawBytes::default()).unwrap();
+        assert_eq!(res.burn.balance, 1);
+        assert_eq!(res.burn.supply, 1);
+        assert_eq!(nft.runtime.root_cid().unwrap(), nft.flush().unwrap());
+        {
+            // the redeemer was sent the burnt tokens
+            let msg = nft.runtime.syscalls.last_message.borrow().clone().unwrap();
+            assert_eq!(msg.method, RECEIVER_HOOK_METHOD_NUM);
+            let params: UniversalReceiverParams = msg.params.unwrap().deserialize().unwrap();
+            assert_eq!(params.type_, FRC53_REDEEM_TYPE);
+            let payload: FRC53TokensRedeemed = params.payload.deserialize().unwrap();
+            assert_eq!(payload.from, ALICE_ID);
+            assert_eq!(payload.redeemer, BOB_ID);
+            assert_eq!(payload.token_ids, vec![0, 1]);
+        }
+
+        // an aborting redeemer reverts the burn
+        let root = nft.runtime.root_cid().unwrap();
+        nft.runtime.syscalls.abort_next_send.replace(true);
+        nft.burn_and_notify(&ALICE, &[2], &BOB, RawBytes::default()).unwrap_err();
+        assert_eq!(nft.owner_of(2).unwrap(), ALICE_ID);
+        assert_eq!(nft.total_supply(), 1);
+        assert_eq!(nft.runtime.root_cid().unwrap(), root);
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_completes_custom_intermediates() {
+        /// Reports the recipient's balance and the total supply after a mint, with the hook's data
+        struct SupplyIntermediate {
+            to: ActorID,
+            recipient_data: RawBytes,
+        }
+
+        impl RecipientData for SupplyIntermediate {
+            fn set_recipient_data(&mut self, data: RawBytes) {
+                self.recipient_data = data;
+            }
+        }
+
+        impl ReturnBuilder for SupplyIntermediate {
+            type Return = (u64, u64, RawBytes);
+
+            fn build_return<BS: Blockstore>(
+                self,
+                state: &NFTState,
+                bs: &BS,
+            ) -> std::result::Result<Self::Return, StateError> {
+                Ok((state.get_balance(bs, self.to)?, state.total_supply, self.recipient_data))
+            }
+        }
+
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        let prior_cid = nft.flush().unwrap();
+        nft.runtime.set_root(&prior_cid).unwrap();
+
+        let intermediate = SupplyIntermediate { to: ALICE_ID, recipient_data: RawBytes::default() };
+        assert_eq!(nft.complete(intermediate, prior_cid).unwrap(), (2, 2, RawBytes::default()));
+
+        // simulate a re-entrant mint during a hook that moved the root
+        let mut reentrant = nft.state.clone();
+        reentrant.mint_tokens(&nft.runtime, ALICE_ID, vec![String::new()], 0).unwrap();
+        let new_cid = reentrant.save(&nft.runtime).unwrap();
+        nft.runtime.set_root(&new_cid).unwrap();
+
+        // the handle reloads the changed state before building the return
+        let intermediate = SupplyIntermediate { to: ALICE_ID, recipient_data: RawBytes::default() };
+        assert_eq!(nft.complete(intermediate, prior_cid).unwrap(), (3, 3, RawBytes::default()));
+        assert_eq!(nft.total_supply(), 3);
+    }
+
+    #[test]
+    fn it_forwards_per_token_data() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1, 2]
+
+        // per-token data must match the number of tokens
+        let err = nft
+            .transfer_with_token_data(
+                &ALICE,
+                &BOB,
+                &[0, 1],
+                RawBytes::default(),
+                RawBytes::default(),
+                vec![RawBytes::new(vec![0])],
+            )
+            .unwrap_err();
+        if let NFTError::NFTState(StateError::TokenDataMismatch { token_count, data_count }) = err {
+            assert_eq!(token_count, 2);
+            assert_eq!(data_count, 1);
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+        assert_eq!(nft.owner_of(0).unwrap(), ALICE_ID);
+
+        let per_token_data = vec![RawBytes::new(vec![0]), RawBytes::new(vec![1])];
+        let mut hook = nft
+            .transfer_with_token_data(
+                &ALICE,
+                &BOB,
+                &[0, 1],
+                RawBytes::default(),
+                RawBytes::default(),
+                per_token_data.clone(),
+            )
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        {
+            let msg = nft.runtime.syscalls.last_message.borrow().clone().unwrap();
+            let params: UniversalReceiverParams = msg.params.unwrap().deserialize().unwrap();
+            let payload: FRC53TokenReceived = params.payload.deserialize().unwrap();
+            assert_eq!(payload.token_ids, vec![0, 1]);
+            assert_eq!(payload.per_token_data, per_token_data);
+        }
+
+        // plain transfers send no per-token data
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[2], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        {
+            let msg = nft.runtime.syscalls.last_message.borrow().clone().unwrap();
+            let params: UniversalReceiverParams = msg.params.unwrap().deserialize().unwrap();
+            let payload: FRC53TokenReceived = params.payload.deserialize().unwrap();
+            assert!(payload.per_token_data.is_empty());
+        }
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_limits_account_operators_to_a_budget() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 4], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1, 2, 3]
+
+        nft.approve_for_owner_with_budget(&ALICE, &BOB, 2, None).unwrap();
+        assert_eq!(nft.operator_budget(&ALICE, &BOB).unwrap(), Some(2));
+
+        // tokens bob is individually approved for don't count against the budget
+        nft.approve(&ALICE, &BOB, &[3]).unwrap();
+        let mut hook = nft
+            .transfer_from(
+                &ALICE,
+                &BOB,
+                &CHARLIE,
+                &[0, 3],
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.operator_budget(&ALICE, &BOB).unwrap(), Some(1));
+
+        // bob cannot exceed the remaining budget
+        let err = nft.burn_from(&ALICE, &BOB, &[1, 2]).unwrap_err();
+        if let NFTError::NFTState(StateError::OperatorBudgetExceeded {
+            operator,
+            owner,
+            remaining,
+            requested,
+        }) = err
+        {
+            assert_eq!(operator, BOB_ID);
+            assert_eq!(owner, ALICE_ID);
+            assert_eq!(remaining, 1);
+            assert_eq!(requested, 2);
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+        assert_eq!(nft.balance_of(&ALICE).unwrap(), 2);
+
+        // spending the budget revokes the approval
+        nft.burn_from(&ALICE, &BOB, &[1]).unwrap();
+        assert!(!nft.is_account_operator(&ALICE, &BOB).unwrap());
+        assert_eq!(nft.operator_budget(&ALICE, &BOB).unwrap(), None);
+        nft.burn_from(&ALICE, &BOB, &[2]).unwrap_err();
+
+        // revoking the approval drops the budget
+        nft.approve_for_owner_with_budget(&ALICE, &BOB, 5, None).unwrap();
+        nft.revoke_for_all(&ALICE, &BOB).unwrap();
+        assert_eq!(nft.operator_budget(&ALICE, &BOB).unwrap(), None);
+
+        // re-approving without a budget lifts the limit
+        nft.approve_for_owner_with_budget(&ALICE, &BOB, 5, None).unwrap();
+        nft.approve_for_owner(&ALICE, &BOB).unwrap();
+        assert_eq!(nft.operator_budget(&ALICE, &BOB).unwrap(), None);
+        nft.burn_from(&ALICE, &BOB, &[2]).unwrap();
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_keeps_the_tokens_of_large_holders_in_their_own_block() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        let limit = OWNER_TOKENS_INLINE_LIMIT;
+        let owner_data = |nft: &NFT<FakeSyscalls, MemoryBlockstore>| {
+            let owners = nft.state.get_owner_data_hamt(&nft.runtime).unwrap();
+            owners.get(&actor_id_key(ALICE_ID)).unwrap().unwrap().clone()
+        };
+
+        let mut hook = nft
+            .mint(
+                &ALICE,
+                &ALICE,
+                vec![String::new(); limit as usize - 1],
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        let inline = owner_data(&nft);
+        assert_eq!(inline.spilled_tokens, None);
+        assert_eq!(inline.tokens.len(), limit - 1);
+
+        // reaching the limit moves the owner's tokens to their own block
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new()], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        let spilled = owner_data(&nft);
+        assert!(spilled.spilled_tokens.is_some());
+        assert!(spilled.tokens.is_empty());
+        assert_eq!(spilled.owned_tokens(&nft.runtime).unwrap().len(), limit);
+        let owned = nft.list_owned_tokens(&ALICE, RawBytes::default(), limit).unwrap();
+        assert_eq!(owned.tokens.len(), limit);
+        nft.check_invariants().unwrap();
+
+        // dropping below it moves them back inline
+        let mut hook = nft
+            .transfer(&ALICE, &BOB, &[limit - 1], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(owner_data(&nft), inline);
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_restricts_operators_to_recipients() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1, 2]
+
+        // alice lists tokens 0 and 1 with bob, who may only sell them to charlie
+        nft.approve_for_recipient(&ALICE, &BOB, &[0, 1], &CHARLIE, None).unwrap();
+        assert!(nft.is_approved_operator(0, &BOB).unwrap());
+        assert_eq!(nft.approved_recipient(0, &BOB).unwrap(), Some(CHARLIE_ID));
+        assert_eq!(nft.approved_recipient(2, &BOB).unwrap(), None);
+
+        // bob can't divert the tokens to himself or burn them
+        let err = nft
+            .transfer_from(&ALICE, &BOB, &BOB, &[0], RawBytes::default(), RawBytes::default())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            NFTError::NFTState(StateError::RecipientNotAllowed {
+                operator: BOB_ID,
+                token_id: 0,
+                recipient: BOB_ID
+            })
+        ));
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+        let err = nft.burn_from(&ALICE, &BOB, &[1]).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::NotOperator { .. })));
+        assert_eq!(nft.owner_of(0).unwrap(), ALICE_ID);
+
+        // but can transfer them to charlie, which clears the approval
+        let mut hook = nft
+            .transfer_from(&ALICE, &BOB, &CHARLIE, &[0], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.owner_of(0).unwrap(), CHARLIE_ID);
+        assert!(!nft.is_approved_operator(0, &BOB).unwrap());
+        assert_eq!(nft.approved_recipient(0, &BOB).unwrap(), None);
+
+        // approving again without a recipient lifts the restriction
+        nft.approve(&ALICE, &BOB, &[1]).unwrap();
+        assert_eq!(nft.approved_recipient(1, &BOB).unwrap(), None);
+        nft.approve_for_recipient(&ALICE, &BOB, &[1], &CHARLIE, None).unwrap();
+        // and revoking the approval drops it
+        nft.revoke(&ALICE, &BOB, &[1]).unwrap();
+        assert_eq!(nft.approved_recipient(1, &BOB).unwrap(), None);
+
+        // an account-level operator isn't restricted, but spends its budget on transfers to other
+        // recipients
+        nft.approve_for_recipient(&ALICE, &BOB, &[1, 2], &CHARLIE, None).unwrap();
+        nft.approve_for_owner_with_budget(&ALICE, &BOB, 1, None).unwrap();
+        let mut hook = nft
+            .transfer_from(&ALICE, &BOB, &BOB, &[1], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert!(!nft.is_account_operator(&ALICE, &BOB).unwrap());
+        // leaving the token-level approval for charlie
+        let mut hook = nft
+            .transfer_from(&ALICE, &BOB, &CHARLIE, &[2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_lists_outstanding_approvals() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        nft.runtime.syscalls.set_curr_epoch(10);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1]
+
+        nft.approve_until(&ALICE, &BOB, &[0], Some(20)).unwrap();
+        nft.approve(&ALICE, &CHARLIE, &[0]).unwrap();
+        nft.approve_for_owner_until(&ALICE, &BOB, Some(30)).unwrap();
+        nft.approve_for_owner_with_budget(&ALICE, &CHARLIE, 1, None).unwrap();
+
+        assert_eq!(
+            nft.list_token_approvals(0).unwrap(),
+            vec![
+                OperatorApproval { operator: BOB_ID, expiry: Some(20), budget: None },
+                OperatorApproval { operator: CHARLIE_ID, expiry: None, budget: None },
+            ]
+        );
+        assert!(nft.list_token_approvals(1).unwrap().is_empty());
+
+        // account-level approvals are paged
+        let res = nft.list_account_approvals(&ALICE, RawBytes::default(), 1).unwrap();
+        assert_eq!(
+            res.approvals,
+            vec![OperatorApproval { operator: BOB_ID, expiry: Some(30), budget: None }]
+        );
+        let cursor = res.next_cursor.unwrap();
+        let res = nft.list_account_approvals(&ALICE, cursor.clone(), 1).unwrap();
+        assert_eq!(
+            res.approvals,
+            vec![OperatorApproval { operator: CHARLIE_ID, expiry: None, budget: Some(1) }]
+        );
+        assert!(res.next_cursor.is_none());
+
+        // an unbounded page holds every approval
+        let res = nft.list_account_approvals(&ALICE, RawBytes::default(), u64::MAX).unwrap();
+        assert_eq!(res.approvals.len(), 2);
+        assert!(res.next_cursor.is_none());
+
+        // cursors are invalidated by changes to the owner data
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new()], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        let err = nft.list_account_approvals(&ALICE, cursor, 1).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::InvalidCursor)));
+
+        // expired approvals are not reported
+        nft.runtime.syscalls.set_curr_epoch(21);
+        assert_eq!(
+            nft.list_token_approvals(0).unwrap(),
+            vec![OperatorApproval { operator: CHARLIE_ID, expiry: None, budget: None }]
+        );
+    }
+
     #[test]
     fn it_allows_account_level_delegation() {
         let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
@@ -922,8 +2470,8 @@ mod test {
 
         {
             // bob can burn from for alice
-            let remaining_balance = nft.burn_from(&ALICE, &BOB, &[2]).unwrap();
-            assert_eq!(remaining_balance, 1);
+            let res = nft.burn_from(&ALICE, &BOB, &[2]).unwrap();
+            assert_eq!(res.balance, 1);
             // alice: [3]
             // bob: [0, 1]
         }
@@ -967,8 +2515,8 @@ mod test {
 
         {
             // bob can burn newly minted tokens from alice
-            let remaining_balance = nft.burn_from(&ALICE, &BOB, &[7]).unwrap();
-            assert_eq!(remaining_balance, 4);
+            let res = nft.burn_from(&ALICE, &BOB, &[7]).unwrap();
+            assert_eq!(res.balance, 4);
             // alice: [3, 4, 5, 6]
             // bob: [0, 1]
         }
@@ -1170,13 +2718,107 @@ mod test {
         }
 
         nft.transaction(|state, helpers| {
-            let (_, errors) = state.check_invariants(&helpers);
-            assert!(errors.is_empty(), "{errors:?}");
+            let report = state.check_invariants(&helpers);
+            assert!(report.is_ok(), "{:?}", report.errors);
             Ok(())
         })
         .unwrap();
     }
 
+    #[test]
+    fn it_expires_approvals() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        nft.runtime.syscalls.set_curr_epoch(10);
+
+        // mint a few tokens for alice
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1, 2]
+
+        {
+            // expiry cannot be set in the past
+            let err = nft.approve_until(&ALICE, &BOB, &[0], Some(9)).unwrap_err();
+            if let NFTError::NFTState(StateError::ExpiryInPast { expiry, current_epoch }) = err {
+                assert_eq!(expiry, 9);
+                assert_eq!(current_epoch, 10);
+            } else {
+                panic!("unexpected error {err:?}");
+            }
+        }
+
+        // bob is approved on token 0 until epoch 20, charlie on alice's account until epoch 15
+        nft.approve_until(&ALICE, &BOB, &[0], Some(20)).unwrap();
+        nft.approve_for_owner_until(&ALICE, &CHARLIE, Some(15)).unwrap();
+
+        {
+            // approvals are valid up to and including the expiry epoch
+            nft.runtime.syscalls.set_curr_epoch(15);
+            let res = nft.list_token_operators(0, RawBytes::default(), u64::MAX).unwrap();
+            assert!(res.operators.get(BOB_ID));
+            let res = nft.list_account_operators(&ALICE, RawBytes::default(), u64::MAX).unwrap();
+            assert!(res.operators.get(CHARLIE_ID));
+            nft.burn_from(&ALICE, &CHARLIE, &[2]).unwrap();
+            // alice: [0, 1]
+        }
+
+        {
+            // charlie's account-level approval has lapsed
+            nft.runtime.syscalls.set_curr_epoch(16);
+            let err = nft
+                .transfer_from(
+                    &ALICE,
+                    &CHARLIE,
+                    &CHARLIE,
+                    &[1],
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap_err();
+            if let NFTError::NFTState(StateError::NotAuthorized { actor, token_id }) = err {
+                assert_eq!(actor, CHARLIE_ID);
+                assert_eq!(token_id, 1);
+            } else {
+                panic!("unexpected error {err:?}");
+            }
+            let res = nft.list_account_operators(&ALICE, RawBytes::default(), u64::MAX).unwrap();
+            assert!(res.operators.is_empty());
+        }
+
+        {
+            // bob's token-level approval has lapsed
+            nft.runtime.syscalls.set_curr_epoch(21);
+            nft.burn_from(&ALICE, &BOB, &[0]).unwrap_err();
+            let res = nft.list_token_operators(0, RawBytes::default(), u64::MAX).unwrap();
+            assert!(res.operators.is_empty());
+            let res = nft.list_operator_tokens(&BOB, RawBytes::default(), u64::MAX).unwrap();
+            assert!(res.tokens.is_empty());
+        }
+
+        {
+            // re-approving cleans up expired approvals and can grant indefinite approval
+            nft.approve(&ALICE, &CHARLIE, &[0]).unwrap();
+            nft.runtime.syscalls.set_curr_epoch(1000);
+            let mut hook = nft
+                .transfer_from(
+                    &ALICE,
+                    &CHARLIE,
+                    &CHARLIE,
+                    &[0],
+                    RawBytes::default(),
+                    RawBytes::default(),
+                )
+                .unwrap();
+            hook.call(&nft.runtime).unwrap();
+            assert_eq!(nft.owner_of(0).unwrap(), CHARLIE_ID);
+        }
+
+        nft.check_invariants().unwrap();
+    }
+
     #[test]
     fn it_enumerates_token_information() {
         let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
@@ -1260,6 +2902,15 @@ mod test {
             assert!(res.next_cursor.is_none());
         }
 
+        // List owned tokens in pages of size three
+        {
+            let res = nft.list_owned_tokens(&ALICE, RawBytes::default(), 3).unwrap();
+            assert_eq!(res.tokens, bitfield![1, 1, 1]);
+            let res = nft.list_owned_tokens(&ALICE, res.next_cursor.unwrap(), 3).unwrap();
+            assert_eq!(res.tokens, bitfield![0, 0, 0, 1]);
+            assert!(res.next_cursor.is_none());
+        }
+
         // List token operators
         {
             // Charlie is an operator for alice but only explicitly approved on tokens 0 & 1
@@ -1312,6 +2963,26 @@ mod test {
             assert!(res.next_cursor.is_none());
         }
 
+        // Query individual approvals
+        {
+            let operators = nft.approved_operators(0).unwrap();
+            assert!(operators.get(BOB_ID));
+            assert!(operators.get(CHARLIE_ID));
+            assert!(nft.approved_operators(2).unwrap().is_empty());
+
+            assert!(nft.is_approved_operator(1, &CHARLIE).unwrap());
+            assert!(!nft.is_approved_operator(1, &BOB).unwrap());
+            // unresolvable addresses are never operators
+            let unknown = Address::new_secp256k1(&[1; 65]).unwrap();
+            assert!(!nft.is_approved_operator(1, &unknown).unwrap());
+
+            assert!(nft.is_account_operator(&ALICE, &CHARLIE).unwrap());
+            assert!(nft.is_account_operator(&BOB, &CHARLIE).unwrap());
+            assert!(!nft.is_account_operator(&ALICE, &BOB).unwrap());
+            assert!(!nft.is_account_operator(&CHARLIE, &ALICE).unwrap());
+            assert!(!nft.is_account_operator(&unknown, &CHARLIE).unwrap());
+        }
+
         // List account operators
         {
             // Charlie is an account operator for alice and bob
@@ -0,0 +1,485 @@
+//! Versioning of the NFT state
+//!
+//! The state is encoded as a tuple whose first element is the layout version. The layout of 5.x
+//! releases, from before versioning was introduced, starts with the root of the token data instead
+//! and is treated as version 0.
+//!
+//! Version 0 stored only the owner, operators and metadata of each token and the balance and
+//! operators of each owner, so upgrading it rewrites every entry of the token data AMT and owner
+//! data HAMT, rebuilding each owner's token set from the tokens. As the cost of that grows with the
+//! size of the collection, [`load_state`] refuses version 0 state rather than upgrading it, and a
+//! [`Migration`] upgrades it a page of entries at a time instead. Actors keep the migration in
+//! their state between messages and replace it with the upgraded state once it is done.
+//!
+//! To change the layout: copy the affected types here as the previous version, bump
+//! [`STATE_VERSION`] and add a paged upgrade from the previous version.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use cid::Cid;
+use fvm_ipld_amt::Amt;
+use fvm_ipld_bitfield::BitField;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_encoding::{CborStore, RawBytes};
+use fvm_ipld_hamt::{BytesKey, Hamt};
+use fvm_shared::ActorID;
+use serde::de::{self, DeserializeOwned, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+
+use crate::state::{
+    actor_id_key, decode_actor_id, NFTState, OwnerData, StateError, TokenData, HAMT_BIT_WIDTH,
+};
+use crate::types::{TokenID, TokenSet};
+
+type Result<T> = std::result::Result<T, StateError>;
+
+/// Version of the state layout written by this code
+pub const STATE_VERSION: u64 = 1;
+
+/// The state before versioning was introduced
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct NFTStateV0 {
+    pub token_data: Cid,
+    pub owner_data: Cid,
+    pub next_token: TokenID,
+    pub total_supply: u64,
+}
+
+/// The data of each token before versioning was introduced
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct TokenDataV0 {
+    pub owner: ActorID,
+    pub operators: BitField,
+    pub metadata: String,
+}
+
+impl From<TokenDataV0> for TokenData {
+    fn from(old: TokenDataV0) -> Self {
+        TokenData {
+            owner: old.owner,
+            operators: old.operators,
+            metadata: old.metadata,
+            operator_expiries: vec![],
+            user: None,
+            extra: RawBytes::default(),
+            parent: None,
+            children: TokenSet::default(),
+            staked_at: None,
+            operator_recipients: vec![],
+        }
+    }
+}
+
+/// The data of each owner before versioning was introduced
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct OwnerDataV0 {
+    pub balance: u64,
+    pub operators: BitField,
+}
+
+/// Loads the state at `root`, which must be in the current layout
+///
+/// Version 0 state fails with [`StateError::MigrationRequired`] and needs to be upgraded with a
+/// [`Migration`] first.
+pub fn load_state<BS: Blockstore>(bs: &BS, root: &Cid) -> Result<NFTState> {
+    let StoredVersion(version) = get(bs, root)?;
+    match version {
+        0 => Err(StateError::MigrationRequired),
+        STATE_VERSION => get(bs, root),
+        v => Err(StateError::InvariantFailed(format!("unsupported state version {v}"))),
+    }
+}
+
+/// The entries of version 0 state that remain to be migrated
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub enum MigrationStage {
+    /// Tokens from this TokenID on
+    Tokens(TokenID),
+    /// Owners from this key on, or from the first if `None`
+    Owners(Option<BytesKey>),
+    /// Every entry has been migrated
+    Done,
+}
+
+/// An upgrade of version 0 state to the current layout, run a page of entries at a time
+///
+/// Tokens are migrated first, each one being added to its owner's token set. The owners are then
+/// checked against the tokens migrated to them, and those that own no tokens are copied over.
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct Migration {
+    /// The state being upgraded
+    pub old: NFTStateV0,
+    /// The upgraded state, holding the entries migrated so far
+    pub state: NFTState,
+    pub stage: MigrationStage,
+}
+
+impl Migration {
+    /// Starts upgrading the version 0 state at `root`
+    pub fn start<BS: Blockstore>(bs: &BS, root: &Cid) -> Result<Self> {
+        let StoredVersion(version) = get(bs, root)?;
+        if version != 0 {
+            return Err(StateError::InvariantFailed(format!(
+                "state version {version} does not need to be migrated"
+            )));
+        }
+        let old: NFTStateV0 = get(bs, root)?;
+        let mut state = NFTState::new(bs)?;
+        state.next_token = old.next_token;
+        state.total_supply = old.total_supply;
+        Ok(Self { old, state, stage: MigrationStage::Tokens(0) })
+    }
+
+    /// Migrates up to `limit` more tokens or owners, returning whether the migration is done
+    pub fn step<BS: Blockstore>(&mut self, bs: &BS, limit: u64) -> Result<bool> {
+        match self.stage.clone() {
+            MigrationStage::Tokens(start) => self.migrate_tokens(bs, start, limit)?,
+            MigrationStage::Owners(start) => self.migrate_owners(bs, start, limit)?,
+            MigrationStage::Done => {}
+        }
+        Ok(self.stage == MigrationStage::Done)
+    }
+
+    /// Returns the upgraded state once every entry has been migrated
+    pub fn finish(self) -> Result<NFTState> {
+        if self.stage != MigrationStage::Done {
+            return Err(StateError::MigrationRequired);
+        }
+        Ok(self.state)
+    }
+
+    fn old_owners<'bs, BS: Blockstore>(
+        &self,
+        bs: &'bs BS,
+    ) -> Result<Hamt<&'bs BS, OwnerDataV0, BytesKey>> {
+        Ok(Hamt::load_with_bit_width(&self.old.owner_data, bs, HAMT_BIT_WIDTH)?)
+    }
+
+    /// Rewrites a page of tokens, adding each one to its owner's token set
+    fn migrate_tokens<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        start: TokenID,
+        limit: u64,
+    ) -> Result<()> {
+        let old_tokens = Amt::<TokenDataV0, &BS>::load(&self.old.token_data, bs)?;
+        let mut page = Vec::new();
+        let (_, next_key) =
+            old_tokens.for_each_ranged(Some(start), Some(limit), |token_id, data| {
+                page.push((token_id, data.clone()));
+                Ok(())
+            })?;
+
+        let mut token_array = self.state.get_token_data_amt(bs)?;
+        let mut owned_tokens = BTreeMap::<ActorID, Vec<TokenID>>::new();
+        for (token_id, data) in page {
+            owned_tokens.entry(data.owner).or_default().push(token_id);
+            token_array.set(token_id, data.into())?;
+        }
+        self.state.token_data = token_array.flush()?;
+
+        let old_owners = self.old_owners(bs)?;
+        let mut owner_map = self.state.get_owner_data_hamt(bs)?;
+        for (owner, token_ids) in owned_tokens {
+            let key = actor_id_key(owner);
+            let mut data = match owner_map.get(&key)? {
+                Some(data) => data.clone(),
+                None => {
+                    let old = old_owners.get(&key)?.ok_or_else(|| {
+                        StateError::InvariantFailed(format!("owner {owner} has no owner data"))
+                    })?;
+                    OwnerData { operators: old.operators.clone(), ..OwnerData::new() }
+                }
+            };
+            data.add_tokens(bs, &token_ids)?;
+            owner_map.set(key, data)?;
+        }
+        self.state.owner_data = owner_map.flush()?;
+
+        self.stage = match next_key {
+            Some(token_id) => MigrationStage::Tokens(token_id),
+            None => MigrationStage::Owners(None),
+        };
+        Ok(())
+    }
+
+    /// Checks a page of owners' balances against the tokens migrated to them, copying over the
+    /// owners that own no tokens
+    fn migrate_owners<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        start: Option<BytesKey>,
+        limit: u64,
+    ) -> Result<()> {
+        let old_owners = self.old_owners(bs)?;
+        let mut page = Vec::new();
+        let (_, next_key) =
+            old_owners.for_each_ranged(start.as_ref(), Some(limit as usize), |key, data| {
+                page.push((key.clone(), data.clone()));
+                Ok(())
+            })?;
+
+        let mut owner_map = self.state.get_owner_data_hamt(bs)?;
+        for (key, old_data) in page {
+            let owner = decode_actor_id(&key)
+                .ok_or_else(|| StateError::InvariantFailed("invalid owner key".into()))?;
+            let balance = owner_map.get(&key)?.map(|data| data.balance).unwrap_or_default();
+            if balance != old_data.balance {
+                return Err(StateError::InvariantFailed(format!(
+                    "owner {owner} has a balance of {} but owns {balance} tokens",
+                    old_data.balance
+                )));
+            }
+            if balance == 0 {
+                owner_map
+                    .set(key, OwnerData { operators: old_data.operators, ..OwnerData::new() })?;
+            }
+        }
+        self.state.owner_data = owner_map.flush()?;
+
+        self.stage = match next_key {
+            Some(key) => MigrationStage::Owners(Some(key)),
+            None => MigrationStage::Done,
+        };
+        Ok(())
+    }
+}
+
+fn get<BS: Blockstore, T: DeserializeOwned>(bs: &BS, cid: &Cid) -> Result<T> {
+    match bs.get_cbor::<T>(cid) {
+        Ok(Some(state)) => Ok(state),
+        Ok(None) => Err(StateError::InvariantFailed("State root not found".into())),
+        Err(e) => Err(StateError::InvariantFailed(e.to_string())),
+    }
+}
+
+/// The version of a stored state, read from the first element of its tuple encoding
+struct StoredVersion(u64);
+
+impl<'de> Deserialize<'de> for StoredVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_seq(StoredVersionVisitor)
+    }
+}
+
+struct StoredVersionVisitor;
+
+impl<'de> Visitor<'de> for StoredVersionVisitor {
+    type Value = StoredVersion;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a state tuple")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        let first = seq
+            .next_element::<FirstElement>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(StoredVersion(match first {
+            FirstElement::Version(v) => v,
+            FirstElement::Unversioned => 0,
+        }))
+    }
+}
+
+/// The first element of a state tuple: a version number, or a link in the unversioned layout
+enum FirstElement {
+    Version(u64),
+    Unversioned,
+}
+
+impl<'de> Deserialize<'de> for FirstElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct FirstElementVisitor;
+
+        impl<'de> Visitor<'de> for FirstElementVisitor {
+            type Value = FirstElement;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a version number or the token data root")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Self::Value, E> {
+                Ok(FirstElement::Version(v))
+            }
+
+            // links are passed to visitors as a newtype wrapping their bytes
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Self::Value, D::Error> {
+                IgnoredAny::deserialize(deserializer)?;
+                Ok(FirstElement::Unversioned)
+            }
+        }
+
+        deserializer.deserialize_any(FirstElementVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use fvm_ipld_amt::Amt;
+    use fvm_ipld_bitfield::BitField;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::{tuple::*, CborStore};
+    use fvm_ipld_hamt::{BytesKey, Hamt};
+    use fvm_shared::ActorID;
+
+    use super::{
+        load_state, Migration, MigrationStage, NFTStateV0, OwnerDataV0, TokenDataV0, STATE_VERSION,
+    };
+    use crate::state::{
+        actor_id_key, NFTState, StateError, DEFAULT_AMT_BIT_WIDTH, HAMT_BIT_WIDTH,
+        OWNER_TOKENS_INLINE_LIMIT,
+    };
+
+    const ALICE: ActorID = 1;
+    const BOB: ActorID = 2;
+    const CAROL: ActorID = 3;
+    const DAVE: ActorID = 4;
+
+    /// Writes version 0 state in which ALICE owns enough tokens for them to be spilled from the
+    /// owner data and BOB owns the last token, with CAROL approved for the first token and by BOB,
+    /// and DAVE, who owns no tokens, approving CAROL
+    fn unversioned_state(bs: &MemoryBlockstore) -> NFTStateV0 {
+        let supply = OWNER_TOKENS_INLINE_LIMIT + 1;
+        let mut tokens = Amt::<TokenDataV0, _>::new_with_bit_width(bs, DEFAULT_AMT_BIT_WIDTH);
+        for token_id in 0..supply {
+            let owner = if token_id < OWNER_TOKENS_INLINE_LIMIT { ALICE } else { BOB };
+            let mut operators = BitField::new();
+            if token_id == 0 {
+                operators.set(CAROL);
+            }
+            let metadata = format!("ipfs://{token_id}");
+            tokens.set(token_id, TokenDataV0 { owner, operators, metadata }).unwrap();
+        }
+
+        let mut owners = Hamt::<_, OwnerDataV0, BytesKey>::new_with_bit_width(bs, HAMT_BIT_WIDTH);
+        let alice = OwnerDataV0 { balance: OWNER_TOKENS_INLINE_LIMIT, operators: BitField::new() };
+        owners.set(actor_id_key(ALICE), alice).unwrap();
+        let mut operators = BitField::new();
+        operators.set(CAROL);
+        owners
+            .set(actor_id_key(BOB), OwnerDataV0 { balance: 1, operators: operators.clone() })
+            .unwrap();
+        owners.set(actor_id_key(DAVE), OwnerDataV0 { balance: 0, operators }).unwrap();
+
+        NFTStateV0 {
+            token_data: tokens.flush().unwrap(),
+            owner_data: owners.flush().unwrap(),
+            next_token: supply,
+            total_supply: supply,
+        }
+    }
+
+    #[test]
+    fn it_migrates_unversioned_state() {
+        let bs = MemoryBlockstore::default();
+        let old = unversioned_state(&bs);
+        let cid = bs.put_cbor(&old, Code::Blake2b256).unwrap();
+
+        // the state isn't upgraded while loading
+        assert!(matches!(NFTState::load(&bs, &cid).unwrap_err(), StateError::MigrationRequired));
+
+        // each step migrates a bounded page of entries, and the migration can be stored between
+        // steps
+        let mut migration = Migration::start(&bs, &cid).unwrap();
+        let mut steps = 0;
+        loop {
+            let cid = bs.put_cbor(&migration, Code::Blake2b256).unwrap();
+            migration = bs.get_cbor(&cid).unwrap().unwrap();
+            let done = migration.step(&bs, 10).unwrap();
+            steps += 1;
+            if done {
+                break;
+            }
+            assert!(matches!(
+                migration.clone().finish().unwrap_err(),
+                StateError::MigrationRequired
+            ));
+        }
+        assert!(steps > 2);
+        assert_eq!(migration.stage, MigrationStage::Done);
+        let state = migration.finish().unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.next_token, old.next_token);
+        assert_eq!(state.total_supply, old.total_supply);
+        assert!(state.check_invariants(&bs).is_ok());
+
+        // tokens keep their owner, operators and metadata
+        assert_eq!(state.get_owner(&bs, 0).unwrap(), ALICE);
+        assert_eq!(state.get_metadata(&bs, 0).unwrap(), "ipfs://0");
+        let (operators, _) = state.list_token_operators(&bs, 0, None, u64::MAX, 0).unwrap();
+        assert_eq!(operators.iter().collect::<Vec<_>>(), vec![CAROL]);
+        assert!(state.is_owner_operator(&bs, BOB, CAROL, 0).unwrap());
+        // owners that own no tokens keep their operators
+        assert!(state.is_owner_operator(&bs, DAVE, CAROL, 0).unwrap());
+
+        // owners' token sets are rebuilt from the tokens, spilling those of large holders
+        let owners = state.get_owner_data_hamt(&bs).unwrap();
+        let alice = owners.get(&actor_id_key(ALICE)).unwrap().unwrap();
+        assert!(alice.spilled_tokens.is_some());
+        let (owned, _) = state.list_owned_tokens(&bs, ALICE, None, u64::MAX).unwrap();
+        assert_eq!(owned.len(), OWNER_TOKENS_INLINE_LIMIT);
+        let (owned, _) = state.list_owned_tokens(&bs, BOB, None, u64::MAX).unwrap();
+        assert_eq!(owned.iter().collect::<Vec<_>>(), vec![OWNER_TOKENS_INLINE_LIMIT]);
+
+        // once saved, the state loads as the current version and needs no further migration
+        let cid = state.save(&bs).unwrap();
+        assert!(Migration::start(&bs, &cid).is_err());
+        assert_eq!(load_state(&bs, &cid).unwrap(), state);
+    }
+
+    #[test]
+    fn it_rejects_inconsistent_unversioned_state() {
+        let bs = MemoryBlockstore::default();
+        let mut old = unversioned_state(&bs);
+        let mut owners = Hamt::<_, OwnerDataV0, BytesKey>::load_with_bit_width(
+            &old.owner_data,
+            &bs,
+            HAMT_BIT_WIDTH,
+        )
+        .unwrap();
+        owners
+            .set(actor_id_key(BOB), OwnerDataV0 { balance: 2, operators: BitField::new() })
+            .unwrap();
+        old.owner_data = owners.flush().unwrap();
+        let cid = bs.put_cbor(&old, Code::Blake2b256).unwrap();
+
+        let mut migration = Migration::start(&bs, &cid).unwrap();
+        let err = loop {
+            match migration.step(&bs, 10) {
+                Ok(done) => assert!(!done),
+                Err(err) => break err,
+            }
+        };
+        match err {
+            StateError::InvariantFailed(msg) => assert!(msg.contains("balance")),
+            e => panic!("unexpected error {e}"),
+        }
+    }
+
+    #[test]
+    fn it_rejects_unknown_versions() {
+        #[derive(Serialize_tuple)]
+        struct FutureState {
+            version: u64,
+            data: String,
+        }
+
+        let bs = MemoryBlockstore::default();
+        let future = FutureState { version: STATE_VERSION + 1, data: String::new() };
+        let cid = bs.put_cbor(&future, Code::Blake2b256).unwrap();
+        match load_state(&bs, &cid).unwrap_err() {
+            StateError::InvariantFailed(msg) => assert!(msg.contains("unsupported")),
+            e => panic!("unexpected error {e}"),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Composable NFTs where tokens may be owned by other tokens
+//!
+//! A token can be nested inside another token held by the same owner, forming a tree. Nested
+//! tokens keep the actor-level owner of the root of their tree and cannot be transferred or burned
+//! on their own. Transferring the root token carries every token nested beneath it, and the
+//! transfer policy and receiver hook are given the nested tokens along with the root. A token must
+//! have no nested children before it can be burned.
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_amt::Amt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::address::Address;
+
+use crate::state::{NFTState, StateError, TokenData};
+use crate::types::{TokenID, TokenSet};
+use crate::{Result, NFT};
+
+impl NFTState {
+    /// Nests a token inside a parent token
+    ///
+    /// Both tokens must exist and belong to the same owner, the child must not already be nested
+    /// and the parent must not be a descendant of the child. The predicate is checked against the
+    /// child token. It is the caller's responsibility to check that the actor using this method is
+    /// permitted to do so.
+    pub fn nest<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        child_id: TokenID,
+        parent_id: TokenID,
+        nest_predicate: F,
+    ) -> std::result::Result<(), StateError>
+    where
+        F: Fn(&TokenData, TokenID) -> std::result::Result<(), StateError>,
+    {
+        let mut token_array = self.get_token_data_amt(bs)?;
+        let mut child =
+            token_array.get(child_id)?.ok_or(StateError::TokenNotFound(child_id))?.clone();
+        let mut parent =
+            token_array.get(parent_id)?.ok_or(StateError::TokenNotFound(parent_id))?.clone();
+        nest_predicate(&child, child_id)?;
+
+        if child.parent.is_some() {
+            return Err(StateError::TokenNested(child_id));
+        }
+        if parent.owner != child.owner {
+            return Err(StateError::NotOwner { actor: parent.owner, token_id: child_id });
+        }
+        // walk up from the parent to make sure the child is not one of its ancestors
+        let mut ancestor = Some(parent_id);
+        while let Some(id) = ancestor {
+            if id == child_id {
+                return Err(StateError::NestingCycle { child: child_id, parent: parent_id });
+            }
+            ancestor = token_array
+                .get(id)?
+                .ok_or_else(|| {
+                    StateError::InvariantFailed(format!("ancestor token {id} not found"))
+                })?
+                .parent;
+        }
+
+        parent.children.set(child_id);
+        child.parent = Some(parent_id);
+        token_array.set(parent_id, parent)?;
+        token_array.set(child_id, child)?;
+        self.token_data = token_array.flush()?;
+        Ok(())
+    }
+
+    /// Removes a token from its parent, returning the id of the former parent
+    ///
+    /// The predicate is checked against the child token. It is the caller's responsibility to
+    /// check that the actor using this method is permitted to do so.
+    pub fn unnest<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        child_id: TokenID,
+        unnest_predicate: F,
+    ) -> std::result::Result<Option<TokenID>, StateError>
+    where
+        F: Fn(&TokenData, TokenID) -> std::result::Result<(), StateError>,
+    {
+        let mut token_array = self.get_token_data_amt(bs)?;
+        let mut child =
+            token_array.get(child_id)?.ok_or(StateError::TokenNotFound(child_id))?.clone();
+        unnest_predicate(&child, child_id)?;
+
+        let parent_id = match child.parent.take() {
+            Some(parent_id) => parent_id,
+            None => return Ok(None),
+        };
+        let mut parent = token_array
+            .get(parent_id)?
+            .ok_or_else(|| {
+                StateError::InvariantFailed(format!("parent token {parent_id} not found"))
+            })?
+            .clone();
+        parent.children.unset(child_id);
+        token_array.set(parent_id, parent)?;
+        token_array.set(child_id, child)?;
+        self.token_data = token_array.flush()?;
+        Ok(Some(parent_id))
+    }
+
+    /// Returns `token_ids` followed by every token nested beneath them, at any depth
+    ///
+    /// These are the tokens moved by a transfer of `token_ids`.
+    pub fn with_descendants<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_ids: &[TokenID],
+    ) -> std::result::Result<Vec<TokenID>, StateError> {
+        let token_array = self.get_token_data_amt(bs)?;
+        let mut moved = token_ids.to_vec();
+        for &token_id in token_ids {
+            let token_data =
+                token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+            moved.extend(Self::descendants(&token_array, token_data)?);
+        }
+        Ok(moved)
+    }
+
+    /// Returns every token nested beneath a token, at any depth
+    pub(crate) fn descendants<BS: Blockstore>(
+        token_array: &Amt<TokenData, &BS>,
+        token_data: &TokenData,
+    ) -> std::result::Result<Vec<TokenID>, StateError> {
+        let mut pending: Vec<TokenID> = token_data.children.iter().collect();
+        let mut descendants = Vec::new();
+        while let Some(child_id) = pending.pop() {
+            let child = token_array.get(child_id)?.ok_or_else(|| {
+                StateError::InvariantFailed(format!("nested token {child_id} not found"))
+            })?;
+            pending.extend(child.children.iter());
+            descendants.push(child_id);
+        }
+        Ok(descendants)
+    }
+
+    /// Returns the tokens nested directly inside a token
+    pub fn children_of<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+    ) -> std::result::Result<TokenSet, StateError> {
+        let token_array = self.get_token_data_amt(bs)?;
+        let token_data = token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token_data.children.clone())
+    }
+
+    /// Returns the token that a token is nested inside, if any
+    pub fn parent_of<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+    ) -> std::result::Result<Option<TokenID>, StateError> {
+        let token_array = self.get_token_data_amt(bs)?;
+        let token_data = token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token_data.parent)
+    }
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Nests `child_id` inside `parent_id`
+    ///
+    /// `caller` may be the owner of both tokens or an account-level operator of the owner
+    pub fn nest(&mut self, caller: &Address, child_id: TokenID, parent_id: TokenID) -> Result<()> {
+        let caller = self.runtime.resolve_id(caller)?;
+        let current_epoch = self.runtime.curr_epoch();
+        let owner = self.state.get_owner(&self.runtime, child_id)?;
+        self.check_policy(|policy| policy.before_nest(caller, owner, child_id, parent_id))?;
+
+        self.transaction(|state, bs| {
+            let owner = state.get_owner(bs, child_id)?;
+            let account_operator = state.is_owner_operator(bs, owner, caller, current_epoch)?;
+            state.nest(bs, child_id, parent_id, |token_data, token_id| {
+                if token_data.owner == caller || account_operator {
+                    Ok(())
+                } else {
+                    Err(StateError::NotAuthorized { actor: caller, token_id })
+                }
+            })?;
+            Ok(())
+        })
+    }
+
+    /// Removes `child_id` from the token it is nested inside, returning the former parent
+    ///
+    /// `caller` may be the owner of the token or an account-level operator of the owner
+    pub fn unnest(&mut self, caller: &Address, child_id: TokenID) -> Result<Option<TokenID>> {
+        let caller = self.runtime.resolve_id(caller)?;
+        let current_epoch = self.runtime.curr_epoch();
+
+        self.transaction(|state, bs| {
+            let owner = state.get_owner(bs, child_id)?;
+            let account_operator = state.is_owner_operator(bs, owner, caller, current_epoch)?;
+            let parent = state.unnest(bs, child_id, |token_data, token_id| {
+                if token_data.owner == caller || account_operator {
+                    Ok(())
+                } else {
+                    Err(StateError::NotAuthorized { actor: caller, token_id })
+                }
+            })?;
+            Ok(parent)
+        })
+    }
+
+    /// Returns the tokens nested directly inside a token
+    pub fn children_of(&self, token_id: TokenID) -> Result<TokenSet> {
+        Ok(self.state.children_of(&self.runtime, token_id)?)
+    }
+
+    /// Returns the token that a token is nested inside, if any
+    pub fn parent_of(&self, token_id: TokenID) -> Result<Option<TokenID>> {
+        Ok(self.state.parent_of(&self.runtime, token_id)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{
+        receiver::UniversalReceiverParams, syscalls::fake_syscalls::FakeSyscalls,
+        util::ActorRuntime,
+    };
+    use fvm_ipld_bitfield::bitfield;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, ActorID};
+
+    use crate::{receiver::FRC53TokenReceived, state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+
+    #[test]
+    fn it_nests_tokens() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 4], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1, 2, 3]
+
+        // 0 <- 1 <- 2
+        nft.nest(&ALICE, 1, 0).unwrap();
+        nft.nest(&ALICE, 2, 1).unwrap();
+        assert_eq!(nft.children_of(0).unwrap(), bitfield![0, 1]);
+        assert_eq!(nft.parent_of(2).unwrap(), Some(1));
+        assert_eq!(nft.parent_of(0).unwrap(), None);
+
+        // a token cannot be nested beneath its own descendant
+        let err = nft.nest(&ALICE, 0, 2).unwrap_err();
+        if let NFTError::NFTState(StateError::NestingCycle { child, parent }) = err {
+            assert_eq!(child, 0);
+            assert_eq!(parent, 2);
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+        nft.nest(&ALICE, 0, 0).unwrap_err();
+        // nor nested twice
+        nft.nest(&ALICE, 2, 3).unwrap_err();
+        // only the owner or an account operator can nest tokens
+        nft.nest(&BOB, 3, 0).unwrap_err();
+
+        // nested tokens cannot be moved or burned on their own
+        let err =
+            nft.transfer(&ALICE, &BOB, &[1], RawBytes::default(), RawBytes::default()).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::TokenNested(1))));
+        nft.burn(&ALICE, &[2]).unwrap_err();
+        // nor can tokens that have children be burned
+        let err = nft.burn(&ALICE, &[0]).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::HasChildren(0))));
+
+        // transferring the root carries the whole tree, which the receiver is told about
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap();
+        let intermediate = hook.call(&nft.runtime).unwrap();
+        assert_eq!(intermediate.token_ids, vec![0, 1, 2]);
+        {
+            let msg = nft.runtime.syscalls.last_message.borrow().clone().unwrap();
+            let params: UniversalReceiverParams = msg.params.unwrap().deserialize().unwrap();
+            let payload: FRC53TokenReceived = params.payload.deserialize().unwrap();
+            assert_eq!(payload.token_ids, vec![0, 1, 2]);
+        }
+        assert_eq!(nft.owner_of(0).unwrap(), BOB_ID);
+        assert_eq!(nft.owner_of(1).unwrap(), BOB_ID);
+        assert_eq!(nft.owner_of(2).unwrap(), BOB_ID);
+        assert_eq!(nft.owner_of(3).unwrap(), ALICE_ID);
+        assert_eq!(nft.balance_of(&BOB).unwrap(), 3);
+        assert_eq!(nft.balance_of(&ALICE).unwrap(), 1);
+        nft.check_invariants().unwrap();
+
+        // tokens with different owners cannot be nested
+        nft.nest(&ALICE, 3, 0).unwrap_err();
+
+        // unnested tokens are independent again
+        assert_eq!(nft.unnest(&BOB, 2).unwrap(), Some(1));
+        assert_eq!(nft.unnest(&BOB, 2).unwrap(), None);
+        assert!(nft.children_of(1).unwrap().is_empty());
+        nft.burn(&BOB, &[2]).unwrap();
+        nft.check_invariants().unwrap();
+    }
+}
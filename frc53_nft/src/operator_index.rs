@@ -0,0 +1,300 @@
+//! Reverse index of the tokens each operator is approved for
+//!
+//! Token-level approvals are stored with each token, so finding the tokens an operator may act on
+//! otherwise means scanning every token in the collection. Once enabled, the index maps each
+//! operator to the set of tokens it holds a token-level approval for, and is kept up to date as
+//! tokens are approved, revoked, transferred and burned. Enabling the index on an existing
+//! collection builds it from the approvals already granted.
+//!
+//! Approvals that have expired stay in the index until they are pruned from their token, just as
+//! they stay in the token's own operator set. Queries skip them.
+use std::collections::BTreeMap;
+
+use cid::Cid;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_bitfield::BitField;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::RawBytes;
+use fvm_ipld_hamt::{BytesKey, Hamt};
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::ActorID;
+
+use crate::state::{actor_id_key, decode_actor_id, Cursor, NFTState, StateError, HAMT_BIT_WIDTH};
+use crate::types::{ListOperatorTokensReturn, TokenID, TokenSet};
+use crate::util::ExpiringOperatorSet;
+use crate::{Result, NFT};
+
+type OperatorMap<'bs, BS> = Hamt<&'bs BS, TokenSet, BytesKey>;
+
+/// The operator index loaded for an operation that changes token-level approvals
+///
+/// Changes are ignored if the index is not enabled.
+pub(crate) struct OperatorIndex<'bs, BS: Blockstore> {
+    map: Option<OperatorMap<'bs, BS>>,
+}
+
+impl<'bs, BS: Blockstore> OperatorIndex<'bs, BS> {
+    /// Records a change in the operators approved for a token
+    pub(crate) fn update(
+        &mut self,
+        token_id: TokenID,
+        before: &BitField,
+        after: &BitField,
+    ) -> std::result::Result<(), StateError> {
+        let Some(map) = &mut self.map else {
+            return Ok(());
+        };
+        for operator in before.iter().filter(|&operator| !after.get(operator)) {
+            let key = actor_id_key(operator);
+            let Some(tokens) = map.get(&key)? else {
+                continue;
+            };
+            let mut tokens = tokens.clone();
+            tokens.unset(token_id);
+            if tokens.is_empty() {
+                map.delete(&key)?;
+            } else {
+                map.set(key, tokens)?;
+            }
+        }
+        for operator in after.iter().filter(|&operator| !before.get(operator)) {
+            let key = actor_id_key(operator);
+            let mut tokens = map.get(&key)?.cloned().unwrap_or_default();
+            tokens.set(token_id);
+            map.set(key, tokens)?;
+        }
+        Ok(())
+    }
+
+    /// Records that a token no longer has any approved operators
+    pub(crate) fn clear(
+        &mut self,
+        token_id: TokenID,
+        operators: &BitField,
+    ) -> std::result::Result<(), StateError> {
+        self.update(token_id, operators, &BitField::default())
+    }
+
+    /// Flushes the index, returning its new root if it is enabled
+    pub(crate) fn flush(self) -> std::result::Result<Option<Cid>, StateError> {
+        Ok(self.map.map(|mut map| map.flush()).transpose()?)
+    }
+}
+
+impl NFTState {
+    /// Starts indexing the tokens each operator is approved for, indexing existing approvals
+    ///
+    /// Has no effect if the index is already enabled
+    pub fn enable_operator_index<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+    ) -> std::result::Result<(), StateError> {
+        if self.operator_index.is_some() {
+            return Ok(());
+        }
+        let mut approved = BTreeMap::<ActorID, TokenSet>::new();
+        self.get_token_data_amt(bs)?.for_each(|token_id, data| {
+            for operator in data.operators.iter() {
+                approved.entry(operator).or_default().set(token_id);
+            }
+            Ok(())
+        })?;
+        let mut map = OperatorMap::new_with_bit_width(bs, HAMT_BIT_WIDTH);
+        for (operator, tokens) in approved {
+            map.set(actor_id_key(operator), tokens)?;
+        }
+        self.operator_index = Some(map.flush()?);
+        Ok(())
+    }
+
+    /// Loads the operator index for an operation that changes token-level approvals
+    pub(crate) fn load_operator_index<'bs, BS: Blockstore>(
+        &self,
+        bs: &'bs BS,
+    ) -> std::result::Result<OperatorIndex<'bs, BS>, StateError> {
+        let map = self
+            .operator_index
+            .map(|root| OperatorMap::load_with_bit_width(&root, bs, HAMT_BIT_WIDTH))
+            .transpose()?;
+        Ok(OperatorIndex { map })
+    }
+
+    /// Returns the tokens each operator holds a token-level approval for, expired or not, or None
+    /// if the index is not enabled
+    pub fn get_operator_index<BS: Blockstore>(
+        &self,
+        bs: &BS,
+    ) -> std::result::Result<Option<BTreeMap<ActorID, TokenSet>>, StateError> {
+        let Some(root) = &self.operator_index else {
+            return Ok(None);
+        };
+        let mut entries = Vec::new();
+        OperatorMap::load_with_bit_width(root, bs, HAMT_BIT_WIDTH)?.for_each(|key, tokens| {
+            entries.push((key.clone(), tokens.clone()));
+            Ok(())
+        })?;
+        let mut index = BTreeMap::new();
+        for (key, tokens) in entries {
+            let operator = decode_actor_id(&key).ok_or_else(|| {
+                StateError::InvariantFailed(format!("invalid operator key {key:?}"))
+            })?;
+            index.insert(operator, tokens);
+        }
+        Ok(Some(index))
+    }
+
+    /// Lists the tokens for which an operator holds an unexpired token-level approval, using the
+    /// operator index
+    ///
+    /// Pages through the tokens the operator is indexed against, so a page may hold fewer than
+    /// `limit` tokens if some of the approvals have expired. Fails if the index is not enabled.
+    pub fn list_approved_tokens<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        operator: ActorID,
+        cursor: Option<Cursor>,
+        limit: u64,
+        current_epoch: ChainEpoch,
+    ) -> std::result::Result<(TokenSet, Option<Cursor>), StateError> {
+        let root = self.operator_index.ok_or(StateError::OperatorIndexDisabled)?;
+        if let Some(cursor) = &cursor {
+            if cursor.root != root {
+                return Err(StateError::InvalidCursor);
+            }
+        }
+
+        let map = OperatorMap::load_with_bit_width(&root, bs, HAMT_BIT_WIDTH)?;
+        let approved = match map.get(&actor_id_key(operator))? {
+            Some(tokens) => tokens.clone(),
+            None => return Ok((TokenSet::new(), None)),
+        };
+        let token_array = self.get_token_data_amt(bs)?;
+
+        let range_start = cursor.map(|c| c.index).unwrap_or(0);
+        let range_end = range_start.saturating_add(limit);
+        let mut token_ids = TokenSet::new();
+        for token_id in approved.iter().skip(range_start as usize).take(limit as usize) {
+            let token_data = token_array.get(token_id)?.ok_or_else(|| {
+                StateError::InvariantFailed(format!("indexed token {token_id} not found"))
+            })?;
+            if token_data.is_active_operator(&operator, current_epoch) {
+                token_ids.set(token_id);
+            }
+        }
+
+        let next_cursor = match approved.len() > range_end {
+            true => Some(Cursor::new(root, range_end)),
+            false => None,
+        };
+        Ok((token_ids, next_cursor))
+    }
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Starts indexing the tokens each operator is approved for, so that
+    /// `approved_tokens_of_operator` can be queried
+    pub fn enable_operator_index(&mut self) -> Result<()> {
+        self.transaction(|state, bs| Ok(state.enable_operator_index(bs)?))
+    }
+
+    /// Enumerates a page of the tokens for which an operator holds an unexpired token-level
+    /// approval
+    ///
+    /// Unlike [`list_operator_tokens`](Self::list_operator_tokens), which scans every token in the
+    /// collection, this reads the operator index and fails if it isn't enabled. Account-level
+    /// approvals are not included.
+    pub fn approved_tokens_of_operator(
+        &self,
+        operator: &Address,
+        cursor: RawBytes,
+        limit: u64,
+    ) -> Result<ListOperatorTokensReturn> {
+        let operator_id = self.runtime.resolve_id(operator)?;
+        let cursor = Cursor::from_bytes(cursor)?;
+        let (tokens, next_cursor) = self.state.list_approved_tokens(
+            &self.runtime,
+            operator_id,
+            cursor,
+            limit,
+            self.runtime.curr_epoch(),
+        )?;
+        let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
+        Ok(ListOperatorTokensReturn { tokens, next_cursor })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, ActorID};
+
+    use crate::{state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+    const CHARLIE_ID: ActorID = 111;
+    const CHARLIE: Address = Address::new_id(CHARLIE_ID);
+
+    #[test]
+    fn it_indexes_tokens_by_operator() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        nft.runtime.syscalls.set_curr_epoch(5);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 5], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        // approvals granted before the index is enabled are indexed when it is
+        nft.approve(&ALICE, &BOB, &[0, 1]).unwrap();
+        let err = nft.approved_tokens_of_operator(&BOB, RawBytes::default(), 10).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::OperatorIndexDisabled)));
+        nft.enable_operator_index().unwrap();
+
+        nft.approve_until(&ALICE, &BOB, &[2, 3], Some(20)).unwrap();
+        nft.approve(&ALICE, &CHARLIE, &[4]).unwrap();
+
+        // the tokens are paged through in order
+        let page = nft.approved_tokens_of_operator(&BOB, RawBytes::default(), 2).unwrap();
+        assert_eq!(page.tokens.iter().collect::<Vec<_>>(), vec![0, 1]);
+        let cursor = page.next_cursor.unwrap();
+        let page = nft.approved_tokens_of_operator(&BOB, cursor.clone(), 2).unwrap();
+        assert_eq!(page.tokens.iter().collect::<Vec<_>>(), vec![2, 3]);
+        assert!(page.next_cursor.is_none());
+
+        // revoking, transferring and burning remove tokens from the index
+        nft.revoke(&ALICE, &BOB, &[1]).unwrap();
+        let mut hook =
+            nft.transfer(&ALICE, &CHARLIE, &[0], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        nft.burn(&ALICE, &[4]).unwrap();
+
+        let index = nft.state.get_operator_index(&nft.runtime).unwrap().unwrap();
+        assert_eq!(index.keys().copied().collect::<Vec<_>>(), vec![BOB_ID]);
+        assert_eq!(index[&BOB_ID].iter().collect::<Vec<_>>(), vec![2, 3]);
+        let page = nft.approved_tokens_of_operator(&CHARLIE, RawBytes::default(), 10).unwrap();
+        assert!(page.tokens.is_empty());
+
+        // cursors are invalidated by changes to the index
+        let err = nft.approved_tokens_of_operator(&BOB, cursor, 2).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::InvalidCursor)));
+
+        // expired approvals are skipped
+        nft.runtime.syscalls.set_curr_epoch(21);
+        let page = nft.approved_tokens_of_operator(&BOB, RawBytes::default(), 10).unwrap();
+        assert_eq!(page.tokens.iter().collect::<Vec<_>>(), vec![2]);
+
+        nft.check_invariants().unwrap();
+    }
+}
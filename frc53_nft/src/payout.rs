@@ -0,0 +1,290 @@
+//! Royalty payouts split across several recipients
+//!
+//! A collection may store a set of payout shares so that royalties it receives, either in FIL or in
+//! an FRC-46 token, can be forwarded to each recipient in proportion to its share. Creators with a
+//! multi-party split can then receive royalties through the collection actor itself rather than
+//! routing them through a separate splitter actor.
+use std::collections::HashSet;
+
+use frc42_dispatch::method_hash;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::bigint::{BigInt, Zero};
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::{ActorID, Response, METHOD_SEND};
+use thiserror::Error;
+
+use crate::state::{NFTState, StateError};
+use crate::{Result, NFT};
+
+/// A recipient's share of every payout
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct PayoutShare {
+    pub recipient: ActorID,
+    /// The recipient receives `shares / total shares` of each payout
+    pub shares: u64,
+}
+
+/// The amount paid to a recipient out of a distribution
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct Payout {
+    pub recipient: ActorID,
+    pub amount: TokenAmount,
+}
+
+/// Parameters of the FRC-46 Transfer method
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+struct TokenTransferParams {
+    to: Address,
+    amount: TokenAmount,
+    operator_data: RawBytes,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PayoutError {
+    #[error("no payout shares are configured for the collection")]
+    NoPayoutShares,
+    #[error("cannot distribute a negative amount {0}")]
+    NegativeAmount(TokenAmount),
+    #[error("token granularity must be at least 1")]
+    InvalidGranularity,
+    #[error("payout to {recipient:?} failed with exit_code={exit_code:?}")]
+    SendFailed { recipient: ActorID, exit_code: ExitCode },
+}
+
+impl From<&PayoutError> for ExitCode {
+    fn from(error: &PayoutError) -> Self {
+        match error {
+            PayoutError::NoPayoutShares => ExitCode::USR_ILLEGAL_STATE,
+            PayoutError::NegativeAmount(_) | PayoutError::InvalidGranularity => {
+                ExitCode::USR_ILLEGAL_ARGUMENT
+            }
+            PayoutError::SendFailed { recipient: _, exit_code } => *exit_code,
+        }
+    }
+}
+
+impl NFTState {
+    /// Replaces the payout shares of the collection
+    ///
+    /// Each recipient may appear only once and must hold a non-zero share. An empty list disables
+    /// payouts.
+    pub fn set_payout_shares(
+        &mut self,
+        payout_shares: Vec<PayoutShare>,
+    ) -> std::result::Result<(), StateError> {
+        let mut recipients = HashSet::new();
+        let unique = payout_shares.iter().all(|s| recipients.insert(s.recipient));
+        let non_zero = payout_shares.iter().all(|s| s.shares > 0);
+        let total = payout_shares.iter().try_fold(0u64, |total, s| total.checked_add(s.shares));
+        if !unique || !non_zero || total.is_none() {
+            return Err(StateError::InvalidPayoutShares);
+        }
+        self.payout_shares = payout_shares;
+        Ok(())
+    }
+
+    /// Splits an amount across the payout recipients in proportion to their shares
+    ///
+    /// Each recipient's portion is rounded down to a multiple of `granularity` (in atto units) and
+    /// any remainder goes to the first recipient, so the portions add up to `amount` rounded down
+    /// to a multiple of `granularity`. Returns an empty list if no shares are configured.
+    pub fn split_payout(&self, amount: &TokenAmount, granularity: u64) -> Vec<Payout> {
+        let total: u64 = self.payout_shares.iter().map(|s| s.shares).sum();
+        if total == 0 {
+            return vec![];
+        }
+        let granularity = BigInt::from(granularity);
+        let units = amount.atto() / &granularity;
+        let mut payouts: Vec<Payout> = self
+            .payout_shares
+            .iter()
+            .map(|s| Payout {
+                recipient: s.recipient,
+                amount: TokenAmount::from_atto(&units * s.shares / total * &granularity),
+            })
+            .collect();
+        let paid = payouts.iter().fold(TokenAmount::zero(), |paid, p| paid + &p.amount);
+        payouts[0].amount += TokenAmount::from_atto(units * &granularity) - paid;
+        payouts
+    }
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Sets the recipients of payouts and their shares, replacing any existing configuration
+    ///
+    /// Access control is the responsibility of the calling actor.
+    pub fn set_payout_shares(&mut self, payout_shares: &[(Address, u64)]) -> Result<()> {
+        let payout_shares = payout_shares
+            .iter()
+            .map(|(recipient, shares)| -> Result<PayoutShare> {
+                Ok(PayoutShare {
+                    recipient: self.runtime.resolve_or_init(recipient)?,
+                    shares: *shares,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.transaction(|state, _bs| Ok(state.set_payout_shares(payout_shares)?))
+    }
+
+    /// Returns the configured payout recipients and their shares
+    pub fn payout_shares(&self) -> Vec<PayoutShare> {
+        self.state.payout_shares.clone()
+    }
+
+    /// Sends `amount` of FIL held by the collection actor to the payout recipients
+    ///
+    /// Returns the amount paid to each recipient. Recipients whose portion rounds down to zero are
+    /// not sent a message.
+    pub fn distribute(&self, amount: &TokenAmount) -> Result<Vec<Payout>> {
+        self.send_payouts(amount, 1, |recipient, amount| {
+            Ok(self.runtime.send(recipient, METHOD_SEND, None, amount.clone())?)
+        })
+    }
+
+    /// Transfers `amount` of an FRC-46 token held by the collection actor to the payout recipients
+    ///
+    /// `granularity` must be the token's `Granularity`, as each portion is rounded down to a
+    /// multiple of it for the transfer to succeed. Any part of `amount` that is not a multiple of
+    /// it stays with the collection. Returns the amount paid to each recipient. Recipients whose
+    /// portion rounds down to zero are not sent a transfer.
+    pub fn distribute_token(
+        &self,
+        token: &Address,
+        amount: &TokenAmount,
+        granularity: u64,
+    ) -> Result<Vec<Payout>> {
+        self.send_payouts(amount, granularity, |recipient, amount| {
+            let params = IpldBlock::serialize_cbor(&TokenTransferParams {
+                to: *recipient,
+                amount: amount.clone(),
+                operator_data: RawBytes::default(),
+            })?;
+            Ok(self.runtime.send(token, method_hash!("Transfer"), params, TokenAmount::zero())?)
+        })
+    }
+
+    /// Splits `amount` across the payout recipients and calls `send` for each non-zero portion
+    fn send_payouts<F>(
+        &self,
+        amount: &TokenAmount,
+        granularity: u64,
+        send: F,
+    ) -> Result<Vec<Payout>>
+    where
+        F: Fn(&Address, &TokenAmount) -> Result<Response>,
+    {
+        if amount < &TokenAmount::zero() {
+            return Err(PayoutError::NegativeAmount(amount.clone()).into());
+        }
+        if granularity == 0 {
+            return Err(PayoutError::InvalidGranularity.into());
+        }
+        let payouts = self.state.split_payout(amount, granularity);
+        if payouts.is_empty() {
+            return Err(PayoutError::NoPayoutShares.into());
+        }
+        for payout in payouts.iter().filter(|p| !p.amount.is_zero()) {
+            let res = send(&Address::new_id(payout.recipient), &payout.amount)?;
+            if !res.exit_code.is_success() {
+                return Err(PayoutError::SendFailed {
+                    recipient: payout.recipient,
+                    exit_code: res.exit_code,
+                }
+                .into());
+            }
+        }
+        Ok(payouts)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use frc42_dispatch::method_hash;
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::{address::Address, econ::TokenAmount, error::ExitCode, ActorID, METHOD_SEND};
+
+    use super::{Payout, PayoutError, PayoutShare, TokenTransferParams};
+    use crate::{state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+    const TOKEN: Address = Address::new_id(1000);
+
+    #[test]
+    fn it_splits_payouts_by_share() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let err = nft.distribute(&TokenAmount::from_atto(10)).unwrap_err();
+        assert!(matches!(err, NFTError::Payout(PayoutError::NoPayoutShares)));
+
+        // recipients must be unique with non-zero shares
+        let err = nft.set_payout_shares(&[(ALICE, 1), (ALICE, 2)]).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::InvalidPayoutShares)));
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_ILLEGAL_ARGUMENT);
+        nft.set_payout_shares(&[(ALICE, 1), (BOB, 0)]).unwrap_err();
+
+        nft.set_payout_shares(&[(ALICE, 1), (BOB, 2)]).unwrap();
+        assert_eq!(
+            nft.payout_shares(),
+            vec![
+                PayoutShare { recipient: ALICE_ID, shares: 1 },
+                PayoutShare { recipient: BOB_ID, shares: 2 }
+            ]
+        );
+
+        // the rounding remainder goes to the first recipient
+        let payouts = nft.distribute(&TokenAmount::from_atto(10)).unwrap();
+        assert_eq!(
+            payouts,
+            vec![
+                Payout { recipient: ALICE_ID, amount: TokenAmount::from_atto(4) },
+                Payout { recipient: BOB_ID, amount: TokenAmount::from_atto(6) }
+            ]
+        );
+        {
+            let msg = nft.runtime.syscalls.last_message.borrow().clone().unwrap();
+            assert_eq!(msg.method, METHOD_SEND);
+            assert_eq!(msg.value, TokenAmount::from_atto(6));
+        }
+
+        let payouts = nft.distribute_token(&TOKEN, &TokenAmount::from_atto(3), 1).unwrap();
+        assert_eq!(payouts[1], Payout { recipient: BOB_ID, amount: TokenAmount::from_atto(2) });
+        {
+            let msg = nft.runtime.syscalls.last_message.borrow().clone().unwrap();
+            assert_eq!(msg.method, method_hash!("Transfer"));
+            assert_eq!(msg.value, TokenAmount::from_atto(0));
+            let params: TokenTransferParams = msg.params.unwrap().deserialize().unwrap();
+            assert_eq!(params.to, BOB);
+            assert_eq!(params.amount, TokenAmount::from_atto(2));
+        }
+
+        // portions of a token are multiples of its granularity, with the remainder kept back
+        let payouts = nft.distribute_token(&TOKEN, &TokenAmount::from_atto(107), 10).unwrap();
+        assert_eq!(
+            payouts,
+            vec![
+                Payout { recipient: ALICE_ID, amount: TokenAmount::from_atto(40) },
+                Payout { recipient: BOB_ID, amount: TokenAmount::from_atto(60) }
+            ]
+        );
+        let err = nft.distribute_token(&TOKEN, &TokenAmount::from_atto(10), 0).unwrap_err();
+        assert!(matches!(err, NFTError::Payout(PayoutError::InvalidGranularity)));
+
+        nft.distribute(&TokenAmount::from_atto(-1)).unwrap_err();
+    }
+}
@@ -0,0 +1,272 @@
+//! Collection-specific rules applied to transfers, burns and mints
+//!
+//! A `TransferPolicy` attached to an `NFT` handle is consulted before any tokens change hands,
+//! letting a collection reject operations (e.g. to make tokens soul-bound, restrict recipients to
+//! an allowlist or require transfers to go through a royalty-paying marketplace) without changing
+//! the underlying state logic. Policies are not consulted when `NFTState` is used directly.
+use fvm_shared::ActorID;
+use thiserror::Error;
+
+use crate::types::TokenID;
+
+/// Returned by a TransferPolicy to reject an operation, with a reason for the caller
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[error("rejected by transfer policy: {0}")]
+pub struct PolicyViolation(pub String);
+
+pub type PolicyResult = std::result::Result<(), PolicyViolation>;
+
+/// Hooks called by the NFT handle before mutating token ownership
+///
+/// Each hook is called once per operation, after addresses have been resolved and before any
+/// state is changed. All hooks permit the operation by default.
+pub trait TransferPolicy {
+    /// Called before `token_ids` are moved from `from` to `to` by `operator`
+    ///
+    /// `operator` is the same as `from` when the owner transfers their own tokens. `token_ids`
+    /// includes the tokens nested inside the transferred tokens, which move along with them.
+    fn before_transfer(
+        &self,
+        _operator: ActorID,
+        _from: ActorID,
+        _to: ActorID,
+        _token_ids: &[TokenID],
+    ) -> PolicyResult {
+        Ok(())
+    }
+
+    /// Called before `token_ids` belonging to `owner` are burned by `operator`
+    fn before_burn(
+        &self,
+        _operator: ActorID,
+        _owner: ActorID,
+        _token_ids: &[TokenID],
+    ) -> PolicyResult {
+        Ok(())
+    }
+
+    /// Called before `token_ids` are minted to `to` by `operator`
+    fn before_mint(
+        &self,
+        _operator: ActorID,
+        _to: ActorID,
+        _token_ids: &[TokenID],
+    ) -> PolicyResult {
+        Ok(())
+    }
+
+    /// Called before `child_id` belonging to `owner` is nested inside `parent_id` by `operator`,
+    /// after which it moves whenever its parent is transferred
+    fn before_nest(
+        &self,
+        _operator: ActorID,
+        _owner: ActorID,
+        _child_id: TokenID,
+        _parent_id: TokenID,
+    ) -> PolicyResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, ActorID};
+
+    use super::{PolicyResult, PolicyViolation, TransferPolicy};
+    use crate::{state::StateError, types::TokenID, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+    const CHARLIE_ID: ActorID = 111;
+    const CHARLIE: Address = Address::new_id(CHARLIE_ID);
+
+    /// Tokens can be minted and burned but never transferred
+    struct SoulBound;
+
+    impl TransferPolicy for SoulBound {
+        fn before_transfer(
+            &self,
+            _operator: ActorID,
+            _from: ActorID,
+            _to: ActorID,
+            _token_ids: &[TokenID],
+        ) -> PolicyResult {
+            Err(PolicyViolation("tokens are soul-bound".into()))
+        }
+    }
+
+    /// Tokens may only be held by allowlisted actors
+    struct Allowlist(Vec<ActorID>);
+
+    impl TransferPolicy for Allowlist {
+        fn before_transfer(
+            &self,
+            _operator: ActorID,
+            _from: ActorID,
+            to: ActorID,
+            _token_ids: &[TokenID],
+        ) -> PolicyResult {
+            self.before_mint(0, to, &[])
+        }
+
+        fn before_mint(
+            &self,
+            _operator: ActorID,
+            to: ActorID,
+            _token_ids: &[TokenID],
+        ) -> PolicyResult {
+            if self.0.contains(&to) {
+                Ok(())
+            } else {
+                Err(PolicyViolation(format!("{to} is not allowlisted")))
+            }
+        }
+    }
+
+    #[test]
+    fn it_enforces_soul_binding() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state).with_policy(&SoulBound);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        let err =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap_err();
+        if let NFTError::Policy(PolicyViolation(reason)) = err {
+            assert_eq!(reason, "tokens are soul-bound");
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+
+        // operators are bound by the same policy
+        nft.approve_for_owner(&ALICE, &BOB).unwrap();
+        nft.transfer_from(&ALICE, &BOB, &BOB, &[0], RawBytes::default(), RawBytes::default())
+            .unwrap_err();
+        assert_eq!(nft.owner_of(0).unwrap(), ALICE_ID);
+
+        // burning is still permitted
+        nft.burn(&ALICE, &[1]).unwrap();
+        nft.check_invariants().unwrap();
+    }
+
+    /// Only the listed tokens are soul-bound, and they may not be nested inside other tokens
+    struct SoulBoundTokens(Vec<TokenID>);
+
+    impl TransferPolicy for SoulBoundTokens {
+        fn before_transfer(
+            &self,
+            _operator: ActorID,
+            _from: ActorID,
+            _to: ActorID,
+            token_ids: &[TokenID],
+        ) -> PolicyResult {
+            match token_ids.iter().find(|id| self.0.contains(id)) {
+                Some(id) => Err(PolicyViolation(format!("token {id} is soul-bound"))),
+                None => Ok(()),
+            }
+        }
+
+        fn before_nest(
+            &self,
+            _operator: ActorID,
+            _owner: ActorID,
+            child_id: TokenID,
+            _parent_id: TokenID,
+        ) -> PolicyResult {
+            self.before_transfer(0, 0, 0, &[child_id])
+        }
+    }
+
+    #[test]
+    fn it_enforces_soul_binding_of_nested_tokens() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // 0 <- 1, nested before the policy was attached
+        nft.nest(&ALICE, 1, 0).unwrap();
+        let helper = nft.runtime;
+
+        let policy = SoulBoundTokens(vec![1, 2]);
+        let mut nft = NFT::wrap(helper, &mut state).with_policy(&policy);
+        // the policy sees the tokens nested inside the transferred token
+        let err =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap_err();
+        if let NFTError::Policy(PolicyViolation(reason)) = err {
+            assert_eq!(reason, "token 1 is soul-bound");
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+        nft.approve_for_owner(&ALICE, &BOB).unwrap();
+        nft.transfer_from(&ALICE, &BOB, &BOB, &[0], RawBytes::default(), RawBytes::default())
+            .unwrap_err();
+        assert_eq!(nft.owner_of(0).unwrap(), ALICE_ID);
+        assert_eq!(nft.owner_of(1).unwrap(), ALICE_ID);
+
+        // soul-bound tokens cannot be nested to carry them along with another token
+        let err = nft.nest(&ALICE, 2, 0).unwrap_err();
+        assert!(matches!(err, NFTError::Policy(_)));
+        assert_eq!(nft.parent_of(2).unwrap(), None);
+
+        // once unnested, the parent moves freely
+        nft.unnest(&ALICE, 1).unwrap();
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.owner_of(0).unwrap(), BOB_ID);
+        assert_eq!(nft.owner_of(1).unwrap(), ALICE_ID);
+        // and nonexistent tokens are still reported as such
+        let err =
+            nft.transfer(&ALICE, &BOB, &[3], RawBytes::default(), RawBytes::default()).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::TokenNotFound(3))));
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_enforces_allowlists() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let policy = Allowlist(vec![ALICE_ID, BOB_ID]);
+        let mut nft = NFT::wrap(helper, &mut state).with_policy(&policy);
+
+        nft.mint(&ALICE, &CHARLIE, vec![String::new()], RawBytes::default(), RawBytes::default())
+            .unwrap_err();
+        assert_eq!(nft.total_supply(), 0);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.owner_of(0).unwrap(), BOB_ID);
+
+        let err = nft
+            .transfer_multi(
+                &ALICE,
+                &[(BOB, vec![]), (CHARLIE, vec![1])],
+                RawBytes::default(),
+                RawBytes::default(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, NFTError::Policy(_)));
+        assert_eq!(nft.owner_of(1).unwrap(), ALICE_ID);
+
+        nft.check_invariants().unwrap();
+    }
+}
@@ -6,7 +6,9 @@ use serde_tuple::{Deserialize_tuple, Serialize_tuple};
 
 use crate::types::TokenID;
 
-pub const FRC53_TOKEN_TYPE: ReceiverType = method_hash!("FRC53") as u32;
+pub use fvm_actor_utils::receiver::universal::{FRC53TokenReceived, FRC53_TOKEN_TYPE};
+
+pub const FRC53_REDEEM_TYPE: ReceiverType = method_hash!("FRC53Redeem") as u32;
 
 pub trait FRC53ReceiverHook<T: RecipientData> {
     fn new_frc53(
@@ -14,6 +16,12 @@ pub trait FRC53ReceiverHook<T: RecipientData> {
         frc53_params: FRC53TokenReceived,
         result_data: T,
     ) -> std::result::Result<ReceiverHook<T>, ReceiverHookError>;
+
+    fn new_frc53_redeem(
+        address: Address,
+        redeem_params: FRC53TokensRedeemed,
+        result_data: T,
+    ) -> std::result::Result<ReceiverHook<T>, ReceiverHookError>;
 }
 
 impl<T: RecipientData> FRC53ReceiverHook<T> for ReceiverHook<T> {
@@ -30,19 +38,33 @@ impl<T: RecipientData> FRC53ReceiverHook<T> for ReceiverHook<T> {
             result_data,
         ))
     }
+
+    /// Construct a new ReceiverHook call notifying a redeemer of burnt FRC53 tokens
+    fn new_frc53_redeem(
+        address: Address,
+        redeem_params: FRC53TokensRedeemed,
+        result_data: T,
+    ) -> std::result::Result<ReceiverHook<T>, ReceiverHookError> {
+        Ok(ReceiverHook::new(
+            address,
+            RawBytes::serialize(redeem_params)?,
+            FRC53_REDEEM_TYPE,
+            result_data,
+        ))
+    }
 }
 
-/// Receive parameters for an FRC53 token
+/// Notification parameters for FRC53 tokens burnt in order to be redeemed
 #[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
-pub struct FRC53TokenReceived {
-    /// The account that the tokens are being sent to (the receiver address)
-    pub to: ActorID,
-    /// Address of the operator that initiated the transfer/mint
-    pub operator: ActorID,
-    /// Amount of tokens being transferred/minted
+pub struct FRC53TokensRedeemed {
+    /// The account that owned the burnt tokens
+    pub from: ActorID,
+    /// The actor being notified of the redemption
+    pub redeemer: ActorID,
+    /// The tokens that were burnt
     pub token_ids: Vec<TokenID>,
-    /// Data specified by the operator during transfer/mint
+    /// Data specified by the owner when redeeming
     pub operator_data: RawBytes,
-    /// Additional data specified by the token-actor during transfer/mint
+    /// Additional data specified by the token-actor when redeeming
     pub token_data: RawBytes,
 }
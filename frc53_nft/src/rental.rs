@@ -0,0 +1,227 @@
+//! Time-limited usage rights for NFTs
+//!
+//! The owner of a token (or an operator acting on their behalf) may grant another actor the "user"
+//! role on a token until an expiry epoch. The user role confers no rights to transfer, approve or
+//! burn the token, it only records who is entitled to use it. The role is void once the expiry
+//! epoch has passed and is cleared whenever the token is transferred.
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::ActorID;
+
+use crate::state::{assert_expiry_valid, NFTState, StateError, TokenData};
+use crate::types::TokenID;
+use crate::{Result, NFT};
+
+/// The actor holding the user role on a token and the last epoch at which the role is valid
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct TokenUser {
+    pub user: ActorID,
+    pub expires: ChainEpoch,
+}
+
+impl TokenUser {
+    /// Checks if the user role is still valid at the current epoch
+    pub fn is_active(&self, current_epoch: ChainEpoch) -> bool {
+        current_epoch <= self.expires
+    }
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct SetUserParams {
+    pub token_id: TokenID,
+    pub user: Address,
+    pub expires: ChainEpoch,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct UserOfReturn {
+    pub user: Option<ActorID>,
+    pub expires: Option<ChainEpoch>,
+}
+
+impl NFTState {
+    /// Sets or clears the user of a token
+    ///
+    /// The predicate is checked against the token before the user is changed. It is the caller's
+    /// responsibility to check that the actor using this method is permitted to do so.
+    pub fn set_user<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        token_id: TokenID,
+        user: Option<TokenUser>,
+        current_epoch: ChainEpoch,
+        set_user_predicate: F,
+    ) -> std::result::Result<(), StateError>
+    where
+        F: Fn(&TokenData, TokenID) -> std::result::Result<(), StateError>,
+    {
+        assert_expiry_valid(user.as_ref().map(|u| u.expires), current_epoch)?;
+        let mut token_array = self.get_token_data_amt(bs)?;
+        let mut token_data =
+            token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?.clone();
+        set_user_predicate(&token_data, token_id)?;
+        token_data.user = user;
+        token_array.set(token_id, token_data)?;
+        self.token_data = token_array.flush()?;
+        Ok(())
+    }
+
+    /// Gets the user of a token, if the user role has been granted and has not expired
+    pub fn get_user<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+        current_epoch: ChainEpoch,
+    ) -> std::result::Result<Option<TokenUser>, StateError> {
+        let token_array = self.get_token_data_amt(bs)?;
+        let token_data = token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token_data.user.clone().filter(|u| u.is_active(current_epoch)))
+    }
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Grants `user` the user role on a token until the `expires` epoch (inclusive), replacing any
+    /// existing user
+    ///
+    /// `caller` may be the owner of the token or an operator approved at token or account level
+    pub fn set_user(
+        &mut self,
+        caller: &Address,
+        token_id: TokenID,
+        user: &Address,
+        expires: ChainEpoch,
+    ) -> Result<()> {
+        let user = self.runtime.resolve_or_init(user)?;
+        self.update_user(caller, token_id, Some(TokenUser { user, expires }))
+    }
+
+    /// Removes the user role from a token
+    ///
+    /// `caller` may be the owner of the token or an operator approved at token or account level
+    pub fn clear_user(&mut self, caller: &Address, token_id: TokenID) -> Result<()> {
+        self.update_user(caller, token_id, None)
+    }
+
+    /// Returns the current user of a token, or None if there is no user or the role has expired
+    pub fn user_of(&self, token_id: TokenID) -> Result<Option<ActorID>> {
+        Ok(self.user_info(token_id)?.map(|u| u.user))
+    }
+
+    /// Returns the epoch at which the current user role of a token expires, or None if there is no
+    /// unexpired user
+    pub fn user_expires(&self, token_id: TokenID) -> Result<Option<ChainEpoch>> {
+        Ok(self.user_info(token_id)?.map(|u| u.expires))
+    }
+
+    fn user_info(&self, token_id: TokenID) -> Result<Option<TokenUser>> {
+        Ok(self.state.get_user(&self.runtime, token_id, self.runtime.curr_epoch())?)
+    }
+
+    fn update_user(
+        &mut self,
+        caller: &Address,
+        token_id: TokenID,
+        user: Option<TokenUser>,
+    ) -> Result<()> {
+        let caller = self.runtime.resolve_id(caller)?;
+        let current_epoch = self.runtime.curr_epoch();
+
+        self.transaction(|state, bs| {
+            let owner = state.get_owner(bs, token_id)?;
+            let account_operator = state.is_owner_operator(bs, owner, caller, current_epoch)?;
+            state.set_user(bs, token_id, user, current_epoch, |token_data, token_id| {
+                if token_data.owner == caller
+                    || account_operator
+                    || token_data.is_unrestricted_operator(&caller, current_epoch)
+                {
+                    Ok(())
+                } else {
+                    Err(StateError::NotAuthorized { actor: caller, token_id })
+                }
+            })?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, ActorID};
+
+    use crate::{state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+    const CHARLIE_ID: ActorID = 111;
+    const CHARLIE: Address = Address::new_id(CHARLIE_ID);
+
+    #[test]
+    fn it_grants_time_limited_users() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        nft.runtime.syscalls.set_curr_epoch(10);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 2], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1]
+
+        assert_eq!(nft.user_of(0).unwrap(), None);
+
+        {
+            // only the owner or an operator can set the user
+            let err = nft.set_user(&BOB, 0, &BOB, 20).unwrap_err();
+            if let NFTError::NFTState(StateError::NotAuthorized { actor, token_id }) = err {
+                assert_eq!(actor, BOB_ID);
+                assert_eq!(token_id, 0);
+            } else {
+                panic!("unexpected error {err:?}");
+            }
+        }
+
+        // alice lends token 0 to bob until epoch 20
+        nft.set_user(&ALICE, 0, &BOB, 20).unwrap();
+        assert_eq!(nft.user_of(0).unwrap(), Some(BOB_ID));
+        assert_eq!(nft.user_expires(0).unwrap(), Some(20));
+        // being the user confers no ownership rights
+        assert_eq!(nft.owner_of(0).unwrap(), ALICE_ID);
+        nft.burn_from(&ALICE, &BOB, &[0]).unwrap_err();
+
+        // the user role lapses after the expiry epoch
+        nft.runtime.syscalls.set_curr_epoch(20);
+        assert_eq!(nft.user_of(0).unwrap(), Some(BOB_ID));
+        nft.runtime.syscalls.set_curr_epoch(21);
+        assert_eq!(nft.user_of(0).unwrap(), None);
+        assert_eq!(nft.user_expires(0).unwrap(), None);
+
+        // an account-level operator can set the user
+        nft.approve_for_owner(&ALICE, &CHARLIE).unwrap();
+        nft.set_user(&CHARLIE, 1, &CHARLIE, 30).unwrap();
+        assert_eq!(nft.user_of(1).unwrap(), Some(CHARLIE_ID));
+        nft.clear_user(&ALICE, 1).unwrap();
+        assert_eq!(nft.user_of(1).unwrap(), None);
+
+        // the user is cleared when the token is transferred
+        nft.set_user(&ALICE, 1, &CHARLIE, 30).unwrap();
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[1], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.user_of(1).unwrap(), None);
+
+        nft.check_invariants().unwrap();
+    }
+}
@@ -0,0 +1,415 @@
+//! Provenance commitments and delayed reveal of token metadata for blind drops
+//!
+//! Before any tokens are minted, a collection commits to a hash of its ordered metadata list and
+//! to the mapping that decides which entry of the list each TokenID receives. Fixing the mapping
+//! up front keeps the collection from choosing it after seeing who minted which token. Until the
+//! collection is revealed, every token reports the same placeholder metadata.
+//!
+//! The commitment is the head of a hash chain over the list, so the list can be revealed over as
+//! many messages as it takes: each page of entries is checked against the link left by the page
+//! before it. Revealed entries are stored in their own AMT and looked up when a token's metadata is
+//! read, rather than being copied into every token. An explicit mapping is stored in an AMT of its
+//! own too, so the state read by every method holds only its root.
+use cid::Cid;
+use fvm_actor_utils::crypto::{keccak256, Digest};
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_amt::Amt;
+use fvm_ipld_bitfield::BitField;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::strict_bytes;
+use fvm_ipld_encoding::tuple::*;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{NFTState, StateError, DEFAULT_AMT_BIT_WIDTH};
+use crate::types::TokenID;
+use crate::{Result, NFT};
+
+/// The link that ends the provenance hash chain
+pub const PROVENANCE_CHAIN_END: Digest = [0; 32];
+
+/// A commitment to the metadata of a collection made before minting begins
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct Provenance {
+    /// Head of the hash chain over the ordered metadata list, see [`provenance_chain`]
+    #[serde(with = "strict_bytes")]
+    pub hash: Digest,
+    /// Number of entries in the metadata list
+    pub len: u64,
+    /// Which entry of the metadata list each TokenID receives
+    pub mapping: CommittedMapping,
+    /// Metadata reported for every token until the collection is revealed
+    pub placeholder: String,
+    /// Number of entries revealed so far
+    pub revealed: u64,
+    /// Link of the hash chain that the next page of entries must lead to
+    #[serde(with = "strict_bytes")]
+    pub next_link: Digest,
+    /// Amt<String> of the entries revealed so far, by their index in the list
+    pub revealed_metadata: Option<Cid>,
+}
+
+impl Provenance {
+    /// Whether the whole metadata list has been revealed
+    pub fn is_revealed(&self) -> bool {
+        self.revealed == self.len
+    }
+}
+
+/// Decides which entry of the committed metadata list each TokenID receives on reveal
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub enum RevealMapping {
+    /// TokenID `id` receives entry `(id + offset) % len` of the metadata list
+    Offset(u64),
+    /// TokenID `id` receives entry `mapping[id]` of the metadata list, which must be a permutation
+    Mapping(Vec<u64>),
+}
+
+/// A [`RevealMapping`] as stored in the provenance commitment
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub enum CommittedMapping {
+    /// TokenID `id` receives entry `(id + offset) % len` of the metadata list
+    Offset(u64),
+    /// Amt<u64> of the entry of the metadata list each TokenID receives
+    Mapping(Cid),
+}
+
+impl CommittedMapping {
+    /// Returns the index into a metadata list of length `len` for the given token
+    fn index_of<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+        len: u64,
+    ) -> std::result::Result<Option<u64>, StateError> {
+        if token_id >= len {
+            return Ok(None);
+        }
+        match self {
+            CommittedMapping::Offset(offset) => Ok(Some((token_id + offset % len) % len)),
+            CommittedMapping::Mapping(root) => {
+                Ok(Amt::<u64, _>::load(root, bs)?.get(token_id)?.copied())
+            }
+        }
+    }
+}
+
+impl RevealMapping {
+    /// Checks that the mapping assigns each entry of a list of length `len` to exactly one token
+    fn is_permutation(&self, len: u64) -> bool {
+        match self {
+            RevealMapping::Offset(_) => true,
+            RevealMapping::Mapping(mapping) => {
+                let mut seen = BitField::new();
+                mapping.len() as u64 == len
+                    && mapping.iter().all(|&index| {
+                        let fresh = index < len && !seen.get(index);
+                        seen.set(index);
+                        fresh
+                    })
+            }
+        }
+    }
+
+    /// Stores the mapping for the provenance commitment, writing an explicit mapping to its own AMT
+    fn commit<BS: Blockstore>(self, bs: &BS) -> std::result::Result<CommittedMapping, StateError> {
+        match self {
+            RevealMapping::Offset(offset) => Ok(CommittedMapping::Offset(offset)),
+            RevealMapping::Mapping(mapping) => {
+                let mut entries = Amt::new_with_bit_width(bs, DEFAULT_AMT_BIT_WIDTH);
+                entries.batch_set(mapping)?;
+                Ok(CommittedMapping::Mapping(entries.flush()?))
+            }
+        }
+    }
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct CommitProvenanceParams {
+    #[serde(with = "strict_bytes")]
+    pub hash: Digest,
+    pub len: u64,
+    pub mapping: RevealMapping,
+    pub placeholder: String,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct RevealParams {
+    /// The next page of entries of the metadata list
+    pub metadata: Vec<String>,
+    /// The link of the hash chain following the last entry of the page
+    #[serde(with = "strict_bytes")]
+    pub next_link: Digest,
+}
+
+/// Returns every link of the hash chain over an ordered list of metadata
+///
+/// Link `i` is the keccak-256 hash of entry `i` followed by link `i + 1`, and the chain ends with
+/// [`PROVENANCE_CHAIN_END`]. The first link is the provenance hash committed to, and revealing
+/// entries `a..b` takes link `b`.
+pub fn provenance_chain(metadata: &[String]) -> Vec<Digest> {
+    let mut links = vec![PROVENANCE_CHAIN_END; metadata.len() + 1];
+    for (i, entry) in metadata.iter().enumerate().rev() {
+        links[i] = chain_link(entry, &links[i + 1]);
+    }
+    links
+}
+
+/// Computes the provenance hash committing to an ordered list of metadata
+pub fn provenance_hash(metadata: &[String]) -> Digest {
+    provenance_chain(metadata)[0]
+}
+
+fn chain_link(entry: &str, next: &Digest) -> Digest {
+    keccak256(&[entry.as_bytes(), next].concat())
+}
+
+impl NFTState {
+    /// Commits to the provenance hash of the collection's metadata and the mapping of tokens to it
+    ///
+    /// The commitment must be made before any tokens are minted and cannot be changed afterwards.
+    /// The mapping must assign each of the `len` entries of the list to exactly one token.
+    pub fn commit_provenance<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        hash: Digest,
+        len: u64,
+        mapping: RevealMapping,
+        placeholder: String,
+    ) -> std::result::Result<(), StateError> {
+        if self.provenance.is_some() || self.next_token > 0 {
+            return Err(StateError::ProvenanceAlreadyCommitted);
+        }
+        if !mapping.is_permutation(len) {
+            return Err(StateError::InvalidRevealMapping);
+        }
+        self.provenance = Some(Provenance {
+            hash,
+            len,
+            mapping: mapping.commit(bs)?,
+            placeholder,
+            revealed: 0,
+            next_link: hash,
+            revealed_metadata: None,
+        });
+        Ok(())
+    }
+
+    /// Reveals the next page of the committed metadata list
+    ///
+    /// The entries must continue the list from where the last page ended and, with `next_link`,
+    /// match the committed hash chain. Tokens report their entry of the list once the whole list
+    /// has been revealed.
+    pub fn reveal<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        metadata: Vec<String>,
+        next_link: Digest,
+    ) -> std::result::Result<(), StateError> {
+        let provenance = match &mut self.provenance {
+            Some(provenance) if !provenance.is_revealed() => provenance,
+            _ => return Err(StateError::NotRevealable),
+        };
+        let link = metadata.iter().rev().fold(next_link, |link, entry| chain_link(entry, &link));
+        let revealed = provenance.revealed.saturating_add(metadata.len() as u64);
+        if link != provenance.next_link
+            || revealed > provenance.len
+            || (revealed == provenance.len) != (next_link == PROVENANCE_CHAIN_END)
+        {
+            return Err(StateError::ProvenanceMismatch);
+        }
+
+        let mut entries = match &provenance.revealed_metadata {
+            Some(root) => Amt::load(root, bs)?,
+            None => Amt::new_with_bit_width(bs, DEFAULT_AMT_BIT_WIDTH),
+        };
+        for (index, entry) in (provenance.revealed..).zip(metadata) {
+            entries.set(index, entry)?;
+        }
+        provenance.revealed_metadata = Some(entries.flush()?);
+        provenance.revealed = revealed;
+        provenance.next_link = next_link;
+        Ok(())
+    }
+
+    /// Returns the metadata the provenance commitment gives a token, if it covers the token
+    ///
+    /// This is the placeholder until the whole list has been revealed and the token's entry of the
+    /// list after that. Tokens beyond the end of the list keep the metadata they were minted with.
+    pub fn committed_metadata<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+    ) -> std::result::Result<Option<String>, StateError> {
+        let Some(provenance) = &self.provenance else {
+            return Ok(None);
+        };
+        if !provenance.is_revealed() {
+            return Ok(Some(provenance.placeholder.clone()));
+        }
+        let (Some(index), Some(root)) = (
+            provenance.mapping.index_of(bs, token_id, provenance.len)?,
+            &provenance.revealed_metadata,
+        ) else {
+            return Ok(None);
+        };
+        let entries = Amt::<String, _>::load(root, bs)?;
+        let entry = entries.get(index)?.ok_or_else(|| {
+            StateError::InvariantFailed(format!("revealed metadata entry {index} not found"))
+        })?;
+        Ok(Some(entry.clone()))
+    }
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Commits to the provenance hash of the collection's metadata, see `provenance_hash`
+    ///
+    /// Until the collection is revealed, all tokens report the placeholder metadata. The caller is
+    /// responsible for checking that the actor calling this method is permitted to do so
+    pub fn commit_provenance(&mut self, params: CommitProvenanceParams) -> Result<()> {
+        Ok(self.state.commit_provenance(
+            &self.runtime,
+            params.hash,
+            params.len,
+            params.mapping,
+            params.placeholder,
+        )?)
+    }
+
+    /// Returns the provenance commitment of the collection, if any
+    pub fn provenance(&self) -> Option<Provenance> {
+        self.state.provenance.clone()
+    }
+
+    /// Reveals the next page of the committed metadata list
+    ///
+    /// The caller is responsible for checking that the actor calling this method is permitted to
+    /// do so
+    pub fn reveal(&mut self, params: RevealParams) -> Result<()> {
+        self.transaction(|state, bs| Ok(state.reveal(bs, params.metadata, params.next_link)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_amt::Amt;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, ActorID};
+
+    use super::{
+        provenance_chain, CommitProvenanceParams, CommittedMapping, RevealMapping, RevealParams,
+    };
+    use crate::{state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+
+    fn commitment(metadata: &[String], mapping: RevealMapping) -> CommitProvenanceParams {
+        CommitProvenanceParams {
+            hash: provenance_chain(metadata)[0],
+            len: metadata.len() as u64,
+            mapping,
+            placeholder: "ipfs://placeholder".into(),
+        }
+    }
+
+    #[test]
+    fn it_reveals_committed_metadata_in_pages() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        let metadata: Vec<String> = (0..4).map(|i| format!("ipfs://token-{i}")).collect();
+        let links = provenance_chain(&metadata);
+        nft.commit_provenance(commitment(&metadata, RevealMapping::Offset(2))).unwrap();
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 5], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        // provenance cannot be changed after minting
+        let err =
+            nft.commit_provenance(commitment(&metadata, RevealMapping::Offset(0))).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::ProvenanceAlreadyCommitted)));
+
+        // all tokens report the placeholder until reveal
+        for token_id in 0..5 {
+            assert_eq!(nft.metadata(token_id).unwrap(), "ipfs://placeholder");
+        }
+
+        // pages must match the commitment and continue from the previous page
+        let page = |range: std::ops::Range<usize>| RevealParams {
+            metadata: metadata[range.clone()].to_vec(),
+            next_link: links[range.end],
+        };
+        let mut tampered = page(0..2);
+        tampered.metadata.swap(0, 1);
+        let err = nft.reveal(tampered).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::ProvenanceMismatch)));
+        let err = nft.reveal(page(1..3)).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::ProvenanceMismatch)));
+
+        nft.reveal(page(0..2)).unwrap();
+        let err = nft.reveal(page(0..2)).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::ProvenanceMismatch)));
+
+        // tokens keep the placeholder while the list is partly revealed
+        assert_eq!(nft.metadata(0).unwrap(), "ipfs://placeholder");
+        assert!(!nft.provenance().unwrap().is_revealed());
+
+        nft.reveal(page(2..4)).unwrap();
+        assert!(nft.provenance().unwrap().is_revealed());
+        assert_eq!(nft.metadata(0).unwrap(), "ipfs://token-2");
+        assert_eq!(nft.metadata(1).unwrap(), "ipfs://token-3");
+        assert_eq!(nft.metadata(2).unwrap(), "ipfs://token-0");
+        assert_eq!(nft.metadata(3).unwrap(), "ipfs://token-1");
+        // tokens beyond the committed list keep their own metadata
+        assert_eq!(nft.metadata(4).unwrap(), "");
+
+        // cannot reveal twice
+        let err = nft.reveal(page(0..4)).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::NotRevealable)));
+
+        nft.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn it_requires_mappings_to_be_permutations() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        let metadata: Vec<String> = (0..3).map(|i| format!("ipfs://token-{i}")).collect();
+
+        for mapping in [vec![0, 0, 1], vec![0, 1], vec![0, 1, 3], vec![0, 1, 2, 3]] {
+            let params = commitment(&metadata, RevealMapping::Mapping(mapping));
+            let err = nft.commit_provenance(params).unwrap_err();
+            assert!(matches!(err, NFTError::NFTState(StateError::InvalidRevealMapping)));
+        }
+
+        let mapping = RevealMapping::Mapping(vec![2, 0, 1]);
+        nft.commit_provenance(commitment(&metadata, mapping)).unwrap();
+        // only the root of the mapping is kept in the state
+        let CommittedMapping::Mapping(root) = nft.provenance().unwrap().mapping else {
+            panic!("expected the mapping to be stored in its own AMT");
+        };
+        let entries = Amt::<u64, _>::load(&root, &nft.runtime).unwrap();
+        assert_eq!(entries.count(), 3);
+        assert_eq!(entries.get(0).unwrap(), Some(&2));
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+
+        let links = provenance_chain(&metadata);
+        nft.reveal(RevealParams { metadata: metadata.clone(), next_link: links[3] }).unwrap();
+        assert_eq!(nft.metadata(0).unwrap(), "ipfs://token-2");
+        assert_eq!(nft.metadata(1).unwrap(), "ipfs://token-0");
+        assert_eq!(nft.metadata(2).unwrap(), "ipfs://token-1");
+    }
+}
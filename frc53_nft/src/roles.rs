@@ -0,0 +1,204 @@
+//! Collection-level roles for access control
+//!
+//! Roles are named sets of actors stored in the collection state. Members of the `ADMIN_ROLE` may
+//! grant and revoke any role, and any member may renounce a role it holds. Actors built on the
+//! library can use `NFT::require_role` to restrict privileged methods (minting, metadata updates,
+//! pausing etc.) to the members of a role instead of implementing their own access control.
+use fvm_actor_utils::messaging::MessagingError;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+use fvm_shared::ActorID;
+
+use crate::state::{NFTState, StateError};
+use crate::util::OperatorSet;
+use crate::{Result, NFT};
+
+/// Members may grant and revoke every role, including this one
+pub const ADMIN_ROLE: &str = "admin";
+/// Members may mint new tokens
+pub const MINTER_ROLE: &str = "minter";
+/// Members may update collection and token metadata
+pub const METADATA_ADMIN_ROLE: &str = "metadata-admin";
+/// Members may pause and unpause the collection
+pub const PAUSER_ROLE: &str = "pauser";
+/// Members may replace the extra data attached to any token
+pub const TOKEN_DATA_ADMIN_ROLE: &str = "token-data-admin";
+
+/// The actors holding a role
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct RoleMembers {
+    pub role: String,
+    /// Sorted list of the members of the role
+    pub members: Vec<ActorID>,
+}
+
+impl NFTState {
+    /// Adds an actor to a role
+    ///
+    /// It is the caller's responsibility to check that the actor using this method is permitted to
+    /// do so. This can be used to assign the initial admin when constructing a collection.
+    pub fn grant_role(&mut self, role: &str, actor: ActorID) {
+        match self.roles.binary_search_by(|r| r.role.as_str().cmp(role)) {
+            Ok(pos) => self.roles[pos].members.add_operator(actor),
+            Err(pos) => {
+                self.roles.insert(pos, RoleMembers { role: role.into(), members: vec![actor] })
+            }
+        }
+    }
+
+    /// Removes an actor from a role
+    ///
+    /// It is the caller's responsibility to check that the actor using this method is permitted to
+    /// do so.
+    pub fn revoke_role(&mut self, role: &str, actor: ActorID) {
+        if let Ok(pos) = self.roles.binary_search_by(|r| r.role.as_str().cmp(role)) {
+            self.roles[pos].members.remove_operator(&actor);
+            if self.roles[pos].members.is_empty() {
+                self.roles.remove(pos);
+            }
+        }
+    }
+
+    /// Checks if an actor holds a role
+    pub fn has_role(&self, role: &str, actor: ActorID) -> bool {
+        self.role_members(role).contains_actor(&actor)
+    }
+
+    /// Returns the members of a role
+    pub fn role_members(&self, role: &str) -> &[ActorID] {
+        match self.roles.binary_search_by(|r| r.role.as_str().cmp(role)) {
+            Ok(pos) => &self.roles[pos].members,
+            Err(_) => &[],
+        }
+    }
+
+    /// Fails with `StateError::MissingRole` unless the actor holds the role
+    pub fn assert_role(&self, role: &str, actor: ActorID) -> std::result::Result<(), StateError> {
+        if self.has_role(role, actor) {
+            Ok(())
+        } else {
+            Err(StateError::MissingRole { actor, role: role.into() })
+        }
+    }
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Grants a role to an account
+    ///
+    /// `caller` must hold the `ADMIN_ROLE`
+    pub fn grant_role(&mut self, caller: &Address, role: &str, account: &Address) -> Result<()> {
+        let caller = self.runtime.resolve_id(caller)?;
+        let account = self.runtime.resolve_or_init(account)?;
+        self.transaction(|state, _bs| {
+            state.assert_role(ADMIN_ROLE, caller)?;
+            state.grant_role(role, account);
+            Ok(())
+        })
+    }
+
+    /// Revokes a role from an account
+    ///
+    /// `caller` must hold the `ADMIN_ROLE`
+    pub fn revoke_role(&mut self, caller: &Address, role: &str, account: &Address) -> Result<()> {
+        let caller = self.runtime.resolve_id(caller)?;
+        let account = match self.runtime.resolve_id(account) {
+            Ok(id) => id,
+            Err(MessagingError::AddressNotResolved(_)) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        self.transaction(|state, _bs| {
+            state.assert_role(ADMIN_ROLE, caller)?;
+            state.revoke_role(role, account);
+            Ok(())
+        })
+    }
+
+    /// Gives up a role held by the caller
+    pub fn renounce_role(&mut self, caller: &Address, role: &str) -> Result<()> {
+        let caller = self.runtime.resolve_id(caller)?;
+        self.transaction(|state, _bs| {
+            state.revoke_role(role, caller);
+            Ok(())
+        })
+    }
+
+    /// Returns whether an account holds a role
+    pub fn has_role(&self, account: &Address, role: &str) -> Result<bool> {
+        match self.runtime.resolve_id(account) {
+            Ok(id) => Ok(self.state.has_role(role, id)),
+            Err(MessagingError::AddressNotResolved(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Fails with `StateError::MissingRole` unless the account holds the role
+    ///
+    /// Intended to be called at the top of privileged actor methods
+    pub fn require_role(&self, account: &Address, role: &str) -> Result<()> {
+        let account = self.runtime.resolve_id(account)?;
+        Ok(self.state.assert_role(role, account)?)
+    }
+
+    /// Returns the members of a role
+    pub fn role_members(&self, role: &str) -> Vec<ActorID> {
+        self.state.role_members(role).to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::{address::Address, ActorID};
+
+    use super::{ADMIN_ROLE, MINTER_ROLE, PAUSER_ROLE};
+    use crate::{state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+    const CHARLIE_ID: ActorID = 111;
+    const CHARLIE: Address = Address::new_id(CHARLIE_ID);
+
+    #[test]
+    fn it_manages_roles() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        state.grant_role(ADMIN_ROLE, ALICE_ID);
+        let mut nft = NFT::wrap(helper, &mut state);
+
+        // only admins can grant roles
+        let err = nft.grant_role(&BOB, MINTER_ROLE, &BOB).unwrap_err();
+        if let NFTError::NFTState(StateError::MissingRole { actor, role }) = err {
+            assert_eq!(actor, BOB_ID);
+            assert_eq!(role, ADMIN_ROLE);
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+
+        nft.grant_role(&ALICE, MINTER_ROLE, &CHARLIE).unwrap();
+        nft.grant_role(&ALICE, MINTER_ROLE, &BOB).unwrap();
+        nft.grant_role(&ALICE, PAUSER_ROLE, &BOB).unwrap();
+        assert_eq!(nft.role_members(MINTER_ROLE), vec![BOB_ID, CHARLIE_ID]);
+        assert!(nft.has_role(&BOB, PAUSER_ROLE).unwrap());
+        nft.require_role(&CHARLIE, MINTER_ROLE).unwrap();
+        nft.require_role(&CHARLIE, PAUSER_ROLE).unwrap_err();
+
+        // members can renounce their own roles
+        nft.renounce_role(&BOB, PAUSER_ROLE).unwrap();
+        assert!(!nft.has_role(&BOB, PAUSER_ROLE).unwrap());
+        assert!(nft.role_members(PAUSER_ROLE).is_empty());
+
+        // only admins can revoke roles
+        nft.revoke_role(&BOB, MINTER_ROLE, &CHARLIE).unwrap_err();
+        nft.revoke_role(&ALICE, MINTER_ROLE, &CHARLIE).unwrap();
+        assert_eq!(nft.role_members(MINTER_ROLE), vec![BOB_ID]);
+    }
+}
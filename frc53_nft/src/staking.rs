@@ -0,0 +1,259 @@
+//! In-state staking of NFTs
+//!
+//! An owner may stake tokens to lock them in place without handing custody to an escrow actor.
+//! Staked tokens remain owned by the staker but cannot be transferred or burned, and record the
+//! epoch at which they were staked. A collection may require tokens to remain staked for a minimum
+//! number of epochs before they can be unstaked.
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::ActorID;
+
+use crate::state::{actor_id_key, NFTState, StateError, TokenData};
+use crate::types::{TokenID, TokenSet};
+use crate::{Result, NFT};
+
+impl NFTState {
+    /// Sets the number of epochs a token must remain staked before it can be unstaked
+    ///
+    /// The minimum applies to tokens that are already staked as well as future stakes
+    pub fn set_min_stake_duration(&mut self, min_stake_duration: Option<ChainEpoch>) {
+        self.min_stake_duration = min_stake_duration;
+    }
+
+    /// Stakes a set of tokens held by the owner at the current epoch
+    ///
+    /// Fails if any token is not owned by `owner` or is already staked. The predicate is checked
+    /// against each token before it is staked.
+    pub fn stake_tokens<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        owner: ActorID,
+        token_ids: &[TokenID],
+        current_epoch: ChainEpoch,
+        stake_predicate: F,
+    ) -> std::result::Result<(), StateError>
+    where
+        F: Fn(&TokenData, TokenID) -> std::result::Result<(), StateError>,
+    {
+        self.update_stakes(bs, owner, token_ids, |token_data, token_id| {
+            stake_predicate(token_data, token_id)?;
+            if token_data.staked_at.is_some() {
+                return Err(StateError::TokenStaked(token_id));
+            }
+            token_data.staked_at = Some(current_epoch);
+            Ok(())
+        })
+    }
+
+    /// Unstakes a set of tokens held by the owner
+    ///
+    /// Fails if any token is not owned by `owner`, is not staked or has not been staked for the
+    /// minimum duration of the collection. The predicate is checked against each token before it
+    /// is unstaked.
+    pub fn unstake_tokens<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        owner: ActorID,
+        token_ids: &[TokenID],
+        current_epoch: ChainEpoch,
+        unstake_predicate: F,
+    ) -> std::result::Result<(), StateError>
+    where
+        F: Fn(&TokenData, TokenID) -> std::result::Result<(), StateError>,
+    {
+        let min_stake_duration = self.min_stake_duration.unwrap_or(0);
+        self.update_stakes(bs, owner, token_ids, |token_data, token_id| {
+            unstake_predicate(token_data, token_id)?;
+            let staked_at = token_data.staked_at.ok_or(StateError::NotStaked(token_id))?;
+            let unlocks_at = staked_at.saturating_add(min_stake_duration);
+            if current_epoch < unlocks_at {
+                return Err(StateError::StakeLocked { token_id, unlocks_at });
+            }
+            token_data.staked_at = None;
+            Ok(())
+        })
+    }
+
+    /// Applies a change to the stake of each token and mirrors it in the owner's staked set
+    fn update_stakes<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        owner: ActorID,
+        token_ids: &[TokenID],
+        update: F,
+    ) -> std::result::Result<(), StateError>
+    where
+        F: Fn(&mut TokenData, TokenID) -> std::result::Result<(), StateError>,
+    {
+        if token_ids.is_empty() {
+            return Ok(());
+        }
+        let mut token_array = self.get_token_data_amt(bs)?;
+        let mut owner_map = self.get_owner_data_hamt(bs)?;
+        let owner_key = actor_id_key(owner);
+        let mut owner_data = owner_map
+            .get(&owner_key)?
+            .ok_or(StateError::NotOwner { actor: owner, token_id: token_ids[0] })?
+            .clone();
+
+        for &token_id in token_ids {
+            let mut token_data =
+                token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?.clone();
+            NFTState::assert_owns_token(&token_data, token_id, owner)?;
+            update(&mut token_data, token_id)?;
+            match token_data.staked_at {
+                Some(_) => owner_data.staked.set(token_id),
+                None => owner_data.staked.unset(token_id),
+            }
+            token_array.set(token_id, token_data)?;
+        }
+
+        owner_map.set(owner_key, owner_data)?;
+        self.token_data = token_array.flush()?;
+        self.owner_data = owner_map.flush()?;
+        Ok(())
+    }
+
+    /// Returns the epoch at which a token was staked, or None if it is not staked
+    pub fn get_staked_at<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+    ) -> std::result::Result<Option<ChainEpoch>, StateError> {
+        let token_array = self.get_token_data_amt(bs)?;
+        let token_data = token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token_data.staked_at)
+    }
+
+    /// Returns the set of tokens an owner currently has staked
+    pub fn get_staked_tokens<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        owner: ActorID,
+    ) -> std::result::Result<TokenSet, StateError> {
+        let owner_map = self.get_owner_data_hamt(bs)?;
+        Ok(owner_map.get(&actor_id_key(owner))?.map(|data| data.staked.clone()).unwrap_or_default())
+    }
+}
+
+impl<'st, S, BS> NFT<'st, S, BS>
+where
+    S: Syscalls,
+    BS: Blockstore,
+{
+    /// Sets the number of epochs a token must remain staked before it can be unstaked
+    ///
+    /// Access control is the responsibility of the calling actor.
+    pub fn set_min_stake_duration(&mut self, min_stake_duration: Option<ChainEpoch>) -> Result<()> {
+        self.transaction(|state, _bs| {
+            state.set_min_stake_duration(min_stake_duration);
+            Ok(())
+        })
+    }
+
+    /// Stakes tokens held by `owner`, preventing them from being transferred or burned until
+    /// unstaked
+    ///
+    /// `owner` must be the address that called this method
+    pub fn stake(&mut self, owner: &Address, token_ids: &[TokenID]) -> Result<()> {
+        let owner = self.runtime.resolve_id(owner)?;
+        let current_epoch = self.runtime.curr_epoch();
+        self.transaction(|state, bs| {
+            Ok(state.stake_tokens(bs, owner, token_ids, current_epoch, |_, _| Ok(()))?)
+        })
+    }
+
+    /// Unstakes tokens held by `owner` once they have been staked for the minimum duration
+    ///
+    /// `owner` must be the address that called this method
+    pub fn unstake(&mut self, owner: &Address, token_ids: &[TokenID]) -> Result<()> {
+        let owner = self.runtime.resolve_id(owner)?;
+        let current_epoch = self.runtime.curr_epoch();
+        self.transaction(|state, bs| {
+            Ok(state.unstake_tokens(bs, owner, token_ids, current_epoch, |_, _| Ok(()))?)
+        })
+    }
+
+    /// Returns the epoch at which a token was staked, or None if it is not staked
+    pub fn staked_at(&self, token_id: TokenID) -> Result<Option<ChainEpoch>> {
+        Ok(self.state.get_staked_at(&self.runtime, token_id)?)
+    }
+
+    /// Returns the set of tokens an owner currently has staked
+    pub fn staked_tokens(&self, owner: &Address) -> Result<TokenSet> {
+        let owner = match self.runtime.resolve_id(owner) {
+            Ok(owner) => owner,
+            Err(_) => return Ok(TokenSet::default()),
+        };
+        Ok(self.state.get_staked_tokens(&self.runtime, owner)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
+    use fvm_ipld_bitfield::bitfield;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, ActorID};
+
+    use crate::{state::StateError, NFTError, NFTState, NFT};
+
+    const ALICE_ID: ActorID = 1;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB_ID: ActorID = 11;
+    const BOB: Address = Address::new_id(BOB_ID);
+
+    #[test]
+    fn it_stakes_tokens() {
+        let helper = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut state = NFTState::new(&helper).unwrap();
+        let mut nft = NFT::wrap(helper, &mut state);
+        nft.set_min_stake_duration(Some(10)).unwrap();
+        nft.runtime.syscalls.set_curr_epoch(5);
+
+        let mut hook = nft
+            .mint(&ALICE, &ALICE, vec![String::new(); 3], RawBytes::default(), RawBytes::default())
+            .unwrap();
+        hook.call(&nft.runtime).unwrap();
+        // alice: [0, 1, 2]
+
+        // only the owner can stake
+        nft.stake(&BOB, &[0]).unwrap_err();
+
+        nft.stake(&ALICE, &[0, 2]).unwrap();
+        assert_eq!(nft.staked_at(0).unwrap(), Some(5));
+        assert_eq!(nft.staked_at(1).unwrap(), None);
+        assert_eq!(nft.staked_tokens(&ALICE).unwrap(), bitfield![1, 0, 1]);
+        nft.stake(&ALICE, &[0]).unwrap_err();
+
+        // staked tokens are locked in place
+        let err =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap_err();
+        assert!(matches!(err, NFTError::NFTState(StateError::TokenStaked(0))));
+        nft.burn(&ALICE, &[2]).unwrap_err();
+
+        // tokens can only be unstaked after the minimum duration
+        nft.runtime.syscalls.set_curr_epoch(14);
+        let err = nft.unstake(&ALICE, &[0]).unwrap_err();
+        if let NFTError::NFTState(StateError::StakeLocked { token_id, unlocks_at }) = err {
+            assert_eq!(token_id, 0);
+            assert_eq!(unlocks_at, 15);
+        } else {
+            panic!("unexpected error {err:?}");
+        }
+        nft.unstake(&ALICE, &[1]).unwrap_err();
+
+        nft.runtime.syscalls.set_curr_epoch(15);
+        nft.unstake(&ALICE, &[0]).unwrap();
+        assert_eq!(nft.staked_tokens(&ALICE).unwrap(), bitfield![0, 0, 1]);
+        let mut hook =
+            nft.transfer(&ALICE, &BOB, &[0], RawBytes::default(), RawBytes::default()).unwrap();
+        hook.call(&nft.runtime).unwrap();
+        assert_eq!(nft.owner_of(0).unwrap(), BOB_ID);
+
+        nft.check_invariants().unwrap();
+    }
+}
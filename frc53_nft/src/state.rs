@@ -1,11 +1,13 @@
 //! Abstraction of the on-chain state related to NFT accounting
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::mem;
 use std::vec;
 
 use cid::multihash::Code;
 use cid::Cid;
+use fvm_actor_utils::inspect::{KeyFormat, Layout};
 use fvm_actor_utils::receiver::ReceiverHookError;
+use fvm_actor_utils::transaction::Transactional;
 use fvm_ipld_amt::Amt;
 use fvm_ipld_amt::Error as AmtError;
 use fvm_ipld_bitfield::BitField;
@@ -18,18 +20,34 @@ use fvm_ipld_encoding::DAG_CBOR;
 use fvm_ipld_hamt::BytesKey;
 use fvm_ipld_hamt::Error as HamtError;
 use fvm_ipld_hamt::Hamt;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::error::ExitCode;
 use fvm_shared::ActorID;
 use integer_encoding::VarInt;
 use thiserror::Error;
 
+use crate::history::OwnershipHistory;
+use crate::migration::{self, STATE_VERSION};
+use crate::operator_index::OperatorIndex;
+use crate::payout::PayoutShare;
+use crate::rental::TokenUser;
+use crate::reveal::Provenance;
+use crate::roles::RoleMembers;
 use crate::types::ActorIDSet;
+use crate::types::BurnReturn;
+use crate::types::BurnedToken;
+use crate::types::CollectionMetadata;
 use crate::types::MintIntermediate;
 use crate::types::MintReturn;
+use crate::types::OperatorApproval;
 use crate::types::TokenID;
 use crate::types::TokenSet;
 use crate::types::TransferIntermediate;
 use crate::types::TransferReturn;
-use crate::util::OperatorSet;
+use crate::util::ExpiringOperatorSet;
+use crate::util::OperatorBudget;
+use crate::util::OperatorExpiry;
+use crate::util::OperatorRecipient;
 
 /// Opaque cursor to iterate over internal data structures
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
@@ -39,7 +57,7 @@ pub struct Cursor {
 }
 
 impl Cursor {
-    fn new(cid: Cid, index: u64) -> Self {
+    pub(crate) fn new(cid: Cid, index: u64) -> Self {
         Self { root: cid, index }
     }
 }
@@ -51,19 +69,276 @@ pub struct TokenData {
     // operators on this token
     pub operators: BitField, // or maybe as a Cid to an Amt
     pub metadata: String,
+    // expiry epochs of the time-limited token-level operators
+    pub operator_expiries: Vec<OperatorExpiry>,
+    // the actor holding time-limited usage rights to the token, if any
+    pub user: Option<TokenUser>,
+    // arbitrary CBOR payload attached to the token, empty if unset
+    pub extra: RawBytes,
+    // the token this token is nested inside, if any
+    pub parent: Option<TokenID>,
+    // the tokens nested directly inside this token
+    pub children: TokenSet,
+    // the epoch at which the token was staked, if it is staked
+    pub staked_at: Option<ChainEpoch>,
+    // the only recipients the restricted token-level operators may transfer the token to
+    pub operator_recipients: Vec<OperatorRecipient>,
 }
 
+impl TokenData {
+    /// Returns the only account the operator may transfer the token to, or None if the operator is
+    /// not restricted
+    pub fn recipient_of(&self, operator: &ActorID) -> Option<ActorID> {
+        self.operator_recipients
+            .binary_search_by_key(operator, |r| r.operator)
+            .ok()
+            .map(|pos| self.operator_recipients[pos].recipient)
+    }
+
+    /// Checks if the operator holds an unexpired approval that isn't restricted to a recipient
+    ///
+    /// Only unrestricted operators may act on the token other than by transferring it.
+    pub fn is_unrestricted_operator(&self, operator: &ActorID, current_epoch: ChainEpoch) -> bool {
+        self.is_active_operator(operator, current_epoch) && self.recipient_of(operator).is_none()
+    }
+
+    /// Checks if the operator holds an unexpired approval allowing it to transfer the token to the
+    /// recipient
+    pub fn may_transfer_to(
+        &self,
+        operator: &ActorID,
+        recipient: ActorID,
+        current_epoch: ChainEpoch,
+    ) -> bool {
+        self.is_active_operator(operator, current_epoch)
+            && self.recipient_of(operator).map_or(true, |allowed| allowed == recipient)
+    }
+
+    /// Restricts the recipient of an operator, or lifts the restriction if no recipient is given
+    fn set_recipient(&mut self, operator: ActorID, recipient: Option<ActorID>) {
+        let recipients = &mut self.operator_recipients;
+        match (recipients.binary_search_by_key(&operator, |r| r.operator), recipient) {
+            (Ok(pos), Some(recipient)) => recipients[pos].recipient = recipient,
+            (Ok(pos), None) => {
+                recipients.remove(pos);
+            }
+            (Err(pos), Some(recipient)) => {
+                recipients.insert(pos, OperatorRecipient { operator, recipient })
+            }
+            (Err(_), None) => {}
+        }
+    }
+
+    /// Drops the recipient restrictions of operators that are no longer approved
+    fn prune_recipients(&mut self) {
+        let operators = &self.operators;
+        self.operator_recipients.retain(|r| operators.get(r.operator));
+    }
+}
+
+/// Owners holding at least this many tokens keep their token set in a block of its own, rather
+/// than inline in their entry of the owner map
+///
+/// Most owners hold a few tokens, whose set is small enough to store with the rest of their data.
+/// The sets of large holders are moved out so they don't bloat the HAMT nodes shared with other
+/// owners, which are rewritten whenever any of those owners changes.
+pub const OWNER_TOKENS_INLINE_LIMIT: u64 = 64;
+
 /// Each owner stores their own balance and other indexed data
 #[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Clone, Debug)]
 pub struct OwnerData {
     pub balance: u64,
+    // the tokens owned by this account, as a compact RLE+ set, while they are kept inline (empty
+    // once they are spilled to their own block)
+    pub tokens: TokenSet,
     // account-level operators
     pub operators: BitField, // maybe as a Cid to an Amt
+    // expiry epochs of the time-limited account-level operators
+    pub operator_expiries: Vec<OperatorExpiry>,
+    // remaining token allowances of the budget-limited account-level operators
+    pub operator_budgets: Vec<OperatorBudget>,
+    // the tokens of this account that are currently staked
+    pub staked: TokenSet,
+    // the block holding the tokens owned by this account, once it holds at least
+    // OWNER_TOKENS_INLINE_LIMIT of them
+    pub spilled_tokens: Option<Cid>,
+}
+
+impl OwnerData {
+    pub(crate) fn new() -> Self {
+        Self {
+            balance: 0,
+            tokens: TokenSet::default(),
+            operators: BitField::default(),
+            operator_expiries: vec![],
+            operator_budgets: vec![],
+            staked: TokenSet::default(),
+            spilled_tokens: None,
+        }
+    }
+
+    /// Returns the tokens owned by this account, loading them if they are kept in their own block
+    pub fn owned_tokens<BS: Blockstore>(&self, bs: &BS) -> Result<TokenSet> {
+        let Some(cid) = &self.spilled_tokens else {
+            return Ok(self.tokens.clone());
+        };
+        match bs.get_cbor::<TokenSet>(cid) {
+            Ok(Some(tokens)) => Ok(tokens),
+            Ok(None) => {
+                Err(StateError::InvariantFailed(format!("owned tokens not found at {cid}")))
+            }
+            Err(e) => Err(StateError::InvariantFailed(e.to_string())),
+        }
+    }
+
+    /// Returns the number of tokens the operator may still transfer or burn, or None if the
+    /// operator is not limited by a budget
+    pub fn budget_of(&self, operator: &ActorID) -> Option<u64> {
+        self.operator_budgets
+            .binary_search_by_key(operator, |b| b.operator)
+            .ok()
+            .map(|pos| self.operator_budgets[pos].remaining)
+    }
+
+    /// Sets the token budget of an operator, or removes the limit if no budget is given
+    fn set_budget(&mut self, operator: ActorID, budget: Option<u64>) {
+        let budgets = &mut self.operator_budgets;
+        match (budgets.binary_search_by_key(&operator, |b| b.operator), budget) {
+            (Ok(pos), Some(remaining)) => budgets[pos].remaining = remaining,
+            (Ok(pos), None) => {
+                budgets.remove(pos);
+            }
+            (Err(pos), Some(remaining)) => {
+                budgets.insert(pos, OperatorBudget { operator, remaining })
+            }
+            (Err(_), None) => {}
+        }
+    }
+
+    /// Drops the budgets of operators that are no longer approved
+    fn prune_budgets(&mut self) {
+        let operators = &self.operators;
+        self.operator_budgets.retain(|b| operators.get(b.operator));
+    }
+
+    /// Records tokens as owned by this account
+    pub(crate) fn add_tokens<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        token_ids: &[TokenID],
+    ) -> Result<()> {
+        let mut tokens = self.owned_tokens(bs)?;
+        token_ids.iter().for_each(|&token_id| tokens.set(token_id));
+        self.balance += token_ids.len() as u64;
+        self.store_tokens(bs, tokens)
+    }
+
+    /// Removes tokens from the set owned by this account
+    fn remove_tokens<BS: Blockstore>(&mut self, bs: &BS, token_ids: &[TokenID]) -> Result<()> {
+        let mut tokens = self.owned_tokens(bs)?;
+        token_ids.iter().for_each(|&token_id| tokens.unset(token_id));
+        self.balance -= token_ids.len() as u64;
+        self.store_tokens(bs, tokens)
+    }
+
+    /// Keeps the owned tokens inline while there are fewer than [`OWNER_TOKENS_INLINE_LIMIT`], and
+    /// in their own block otherwise
+    fn store_tokens<BS: Blockstore>(&mut self, bs: &BS, tokens: TokenSet) -> Result<()> {
+        if tokens.len() < OWNER_TOKENS_INLINE_LIMIT {
+            self.tokens = tokens;
+            self.spilled_tokens = None;
+        } else {
+            let cid = bs
+                .put_cbor(&tokens, Code::Blake2b256)
+                .map_err(|e| StateError::InvariantFailed(e.to_string()))?;
+            self.tokens = TokenSet::default();
+            self.spilled_tokens = Some(cid);
+        }
+        Ok(())
+    }
+
+    /// Checks that the owned tokens are kept inline exactly when there are few enough of them
+    fn stores_tokens_correctly(&self) -> bool {
+        match self.spilled_tokens {
+            Some(_) => self.tokens.is_empty() && self.balance >= OWNER_TOKENS_INLINE_LIMIT,
+            None => self.balance < OWNER_TOKENS_INLINE_LIMIT,
+        }
+    }
+
+    /// An owner entry with no tokens and no operators should not be stored
+    fn is_empty(&self) -> bool {
+        self.balance == 0 && self.operators.is_empty()
+    }
+}
+
+impl ExpiringOperatorSet for TokenData {
+    fn operators(&self) -> &BitField {
+        &self.operators
+    }
+
+    fn operators_mut(&mut self) -> &mut BitField {
+        &mut self.operators
+    }
+
+    fn operator_expiries(&self) -> &Vec<OperatorExpiry> {
+        &self.operator_expiries
+    }
+
+    fn operator_expiries_mut(&mut self) -> &mut Vec<OperatorExpiry> {
+        &mut self.operator_expiries
+    }
+}
+
+impl ExpiringOperatorSet for OwnerData {
+    fn operators(&self) -> &BitField {
+        &self.operators
+    }
+
+    fn operators_mut(&mut self) -> &mut BitField {
+        &mut self.operators
+    }
+
+    fn operator_expiries(&self) -> &Vec<OperatorExpiry> {
+        &self.operator_expiries
+    }
+
+    fn operator_expiries_mut(&mut self) -> &mut Vec<OperatorExpiry> {
+        &mut self.operator_expiries
+    }
+}
+
+/// Tracks the number of tokens minted in the most recent epoch that saw a mint, to limit the rate
+/// of minting
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct MintRateLimit {
+    /// The maximum number of tokens that may be minted in a single epoch
+    pub max_per_epoch: u64,
+    /// The last epoch in which tokens were minted
+    pub epoch: ChainEpoch,
+    /// The number of tokens minted in `epoch`
+    pub minted: u64,
+}
+
+impl MintRateLimit {
+    pub fn new(max_per_epoch: u64) -> Self {
+        Self { max_per_epoch, epoch: ChainEpoch::MIN, minted: 0 }
+    }
+
+    /// Returns the number of tokens that may still be minted at the current epoch
+    pub fn remaining(&self, current_epoch: ChainEpoch) -> u64 {
+        if current_epoch == self.epoch {
+            self.max_per_epoch.saturating_sub(self.minted)
+        } else {
+            self.max_per_epoch
+        }
+    }
 }
 
 /// NFT state IPLD structure
 #[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
 pub struct NFTState {
+    /// Version of the state layout, see [`migration`](crate::migration)
+    pub version: u64,
     /// Amt<TokenId, TokenData> encodes information per token - ownership, operators, metadata etc.
     pub token_data: Cid,
     /// Hamt<ActorID, OwnerData> index for faster lookup of data often queried by owner
@@ -72,11 +347,41 @@ pub struct NFTState {
     pub next_token: TokenID,
     /// The number of minted tokens less the number of burned tokens
     pub total_supply: u64,
+    /// Descriptive metadata for the collection as a whole
+    pub collection_metadata: CollectionMetadata,
+    /// Whether minting, transferring and burning are suspended across the collection
+    pub paused: bool,
+    /// The maximum number of tokens that may ever be minted, burned tokens included
+    pub max_supply: Option<u64>,
+    /// The maximum number of tokens that may be minted per epoch
+    pub mint_rate_limit: Option<MintRateLimit>,
+    /// Commitment to the collection's metadata for a delayed reveal
+    pub provenance: Option<Provenance>,
+    /// Record of ownership changes, if enabled
+    pub ownership_history: Option<OwnershipHistory>,
+    /// The number of epochs a token must remain staked before it can be unstaked
+    pub min_stake_duration: Option<ChainEpoch>,
+    /// Members of each collection-level role, sorted by role
+    pub roles: Vec<RoleMembers>,
+    /// Recipients of royalty payouts and their shares
+    pub payout_shares: Vec<PayoutShare>,
+    /// Hamt<ActorID, TokenSet> of the tokens each operator is approved for, if enabled
+    pub operator_index: Option<Cid>,
 }
 
-// TODO: benchmark and tune these values
-const AMT_BIT_WIDTH: u32 = 5;
-const HAMT_BIT_WIDTH: u32 = 3;
+impl Transactional for NFTState {}
+
+/// Bit width of the AMT of token data in newly created state
+///
+/// The bit width is recorded in the root of the AMT, so state created with another bit width (see
+/// [`NFTState::new_with_bit_width`]) remains readable. The `state_layout` benchmarks compare the
+/// blocks touched by mints and transfers across bit widths.
+pub const DEFAULT_AMT_BIT_WIDTH: u32 = 5;
+/// Bit width of the HAMT of owner data
+///
+/// Unlike the AMT bit width, this is not recorded in the state, so changing it requires a migration
+/// of existing state.
+pub const HAMT_BIT_WIDTH: u32 = 3;
 
 type Result<T> = std::result::Result<T, StateError>;
 
@@ -95,39 +400,164 @@ pub enum StateError {
     NotOwner { actor: ActorID, token_id: TokenID },
     #[error("actor {actor:?} is not authorized for token {token_id:?}")]
     NotAuthorized { actor: ActorID, token_id: TokenID },
+    #[error(
+        "actor {operator:?} is neither an account-level operator for {owner:?} nor approved for token {token_id:?}"
+    )]
+    NotOperator { operator: ActorID, owner: ActorID, token_id: TokenID },
+    #[error("operator {operator:?} may not transfer token {token_id:?} to {recipient:?}")]
+    RecipientNotAllowed { operator: ActorID, token_id: TokenID, recipient: ActorID },
     #[error("receiver hook error: {0}")]
     ReceiverHook(#[from] ReceiverHookError),
     #[error("invalid cursor")]
     InvalidCursor,
+    #[error("collection is paused")]
+    Paused,
+    #[error("minting {requested:?} tokens would exceed the max supply of {max_supply:?}")]
+    MaxSupplyExceeded { max_supply: u64, requested: u64 },
+    #[error(
+        "minting {requested:?} tokens would exceed the limit of {remaining:?} remaining this epoch"
+    )]
+    MintRateExceeded { remaining: u64, requested: u64 },
+    #[error("max supply {max_supply:?} is less than the {minted:?} tokens already minted")]
+    InvalidMaxSupply { max_supply: u64, minted: u64 },
+    #[error("provenance can only be committed once, before any tokens are minted")]
+    ProvenanceAlreadyCommitted,
+    #[error("revealed metadata does not match the committed provenance hash")]
+    ProvenanceMismatch,
+    #[error("collection has no pending reveal")]
+    NotRevealable,
+    #[error("reveal mapping does not assign each entry of the metadata list to exactly one token")]
+    InvalidRevealMapping,
+    #[error("approval expiry {expiry:?} has already passed at epoch {current_epoch:?}")]
+    ExpiryInPast { expiry: ChainEpoch, current_epoch: ChainEpoch },
+    #[error("token {0:?} is nested inside another token and cannot be moved or burned directly")]
+    TokenNested(TokenID),
+    #[error("token {0:?} has nested tokens and cannot be burned")]
+    HasChildren(TokenID),
+    #[error("nesting token {child:?} inside token {parent:?} would create a cycle")]
+    NestingCycle { child: TokenID, parent: TokenID },
+    #[error(
+        "operator {operator:?} may act on {remaining:?} more tokens of {owner:?} but {requested:?} were requested"
+    )]
+    OperatorBudgetExceeded { operator: ActorID, owner: ActorID, remaining: u64, requested: u64 },
+    #[error("ownership history is not available for epoch {0:?}")]
+    OwnershipHistoryUnavailable(ChainEpoch),
+    #[error("token {0:?} is staked and cannot be moved or burned")]
+    TokenStaked(TokenID),
+    #[error("token {0:?} is not staked")]
+    NotStaked(TokenID),
+    #[error("token {token_id:?} cannot be unstaked before epoch {unlocks_at:?}")]
+    StakeLocked { token_id: TokenID, unlocks_at: ChainEpoch },
+    #[error("actor {actor:?} does not hold the {role:?} role")]
+    MissingRole { actor: ActorID, role: String },
+    #[error("{data_count:?} token data entries were supplied for {token_count:?} tokens")]
+    TokenDataMismatch { token_count: usize, data_count: usize },
+    #[error("payout recipients must be unique with non-zero shares")]
+    InvalidPayoutShares,
+    #[error("the operator index is not enabled")]
+    OperatorIndexDisabled,
+    #[error("state is in an older layout and needs to be migrated")]
+    MigrationRequired,
     /// This error is returned for errors that should never happen
     #[error("invariant failed: {0}")]
     InvariantFailed(String),
 }
 
+impl From<&StateError> for ExitCode {
+    fn from(error: &StateError) -> Self {
+        match error {
+            StateError::IpldAmt(_) | StateError::IpldHamt(_) => ExitCode::USR_SERIALIZATION,
+            StateError::TokenNotFound(_) => ExitCode::USR_NOT_FOUND,
+            StateError::NotOwner { actor: _, token_id: _ }
+            | StateError::NotAuthorized { actor: _, token_id: _ }
+            | StateError::NotOperator { operator: _, owner: _, token_id: _ }
+            | StateError::RecipientNotAllowed { operator: _, token_id: _, recipient: _ }
+            | StateError::Paused
+            | StateError::MaxSupplyExceeded { max_supply: _, requested: _ }
+            | StateError::MintRateExceeded { remaining: _, requested: _ } => {
+                ExitCode::USR_FORBIDDEN
+            }
+            StateError::ReceiverHook(e) => e.into(),
+            StateError::InvalidCursor
+            | StateError::InvalidMaxSupply { max_supply: _, minted: _ }
+            | StateError::ProvenanceMismatch
+            | StateError::InvalidRevealMapping
+            | StateError::ExpiryInPast { expiry: _, current_epoch: _ }
+            | StateError::NestingCycle { child: _, parent: _ }
+            | StateError::OwnershipHistoryUnavailable(_)
+            | StateError::NotStaked(_)
+            | StateError::TokenDataMismatch { token_count: _, data_count: _ }
+            | StateError::InvalidPayoutShares => ExitCode::USR_ILLEGAL_ARGUMENT,
+            StateError::TokenNested(_)
+            | StateError::HasChildren(_)
+            | StateError::TokenStaked(_)
+            | StateError::StakeLocked { token_id: _, unlocks_at: _ }
+            | StateError::MissingRole { actor: _, role: _ }
+            | StateError::OperatorBudgetExceeded {
+                operator: _,
+                owner: _,
+                remaining: _,
+                requested: _,
+            } => ExitCode::USR_FORBIDDEN,
+            StateError::ProvenanceAlreadyCommitted
+            | StateError::NotRevealable
+            | StateError::OperatorIndexDisabled
+            | StateError::MigrationRequired
+            | StateError::InvariantFailed(_) => ExitCode::USR_ILLEGAL_STATE,
+        }
+    }
+}
+
 impl NFTState {
     /// Create a new NFT state-tree, without committing it (the root Cid) to a blockstore
     pub fn new<BS: Blockstore>(store: &BS) -> Result<Self> {
+        Self::new_with_metadata(store, CollectionMetadata::default())
+    }
+
+    /// Create a new NFT state-tree, specifying the bit width of the token data AMT, without
+    /// committing it (the root Cid) to a blockstore
+    pub fn new_with_bit_width<BS: Blockstore>(store: &BS, amt_bit_width: u32) -> Result<Self> {
+        let mut state = Self::new(store)?;
+        state.token_data =
+            Amt::<TokenData, &BS>::new_with_bit_width(store, amt_bit_width).flush()?;
+        Ok(state)
+    }
+
+    /// Create a new NFT state-tree with the given collection metadata, without committing it (the
+    /// root Cid) to a blockstore
+    pub fn new_with_metadata<BS: Blockstore>(
+        store: &BS,
+        collection_metadata: CollectionMetadata,
+    ) -> Result<Self> {
         // Blockstore is still needed to create valid Cids for the Hamts
         let empty_token_array =
-            Amt::<TokenData, &BS>::new_with_bit_width(store, AMT_BIT_WIDTH).flush()?;
+            Amt::<TokenData, &BS>::new_with_bit_width(store, DEFAULT_AMT_BIT_WIDTH).flush()?;
         // Blockstore is still needed to create valid Cids for the Hamts
         let empty_owner_map =
             Hamt::<&BS, OwnerData, ActorID>::new_with_bit_width(store, HAMT_BIT_WIDTH).flush()?;
 
         Ok(Self {
+            version: STATE_VERSION,
             token_data: empty_token_array,
             owner_data: empty_owner_map,
             next_token: 0,
             total_supply: 0,
+            collection_metadata,
+            paused: false,
+            max_supply: None,
+            mint_rate_limit: None,
+            provenance: None,
+            ownership_history: None,
+            min_stake_duration: None,
+            roles: vec![],
+            payout_shares: vec![],
+            operator_index: None,
         })
     }
 
+    /// Loads the state at `root`, see [`migration`](crate::migration) for state in older layouts
     pub fn load<BS: Blockstore>(store: &BS, root: &Cid) -> Result<Self> {
-        match store.get_cbor::<Self>(root) {
-            Ok(Some(state)) => Ok(state),
-            Ok(None) => Err(StateError::InvariantFailed("State root not found".into())),
-            Err(e) => Err(StateError::InvariantFailed(e.to_string())),
-        }
+        migration::load_state(store, root)
     }
 
     pub fn save<BS: Blockstore>(&self, store: &BS) -> Result<Cid> {
@@ -143,6 +573,62 @@ impl NFTState {
         Ok(cid)
     }
 
+    /// Describes the encoding of the state for rendering with [`fvm_actor_utils::inspect`]
+    ///
+    /// Token and owner data are rendered in full, while smaller nested structures are left as
+    /// generic CBOR.
+    pub fn layout() -> Layout {
+        let token_data = Layout::fields([
+            ("owner", Layout::Cbor),
+            ("operators", Layout::Cbor),
+            ("metadata", Layout::Cbor),
+            ("operator_expiries", Layout::Cbor),
+            ("user", Layout::Cbor),
+            ("extra", Layout::Cbor),
+            ("parent", Layout::Cbor),
+            ("children", Layout::Cbor),
+            ("staked_at", Layout::Cbor),
+            ("operator_recipients", Layout::Cbor),
+        ]);
+        let owner_data = Layout::fields([
+            ("balance", Layout::Cbor),
+            ("tokens", Layout::Cbor),
+            ("operators", Layout::Cbor),
+            ("operator_expiries", Layout::Cbor),
+            ("operator_budgets", Layout::Cbor),
+            ("staked", Layout::Cbor),
+            ("spilled_tokens", Layout::Link(Box::new(Layout::Cbor))),
+        ]);
+        let checkpoint = Layout::fields([
+            ("epoch", Layout::Cbor),
+            ("previous_owner", Layout::Cbor),
+            ("owner", Layout::Cbor),
+        ]);
+        Layout::fields([
+            ("version", Layout::Cbor),
+            ("token_data", Layout::amt(token_data)),
+            ("owner_data", Layout::hamt(KeyFormat::Cbor, owner_data)),
+            ("next_token", Layout::Cbor),
+            ("total_supply", Layout::Cbor),
+            ("collection_metadata", Layout::Cbor),
+            ("paused", Layout::Cbor),
+            ("max_supply", Layout::Cbor),
+            ("mint_rate_limit", Layout::Cbor),
+            ("provenance", Layout::Cbor),
+            (
+                "ownership_history",
+                Layout::fields([
+                    ("enabled_at", Layout::Cbor),
+                    ("checkpoints", Layout::amt(Layout::List(Box::new(checkpoint)))),
+                ]),
+            ),
+            ("min_stake_duration", Layout::Cbor),
+            ("roles", Layout::Cbor),
+            ("payout_shares", Layout::Cbor),
+            ("operator_index", Layout::hamt(KeyFormat::ActorId, Layout::Cbor)),
+        ])
+    }
+
     pub fn get_token_data_amt<'bs, BS: Blockstore>(
         &self,
         store: &'bs BS,
@@ -178,34 +664,27 @@ impl NFTState {
 
 impl NFTState {
     /// Mint a new token to the specified address
+    ///
+    /// Fails if minting would exceed the max supply or the per-epoch mint limit of the collection
     pub fn mint_tokens<BS: Blockstore>(
         &mut self,
         bs: &BS,
         initial_owner: ActorID,
         metadatas: Vec<String>,
+        current_epoch: ChainEpoch,
     ) -> Result<MintIntermediate> {
+        self.assert_not_paused()?;
+        self.use_mint_allowance(metadatas.len() as u64, current_epoch)?;
         let first_token_id = self.next_token;
         let num_to_mint = metadatas.len();
 
         let mut token_array = self.get_token_data_amt(bs)?;
         let mut owner_map = self.get_owner_data_hamt(bs)?;
 
-        // update owner data map
-        let new_owner_data = match owner_map.get(&actor_id_key(initial_owner)) {
-            Ok(entry) => {
-                if let Some(existing_data) = entry {
-                    //TODO: a move or replace here may avoid the clone (which may be expensive on the vec)
-                    OwnerData {
-                        balance: existing_data.balance + metadatas.len() as u64,
-                        ..existing_data.clone()
-                    }
-                } else {
-                    OwnerData { balance: metadatas.len() as u64, operators: BitField::default() }
-                }
-            }
-            Err(e) => return Err(e.into()),
+        let mut new_owner_data = match owner_map.get(&actor_id_key(initial_owner))? {
+            Some(existing_data) => existing_data.clone(),
+            None => OwnerData::new(),
         };
-        owner_map.set(actor_id_key(initial_owner), new_owner_data)?;
 
         // update token data array
         for mut metadata in metadatas {
@@ -216,10 +695,24 @@ impl NFTState {
                     owner: initial_owner,
                     operators: BitField::default(),
                     metadata: mem::take(&mut metadata),
+                    operator_expiries: vec![],
+                    user: None,
+                    extra: RawBytes::default(),
+                    parent: None,
+                    children: TokenSet::default(),
+                    staked_at: None,
+                    operator_recipients: vec![],
                 },
             )?;
             self.next_token += 1;
         }
+        let minted: Vec<TokenID> = (first_token_id..self.next_token).collect();
+        new_owner_data.add_tokens(bs, &minted)?;
+
+        // update owner data map, which has no entry for an owner minted no tokens
+        if !new_owner_data.is_empty() {
+            owner_map.set(actor_id_key(initial_owner), new_owner_data)?;
+        }
 
         // update global trackers
         self.total_supply += num_to_mint as u64;
@@ -230,10 +723,85 @@ impl NFTState {
         Ok(MintIntermediate {
             to: initial_owner,
             recipient_data: RawBytes::default(),
-            token_ids: (first_token_id..self.next_token).collect(),
+            token_ids: minted,
         })
     }
 
+    /// Replaces the collection-level metadata, returning the previous value
+    pub fn set_collection_metadata(
+        &mut self,
+        collection_metadata: CollectionMetadata,
+    ) -> CollectionMetadata {
+        mem::replace(&mut self.collection_metadata, collection_metadata)
+    }
+
+    /// Replaces the base URI from which token metadata is derived, returning the previous value
+    pub fn set_base_uri(&mut self, base_uri: String) -> String {
+        mem::replace(&mut self.collection_metadata.base_uri, base_uri)
+    }
+
+    /// Sets the maximum number of tokens that may ever be minted
+    ///
+    /// The max supply cannot be set below the number of tokens already minted
+    pub fn set_max_supply(&mut self, max_supply: Option<u64>) -> Result<()> {
+        if let Some(max_supply) = max_supply {
+            if max_supply < self.next_token {
+                return Err(StateError::InvalidMaxSupply { max_supply, minted: self.next_token });
+            }
+        }
+        self.max_supply = max_supply;
+        Ok(())
+    }
+
+    /// Sets the maximum number of tokens that may be minted per epoch
+    pub fn set_mint_limit_per_epoch(&mut self, max_per_epoch: Option<u64>) {
+        self.mint_rate_limit = max_per_epoch.map(|max_per_epoch| match &self.mint_rate_limit {
+            // keep the tally of the current epoch
+            Some(limit) => MintRateLimit { max_per_epoch, ..limit.clone() },
+            None => MintRateLimit::new(max_per_epoch),
+        });
+    }
+
+    /// Returns the number of tokens that may still be minted, or None if there is no max supply
+    pub fn remaining_supply(&self) -> Option<u64> {
+        self.max_supply.map(|max_supply| max_supply.saturating_sub(self.next_token))
+    }
+
+    /// Checks that minting the requested number of tokens respects the supply limits of the
+    /// collection, recording the mint against the per-epoch limit
+    fn use_mint_allowance(&mut self, requested: u64, current_epoch: ChainEpoch) -> Result<()> {
+        if let Some(max_supply) = self.max_supply {
+            if self.remaining_supply().unwrap_or_default() < requested {
+                return Err(StateError::MaxSupplyExceeded { max_supply, requested });
+            }
+        }
+        if let Some(limit) = &mut self.mint_rate_limit {
+            let remaining = limit.remaining(current_epoch);
+            if remaining < requested {
+                return Err(StateError::MintRateExceeded { remaining, requested });
+            }
+            if limit.epoch != current_epoch {
+                limit.epoch = current_epoch;
+                limit.minted = 0;
+            }
+            limit.minted += requested;
+        }
+        Ok(())
+    }
+
+    /// Suspends or resumes minting, transferring and burning across the collection
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Asserts that the collection is not paused
+    pub fn assert_not_paused(&self) -> Result<()> {
+        if self.paused {
+            return Err(StateError::Paused);
+        }
+        Ok(())
+    }
+
     /// Get the number of tokens owned by a particular address
     pub fn get_balance<BS: Blockstore>(&self, bs: &BS, owner: ActorID) -> Result<u64> {
         let owner_data = self.get_owner_data_hamt(bs)?;
@@ -247,28 +815,72 @@ impl NFTState {
 
     /// Approves an operator to transfer a set of specified tokens
     ///
+    /// The approval lapses after the `expiry` epoch if one is given, otherwise it lasts until
+    /// revoked or the token is transferred. Expired approvals on the affected tokens are cleaned up.
+    ///
     /// The caller should own the tokens or an account-level operator on the owner of the tokens.
     pub fn approve_for_tokens<F, BS: Blockstore>(
         &mut self,
         bs: &BS,
         operator: ActorID,
         token_ids: &[TokenID],
+        expiry: Option<ChainEpoch>,
+        current_epoch: ChainEpoch,
+        approve_predicate: F,
+    ) -> Result<()>
+    where
+        F: Fn(&TokenData, TokenID) -> Result<()>,
+    {
+        self.approve_for_tokens_to(
+            bs,
+            operator,
+            token_ids,
+            None,
+            expiry,
+            current_epoch,
+            approve_predicate,
+        )
+    }
+
+    /// Approves an operator to transfer a set of specified tokens, only to the `recipient` if one
+    /// is given
+    ///
+    /// An operator restricted to a recipient can't transfer the tokens elsewhere, nor burn them or
+    /// act on them in other ways. Approving the operator again replaces any previous restriction.
+    /// Otherwise as for [`approve_for_tokens`](Self::approve_for_tokens).
+    #[allow(clippy::too_many_arguments)]
+    pub fn approve_for_tokens_to<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        operator: ActorID,
+        token_ids: &[TokenID],
+        recipient: Option<ActorID>,
+        expiry: Option<ChainEpoch>,
+        current_epoch: ChainEpoch,
         approve_predicate: F,
     ) -> Result<()>
     where
         F: Fn(&TokenData, TokenID) -> Result<()>,
     {
+        assert_expiry_valid(expiry, current_epoch)?;
         let mut token_array = self.get_token_data_amt(bs)?;
+        let mut operator_index = self.load_operator_index(bs)?;
 
         for &token_id in token_ids {
             let mut token_data =
                 token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?.clone();
             approve_predicate(&token_data, token_id)?;
-            token_data.operators.add_operator(operator);
+            let previous_operators = token_data.operators.clone();
+            token_data.prune_expired(current_epoch);
+            token_data.approve_operator(operator, expiry);
+            token_data.set_recipient(operator, recipient);
+            token_data.prune_recipients();
+            operator_index.update(token_id, &previous_operators, &token_data.operators)?;
             token_array.set(token_id, token_data)?;
         }
 
         self.token_data = token_array.flush()?;
+        self.operator_index = operator_index.flush()?;
 
         Ok(())
     }
@@ -287,15 +899,20 @@ impl NFTState {
         F: Fn(&TokenData, TokenID) -> Result<()>,
     {
         let mut token_array = self.get_token_data_amt(bs)?;
+        let mut operator_index = self.load_operator_index(bs)?;
         for &token_id in token_ids {
             let mut token_data =
                 token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?.clone();
             revoke_predicate(&token_data, token_id)?;
-            token_data.operators.remove_operator(&operator);
+            let previous_operators = token_data.operators.clone();
+            token_data.revoke_operator(&operator);
+            token_data.set_recipient(operator, None);
+            operator_index.update(token_id, &previous_operators, &token_data.operators)?;
             token_array.set(token_id, token_data)?;
         }
 
         self.token_data = token_array.flush()?;
+        self.operator_index = operator_index.flush()?;
 
         Ok(())
     }
@@ -306,23 +923,52 @@ impl NFTState {
     /// can be transferred, approved or burned by the operator, including future tokens owned by the
     /// account
     ///
+    /// The approval lapses after the `expiry` epoch if one is given, otherwise it lasts until
+    /// revoked. Expired account-level approvals of the owner are cleaned up.
+    ///
     /// The caller should be the owning account.
     pub fn approve_for_owner<BS: Blockstore>(
         &mut self,
         bs: &BS,
         owner: ActorID,
         operator: ActorID,
+        expiry: Option<ChainEpoch>,
+        current_epoch: ChainEpoch,
     ) -> Result<()> {
+        self.approve_for_owner_with_budget(bs, owner, operator, expiry, None, current_epoch)
+    }
+
+    /// Approves an operator to act on behalf of the owner for at most `budget` tokens
+    ///
+    /// Each token transferred or burned by the operator on the strength of its account-level
+    /// approval is deducted from the budget, and the approval is revoked once the budget is spent.
+    /// A `budget` of None grants an unlimited approval. Re-approving an operator replaces its
+    /// previous budget.
+    ///
+    /// The caller should be the owning account.
+    pub fn approve_for_owner_with_budget<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        owner: ActorID,
+        operator: ActorID,
+        expiry: Option<ChainEpoch>,
+        budget: Option<u64>,
+        current_epoch: ChainEpoch,
+    ) -> Result<()> {
+        assert_expiry_valid(expiry, current_epoch)?;
         let mut owner_map = self.get_owner_data_hamt(bs)?;
 
-        let new_owner_data = match owner_map.get(&actor_id_key(owner))? {
+        let mut new_owner_data = match owner_map.get(&actor_id_key(owner))? {
             Some(data) => {
-                let mut operators = data.operators.clone();
-                operators.add_operator(operator);
-                OwnerData { operators, balance: data.balance }
+                let mut data = data.clone();
+                data.prune_expired(current_epoch);
+                data.prune_budgets();
+                data
             }
-            None => OwnerData { balance: 0, operators: BitField::default() },
+            None => OwnerData::new(),
         };
+        new_owner_data.approve_operator(operator, expiry);
+        new_owner_data.set_budget(operator, budget);
         owner_map.set(actor_id_key(owner), new_owner_data)?;
 
         self.owner_data = owner_map.flush()?;
@@ -342,14 +988,15 @@ impl NFTState {
         let mut owner_map = self.get_owner_data_hamt(bs)?;
 
         let new_owner_data = owner_map.get(&actor_id_key(owner))?.map(|existing_data| {
-            let mut operators = existing_data.operators.clone();
-            operators.remove_operator(&operator);
-            OwnerData { balance: existing_data.balance, operators }
+            let mut data = existing_data.clone();
+            data.revoke_operator(&operator);
+            data.set_budget(operator, None);
+            data
         });
 
         if let Some(data) = new_owner_data {
             let actor_key = actor_id_key(owner);
-            if data.balance == 0 && data.operators.is_empty() {
+            if data.is_empty() {
                 owner_map.delete(&actor_key)?;
             } else {
                 owner_map.set(actor_key, data)?;
@@ -361,6 +1008,66 @@ impl NFTState {
         Ok(())
     }
 
+    /// Deducts tokens acted on by an account-level operator from its budget
+    ///
+    /// Operators approved without a budget are unaffected. Fails if the operator's remaining budget
+    /// is smaller than `count`. An operator whose budget is spent has its approval revoked.
+    pub fn use_operator_budget<BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        owner: ActorID,
+        operator: ActorID,
+        count: u64,
+    ) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        let mut owner_map = self.get_owner_data_hamt(bs)?;
+        let owner_key = actor_id_key(owner);
+        let mut data = match owner_map.get(&owner_key)? {
+            Some(data) => data.clone(),
+            None => return Ok(()),
+        };
+        let remaining = match data.budget_of(&operator) {
+            Some(remaining) => remaining,
+            None => return Ok(()),
+        };
+        if remaining < count {
+            return Err(StateError::OperatorBudgetExceeded {
+                operator,
+                owner,
+                remaining,
+                requested: count,
+            });
+        }
+
+        if remaining == count {
+            data.revoke_operator(&operator);
+            data.set_budget(operator, None);
+        } else {
+            data.set_budget(operator, Some(remaining - count));
+        }
+        if data.is_empty() {
+            owner_map.delete(&owner_key)?;
+        } else {
+            owner_map.set(owner_key, data)?;
+        }
+        self.owner_data = owner_map.flush()?;
+        Ok(())
+    }
+
+    /// Returns the remaining token budget of an account-level operator, or None if the operator
+    /// is not limited by a budget
+    pub fn get_operator_budget<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        owner: ActorID,
+        operator: ActorID,
+    ) -> Result<Option<u64>> {
+        let owner_map = self.get_owner_data_hamt(bs)?;
+        Ok(owner_map.get(&actor_id_key(owner))?.and_then(|data| data.budget_of(&operator)))
+    }
+
     /// Burns a set of tokens, removing them from circulation and deleting associated metadata
     ///
     /// If any of the token_ids cannot be burned (e.g. non-existent, already burned), the entire
@@ -368,24 +1075,43 @@ impl NFTState {
     ///
     /// The tokens must all be owned by the same owner
     ///
-    /// Returns the new balance of the owner if all tokens were burned successfully
+    /// Returns the new balance of the owner and the previous owner and cleared token-level
+    /// operators of each token if all tokens were burned successfully
     pub fn burn_tokens<F, BS: Blockstore>(
         &mut self,
         bs: &BS,
         owner: ActorID,
         token_ids: &[TokenID],
         burn_predicate: F,
-    ) -> Result<u64>
+    ) -> Result<BurnReturn>
     where
         F: Fn(&TokenData, TokenID) -> Result<()>,
     {
+        self.assert_not_paused()?;
         let mut token_array = self.get_token_data_amt(bs)?;
         let mut owner_map = self.get_owner_data_hamt(bs)?;
+        let mut operator_index = self.load_operator_index(bs)?;
 
+        let mut burned = Vec::with_capacity(token_ids.len());
         for &token_id in token_ids {
             let token_data =
                 token_array.delete(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
             burn_predicate(&token_data, token_id)?;
+            if token_data.parent.is_some() {
+                return Err(StateError::TokenNested(token_id));
+            }
+            if !token_data.children.is_empty() {
+                return Err(StateError::HasChildren(token_id));
+            }
+            if token_data.staked_at.is_some() {
+                return Err(StateError::TokenStaked(token_id));
+            }
+            operator_index.clear(token_id, &token_data.operators)?;
+            burned.push(BurnedToken {
+                token_id,
+                previous_owner: token_data.owner,
+                cleared_operators: token_data.operators,
+            });
         }
 
         // we only reach here if all tokens were burned successfully so assume the caller is valid
@@ -394,11 +1120,11 @@ impl NFTState {
             .get(&owner_key)?
             .ok_or_else(|| StateError::InvariantFailed("owner of tokens not found".into()))?
             .clone();
-        let new_balance = new_owner_data.balance - token_ids.len() as u64;
 
-        // update the owner's balance
-        new_owner_data.balance = new_balance;
-        if new_owner_data.balance == 0 && new_owner_data.operators.is_empty() {
+        // update the owner's balance and owned tokens
+        new_owner_data.remove_tokens(bs, token_ids)?;
+        let new_balance = new_owner_data.balance;
+        if new_owner_data.is_empty() {
             owner_map.delete(&owner_key)?;
         } else {
             owner_map.set(owner_key, new_owner_data)?;
@@ -407,8 +1133,9 @@ impl NFTState {
         self.total_supply -= token_ids.len() as u64;
         self.token_data = token_array.flush()?;
         self.owner_data = owner_map.flush()?;
+        self.operator_index = operator_index.flush()?;
 
-        Ok(new_balance)
+        Ok(BurnReturn { balance: new_balance, supply: self.total_supply, burned })
     }
 
     /// Transfers a batch of tokens between the owner and receiver
@@ -416,6 +1143,9 @@ impl NFTState {
     /// The predicate is checked for each token to be transferred, and the entire transfer is
     /// aborted if the predicate fails. It is the caller's responsibility to check that the
     /// actor using this method is permitted to do so.
+    ///
+    /// Tokens nested inside the transferred tokens move with them, and the returned intermediate
+    /// lists them after `token_ids`, as given by [`NFTState::with_descendants`].
     pub fn transfer<F, BS: Blockstore>(
         &mut self,
         bs: &BS,
@@ -427,38 +1157,99 @@ impl NFTState {
     where
         F: Fn(&TokenData, TokenID) -> Result<()>,
     {
+        self.assert_not_paused()?;
         let mut token_array = self.get_token_data_amt(bs)?;
         let mut owner_map = self.get_owner_data_hamt(bs)?;
+        let mut operator_index = self.load_operator_index(bs)?;
 
+        let mut moved = token_ids.to_vec();
         for &token_id in token_ids {
             // update the token_data to reflect the new owner and clear approved operators
-            self.make_transfer(
+            moved.extend(self.make_transfer(
+                bs,
                 &mut token_array,
                 &mut owner_map,
+                &mut operator_index,
                 token_id,
                 receiver,
                 transfer_predicate,
-            )?;
+            )?);
         }
 
+        self.token_data = token_array.flush()?;
+        self.owner_data = owner_map.flush()?;
+        self.operator_index = operator_index.flush()?;
+
         Ok(TransferIntermediate {
-            token_ids: token_ids.into(),
+            token_ids: moved,
             from: owner,
             to: receiver,
             recipient_data: RawBytes::default(),
         })
     }
 
+    /// Transfers different sets of tokens from a single owner to several receivers
+    ///
+    /// The token AMT and owner HAMT are flushed once after all assignments have been applied.
+    /// Returns one TransferIntermediate per receiver, in the order the assignments were given.
+    pub fn transfer_multi<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        assignments: &[(ActorID, Vec<TokenID>)],
+        owner: ActorID,
+        transfer_predicate: &F,
+    ) -> Result<Vec<TransferIntermediate>>
+    where
+        F: Fn(&TokenData, TokenID) -> Result<()>,
+    {
+        self.assert_not_paused()?;
+        let mut token_array = self.get_token_data_amt(bs)?;
+        let mut owner_map = self.get_owner_data_hamt(bs)?;
+        let mut operator_index = self.load_operator_index(bs)?;
+
+        let mut intermediates = Vec::with_capacity(assignments.len());
+        for (receiver, token_ids) in assignments {
+            let mut moved = token_ids.clone();
+            for &token_id in token_ids {
+                moved.extend(self.make_transfer(
+                    bs,
+                    &mut token_array,
+                    &mut owner_map,
+                    &mut operator_index,
+                    token_id,
+                    *receiver,
+                    transfer_predicate,
+                )?);
+            }
+            intermediates.push(TransferIntermediate {
+                token_ids: moved,
+                from: owner,
+                to: *receiver,
+                recipient_data: RawBytes::default(),
+            });
+        }
+
+        self.token_data = token_array.flush()?;
+        self.owner_data = owner_map.flush()?;
+        self.operator_index = operator_index.flush()?;
+
+        Ok(intermediates)
+    }
+
     /// Makes a transfer of a token from one address to another. The caller must verify that such a
     /// transfer is allowed.
+    ///
+    /// Returns the tokens nested inside the token, which move along with it.
     fn make_transfer<F, BS: Blockstore>(
         &mut self,
+        bs: &BS,
         token_array: &mut Amt<TokenData, &BS>,
         owner_map: &mut Hamt<&BS, OwnerData>,
+        operator_index: &mut OperatorIndex<BS>,
         token_id: TokenID,
         receiver: ActorID,
         transfer_predicate: &F,
-    ) -> Result<()>
+    ) -> Result<Vec<TokenID>>
     where
         F: Fn(&TokenData, TokenID) -> Result<()>,
     {
@@ -466,50 +1257,104 @@ impl NFTState {
             token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?.clone();
         // check the transfer against business rules
         transfer_predicate(&old_token_data, token_id)?;
+        // nested tokens only move along with their parent
+        if old_token_data.parent.is_some() {
+            return Err(StateError::TokenNested(token_id));
+        }
+
+        // tokens nested (at any depth) inside the transferred token move with it
+        let descendants = Self::descendants(token_array, &old_token_data)?;
+        Self::move_token(
+            bs,
+            token_array,
+            owner_map,
+            operator_index,
+            token_id,
+            old_token_data,
+            receiver,
+        )?;
+        for &child_id in &descendants {
+            let child_data = token_array
+                .get(child_id)?
+                .ok_or_else(|| {
+                    StateError::InvariantFailed(format!("nested token {child_id} not found"))
+                })?
+                .clone();
+            Self::move_token(
+                bs,
+                token_array,
+                owner_map,
+                operator_index,
+                child_id,
+                child_data,
+                receiver,
+            )?;
+        }
 
-        let new_token_data =
-            TokenData { owner: receiver, operators: BitField::default(), ..old_token_data };
+        Ok(descendants)
+    }
+
+    /// Reassigns a token to a new owner, clearing its approvals and user and updating the owner map
+    fn move_token<BS: Blockstore>(
+        bs: &BS,
+        token_array: &mut Amt<TokenData, &BS>,
+        owner_map: &mut Hamt<&BS, OwnerData>,
+        operator_index: &mut OperatorIndex<BS>,
+        token_id: TokenID,
+        old_token_data: TokenData,
+        receiver: ActorID,
+    ) -> Result<()> {
+        if old_token_data.staked_at.is_some() {
+            return Err(StateError::TokenStaked(token_id));
+        }
+        operator_index.clear(token_id, &old_token_data.operators)?;
+        let new_token_data = TokenData {
+            owner: receiver,
+            operators: BitField::default(),
+            operator_expiries: vec![],
+            operator_recipients: vec![],
+            user: None,
+            ..old_token_data
+        };
         token_array.set(token_id, new_token_data)?;
 
         let previous_owner_key = actor_id_key(old_token_data.owner);
-        let previous_owner_data = owner_map
+        let mut previous_owner_data = owner_map
             .get(&previous_owner_key)?
             .ok_or_else(|| {
                 StateError::InvariantFailed(format!("owner of token {token_id} not found"))
             })?
             .clone();
-        let previous_owner_data =
-            OwnerData { balance: previous_owner_data.balance - 1, ..previous_owner_data };
+        previous_owner_data.remove_tokens(bs, &[token_id])?;
 
-        if previous_owner_data.balance == 0 && previous_owner_data.operators.is_empty() {
+        if previous_owner_data.is_empty() {
             owner_map.delete(&previous_owner_key)?;
         } else {
             owner_map.set(previous_owner_key, previous_owner_data)?;
         }
 
         let new_owner_key = actor_id_key(receiver);
-        let new_owner_data = match owner_map.get(&new_owner_key)? {
-            Some(data) => OwnerData { balance: data.balance + 1, ..data.clone() },
-            None => OwnerData { balance: 1, operators: BitField::default() },
+        let mut new_owner_data = match owner_map.get(&new_owner_key)? {
+            Some(data) => data.clone(),
+            None => OwnerData::new(),
         };
+        new_owner_data.add_tokens(bs, &[token_id])?;
         owner_map.set(new_owner_key, new_owner_data)?;
 
-        self.token_data = token_array.flush()?;
-        self.owner_data = owner_map.flush()?;
-
         Ok(())
     }
 
-    /// Checks for account-level approval between owner and operator
+    /// Checks for unexpired account-level approval between owner and operator
     pub fn is_account_operator<BS: Blockstore>(
         owner_map: &Hamt<&BS, OwnerData>,
         owner: ActorID,
         operator: ActorID,
+        current_epoch: ChainEpoch,
     ) -> Result<bool> {
         let owner_data = owner_map
             .get(&actor_id_key(owner))?
             .ok_or_else(|| StateError::InvariantFailed(format!("owner {owner} not found")))?;
-        Ok(owner_data.operators.contains_actor(&operator))
+        Ok(owner_data.is_active_operator(&operator, current_epoch))
     }
 
     /// Asserts that the actor either owns the token or is an account level operator on the owner of the token
@@ -530,15 +1375,32 @@ impl NFTState {
         token_data: &TokenData,
         token_id: TokenID,
         operator: ActorID,
+        current_epoch: ChainEpoch,
     ) -> Result<()> {
-        // operator is approved at token-level
-        if !token_data.operators.contains_actor(&operator) {
+        // operator is approved at token-level and the approval has not expired
+        if !token_data.is_active_operator(&operator, current_epoch) {
             return Err(StateError::NotAuthorized { actor: operator, token_id });
         }
 
         Ok(())
     }
 
+    /// Asserts that the operator's token-level approval allows transferring the token to the
+    /// recipient
+    pub fn assert_recipient_allowed(
+        token_data: &TokenData,
+        token_id: TokenID,
+        operator: ActorID,
+        recipient: ActorID,
+    ) -> Result<()> {
+        match token_data.recipient_of(&operator) {
+            Some(allowed) if allowed != recipient => {
+                Err(StateError::RecipientNotAllowed { operator, token_id, recipient })
+            }
+            _ => Ok(()),
+        }
+    }
+
     // Asserts that a given account owns the specified token
     pub fn assert_owns_token(
         token_data: &TokenData,
@@ -585,10 +1447,55 @@ impl NFTState {
     }
 
     /// Get the metadata for a token
+    ///
+    /// Returns the metadata given by the provenance commitment if the collection has one, which is
+    /// the placeholder while the collection is awaiting reveal
+    ///
+    /// Tokens minted with empty metadata report the collection's `base_uri` followed by their
+    /// TokenID if a base URI is set. Metadata stored on a token always takes precedence.
     pub fn get_metadata<BS: Blockstore>(&self, bs: &BS, token_id: TokenID) -> Result<String> {
         let token_data_array = self.get_token_data_amt(bs)?;
         let token = token_data_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
-        Ok(token.metadata.clone())
+        let base_uri = &self.collection_metadata.base_uri;
+        match self.committed_metadata(bs, token_id)? {
+            Some(metadata) => Ok(metadata),
+            None if token.metadata.is_empty() && !base_uri.is_empty() => {
+                Ok(format!("{base_uri}{token_id}"))
+            }
+            None => Ok(token.metadata.clone()),
+        }
+    }
+
+    /// Get the extra data attached to a token
+    ///
+    /// Returns empty bytes if no data has been set
+    pub fn get_token_data<BS: Blockstore>(&self, bs: &BS, token_id: TokenID) -> Result<RawBytes> {
+        let token_data_array = self.get_token_data_amt(bs)?;
+        let token = token_data_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token.extra.clone())
+    }
+
+    /// Replace the extra data attached to a token
+    ///
+    /// The predicate is checked against the token before the data is changed. It is the caller's
+    /// responsibility to check that the actor using this method is permitted to do so.
+    pub fn set_token_data<F, BS: Blockstore>(
+        &mut self,
+        bs: &BS,
+        token_id: TokenID,
+        data: RawBytes,
+        predicate: F,
+    ) -> Result<()>
+    where
+        F: Fn(&TokenData, TokenID) -> Result<()>,
+    {
+        let mut token_array = self.get_token_data_amt(bs)?;
+        let token_data =
+            token_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?.clone();
+        predicate(&token_data, token_id)?;
+        token_array.set(token_id, TokenData { extra: data, ..token_data })?;
+        self.token_data = token_array.flush()?;
+        Ok(())
     }
 
     /// Get the owner of a token
@@ -598,6 +1505,86 @@ impl NFTState {
         Ok(token.owner)
     }
 
+    /// Get the unexpired token-level operators of a token
+    ///
+    /// Account-level operators of the token's owner are not included
+    pub fn get_token_operators<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+        current_epoch: ChainEpoch,
+    ) -> Result<ActorIDSet> {
+        let token_data_array = self.get_token_data_amt(bs)?;
+        let token = token_data_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token.active_operators(current_epoch))
+    }
+
+    /// Get the unexpired token-level approvals of a token along with their expiry epochs
+    ///
+    /// Account-level operators of the token's owner are not included
+    pub fn list_token_approvals<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+        current_epoch: ChainEpoch,
+    ) -> Result<Vec<OperatorApproval>> {
+        let token_data_array = self.get_token_data_amt(bs)?;
+        let token = token_data_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token
+            .active_operators(current_epoch)
+            .iter()
+            .map(|operator| OperatorApproval {
+                operator,
+                expiry: token.expiry_of(&operator),
+                budget: None,
+            })
+            .collect())
+    }
+
+    /// Checks if an actor holds an unexpired token-level approval on a token
+    pub fn is_token_operator<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+        operator: ActorID,
+        current_epoch: ChainEpoch,
+    ) -> Result<bool> {
+        let token_data_array = self.get_token_data_amt(bs)?;
+        let token = token_data_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token.is_active_operator(&operator, current_epoch))
+    }
+
+    /// Get the only recipient a token-level operator may transfer a token to, if it is restricted
+    pub fn get_operator_recipient<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        token_id: TokenID,
+        operator: ActorID,
+    ) -> Result<Option<ActorID>> {
+        let token_data_array = self.get_token_data_amt(bs)?;
+        let token = token_data_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        Ok(token.recipient_of(&operator))
+    }
+
+    /// Checks if an actor holds an unexpired account-level approval from an owner
+    ///
+    /// Unlike `is_account_operator`, an owner without an entry in the owner map is not an error and
+    /// simply has no operators
+    pub fn is_owner_operator<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        owner: ActorID,
+        operator: ActorID,
+        current_epoch: ChainEpoch,
+    ) -> Result<bool> {
+        let owner_map = self.get_owner_data_hamt(bs)?;
+        let is_operator = match owner_map.get(&actor_id_key(owner))? {
+            Some(data) => data.is_active_operator(&operator, current_epoch),
+            None => false,
+        };
+        Ok(is_operator)
+    }
+
     /// List all the minted tokens
     pub fn list_tokens<BS: Blockstore>(
         &self,
@@ -618,8 +1605,7 @@ impl NFTState {
         Ok((token_ids, next_cursor))
     }
 
-    /// List the tokens owned by an actor. In the reference implementation this is may be a
-    /// prohibitievly expensive operation as it involves iterating over the entire token set.
+    /// List the tokens owned by an actor, read from the owner's indexed token set.
     /// Returns a bitfield of the tokens owned by the actor and a cursor to the next page of data.
     pub fn list_owned_tokens<BS: Blockstore>(
         &self,
@@ -628,43 +1614,53 @@ impl NFTState {
         cursor: Option<Cursor>,
         limit: u64,
     ) -> Result<(TokenSet, Option<Cursor>)> {
-        let token_data_array = self.get_token_amt_for_cursor(bs, &cursor)?;
+        if let Some(cursor) = &cursor {
+            if cursor.root != self.owner_data {
+                return Err(StateError::InvalidCursor);
+            }
+        }
 
-        // Build the TokenSet
+        let owner_map = self.get_owner_data_hamt(bs)?;
+        let owned_tokens = match owner_map.get(&actor_id_key(owner))? {
+            Some(data) => data.owned_tokens(bs)?,
+            None => return Ok((TokenSet::new(), None)),
+        };
+
+        let range_start = cursor.map(|c| c.index).unwrap_or(0);
+        let range_end = range_start.saturating_add(limit);
         let mut token_ids = TokenSet::new();
-        let (_, next_key) =
-            token_data_array.for_each_ranged(cursor.map(|r| r.index), Some(limit), |i, data| {
-                if data.owner == owner {
-                    token_ids.set(i);
-                }
-                Ok(())
-            })?;
+        owned_tokens.iter().skip(range_start as usize).take(limit as usize).for_each(|token_id| {
+            token_ids.set(token_id);
+        });
 
-        let next_cursor = next_key.map(|key| Cursor::new(self.token_data, key));
+        let next_cursor = match owned_tokens.len() > range_end {
+            true => Some(Cursor::new(self.owner_data, range_end)),
+            false => None,
+        };
         Ok((token_ids, next_cursor))
     }
 
-    /// List all the token operators for a given token_id
+    /// List all the unexpired token operators for a given token_id
     pub fn list_token_operators<BS: Blockstore>(
         &self,
         bs: &BS,
         token_id: TokenID,
         cursor: Option<Cursor>,
         limit: u64,
+        current_epoch: ChainEpoch,
     ) -> Result<(ActorIDSet, Option<Cursor>)> {
         let token_data_array = self.get_token_amt_for_cursor(bs, &cursor)?;
         let token_data =
             token_data_array.get(token_id)?.ok_or(StateError::TokenNotFound(token_id))?;
+        let operators = token_data.active_operators(current_epoch);
 
         let range_start = cursor.map(|c| c.index).unwrap_or(0);
         let mut actor_set = ActorIDSet::new();
-        token_data.operators.iter().skip(range_start as usize).take(limit as usize).for_each(
-            |operator| {
-                actor_set.set(operator);
-            },
-        );
+        operators.iter().skip(range_start as usize).take(limit as usize).for_each(|operator| {
+            actor_set.set(operator);
+        });
 
-        let next_cursor = match token_data.operators.len() > range_start + limit {
+        let next_cursor = match operators.len() > range_start + limit {
             true => Some(Cursor::new(self.token_data, range_start + limit)),
             false => None,
         };
@@ -672,13 +1668,14 @@ impl NFTState {
         Ok((actor_set, next_cursor))
     }
 
-    /// Enumerates tokens for which an account is an operator
+    /// Enumerates tokens for which an account is an unexpired operator
     pub fn list_operator_tokens<BS: Blockstore>(
         &self,
         bs: &BS,
         operator: ActorID,
         cursor: Option<Cursor>,
         limit: u64,
+        current_epoch: ChainEpoch,
     ) -> Result<(TokenSet, Option<Cursor>)> {
         let token_data_array = self.get_token_amt_for_cursor(bs, &cursor)?;
 
@@ -686,7 +1683,7 @@ impl NFTState {
         let mut operatable_tokens = TokenSet::new();
         let (_, next_key) =
             token_data_array.for_each_ranged(cursor.map(|r| r.index), Some(limit), |i, data| {
-                if data.operators.get(operator) {
+                if data.is_active_operator(&operator, current_epoch) {
                     operatable_tokens.set(i);
                 }
                 Ok(())
@@ -696,28 +1693,30 @@ impl NFTState {
         Ok((operatable_tokens, next_cursor))
     }
 
-    /// List all the token operators for a given account
+    /// List all the unexpired account-level operators for a given account
     pub fn list_account_operators<BS: Blockstore>(
         &self,
         bs: &BS,
         actor_id: ActorID,
         cursor: Option<Cursor>,
         limit: u64,
+        current_epoch: ChainEpoch,
     ) -> Result<(ActorIDSet, Option<Cursor>)> {
         let owner_data_map = self.get_owner_data_hamt(bs)?;
         let account = owner_data_map.get(&actor_id_key(actor_id))?;
         match account {
             Some(account) => {
+                let operators = account.active_operators(current_epoch);
                 let mut operator_set = ActorIDSet::new();
                 let range_start = cursor.map(|c| c.index).unwrap_or(0);
 
-                account.operators.iter().skip(range_start as usize).take(limit as usize).for_each(
+                operators.iter().skip(range_start as usize).take(limit as usize).for_each(
                     |operator| {
                         operator_set.set(operator);
                     },
                 );
 
-                let next_cursor = match account.operators.len() > range_start + limit {
+                let next_cursor = match operators.len() > range_start + limit {
                     true => Some(Cursor::new(self.token_data, range_start + limit)),
                     false => None,
                 };
@@ -727,6 +1726,51 @@ impl NFTState {
             None => Ok((ActorIDSet::new(), None)),
         }
     }
+
+    /// List the unexpired account-level approvals of an account along with their expiry epochs
+    /// and remaining budgets
+    pub fn list_account_approvals<BS: Blockstore>(
+        &self,
+        bs: &BS,
+        actor_id: ActorID,
+        cursor: Option<Cursor>,
+        limit: u64,
+        current_epoch: ChainEpoch,
+    ) -> Result<(Vec<OperatorApproval>, Option<Cursor>)> {
+        if let Some(cursor) = &cursor {
+            if cursor.root != self.owner_data {
+                return Err(StateError::InvalidCursor);
+            }
+        }
+
+        let owner_data_map = self.get_owner_data_hamt(bs)?;
+        match owner_data_map.get(&actor_id_key(actor_id))? {
+            Some(account) => {
+                let operators = account.active_operators(current_epoch);
+                let range_start = cursor.map(|c| c.index).unwrap_or(0);
+                let range_end = range_start.saturating_add(limit);
+
+                let approvals = operators
+                    .iter()
+                    .skip(range_start as usize)
+                    .take(limit as usize)
+                    .map(|operator| OperatorApproval {
+                        operator,
+                        expiry: account.expiry_of(&operator),
+                        budget: account.budget_of(&operator),
+                    })
+                    .collect();
+
+                let next_cursor = match operators.len() > range_end {
+                    true => Some(Cursor::new(self.owner_data, range_end)),
+                    false => None,
+                };
+
+                Ok((approvals, next_cursor))
+            }
+            None => Ok((vec![], None)),
+        }
+    }
 }
 
 pub struct StateSummary {
@@ -735,6 +1779,30 @@ pub struct StateSummary {
     pub token_data: Option<HashMap<TokenID, TokenData>>,
 }
 
+/// The outcome of checking the state for consistency
+pub struct InvariantReport {
+    /// A summary of the state that can be used to check application specific invariants
+    pub summary: StateSummary,
+    /// The invariant violations that were found
+    pub errors: Vec<StateInvariantError>,
+}
+
+impl InvariantReport {
+    /// Returns true if no invariant violations were found
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns the summary if no invariant violations were found, otherwise the violations
+    pub fn into_result(self) -> std::result::Result<StateSummary, Vec<StateInvariantError>> {
+        if self.errors.is_empty() {
+            Ok(self.summary)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum StateInvariantError {
     #[error(
@@ -753,23 +1821,47 @@ pub enum StateInvariantError {
     State(#[from] StateError),
     #[error("entry for {0:?} in owner map had no tokens and no operators")]
     ExplicitEmptyOwner(u64),
+    #[error(
+        "the token set recorded for {0:?} does not match the tokens it owns in the token array"
+    )]
+    OwnedTokensMismatch(ActorID),
+    #[error("actor {0:?} owns tokens but has no entry in the owner map")]
+    MissingOwner(ActorID),
+    #[error("token {token_id:?} is at or beyond the next token id {next_token:?}")]
+    TokenIdOutOfRange { token_id: TokenID, next_token: TokenID },
+    #[error("expiry recorded for {operator:?} who is not an approved operator")]
+    OrphanedOperatorExpiry { operator: ActorID },
+    #[error("nesting links for token {0:?} are inconsistent with its parent")]
+    NestingMismatch(TokenID),
+    #[error("budget recorded for {operator:?} who is not an approved operator")]
+    OrphanedOperatorBudget { operator: ActorID },
+    #[error("recipient recorded for {operator:?} who is not an approved operator")]
+    OrphanedOperatorRecipient { operator: ActorID },
+    #[error("staked tokens of {0:?} do not match the staked tokens in the token array")]
+    StakedTokensMismatch(ActorID),
+    #[error("tokens of {0:?} are not stored inline exactly when there are few enough of them")]
+    MisplacedOwnedTokens(ActorID),
+    #[error("the operator index entry for {0:?} does not match its approvals in the token array")]
+    OperatorIndexMismatch(ActorID),
 }
 
 impl NFTState {
     /**
      * Checks that the state is internally consistent and obeys the specified invariants
      *
-     * Checks that balances in the TokenArray and OwnerMap are consistent. Checks that the total supply
-     * is consistent with the number of tokens in the TokenArray. Checks that the OwnerHamt is clear of
+     * Checks that balances and owned token sets in the TokenArray and OwnerMap are consistent and
+     * that every owner of a token has an entry in the OwnerMap. Checks that the total supply is
+     * consistent with the number of tokens in the TokenArray and that no token is recorded beyond
+     * the next token id (burned ids are never reused, so approvals cannot outlive their token).
+     * Checks that operator expiries only refer to approved operators. Checks that nested tokens
+     * are linked to their parent in both directions and share its owner. Checks that the operator
+     * index, if enabled, matches the token-level approvals. Checks that the OwnerHamt is clear of
      * semantically empty entries. Checks that all bytes keys are valid actor ids.
      *
-     * Returns a state summary that can be used to check application specific invariants and a list
-     * of errors that were found.
+     * Returns a report containing a state summary that can be used to check application specific
+     * invariants and a list of errors that were found.
      */
-    pub fn check_invariants<BS: Blockstore>(
-        &self,
-        bs: &BS,
-    ) -> (StateSummary, Vec<StateInvariantError>) {
+    pub fn check_invariants<BS: Blockstore>(&self, bs: &BS) -> InvariantReport {
         // accumulate errors encountered in the state
         let mut errors: Vec<StateInvariantError> = vec![];
 
@@ -794,14 +1886,14 @@ impl NFTState {
         // there's no point continuing if either are missing as something serious is wrong
         // we can't do meaningful state checks without the underlying data being loadable
         if owner_data.is_none() || token_data.is_none() {
-            return (
-                StateSummary {
+            return InvariantReport {
+                summary: StateSummary {
                     owner_data: None,
                     token_data: None,
                     total_supply: self.total_supply,
                 },
                 errors,
-            );
+            };
         }
 
         let owner_data = owner_data.unwrap();
@@ -817,6 +1909,9 @@ impl NFTState {
 
         // tally the ownership of each token to check for consistency against owner_data
         let mut counted_balances = HashMap::<ActorID, u64>::new();
+        let mut counted_tokens = HashMap::<ActorID, TokenSet>::new();
+        let mut counted_staked = HashMap::<ActorID, TokenSet>::new();
+        let mut counted_approvals = BTreeMap::<ActorID, TokenSet>::new();
 
         let mut token_map = HashMap::<TokenID, TokenData>::new();
         token_data
@@ -825,6 +1920,35 @@ impl NFTState {
                 let owner = data.owner;
                 let count = counted_balances.entry(owner).or_insert(0);
                 *count += 1;
+                counted_tokens.entry(owner).or_default().set(id);
+                if data.staked_at.is_some() {
+                    counted_staked.entry(owner).or_default().set(id);
+                }
+
+                if id >= self.next_token {
+                    errors.push(StateInvariantError::TokenIdOutOfRange {
+                        token_id: id,
+                        next_token: self.next_token,
+                    });
+                }
+                for operator in data.operators.iter() {
+                    counted_approvals.entry(operator).or_default().set(id);
+                }
+
+                Self::check_operator_expiries(data, &mut errors);
+                let recipients = &data.operator_recipients;
+                if recipients.windows(2).any(|pair| pair[0].operator >= pair[1].operator) {
+                    errors.push(StateInvariantError::InvalidOperatorArray(
+                        recipients.iter().map(|r| r.operator).collect(),
+                    ));
+                }
+                for recipient in recipients {
+                    if !data.operators.get(recipient.operator) {
+                        errors.push(StateInvariantError::OrphanedOperatorRecipient {
+                            operator: recipient.operator,
+                        });
+                    }
+                }
 
                 token_map.insert(id, data.clone());
                 Ok(())
@@ -845,11 +1969,43 @@ impl NFTState {
                         });
                     }
 
+                    // assert the indexed token set matches the tokens derived from the token array
+                    let expected_tokens = counted_tokens.remove(&actor_id).unwrap_or_default();
+                    match data.owned_tokens(bs) {
+                        Ok(tokens) if tokens == expected_tokens => {}
+                        Ok(_) => errors.push(StateInvariantError::OwnedTokensMismatch(actor_id)),
+                        Err(e) => errors.push(e.into()),
+                    }
+                    if !data.stores_tokens_correctly() {
+                        errors.push(StateInvariantError::MisplacedOwnedTokens(actor_id));
+                    }
+
+                    // assert the staked set matches the staked tokens in the token array
+                    let expected_staked = counted_staked.remove(&actor_id).unwrap_or_default();
+                    if expected_staked != data.staked {
+                        errors.push(StateInvariantError::StakedTokensMismatch(actor_id));
+                    }
+
                     // if balance is zero and there are no operators, there should be no entry in the owner map
-                    if data.balance == 0 && data.operators.is_empty() {
+                    if data.is_empty() {
                         errors.push(StateInvariantError::ExplicitEmptyOwner(actor_id));
                     }
 
+                    Self::check_operator_expiries(data, &mut errors);
+                    let budgets = &data.operator_budgets;
+                    if budgets.windows(2).any(|pair| pair[0].operator >= pair[1].operator) {
+                        errors.push(StateInvariantError::InvalidOperatorArray(
+                            budgets.iter().map(|b| b.operator).collect(),
+                        ));
+                    }
+                    for budget in budgets {
+                        if !data.operators.get(budget.operator) {
+                            errors.push(StateInvariantError::OrphanedOperatorBudget {
+                                operator: budget.operator,
+                            });
+                        }
+                    }
+
                     owner_map.insert(actor_id, data.clone());
                 } else {
                     errors.push(StateInvariantError::InvalidBytesKey(owner_key.clone()));
@@ -859,14 +2015,70 @@ impl NFTState {
             })
             .unwrap();
 
-        (
-            StateSummary {
+        // the operator index must record exactly the token-level approvals
+        match self.get_operator_index(bs) {
+            Ok(Some(index)) => {
+                let operators: BTreeSet<ActorID> =
+                    index.keys().chain(counted_approvals.keys()).copied().collect();
+                for operator in operators {
+                    if index.get(&operator) != counted_approvals.get(&operator) {
+                        errors.push(StateInvariantError::OperatorIndexMismatch(operator));
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => errors.push(e.into()),
+        }
+
+        // nested tokens must be linked in both directions and share their parent's owner
+        for (id, data) in token_map.iter() {
+            if let Some(parent_id) = data.parent {
+                match token_map.get(&parent_id) {
+                    Some(parent) if parent.children.get(*id) && parent.owner == data.owner => {}
+                    _ => errors.push(StateInvariantError::NestingMismatch(*id)),
+                }
+            }
+            for child_id in data.children.iter() {
+                match token_map.get(&child_id) {
+                    Some(child) if child.parent == Some(*id) => {}
+                    _ => errors.push(StateInvariantError::NestingMismatch(child_id)),
+                }
+            }
+        }
+
+        // any owners left over hold tokens but were not found in the owner map
+        let mut missing_owners: Vec<ActorID> = counted_tokens.into_keys().collect();
+        missing_owners.sort_unstable();
+        errors.extend(missing_owners.into_iter().map(StateInvariantError::MissingOwner));
+
+        InvariantReport {
+            summary: StateSummary {
                 owner_data: Some(owner_map),
                 token_data: Some(token_map),
                 total_supply: self.total_supply,
             },
             errors,
-        )
+        }
+    }
+
+    /// Helper to check that operator expiries are ordered and refer to approved operators
+    fn check_operator_expiries<T: ExpiringOperatorSet>(
+        data: &T,
+        errors: &mut Vec<StateInvariantError>,
+    ) {
+        let expiries = data.operator_expiries();
+        if expiries.windows(2).any(|pair| pair[0].operator >= pair[1].operator) {
+            errors.push(StateInvariantError::InvalidOperatorArray(
+                expiries.iter().map(|e| e.operator).collect(),
+            ));
+        }
+        for expiry in expiries {
+            if !data.operators().get(expiry.operator) {
+                errors.push(StateInvariantError::OrphanedOperatorExpiry {
+                    operator: expiry.operator,
+                });
+            }
+        }
     }
 
     /// Helper to decode keys from bytes, recording errors if they fail
@@ -881,6 +2093,19 @@ impl NFTState {
     }
 }
 
+/// Asserts that an approval expiry, if given, has not already passed
+pub(crate) fn assert_expiry_valid(
+    expiry: Option<ChainEpoch>,
+    current_epoch: ChainEpoch,
+) -> Result<()> {
+    match expiry {
+        Some(expiry) if expiry < current_epoch => {
+            Err(StateError::ExpiryInPast { expiry, current_epoch })
+        }
+        _ => Ok(()),
+    }
+}
+
 pub fn actor_id_key(a: ActorID) -> BytesKey {
     a.encode_var_vec().into()
 }
@@ -0,0 +1,311 @@
+//! Property-based tests of `NFTState` against a model implementation
+//!
+//! Random sequences of mints, transfers, approvals and burns are applied to both an `NFTState` and
+//! a simple in-memory model of token ownership and operators. After every step, the operation must
+//! have succeeded or failed in both, the state must pass `check_invariants`, and its tokens, owners
+//! and operators must match the model. Failing sequences are shrunk to a minimal reproduction.
+use std::collections::{BTreeMap, BTreeSet};
+
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_shared::ActorID;
+use proptest::prelude::*;
+
+use crate::state::{NFTState, StateError};
+use crate::types::TokenID;
+use crate::util::ExpiringOperatorSet;
+
+/// Accounts are drawn from a small range so that operations often touch the same accounts
+const ACCOUNTS: ActorID = 4;
+/// Token IDs are drawn from a small range so that operations often touch existing tokens
+const TOKEN_IDS: TokenID = 12;
+/// The largest batch of tokens minted at once
+const MAX_MINT: usize = 3;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Mint { to: ActorID, count: usize },
+    Transfer { owner: ActorID, to: ActorID, token_ids: Vec<TokenID> },
+    TransferFrom { operator: ActorID, owner: ActorID, to: ActorID, token_ids: Vec<TokenID> },
+    Burn { owner: ActorID, token_ids: Vec<TokenID> },
+    BurnFrom { operator: ActorID, owner: ActorID, token_ids: Vec<TokenID> },
+    Approve { caller: ActorID, operator: ActorID, token_ids: Vec<TokenID> },
+    Revoke { caller: ActorID, operator: ActorID, token_ids: Vec<TokenID> },
+    ApproveForOwner { owner: ActorID, operator: ActorID },
+    RevokeForAll { owner: ActorID, operator: ActorID },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let account = || 0..ACCOUNTS;
+    let token_ids = || prop::collection::vec(0..TOKEN_IDS, 1..=3);
+    prop_oneof![
+        (account(), 0..=MAX_MINT).prop_map(|(to, count)| Op::Mint { to, count }),
+        (account(), account(), token_ids()).prop_map(|(owner, to, token_ids)| Op::Transfer {
+            owner,
+            to,
+            token_ids
+        }),
+        (account(), account(), account(), token_ids()).prop_map(
+            |(operator, owner, to, token_ids)| Op::TransferFrom { operator, owner, to, token_ids }
+        ),
+        (account(), token_ids()).prop_map(|(owner, token_ids)| Op::Burn { owner, token_ids }),
+        (account(), account(), token_ids()).prop_map(|(operator, owner, token_ids)| {
+            Op::BurnFrom { operator, owner, token_ids }
+        }),
+        (account(), account(), token_ids()).prop_map(|(caller, operator, token_ids)| {
+            Op::Approve { caller, operator, token_ids }
+        }),
+        (account(), account(), token_ids()).prop_map(|(caller, operator, token_ids)| {
+            Op::Revoke { caller, operator, token_ids }
+        }),
+        (account(), account())
+            .prop_map(|(owner, operator)| Op::ApproveForOwner { owner, operator }),
+        (account(), account()).prop_map(|(owner, operator)| Op::RevokeForAll { owner, operator }),
+    ]
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ModelToken {
+    owner: ActorID,
+    operators: BTreeSet<ActorID>,
+}
+
+/// Reference implementation of the NFT state
+#[derive(Clone, Default)]
+struct Model {
+    next_token: TokenID,
+    tokens: BTreeMap<TokenID, ModelToken>,
+    /// Account-level operators of each owner, omitting owners without operators
+    account_operators: BTreeMap<ActorID, BTreeSet<ActorID>>,
+}
+
+impl Model {
+    fn is_account_operator(&self, owner: ActorID, operator: ActorID) -> bool {
+        self.account_operators.get(&owner).map_or(false, |operators| operators.contains(&operator))
+    }
+
+    /// Applies an operation, returning whether it succeeded
+    ///
+    /// Operations that fail leave the model unchanged.
+    fn apply(&mut self, op: &Op) -> bool {
+        let mut model = self.clone();
+        let succeeded = model.try_apply(op).is_some();
+        if succeeded {
+            *self = model;
+        }
+        succeeded
+    }
+
+    fn try_apply(&mut self, op: &Op) -> Option<()> {
+        match *op {
+            Op::Mint { to, count } => {
+                for _ in 0..count {
+                    self.tokens
+                        .insert(self.next_token, ModelToken { owner: to, operators: [].into() });
+                    self.next_token += 1;
+                }
+            }
+            Op::Transfer { owner, to, ref token_ids } => {
+                self.transfer(None, owner, to, token_ids)?
+            }
+            Op::TransferFrom { operator, owner, to, ref token_ids } => {
+                self.transfer(Some(operator), owner, to, token_ids)?
+            }
+            Op::Burn { owner, ref token_ids } => self.burn(None, owner, token_ids)?,
+            Op::BurnFrom { operator, owner, ref token_ids } => {
+                self.burn(Some(operator), owner, token_ids)?
+            }
+            Op::Approve { caller, operator, ref token_ids } => {
+                for token_id in token_ids {
+                    let token = self.tokens.get_mut(token_id).filter(|t| t.owner == caller)?;
+                    token.operators.insert(operator);
+                }
+            }
+            Op::Revoke { caller, operator, ref token_ids } => {
+                for token_id in token_ids {
+                    let token = self.tokens.get_mut(token_id).filter(|t| t.owner == caller)?;
+                    token.operators.remove(&operator);
+                }
+            }
+            Op::ApproveForOwner { owner, operator } => {
+                self.account_operators.entry(owner).or_default().insert(operator);
+            }
+            Op::RevokeForAll { owner, operator } => {
+                if let Some(operators) = self.account_operators.get_mut(&owner) {
+                    operators.remove(&operator);
+                    if operators.is_empty() {
+                        self.account_operators.remove(&owner);
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// Transfers tokens as their owner, or as an operator if one is given
+    fn transfer(
+        &mut self,
+        operator: Option<ActorID>,
+        owner: ActorID,
+        to: ActorID,
+        token_ids: &[TokenID],
+    ) -> Option<()> {
+        let account_operator = operator.map_or(true, |op| self.is_account_operator(owner, op));
+        for token_id in token_ids {
+            let token = self.tokens.get_mut(token_id).filter(|t| t.owner == owner)?;
+            if !account_operator && !token.operators.contains(&operator?) {
+                return None;
+            }
+            *token = ModelToken { owner: to, operators: [].into() };
+        }
+        Some(())
+    }
+
+    /// Burns tokens as their owner, or as an operator if one is given
+    fn burn(
+        &mut self,
+        operator: Option<ActorID>,
+        owner: ActorID,
+        token_ids: &[TokenID],
+    ) -> Option<()> {
+        let account_operator = operator.map_or(true, |op| self.is_account_operator(owner, op));
+        for token_id in token_ids {
+            let token = self.tokens.remove(token_id).filter(|t| t.owner == owner)?;
+            if !account_operator && !token.operators.contains(&operator?) {
+                return None;
+            }
+        }
+        Some(())
+    }
+}
+
+/// Applies an operation the way `NFT` does, returning whether it succeeded
+///
+/// Operations that fail leave the state unchanged, as `NFT` runs each one in a transaction.
+fn apply(state: &mut NFTState, bs: &MemoryBlockstore, op: &Op) -> bool {
+    let mut new_state = state.clone();
+    let res: Result<(), StateError> = match *op {
+        Op::Mint { to, count } => {
+            new_state.mint_tokens(bs, to, vec![String::new(); count], 0).map(|_| ())
+        }
+        Op::Transfer { owner, to, ref token_ids } => new_state
+            .transfer(bs, token_ids, owner, to, &|token_data, token_id| {
+                NFTState::assert_owns_token(token_data, token_id, owner)
+            })
+            .map(|_| ()),
+        Op::TransferFrom { operator, owner, to, ref token_ids } => {
+            new_state.get_owner_data_hamt(bs).and_then(|owner_map| {
+                let account_operator =
+                    NFTState::is_account_operator(&owner_map, owner, operator, 0)?;
+                new_state
+                    .transfer(bs, token_ids, owner, to, &|token_data, token_id| {
+                        NFTState::assert_owns_token(token_data, token_id, owner)?;
+                        if account_operator {
+                            return Ok(());
+                        }
+                        NFTState::assert_token_level_approval(token_data, token_id, operator, 0)
+                    })
+                    .map(|_| ())
+            })
+        }
+        Op::Burn { owner, ref token_ids } => new_state
+            .burn_tokens(bs, owner, token_ids, |token_data, token_id| {
+                NFTState::assert_owns_token(token_data, token_id, owner)
+            })
+            .map(|_| ()),
+        Op::BurnFrom { operator, owner, ref token_ids } => {
+            new_state.is_owner_operator(bs, owner, operator, 0).and_then(|account_operator| {
+                new_state
+                    .burn_tokens(bs, owner, token_ids, |token_data, token_id| {
+                        NFTState::assert_owns_token(token_data, token_id, owner)?;
+                        if account_operator || token_data.is_active_operator(&operator, 0) {
+                            Ok(())
+                        } else {
+                            Err(StateError::NotOperator { operator, owner, token_id })
+                        }
+                    })
+                    .map(|_| ())
+            })
+        }
+        Op::Approve { caller, operator, ref token_ids } => new_state.approve_for_tokens(
+            bs,
+            operator,
+            token_ids,
+            None,
+            0,
+            |token_data, token_id| NFTState::assert_owns_token(token_data, token_id, caller),
+        ),
+        Op::Revoke { caller, operator, ref token_ids } => {
+            new_state.revoke_for_tokens(bs, operator, token_ids, |token_data, token_id| {
+                NFTState::assert_owns_token(token_data, token_id, caller)
+            })
+        }
+        Op::ApproveForOwner { owner, operator } => {
+            new_state.approve_for_owner(bs, owner, operator, None, 0)
+        }
+        Op::RevokeForAll { owner, operator } => new_state.revoke_for_all(bs, owner, operator),
+    };
+    if res.is_ok() {
+        *state = new_state;
+    }
+    res.is_ok()
+}
+
+/// Checks the state's invariants and compares it with the model
+fn check_state(state: &NFTState, bs: &MemoryBlockstore, model: &Model) {
+    let report = state.check_invariants(bs);
+    assert!(report.is_ok(), "invariants violated: {:?}", report.errors);
+    let summary = report.summary;
+    assert_eq!(summary.total_supply, model.tokens.len() as u64);
+    assert_eq!(state.next_token, model.next_token);
+
+    let tokens: BTreeMap<_, _> = summary
+        .token_data
+        .unwrap()
+        .into_iter()
+        .map(|(id, data)| {
+            (id, ModelToken { owner: data.owner, operators: data.operators.iter().collect() })
+        })
+        .collect();
+    assert_eq!(tokens, model.tokens);
+
+    // owners are stored while they hold tokens or have approved operators
+    let mut expected_owners: BTreeMap<ActorID, (BTreeSet<TokenID>, BTreeSet<ActorID>)> = model
+        .account_operators
+        .iter()
+        .map(|(&owner, operators)| (owner, (BTreeSet::new(), operators.clone())))
+        .collect();
+    for (&token_id, token) in &model.tokens {
+        expected_owners.entry(token.owner).or_default().0.insert(token_id);
+    }
+    let owners: BTreeMap<_, _> = summary
+        .owner_data
+        .unwrap()
+        .into_iter()
+        .map(|(owner, data)| {
+            (
+                owner,
+                (data.owned_tokens(bs).unwrap().iter().collect(), data.operators.iter().collect()),
+            )
+        })
+        .collect();
+    assert_eq!(owners, expected_owners);
+
+    // the state survives a round trip through the blockstore
+    let cid = state.save(bs).unwrap();
+    assert_eq!(&NFTState::load(bs, &cid).unwrap(), state);
+}
+
+proptest! {
+    #[test]
+    fn it_matches_the_model(ops in prop::collection::vec(op(), 1..64)) {
+        let bs = MemoryBlockstore::default();
+        let mut state = NFTState::new(&bs).unwrap();
+        let mut model = Model::default();
+
+        for op in &ops {
+            let succeeded = apply(&mut state, &bs, op);
+            prop_assert_eq!(succeeded, model.apply(op), "outcome of {:?} differs", op);
+            check_state(&state, &bs, &model);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Golden tests of the serialized layout of `NFTState`
+//!
+//! Deployed NFT actors store their state in this layout, so a change to the CBOR bytes or root CID
+//! of these fixtures means existing state can no longer be read. The populated fixture covers the
+//! layout of token and owner data as well, as their roots are embedded in the state, while the
+//! data only some tokens, owners and collections hold is pinned separately. If the change is
+//! intended, it needs a state migration before the golden values here are updated.
+use cid::Cid;
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_shared::ActorID;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::state::{actor_id_key, NFTState, OWNER_TOKENS_INLINE_LIMIT};
+use crate::types::CollectionMetadata;
+
+const ALICE: ActorID = 1;
+const BOB: ActorID = 2;
+const CAROL: ActorID = 3;
+
+const EMPTY_CBOR: &str = concat!(
+    "8f01d82a5827000171a0e40220054de1cd03c0741eec69f34aabfec51f64b304c307a5f5beb965d9",
+    "4fba91d9e0d82a5827000171a0e4022018fe6acc61a3a36b0c373c4a3a8ea64b812bf2ca9b528050",
+    "909c78d408558a0c000086606060f66060f4f6f6f6f6f68080f6",
+);
+const EMPTY_CID: &str = "bafy2bzacebzafqzqoocm7xmcftf5tuodf4vqz5ayd76kdefxjyks5ispf2ixw";
+
+const POPULATED_CBOR: &str = concat!(
+    "8f01d82a5827000171a0e40220822d8e6d533f23ee8e2b8c7debfcbf052e21676a33c679906eea29",
+    "65a36819ecd82a5827000171a0e4022082f0231c5fa08c257c3530bb45865c81f1a93e71cc50eb2a",
+    "9ff3172151041c8803038666476f6c64656e63474c4478184120676f6c64656e207465737420636f",
+    "6c6c656374696f6ef67368747470733a2f2f6578616d706c652e636f6d67697066733a2f2ff4f6f6",
+    "f6f6f68080f6",
+);
+const POPULATED_CID: &str = "bafy2bzacedd5casrrabjw3vnwzycs273nkxqivbiictotdlux4wcr7774744k";
+
+const RESTRICTED_TOKEN_CBOR: &str = "8a0142700268697066733a2f2f3080f640f640f681820302";
+
+const SPILLED_OWNER_CBOR: &str = concat!(
+    "8718404040808040d82a5827000171a0e402201c7eb0821678033fe64649a91f5b76b93d06c6152f",
+    "fb355021b428df71b1edfb",
+);
+
+const INDEXED_STATE_CBOR: &str = concat!(
+    "8f01d82a5827000171a0e40220afcc54f1ea0a64cbf824b5faea7d9542e703e7a93ee7aa2aa7825e",
+    "f6526f8218d82a5827000171a0e40220ac98061884c9d3dcfe43e8744eecc326b85befbf79ed9920",
+    "59008e761cb6a28c010186606060f66060f4f6f6f6f6f68080d82a5827000171a0e4022073a221a3",
+    "4c9546ace9bdbaaae6df4a977feeee293eb3ca3ba052d043eadf794d",
+);
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Checks the value serializes to bytes it deserializes from, returning them
+fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> Vec<u8> {
+    let bytes = fvm_ipld_encoding::to_vec(value).unwrap();
+    let decoded: T = fvm_ipld_encoding::from_slice(&bytes).unwrap();
+    assert_eq!(fvm_ipld_encoding::to_vec(&decoded).unwrap(), bytes);
+    bytes
+}
+
+/// Checks the state serializes to the golden bytes and root CID, and deserializes from them
+fn assert_golden(state: &NFTState, bs: &MemoryBlockstore, cbor: &str, cid: &str) {
+    let bytes = fvm_ipld_encoding::to_vec(state).unwrap();
+    assert_eq!(to_hex(&bytes), cbor, "serialized state layout changed");
+    assert_eq!(state.save(bs).unwrap(), Cid::try_from(cid).unwrap(), "state root changed");
+    assert_eq!(&fvm_ipld_encoding::from_slice::<NFTState>(&bytes).unwrap(), state);
+}
+
+#[test]
+fn empty_state_matches_golden() {
+    let bs = MemoryBlockstore::default();
+    let state = NFTState::new(&bs).unwrap();
+    assert_golden(&state, &bs, EMPTY_CBOR, EMPTY_CID);
+}
+
+#[test]
+fn populated_state_matches_golden() {
+    let bs = MemoryBlockstore::default();
+    let metadata = CollectionMetadata {
+        name: "Golden".into(),
+        symbol: "GLD".into(),
+        description: "A golden test collection".into(),
+        image_cid: None,
+        external_url: "https://example.com".into(),
+        base_uri: "ipfs://".into(),
+    };
+    let mut state = NFTState::new_with_metadata(&bs, metadata).unwrap();
+    state.mint_tokens(&bs, ALICE, vec!["ipfs://0".into(), "ipfs://1".into()], 0).unwrap();
+    state.mint_tokens(&bs, BOB, vec!["ipfs://2".into()], 0).unwrap();
+    state.approve_for_tokens(&bs, CAROL, &[0], None, 0, |_, _| Ok(())).unwrap();
+    state.approve_for_owner(&bs, BOB, CAROL, None, 0).unwrap();
+
+    assert_golden(&state, &bs, POPULATED_CBOR, POPULATED_CID);
+}
+
+#[test]
+fn optional_data_matches_golden() {
+    let bs = MemoryBlockstore::default();
+
+    // a token whose operator may only transfer it to one recipient
+    let mut restricted = NFTState::new(&bs).unwrap();
+    restricted.mint_tokens(&bs, ALICE, vec!["ipfs://0".into()], 0).unwrap();
+    restricted.approve_for_tokens_to(&bs, CAROL, &[0], Some(BOB), None, 0, |_, _| Ok(())).unwrap();
+    let token = restricted.get_token_data_amt(&bs).unwrap().get(0).unwrap().unwrap().clone();
+
+    // an owner holding enough tokens for them to be kept in a block of their own
+    let mut large = NFTState::new(&bs).unwrap();
+    let metadata = vec![String::new(); OWNER_TOKENS_INLINE_LIMIT as usize];
+    large.mint_tokens(&bs, ALICE, metadata, 0).unwrap();
+    let owners = large.get_owner_data_hamt(&bs).unwrap();
+    let owner = owners.get(&actor_id_key(ALICE)).unwrap().unwrap().clone();
+
+    // a collection indexing the tokens each operator is approved for
+    let mut indexed = NFTState::new(&bs).unwrap();
+    indexed.mint_tokens(&bs, ALICE, vec!["ipfs://0".into()], 0).unwrap();
+    indexed.approve_for_tokens(&bs, CAROL, &[0], None, 0, |_, _| Ok(())).unwrap();
+    indexed.enable_operator_index(&bs).unwrap();
+
+    let cases = [
+        ("restricted token data", round_trip(&token), RESTRICTED_TOKEN_CBOR),
+        ("spilled owner data", round_trip(&owner), SPILLED_OWNER_CBOR),
+        ("indexed state", round_trip(&indexed), INDEXED_STATE_CBOR),
+    ];
+    for (name, bytes, golden) in cases {
+        assert_eq!(to_hex(&bytes), golden, "serialized {name} layout changed");
+    }
+}
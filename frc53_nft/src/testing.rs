@@ -0,0 +1,163 @@
+//! Fixtures for building NFT collections in tests and benchmarks
+//!
+//! Minting through `NFT::mint` runs the receiver hook and flushes the state for every batch, which
+//! makes large collections slow to set up. `NFTStateFixture` instead writes the token array and
+//! owner map directly to a blockstore in a single pass. The resulting state is identical to one
+//! produced by minting the same tokens in order.
+use std::collections::BTreeMap;
+
+use fvm_ipld_bitfield::BitField;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::ActorID;
+
+use crate::state::{actor_id_key, NFTState, OwnerData, StateError, TokenData};
+use crate::types::{CollectionMetadata, TokenID, TokenSet};
+
+type TokenMetadataFn = Box<dyn Fn(TokenID) -> String>;
+
+/// Builds an `NFTState` holding a deterministic set of tokens
+///
+/// Tokens are assigned consecutive ids from zero in the order they are added to the fixture.
+#[derive(Default)]
+pub struct NFTStateFixture {
+    collection_metadata: CollectionMetadata,
+    token_metadata: Option<TokenMetadataFn>,
+    owners: Vec<ActorID>,
+}
+
+impl NFTStateFixture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the metadata of the collection as a whole
+    pub fn with_collection_metadata(mut self, collection_metadata: CollectionMetadata) -> Self {
+        self.collection_metadata = collection_metadata;
+        self
+    }
+
+    /// Derives the metadata of each token from its id, tokens have empty metadata otherwise
+    pub fn with_token_metadata<F>(mut self, token_metadata: F) -> Self
+    where
+        F: Fn(TokenID) -> String + 'static,
+    {
+        self.token_metadata = Some(Box::new(token_metadata));
+        self
+    }
+
+    /// Adds `count` tokens owned by `owner`
+    pub fn mint_to(mut self, owner: ActorID, count: u64) -> Self {
+        self.owners.extend(std::iter::repeat(owner).take(count as usize));
+        self
+    }
+
+    /// Adds `count` tokens, each owned by the actor returned from `owner_of` for its token id
+    pub fn distribute<F>(mut self, count: u64, mut owner_of: F) -> Self
+    where
+        F: FnMut(TokenID) -> ActorID,
+    {
+        let first = self.owners.len() as TokenID;
+        self.owners.extend((first..first + count).map(&mut owner_of));
+        self
+    }
+
+    /// The number of tokens the fixture will hold
+    pub fn len(&self) -> u64 {
+        self.owners.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+
+    /// Writes the collection to the blockstore, returning the state without committing its root
+    pub fn build<BS: Blockstore>(&self, bs: &BS) -> Result<NFTState, StateError> {
+        let mut state = NFTState::new_with_metadata(bs, self.collection_metadata.clone())?;
+        let mut token_array = state.get_token_data_amt(bs)?;
+        let mut owner_map = state.get_owner_data_hamt(bs)?;
+
+        let mut owned_tokens = BTreeMap::<ActorID, Vec<TokenID>>::new();
+        for (token_id, owner) in self.owners.iter().enumerate() {
+            let token_id = token_id as TokenID;
+            let metadata = self.token_metadata.as_ref().map(|f| f(token_id)).unwrap_or_default();
+            token_array.set(
+                token_id,
+                TokenData {
+                    owner: *owner,
+                    operators: BitField::default(),
+                    metadata,
+                    operator_expiries: vec![],
+                    user: None,
+                    extra: RawBytes::default(),
+                    parent: None,
+                    children: TokenSet::default(),
+                    staked_at: None,
+                    operator_recipients: vec![],
+                },
+            )?;
+            owned_tokens.entry(*owner).or_default().push(token_id);
+        }
+        for (owner, token_ids) in owned_tokens {
+            let mut data = OwnerData::new();
+            data.add_tokens(bs, &token_ids)?;
+            owner_map.set(actor_id_key(owner), data)?;
+        }
+
+        state.token_data = token_array.flush()?;
+        state.owner_data = owner_map.flush()?;
+        state.next_token = self.len();
+        state.total_supply = self.len();
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::ActorID;
+
+    use super::NFTStateFixture;
+    use crate::state::NFTState;
+
+    const ALICE_ID: ActorID = 1;
+    const BOB_ID: ActorID = 11;
+    const CHARLIE_ID: ActorID = 111;
+
+    #[test]
+    fn it_matches_the_mint_path() {
+        let bs = MemoryBlockstore::new();
+        let mut minted = NFTState::new(&bs).unwrap();
+        minted.mint_tokens(&bs, ALICE_ID, vec!["0".into(), "1".into()], 0).unwrap();
+        minted.mint_tokens(&bs, BOB_ID, vec!["2".into()], 0).unwrap();
+
+        let built = NFTStateFixture::new()
+            .with_token_metadata(|id| id.to_string())
+            .mint_to(ALICE_ID, 2)
+            .mint_to(BOB_ID, 1)
+            .build(&bs)
+            .unwrap();
+
+        assert_eq!(built, minted);
+    }
+
+    #[test]
+    fn it_builds_arbitrary_distributions() {
+        let bs = MemoryBlockstore::new();
+        let owners = [ALICE_ID, BOB_ID, CHARLIE_ID];
+        let state = NFTStateFixture::new()
+            .distribute(10_000, |id| owners[(id * id % 3) as usize])
+            .mint_to(CHARLIE_ID, 5)
+            .build(&bs)
+            .unwrap();
+
+        let summary = state.check_invariants(&bs).into_result().unwrap();
+        assert_eq!(summary.total_supply, 10_005);
+        assert_eq!(state.next_token, 10_005);
+        // squares are never 2 mod 3
+        assert_eq!(state.get_balance(&bs, CHARLIE_ID).unwrap(), 5);
+        assert_eq!(state.get_owner(&bs, 3).unwrap(), ALICE_ID);
+        assert_eq!(state.get_owner(&bs, 4).unwrap(), BOB_ID);
+        assert_eq!(state.get_owner(&bs, 10_004).unwrap(), CHARLIE_ID);
+    }
+}
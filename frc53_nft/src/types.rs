@@ -2,11 +2,15 @@
 use cid::Cid;
 use fvm_actor_utils::receiver::RecipientData;
 use fvm_ipld_bitfield::BitField;
+use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
 use fvm_shared::ActorID;
 
+use crate::state::{NFTState, StateError};
+
 pub type TokenID = u64;
 
 /// Multiple token IDs are represented as a BitField encoded with RLE+ the index of each set bit
@@ -17,52 +21,114 @@ pub type TokenSet = BitField;
 /// corresponds to a ActorID
 pub type ActorIDSet = BitField;
 
+/// Descriptive information about the collection as a whole, as opposed to individual NFTs
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug, Default)]
+pub struct CollectionMetadata {
+    /// A descriptive name for the collection
+    pub name: String,
+    /// An abbreviated name for NFTs in the collection
+    pub symbol: String,
+    /// A longer, human-readable description of the collection
+    pub description: String,
+    /// (Optional) link to an image representing the collection
+    pub image_cid: Option<Cid>,
+    /// (Optional) link to an external site for the collection
+    pub external_url: String,
+    /// (Optional) prefix from which the metadata of tokens without their own is derived, as
+    /// `base_uri` followed by the TokenID
+    pub base_uri: String,
+}
+
 /// A trait to be implemented by FRC-0053 compliant actors
 pub trait FRC53NFT {
+    type NFTError;
+
     /// A descriptive name for the collection of NFTs in this actor
     fn name(&self) -> String;
 
     /// An abbreviated name for NFTs in this contract
     fn symbol(&self) -> String;
 
-    /// Gets a link to associated metadata for a given NFT
-    fn metadata(&self, params: TokenID) -> Cid;
-
-    /// Gets the total number of NFTs in this actor
-    fn total_supply(&self) -> u64;
+    /// Gets the descriptive metadata for the collection as a whole
+    fn collection_metadata(&self) -> CollectionMetadata;
 
-    /// Burns a given NFT, removing it from the total supply and preventing new NFTs from being
-    /// minted with the same ID
-    fn burn(&self, params: TokenID);
+    /// Gets the metadata for a given NFT
+    fn metadata(&mut self, params: TokenID) -> Result<String, Self::NFTError>;
 
-    /// Gets a list of all the tokens in the collection
-    /// FIXME: make this paginated
-    fn list_tokens(&self) -> Vec<TokenID>;
+    /// Gets the total number of NFTs in this actor
+    fn total_supply(&mut self) -> u64;
+
+    /// Burns the given NFTs owned by the caller, removing them from the total supply and
+    /// preventing new NFTs from being minted with the same IDs
+    fn burn(&mut self, params: Vec<TokenID>) -> Result<BurnReturn, Self::NFTError>;
+
+    /// Burns the given NFTs owned by the `from` address, where the caller is an approved operator
+    fn burn_from(&mut self, params: BurnFromParams) -> Result<BurnReturn, Self::NFTError>;
+
+    /// Gets a page of the tokens in the collection
+    fn list_tokens(&mut self, params: ListTokensParams)
+        -> Result<ListTokensReturn, Self::NFTError>;
+
+    /// Gets a page of the tokens owned by a particular address
+    fn list_owned_tokens(
+        &mut self,
+        params: ListOwnedTokensParams,
+    ) -> Result<ListOwnedTokensReturn, Self::NFTError>;
+
+    /// Gets a page of the operators approved for a particular NFT
+    fn list_token_operators(
+        &mut self,
+        params: ListTokenOperatorsParams,
+    ) -> Result<ListTokenOperatorsReturn, Self::NFTError>;
+
+    /// Gets a page of the NFTs a particular address is approved for at token-level
+    fn list_operator_tokens(
+        &mut self,
+        params: ListOperatorTokensParams,
+    ) -> Result<ListOperatorTokensReturn, Self::NFTError>;
+
+    /// Gets a page of the account-level operators approved by a particular address
+    fn list_account_operators(
+        &mut self,
+        params: ListAccountOperatorsParams,
+    ) -> Result<ListAccountOperatorsReturn, Self::NFTError>;
 
     /// Gets the number of tokens held by a particular address (if it exists)
-    fn balance_of(&self, params: Address) -> u64;
+    fn balance_of(&mut self, params: Address) -> Result<u64, Self::NFTError>;
 
     /// Returns the owner of the NFT specified by `token_id`
-    fn owner_of(&self, params: TokenID) -> ActorID;
+    fn owner_of(&mut self, params: TokenID) -> Result<ActorID, Self::NFTError>;
 
     /// Transfers specific NFTs from the caller to another account
-    fn transfer(&self, params: TransferParams);
+    fn transfer(&mut self, params: TransferParams) -> Result<TransferReturn, Self::NFTError>;
 
     /// Transfers specific NFTs between the `from` and `to` addresses
-    fn transfer_from(&self, params: TransferFromParams);
+    fn transfer_from(
+        &mut self,
+        params: TransferFromParams,
+    ) -> Result<TransferReturn, Self::NFTError>;
+
+    /// Approves an operator to transfer or burn a set of NFTs owned by the caller
+    fn approve(&mut self, params: ApproveParams) -> Result<(), Self::NFTError>;
 
-    /// Change or reaffirm the approved address for a set of NFTs, setting to zero means there is no approved address
-    fn approve(&self, params: ApproveParams);
+    /// Revokes an operator's approval for a set of NFTs owned by the caller
+    fn revoke(&mut self, params: RevokeParams) -> Result<(), Self::NFTError>;
 
     /// Set approval for all, allowing an operator to control all of the caller's tokens (including future tokens)
     /// until approval is revoked
-    fn set_approval_for_all(&self, params: ApproveForAllParams);
+    fn approve_for_all(&mut self, params: ApproveForAllParams) -> Result<(), Self::NFTError>;
+
+    /// Revokes an operator's approval to control all of the caller's tokens
+    fn revoke_for_all(&mut self, params: RevokeForAllParams) -> Result<(), Self::NFTError>;
 
-    /// Get the approved address for a single NFT
-    fn get_approved(&self, params: TokenID) -> ActorID;
+    /// Get the operators approved at token-level for a single NFT
+    fn get_approved(&mut self, params: TokenID) -> Result<ActorIDSet, Self::NFTError>;
 
     /// Query if the address is the approved operator for another address
-    fn is_approved_for_all(&self, params: IsApprovedForAllParams) -> bool;
+    fn is_approved_for_all(
+        &mut self,
+        params: IsApprovedForAllParams,
+    ) -> Result<bool, Self::NFTError>;
 }
 
 /// Return value after a successful mint
@@ -80,6 +146,22 @@ pub struct MintReturn {
     pub recipient_data: RawBytes,
 }
 
+/// Converts the intermediate result of an operation into its return value once the receiver hook
+/// has been called
+///
+/// Implemented for the intermediates of the library's own operations. Actors that define their own
+/// intermediates can implement it to have `NFT::complete` build their return values.
+pub trait ReturnBuilder {
+    type Return;
+
+    /// Builds the return value from an up-to-date view of the state
+    fn build_return<BS: Blockstore>(
+        self,
+        state: &NFTState,
+        bs: &BS,
+    ) -> Result<Self::Return, StateError>;
+}
+
 /// Intermediate data used by mint_return to construct the return data
 #[derive(Clone, Debug)]
 pub struct MintIntermediate {
@@ -97,6 +179,54 @@ impl RecipientData for MintIntermediate {
     }
 }
 
+impl ReturnBuilder for MintIntermediate {
+    type Return = MintReturn;
+
+    fn build_return<BS: Blockstore>(
+        self,
+        state: &NFTState,
+        bs: &BS,
+    ) -> Result<MintReturn, StateError> {
+        state.mint_return(bs, self)
+    }
+}
+
+/// Details of a single burnt token
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Clone, Debug)]
+pub struct BurnedToken {
+    pub token_id: TokenID,
+    /// The owner of the token at the time it was burnt
+    pub previous_owner: ActorID,
+    /// The token-level operators whose approvals were removed along with the token
+    pub cleared_operators: ActorIDSet,
+}
+
+/// Return value after a successful burn
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Clone, Debug)]
+pub struct BurnReturn {
+    /// The new balance of the owner
+    pub balance: u64,
+    /// The new total supply
+    pub supply: u64,
+    /// Per-token results, in the order the tokens were burnt
+    pub burned: Vec<BurnedToken>,
+}
+
+/// The result of burning tokens and notifying a redeemer
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Clone, Debug)]
+pub struct RedeemReturn {
+    /// The details of the burn
+    pub burn: BurnReturn,
+    /// (Optional) data returned from the redeemer's hook
+    pub recipient_data: RawBytes,
+}
+
+impl RecipientData for RedeemReturn {
+    fn set_recipient_data(&mut self, data: RawBytes) {
+        self.recipient_data = data;
+    }
+}
+
 /// Intermediate data used by transfer_return to construct the return data
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
 pub struct TransferIntermediate {
@@ -113,11 +243,25 @@ impl RecipientData for TransferIntermediate {
     }
 }
 
+impl ReturnBuilder for TransferIntermediate {
+    type Return = TransferReturn;
+
+    fn build_return<BS: Blockstore>(
+        self,
+        state: &NFTState,
+        bs: &BS,
+    ) -> Result<TransferReturn, StateError> {
+        state.transfer_return(bs, self)
+    }
+}
+
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
 pub struct TransferParams {
     pub to: Address,
     pub token_ids: Vec<TokenID>,
     pub operator_data: RawBytes,
+    /// Data for each token, empty or in the same order as `token_ids`
+    pub per_token_data: Vec<RawBytes>,
 }
 
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
@@ -133,6 +277,8 @@ pub struct TransferFromParams {
     pub to: Address,
     pub token_ids: Vec<TokenID>,
     pub operator_data: RawBytes,
+    /// Data for each token, empty or in the same order as `token_ids`
+    pub per_token_data: Vec<RawBytes>,
 }
 
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
@@ -242,3 +388,20 @@ pub struct ListAccountOperatorsReturn {
     /// Opaque serialisation of frc53_nft::state::Cursor, with empty cursor meaning no more items
     pub next_cursor: Option<RawBytes>,
 }
+
+/// An outstanding operator approval and the limits it is subject to
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct OperatorApproval {
+    pub operator: ActorID,
+    /// The last epoch at which the approval is valid, None if it lasts until revoked
+    pub expiry: Option<ChainEpoch>,
+    /// The number of tokens the operator may still act on, None if unlimited
+    pub budget: Option<u64>,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct ListAccountApprovalsReturn {
+    pub approvals: Vec<OperatorApproval>,
+    /// Opaque serialisation of frc53_nft::state::Cursor, with empty cursor meaning no more items
+    pub next_cursor: Option<RawBytes>,
+}
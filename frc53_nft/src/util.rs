@@ -1,4 +1,6 @@
 use fvm_ipld_bitfield::BitField;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::clock::ChainEpoch;
 use fvm_shared::ActorID;
 
 pub trait OperatorSet {
@@ -54,6 +56,104 @@ impl OperatorSet for Vec<ActorID> {
     }
 }
 
+/// Records the last epoch at which an operator's approval remains valid
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct OperatorExpiry {
+    pub operator: ActorID,
+    pub expiry: ChainEpoch,
+}
+
+/// Records the number of tokens an account-level operator may still transfer or burn
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct OperatorBudget {
+    pub operator: ActorID,
+    pub remaining: u64,
+}
+
+/// Records the only account a token-level operator may transfer the token to
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct OperatorRecipient {
+    pub operator: ActorID,
+    pub recipient: ActorID,
+}
+
+/// A set of approved operators where each approval may lapse after an expiry epoch
+///
+/// Operators without a recorded expiry are approved indefinitely. Approvals past their expiry are
+/// treated as revoked and are only removed from state when `prune_expired` is next called.
+pub trait ExpiringOperatorSet {
+    /// The set of all recorded operators, including those whose approval has expired
+    fn operators(&self) -> &BitField;
+
+    fn operators_mut(&mut self) -> &mut BitField;
+
+    /// Expiry epochs of the time-limited operators, sorted by operator
+    fn operator_expiries(&self) -> &Vec<OperatorExpiry>;
+
+    fn operator_expiries_mut(&mut self) -> &mut Vec<OperatorExpiry>;
+
+    /// Approves the operator until the expiry epoch (inclusive), or indefinitely if no expiry is
+    /// given
+    ///
+    /// Re-approving an existing operator replaces its previous expiry
+    fn approve_operator(&mut self, operator: ActorID, expiry: Option<ChainEpoch>) {
+        self.operators_mut().add_operator(operator);
+        let expiries = self.operator_expiries_mut();
+        match (expiries.binary_search_by_key(&operator, |e| e.operator), expiry) {
+            (Ok(pos), Some(expiry)) => expiries[pos].expiry = expiry,
+            (Ok(pos), None) => {
+                expiries.remove(pos);
+            }
+            (Err(pos), Some(expiry)) => expiries.insert(pos, OperatorExpiry { operator, expiry }),
+            (Err(_), None) => {}
+        }
+    }
+
+    /// Removes the operator and any recorded expiry
+    fn revoke_operator(&mut self, operator: &ActorID) {
+        self.operators_mut().remove_operator(operator);
+        let expiries = self.operator_expiries_mut();
+        if let Ok(pos) = expiries.binary_search_by_key(operator, |e| e.operator) {
+            expiries.remove(pos);
+        }
+    }
+
+    /// Returns the epoch after which the operator's approval lapses, if any
+    fn expiry_of(&self, operator: &ActorID) -> Option<ChainEpoch> {
+        let expiries = self.operator_expiries();
+        expiries.binary_search_by_key(operator, |e| e.operator).ok().map(|pos| expiries[pos].expiry)
+    }
+
+    /// Checks if the operator holds an unexpired approval at the current epoch
+    fn is_active_operator(&self, operator: &ActorID, current_epoch: ChainEpoch) -> bool {
+        self.operators().contains_actor(operator)
+            && self.expiry_of(operator).map_or(true, |expiry| current_epoch <= expiry)
+    }
+
+    /// Returns the set of operators holding an unexpired approval at the current epoch
+    fn active_operators(&self, current_epoch: ChainEpoch) -> BitField {
+        let mut active = self.operators().clone();
+        self.operator_expiries()
+            .iter()
+            .filter(|e| e.expiry < current_epoch)
+            .for_each(|e| active.unset(e.operator));
+        active
+    }
+
+    /// Removes all operators whose approval has expired by the current epoch
+    fn prune_expired(&mut self, current_epoch: ChainEpoch) {
+        let expired: Vec<ActorID> = self
+            .operator_expiries()
+            .iter()
+            .filter(|e| e.expiry < current_epoch)
+            .map(|e| e.operator)
+            .collect();
+        for operator in expired {
+            self.revoke_operator(&operator);
+        }
+    }
+}
+
 #[cfg(test)]
 mod vec_test {
     use fvm_shared::ActorID;
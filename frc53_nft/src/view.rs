@@ -0,0 +1,181 @@
+//! Read-only access to NFT state
+//!
+//! `NFTStateView` borrows the state immutably so query-only actor methods and off-chain readers can
+//! inspect a collection without the `&mut NFTState` that `NFT::wrap` requires. Addresses are not
+//! resolved by the view, so accounts are identified by ActorID.
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::ActorID;
+
+use crate::state::{Cursor, NFTState};
+use crate::types::{
+    ActorIDSet, CollectionMetadata, ListAccountOperatorsReturn, ListOperatorTokensReturn,
+    ListOwnedTokensReturn, ListTokenOperatorsReturn, ListTokensReturn, TokenID,
+};
+use crate::Result;
+
+/// An immutable view over an NFTState and the blockstore it is stored in
+pub struct NFTStateView<'st, BS: Blockstore> {
+    state: &'st NFTState,
+    bs: &'st BS,
+}
+
+impl<'st, BS: Blockstore> NFTStateView<'st, BS> {
+    pub fn new(state: &'st NFTState, bs: &'st BS) -> Self {
+        Self { state, bs }
+    }
+
+    /// Return the underlying state
+    pub fn state(&self) -> &NFTState {
+        self.state
+    }
+
+    /// Return the descriptive metadata of the collection
+    pub fn collection_metadata(&self) -> &CollectionMetadata {
+        &self.state.collection_metadata
+    }
+
+    /// Return the total number of NFTs in circulation from this collection
+    pub fn total_supply(&self) -> u64 {
+        self.state.total_supply
+    }
+
+    /// Return the number of NFTs held by a particular actor
+    pub fn balance_of(&self, owner: ActorID) -> Result<u64> {
+        Ok(self.state.get_balance(self.bs, owner)?)
+    }
+
+    /// Return the owner of an NFT
+    pub fn owner_of(&self, token_id: TokenID) -> Result<ActorID> {
+        Ok(self.state.get_owner(self.bs, token_id)?)
+    }
+
+    /// Return the metadata for an NFT
+    pub fn metadata(&self, token_id: TokenID) -> Result<String> {
+        Ok(self.state.get_metadata(self.bs, token_id)?)
+    }
+
+    /// Return the operators approved at token-level for an NFT at the given epoch
+    pub fn approved_operators(
+        &self,
+        token_id: TokenID,
+        current_epoch: ChainEpoch,
+    ) -> Result<ActorIDSet> {
+        Ok(self.state.get_token_operators(self.bs, token_id, current_epoch)?)
+    }
+
+    /// Return whether an actor is an account-level operator for an owner at the given epoch
+    pub fn is_account_operator(
+        &self,
+        owner: ActorID,
+        operator: ActorID,
+        current_epoch: ChainEpoch,
+    ) -> Result<bool> {
+        Ok(self.state.is_owner_operator(self.bs, owner, operator, current_epoch)?)
+    }
+
+    /// Enumerates a page of TokenIDs
+    pub fn list_tokens(&self, cursor: RawBytes, limit: u64) -> Result<ListTokensReturn> {
+        let cursor = Cursor::from_bytes(cursor)?;
+        let (tokens, next_cursor) = self.state.list_tokens(self.bs, cursor, limit)?;
+        let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
+        Ok(ListTokensReturn { tokens, next_cursor })
+    }
+
+    /// Enumerates a page of TokenIDs owned by a specific actor
+    pub fn list_owned_tokens(
+        &self,
+        owner: ActorID,
+        cursor: RawBytes,
+        limit: u64,
+    ) -> Result<ListOwnedTokensReturn> {
+        let cursor = Cursor::from_bytes(cursor)?;
+        let (tokens, next_cursor) = self.state.list_owned_tokens(self.bs, owner, cursor, limit)?;
+        let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
+        Ok(ListOwnedTokensReturn { tokens, next_cursor })
+    }
+
+    /// Enumerates a page of the operators approved for a token at the given epoch
+    pub fn list_token_operators(
+        &self,
+        token_id: TokenID,
+        cursor: RawBytes,
+        limit: u64,
+        current_epoch: ChainEpoch,
+    ) -> Result<ListTokenOperatorsReturn> {
+        let cursor = Cursor::from_bytes(cursor)?;
+        let (operators, next_cursor) =
+            self.state.list_token_operators(self.bs, token_id, cursor, limit, current_epoch)?;
+        let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
+        Ok(ListTokenOperatorsReturn { operators, next_cursor })
+    }
+
+    /// Enumerates a page of the tokens for which an actor is an operator at the given epoch
+    pub fn list_operator_tokens(
+        &self,
+        operator: ActorID,
+        cursor: RawBytes,
+        limit: u64,
+        current_epoch: ChainEpoch,
+    ) -> Result<ListOperatorTokensReturn> {
+        let cursor = Cursor::from_bytes(cursor)?;
+        let (tokens, next_cursor) =
+            self.state.list_operator_tokens(self.bs, operator, cursor, limit, current_epoch)?;
+        let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
+        Ok(ListOperatorTokensReturn { tokens, next_cursor })
+    }
+
+    /// Enumerates a page of the account-level operators approved by an owner at the given epoch
+    pub fn list_account_operators(
+        &self,
+        owner: ActorID,
+        cursor: RawBytes,
+        limit: u64,
+        current_epoch: ChainEpoch,
+    ) -> Result<ListAccountOperatorsReturn> {
+        let cursor = Cursor::from_bytes(cursor)?;
+        let (operators, next_cursor) =
+            self.state.list_account_operators(self.bs, owner, cursor, limit, current_epoch)?;
+        let next_cursor = next_cursor.map(|c| c.to_bytes()).transpose()?;
+        Ok(ListAccountOperatorsReturn { operators, next_cursor })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_ipld_bitfield::bitfield;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::ActorID;
+
+    use super::NFTStateView;
+    use crate::state::NFTState;
+
+    const ALICE_ID: ActorID = 1;
+    const BOB_ID: ActorID = 11;
+
+    #[test]
+    fn it_reads_state_without_mutable_access() {
+        let bs = MemoryBlockstore::default();
+        let mut state = NFTState::new(&bs).unwrap();
+        state.mint_tokens(&bs, ALICE_ID, vec!["a".into(), "b".into()], 0).unwrap();
+        state.mint_tokens(&bs, BOB_ID, vec!["c".into()], 0).unwrap();
+        state.approve_for_owner(&bs, ALICE_ID, BOB_ID, None, 0).unwrap();
+
+        let view = NFTStateView::new(&state, &bs);
+        assert_eq!(view.total_supply(), 3);
+        assert_eq!(view.balance_of(ALICE_ID).unwrap(), 2);
+        assert_eq!(view.balance_of(BOB_ID).unwrap(), 1);
+        assert_eq!(view.owner_of(2).unwrap(), BOB_ID);
+        assert_eq!(view.metadata(1).unwrap(), "b");
+        assert!(view.is_account_operator(ALICE_ID, BOB_ID, 0).unwrap());
+
+        let res = view.list_tokens(RawBytes::default(), u64::MAX).unwrap();
+        assert_eq!(res.tokens, bitfield![1, 1, 1]);
+        let res = view.list_owned_tokens(ALICE_ID, RawBytes::default(), u64::MAX).unwrap();
+        assert_eq!(res.tokens, bitfield![1, 1]);
+        let res = view.list_account_operators(ALICE_ID, RawBytes::default(), u64::MAX, 0).unwrap();
+        assert!(res.operators.get(BOB_ID));
+    }
+}
@@ -0,0 +1,56 @@
+//! Streaming JSON rendering of state
+//!
+//! States can hold far more entries than fit comfortably in memory, so rather than building a
+//! document and serializing it, the renderers write each entry to the output as the HAMTs and AMTs
+//! of the state are traversed. Token amounts are rendered as strings of atto units and actor IDs
+//! as object keys, which JSON requires to be strings.
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::ActorID;
+use serde::Serialize;
+
+/// Writes a JSON object one entry at a time
+pub(crate) struct JsonObject<W: Write> {
+    writer: W,
+    empty: bool,
+}
+
+impl<W: Write> JsonObject<W> {
+    /// Opens an object, which must be closed with [`end`](Self::end)
+    pub(crate) fn begin(mut writer: W) -> io::Result<Self> {
+        writer.write_all(b"{")?;
+        Ok(Self { writer, empty: true })
+    }
+
+    /// Writes the key of the next entry, returning the writer its value must be written to
+    pub(crate) fn key(&mut self, key: impl Display) -> io::Result<&mut W> {
+        if !self.empty {
+            self.writer.write_all(b",")?;
+        }
+        self.empty = false;
+        serde_json::to_writer(&mut self.writer, &key.to_string())?;
+        self.writer.write_all(b":")?;
+        Ok(&mut self.writer)
+    }
+
+    /// Writes an entry with a serializable value
+    pub(crate) fn entry(&mut self, key: impl Display, value: &impl Serialize) -> io::Result<()> {
+        serde_json::to_writer(self.key(key)?, value)?;
+        Ok(())
+    }
+
+    /// Closes the object, returning the writer
+    pub(crate) fn end(mut self) -> io::Result<W> {
+        self.writer.write_all(b"}")?;
+        Ok(self.writer)
+    }
+}
+
+/// Decodes an actor ID key of a HAMT while it is being traversed
+pub(crate) fn actor_id_key(key: &BytesKey) -> io::Result<ActorID> {
+    frc46_token::token::state::decode_actor_id(key).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("invalid actor id key {key:?}"))
+    })
+}
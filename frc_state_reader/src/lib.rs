@@ -0,0 +1,36 @@
+//! Off-chain reading of FRC-0046 token and FRC-0053 NFT state
+//!
+//! [`TokenReader`] and [`NFTReader`] load the state of a token or NFT actor from any blockstore
+//! holding it, and provide typed queries, full enumeration of the state, and streaming rendering
+//! of it as JSON. Nothing here calls into the FVM, so the state libraries are used without their
+//! `use_sdk` feature and no `fvm_sdk` is linked.
+use fvm_ipld_amt::Error as AmtError;
+use fvm_ipld_hamt::{BytesKey, Error as HamtError};
+use thiserror::Error;
+
+mod json;
+pub mod nft;
+pub mod token;
+
+pub use nft::NFTReader;
+pub use token::TokenReader;
+
+#[derive(Error, Debug)]
+pub enum ReaderError {
+    #[error("token state error: {0}")]
+    TokenState(#[from] frc46_token::token::state::StateError),
+    #[error("NFT state error: {0}")]
+    NFTState(#[from] frc53_nft::state::StateError),
+    #[error("NFT error: {0}")]
+    NFT(#[from] frc53_nft::NFTError),
+    #[error("ipld hamt error: {0}")]
+    IpldHamt(#[from] HamtError),
+    #[error("ipld amt error: {0}")]
+    IpldAmt(#[from] AmtError),
+    #[error("error writing JSON: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid actor id key {0:?}")]
+    InvalidKey(BytesKey),
+}
+
+pub type Result<T> = std::result::Result<T, ReaderError>;
@@ -0,0 +1,176 @@
+//! Reading of FRC-0053 NFT state
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use cid::Cid;
+use frc53_nft::state::{decode_actor_id, NFTState, OwnerData, TokenData};
+use frc53_nft::types::TokenID;
+use frc53_nft::view::NFTStateView;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::ActorID;
+use serde_json::json;
+
+use crate::json::{actor_id_key, JsonObject};
+use crate::{ReaderError, Result};
+
+/// Read-only access to an NFT collection's state in a blockstore
+///
+/// Queries the actor answers are made through the [`NFTStateView`] returned by
+/// [`view`](Self::view), while the reader adds enumeration of every token and owner.
+pub struct NFTReader<BS: Blockstore> {
+    state: NFTState,
+    bs: BS,
+}
+
+impl<BS: Blockstore> NFTReader<BS> {
+    /// Reads an NFT state which has already been loaded, such as one embedded in an actor's state
+    pub fn new(bs: BS, state: NFTState) -> Self {
+        Self { state, bs }
+    }
+
+    /// Loads the NFT state stored at a root CID
+    pub fn load(bs: BS, root: &Cid) -> Result<Self> {
+        let state = NFTState::load(&bs, root)?;
+        Ok(Self::new(bs, state))
+    }
+
+    /// Return the underlying state
+    pub fn state(&self) -> &NFTState {
+        &self.state
+    }
+
+    /// Return a view of the state answering the queries of the FRC-0053 actor interface
+    pub fn view(&self) -> NFTStateView<'_, BS> {
+        NFTStateView::new(&self.state, &self.bs)
+    }
+
+    /// Enumerates the data of every token, by ID
+    pub fn tokens(&self) -> Result<BTreeMap<TokenID, TokenData>> {
+        let mut tokens = BTreeMap::new();
+        self.state.get_token_data_amt(&self.bs)?.for_each(|token_id, data| {
+            tokens.insert(token_id, data.clone());
+            Ok(())
+        })?;
+        Ok(tokens)
+    }
+
+    /// Enumerates the owners of every token
+    pub fn owners(&self) -> Result<BTreeMap<TokenID, ActorID>> {
+        Ok(self.tokens()?.into_iter().map(|(token_id, data)| (token_id, data.owner)).collect())
+    }
+
+    /// Enumerates the data of every account that owns tokens or has approved operators
+    ///
+    /// The tokens of large holders are kept in a block of their own, which
+    /// [`OwnerData::owned_tokens`] loads.
+    pub fn accounts(&self) -> Result<BTreeMap<ActorID, OwnerData>> {
+        let mut entries = Vec::new();
+        self.state.get_owner_data_hamt(&self.bs)?.for_each(|key, data| {
+            entries.push((key.clone(), data.clone()));
+            Ok(())
+        })?;
+        entries.into_iter().map(|(key, data)| Ok((decode_key(key)?, data))).collect()
+    }
+
+    /// Renders the collection, its tokens and their owners as a JSON object, streamed to the writer
+    ///
+    /// Alongside the collection's `name`, `symbol`, `total_supply` and `next_token`, the object has
+    /// `tokens` keyed by token ID, such as `{"0":{"owner":1,"metadata":"a","operators":[2]}}`, and
+    /// `owners` keyed by actor ID, such as `{"1":{"balance":1,"operators":[3]}}`. Tokens are
+    /// written in ID order and owners in no particular order.
+    pub fn to_json<W: Write>(&self, writer: W) -> Result<W> {
+        let mut root = JsonObject::begin(writer)?;
+        root.entry("name", &self.state.collection_metadata.name)?;
+        root.entry("symbol", &self.state.collection_metadata.symbol)?;
+        root.entry("total_supply", &self.state.total_supply)?;
+        root.entry("next_token", &self.state.next_token)?;
+
+        let mut tokens = JsonObject::begin(root.key("tokens")?)?;
+        self.state.get_token_data_amt(&self.bs)?.for_each(|token_id, data| {
+            let operators: Vec<_> = data.operators.iter().collect();
+            let token = json!({
+                "owner": data.owner,
+                "metadata": data.metadata,
+                "operators": operators,
+            });
+            tokens.entry(token_id, &token)?;
+            Ok(())
+        })?;
+        tokens.end()?;
+
+        let mut owners = JsonObject::begin(root.key("owners")?)?;
+        self.state.get_owner_data_hamt(&self.bs)?.for_each(|key, data| {
+            let operators: Vec<_> = data.operators.iter().collect();
+            let owner = json!({ "balance": data.balance, "operators": operators });
+            owners.entry(actor_id_key(key)?, &owner)?;
+            Ok(())
+        })?;
+        owners.end()?;
+
+        Ok(root.end()?)
+    }
+}
+
+fn decode_key(key: BytesKey) -> Result<ActorID> {
+    decode_actor_id(&key).ok_or(ReaderError::InvalidKey(key))
+}
+
+#[cfg(test)]
+mod test {
+    use frc53_nft::state::NFTState;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::ActorID;
+
+    use super::NFTReader;
+
+    const ALICE: ActorID = 1;
+    const BOB: ActorID = 2;
+
+    #[test]
+    fn it_reads_and_enumerates_nft_state() {
+        let bs = MemoryBlockstore::default();
+        let mut state = NFTState::new(&bs).unwrap();
+        state.mint_tokens(&bs, ALICE, vec!["a".into(), "b".into()], 0).unwrap();
+        state.mint_tokens(&bs, BOB, vec!["c".into()], 0).unwrap();
+        state.approve_for_owner(&bs, BOB, ALICE, None, 0).unwrap();
+        let root = state.save(&bs).unwrap();
+
+        let reader = NFTReader::load(&bs, &root).unwrap();
+        let view = reader.view();
+        assert_eq!(view.total_supply(), 3);
+        assert_eq!(view.owner_of(2).unwrap(), BOB);
+        assert_eq!(view.metadata(1).unwrap(), "b");
+        assert!(view.is_account_operator(BOB, ALICE, 0).unwrap());
+
+        let tokens = reader.tokens().unwrap();
+        let metadata: Vec<_> = tokens.values().map(|data| data.metadata.as_str()).collect();
+        assert_eq!(metadata, ["a", "b", "c"]);
+        let owners: Vec<_> = reader.owners().unwrap().into_iter().collect();
+        assert_eq!(owners, [(0, ALICE), (1, ALICE), (2, BOB)]);
+        let accounts = reader.accounts().unwrap();
+        assert_eq!(accounts[&ALICE].balance, 2);
+        assert_eq!(accounts[&BOB].balance, 1);
+        assert!(accounts[&BOB].operators.get(ALICE));
+
+        let json = reader.to_json(Vec::new()).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["total_supply"], 3);
+        assert_eq!(json["next_token"], 3);
+        assert_eq!(
+            json["tokens"],
+            serde_json::json!({
+                "0": { "owner": ALICE, "metadata": "a", "operators": [] },
+                "1": { "owner": ALICE, "metadata": "b", "operators": [] },
+                "2": { "owner": BOB, "metadata": "c", "operators": [] },
+            })
+        );
+        assert_eq!(
+            json["owners"],
+            serde_json::json!({
+                "1": { "balance": 2, "operators": [] },
+                "2": { "balance": 1, "operators": [ALICE] },
+            })
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Reading of FRC-0046 token state
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use cid::Cid;
+use frc46_token::token::state::{decode_actor_id, TokenState};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::{econ::TokenAmount, ActorID};
+
+use crate::json::{actor_id_key, JsonObject};
+use crate::{ReaderError, Result};
+
+/// Balances by holder
+pub type Balances = BTreeMap<ActorID, TokenAmount>;
+/// Allowances by owner and then operator
+pub type Allowances = BTreeMap<ActorID, BTreeMap<ActorID, TokenAmount>>;
+
+/// Read-only access to a token's state in a blockstore
+pub struct TokenReader<BS: Blockstore> {
+    state: TokenState,
+    bs: BS,
+}
+
+impl<BS: Blockstore> TokenReader<BS> {
+    /// Reads a token state which has already been loaded, such as one embedded in an actor's state
+    pub fn new(bs: BS, state: TokenState) -> Self {
+        Self { state, bs }
+    }
+
+    /// Loads the token state stored at a root CID
+    pub fn load(bs: BS, root: &Cid) -> Result<Self> {
+        let state = TokenState::load(&bs, root)?;
+        Ok(Self::new(bs, state))
+    }
+
+    /// Return the underlying state
+    pub fn state(&self) -> &TokenState {
+        &self.state
+    }
+
+    /// Return the total number of tokens in circulation
+    pub fn total_supply(&self) -> &TokenAmount {
+        &self.state.supply
+    }
+
+    /// Return the balance of a holder, which is zero for actors that hold no tokens
+    pub fn balance_of(&self, holder: ActorID) -> Result<TokenAmount> {
+        Ok(self.state.get_balance(&self.bs, holder)?)
+    }
+
+    /// Return the allowance an owner has given an operator, which is zero if none was given
+    pub fn allowance(&self, owner: ActorID, operator: ActorID) -> Result<TokenAmount> {
+        Ok(self.state.get_allowance_between(&self.bs, owner, operator)?)
+    }
+
+    /// Return the number of actors holding a non-zero balance
+    pub fn holder_count(&self) -> Result<usize> {
+        Ok(self.state.count_balances(&self.bs)?)
+    }
+
+    /// Enumerates the balances of every holder
+    pub fn balances(&self) -> Result<Balances> {
+        let mut entries = Vec::new();
+        self.state.get_balance_map(&self.bs)?.for_each(|key, balance| {
+            entries.push((key.clone(), balance.clone()));
+            Ok(())
+        })?;
+        entries.into_iter().map(|(key, balance)| Ok((decode_key(key)?, balance))).collect()
+    }
+
+    /// Enumerates the allowances of every owner to each of their operators
+    pub fn allowances(&self) -> Result<Allowances> {
+        let mut owners = Vec::new();
+        self.state.get_allowances_map(&self.bs)?.for_each(|key, _| {
+            owners.push(key.clone());
+            Ok(())
+        })?;
+
+        let mut allowances = Allowances::new();
+        for owner in owners {
+            let owner = decode_key(owner)?;
+            let Some(owner_map) = self.state.get_owner_allowance_map(&self.bs, owner)? else {
+                continue;
+            };
+            let mut entries = Vec::new();
+            owner_map.for_each(|key, allowance| {
+                entries.push((key.clone(), allowance.clone()));
+                Ok(())
+            })?;
+            let operators = entries
+                .into_iter()
+                .map(|(key, allowance)| Ok((decode_key(key)?, allowance)))
+                .collect::<Result<_>>()?;
+            allowances.insert(owner, operators);
+        }
+        Ok(allowances)
+    }
+
+    /// Renders the supply, balances and allowances as a JSON object, streamed to the writer
+    ///
+    /// The object has `supply`, `balances` and `allowances` entries, such as
+    /// `{"supply":"150","balances":{"1":"150"},"allowances":{"1":{"2":"10"}}}`, with amounts in
+    /// atto units and allowances keyed by owner and then operator. Balances and allowances are
+    /// written in no particular order.
+    pub fn to_json<W: Write>(&self, writer: W) -> Result<W> {
+        let mut root = JsonObject::begin(writer)?;
+        root.entry("supply", &self.state.supply.atto().to_string())?;
+
+        let mut balances = JsonObject::begin(root.key("balances")?)?;
+        self.state.get_balance_map(&self.bs)?.for_each(|key, balance| {
+            balances.entry(actor_id_key(key)?, &balance.atto().to_string())?;
+            Ok(())
+        })?;
+        balances.end()?;
+
+        let mut allowances = JsonObject::begin(root.key("allowances")?)?;
+        self.state.get_allowances_map(&self.bs)?.for_each(|key, _| {
+            let owner = actor_id_key(key)?;
+            let Some(owner_map) = self.state.get_owner_allowance_map(&self.bs, owner)? else {
+                return Ok(());
+            };
+            let mut operators = JsonObject::begin(allowances.key(owner)?)?;
+            owner_map.for_each(|key, allowance| {
+                operators.entry(actor_id_key(key)?, &allowance.atto().to_string())?;
+                Ok(())
+            })?;
+            operators.end()?;
+            Ok(())
+        })?;
+        allowances.end()?;
+
+        Ok(root.end()?)
+    }
+}
+
+fn decode_key(key: BytesKey) -> Result<ActorID> {
+    decode_actor_id(&key).ok_or(ReaderError::InvalidKey(key))
+}
+
+#[cfg(test)]
+mod test {
+    use frc46_token::token::state::TokenState;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::ActorID;
+
+    use super::TokenReader;
+
+    const ALICE: ActorID = 1;
+    const BOB: ActorID = 2;
+    const CAROL: ActorID = 3;
+
+    fn amount(atto: u64) -> TokenAmount {
+        TokenAmount::from_atto(atto)
+    }
+
+    #[test]
+    fn it_reads_and_enumerates_token_state() {
+        let bs = MemoryBlockstore::default();
+        let mut state = TokenState::new(&bs).unwrap();
+        state.change_balance_by(&bs, ALICE, &amount(100)).unwrap();
+        state.change_balance_by(&bs, BOB, &amount(50)).unwrap();
+        state.change_supply_by(&amount(150)).unwrap();
+        state.change_allowance_by(&bs, ALICE, BOB, &amount(10)).unwrap();
+        state.change_allowance_by(&bs, ALICE, CAROL, &amount(20)).unwrap();
+        state.change_allowance_by(&bs, BOB, ALICE, &amount(30)).unwrap();
+        let root = state.save(&bs).unwrap();
+
+        let reader = TokenReader::load(&bs, &root).unwrap();
+        assert_eq!(reader.state(), &state);
+        assert_eq!(reader.total_supply(), &amount(150));
+        assert_eq!(reader.balance_of(ALICE).unwrap(), amount(100));
+        assert_eq!(reader.balance_of(CAROL).unwrap(), amount(0));
+        assert_eq!(reader.allowance(ALICE, CAROL).unwrap(), amount(20));
+        assert_eq!(reader.allowance(CAROL, ALICE).unwrap(), amount(0));
+        assert_eq!(reader.holder_count().unwrap(), 2);
+
+        let balances = reader.balances().unwrap();
+        assert_eq!(
+            balances.into_iter().collect::<Vec<_>>(),
+            [(ALICE, amount(100)), (BOB, amount(50))]
+        );
+        let allowances = reader.allowances().unwrap();
+        assert_eq!(allowances.len(), 2);
+        assert_eq!(allowances[&ALICE][&BOB], amount(10));
+        assert_eq!(allowances[&ALICE][&CAROL], amount(20));
+        assert_eq!(allowances[&BOB][&ALICE], amount(30));
+
+        let json = reader.to_json(Vec::new()).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "supply": "150",
+                "balances": { "1": "100", "2": "50" },
+                "allowances": { "1": { "2": "10", "3": "20" }, "2": { "1": "30" } },
+            })
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Solidity ABI encoding for actors that serve calls from EVM contracts
+//!
+//! EVM contracts call other actors through their `InvokeEVM` method, passing Solidity calldata: a
+//! 4-byte function selector followed by the arguments as 32-byte ABI words. Hybrid actors, such as
+//! tokens offering an ERC-20 facade alongside their native methods, use [`Calldata`] to decode the
+//! arguments and [`encode`] to build the return data.
+//!
+//! Ethereum addresses are mapped to Filecoin addresses as the EVM actor maps them. An address made
+//! of `0xff`, eleven zero bytes and a big-endian actor ID stands for that ID address, and any other
+//! address for the delegated (f410) address in the namespace of the Ethereum Address Manager.
+use fvm_shared::address::{Address, Payload};
+use fvm_shared::bigint::{BigInt, Sign};
+use fvm_shared::error::ExitCode;
+use fvm_shared::ActorID;
+use thiserror::Error;
+
+/// Size of an ABI-encoded word
+pub const WORD_SIZE: usize = 32;
+
+/// An ABI-encoded word
+pub type Word = [u8; WORD_SIZE];
+
+/// A 4-byte function selector, as computed by `frc42_dispatch::hash::evm_selector`
+pub type Selector = u32;
+
+/// The ID of the Ethereum Address Manager, whose namespace of delegated addresses holds the
+/// addresses of EVM contracts and accounts
+pub const EAM_ACTOR_ID: ActorID = 10;
+
+/// Prefix of an Ethereum address which holds an actor ID in its last 8 bytes
+const ID_ADDRESS_PREFIX: [u8; 12] = [0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum AbiError {
+    #[error("calldata is too short to hold a selector")]
+    MissingSelector,
+    #[error("no function matches the selector {0:#010x}")]
+    UnknownSelector(Selector),
+    #[error("argument {0} is missing from the calldata")]
+    MissingArgument(usize),
+    #[error("argument {0} is not a valid {1}")]
+    InvalidArgument(usize, &'static str),
+    #[error("{0} has no Ethereum address")]
+    NoEthAddress(Address),
+    #[error("value {0} doesn't fit in a uint256")]
+    Overflow(BigInt),
+}
+
+impl From<&AbiError> for ExitCode {
+    fn from(error: &AbiError) -> Self {
+        match error {
+            AbiError::MissingSelector | AbiError::UnknownSelector(_) => {
+                ExitCode::USR_UNHANDLED_MESSAGE
+            }
+            AbiError::MissingArgument(_)
+            | AbiError::InvalidArgument(..)
+            | AbiError::NoEthAddress(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
+            AbiError::Overflow(_) => ExitCode::USR_ILLEGAL_STATE,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, AbiError>;
+
+/// The selector and arguments of a call from an EVM contract
+#[derive(Clone, Copy, Debug)]
+pub struct Calldata<'a> {
+    selector: Selector,
+    args: &'a [u8],
+}
+
+impl<'a> Calldata<'a> {
+    /// Splits calldata into its selector and ABI-encoded arguments
+    pub fn parse(calldata: &'a [u8]) -> Result<Self> {
+        if calldata.len() < 4 {
+            return Err(AbiError::MissingSelector);
+        }
+        let (selector, args) = calldata.split_at(4);
+        Ok(Self { selector: Selector::from_be_bytes(selector.try_into().unwrap()), args })
+    }
+
+    /// Returns the selector of the called function
+    pub fn selector(&self) -> Selector {
+        self.selector
+    }
+
+    /// Returns the word holding a static argument, or the offset of a dynamic one
+    pub fn word(&self, index: usize) -> Result<&'a Word> {
+        self.args
+            .get(index * WORD_SIZE..(index + 1) * WORD_SIZE)
+            .map(|word| word.try_into().unwrap())
+            .ok_or(AbiError::MissingArgument(index))
+    }
+
+    /// Decodes an `address` argument to the Filecoin address it stands for
+    pub fn address(&self, index: usize) -> Result<Address> {
+        let word = self.word(index)?;
+        if word[..12] != [0; 12] {
+            return Err(AbiError::InvalidArgument(index, "address"));
+        }
+        Ok(address_from_eth(word[12..].try_into().unwrap()))
+    }
+
+    /// Decodes a `uint256` argument
+    pub fn uint256(&self, index: usize) -> Result<BigInt> {
+        Ok(BigInt::from_bytes_be(Sign::Plus, self.word(index)?))
+    }
+
+    /// Decodes a `uint64` argument, or any smaller unsigned integer type
+    pub fn uint64(&self, index: usize) -> Result<u64> {
+        let word = self.word(index)?;
+        if word[..WORD_SIZE - 8] != [0; WORD_SIZE - 8] {
+            return Err(AbiError::InvalidArgument(index, "uint64"));
+        }
+        Ok(u64::from_be_bytes(word[WORD_SIZE - 8..].try_into().unwrap()))
+    }
+
+    /// Decodes a `bool` argument
+    pub fn bool(&self, index: usize) -> Result<bool> {
+        match self.uint64(index) {
+            Ok(0) => Ok(false),
+            Ok(1) => Ok(true),
+            _ => Err(AbiError::InvalidArgument(index, "bool")),
+        }
+    }
+
+    /// Decodes a dynamic `bytes` or `string` argument, which is found at the offset given by its
+    /// word
+    pub fn bytes(&self, index: usize) -> Result<&'a [u8]> {
+        let invalid = AbiError::InvalidArgument(index, "bytes");
+        let offset = usize::try_from(self.uint64(index)?).map_err(|_| invalid.clone())?;
+        let len_word = offset
+            .checked_add(WORD_SIZE)
+            .and_then(|end| self.args.get(offset..end))
+            .ok_or_else(|| invalid.clone())?;
+        if len_word[..WORD_SIZE - 8] != [0; WORD_SIZE - 8] {
+            return Err(invalid);
+        }
+        let len = u64::from_be_bytes(len_word[WORD_SIZE - 8..].try_into().unwrap());
+        let start = offset + WORD_SIZE;
+        usize::try_from(len)
+            .ok()
+            .and_then(|len| self.args.get(start..start.checked_add(len)?))
+            .ok_or(invalid)
+    }
+}
+
+/// A value to be ABI-encoded
+#[derive(Clone, Copy, Debug)]
+pub enum AbiValue<'a> {
+    /// A static value, already encoded as a word
+    Word(Word),
+    /// A dynamic `bytes` or `string` value
+    Bytes(&'a [u8]),
+}
+
+/// Encodes a list of values, as the return data of a function or the arguments of a call
+///
+/// Static values are encoded in place, while dynamic values are appended after them and replaced
+/// by their offset.
+pub fn encode(values: &[AbiValue]) -> Vec<u8> {
+    let head_size = values.len() * WORD_SIZE;
+    let mut head = Vec::with_capacity(head_size);
+    let mut tail = Vec::new();
+    for value in values {
+        match value {
+            AbiValue::Word(word) => head.extend(word),
+            AbiValue::Bytes(bytes) => {
+                head.extend(encode_uint64((head_size + tail.len()) as u64));
+                tail.extend(encode_uint64(bytes.len() as u64));
+                tail.extend(*bytes);
+                tail.resize(tail.len().div_ceil(WORD_SIZE) * WORD_SIZE, 0);
+            }
+        }
+    }
+    head.extend(tail);
+    head
+}
+
+/// Encodes a non-negative integer below 2^256 as a `uint256`
+pub fn encode_uint256(value: &BigInt) -> Result<Word> {
+    let (sign, bytes) = value.to_bytes_be();
+    if sign == Sign::Minus || bytes.len() > WORD_SIZE {
+        return Err(AbiError::Overflow(value.clone()));
+    }
+    let mut word = [0; WORD_SIZE];
+    word[WORD_SIZE - bytes.len()..].copy_from_slice(&bytes);
+    Ok(word)
+}
+
+/// Encodes a `uint64`, or any smaller unsigned integer type
+pub fn encode_uint64(value: u64) -> Word {
+    let mut word = [0; WORD_SIZE];
+    word[WORD_SIZE - 8..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// Encodes a `bool`
+pub fn encode_bool(value: bool) -> Word {
+    encode_uint64(value.into())
+}
+
+/// Encodes the Ethereum address of an ID or EAM delegated address as an `address`
+pub fn encode_address(address: &Address) -> Result<Word> {
+    let eth_address = eth_address_of(address).ok_or(AbiError::NoEthAddress(*address))?;
+    let mut word = [0; WORD_SIZE];
+    word[12..].copy_from_slice(&eth_address);
+    Ok(word)
+}
+
+/// Returns the Ethereum address standing for an ID address or an EAM delegated address
+pub fn eth_address_of(address: &Address) -> Option<[u8; 20]> {
+    match address.payload() {
+        Payload::ID(id) => {
+            let mut eth_address = [0; 20];
+            eth_address[..12].copy_from_slice(&ID_ADDRESS_PREFIX);
+            eth_address[12..].copy_from_slice(&id.to_be_bytes());
+            Some(eth_address)
+        }
+        Payload::Delegated(delegated) if delegated.namespace() == EAM_ACTOR_ID => {
+            delegated.subaddress().try_into().ok()
+        }
+        _ => None,
+    }
+}
+
+/// Returns the Filecoin address an Ethereum address stands for
+pub fn address_from_eth(eth_address: &[u8; 20]) -> Address {
+    if eth_address[..12] == ID_ADDRESS_PREFIX {
+        Address::new_id(u64::from_be_bytes(eth_address[12..].try_into().unwrap()))
+    } else {
+        // an EAM subaddress of 20 bytes is always valid
+        Address::new_delegated(EAM_ACTOR_ID, eth_address).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_shared::address::Address;
+    use fvm_shared::bigint::BigInt;
+
+    use super::{
+        address_from_eth, encode, encode_address, encode_bool, encode_uint256, encode_uint64,
+        eth_address_of, AbiError, AbiValue, Calldata, EAM_ACTOR_ID, WORD_SIZE,
+    };
+
+    const SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+    fn calldata(args: &[u8]) -> Vec<u8> {
+        [&SELECTOR[..], args].concat()
+    }
+
+    #[test]
+    fn it_maps_addresses() {
+        let id = Address::new_id(1234);
+        let eth_address = eth_address_of(&id).unwrap();
+        assert_eq!(eth_address[0], 0xff);
+        assert_eq!(address_from_eth(&eth_address), id);
+
+        let delegated = Address::new_delegated(EAM_ACTOR_ID, &[0x11; 20]).unwrap();
+        assert_eq!(eth_address_of(&delegated), Some([0x11; 20]));
+        assert_eq!(address_from_eth(&[0x11; 20]), delegated);
+
+        // only EAM addresses of 20 bytes have an Ethereum address
+        assert_eq!(eth_address_of(&Address::new_delegated(32, &[0x11; 20]).unwrap()), None);
+        assert_eq!(
+            eth_address_of(&Address::new_delegated(EAM_ACTOR_ID, &[0x11; 8]).unwrap()),
+            None
+        );
+        let secp = Address::new_secp256k1(&[0x22; 65]).unwrap();
+        assert_eq!(encode_address(&secp), Err(AbiError::NoEthAddress(secp)));
+    }
+
+    #[test]
+    fn it_decodes_static_arguments() {
+        let id = Address::new_id(1234);
+        let args = [
+            encode_address(&id).unwrap(),
+            encode_uint256(&BigInt::from(10).pow(30)).unwrap(),
+            encode_uint64(7),
+            encode_bool(true),
+        ]
+        .concat();
+        let data = calldata(&args);
+        let calldata = Calldata::parse(&data).unwrap();
+        assert_eq!(calldata.selector(), 0xa9059cbb);
+        assert_eq!(calldata.address(0).unwrap(), id);
+        assert_eq!(calldata.uint256(1).unwrap(), BigInt::from(10).pow(30));
+        assert_eq!(calldata.uint64(2).unwrap(), 7);
+        assert!(calldata.bool(3).unwrap());
+        assert_eq!(calldata.word(4), Err(AbiError::MissingArgument(4)));
+
+        // words out of range for their type are rejected
+        assert_eq!(calldata.address(1), Err(AbiError::InvalidArgument(1, "address")));
+        assert_eq!(calldata.uint64(1), Err(AbiError::InvalidArgument(1, "uint64")));
+        assert_eq!(calldata.bool(2), Err(AbiError::InvalidArgument(2, "bool")));
+
+        assert_eq!(Calldata::parse(&SELECTOR[..3]).unwrap_err(), AbiError::MissingSelector);
+    }
+
+    #[test]
+    fn it_round_trips_dynamic_values() {
+        let data = b"a string longer than a single word";
+        let encoded = encode(&[
+            AbiValue::Word(encode_uint64(5)),
+            AbiValue::Bytes(data),
+            AbiValue::Bytes(b""),
+        ]);
+        // head, then the length and two padded words of the first value, then the empty value
+        assert_eq!(encoded.len(), 7 * WORD_SIZE);
+
+        let calldata_bytes = calldata(&encoded);
+        let calldata = Calldata::parse(&calldata_bytes).unwrap();
+        assert_eq!(calldata.uint64(0).unwrap(), 5);
+        assert_eq!(calldata.bytes(1).unwrap(), data);
+        assert_eq!(calldata.bytes(2).unwrap(), b"");
+        // a static argument isn't a valid offset
+        assert_eq!(calldata.bytes(0), Err(AbiError::InvalidArgument(0, "bytes")));
+    }
+
+    #[test]
+    fn it_rejects_values_out_of_range() {
+        let max = (BigInt::from(1) << 256) - 1;
+        assert_eq!(encode_uint256(&max).unwrap(), [0xff; WORD_SIZE]);
+        let too_big = BigInt::from(1) << 256;
+        assert_eq!(encode_uint256(&too_big), Err(AbiError::Overflow(too_big)));
+        assert_eq!(encode_uint256(&BigInt::from(-1)), Err(AbiError::Overflow(BigInt::from(-1))));
+    }
+}
@@ -0,0 +1,219 @@
+//! A persistent cache of resolved addresses
+//!
+//! Actors that repeatedly deal with the same counterparties (such as a token resolving the same
+//! holders on every transfer) can keep an [`AddressBook`] in their state to avoid resolving the
+//! same external addresses (f1, f2, f3 and f4) again and again.
+//!
+//! The ID an address resolves to can change until the message that created the actor there is
+//! final, as a chain reorganisation may cause a different ID to be assigned. Entries are therefore
+//! only trusted once they were first observed at least [`AddressBook::finality`] epochs ago.
+//! Younger entries are re-resolved on each use, and are removed if the address no longer resolves.
+//! Addresses that don't resolve are never cached, and ID addresses are returned as-is.
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_hamt::{BytesKey, Error as HamtError, Hamt};
+use fvm_shared::address::{Address, Payload};
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::error::ExitCode;
+use fvm_shared::ActorID;
+use thiserror::Error;
+
+use crate::messaging::MessagingError;
+use crate::syscalls::Syscalls;
+use crate::util::ActorRuntime;
+
+/// Number of epochs after which the chain is considered final and resolved IDs can't change
+pub const CHAIN_FINALITY: ChainEpoch = 900;
+
+/// Bit-width of the HAMT backing the address book
+pub const ADDRESS_BOOK_BIT_WIDTH: u32 = 5;
+
+#[derive(Error, Debug)]
+pub enum AddressBookError {
+    #[error("error in address book hamt: {0}")]
+    Hamt(#[from] HamtError),
+    #[error("error resolving address: {0}")]
+    Messaging(#[from] MessagingError),
+}
+
+impl From<&AddressBookError> for ExitCode {
+    fn from(error: &AddressBookError) -> Self {
+        match error {
+            AddressBookError::Hamt(_) => ExitCode::USR_SERIALIZATION,
+            AddressBookError::Messaging(e) => e.into(),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, AddressBookError>;
+
+/// An ID resolved from an address, and the epoch at which it was first seen
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CachedId {
+    pub id: ActorID,
+    pub first_seen: ChainEpoch,
+}
+
+impl CachedId {
+    /// Returns true if the ID was first seen long enough before `epoch` that it can't change
+    pub fn is_final(&self, epoch: ChainEpoch, finality: ChainEpoch) -> bool {
+        epoch.saturating_sub(self.first_seen) >= finality
+    }
+}
+
+/// A map from external addresses to the IDs they resolve to, stored as a HAMT
+pub struct AddressBook<BS: Blockstore> {
+    map: Hamt<BS, CachedId, BytesKey>,
+    finality: ChainEpoch,
+}
+
+impl<BS: Blockstore> AddressBook<BS> {
+    /// Creates an empty address book
+    pub fn new(store: BS) -> Self {
+        Self {
+            map: Hamt::new_with_bit_width(store, ADDRESS_BOOK_BIT_WIDTH),
+            finality: CHAIN_FINALITY,
+        }
+    }
+
+    /// Loads an address book previously saved with [`AddressBook::flush`]
+    pub fn load(root: &Cid, store: BS) -> Result<Self> {
+        Ok(Self {
+            map: Hamt::load_with_bit_width(root, store, ADDRESS_BOOK_BIT_WIDTH)?,
+            finality: CHAIN_FINALITY,
+        })
+    }
+
+    /// Sets the number of epochs after which cached entries are trusted without re-resolving them
+    ///
+    /// This isn't persisted, so must be set each time the address book is loaded.
+    pub fn with_finality(mut self, finality: ChainEpoch) -> Self {
+        self.finality = finality;
+        self
+    }
+
+    /// The number of epochs after which cached entries are trusted without re-resolving them
+    pub fn finality(&self) -> ChainEpoch {
+        self.finality
+    }
+
+    /// Saves any changes to the blockstore, returning the root to store in actor state
+    pub fn flush(&mut self) -> Result<Cid> {
+        Ok(self.map.flush()?)
+    }
+
+    /// Resolves an address to an ID, using the cached ID if it is final
+    ///
+    /// Otherwise the address is resolved through the runtime and the result recorded. Returns
+    /// [`MessagingError::AddressNotResolved`] if the address doesn't resolve.
+    pub fn resolve<S: Syscalls, RBS: Blockstore>(
+        &mut self,
+        runtime: &ActorRuntime<S, RBS>,
+        address: &Address,
+    ) -> Result<ActorID> {
+        if let Payload::ID(id) = address.payload() {
+            return Ok(*id);
+        }
+
+        let key = BytesKey(address.to_bytes());
+        let epoch = runtime.curr_epoch();
+        let cached = self.map.get(&key)?.copied();
+        if let Some(cached) = cached {
+            if cached.is_final(epoch, self.finality) {
+                return Ok(cached.id);
+            }
+        }
+
+        let id = match runtime.resolve_id(address) {
+            Ok(id) => id,
+            Err(e) => {
+                if cached.is_some() {
+                    self.map.delete(&key)?;
+                }
+                return Err(e.into());
+            }
+        };
+        // an entry keeps maturing as long as the address keeps resolving to the same ID
+        match cached {
+            Some(cached) if cached.id == id => {}
+            _ => {
+                self.map.set(key, CachedId { id, first_seen: epoch })?;
+            }
+        }
+        Ok(id)
+    }
+
+    /// Returns the cached entry for an address without resolving it
+    pub fn get(&self, address: &Address) -> Result<Option<CachedId>> {
+        Ok(self.map.get(&BytesKey(address.to_bytes()))?.copied())
+    }
+
+    /// Removes the cached entry for an address, returning it if there was one
+    pub fn invalidate(&mut self, address: &Address) -> Result<Option<CachedId>> {
+        Ok(self.map.delete(&BytesKey(address.to_bytes()))?.map(|(_, cached)| cached))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::address::Address;
+
+    use super::{AddressBook, AddressBookError, CachedId, CHAIN_FINALITY};
+    use crate::messaging::MessagingError;
+    use crate::syscalls::fake_syscalls::FakeSyscalls;
+    use crate::util::ActorRuntime;
+
+    #[test]
+    fn it_trusts_entries_once_final() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let bs = MemoryBlockstore::new();
+        let mut book = AddressBook::new(&bs);
+        let secp = Address::new_secp256k1(&[1; 65]).unwrap();
+
+        // unresolvable addresses aren't cached
+        let err = book.resolve(&runtime, &secp).unwrap_err();
+        assert!(matches!(err, AddressBookError::Messaging(MessagingError::AddressNotResolved(_))));
+        assert_eq!(book.get(&secp).unwrap(), None);
+        assert_eq!(book.resolve(&runtime, &Address::new_id(7)).unwrap(), 7);
+
+        runtime.syscalls.addresses.borrow_mut().insert(secp, 100);
+        runtime.syscalls.set_curr_epoch(10);
+        assert_eq!(book.resolve(&runtime, &secp).unwrap(), 100);
+        assert_eq!(book.get(&secp).unwrap(), Some(CachedId { id: 100, first_seen: 10 }));
+
+        // before finality, changes are picked up without resetting the age of unchanged entries
+        runtime.syscalls.set_curr_epoch(20);
+        assert_eq!(book.resolve(&runtime, &secp).unwrap(), 100);
+        assert_eq!(book.get(&secp).unwrap(), Some(CachedId { id: 100, first_seen: 10 }));
+        runtime.syscalls.addresses.borrow_mut().insert(secp, 101);
+        assert_eq!(book.resolve(&runtime, &secp).unwrap(), 101);
+        assert_eq!(book.get(&secp).unwrap(), Some(CachedId { id: 101, first_seen: 20 }));
+
+        // once final the cached ID is used without resolving, and survives reloading
+        let root = book.flush().unwrap();
+        let mut book = AddressBook::load(&root, &bs).unwrap();
+        runtime.syscalls.addresses.borrow_mut().clear();
+        runtime.syscalls.set_curr_epoch(20 + CHAIN_FINALITY);
+        assert_eq!(book.resolve(&runtime, &secp).unwrap(), 101);
+
+        assert!(book.invalidate(&secp).unwrap().is_some());
+        book.resolve(&runtime, &secp).unwrap_err();
+    }
+
+    #[test]
+    fn it_drops_entries_that_stop_resolving() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let bs = MemoryBlockstore::new();
+        let mut book = AddressBook::new(&bs).with_finality(5);
+        let secp = Address::new_secp256k1(&[2; 65]).unwrap();
+
+        runtime.syscalls.addresses.borrow_mut().insert(secp, 100);
+        book.resolve(&runtime, &secp).unwrap();
+        runtime.syscalls.addresses.borrow_mut().clear();
+        runtime.syscalls.set_curr_epoch(4);
+        book.resolve(&runtime, &secp).unwrap_err();
+        assert_eq!(book.get(&secp).unwrap(), None);
+    }
+}
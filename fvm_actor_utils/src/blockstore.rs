@@ -1,16 +1,26 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
 use anyhow::anyhow;
 use anyhow::Result;
+#[cfg(feature = "use_sdk")]
 use cid::multihash::Code;
 use cid::Cid;
+#[cfg(feature = "use_sdk")]
 use fvm_ipld_blockstore::Block;
+use fvm_ipld_encoding::DAG_CBOR;
+#[cfg(feature = "use_sdk")]
 use fvm_sdk::ipld;
+use serde::Serialize;
 
 /// A blockstore that delegates to IPLD syscalls.
+#[cfg(feature = "use_sdk")]
 #[derive(Default, Debug, Copy, Clone)]
 pub struct Blockstore;
 
 /// Blockstore implementation is borrowed from https://github.com/filecoin-project/builtin-actors/blob/6df845dcdf9872beb6e871205eb34dcc8f7550b5/runtime/src/runtime/actor_blockstore.rs
 /// This impl will likely be made redundant if low-level SDKs export blockstore implementations
+#[cfg(feature = "use_sdk")]
 impl fvm_ipld_blockstore::Blockstore for Blockstore {
     fn get(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
         // If this fails, the _CID_ is invalid. I.e., we have a bug.
@@ -38,3 +48,202 @@ impl fvm_ipld_blockstore::Blockstore for Blockstore {
         Ok(k)
     }
 }
+
+/// A blockstore that holds new blocks in memory until they are explicitly flushed
+///
+/// Modifying a HAMT or AMT writes new nodes on every flush, most of which are replaced again
+/// before the state is saved. Buffering the writes of a transaction and flushing only the blocks
+/// reachable from the final state means those intermediate nodes are never written to the
+/// underlying store. Unflushed blocks are discarded when the buffer is dropped.
+#[derive(Debug)]
+pub struct BufferedBlockstore<BS: fvm_ipld_blockstore::Blockstore> {
+    inner: BS,
+    buffer: RefCell<HashMap<Cid, Vec<u8>>>,
+}
+
+impl<BS: fvm_ipld_blockstore::Blockstore> BufferedBlockstore<BS> {
+    pub fn new(inner: BS) -> Self {
+        Self { inner, buffer: RefCell::new(HashMap::new()) }
+    }
+
+    /// Returns the number of blocks written since the last flush
+    pub fn pending(&self) -> usize {
+        self.buffer.borrow().len()
+    }
+
+    /// Writes the buffered blocks reachable from `root` to the underlying store
+    ///
+    /// Blocks that aren't buffered are assumed to already be in the underlying store, so their
+    /// links aren't followed.
+    pub fn flush(&self, root: &Cid) -> Result<()> {
+        let mut stack = vec![*root];
+        while let Some(cid) = stack.pop() {
+            let block = match self.buffer.borrow_mut().remove(&cid) {
+                Some(block) => block,
+                None => continue,
+            };
+            self.inner.put_keyed(&cid, &block)?;
+            if cid.codec() == DAG_CBOR {
+                scan_links(&block, |link| stack.push(link))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered blocks reachable from any of the links in `value`, such as the HAMT and
+    /// AMT roots held in an actor's state
+    pub fn flush_links<T: Serialize + ?Sized>(&self, value: &T) -> Result<()> {
+        let encoded = fvm_ipld_encoding::to_vec(value)?;
+        let mut roots = Vec::new();
+        scan_links(&encoded, |link| roots.push(link))?;
+        roots.iter().try_for_each(|root| self.flush(root))
+    }
+
+    /// Returns the underlying store
+    pub fn into_inner(self) -> BS {
+        self.inner
+    }
+}
+
+impl<BS: fvm_ipld_blockstore::Blockstore> fvm_ipld_blockstore::Blockstore
+    for BufferedBlockstore<BS>
+{
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        match self.buffer.borrow().get(k) {
+            Some(block) => Ok(Some(block.clone())),
+            None => self.inner.get(k),
+        }
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        self.buffer.borrow_mut().insert(*k, block.to_vec());
+        Ok(())
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool> {
+        if self.buffer.borrow().contains_key(k) {
+            return Ok(true);
+        }
+        self.inner.has(k)
+    }
+}
+
+/// The CBOR tag marking a CID in DAG-CBOR
+pub(crate) const CID_TAG: u64 = 42;
+
+/// Calls `f` with each CID linked from a DAG-CBOR encoded block
+pub(crate) fn scan_links(mut data: &[u8], mut f: impl FnMut(Cid)) -> Result<()> {
+    let mut remaining = 1u64;
+    while remaining > 0 {
+        remaining -= 1;
+        let (major, value) = read_header(&mut data)?;
+        match major {
+            // integers and simple values, whose value was read with the header
+            0 | 1 | 7 => {}
+            // byte and text strings
+            2 | 3 => {
+                take(&mut data, value)?;
+            }
+            4 => remaining = add_items(remaining, Some(value))?,
+            5 => remaining = add_items(remaining, value.checked_mul(2))?,
+            6 if value == CID_TAG => {
+                let (major, len) = read_header(&mut data)?;
+                let bytes = take(&mut data, len)?;
+                // CIDs are byte strings prefixed with the identity multibase
+                match (major, bytes.split_first()) {
+                    (2, Some((0, cid))) => f(Cid::try_from(cid)?),
+                    _ => return Err(anyhow!("invalid CID in DAG-CBOR block")),
+                }
+            }
+            // other tags apply to the following item
+            6 => remaining = add_items(remaining, Some(1))?,
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Adds the number of items in an array or map to those remaining, failing on overflow
+fn add_items(remaining: u64, items: Option<u64>) -> Result<u64> {
+    items
+        .and_then(|items| remaining.checked_add(items))
+        .ok_or_else(|| anyhow!("too many items in DAG-CBOR block"))
+}
+
+/// Reads the major type and argument of a CBOR item header
+pub(crate) fn read_header(data: &mut &[u8]) -> Result<(u8, u64)> {
+    let first = take(data, 1)?[0];
+    let value = match first & 0x1f {
+        info @ 0..=23 => info as u64,
+        24 => take(data, 1)?[0] as u64,
+        25 => u16::from_be_bytes(take(data, 2)?.try_into()?) as u64,
+        26 => u32::from_be_bytes(take(data, 4)?.try_into()?) as u64,
+        27 => u64::from_be_bytes(take(data, 8)?.try_into()?),
+        _ => return Err(anyhow!("indefinite length items are not allowed in DAG-CBOR")),
+    };
+    Ok((first >> 5, value))
+}
+
+pub(crate) fn take<'a>(data: &mut &'a [u8], len: u64) -> Result<&'a [u8]> {
+    let len = usize::try_from(len)?;
+    if data.len() < len {
+        return Err(anyhow!("unexpected end of DAG-CBOR block"));
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+    use fvm_ipld_encoding::CborStore;
+
+    use super::{scan_links, BufferedBlockstore};
+
+    #[test]
+    fn it_flushes_reachable_blocks() {
+        let inner = MemoryBlockstore::new();
+        let bs = BufferedBlockstore::new(&inner);
+
+        let leaf = bs.put_cbor(&"leaf", Code::Blake2b256).unwrap();
+        let orphan = bs.put_cbor(&"orphan", Code::Blake2b256).unwrap();
+        let root = bs.put_cbor(&(vec![leaf], 1u64, "root"), Code::Blake2b256).unwrap();
+        assert_eq!(bs.pending(), 3);
+        assert!(bs.has(&leaf).unwrap());
+        assert!(!inner.has(&leaf).unwrap());
+        assert_eq!(bs.get_cbor::<String>(&leaf).unwrap().unwrap(), "leaf");
+
+        bs.flush(&root).unwrap();
+        assert!(inner.has(&root).unwrap());
+        assert!(inner.has(&leaf).unwrap());
+        assert!(!inner.has(&orphan).unwrap());
+        assert_eq!(bs.pending(), 1);
+    }
+
+    #[test]
+    fn it_flushes_links_in_a_value() {
+        let inner = MemoryBlockstore::new();
+        let bs = BufferedBlockstore::new(&inner);
+
+        let a = bs.put_cbor(&1u64, Code::Blake2b256).unwrap();
+        let b = bs.put_cbor(&2u64, Code::Blake2b256).unwrap();
+        let orphan = bs.put_cbor(&3u64, Code::Blake2b256).unwrap();
+        bs.flush_links(&(a, Some(b), -5i64, 1.5f64)).unwrap();
+        assert!(inner.has(&a).unwrap());
+        assert!(inner.has(&b).unwrap());
+        assert!(!inner.has(&orphan).unwrap());
+    }
+
+    #[test]
+    fn it_rejects_oversized_headers() {
+        let huge = [0xff; 8];
+        // an array of two arrays that each claim u64::MAX items
+        let arrays = [&[0x82, 0x9b][..], &huge, &[0x9b], &huge].concat();
+        assert!(scan_links(&arrays, |_| {}).is_err());
+        // a map whose number of keys and values overflows
+        let map = [&[0xbb][..], &huge].concat();
+        assert!(scan_links(&map, |_| {}).is_err());
+    }
+}
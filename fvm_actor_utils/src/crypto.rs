@@ -0,0 +1,297 @@
+//! Verification of messages signed off-chain, such as permits, vouchers and bridge attestations
+//!
+//! [`verify_digest`] checks a signature over a 32-byte digest by the key behind the signer's
+//! address, accepting secp256k1 (f1), BLS (f3) and Ethereum-style delegated (f410) signers alike.
+//! [`verify_signature`] checks a signature over a whole message, hashing it as the signer's wallet
+//! does before checking the digest the same way.
+//! Secp256k1 signatures are checked by recovering the signing key with
+//! [`Syscalls::recover_secp_public_key`] and BLS signatures with [`Syscalls::verify_bls`]. Fake
+//! syscalls implement both for signatures made by a
+//! [`FakeKey`](crate::syscalls::fake_syscalls::FakeKey), so the same verification runs in tests.
+//!
+//! Ethereum wallets sign typed data as described by EIP-712: the digest commits to a domain
+//! separator, naming the application and the actor verifying the signature, and to the hash of
+//! the typed message. [`Eip712Domain`] and [`struct_hash`] build both, and [`eip712_digest`]
+//! combines them into the digest that is signed.
+use cid::multihash::{Code, MultihashDigest};
+use fvm_shared::address::{Address, Payload};
+use fvm_shared::crypto::signature::{BLS_PUB_LEN, BLS_SIG_LEN, SECP_PUB_LEN, SECP_SIG_LEN};
+use fvm_shared::error::ErrorNumber;
+
+pub use frc42_dispatch::hasher::keccak::keccak256;
+
+use crate::abi::{encode_address, encode_uint64, AbiError, Word, EAM_ACTOR_ID};
+use crate::syscalls::Syscalls;
+
+/// A 32-byte message digest
+pub type Digest = [u8; 32];
+
+/// The type of an [`Eip712Domain`], whose hash is part of its separator
+pub const EIP712_DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+
+/// Checks that `signature` was made over `digest` by the key behind `signer`
+///
+/// Secp256k1 and delegated signers sign the digest itself with a 65-byte `r || s || v` signature,
+/// and BLS signers sign the digest as the message. Delegated signers must have an Ethereum address,
+/// in the namespace of the Ethereum address manager. Returns false for invalid signatures and for
+/// other kinds of address.
+pub fn verify_digest<S: Syscalls + ?Sized>(
+    syscalls: &S,
+    signer: &Address,
+    signature: &[u8],
+    digest: &Digest,
+) -> Result<bool, ErrorNumber> {
+    match signer.payload() {
+        Payload::BLS(public_key) => verify_bls(syscalls, public_key, signature, digest),
+        Payload::Secp256k1(_) => {
+            let key = recover_secp_public_key(syscalls, signature, digest)?;
+            Ok(key.and_then(|key| Address::new_secp256k1(&key).ok()) == Some(*signer))
+        }
+        Payload::Delegated(delegated) if delegated.namespace() == EAM_ACTOR_ID => {
+            let key = recover_secp_public_key(syscalls, signature, digest)?;
+            Ok(key.map(|key| eth_address(&key)) == Some(*signer))
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Checks that `signature` was made over `plaintext` by the key behind `signer`
+///
+/// Secp256k1 (f1) signers sign the blake2b-256 hash of the plaintext and Ethereum-style delegated
+/// (f410) signers its keccak-256 hash, which is checked as by [`verify_digest`]. BLS (f3) signers
+/// sign the plaintext itself. Returns false for invalid signatures and for other kinds of address.
+pub fn verify_signature<S: Syscalls + ?Sized>(
+    syscalls: &S,
+    signer: &Address,
+    signature: &[u8],
+    plaintext: &[u8],
+) -> Result<bool, ErrorNumber> {
+    match signer.payload() {
+        Payload::BLS(public_key) => verify_bls(syscalls, public_key, signature, plaintext),
+        Payload::Secp256k1(_) => verify_digest(syscalls, signer, signature, &blake2b256(plaintext)),
+        Payload::Delegated(_) => verify_digest(syscalls, signer, signature, &keccak256(plaintext)),
+        _ => Ok(false),
+    }
+}
+
+/// Returns the blake2b-256 hash of some data, as signed by secp256k1 (f1) wallets
+pub fn blake2b256(data: &[u8]) -> Digest {
+    // a blake2b-256 digest is always 32 bytes
+    Code::Blake2b256.digest(data).digest().try_into().unwrap()
+}
+
+fn verify_bls<S: Syscalls + ?Sized>(
+    syscalls: &S,
+    public_key: &[u8; BLS_PUB_LEN],
+    signature: &[u8],
+    message: &[u8],
+) -> Result<bool, ErrorNumber> {
+    let Ok(signature) = <&[u8; BLS_SIG_LEN]>::try_from(signature) else {
+        return Ok(false);
+    };
+    syscalls.verify_bls(signature, public_key, message)
+}
+
+/// Recovers the key that made a secp256k1 signature, or None if the signature is invalid
+fn recover_secp_public_key<S: Syscalls + ?Sized>(
+    syscalls: &S,
+    signature: &[u8],
+    digest: &Digest,
+) -> Result<Option<[u8; SECP_PUB_LEN]>, ErrorNumber> {
+    let Ok(signature) = <&[u8; SECP_SIG_LEN]>::try_from(signature) else {
+        return Ok(None);
+    };
+    match syscalls.recover_secp_public_key(digest, signature) {
+        Ok(key) => Ok(Some(key)),
+        Err(ErrorNumber::IllegalArgument) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the Ethereum-style delegated address of a secp256k1 public key
+///
+/// An Ethereum address is the last 20 bytes of the keccak-256 hash of the uncompressed key,
+/// without its `0x04` prefix.
+pub fn eth_address(public_key: &[u8; SECP_PUB_LEN]) -> Address {
+    // an EAM subaddress of 20 bytes is always valid
+    Address::new_delegated(EAM_ACTOR_ID, &keccak256(&public_key[1..])[12..]).unwrap()
+}
+
+/// The domain in which EIP-712 signatures are made, which keeps them from being replayed in
+/// another application, version, chain or actor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Eip712Domain {
+    pub name: String,
+    pub version: String,
+    pub chain_id: u64,
+    /// The actor verifying the signatures, which must have an Ethereum address
+    pub verifying_contract: Address,
+}
+
+impl Eip712Domain {
+    /// Returns the domain separator that is part of every digest signed in the domain
+    pub fn separator(&self) -> Result<Digest, AbiError> {
+        Ok(struct_hash(
+            EIP712_DOMAIN_TYPE,
+            &[
+                keccak256(self.name.as_bytes()),
+                keccak256(self.version.as_bytes()),
+                encode_uint64(self.chain_id),
+                encode_address(&self.verifying_contract)?,
+            ],
+        ))
+    }
+}
+
+/// Hashes a typed struct, given its type as in `Mail(address from,string contents)` and its
+/// fields encoded as ABI words in the same order
+///
+/// Static fields are encoded as for a call, while `string` and `bytes` fields are replaced by
+/// their keccak-256 hash and nested structs by their own struct hash. The type must list the
+/// types of nested structs after its own, as EIP-712 requires.
+pub fn struct_hash(type_: &str, fields: &[Word]) -> Digest {
+    keccak256(&[&keccak256(type_.as_bytes())[..], &fields.concat()].concat())
+}
+
+/// Returns the digest signed for a typed struct in a domain
+pub fn eip712_digest(domain_separator: &Digest, struct_hash: &Digest) -> Digest {
+    keccak256(&[&[0x19, 0x01][..], domain_separator, struct_hash].concat())
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_shared::address::{Address, Payload};
+
+    use super::{
+        blake2b256, eip712_digest, keccak256, struct_hash, verify_digest, verify_signature,
+        Eip712Domain,
+    };
+    use crate::abi::{address_from_eth, encode_address, EAM_ACTOR_ID};
+    use crate::syscalls::fake_syscalls::{FakeKey, FakeSyscalls};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn eth(address: &str) -> Address {
+        let bytes: Vec<u8> = (0..40)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&address[i..i + 2], 16).unwrap())
+            .collect();
+        address_from_eth(&bytes.try_into().unwrap())
+    }
+
+    #[test]
+    fn it_builds_eip712_digests() {
+        // the example given in EIP-712
+        let domain = Eip712Domain {
+            name: "Ether Mail".into(),
+            version: "1".into(),
+            chain_id: 1,
+            verifying_contract: eth("cccccccccccccccccccccccccccccccccccccccc"),
+        };
+        let separator = domain.separator().unwrap();
+        assert_eq!(
+            hex(&separator),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+
+        let person = |name: &str, wallet: &str| {
+            let wallet = encode_address(&eth(wallet)).unwrap();
+            struct_hash("Person(string name,address wallet)", &[keccak256(name.as_bytes()), wallet])
+        };
+        let mail = struct_hash(
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)",
+            &[
+                person("Cow", "cd2a3d9f938e13cd947ec05abc7fe734df8dd826"),
+                person("Bob", "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"),
+                keccak256(b"Hello, Bob!"),
+            ],
+        );
+        assert_eq!(hex(&mail), "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e");
+        assert_eq!(
+            hex(&eip712_digest(&separator, &mail)),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+
+        // the verifying actor must have an Ethereum address
+        let domain = Eip712Domain {
+            verifying_contract: Address::new_secp256k1(&[1; 65]).unwrap(),
+            ..domain
+        };
+        assert!(domain.separator().is_err());
+    }
+
+    #[test]
+    fn it_verifies_signatures_by_every_kind_of_signer() {
+        let syscalls = FakeSyscalls::default();
+        let key = FakeKey::new(b"alice");
+        let digest = keccak256(b"voucher");
+        let other_digest = keccak256(b"other voucher");
+
+        let secp = key.sign_secp(&digest);
+        let bls = key.sign_bls(&digest);
+        for (signer, signature) in [
+            (key.secp_address(), &secp[..]),
+            (key.eth_address(), &secp[..]),
+            (key.bls_address(), &bls[..]),
+        ] {
+            assert!(verify_digest(&syscalls, &signer, signature, &digest).unwrap());
+            assert!(!verify_digest(&syscalls, &signer, signature, &other_digest).unwrap());
+            // truncated signatures are rejected rather than failing
+            let truncated = &signature[..signature.len() - 1];
+            assert!(!verify_digest(&syscalls, &signer, truncated, &digest).unwrap());
+        }
+
+        // signatures by another key don't verify
+        let mallory = FakeKey::new(b"mallory");
+        let forged = mallory.sign_secp(&digest);
+        assert!(!verify_digest(&syscalls, &key.eth_address(), &forged, &digest).unwrap());
+        let forged = mallory.sign_bls(&digest);
+        assert!(!verify_digest(&syscalls, &key.bls_address(), &forged, &digest).unwrap());
+
+        // nor do signatures checked against another address
+        let other = Address::new_delegated(EAM_ACTOR_ID, &[0xff; 20]).unwrap();
+        assert!(!verify_digest(&syscalls, &other, &secp, &digest).unwrap());
+        assert!(!verify_digest(&syscalls, &Address::new_id(1), &secp, &digest).unwrap());
+        // including delegated addresses outside the Ethereum address manager's namespace
+        let foreign = foreign_delegated(&key.eth_address());
+        assert!(!verify_digest(&syscalls, &foreign, &secp, &digest).unwrap());
+    }
+
+    #[test]
+    fn it_verifies_signatures_over_plaintext() {
+        let syscalls = FakeSyscalls::default();
+        let key = FakeKey::new(b"alice");
+        let message = b"permit";
+
+        // each kind of wallet hashes the message its own way before signing
+        let secp = key.sign_secp(&blake2b256(message));
+        let eth = key.sign_secp(&keccak256(message));
+        let bls = key.sign_bls(message);
+        for (signer, signature) in [
+            (key.secp_address(), &secp[..]),
+            (key.eth_address(), &eth[..]),
+            (key.bls_address(), &bls[..]),
+        ] {
+            assert!(verify_signature(&syscalls, &signer, signature, message).unwrap());
+            assert!(!verify_signature(&syscalls, &signer, signature, b"other").unwrap());
+        }
+        assert!(!verify_signature(&syscalls, &key.eth_address(), &secp, message).unwrap());
+        assert!(!verify_signature(&syscalls, &key.secp_address(), &eth, message).unwrap());
+
+        // as on the FVM, only Ethereum-style delegated addresses can sign
+        let foreign = foreign_delegated(&key.eth_address());
+        assert!(!verify_signature(&syscalls, &foreign, &eth, message).unwrap());
+        assert!(!verify_signature(&syscalls, &Address::new_id(1), &eth, message).unwrap());
+    }
+
+    /// Returns the delegated address with the same subaddress in another namespace
+    fn foreign_delegated(eth: &Address) -> Address {
+        let Payload::Delegated(delegated) = eth.payload() else {
+            panic!("expected a delegated address");
+        };
+        Address::new_delegated(EAM_ACTOR_ID + 1, delegated.subaddress()).unwrap()
+    }
+}
@@ -0,0 +1,94 @@
+//! Helpers for building FVM actor events
+//!
+//! Events are a list of key/value entries. By convention the first entry has the key `$type` and
+//! names the event, and the remaining entries hold its fields. Values are CBOR encoded.
+use fvm_ipld_encoding::ser::Serialize;
+use fvm_ipld_encoding::{to_vec, Error as IpldError, CBOR};
+use fvm_shared::event::{ActorEvent, Entry, Flags};
+
+/// The key of the entry naming the event type
+pub const EVENT_TYPE_KEY: &str = "$type";
+
+/// Builds an [`ActorEvent`] from typed fields
+///
+/// Any field that fails to serialize is reported when the event is built.
+#[derive(Debug, Default)]
+pub struct EventBuilder {
+    entries: Vec<Entry>,
+    error: Option<IpldError>,
+}
+
+impl EventBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the type of the event, indexed so that it can be filtered on
+    pub fn typ(self, typ: &str) -> Self {
+        self.push_entry(EVENT_TYPE_KEY, typ, Flags::FLAG_INDEXED_ALL)
+    }
+
+    /// Adds a field that is indexed by key and value
+    pub fn field_indexed<T: Serialize + ?Sized>(self, key: &str, value: &T) -> Self {
+        self.push_entry(key, value, Flags::FLAG_INDEXED_ALL)
+    }
+
+    /// Adds a field that is not indexed
+    pub fn field<T: Serialize + ?Sized>(self, key: &str, value: &T) -> Self {
+        self.push_entry(key, value, Flags::empty())
+    }
+
+    /// Returns the event, or the first error encountered serializing its fields
+    pub fn build(self) -> Result<ActorEvent, IpldError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(ActorEvent { entries: self.entries }),
+        }
+    }
+
+    fn push_entry<T: Serialize + ?Sized>(mut self, key: &str, value: &T, flags: Flags) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        match to_vec(value) {
+            Ok(value) => {
+                self.entries.push(Entry { flags, key: key.to_string(), codec: CBOR, value })
+            }
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::{from_slice, CBOR};
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::event::Flags;
+
+    use super::{EventBuilder, EVENT_TYPE_KEY};
+    use crate::syscalls::fake_syscalls::FakeSyscalls;
+    use crate::util::ActorRuntime;
+
+    #[test]
+    fn it_builds_and_records_events() {
+        let event = EventBuilder::new()
+            .typ("transfer")
+            .field_indexed("from", &1u64)
+            .field("amount", &TokenAmount::from_atto(100))
+            .build()
+            .unwrap();
+
+        let keys: Vec<_> = event.entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec![EVENT_TYPE_KEY, "from", "amount"]);
+        assert!(event.entries.iter().all(|e| e.codec == CBOR));
+        assert_eq!(event.entries[0].flags, Flags::FLAG_INDEXED_ALL);
+        assert_eq!(event.entries[2].flags, Flags::empty());
+        assert_eq!(from_slice::<String>(&event.entries[0].value).unwrap(), "transfer");
+
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        runtime.emit_event(&event).unwrap();
+        assert_eq!(runtime.syscalls.events.borrow().as_slice(), &[event]);
+    }
+}
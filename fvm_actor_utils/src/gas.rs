@@ -0,0 +1,220 @@
+//! An approximate gas model for comparing implementations in unit tests
+//!
+//! The FVM charges gas for IPLD block operations, sends and events, which dominate the cost of
+//! most token operations. A [`GasMeter`] applies a [`PriceTable`] to those operations as they are
+//! made through a [`MeteredBlockstore`] and [`FakeSyscalls`](crate::syscalls::fake_syscalls::FakeSyscalls),
+//! so that tests can check that a change doesn't make an operation more expensive without running
+//! the real FVM. Charges are approximate and only meaningful relative to each other.
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use anyhow::Result;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+
+/// The gas charged for each kind of operation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PriceTable {
+    /// Charged for each block read
+    pub block_read_base: u64,
+    /// Charged per byte of each block read
+    pub block_read_per_byte: u64,
+    /// Charged for each block written
+    pub block_write_base: u64,
+    /// Charged per byte of each block written
+    pub block_write_per_byte: u64,
+    /// Charged for each message sent
+    pub send_base: u64,
+    /// Charged per byte of the parameters of each message sent
+    pub send_per_byte: u64,
+    /// Charged for each event emitted
+    pub event_base: u64,
+    /// Charged per byte of the entries of each event emitted
+    pub event_per_byte: u64,
+}
+
+impl Default for PriceTable {
+    /// Prices roughly in line with those of the FVM as of network version 21
+    fn default() -> Self {
+        Self {
+            block_read_base: 187_000,
+            block_read_per_byte: 10,
+            block_write_base: 310_000,
+            block_write_per_byte: 1_300,
+            send_base: 280_000,
+            send_per_byte: 10,
+            event_base: 40_000,
+            event_per_byte: 1_000,
+        }
+    }
+}
+
+/// Gas charged by a [`GasMeter`], broken down by kind of operation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GasUsage {
+    pub block_reads: u64,
+    pub block_writes: u64,
+    pub sends: u64,
+    pub events: u64,
+}
+
+impl GasUsage {
+    /// Returns the total gas charged
+    pub fn total(&self) -> u64 {
+        self.block_reads + self.block_writes + self.sends + self.events
+    }
+}
+
+/// Accumulates the gas charged for operations according to a [`PriceTable`]
+#[derive(Debug, Default)]
+pub struct GasMeter {
+    prices: PriceTable,
+    usage: RefCell<GasUsage>,
+}
+
+impl GasMeter {
+    pub fn new(prices: PriceTable) -> Self {
+        Self { prices, usage: RefCell::new(GasUsage::default()) }
+    }
+
+    pub fn prices(&self) -> &PriceTable {
+        &self.prices
+    }
+
+    pub fn charge_block_read(&self, len: usize) {
+        let gas = self.prices.block_read_base + self.prices.block_read_per_byte * len as u64;
+        self.usage.borrow_mut().block_reads += gas;
+    }
+
+    pub fn charge_block_write(&self, len: usize) {
+        let gas = self.prices.block_write_base + self.prices.block_write_per_byte * len as u64;
+        self.usage.borrow_mut().block_writes += gas;
+    }
+
+    pub fn charge_send(&self, params_len: usize) {
+        let gas = self.prices.send_base + self.prices.send_per_byte * params_len as u64;
+        self.usage.borrow_mut().sends += gas;
+    }
+
+    pub fn charge_event(&self, len: usize) {
+        let gas = self.prices.event_base + self.prices.event_per_byte * len as u64;
+        self.usage.borrow_mut().events += gas;
+    }
+
+    /// Returns the gas charged since creation or the last reset
+    pub fn usage(&self) -> GasUsage {
+        self.usage.borrow().clone()
+    }
+
+    /// Clears the gas charged so far
+    pub fn reset(&self) {
+        self.usage.take();
+    }
+
+    /// Runs `f`, returning its result along with the gas it was charged
+    ///
+    /// Gas charged before `f` runs is discarded.
+    pub fn measure<R>(&self, f: impl FnOnce() -> R) -> (R, GasUsage) {
+        self.reset();
+        let res = f();
+        (res, self.usage.take())
+    }
+}
+
+/// A wrapper around a blockstore that charges a [`GasMeter`] for the reads and writes made through
+/// it
+///
+/// Reads of blocks that don't exist are charged the base price, as the FVM charges for them too.
+#[derive(Debug, Clone)]
+pub struct MeteredBlockstore<BS: Blockstore> {
+    inner: BS,
+    meter: Rc<GasMeter>,
+}
+
+impl<BS: Blockstore> MeteredBlockstore<BS> {
+    pub fn new(inner: BS, meter: Rc<GasMeter>) -> Self {
+        Self { inner, meter }
+    }
+
+    pub fn meter(&self) -> &Rc<GasMeter> {
+        &self.meter
+    }
+
+    /// Returns the underlying store
+    pub fn inner(&self) -> &BS {
+        &self.inner
+    }
+}
+
+impl<BS: Blockstore> Blockstore for MeteredBlockstore<BS> {
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        let block = self.inner.get(k)?;
+        self.meter.charge_block_read(block.as_ref().map_or(0, Vec::len));
+        Ok(block)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        self.meter.charge_block_write(block.len());
+        self.inner.put_keyed(k, block)
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool> {
+        self.inner.has(k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_ipld_encoding::CborStore;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+
+    use super::{GasUsage, MeteredBlockstore, PriceTable};
+    use crate::syscalls::fake_syscalls::FakeSyscalls;
+    use crate::util::ActorRuntime;
+
+    #[test]
+    fn it_charges_for_blocks_and_sends() {
+        let runtime = ActorRuntime::<FakeSyscalls, MeteredBlockstore<MemoryBlockstore>>::new_metered_test_runtime(
+            PriceTable::default(),
+        );
+        let meter = runtime.blockstore.meter().clone();
+        let prices = meter.prices().clone();
+
+        let (cid, usage) = meter.measure(|| runtime.put_cbor(&"state", Code::Blake2b256).unwrap());
+        assert_eq!(usage.block_writes, prices.block_write_base + prices.block_write_per_byte * 6);
+        assert_eq!(usage.total(), usage.block_writes);
+
+        let (_, usage) = meter.measure(|| runtime.get_cbor::<String>(&cid).unwrap());
+        assert_eq!(usage.block_reads, prices.block_read_base + prices.block_read_per_byte * 6);
+
+        let params = IpldBlock::serialize_cbor(&1u64).unwrap();
+        let (_, usage) = meter.measure(|| {
+            runtime.send(&Address::new_id(2), 2, params, TokenAmount::default()).unwrap()
+        });
+        assert_eq!(
+            usage,
+            GasUsage { sends: prices.send_base + prices.send_per_byte, ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn it_compares_implementations() {
+        let runtime = ActorRuntime::<FakeSyscalls, MeteredBlockstore<MemoryBlockstore>>::new_metered_test_runtime(
+            PriceTable::default(),
+        );
+        let meter = runtime.blockstore.meter().clone();
+
+        // storing two values in one block is cheaper than storing them separately
+        let (_, separate) = meter.measure(|| {
+            runtime.put_cbor(&1u64, Code::Blake2b256).unwrap();
+            runtime.put_cbor(&2u64, Code::Blake2b256).unwrap();
+        });
+        let (_, combined) =
+            meter.measure(|| runtime.put_cbor(&(1u64, 2u64), Code::Blake2b256).unwrap());
+        assert!(combined.total() < separate.total());
+    }
+}
@@ -0,0 +1,41 @@
+//! Types for creating actors through the init actor
+use cid::Cid;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::MethodNum;
+
+/// The address of the init actor
+pub const INIT_ACTOR_ADDR: Address = Address::new_id(1);
+
+/// Creates an actor with an actor (f2) address
+pub const EXEC_METHOD: MethodNum = 2;
+
+/// Creates an actor with both an actor (f2) address and a delegated (f4) address in the namespace
+/// of the calling actor
+pub const EXEC4_METHOD: MethodNum = 3;
+
+/// Parameters for [`EXEC_METHOD`]
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ExecParams {
+    pub code_cid: Cid,
+    pub constructor_params: RawBytes,
+}
+
+/// Parameters for [`EXEC4_METHOD`]
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct Exec4Params {
+    pub code_cid: Cid,
+    pub constructor_params: RawBytes,
+    /// The subaddress of the new actor's delegated address, whose namespace is the caller's ID
+    pub subaddress: RawBytes,
+}
+
+/// The addresses of a newly created actor
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ExecReturn {
+    /// The ID address of the new actor
+    pub id_address: Address,
+    /// The reorg-safe actor (f2) address of the new actor
+    pub robust_address: Address,
+}
@@ -0,0 +1,405 @@
+//! Rendering of actor state as JSON for debugging and off-chain inspection
+//!
+//! State is decoded from its DAG-CBOR blocks directly, guided by a [`Layout`] naming the fields of
+//! tuple-encoded structs and identifying the links to HAMTs and AMTs, whose entries are rendered
+//! in place of their root CIDs. Libraries publish the layouts of their state types (such as
+//! `TokenState::layout()`) so that tools can inspect a state root without loading it through the
+//! library.
+//!
+//! Values are rendered as follows:
+//! - integers, strings, booleans, nulls and floats as their JSON equivalents
+//! - byte strings as hex strings
+//! - links as `{"/": "<cid>"}`, as in DAG-JSON
+//! - maps as objects, with keys that aren't strings rendered as JSON text
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::bigint::{BigInt, Sign};
+use serde_json::{Map, Number, Value};
+use thiserror::Error;
+
+use crate::blockstore::{read_header, take, CID_TAG};
+
+#[derive(Error, Debug)]
+pub enum InspectError {
+    #[error("block {0} not found in blockstore")]
+    MissingBlock(Cid),
+    #[error("blockstore error: {0}")]
+    Blockstore(#[from] anyhow::Error),
+    #[error("invalid DAG-CBOR: {0}")]
+    InvalidCbor(String),
+    #[error("state does not match layout: expected {0}")]
+    LayoutMismatch(&'static str),
+}
+
+type Result<T> = std::result::Result<T, InspectError>;
+
+/// Describes how the encoded state of an actor maps to a readable structure
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Any value, rendered generically without resolving links
+    Cbor,
+    /// A tuple-encoded struct, rendered as an object with the given field names and layouts
+    Struct(Vec<(&'static str, Layout)>),
+    /// A list whose items all have the given layout
+    List(Box<Layout>),
+    /// A link to a block with the given layout
+    Link(Box<Layout>),
+    /// A link to the root of a HAMT, rendered as an object of its entries
+    Hamt { key: KeyFormat, value: Box<Layout> },
+    /// A link to the root of an AMT, rendered as an object of its entries keyed by index
+    Amt(Box<Layout>),
+    /// A `TokenAmount` or other big integer, rendered as a decimal string
+    BigInt,
+}
+
+impl Layout {
+    /// Convenience constructor for a [`Layout::Struct`]
+    pub fn fields<const N: usize>(fields: [(&'static str, Layout); N]) -> Self {
+        Layout::Struct(fields.into())
+    }
+
+    /// Convenience constructor for a [`Layout::Hamt`]
+    pub fn hamt(key: KeyFormat, value: Layout) -> Self {
+        Layout::Hamt { key, value: Box::new(value) }
+    }
+
+    /// Convenience constructor for a [`Layout::Amt`]
+    pub fn amt(value: Layout) -> Self {
+        Layout::Amt(Box::new(value))
+    }
+}
+
+/// The encoding of the keys of a HAMT
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// Keys stored as plain values, such as integers, rendered generically
+    Cbor,
+    /// Actor IDs encoded as varint bytes, rendered as decimal strings
+    ActorId,
+}
+
+/// Renders the state at `root` according to `layout`
+///
+/// `None` values in the state, encoded as null, are rendered as null whatever their layout.
+pub fn inspect<BS: Blockstore>(bs: &BS, root: &Cid, layout: &Layout) -> Result<Value> {
+    render(bs, &load(bs, root)?, layout)
+}
+
+/// Renders a single DAG-CBOR encoded value, leaving any links unresolved
+pub fn cbor_to_json(data: &[u8]) -> Result<Value> {
+    Ok(to_json(&decode(data)?))
+}
+
+/// A decoded DAG-CBOR value
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Int(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    List(Vec<Node>),
+    Map(Vec<(Node, Node)>),
+    Link(Cid),
+    Bool(bool),
+    Null,
+    Float(f64),
+}
+
+fn load<BS: Blockstore>(bs: &BS, cid: &Cid) -> Result<Node> {
+    let block = bs.get(cid)?.ok_or(InspectError::MissingBlock(*cid))?;
+    decode(&block)
+}
+
+fn decode(mut data: &[u8]) -> Result<Node> {
+    let node = decode_item(&mut data)?;
+    if !data.is_empty() {
+        return Err(InspectError::InvalidCbor("trailing data after value".into()));
+    }
+    Ok(node)
+}
+
+fn decode_item(data: &mut &[u8]) -> Result<Node> {
+    let invalid = |e: anyhow::Error| InspectError::InvalidCbor(e.to_string());
+    let (major, value) = read_header(data).map_err(invalid)?;
+    let node = match major {
+        0 => Node::Int(value as i128),
+        1 => Node::Int(-1 - value as i128),
+        2 => Node::Bytes(take(data, value).map_err(invalid)?.to_vec()),
+        3 => {
+            let bytes = take(data, value).map_err(invalid)?;
+            let text = std::str::from_utf8(bytes)
+                .map_err(|_| InspectError::InvalidCbor("invalid UTF-8 in string".into()))?;
+            Node::Text(text.to_string())
+        }
+        4 => Node::List((0..value).map(|_| decode_item(data)).collect::<Result<_>>()?),
+        5 => Node::Map(
+            (0..value)
+                .map(|_| Ok((decode_item(data)?, decode_item(data)?)))
+                .collect::<Result<_>>()?,
+        ),
+        6 if value == CID_TAG => match decode_item(data)? {
+            Node::Bytes(bytes) if bytes.first() == Some(&0) => Node::Link(
+                Cid::try_from(&bytes[1..]).map_err(|e| InspectError::InvalidCbor(e.to_string()))?,
+            ),
+            _ => return Err(InspectError::InvalidCbor("invalid CID".into())),
+        },
+        6 => return Err(InspectError::InvalidCbor(format!("unsupported tag {value}"))),
+        _ => match value {
+            20 => Node::Bool(false),
+            21 => Node::Bool(true),
+            22 => Node::Null,
+            // DAG-CBOR only allows 64-bit floats
+            bits => Node::Float(f64::from_bits(bits)),
+        },
+    };
+    Ok(node)
+}
+
+fn to_json(node: &Node) -> Value {
+    match node {
+        Node::Int(i) => match (i64::try_from(*i), u64::try_from(*i)) {
+            (Ok(i), _) => Value::from(i),
+            (_, Ok(u)) => Value::from(u),
+            _ => Value::String(i.to_string()),
+        },
+        Node::Bytes(bytes) => Value::String(hex(bytes)),
+        Node::Text(text) => Value::String(text.clone()),
+        Node::List(items) => Value::Array(items.iter().map(to_json).collect()),
+        Node::Map(entries) => {
+            Value::Object(entries.iter().map(|(k, v)| (key_to_string(k), to_json(v))).collect())
+        }
+        Node::Link(cid) => link_to_json(cid),
+        Node::Bool(b) => Value::Bool(*b),
+        Node::Null => Value::Null,
+        Node::Float(f) => Number::from_f64(*f).map_or(Value::Null, Value::Number),
+    }
+}
+
+fn link_to_json(cid: &Cid) -> Value {
+    let mut map = Map::new();
+    map.insert("/".into(), Value::String(cid.to_string()));
+    Value::Object(map)
+}
+
+fn key_to_string(key: &Node) -> String {
+    match key {
+        Node::Text(text) => text.clone(),
+        other => to_json(other).to_string(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn render<BS: Blockstore>(bs: &BS, node: &Node, layout: &Layout) -> Result<Value> {
+    if *node == Node::Null {
+        return Ok(Value::Null);
+    }
+    match layout {
+        Layout::Cbor => Ok(to_json(node)),
+        Layout::Struct(fields) => match node {
+            Node::List(items) if items.len() == fields.len() => {
+                let mut map = Map::new();
+                for ((name, layout), item) in fields.iter().zip(items) {
+                    map.insert(name.to_string(), render(bs, item, layout)?);
+                }
+                Ok(Value::Object(map))
+            }
+            _ => Err(InspectError::LayoutMismatch("a struct of the same number of fields")),
+        },
+        Layout::List(layout) => match node {
+            Node::List(items) => Ok(Value::Array(
+                items.iter().map(|i| render(bs, i, layout)).collect::<Result<_>>()?,
+            )),
+            _ => Err(InspectError::LayoutMismatch("a list")),
+        },
+        Layout::Link(layout) => render(bs, &load(bs, as_link(node)?)?, layout),
+        Layout::Hamt { key, value } => {
+            let mut entries = Map::new();
+            walk_hamt(bs, &load(bs, as_link(node)?)?, &mut |k, v| {
+                entries.insert(render_key(k, *key)?, render(bs, v, value)?);
+                Ok(())
+            })?;
+            Ok(Value::Object(entries))
+        }
+        Layout::Amt(value) => {
+            let mut entries = Map::new();
+            walk_amt(bs, &load(bs, as_link(node)?)?, &mut |index, v| {
+                entries.insert(index.to_string(), render(bs, v, value)?);
+                Ok(())
+            })?;
+            Ok(Value::Object(entries))
+        }
+        Layout::BigInt => match node {
+            // big integers are a sign byte followed by the big-endian magnitude, empty for zero
+            Node::Bytes(bytes) => {
+                let value = match bytes.split_first() {
+                    None => BigInt::default(),
+                    Some((0, magnitude)) => BigInt::from_bytes_be(Sign::Plus, magnitude),
+                    Some((1, magnitude)) => BigInt::from_bytes_be(Sign::Minus, magnitude),
+                    Some(_) => return Err(InspectError::LayoutMismatch("a big integer")),
+                };
+                Ok(Value::String(value.to_string()))
+            }
+            _ => Err(InspectError::LayoutMismatch("a big integer")),
+        },
+    }
+}
+
+fn as_link(node: &Node) -> Result<&Cid> {
+    match node {
+        Node::Link(cid) => Ok(cid),
+        _ => Err(InspectError::LayoutMismatch("a link")),
+    }
+}
+
+fn render_key(key: &Node, format: KeyFormat) -> Result<String> {
+    match (format, key) {
+        (KeyFormat::Cbor, key) => Ok(key_to_string(key)),
+        (KeyFormat::ActorId, Node::Bytes(bytes)) => {
+            let id = decode_varint(bytes).ok_or(InspectError::LayoutMismatch("a varint key"))?;
+            Ok(id.to_string())
+        }
+        _ => Err(InspectError::LayoutMismatch("a varint key")),
+    }
+}
+
+fn decode_varint(bytes: &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return (i + 1 == bytes.len()).then_some(value);
+        }
+    }
+    None
+}
+
+/// Calls `f` with each key and value of a HAMT node and its children
+///
+/// Nodes are a bitfield followed by a list of pointers, each either a link to a child node or a
+/// bucket of key-value pairs.
+fn walk_hamt<BS: Blockstore>(
+    bs: &BS,
+    node: &Node,
+    f: &mut impl FnMut(&Node, &Node) -> Result<()>,
+) -> Result<()> {
+    let pointers = match node {
+        Node::List(fields) if fields.len() == 2 => match &fields[1] {
+            Node::List(pointers) => pointers,
+            _ => return Err(InspectError::LayoutMismatch("a HAMT node")),
+        },
+        _ => return Err(InspectError::LayoutMismatch("a HAMT node")),
+    };
+    for pointer in pointers {
+        match pointer {
+            Node::Link(cid) => walk_hamt(bs, &load(bs, cid)?, f)?,
+            Node::List(bucket) => {
+                for pair in bucket {
+                    match pair {
+                        Node::List(kv) if kv.len() == 2 => f(&kv[0], &kv[1])?,
+                        _ => return Err(InspectError::LayoutMismatch("a HAMT key-value pair")),
+                    }
+                }
+            }
+            _ => return Err(InspectError::LayoutMismatch("a HAMT pointer")),
+        }
+    }
+    Ok(())
+}
+
+/// Calls `f` with each index and value of an AMT, in index order
+///
+/// The root holds the bit width, height and count of the AMT followed by the top node. Each node
+/// is a bitmap of occupied slots followed by the links to its children and the values it holds,
+/// only one of which is non-empty depending on whether the node is a leaf.
+fn walk_amt<BS: Blockstore>(
+    bs: &BS,
+    root: &Node,
+    f: &mut impl FnMut(u64, &Node) -> Result<()>,
+) -> Result<()> {
+    match root {
+        Node::List(fields) if fields.len() == 4 => match (&fields[0], &fields[1]) {
+            (Node::Int(bit_width), Node::Int(height)) if (1..=18).contains(bit_width) => {
+                walk_amt_node(bs, &fields[3], *bit_width as u32, *height as u32, 0, f)
+            }
+            _ => Err(InspectError::LayoutMismatch("an AMT root")),
+        },
+        _ => Err(InspectError::LayoutMismatch("an AMT root")),
+    }
+}
+
+fn walk_amt_node<BS: Blockstore>(
+    bs: &BS,
+    node: &Node,
+    bit_width: u32,
+    height: u32,
+    offset: u64,
+    f: &mut impl FnMut(u64, &Node) -> Result<()>,
+) -> Result<()> {
+    let (bitmap, links, values) = match node {
+        Node::List(fields) if fields.len() == 3 => match (&fields[0], &fields[1], &fields[2]) {
+            (Node::Bytes(bitmap), Node::List(links), Node::List(values)) => (bitmap, links, values),
+            _ => return Err(InspectError::LayoutMismatch("an AMT node")),
+        },
+        _ => return Err(InspectError::LayoutMismatch("an AMT node")),
+    };
+    let slots = (0..1u64 << bit_width)
+        .filter(|i| bitmap.get((i / 8) as usize).map_or(false, |byte| byte & (1 << (i % 8)) != 0));
+    if height == 0 {
+        for (slot, value) in slots.zip(values) {
+            f(offset + slot, value)?;
+        }
+    } else {
+        let slot_size = 1u64
+            .checked_shl(bit_width * height)
+            .ok_or(InspectError::LayoutMismatch("an AMT of valid height"))?;
+        for (slot, link) in slots.zip(links) {
+            let child = load(bs, as_link(link)?)?;
+            walk_amt_node(bs, &child, bit_width, height - 1, offset + slot * slot_size, f)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::{to_vec, CborStore, RawBytes};
+    use serde_json::json;
+
+    use super::{cbor_to_json, inspect, InspectError, Layout};
+
+    #[test]
+    fn it_renders_arbitrary_cbor() {
+        let bs = MemoryBlockstore::new();
+        let leaf = bs.put_cbor(&"leaf", Code::Blake2b256).unwrap();
+        let data =
+            to_vec(&(1u64, -2i64, "text", RawBytes::new(vec![0xab]), vec![leaf], ())).unwrap();
+        assert_eq!(
+            cbor_to_json(&data).unwrap(),
+            json!([1, -2, "text", "ab", [{ "/": leaf.to_string() }], null])
+        );
+    }
+
+    #[test]
+    fn it_renders_structs_and_resolves_links() {
+        let bs = MemoryBlockstore::new();
+        let leaf = bs.put_cbor(&(7u64, "leaf"), Code::Blake2b256).unwrap();
+        let root = bs.put_cbor(&(leaf, Option::<u64>::None), Code::Blake2b256).unwrap();
+
+        let leaf_layout = Layout::fields([("count", Layout::Cbor), ("name", Layout::Cbor)]);
+        let layout = Layout::fields([
+            ("leaf", Layout::Link(Box::new(leaf_layout.clone()))),
+            ("optional", leaf_layout),
+        ]);
+        assert_eq!(
+            inspect(&bs, &root, &layout).unwrap(),
+            json!({ "leaf": { "count": 7, "name": "leaf" }, "optional": null })
+        );
+
+        let wrong = Layout::fields([("only", Layout::Cbor)]);
+        assert!(matches!(inspect(&bs, &root, &wrong), Err(InspectError::LayoutMismatch(_))));
+    }
+}
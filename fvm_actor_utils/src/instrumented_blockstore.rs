@@ -0,0 +1,142 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use anyhow::Result;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+
+/// Counts of the blockstore operations made while an [`InstrumentedBlockstore`] was in use
+///
+/// Writes are what dominate the gas cost of state changes, so tests can assert on these to catch
+/// operations that start touching more blocks than expected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockstoreStats {
+    /// Number of calls to `get`, including those for blocks that weren't found
+    pub reads: usize,
+    /// Number of blocks written
+    pub writes: usize,
+    /// Total size of the blocks returned by `get`
+    pub bytes_read: usize,
+    /// Total size of the blocks written
+    pub bytes_written: usize,
+    /// Number of distinct CIDs read
+    pub unique_reads: usize,
+    /// Number of distinct CIDs written
+    pub unique_writes: usize,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    reads: usize,
+    writes: usize,
+    bytes_read: usize,
+    bytes_written: usize,
+    read_cids: HashSet<Cid>,
+    written_cids: HashSet<Cid>,
+}
+
+/// A wrapper around a blockstore that records the reads and writes made through it
+#[derive(Debug, Default)]
+pub struct InstrumentedBlockstore<BS: Blockstore> {
+    inner: BS,
+    counters: RefCell<Counters>,
+}
+
+impl<BS: Blockstore> InstrumentedBlockstore<BS> {
+    pub fn new(inner: BS) -> Self {
+        Self { inner, counters: RefCell::new(Counters::default()) }
+    }
+
+    /// Returns the operations recorded since creation or the last reset
+    pub fn stats(&self) -> BlockstoreStats {
+        let counters = self.counters.borrow();
+        BlockstoreStats {
+            reads: counters.reads,
+            writes: counters.writes,
+            bytes_read: counters.bytes_read,
+            bytes_written: counters.bytes_written,
+            unique_reads: counters.read_cids.len(),
+            unique_writes: counters.written_cids.len(),
+        }
+    }
+
+    /// Clears the recorded operations
+    pub fn reset(&self) {
+        self.counters.take();
+    }
+
+    /// Returns the operations recorded so far and clears them
+    pub fn take_stats(&self) -> BlockstoreStats {
+        let stats = self.stats();
+        self.reset();
+        stats
+    }
+
+    /// Runs `f`, returning its result along with the operations it made on this blockstore
+    ///
+    /// Operations recorded before `f` runs are discarded.
+    pub fn measure<R>(&self, f: impl FnOnce() -> R) -> (R, BlockstoreStats) {
+        self.reset();
+        let res = f();
+        (res, self.take_stats())
+    }
+
+    /// Returns the underlying store
+    pub fn inner(&self) -> &BS {
+        &self.inner
+    }
+}
+
+impl<BS: Blockstore> Blockstore for InstrumentedBlockstore<BS> {
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        let block = self.inner.get(k)?;
+        let mut counters = self.counters.borrow_mut();
+        counters.reads += 1;
+        counters.bytes_read += block.as_ref().map_or(0, Vec::len);
+        counters.read_cids.insert(*k);
+        Ok(block)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        self.inner.put_keyed(k, block)?;
+        let mut counters = self.counters.borrow_mut();
+        counters.writes += 1;
+        counters.bytes_written += block.len();
+        counters.written_cids.insert(*k);
+        Ok(())
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool> {
+        self.inner.has(k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::CborStore;
+
+    use super::{BlockstoreStats, InstrumentedBlockstore};
+
+    #[test]
+    fn it_records_operations() {
+        let bs = InstrumentedBlockstore::new(MemoryBlockstore::new());
+        let cid = bs.put_cbor(&"block", Code::Blake2b256).unwrap();
+        bs.put_cbor(&"block", Code::Blake2b256).unwrap();
+        let stats = bs.stats();
+        assert_eq!(stats.writes, 2);
+        assert_eq!(stats.unique_writes, 1);
+        assert!(stats.bytes_written > 0);
+        assert_eq!(stats.reads, 0);
+
+        let (value, stats) = bs.measure(|| bs.get_cbor::<String>(&cid).unwrap().unwrap());
+        assert_eq!(value, "block");
+        assert_eq!(stats.reads, 1);
+        assert_eq!(stats.unique_reads, 1);
+        assert!(stats.bytes_read > 0);
+        assert_eq!(stats.writes, 0);
+
+        assert_eq!(bs.take_stats(), BlockstoreStats::default());
+    }
+}
@@ -1,8 +1,21 @@
+pub mod abi;
+#[cfg(feature = "use_sdk")]
 pub mod actor;
+pub mod addresses;
 pub mod blockstore;
+pub mod car;
+pub mod crypto;
+pub mod events;
+pub mod gas;
+pub mod init;
+pub mod inspect;
 pub mod messaging;
 pub mod receiver;
+pub mod reentrancy;
+pub mod send_queue;
 
+pub mod instrumented_blockstore;
 pub mod shared_blockstore;
 pub mod syscalls;
+pub mod transaction;
 pub mod util;
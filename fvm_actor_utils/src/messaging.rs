@@ -1,8 +1,12 @@
 use frc42_dispatch::method_hash;
+use fvm_ipld_encoding::de::DeserializeOwned;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
-use fvm_ipld_encoding::Error as IpldError;
-use fvm_sdk::{send, sys::ErrorNumber};
-use fvm_shared::error::ExitCode;
+use fvm_ipld_encoding::ser::Serialize;
+use fvm_ipld_encoding::{from_slice, Error as IpldError};
+#[cfg(feature = "use_sdk")]
+use fvm_sdk::send;
+use fvm_shared::error::{ErrorNumber, ExitCode};
+#[cfg(feature = "use_sdk")]
 use fvm_shared::sys::SendFlags;
 use fvm_shared::{address::Address, econ::TokenAmount};
 use fvm_shared::{MethodNum, Response};
@@ -20,6 +24,10 @@ pub enum MessagingError {
     AddressNotInitialized(Address),
     #[error("ipld serialization error: `{0}`")]
     Ipld(#[from] IpldError),
+    #[error("message to `{address}` aborted with exit code `{exit_code}`")]
+    Aborted { address: Address, exit_code: ExitCode },
+    #[error("failed to decode return value from `{address}`: `{source}`")]
+    InvalidReturn { address: Address, source: IpldError },
 }
 
 impl From<&MessagingError> for ExitCode {
@@ -42,6 +50,8 @@ impl From<&MessagingError> for ExitCode {
                 ExitCode::USR_NOT_FOUND
             }
             MessagingError::Ipld(_) => ExitCode::USR_SERIALIZATION,
+            MessagingError::Aborted { address: _, exit_code } => *exit_code,
+            MessagingError::InvalidReturn { .. } => ExitCode::USR_SERIALIZATION,
         }
     }
 }
@@ -56,15 +66,47 @@ pub trait Messaging {
         params: Option<IpldBlock>,
         value: TokenAmount,
     ) -> Result<Response>;
+
+    /// Sends a message with CBOR encoded parameters and decodes its return value
+    ///
+    /// A message that aborts is reported as MessagingError::Aborted and a return value that can't
+    /// be decoded as MessagingError::InvalidReturn, distinguishing a failed call from one that
+    /// succeeded but returned something unexpected. A call without return data is decoded as a
+    /// CBOR null, so `R` may be `()` or an `Option` for methods that return nothing.
+    fn send_typed<P: Serialize, R: DeserializeOwned>(
+        &self,
+        to: &Address,
+        method: MethodNum,
+        params: &P,
+        value: TokenAmount,
+    ) -> Result<R>
+    where
+        Self: Sized,
+    {
+        let ret = self.send(to, method, IpldBlock::serialize_cbor(params)?, value)?;
+        if !ret.exit_code.is_success() {
+            return Err(MessagingError::Aborted { address: *to, exit_code: ret.exit_code });
+        }
+        let decoded = match ret.return_data {
+            Some(data) => data.deserialize(),
+            None => from_slice(&CBOR_NULL),
+        };
+        decoded.map_err(|source| MessagingError::InvalidReturn { address: *to, source })
+    }
 }
 
+/// The CBOR encoding of null
+const CBOR_NULL: [u8; 1] = [0xf6];
+
 /// This method number comes from taking the name as "Receive" and applying
 /// the transformation described in [FRC-0042](https://github.com/filecoin-project/FIPs/blob/master/FRCs/frc-0042.md)
 pub const RECEIVER_HOOK_METHOD_NUM: u64 = method_hash!("Receive");
 
+#[cfg(feature = "use_sdk")]
 #[derive(Debug, Default, Clone, Copy)]
 pub struct FvmMessenger {}
 
+#[cfg(feature = "use_sdk")]
 impl Messaging for FvmMessenger {
     fn send(
         &self,
@@ -9,6 +9,10 @@ use thiserror::Error;
 
 use crate::messaging::{Messaging, MessagingError, RECEIVER_HOOK_METHOD_NUM};
 
+pub mod universal;
+
+pub use universal::{ReceivePolicy, UniversalReceiver};
+
 /// Parameters for universal receiver
 ///
 /// Actual payload varies with asset type
@@ -21,17 +25,6 @@ pub struct UniversalReceiverParams {
     pub payload: RawBytes,
 }
 
-/// Standard interface for an actor that wishes to receive FRC-0046 tokens or other assets
-pub trait UniversalReceiver {
-    /// Invoked by a token actor during pending transfer or mint to the receiver's address
-    ///
-    /// Within this hook, the token actor has optimistically persisted the new balance so
-    /// the receiving actor can immediately utilise the received funds. If the receiver wishes to
-    /// reject the incoming transfer, this function should abort which will cause the token actor
-    /// to rollback the transaction.
-    fn receive(params: UniversalReceiverParams);
-}
-
 /// Type of asset received - could be tokens (FRC46 or other) or other assets
 pub type ReceiverType = u32;
 
@@ -45,8 +38,25 @@ pub enum ReceiverHookError {
     IpldEncoding(#[from] fvm_ipld_encoding::Error),
     #[error("error sending message")]
     Messaging(#[from] MessagingError),
-    #[error("receiver hook error from {address:?}: exit_code={exit_code:?}, return_data={return_data:?}")]
-    Receiver { address: Address, exit_code: ExitCode, return_data: RawBytes },
+    #[error(transparent)]
+    Receiver(#[from] ReceiverAbort),
+}
+
+/// Details of a receiver hook call that aborted
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[error("receiver hook error from {address:?}: exit_code={exit_code:?}, message={message:?}, return_data={return_data:?}")]
+pub struct ReceiverAbort {
+    /// The address of the receiver
+    pub address: Address,
+    /// The exit code the receiver aborted with
+    pub exit_code: ExitCode,
+    /// The reason for the abort, if the receiver returned it as a CBOR string
+    ///
+    /// The FVM doesn't pass the abort message on to the caller, so a receiver wishing to explain
+    /// why it rejected the tokens must do so in its return data.
+    pub message: Option<String>,
+    /// The data returned by the receiver
+    pub return_data: RawBytes,
 }
 
 impl ReceiverHookError {
@@ -56,10 +66,20 @@ impl ReceiverHookError {
         exit_code: ExitCode,
         return_data: Option<IpldBlock>,
     ) -> Self {
-        Self::Receiver {
+        let message = return_data.as_ref().and_then(|b| b.deserialize::<String>().ok());
+        Self::Receiver(ReceiverAbort {
             address,
             exit_code,
+            message,
             return_data: return_data.map_or(RawBytes::default(), |b| RawBytes::new(b.data)),
+        })
+    }
+
+    /// Returns the details of the abort if the receiver rejected the call
+    pub fn abort(&self) -> Option<&ReceiverAbort> {
+        match self {
+            Self::Receiver(abort) => Some(abort),
+            _ => None,
         }
     }
 }
@@ -71,7 +91,7 @@ impl From<&ReceiverHookError> for ExitCode {
                 ExitCode::USR_ASSERTION_FAILED
             }
             ReceiverHookError::IpldEncoding(_) => ExitCode::USR_SERIALIZATION,
-            ReceiverHookError::Receiver { address: _, return_data: _, exit_code } => *exit_code,
+            ReceiverHookError::Receiver(abort) => abort.exit_code,
             ReceiverHookError::Messaging(e) => e.into(),
         }
     }
@@ -130,36 +150,193 @@ impl<T: RecipientData> ReceiverHook<T> {
 
         self.called = true;
 
-        let params = UniversalReceiverParams {
-            type_: self.token_type,
-            payload: mem::take(&mut self.token_params), // once encoded and sent, we don't need this anymore
-        };
-
-        let ret = msg.send(
-            &self.address,
-            RECEIVER_HOOK_METHOD_NUM,
-            IpldBlock::serialize_cbor(&params).map_err(|e| {
-                ReceiverHookError::IpldEncoding(fvm_ipld_encoding::Error {
-                    description: e.to_string(),
-                    protocol: fvm_ipld_encoding::CodecProtocol::Cbor,
-                })
-            })?,
-            TokenAmount::zero(),
-        )?;
-
-        match ret.exit_code {
-            ExitCode::OK => {
-                self.result_data.as_mut().unwrap().set_recipient_data(
-                    ret.return_data.map_or(RawBytes::default(), |b| RawBytes::new(b.data)),
-                );
-                Ok(self.result_data.take().unwrap())
+        // once encoded and sent, we don't need the params anymore
+        let payload = mem::take(&mut self.token_params);
+        let data = call_receiver(msg, &self.address, self.token_type, payload)?;
+        self.result_data.as_mut().unwrap().set_recipient_data(data);
+        Ok(self.result_data.take().unwrap())
+    }
+}
+
+/// Sends a payload to the receiver hook of an actor, returning the data it returned
+fn call_receiver(
+    msg: &dyn Messaging,
+    address: &Address,
+    token_type: ReceiverType,
+    payload: RawBytes,
+) -> std::result::Result<RawBytes, ReceiverHookError> {
+    let params = UniversalReceiverParams { type_: token_type, payload };
+
+    let ret = msg.send(
+        address,
+        RECEIVER_HOOK_METHOD_NUM,
+        IpldBlock::serialize_cbor(&params).map_err(|e| {
+            ReceiverHookError::IpldEncoding(fvm_ipld_encoding::Error {
+                description: e.to_string(),
+                protocol: fvm_ipld_encoding::CodecProtocol::Cbor,
+            })
+        })?,
+        TokenAmount::zero(),
+    )?;
+
+    match ret.exit_code {
+        ExitCode::OK => Ok(ret.return_data.map_or(RawBytes::default(), |b| RawBytes::new(b.data))),
+        abort_code => {
+            Err(ReceiverHookError::new_receiver_error(*address, abort_code, ret.return_data))
+        }
+    }
+}
+
+/// A set of receiver hooks to be called together, such as those returned by a batch mint
+///
+/// Like [`ReceiverHook`], the batch will panic if dropped while holding hooks that haven't been
+/// called.
+#[derive(Debug)]
+pub struct ReceiverHookBatch<T: RecipientData> {
+    hooks: Vec<ReceiverHook<T>>,
+}
+
+impl<T: RecipientData> Default for ReceiverHookBatch<T> {
+    fn default() -> Self {
+        Self { hooks: Vec::new() }
+    }
+}
+
+impl<T: RecipientData> ReceiverHookBatch<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a hook to be called with the rest of the batch
+    pub fn push(&mut self, hook: ReceiverHook<T>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Calls each hook in the order they were added, returning the result of each call
+    ///
+    /// All hooks are called even if some of them abort.
+    pub fn call_each(
+        &mut self,
+        msg: &dyn Messaging,
+    ) -> Vec<std::result::Result<T, ReceiverHookError>> {
+        self.hooks.drain(..).map(|mut hook| hook.call(msg)).collect()
+    }
+
+    /// Calls each hook in order, stopping at the first one that fails
+    ///
+    /// Hooks after the failing one are discarded without being called, as the caller is expected
+    /// to abort and roll back the whole batch.
+    pub fn call_all(
+        &mut self,
+        msg: &dyn Messaging,
+    ) -> std::result::Result<Vec<T>, ReceiverHookError> {
+        self.try_call_all(|hook| hook.call(msg))
+    }
+
+    /// Calls each hook in order through `call`, stopping at the first one that fails
+    ///
+    /// Like [`call_all`](Self::call_all), but lets the caller do work around each call, such as
+    /// committing state before it and reloading state changed by the recipient after it.
+    pub fn try_call_all<E, F>(&mut self, mut call: F) -> std::result::Result<Vec<T>, E>
+    where
+        F: FnMut(&mut ReceiverHook<T>) -> std::result::Result<T, E>,
+    {
+        let mut results = Vec::with_capacity(self.hooks.len());
+        let mut hooks = self.hooks.drain(..);
+        for mut hook in hooks.by_ref() {
+            match call(&mut hook) {
+                Ok(data) => results.push(data),
+                Err(e) => {
+                    // `call` may have failed before calling the hook
+                    hook.called = true;
+                    hooks.for_each(|mut hook| hook.called = true);
+                    return Err(e);
+                }
             }
-            abort_code => Err(ReceiverHookError::new_receiver_error(
-                self.address,
-                abort_code,
-                ret.return_data,
-            )),
         }
+        Ok(results)
+    }
+
+    /// Drops the hooks without calling them
+    ///
+    /// For use when the operation that produced the hooks is rolled back, so the recipients must
+    /// not be notified.
+    pub fn discard(&mut self) {
+        self.hooks.drain(..).for_each(|mut hook| hook.called = true);
+    }
+
+    /// Makes one call to each recipient, merging the payloads of hooks with the same address and
+    /// token type
+    ///
+    /// `merge` combines the payloads of the hooks in a group, in the order they were added, into
+    /// the payload sent to the recipient. Groups are called in the order their first hook was
+    /// added and the data returned from each call is given to every hook in the group. Returns the
+    /// address and result of each call.
+    pub fn call_coalesced<F>(
+        &mut self,
+        msg: &dyn Messaging,
+        mut merge: F,
+    ) -> Vec<(Address, std::result::Result<Vec<T>, ReceiverHookError>)>
+    where
+        F: FnMut(ReceiverType, Vec<RawBytes>) -> std::result::Result<RawBytes, ReceiverHookError>,
+    {
+        let mut groups: Vec<Vec<ReceiverHook<T>>> = Vec::new();
+        for hook in self.hooks.drain(..) {
+            match groups.iter_mut().find(|group| {
+                group[0].address == hook.address && group[0].token_type == hook.token_type
+            }) {
+                Some(group) => group.push(hook),
+                None => groups.push(vec![hook]),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|mut group| {
+                let address = group[0].address;
+                let token_type = group[0].token_type;
+                let payloads = group
+                    .iter_mut()
+                    .map(|hook| {
+                        hook.called = true;
+                        mem::take(&mut hook.token_params)
+                    })
+                    .collect();
+                let result = merge(token_type, payloads)
+                    .and_then(|payload| call_receiver(msg, &address, token_type, payload))
+                    .map(|data| {
+                        group
+                            .iter_mut()
+                            .map(|hook| {
+                                let mut result_data = hook.result_data.take().unwrap();
+                                result_data.set_recipient_data(data.clone());
+                                result_data
+                            })
+                            .collect()
+                    });
+                (address, result)
+            })
+            .collect()
+    }
+}
+
+impl<T: RecipientData> Extend<ReceiverHook<T>> for ReceiverHookBatch<T> {
+    fn extend<I: IntoIterator<Item = ReceiverHook<T>>>(&mut self, iter: I) {
+        self.hooks.extend(iter);
+    }
+}
+
+impl<T: RecipientData> FromIterator<ReceiverHook<T>> for ReceiverHookBatch<T> {
+    fn from_iter<I: IntoIterator<Item = ReceiverHook<T>>>(iter: I) -> Self {
+        Self { hooks: iter.into_iter().collect() }
     }
 }
 
@@ -179,26 +356,38 @@ impl<T: RecipientData> std::ops::Drop for ReceiverHook<T> {
 mod test {
     use frc42_dispatch::method_hash;
     use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
     use fvm_ipld_encoding::RawBytes;
     use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
 
-    use super::{ReceiverHook, RecipientData};
+    use super::{ReceiverHook, ReceiverHookBatch, ReceiverHookError, RecipientData};
     use crate::{syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime};
 
     const ALICE: Address = Address::new_id(2);
+    const BOB: Address = Address::new_id(3);
 
-    struct TestReturn;
+    #[derive(Default)]
+    struct TestReturn {
+        data: RawBytes,
+    }
 
     impl RecipientData for TestReturn {
-        fn set_recipient_data(&mut self, _data: RawBytes) {}
+        fn set_recipient_data(&mut self, data: RawBytes) {
+            self.data = data;
+        }
     }
 
     fn generate_hook() -> ReceiverHook<TestReturn> {
+        hook_with_payload(ALICE, vec![])
+    }
+
+    fn hook_with_payload(address: Address, payload: Vec<u8>) -> ReceiverHook<TestReturn> {
         ReceiverHook::new(
-            ALICE,
-            RawBytes::default(),
+            address,
+            RawBytes::new(payload),
             method_hash!("TestToken") as u32,
-            TestReturn {},
+            TestReturn::default(),
         )
     }
 
@@ -217,4 +406,108 @@ mod test {
         let mut _hook = generate_hook();
         // _hook should panic when dropped as we haven't called the hook
     }
+
+    #[test]
+    fn calls_each_hook_in_batch() {
+        let util = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut batch: ReceiverHookBatch<TestReturn> =
+            [hook_with_payload(ALICE, vec![1]), hook_with_payload(BOB, vec![2])]
+                .into_iter()
+                .collect();
+        batch.push(hook_with_payload(ALICE, vec![3]));
+        assert_eq!(batch.len(), 3);
+
+        util.syscalls.abort_next_send.replace(true);
+        let results = batch.call_each(&util);
+        assert!(batch.is_empty());
+        assert!(matches!(results[0], Err(ReceiverHookError::Messaging(_))));
+        assert!(results[1].is_ok());
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn stops_batch_at_first_failure() {
+        let util = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut batch = ReceiverHookBatch::new();
+        batch.extend([hook_with_payload(ALICE, vec![1]), hook_with_payload(BOB, vec![2])]);
+        assert_eq!(batch.call_all(&util).unwrap().len(), 2);
+
+        batch.extend([hook_with_payload(ALICE, vec![1]), hook_with_payload(BOB, vec![2])]);
+        util.syscalls.abort_next_send.replace(true);
+        assert!(batch.call_all(&util).is_err());
+        // the hook that wasn't called is discarded without panicking
+        assert!(batch.is_empty());
+
+        batch.extend([hook_with_payload(ALICE, vec![1]), hook_with_payload(BOB, vec![2])]);
+        let mut calls = 0;
+        let err = batch
+            .try_call_all(|hook| {
+                calls += 1;
+                if calls == 2 {
+                    return Err("failed before calling");
+                }
+                hook.call(&util).map_err(|_| "hook failed")
+            })
+            .unwrap_err();
+        assert_eq!(err, "failed before calling");
+
+        batch.extend([hook_with_payload(ALICE, vec![1])]);
+        batch.discard();
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn coalesces_calls_to_same_recipient() {
+        let util = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut batch = ReceiverHookBatch::new();
+        batch.extend([
+            hook_with_payload(ALICE, vec![1]),
+            hook_with_payload(BOB, vec![2]),
+            hook_with_payload(ALICE, vec![3]),
+        ]);
+
+        let results = batch.call_coalesced(&util, |_, payloads| {
+            Ok(RawBytes::new(payloads.into_iter().flat_map(|p| p.to_vec()).collect()))
+        });
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, ALICE);
+        let alice_results = results[0].1.as_ref().unwrap();
+        assert_eq!(alice_results.len(), 2);
+        assert_eq!(results[1].0, BOB);
+
+        // the fake syscalls echo the params back, so each hook sees the merged payload
+        let message = util.syscalls.last_message.borrow();
+        let params: super::UniversalReceiverParams =
+            message.as_ref().unwrap().params.as_ref().unwrap().deserialize().unwrap();
+        assert_eq!(params.payload, RawBytes::new(vec![2]));
+        assert_eq!(alice_results[0].data, alice_results[1].data);
+        assert!(!alice_results[0].data.is_empty());
+    }
+
+    #[test]
+    fn exposes_abort_details() {
+        let return_data = IpldBlock::serialize_cbor(&"not accepting tokens").unwrap();
+        let err = ReceiverHookError::new_receiver_error(
+            ALICE,
+            ExitCode::USR_FORBIDDEN,
+            return_data.clone(),
+        );
+        let abort = err.abort().unwrap();
+        assert_eq!(abort.address, ALICE);
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        assert_eq!(abort.message.as_deref(), Some("not accepting tokens"));
+        assert_eq!(abort.return_data, RawBytes::new(return_data.unwrap().data));
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+
+        // return data that isn't a string is kept but not treated as a message
+        let return_data = IpldBlock::serialize_cbor(&42u64).unwrap();
+        let err =
+            ReceiverHookError::new_receiver_error(ALICE, ExitCode::USR_FORBIDDEN, return_data);
+        assert_eq!(err.abort().unwrap().message, None);
+        assert!(!err.abort().unwrap().return_data.is_empty());
+
+        let err = ReceiverHookError::new_receiver_error(ALICE, ExitCode::USR_FORBIDDEN, None);
+        assert_eq!(err.abort().unwrap().message, None);
+        assert!(ReceiverHookError::NotCalled.abort().is_none());
+    }
 }
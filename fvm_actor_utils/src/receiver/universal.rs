@@ -0,0 +1,211 @@
+//! Receiving FRC-46 tokens and FRC-53 NFTs through a single receiver hook
+//!
+//! The payloads of both standards are defined here so that an actor accepting either can decode
+//! them without depending on the token libraries. They are re-exported from `frc46_token` and
+//! `frc53_nft`.
+use frc42_dispatch::method_hash;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::{econ::TokenAmount, error::ExitCode, ActorID};
+
+use super::{ReceiverType, UniversalReceiverParams};
+
+pub const FRC46_TOKEN_TYPE: ReceiverType = method_hash!("FRC46") as u32;
+pub const FRC53_TOKEN_TYPE: ReceiverType = method_hash!("FRC53") as u32;
+
+/// Receive parameters for an FRC46 token
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct FRC46TokenReceived {
+    /// The account that the tokens are being pulled from (the token actor address itself for mint)
+    pub from: ActorID,
+    /// The account that the tokens are being sent to (the receiver address)
+    pub to: ActorID,
+    /// Address of the operator that initiated the transfer/mint
+    pub operator: ActorID,
+    /// Amount of tokens being transferred/minted
+    pub amount: TokenAmount,
+    /// Data specified by the operator during transfer/mint
+    pub operator_data: RawBytes,
+    /// Additional data specified by the token-actor during transfer/mint
+    pub token_data: RawBytes,
+}
+
+/// Receive parameters for an FRC53 token
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct FRC53TokenReceived {
+    /// The account that the tokens are being sent to (the receiver address)
+    pub to: ActorID,
+    /// Address of the operator that initiated the transfer/mint
+    pub operator: ActorID,
+    /// Amount of tokens being transferred/minted
+    pub token_ids: Vec<u64>,
+    /// Data specified by the operator during transfer/mint
+    pub operator_data: RawBytes,
+    /// Additional data specified by the token-actor during transfer/mint
+    pub token_data: RawBytes,
+    /// Data specified for each token during transfer, empty or in the same order as `token_ids`
+    pub per_token_data: Vec<RawBytes>,
+}
+
+/// What to do with a payload that the receiver has no specific handling for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReceivePolicy {
+    /// Accept the assets
+    Accept,
+    /// Abort the receiver hook, causing the sender to roll back the transfer
+    #[default]
+    Reject,
+}
+
+impl ReceivePolicy {
+    /// Returns the result of applying the policy
+    pub fn apply(self) -> Result<(), ExitCode> {
+        match self {
+            ReceivePolicy::Accept => Ok(()),
+            ReceivePolicy::Reject => Err(ExitCode::USR_FORBIDDEN),
+        }
+    }
+}
+
+/// Standard interface for an actor that wishes to receive FRC-0046 tokens or other assets
+///
+/// An actor passes the parameters of its receiver hook method to [`receive`](Self::receive),
+/// which decodes the payload and dispatches it to the callback for its type. Callbacks that
+/// aren't overridden apply the [`policy`](Self::policy), so an actor that only accepts FRC-46
+/// tokens need only implement [`on_frc46_received`](Self::on_frc46_received).
+///
+/// An error returned from a callback should be used as the exit code to abort with, which rejects
+/// the assets. Within the hook, the token actor has already persisted the new balance so the
+/// receiving actor can immediately use the received assets.
+pub trait UniversalReceiver {
+    /// The policy for payloads without an overridden callback, rejecting them by default
+    fn policy(&self) -> ReceivePolicy {
+        ReceivePolicy::Reject
+    }
+
+    /// Called when FRC-46 tokens are received
+    fn on_frc46_received(&mut self, _params: FRC46TokenReceived) -> Result<(), ExitCode> {
+        self.policy().apply()
+    }
+
+    /// Called when FRC-53 NFTs (the standard drafted as FRC-XX) are received
+    fn on_frc53_received(&mut self, _params: FRC53TokenReceived) -> Result<(), ExitCode> {
+        self.policy().apply()
+    }
+
+    /// Called for payloads of any other type, which are passed on undecoded
+    fn on_other_received(
+        &mut self,
+        _type_: ReceiverType,
+        _payload: RawBytes,
+    ) -> Result<(), ExitCode> {
+        self.policy().apply()
+    }
+
+    /// Dispatches the parameters of a receiver hook call to the callback for their type
+    ///
+    /// Returns [`ExitCode::USR_SERIALIZATION`] if the payload can't be decoded as its type claims.
+    fn receive(&mut self, params: UniversalReceiverParams) -> Result<(), ExitCode> {
+        match params.type_ {
+            FRC46_TOKEN_TYPE => {
+                let payload =
+                    params.payload.deserialize().map_err(|_| ExitCode::USR_SERIALIZATION)?;
+                self.on_frc46_received(payload)
+            }
+            FRC53_TOKEN_TYPE => {
+                let payload =
+                    params.payload.deserialize().map_err(|_| ExitCode::USR_SERIALIZATION)?;
+                self.on_frc53_received(payload)
+            }
+            type_ => self.on_other_received(type_, params.payload),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{
+        FRC46TokenReceived, FRC53TokenReceived, ReceivePolicy, UniversalReceiver, FRC46_TOKEN_TYPE,
+        FRC53_TOKEN_TYPE,
+    };
+    use crate::receiver::UniversalReceiverParams;
+
+    /// Accepts FRC-46 tokens from a single sender and anything else according to its policy
+    struct Wallet {
+        policy: ReceivePolicy,
+        trusted: u64,
+        received: TokenAmount,
+    }
+
+    impl UniversalReceiver for Wallet {
+        fn policy(&self) -> ReceivePolicy {
+            self.policy
+        }
+
+        fn on_frc46_received(&mut self, params: FRC46TokenReceived) -> Result<(), ExitCode> {
+            if params.from != self.trusted {
+                return Err(ExitCode::USR_FORBIDDEN);
+            }
+            self.received += params.amount;
+            Ok(())
+        }
+    }
+
+    fn frc46_params(from: u64, amount: u64) -> UniversalReceiverParams {
+        let payload = FRC46TokenReceived {
+            from,
+            to: 2,
+            operator: from,
+            amount: TokenAmount::from_atto(amount),
+            operator_data: RawBytes::default(),
+            token_data: RawBytes::default(),
+        };
+        UniversalReceiverParams {
+            type_: FRC46_TOKEN_TYPE,
+            payload: RawBytes::serialize(payload).unwrap(),
+        }
+    }
+
+    fn frc53_params() -> UniversalReceiverParams {
+        let payload = FRC53TokenReceived {
+            to: 2,
+            operator: 3,
+            token_ids: vec![0, 1],
+            operator_data: RawBytes::default(),
+            token_data: RawBytes::default(),
+            per_token_data: vec![],
+        };
+        UniversalReceiverParams {
+            type_: FRC53_TOKEN_TYPE,
+            payload: RawBytes::serialize(payload).unwrap(),
+        }
+    }
+
+    #[test]
+    fn it_dispatches_by_payload_type() {
+        let mut wallet =
+            Wallet { policy: ReceivePolicy::Reject, trusted: 1, received: TokenAmount::default() };
+        wallet.receive(frc46_params(1, 10)).unwrap();
+        assert_eq!(wallet.receive(frc46_params(5, 10)), Err(ExitCode::USR_FORBIDDEN));
+        assert_eq!(wallet.received, TokenAmount::from_atto(10));
+
+        // payloads without a callback fall back to the policy
+        assert_eq!(wallet.receive(frc53_params()), Err(ExitCode::USR_FORBIDDEN));
+        let other = UniversalReceiverParams { type_: 7, payload: RawBytes::default() };
+        assert_eq!(wallet.receive(other.clone()), Err(ExitCode::USR_FORBIDDEN));
+        wallet.policy = ReceivePolicy::Accept;
+        wallet.receive(frc53_params()).unwrap();
+        wallet.receive(other).unwrap();
+
+        // a payload that doesn't match its type is rejected before reaching the callback
+        let malformed = UniversalReceiverParams {
+            type_: FRC46_TOKEN_TYPE,
+            payload: RawBytes::serialize("not a transfer").unwrap(),
+        };
+        assert_eq!(wallet.receive(malformed), Err(ExitCode::USR_SERIALIZATION));
+    }
+}
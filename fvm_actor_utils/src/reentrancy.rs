@@ -0,0 +1,184 @@
+//! Protection against re-entrant calls
+//!
+//! An actor that calls out to another actor (such as a token calling a receiver hook) may be
+//! called back before the outgoing call returns. As each invocation runs in a fresh instance of
+//! the actor, the only thing the nested call can observe is the committed state. Actors opt into
+//! protection in one of two ways:
+//!
+//! - by embedding a [`ReentrancyGuard`] in their state, entering it and committing the state
+//!   before calling out, and checking it at the start of each method that must not be re-entered
+//! - by checking with [`check_root_unchanged`] that the state root is the same after calling out
+//!   as it was before, aborting (and so reverting any nested changes) if not
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::error::ExitCode;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::syscalls::Syscalls;
+use crate::util::{ActorError, ActorRuntime};
+
+#[derive(Error, Debug)]
+pub enum ReentrancyError {
+    #[error("actor was re-entered while a call out was in progress")]
+    Reentered,
+    #[error("actor state was changed from {before} to {after} during a call out")]
+    StateChanged { before: Cid, after: Cid },
+    #[error("actor runtime error: {0}")]
+    Actor(#[from] ActorError),
+}
+
+impl From<&ReentrancyError> for ExitCode {
+    fn from(error: &ReentrancyError) -> Self {
+        match error {
+            ReentrancyError::Reentered | ReentrancyError::StateChanged { .. } => {
+                ExitCode::USR_ILLEGAL_STATE
+            }
+            ReentrancyError::Actor(e) => e.into(),
+        }
+    }
+}
+
+/// A marker stored in actor state recording that a call out is in progress
+///
+/// Serializes as a boolean, so adding it to a tuple-encoded state struct costs a single byte.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct ReentrancyGuard {
+    entered: bool,
+}
+
+impl ReentrancyGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks a call out as in progress
+    ///
+    /// The state holding the guard must be committed after this and before calling out. Fails if
+    /// a call out is already in progress, which means this is a re-entrant call.
+    pub fn enter(&mut self) -> Result<(), ReentrancyError> {
+        self.check()?;
+        self.entered = true;
+        Ok(())
+    }
+
+    /// Marks the call out as finished
+    pub fn exit(&mut self) {
+        self.entered = false;
+    }
+
+    /// Returns true if a call out is in progress
+    pub fn is_entered(&self) -> bool {
+        self.entered
+    }
+
+    /// Fails if a call out is in progress, for methods that must not be re-entered but don't call
+    /// out themselves
+    pub fn check(&self) -> Result<(), ReentrancyError> {
+        if self.entered {
+            return Err(ReentrancyError::Reentered);
+        }
+        Ok(())
+    }
+}
+
+/// Fails if the actor's state root is no longer `before`, meaning that a nested call changed it
+pub fn check_root_unchanged<S: Syscalls, BS: Blockstore>(
+    runtime: &ActorRuntime<S, BS>,
+    before: &Cid,
+) -> Result<(), ReentrancyError> {
+    let after = runtime.root_cid()?;
+    if after != *before {
+        return Err(ReentrancyError::StateChanged { before: *before, after });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use cid::multihash::Code;
+    use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+    use fvm_ipld_encoding::CborStore;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{check_root_unchanged, ReentrancyError, ReentrancyGuard};
+    use crate::shared_blockstore::SharedMemoryBlockstore;
+    use crate::syscalls::fake_syscalls::FakeSyscalls;
+    use crate::syscalls::Syscalls;
+    use crate::util::ActorRuntime;
+
+    #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, Default)]
+    struct State {
+        guard: ReentrancyGuard,
+        withdrawals: u64,
+    }
+
+    type Runtime = ActorRuntime<FakeSyscalls, SharedMemoryBlockstore>;
+
+    /// Withdraws by sending to the caller, which may call back in to withdraw again
+    fn withdraw<S: Syscalls>(
+        runtime: &ActorRuntime<S, SharedMemoryBlockstore>,
+    ) -> Result<(), ReentrancyError> {
+        let mut state: State = runtime.get_cbor(&runtime.root_cid()?).unwrap().unwrap();
+        state.guard.enter()?;
+        state.withdrawals += 1;
+        runtime.set_root(&runtime.put_cbor(&state, Code::Blake2b256).unwrap())?;
+
+        runtime.send(&Address::new_id(2), 0, None, TokenAmount::default()).unwrap();
+
+        let mut state: State = runtime.get_cbor(&runtime.root_cid()?).unwrap().unwrap();
+        state.guard.exit();
+        runtime.set_root(&runtime.put_cbor(&state, Code::Blake2b256).unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn it_rejects_reentrant_calls() {
+        let runtime = Runtime::new_shared_test_runtime();
+        let root = runtime.put_cbor(&State::default(), Code::Blake2b256).unwrap();
+        runtime.set_root(&root).unwrap();
+
+        // the recipient calls straight back into withdraw
+        let nested = Rc::new(RefCell::new(None));
+        let (blockstore, result) = (runtime.blockstore.clone(), nested.clone());
+        runtime.syscalls.set_on_send(move |syscalls, _, _| {
+            let runtime = ActorRuntime::new(syscalls, blockstore.clone());
+            let res = withdraw(&runtime);
+            let exit_code = res.as_ref().map_or_else(ExitCode::from, |_| ExitCode::OK);
+            result.replace(Some(res));
+            exit_code
+        });
+
+        withdraw(&runtime).unwrap();
+        assert!(matches!(nested.borrow().as_ref(), Some(Err(ReentrancyError::Reentered))));
+        let state: State = runtime.get_cbor(&runtime.root_cid().unwrap()).unwrap().unwrap();
+        assert_eq!(state.withdrawals, 1);
+        assert!(!state.guard.is_entered());
+    }
+
+    #[test]
+    fn it_detects_state_changed_by_nested_calls() {
+        let runtime = Runtime::new_shared_test_runtime();
+        let root = runtime.put_cbor(&State::default(), Code::Blake2b256).unwrap();
+        runtime.set_root(&root).unwrap();
+        check_root_unchanged(&runtime, &root).unwrap();
+
+        let blockstore = runtime.blockstore.clone();
+        runtime.syscalls.set_on_send(move |syscalls, _, _| {
+            let state = State { withdrawals: 5, ..Default::default() };
+            syscalls.root.replace(blockstore.put_cbor(&state, Code::Blake2b256).unwrap());
+            ExitCode::OK
+        });
+        runtime.send(&Address::new_id(2), 0, None, TokenAmount::default()).unwrap();
+        assert!(matches!(
+            check_root_unchanged(&runtime, &root),
+            Err(ReentrancyError::StateChanged { before, .. }) if before == root
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+//! Deferring sends until state has been committed
+//!
+//! An actor calling out to another actor must commit its state first: the callee may call back
+//! into the actor (as a receiver hook may do when handling tokens) and would otherwise see stale
+//! state. Any state the actor holds in memory is then stale if the callee changed it, and must be
+//! reloaded. [`SendQueue`] collects the sends made while processing a message and performs them
+//! only once the state is committed, reporting whether the state changed underneath.
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::ser::Serialize;
+use fvm_shared::error::ExitCode;
+use fvm_shared::{address::Address, econ::TokenAmount, MethodNum, Response};
+use thiserror::Error;
+
+use crate::messaging::MessagingError;
+use crate::syscalls::Syscalls;
+use crate::util::{ActorError, ActorRuntime};
+
+#[derive(Error, Debug)]
+pub enum SendQueueError {
+    #[error("error committing state: {0}")]
+    Actor(#[from] ActorError),
+    #[error("error sending queued message: {0}")]
+    Messaging(#[from] MessagingError),
+}
+
+impl From<&SendQueueError> for ExitCode {
+    fn from(error: &SendQueueError) -> Self {
+        match error {
+            SendQueueError::Actor(e) => e.into(),
+            SendQueueError::Messaging(e) => e.into(),
+        }
+    }
+}
+
+/// A message waiting to be sent
+#[derive(Clone, Debug)]
+pub struct PendingSend {
+    pub to: Address,
+    pub method: MethodNum,
+    pub params: Option<IpldBlock>,
+    pub value: TokenAmount,
+}
+
+/// The outcome of flushing a [`SendQueue`]
+#[derive(Clone, Debug)]
+pub struct Flushed {
+    /// The responses to the queued sends, in the order they were queued
+    pub responses: Vec<Response>,
+    /// The state root after the sends, if they changed it by calling back into the actor
+    ///
+    /// State held in memory from before the flush must be reloaded from this root.
+    pub new_root: Option<Cid>,
+}
+
+/// A queue of sends to be made after the actor's state has been committed
+#[derive(Clone, Debug, Default)]
+pub struct SendQueue {
+    sends: Vec<PendingSend>,
+}
+
+impl SendQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a message to be sent when the queue is flushed
+    pub fn push(
+        &mut self,
+        to: Address,
+        method: MethodNum,
+        params: Option<IpldBlock>,
+        value: TokenAmount,
+    ) {
+        self.sends.push(PendingSend { to, method, params, value });
+    }
+
+    /// Queues a message with CBOR encoded parameters
+    pub fn push_typed<P: Serialize>(
+        &mut self,
+        to: Address,
+        method: MethodNum,
+        params: &P,
+        value: TokenAmount,
+    ) -> Result<(), MessagingError> {
+        self.push(to, method, IpldBlock::serialize_cbor(params)?, value);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.sends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sends.is_empty()
+    }
+
+    /// Returns the queued sends
+    pub fn pending(&self) -> &[PendingSend] {
+        &self.sends
+    }
+
+    /// Commits `root` as the actor's state and then makes the queued sends in order
+    ///
+    /// Stops at the first send that fails or aborts, returning MessagingError::Aborted for the
+    /// latter. The caller should then abort too, as the sends before it can't be undone.
+    pub fn flush<S: Syscalls, BS: Blockstore>(
+        self,
+        runtime: &ActorRuntime<S, BS>,
+        root: &Cid,
+    ) -> Result<Flushed, SendQueueError> {
+        let (responses, new_root) = runtime.call_after_commit(root, || {
+            self.sends
+                .into_iter()
+                .map(|send| {
+                    let ret = runtime.send(&send.to, send.method, send.params, send.value)?;
+                    if !ret.exit_code.is_success() {
+                        return Err(MessagingError::Aborted {
+                            address: send.to,
+                            exit_code: ret.exit_code,
+                        }
+                        .into());
+                    }
+                    Ok(ret)
+                })
+                .collect::<Result<Vec<_>, SendQueueError>>()
+        })?;
+        Ok(Flushed { responses, new_root })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::CborStore;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+
+    use super::{SendQueue, SendQueueError};
+    use crate::messaging::MessagingError;
+    use crate::syscalls::fake_syscalls::FakeSyscalls;
+    use crate::util::ActorRuntime;
+
+    #[test]
+    fn it_sends_after_committing_state() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let root = runtime.put_cbor(&"state", Code::Blake2b256).unwrap();
+
+        let mut queue = SendQueue::new();
+        queue.push_typed(Address::new_id(2), 2, &1u64, TokenAmount::default()).unwrap();
+        queue.push_typed(Address::new_id(3), 3, &2u64, TokenAmount::default()).unwrap();
+        assert_eq!(queue.len(), 2);
+        // nothing is sent until the queue is flushed
+        assert!(runtime.syscalls.last_message.borrow().is_none());
+
+        let flushed = queue.flush(&runtime, &root).unwrap();
+        assert_eq!(runtime.root_cid().unwrap(), root);
+        assert_eq!(flushed.responses.len(), 2);
+        assert_eq!(flushed.new_root, None);
+        assert_eq!(runtime.syscalls.last_message.borrow().as_ref().unwrap().method, 3);
+    }
+
+    #[test]
+    fn it_stops_at_the_first_failure() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let root = runtime.put_cbor(&"state", Code::Blake2b256).unwrap();
+
+        let mut queue = SendQueue::new();
+        queue.push(Address::new_id(2), 2, None, TokenAmount::default());
+        queue.push(Address::new_id(3), 3, None, TokenAmount::default());
+        runtime.syscalls.abort_next_send.replace(true);
+
+        let err = queue.flush(&runtime, &root).unwrap_err();
+        assert!(matches!(err, SendQueueError::Messaging(MessagingError::Syscall(_))));
+        assert!(runtime.syscalls.last_message.borrow().is_none());
+    }
+}
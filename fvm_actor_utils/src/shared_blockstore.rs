@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::rc::Rc;
 
 use anyhow::Result;
@@ -8,12 +9,31 @@ use fvm_ipld_blockstore::MemoryBlockstore;
 /// Clones of it will reference the same underlying MemoryBlockstore, allowing for more complex unit testing
 #[derive(Debug, Clone)]
 pub struct SharedMemoryBlockstore {
-    store: Rc<MemoryBlockstore>,
+    store: Rc<RefCell<MemoryBlockstore>>,
+}
+
+/// A copy of the blocks held by a SharedMemoryBlockstore at some point in time
+#[derive(Debug, Clone)]
+pub struct BlockstoreSnapshot {
+    store: MemoryBlockstore,
 }
 
 impl SharedMemoryBlockstore {
     pub fn new() -> Self {
-        Self { store: Rc::new(MemoryBlockstore::new()) }
+        Self { store: Rc::new(RefCell::new(MemoryBlockstore::new())) }
+    }
+
+    /// Captures the current contents of the blockstore
+    pub fn snapshot(&self) -> BlockstoreSnapshot {
+        BlockstoreSnapshot { store: self.store.borrow().clone() }
+    }
+
+    /// Replaces the contents of the blockstore with those of a snapshot, discarding blocks written
+    /// since it was taken
+    ///
+    /// All clones of this blockstore observe the change.
+    pub fn restore(&self, snapshot: BlockstoreSnapshot) {
+        self.store.replace(snapshot.store);
     }
 }
 
@@ -27,7 +47,7 @@ impl Default for SharedMemoryBlockstore {
 impl fvm_ipld_blockstore::Blockstore for SharedMemoryBlockstore {
     /// Gets the block from the blockstore.
     fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
-        self.store.get(k)
+        self.store.borrow().get(k)
     }
 
     /// Put a block with a pre-computed cid.
@@ -37,11 +57,59 @@ impl fvm_ipld_blockstore::Blockstore for SharedMemoryBlockstore {
     ///
     /// If you _do_ already know the CID, use this method as some blockstores _won't_ recompute it.
     fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
-        self.store.put_keyed(k, block)
+        self.store.borrow().put_keyed(k, block)
     }
 
     /// Checks if the blockstore has the specified block.
     fn has(&self, k: &Cid) -> Result<bool> {
-        self.store.has(k)
+        self.store.borrow().has(k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::Blockstore;
+    use fvm_ipld_encoding::CborStore;
+
+    use super::SharedMemoryBlockstore;
+    use crate::syscalls::fake_syscalls::FakeSyscalls;
+    use crate::util::ActorRuntime;
+
+    #[test]
+    fn it_restores_snapshots() {
+        let bs = SharedMemoryBlockstore::new();
+        let clone = bs.clone();
+        let kept = bs.put_cbor(&"kept", Code::Blake2b256).unwrap();
+        let snapshot = bs.snapshot();
+
+        let discarded = clone.put_cbor(&"discarded", Code::Blake2b256).unwrap();
+        assert!(bs.has(&discarded).unwrap());
+
+        // the snapshot can be restored more than once
+        bs.restore(snapshot.clone());
+        assert!(bs.has(&kept).unwrap());
+        assert!(!clone.has(&discarded).unwrap());
+        clone.put_cbor(&"discarded", Code::Blake2b256).unwrap();
+        clone.restore(snapshot);
+        assert!(!bs.has(&discarded).unwrap());
+    }
+
+    #[test]
+    fn it_restores_runtime_snapshots() {
+        let mut runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let root = runtime.blockstore.put_cbor(&1u64, Code::Blake2b256).unwrap();
+        runtime.set_root(&root).unwrap();
+        let snapshot = runtime.snapshot();
+
+        let new_root = runtime.blockstore.put_cbor(&2u64, Code::Blake2b256).unwrap();
+        runtime.set_root(&new_root).unwrap();
+        runtime.syscalls.set_curr_epoch(10);
+
+        runtime.restore(snapshot);
+        assert_eq!(runtime.root_cid().unwrap(), root);
+        assert_eq!(runtime.curr_epoch(), 0);
+        assert!(!runtime.blockstore.has(&new_root).unwrap());
     }
 }
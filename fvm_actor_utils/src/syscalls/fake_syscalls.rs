@@ -1,12 +1,158 @@
-use std::{cell::RefCell, collections::HashMap};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
+use cid::multihash::{Code, MultihashDigest};
 use cid::Cid;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
 use fvm_shared::{
-    address::Address, econ::TokenAmount, error::ErrorNumber, error::ExitCode, ActorID, Response,
+    address::{Address, Protocol},
+    clock::{ChainEpoch, EPOCH_DURATION_SECONDS},
+    crypto::signature::{BLS_PUB_LEN, BLS_SIG_LEN, SECP_PUB_LEN, SECP_SIG_LEN},
+    econ::TokenAmount,
+    error::ErrorNumber,
+    error::ExitCode,
+    event::ActorEvent,
+    randomness::RANDOMNESS_LENGTH,
+    version::NetworkVersion,
+    ActorID, MethodNum, Response,
 };
 
 use super::Syscalls;
+use crate::gas::GasMeter;
+use crate::init::{
+    Exec4Params, ExecParams, ExecReturn, EXEC4_METHOD, EXEC_METHOD, INIT_ACTOR_ADDR,
+};
+
+/// Timestamp of epoch 0 in fake environments, matching mainnet genesis
+pub const FAKE_GENESIS_TIMESTAMP: u64 = 1598306400;
+
+/// Network version reported by fake environments unless configured otherwise
+pub const FAKE_NETWORK_VERSION: NetworkVersion = NetworkVersion::V21;
+
+/// Chain ID reported by fake environments, matching mainnet
+pub const FAKE_CHAIN_ID: u64 = 314;
+
+/// Returns the timestamp of an epoch in fake environments, assuming no null rounds
+pub fn fake_tipset_timestamp(epoch: ChainEpoch) -> u64 {
+    FAKE_GENESIS_TIMESTAMP.saturating_add_signed(epoch * EPOCH_DURATION_SECONDS)
+}
+
+/// Returns the chain randomness of an epoch in fake environments
+///
+/// This is a hash of the epoch, so it is deterministic across runs but differs between epochs.
+/// Fails as the FVM does if the epoch is after the current one.
+pub fn fake_chain_randomness(
+    epoch: ChainEpoch,
+    curr_epoch: ChainEpoch,
+) -> Result<[u8; RANDOMNESS_LENGTH], ErrorNumber> {
+    if epoch > curr_epoch {
+        return Err(ErrorNumber::IllegalArgument);
+    }
+    let digest = Code::Blake2b256.digest(&epoch.to_be_bytes());
+    let mut randomness = [0u8; RANDOMNESS_LENGTH];
+    randomness.copy_from_slice(digest.digest());
+    Ok(randomness)
+}
+
+/// A key that signs digests and messages in fake environments, for [`crate::crypto`]
+///
+/// Fake signatures are hashes of the key and the message rather than real signatures, but they are
+/// checked the same way: secp256k1 signatures by recovering the key that made them, and BLS
+/// signatures against the public key of the signer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FakeKey {
+    id: [u8; 32],
+}
+
+impl FakeKey {
+    /// Returns the key derived from a seed, such as the name of its holder in a test
+    pub fn new(seed: &[u8]) -> Self {
+        Self { id: fake_digest(&[b"fake key", seed]) }
+    }
+
+    /// Returns the uncompressed secp256k1 public key
+    pub fn secp_public_key(&self) -> [u8; SECP_PUB_LEN] {
+        let mut key = [0; SECP_PUB_LEN];
+        key[0] = 0x04;
+        key[1..33].copy_from_slice(&fake_digest(&[&self.id, &[0]]));
+        key[33..].copy_from_slice(&fake_digest(&[&self.id, &[1]]));
+        key
+    }
+
+    /// Returns the BLS public key
+    pub fn bls_public_key(&self) -> [u8; BLS_PUB_LEN] {
+        let digests = [fake_digest(&[&self.id, &[2]]), fake_digest(&[&self.id, &[3]])].concat();
+        digests[..BLS_PUB_LEN].try_into().unwrap()
+    }
+
+    /// Returns the secp256k1 (f1) address of the key
+    pub fn secp_address(&self) -> Address {
+        Address::new_secp256k1(&self.secp_public_key()).unwrap()
+    }
+
+    /// Returns the Ethereum-style delegated (f410) address of the key
+    pub fn eth_address(&self) -> Address {
+        crate::crypto::eth_address(&self.secp_public_key())
+    }
+
+    /// Returns the BLS (f3) address of the key
+    pub fn bls_address(&self) -> Address {
+        Address::new_bls(&self.bls_public_key()).unwrap()
+    }
+
+    /// Signs a digest with the secp256k1 key, for verification by its f1 or f410 address
+    pub fn sign_secp(&self, digest: &[u8; 32]) -> [u8; SECP_SIG_LEN] {
+        let mut signature = [0; SECP_SIG_LEN];
+        signature[..32].copy_from_slice(&self.id);
+        signature[32..64].copy_from_slice(&fake_digest(&[&self.id, digest]));
+        signature
+    }
+
+    /// Signs a message with the BLS key, for verification by its f3 address
+    pub fn sign_bls(&self, message: &[u8]) -> [u8; BLS_SIG_LEN] {
+        fake_bls_signature(&self.bls_public_key(), message)
+    }
+}
+
+/// Recovers the public key of the [`FakeKey`] that made a secp256k1 signature over a digest
+pub fn fake_recover_secp_public_key(
+    digest: &[u8; 32],
+    signature: &[u8; SECP_SIG_LEN],
+) -> Result<[u8; SECP_PUB_LEN], ErrorNumber> {
+    let key = FakeKey { id: signature[..32].try_into().unwrap() };
+    if signature[32..] != key.sign_secp(digest)[32..] {
+        return Err(ErrorNumber::IllegalArgument);
+    }
+    Ok(key.secp_public_key())
+}
+
+/// Verifies a BLS signature made by a [`FakeKey`]
+pub fn fake_verify_bls(
+    signature: &[u8; BLS_SIG_LEN],
+    public_key: &[u8; BLS_PUB_LEN],
+    message: &[u8],
+) -> bool {
+    *signature == fake_bls_signature(public_key, message)
+}
+
+fn fake_bls_signature(public_key: &[u8; BLS_PUB_LEN], message: &[u8]) -> [u8; BLS_SIG_LEN] {
+    let digests: Vec<u8> =
+        (0..3u8).flat_map(|i| fake_digest(&[public_key, message, &[i]])).collect();
+    digests.try_into().unwrap()
+}
+
+fn fake_digest(parts: &[&[u8]]) -> [u8; 32] {
+    Code::Blake2b256.digest(&parts.concat()).digest().try_into().unwrap()
+}
+
+/// A function simulating the recipient of a message sent through [`FakeSyscalls`]
+#[derive(Clone)]
+pub struct SendHook(pub Rc<dyn Fn(&FakeSyscalls, &Address, MethodNum) -> ExitCode>);
+
+impl std::fmt::Debug for SendHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SendHook")
+    }
+}
 
 #[derive(Clone, Default, Debug)]
 pub struct TestMessage {
@@ -24,16 +170,42 @@ pub struct FakeSyscalls {
 
     /// Actor ID to return as caller ID
     pub caller_id: RefCell<ActorID>,
+    /// Epoch to return as the current chain epoch
+    pub curr_epoch: RefCell<ChainEpoch>,
+    /// Timestamp to return for the current tipset. If not set, it is derived from the current epoch
+    pub tipset_timestamp: RefCell<Option<u64>>,
+    /// Base fee to return for the current tipset
+    pub base_fee: RefCell<TokenAmount>,
+    /// Network version to return. If not set, [`FAKE_NETWORK_VERSION`] is returned
+    pub network_version: RefCell<Option<NetworkVersion>>,
+    /// Value to return as sent with the current message
+    pub value_received: RefCell<TokenAmount>,
+    /// Balances of actors, including the receiving actor. Actors without a balance are treated as
+    /// not existing by `balance_of`. Once the receiving actor has a balance, value sent in messages
+    /// is moved from it to the recipient
+    pub balances: RefCell<HashMap<ActorID, TokenAmount>>,
 
     /// A map of addresses that were instantiated in this runtime
     pub addresses: RefCell<HashMap<Address, ActorID>>,
     /// The next-to-allocate f0 address
     pub next_actor_id: RefCell<ActorID>,
+    /// The code of actors created by sending to the init actor
+    pub actor_code: RefCell<HashMap<ActorID, Cid>>,
 
     /// The last message sent via this runtime
     pub last_message: RefCell<Option<TestMessage>>,
     /// Flag to control message success
     pub abort_next_send: RefCell<bool>,
+    /// Whether the receiving actor is executing in a read-only context
+    pub read_only: RefCell<bool>,
+
+    /// The events emitted via this runtime, in order
+    pub events: RefCell<Vec<ActorEvent>>,
+
+    /// Meter charged for sends and events, if gas is being simulated
+    pub gas_meter: Option<Rc<GasMeter>>,
+    /// Simulates the recipient of each send, see [`FakeSyscalls::set_on_send`]
+    pub on_send: RefCell<Option<SendHook>>,
 }
 
 impl FakeSyscalls {
@@ -41,6 +213,106 @@ impl FakeSyscalls {
     pub fn set_caller_id(&self, new_id: ActorID) {
         self.caller_id.replace(new_id);
     }
+
+    /// Set the epoch returned as the current chain epoch
+    pub fn set_curr_epoch(&self, epoch: ChainEpoch) {
+        self.curr_epoch.replace(epoch);
+    }
+
+    /// Set the timestamp returned for the current tipset
+    pub fn set_tipset_timestamp(&self, timestamp: u64) {
+        self.tipset_timestamp.replace(Some(timestamp));
+    }
+
+    /// Set the base fee returned for the current tipset
+    pub fn set_base_fee(&self, base_fee: TokenAmount) {
+        self.base_fee.replace(base_fee);
+    }
+
+    /// Set the network version returned
+    pub fn set_network_version(&self, version: NetworkVersion) {
+        self.network_version.replace(Some(version));
+    }
+
+    /// Set the value returned as sent with the current message
+    pub fn set_value_received(&self, value: TokenAmount) {
+        self.value_received.replace(value);
+    }
+
+    /// Set the balance of an actor, which may be the receiving actor
+    pub fn set_balance(&self, actor_id: ActorID, balance: TokenAmount) {
+        self.balances.borrow_mut().insert(actor_id, balance);
+    }
+
+    /// Set the caller and value of the current message
+    pub fn set_message(&self, caller: ActorID, value: TokenAmount) {
+        self.set_caller_id(caller);
+        self.set_value_received(value);
+    }
+
+    /// Set a function to run as the recipient of each message sent, after the message is recorded
+    ///
+    /// The function is passed these syscalls, so it can simulate a nested invocation of the
+    /// receiving actor by changing its caller and state root, and returns the exit code of the
+    /// send. This is how tests exercise re-entrancy: a send from the actor can call back into it.
+    pub fn set_on_send(
+        &self,
+        hook: impl Fn(&FakeSyscalls, &Address, MethodNum) -> ExitCode + 'static,
+    ) {
+        self.on_send.replace(Some(SendHook(Rc::new(hook))));
+    }
+
+    /// Set whether the receiving actor is executing in a read-only context
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.replace(read_only);
+    }
+
+    /// Remove and return the events emitted so far
+    pub fn take_events(&self) -> Vec<ActorEvent> {
+        self.events.take()
+    }
+
+    /// Handles a message to the init actor, allocating an ID and addresses for the new actor
+    ///
+    /// The constructor of the new actor is not run.
+    fn exec(
+        &self,
+        addresses: &mut HashMap<Address, ActorID>,
+        method: MethodNum,
+        params: Option<&IpldBlock>,
+    ) -> Result<Option<IpldBlock>, ErrorNumber> {
+        let params = params.ok_or(ErrorNumber::IllegalArgument)?;
+        let (code_cid, delegated_address) = match method {
+            EXEC_METHOD => {
+                let params: ExecParams =
+                    params.deserialize().map_err(|_| ErrorNumber::Serialization)?;
+                (params.code_cid, None)
+            }
+            EXEC4_METHOD => {
+                let params: Exec4Params =
+                    params.deserialize().map_err(|_| ErrorNumber::Serialization)?;
+                let address = Address::new_delegated(self.actor_id, &params.subaddress)
+                    .map_err(|_| ErrorNumber::IllegalArgument)?;
+                if addresses.contains_key(&address) {
+                    return Err(ErrorNumber::Forbidden);
+                }
+                (params.code_cid, Some(address))
+            }
+            _ => return Err(ErrorNumber::IllegalArgument),
+        };
+
+        let actor_id = self.next_actor_id.replace_with(|old| *old + 1);
+        let robust_address =
+            Address::new_actor(&[self.actor_id.to_be_bytes(), actor_id.to_be_bytes()].concat());
+        addresses.insert(robust_address, actor_id);
+        if let Some(address) = delegated_address {
+            addresses.insert(address, actor_id);
+        }
+        self.actor_code.borrow_mut().insert(actor_id, code_cid);
+
+        let ret = ExecReturn { id_address: Address::new_id(actor_id), robust_address };
+        IpldBlock::serialize_cbor(&ret).map_err(|_| ErrorNumber::Serialization)
+    }
 }
 
 impl Syscalls for FakeSyscalls {
@@ -61,6 +333,45 @@ impl Syscalls for FakeSyscalls {
         *self.caller_id.borrow()
     }
 
+    fn curr_epoch(&self) -> ChainEpoch {
+        *self.curr_epoch.borrow()
+    }
+
+    fn tipset_timestamp(&self) -> u64 {
+        self.tipset_timestamp.borrow().unwrap_or_else(|| fake_tipset_timestamp(self.curr_epoch()))
+    }
+
+    fn base_fee(&self) -> TokenAmount {
+        self.base_fee.borrow().clone()
+    }
+
+    fn network_version(&self) -> NetworkVersion {
+        self.network_version.borrow().unwrap_or(FAKE_NETWORK_VERSION)
+    }
+
+    fn chain_id(&self) -> u64 {
+        FAKE_CHAIN_ID
+    }
+
+    fn get_chain_randomness(
+        &self,
+        epoch: ChainEpoch,
+    ) -> Result<[u8; RANDOMNESS_LENGTH], ErrorNumber> {
+        fake_chain_randomness(epoch, self.curr_epoch())
+    }
+
+    fn value_received(&self) -> TokenAmount {
+        self.value_received.borrow().clone()
+    }
+
+    fn current_balance(&self) -> TokenAmount {
+        self.balance_of(self.actor_id).unwrap_or_default()
+    }
+
+    fn balance_of(&self, actor_id: ActorID) -> Option<TokenAmount> {
+        self.balances.borrow().get(&actor_id).cloned()
+    }
+
     fn send(
         &self,
         to: &fvm_shared::address::Address,
@@ -68,6 +379,9 @@ impl Syscalls for FakeSyscalls {
         params: Option<fvm_ipld_encoding::ipld_block::IpldBlock>,
         value: fvm_shared::econ::TokenAmount,
     ) -> Result<Response, ErrorNumber> {
+        if let Some(meter) = &self.gas_meter {
+            meter.charge_send(params.as_ref().map_or(0, |p| p.data.len()));
+        }
         if *self.abort_next_send.borrow() {
             self.abort_next_send.replace(false);
             Err(ErrorNumber::AssertionFailed)
@@ -94,11 +408,42 @@ impl Syscalls for FakeSyscalls {
                 }
             }?;
 
+            // value is only moved if the balance of the receiving actor has been configured
+            let mut balances = self.balances.borrow_mut();
+            if let Some(balance) = balances.get(&self.actor_id).cloned() {
+                if balance < value {
+                    return Err(ErrorNumber::InsufficientFunds);
+                }
+                balances.insert(self.actor_id, balance - value.clone());
+                let recipient = match to.payload() {
+                    fvm_shared::address::Payload::ID(id) => Some(*id),
+                    _ => map.get(to).copied(),
+                };
+                if let Some(recipient) = recipient {
+                    let recipient_balance = balances.get(&recipient).cloned().unwrap_or_default();
+                    balances.insert(recipient, recipient_balance + value.clone());
+                }
+            }
+
+            // exec messages to the init actor create actors, anything else echoes the params back
+            let return_data =
+                if *to == INIT_ACTOR_ADDR && matches!(method, EXEC_METHOD | EXEC4_METHOD) {
+                    self.exec(&mut map, method, params.as_ref())?
+                } else {
+                    params.clone()
+                };
+
             // save the fake message as being sent
-            let message = TestMessage { method, params: params.clone(), value };
+            let message = TestMessage { method, params, value };
             self.last_message.replace(Some(message));
 
-            Ok(Response { exit_code: ExitCode::OK, return_data: params })
+            // the recipient may call back into this actor, so nothing can be borrowed while it runs
+            drop(balances);
+            drop(map);
+            let on_send = self.on_send.borrow().clone();
+            let exit_code = on_send.map_or(ExitCode::OK, |hook| (hook.0)(self, to, method));
+
+            Ok(Response { exit_code, return_data })
         }
     }
 
@@ -111,4 +456,209 @@ impl Syscalls for FakeSyscalls {
         let map = self.addresses.borrow();
         map.get(addr).copied()
     }
+
+    fn lookup_delegated_address(&self, actor_id: ActorID) -> Option<Address> {
+        self.addresses.borrow().iter().find_map(|(addr, id)| {
+            (*id == actor_id && addr.protocol() == Protocol::Delegated).then_some(*addr)
+        })
+    }
+
+    fn emit_event(&self, event: &ActorEvent) -> Result<(), ErrorNumber> {
+        if let Some(meter) = &self.gas_meter {
+            meter.charge_event(event.entries.iter().map(|e| e.key.len() + e.value.len()).sum());
+        }
+        self.events.borrow_mut().push(event.clone());
+        Ok(())
+    }
+
+    fn recover_secp_public_key(
+        &self,
+        digest: &[u8; 32],
+        signature: &[u8; SECP_SIG_LEN],
+    ) -> Result<[u8; SECP_PUB_LEN], ErrorNumber> {
+        fake_recover_secp_public_key(digest, signature)
+    }
+
+    fn verify_bls(
+        &self,
+        signature: &[u8; BLS_SIG_LEN],
+        public_key: &[u8; BLS_PUB_LEN],
+        message: &[u8],
+    ) -> Result<bool, ErrorNumber> {
+        Ok(fake_verify_bls(signature, public_key, message))
+    }
+
+    fn read_only(&self) -> bool {
+        *self.read_only.borrow()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::{Code, MultihashDigest};
+    use cid::Cid;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::{CborStore, RawBytes, IPLD_RAW};
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::version::NetworkVersion;
+    use num_traits::Zero;
+
+    use super::{FakeSyscalls, FAKE_CHAIN_ID, FAKE_GENESIS_TIMESTAMP, FAKE_NETWORK_VERSION};
+    use crate::messaging::MessagingError;
+    use crate::util::{ActorError, ActorRuntime};
+
+    #[test]
+    fn it_returns_configured_message_and_balances() {
+        let mut runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        runtime.syscalls.actor_id = 100;
+        runtime.syscalls.set_balance(100, TokenAmount::from_whole(5));
+        runtime.syscalls.set_balance(2, TokenAmount::from_whole(1));
+        assert_eq!(runtime.actor_id(), 100);
+        assert_eq!(runtime.current_balance(), TokenAmount::from_whole(5));
+        assert_eq!(runtime.balance_of(2), Some(TokenAmount::from_whole(1)));
+        assert_eq!(runtime.balance_of(3), None);
+
+        runtime.syscalls.set_message(2, TokenAmount::from_atto(10));
+        runtime.syscalls.set_curr_epoch(7);
+        assert_eq!(runtime.caller(), 2);
+        assert_eq!(runtime.value_received(), TokenAmount::from_atto(10));
+        assert_eq!(runtime.curr_epoch(), 7);
+
+        runtime.syscalls.set_message(3, TokenAmount::default());
+        assert_eq!(runtime.caller(), 3);
+        assert_eq!(runtime.value_received(), TokenAmount::default());
+    }
+
+    #[test]
+    fn it_returns_deterministic_chain_context() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        runtime.syscalls.set_curr_epoch(10);
+        assert_eq!(runtime.tipset_timestamp(), FAKE_GENESIS_TIMESTAMP + 300);
+        assert_eq!(runtime.network_version(), FAKE_NETWORK_VERSION);
+        assert_eq!(runtime.chain_id(), FAKE_CHAIN_ID);
+        assert_eq!(runtime.base_fee(), TokenAmount::zero());
+
+        let randomness = runtime.chain_randomness(10).unwrap();
+        assert_eq!(runtime.chain_randomness(10).unwrap(), randomness);
+        assert_ne!(runtime.chain_randomness(9).unwrap(), randomness);
+        runtime.chain_randomness(11).unwrap_err();
+
+        runtime.syscalls.set_tipset_timestamp(42);
+        runtime.syscalls.set_base_fee(TokenAmount::from_atto(100));
+        runtime.syscalls.set_network_version(NetworkVersion::V22);
+        assert_eq!(runtime.tipset_timestamp(), 42);
+        assert_eq!(runtime.base_fee(), TokenAmount::from_atto(100));
+        assert_eq!(runtime.network_version(), NetworkVersion::V22);
+    }
+
+    #[test]
+    fn it_rejects_writes_when_read_only() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let cid = runtime.put_cbor(&"state", Code::Blake2b256).unwrap();
+        runtime.set_root(&cid).unwrap();
+        runtime.assert_mutable().unwrap();
+
+        runtime.syscalls.set_read_only(true);
+        assert!(runtime.read_only());
+        assert!(matches!(runtime.assert_mutable(), Err(ActorError::ReadOnly)));
+        assert!(matches!(runtime.set_root(&Cid::default()), Err(ActorError::ReadOnly)));
+        let err = runtime.put_cbor(&"new state", Code::Blake2b256).unwrap_err();
+        assert!(matches!(err.downcast_ref::<ActorError>(), Some(ActorError::ReadOnly)));
+
+        // reads are unaffected
+        assert_eq!(runtime.root_cid().unwrap(), cid);
+        assert_eq!(runtime.get_cbor::<String>(&cid).unwrap().unwrap(), "state");
+    }
+
+    #[test]
+    fn it_sends_typed_messages() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let to = Address::new_id(2);
+
+        // fake sends return their params
+        let ret: (u64, String) =
+            runtime.send_typed(&to, 2, &(5u64, "five"), TokenAmount::zero()).unwrap();
+        assert_eq!(ret, (5, "five".to_string()));
+
+        let err = runtime.send_typed::<_, String>(&to, 2, &5u64, TokenAmount::zero()).unwrap_err();
+        assert!(matches!(err, MessagingError::InvalidReturn { address, .. } if address == to));
+
+        runtime.syscalls.abort_next_send.replace(true);
+        let err = runtime.send_typed::<_, u64>(&to, 2, &5u64, TokenAmount::zero()).unwrap_err();
+        assert!(matches!(err, MessagingError::Syscall(_)));
+    }
+
+    #[test]
+    fn it_creates_placeholders_for_delegated_addresses() {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let evm_address = Address::new_delegated(10, &[1; 20]).unwrap();
+        assert!(runtime.resolve_id(&evm_address).is_err());
+
+        let id = runtime.resolve_or_init(&evm_address).unwrap();
+        assert_eq!(runtime.resolve_id(&evm_address).unwrap(), id);
+        assert_eq!(runtime.lookup_delegated_address(id), Some(evm_address));
+        assert_eq!(runtime.display_address(id), evm_address);
+        assert!(runtime.same_address(&evm_address, &Address::new_id(id)));
+
+        // actors without a delegated address are reported by ID
+        let secp_address = Address::new_secp256k1(&[1; 65]).unwrap();
+        let id = runtime.resolve_or_init(&secp_address).unwrap();
+        assert_eq!(runtime.lookup_delegated_address(id), None);
+        assert_eq!(runtime.display_address(id), Address::new_id(id));
+    }
+
+    #[test]
+    fn it_creates_actors_through_the_init_actor() {
+        let mut runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        runtime.syscalls.actor_id = 100;
+        runtime.syscalls.next_actor_id.replace(101);
+        let code_cid = Cid::new_v1(IPLD_RAW, Code::Identity.digest(b"token"));
+
+        let created = runtime.create_actor(code_cid, RawBytes::new(vec![1]), None).unwrap();
+        assert_eq!(created.id_address, Address::new_id(101));
+        assert_eq!(runtime.resolve_id(&created.robust_address).unwrap(), 101);
+        assert_eq!(runtime.lookup_delegated_address(101), None);
+        assert_eq!(runtime.syscalls.actor_code.borrow().get(&101), Some(&code_cid));
+
+        // the delegated address must be in the namespace of the creating actor
+        let delegated = Address::new_delegated(100, &[2; 20]).unwrap();
+        let created =
+            runtime.create_actor(code_cid, RawBytes::default(), Some(&delegated)).unwrap();
+        assert_eq!(created.id_address, Address::new_id(102));
+        assert_eq!(runtime.lookup_delegated_address(102), Some(delegated));
+        assert_eq!(runtime.display_address(102), delegated);
+
+        // an address can only be assigned once
+        runtime.create_actor(code_cid, RawBytes::default(), Some(&delegated)).unwrap_err();
+        let foreign = Address::new_delegated(10, &[2; 20]).unwrap();
+        runtime.create_actor(code_cid, RawBytes::default(), Some(&foreign)).unwrap_err();
+        runtime.create_actor(code_cid, RawBytes::default(), Some(&Address::new_id(5))).unwrap_err();
+    }
+
+    #[test]
+    fn it_moves_value_between_configured_balances() {
+        let mut runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        runtime.syscalls.actor_id = 100;
+        runtime.syscalls.set_balance(100, TokenAmount::from_atto(10));
+        runtime.syscalls.set_message(2, TokenAmount::from_atto(10));
+        assert_eq!(runtime.message_value_received(), TokenAmount::from_atto(10));
+
+        runtime.transfer_fil(&Address::new_id(2), &TokenAmount::from_atto(4)).unwrap();
+        assert_eq!(runtime.balance(), TokenAmount::from_atto(6));
+        assert_eq!(runtime.balance_of(2), Some(TokenAmount::from_atto(4)));
+        let message = runtime.syscalls.last_message.borrow().clone().unwrap();
+        assert_eq!(message.method, fvm_shared::METHOD_SEND);
+        assert_eq!(message.value, TokenAmount::from_atto(4));
+
+        runtime.transfer_fil(&Address::new_id(2), &TokenAmount::from_atto(7)).unwrap_err();
+        assert_eq!(runtime.balance(), TokenAmount::from_atto(6));
+
+        // sending to a new key address creates an account holding the value
+        let secp_address = Address::new_secp256k1(&[2; 65]).unwrap();
+        runtime.transfer_fil(&secp_address, &TokenAmount::from_atto(6)).unwrap();
+        let id = runtime.resolve_id(&secp_address).unwrap();
+        assert_eq!(runtime.balance_of(id), Some(TokenAmount::from_atto(6)));
+        assert!(runtime.balance().is_zero());
+    }
 }
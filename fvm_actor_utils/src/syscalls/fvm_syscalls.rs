@@ -2,6 +2,7 @@ use anyhow::Result;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
 use fvm_sdk;
+use fvm_shared::crypto::signature::{BLS_PUB_LEN, BLS_SIG_LEN, SECP_PUB_LEN, SECP_SIG_LEN};
 use fvm_shared::sys::SendFlags;
 use fvm_shared::{address::Address, MethodNum, Response};
 
@@ -29,6 +30,45 @@ impl Syscalls for FvmSyscalls {
         fvm_sdk::message::caller()
     }
 
+    fn curr_epoch(&self) -> fvm_shared::clock::ChainEpoch {
+        fvm_sdk::network::curr_epoch()
+    }
+
+    fn tipset_timestamp(&self) -> u64 {
+        fvm_sdk::network::tipset_timestamp()
+    }
+
+    fn base_fee(&self) -> fvm_shared::econ::TokenAmount {
+        fvm_sdk::network::base_fee()
+    }
+
+    fn network_version(&self) -> fvm_shared::version::NetworkVersion {
+        fvm_sdk::network::version()
+    }
+
+    fn chain_id(&self) -> u64 {
+        fvm_sdk::network::chain_id().into()
+    }
+
+    fn get_chain_randomness(
+        &self,
+        epoch: fvm_shared::clock::ChainEpoch,
+    ) -> fvm_sdk::SyscallResult<[u8; fvm_shared::randomness::RANDOMNESS_LENGTH]> {
+        fvm_sdk::rand::get_chain_randomness(epoch)
+    }
+
+    fn value_received(&self) -> fvm_shared::econ::TokenAmount {
+        fvm_sdk::message::value_received()
+    }
+
+    fn current_balance(&self) -> fvm_shared::econ::TokenAmount {
+        fvm_sdk::sself::current_balance()
+    }
+
+    fn balance_of(&self, actor_id: fvm_shared::ActorID) -> Option<fvm_shared::econ::TokenAmount> {
+        fvm_sdk::actor::balance_of(actor_id)
+    }
+
     fn send(
         &self,
         to: &Address,
@@ -45,6 +85,35 @@ impl Syscalls for FvmSyscalls {
     fn resolve_address(&self, addr: &Address) -> Option<fvm_shared::ActorID> {
         fvm_sdk::actor::resolve_address(addr)
     }
+
+    fn lookup_delegated_address(&self, actor_id: fvm_shared::ActorID) -> Option<Address> {
+        fvm_sdk::actor::lookup_delegated_address(actor_id)
+    }
+
+    fn emit_event(&self, event: &fvm_shared::event::ActorEvent) -> fvm_sdk::SyscallResult<()> {
+        fvm_sdk::event::emit_event(event)
+    }
+
+    fn recover_secp_public_key(
+        &self,
+        digest: &[u8; 32],
+        signature: &[u8; SECP_SIG_LEN],
+    ) -> fvm_sdk::SyscallResult<[u8; SECP_PUB_LEN]> {
+        fvm_sdk::crypto::recover_secp_public_key(digest, signature)
+    }
+
+    fn verify_bls(
+        &self,
+        signature: &[u8; BLS_SIG_LEN],
+        public_key: &[u8; BLS_PUB_LEN],
+        message: &[u8],
+    ) -> fvm_sdk::SyscallResult<bool> {
+        fvm_sdk::crypto::verify_bls_aggregate(signature, &[*public_key], &[message])
+    }
+
+    fn read_only(&self) -> bool {
+        fvm_sdk::vm::read_only()
+    }
 }
 
 impl<S: Syscalls + Clone, BS: Blockstore + Clone> ActorRuntime<S, BS> {
@@ -1,12 +1,16 @@
 use cid::Cid;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_shared::crypto::signature::{BLS_PUB_LEN, BLS_SIG_LEN, SECP_PUB_LEN, SECP_SIG_LEN};
 use fvm_shared::{
-    address::Address, econ::TokenAmount, error::ErrorNumber, ActorID, MethodNum, Response,
+    address::Address, clock::ChainEpoch, econ::TokenAmount, error::ErrorNumber, event::ActorEvent,
+    randomness::RANDOMNESS_LENGTH, version::NetworkVersion, ActorID, MethodNum, Response,
 };
 use thiserror::Error;
 
 pub mod fake_syscalls;
+#[cfg(feature = "use_sdk")]
 pub mod fvm_syscalls;
+pub mod test_env;
 
 /// Copied to avoid linking against `fvm_sdk` for non-WASM targets
 #[derive(Copy, Clone, Debug, Error)]
@@ -35,6 +39,38 @@ pub trait Syscalls {
     /// Returns the ID address of the calling actor
     fn caller(&self) -> ActorID;
 
+    /// Returns the current epoch of the chain
+    fn curr_epoch(&self) -> ChainEpoch;
+
+    /// Returns the timestamp of the current tipset, in seconds since the Unix epoch
+    fn tipset_timestamp(&self) -> u64;
+
+    /// Returns the base fee of the current tipset
+    fn base_fee(&self) -> TokenAmount;
+
+    /// Returns the network version the message is being executed under
+    fn network_version(&self) -> NetworkVersion;
+
+    /// Returns the chain ID of the network, which EIP-712 signatures commit to
+    fn chain_id(&self) -> u64;
+
+    /// Returns randomness drawn from the ticket chain at the given epoch
+    ///
+    /// Fails if the epoch is in the future or further back than the network allows.
+    fn get_chain_randomness(
+        &self,
+        epoch: ChainEpoch,
+    ) -> Result<[u8; RANDOMNESS_LENGTH], ErrorNumber>;
+
+    /// Returns the value sent with the current message
+    fn value_received(&self) -> TokenAmount;
+
+    /// Returns the balance of the actor
+    fn current_balance(&self) -> TokenAmount;
+
+    /// Returns the balance of another actor, or None if it doesn't exist
+    fn balance_of(&self, actor_id: ActorID) -> Option<TokenAmount>;
+
     /// Sends a message to an actor
     fn send(
         &self,
@@ -49,4 +85,132 @@ pub trait Syscalls {
     /// Returns None if the address cannot be resolved. Successfully resolving an address doesn't
     /// necessarily mean the actor exists (e.g., if the addresss was already an actor ID).
     fn resolve_address(&self, addr: &Address) -> Option<ActorID>;
+
+    /// Looks up the delegated (f4) address of an actor, if it has one
+    fn lookup_delegated_address(&self, actor_id: ActorID) -> Option<Address>;
+
+    /// Emits an actor event, which is recorded in the receipt of the message if it succeeds
+    fn emit_event(&self, event: &ActorEvent) -> Result<(), ErrorNumber>;
+
+    /// Recovers the uncompressed public key that made a secp256k1 signature over a 32-byte digest
+    ///
+    /// The signature is in the 65-byte `r || s || v` form. Fails with IllegalArgument if no key
+    /// can be recovered.
+    fn recover_secp_public_key(
+        &self,
+        digest: &[u8; 32],
+        signature: &[u8; SECP_SIG_LEN],
+    ) -> Result<[u8; SECP_PUB_LEN], ErrorNumber>;
+
+    /// Checks that a BLS signature was made over `message` by the given public key
+    fn verify_bls(
+        &self,
+        signature: &[u8; BLS_SIG_LEN],
+        public_key: &[u8; BLS_PUB_LEN],
+        message: &[u8],
+    ) -> Result<bool, ErrorNumber>;
+
+    /// Returns true if the actor is executing in a read-only context, in which it can't change
+    /// its state, emit events or send value
+    fn read_only(&self) -> bool;
+}
+
+impl<T: Syscalls + ?Sized> Syscalls for &T {
+    fn root(&self) -> Result<Cid, NoStateError> {
+        (**self).root()
+    }
+
+    fn set_root(&self, cid: &Cid) -> Result<(), NoStateError> {
+        (**self).set_root(cid)
+    }
+
+    fn receiver(&self) -> ActorID {
+        (**self).receiver()
+    }
+
+    fn caller(&self) -> ActorID {
+        (**self).caller()
+    }
+
+    fn curr_epoch(&self) -> ChainEpoch {
+        (**self).curr_epoch()
+    }
+
+    fn tipset_timestamp(&self) -> u64 {
+        (**self).tipset_timestamp()
+    }
+
+    fn base_fee(&self) -> TokenAmount {
+        (**self).base_fee()
+    }
+
+    fn network_version(&self) -> NetworkVersion {
+        (**self).network_version()
+    }
+
+    fn chain_id(&self) -> u64 {
+        (**self).chain_id()
+    }
+
+    fn get_chain_randomness(
+        &self,
+        epoch: ChainEpoch,
+    ) -> Result<[u8; RANDOMNESS_LENGTH], ErrorNumber> {
+        (**self).get_chain_randomness(epoch)
+    }
+
+    fn value_received(&self) -> TokenAmount {
+        (**self).value_received()
+    }
+
+    fn current_balance(&self) -> TokenAmount {
+        (**self).current_balance()
+    }
+
+    fn balance_of(&self, actor_id: ActorID) -> Option<TokenAmount> {
+        (**self).balance_of(actor_id)
+    }
+
+    fn send(
+        &self,
+        to: &Address,
+        method: MethodNum,
+        params: Option<IpldBlock>,
+        value: TokenAmount,
+    ) -> Result<Response, ErrorNumber> {
+        (**self).send(to, method, params, value)
+    }
+
+    fn resolve_address(&self, addr: &Address) -> Option<ActorID> {
+        (**self).resolve_address(addr)
+    }
+
+    fn lookup_delegated_address(&self, actor_id: ActorID) -> Option<Address> {
+        (**self).lookup_delegated_address(actor_id)
+    }
+
+    fn emit_event(&self, event: &ActorEvent) -> Result<(), ErrorNumber> {
+        (**self).emit_event(event)
+    }
+
+    fn recover_secp_public_key(
+        &self,
+        digest: &[u8; 32],
+        signature: &[u8; SECP_SIG_LEN],
+    ) -> Result<[u8; SECP_PUB_LEN], ErrorNumber> {
+        (**self).recover_secp_public_key(digest, signature)
+    }
+
+    fn verify_bls(
+        &self,
+        signature: &[u8; BLS_SIG_LEN],
+        public_key: &[u8; BLS_PUB_LEN],
+        message: &[u8],
+    ) -> Result<bool, ErrorNumber> {
+        (**self).verify_bls(signature, public_key, message)
+    }
+
+    fn read_only(&self) -> bool {
+        (**self).read_only()
+    }
 }
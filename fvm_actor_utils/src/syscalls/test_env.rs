@@ -0,0 +1,575 @@
+//! An in-process environment routing messages between native Rust actors
+//!
+//! Where [`FakeSyscalls`](super::fake_syscalls::FakeSyscalls) records sent messages and echoes
+//! their params, a [`TestEnv`] delivers each message to the actor registered at the destination.
+//! Every actor sees the environment through its own [`TestSyscalls`], so state roots are kept per
+//! actor, value is moved between balances and an aborting call reverts the state changes made by
+//! it and any calls it made. This allows flows such as receiver hooks and re-entrant calls to be
+//! tested without compiling actors to wasm.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use cid::Cid;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_shared::address::{Address, Payload, Protocol};
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::crypto::signature::{BLS_PUB_LEN, BLS_SIG_LEN, SECP_PUB_LEN, SECP_SIG_LEN};
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::{ErrorNumber, ExitCode};
+use fvm_shared::event::ActorEvent;
+use fvm_shared::randomness::RANDOMNESS_LENGTH;
+use fvm_shared::version::NetworkVersion;
+use fvm_shared::{ActorID, MethodNum, Response, METHOD_SEND};
+
+use super::fake_syscalls::{
+    fake_chain_randomness, fake_recover_secp_public_key, fake_tipset_timestamp, fake_verify_bls,
+    FAKE_CHAIN_ID, FAKE_NETWORK_VERSION,
+};
+use super::{NoStateError, Syscalls};
+use crate::shared_blockstore::SharedMemoryBlockstore;
+use crate::util::ActorRuntime;
+
+/// The maximum depth of nested calls, matching the FVM
+pub const MAX_CALL_DEPTH: u32 = 1024;
+
+/// The ID of the first actor registered without an explicit ID
+const FIRST_ACTOR_ID: ActorID = 100;
+
+/// The runtime given to actors running in a [`TestEnv`]
+pub type TestRuntime = ActorRuntime<TestSyscalls, SharedMemoryBlockstore>;
+
+/// An actor that can be run in a [`TestEnv`]
+///
+/// Actors keep their state in the blockstore under their state root, as they would on-chain, so
+/// that it can be reverted when a call aborts.
+pub trait NativeActor {
+    /// Handles a message, returning the return data or the exit code to abort with
+    fn invoke(
+        &self,
+        runtime: &TestRuntime,
+        method: MethodNum,
+        params: Option<IpldBlock>,
+    ) -> Result<Option<IpldBlock>, ExitCode>;
+}
+
+struct EnvState {
+    actors: HashMap<ActorID, Rc<dyn NativeActor>>,
+    /// Accounts and other actors without code, which can only receive value
+    accounts: Vec<ActorID>,
+    roots: HashMap<ActorID, Cid>,
+    balances: HashMap<ActorID, TokenAmount>,
+    addresses: HashMap<Address, ActorID>,
+    events: Vec<(ActorID, ActorEvent)>,
+    next_actor_id: ActorID,
+    curr_epoch: ChainEpoch,
+    base_fee: TokenAmount,
+    network_version: NetworkVersion,
+    read_only: bool,
+    depth: u32,
+    max_depth: u32,
+}
+
+impl Default for EnvState {
+    fn default() -> Self {
+        Self {
+            actors: HashMap::new(),
+            accounts: Vec::new(),
+            roots: HashMap::new(),
+            balances: HashMap::new(),
+            addresses: HashMap::new(),
+            events: Vec::new(),
+            next_actor_id: FIRST_ACTOR_ID,
+            curr_epoch: 0,
+            base_fee: TokenAmount::default(),
+            network_version: FAKE_NETWORK_VERSION,
+            read_only: false,
+            depth: 0,
+            max_depth: MAX_CALL_DEPTH,
+        }
+    }
+}
+
+impl EnvState {
+    fn exists(&self, actor_id: ActorID) -> bool {
+        self.actors.contains_key(&actor_id) || self.accounts.contains(&actor_id)
+    }
+
+    fn allocate_id(&mut self) -> ActorID {
+        let id = self.next_actor_id;
+        self.next_actor_id += 1;
+        id
+    }
+}
+
+/// The state reverted when a call aborts
+struct Snapshot {
+    roots: HashMap<ActorID, Cid>,
+    balances: HashMap<ActorID, TokenAmount>,
+    events: usize,
+}
+
+/// A set of actors that can message each other, sharing one blockstore
+///
+/// Clones refer to the same environment.
+#[derive(Clone, Default)]
+pub struct TestEnv {
+    state: Rc<RefCell<EnvState>>,
+    blockstore: SharedMemoryBlockstore,
+}
+
+impl TestEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an actor at the next free ID, returning the ID
+    pub fn register(&self, actor: impl NativeActor + 'static) -> ActorID {
+        let id = self.state.borrow_mut().allocate_id();
+        self.register_at(id, actor);
+        id
+    }
+
+    /// Registers an actor at a specific ID, replacing any actor already there
+    pub fn register_at(&self, actor_id: ActorID, actor: impl NativeActor + 'static) {
+        self.state.borrow_mut().actors.insert(actor_id, Rc::new(actor));
+    }
+
+    /// Creates an account which can send messages and hold value but runs no code
+    pub fn create_account(&self) -> ActorID {
+        let mut state = self.state.borrow_mut();
+        let id = state.allocate_id();
+        state.accounts.push(id);
+        id
+    }
+
+    /// Assigns a non-ID address to an existing actor
+    pub fn assign_address(&self, address: Address, actor_id: ActorID) {
+        self.state.borrow_mut().addresses.insert(address, actor_id);
+    }
+
+    pub fn set_balance(&self, actor_id: ActorID, balance: TokenAmount) {
+        self.state.borrow_mut().balances.insert(actor_id, balance);
+    }
+
+    pub fn balance(&self, actor_id: ActorID) -> TokenAmount {
+        self.state.borrow().balances.get(&actor_id).cloned().unwrap_or_default()
+    }
+
+    /// Sets the current epoch, from which the tipset timestamp is also derived
+    pub fn set_curr_epoch(&self, epoch: ChainEpoch) {
+        self.state.borrow_mut().curr_epoch = epoch;
+    }
+
+    pub fn set_base_fee(&self, base_fee: TokenAmount) {
+        self.state.borrow_mut().base_fee = base_fee;
+    }
+
+    pub fn set_network_version(&self, version: NetworkVersion) {
+        self.state.borrow_mut().network_version = version;
+    }
+
+    /// Sets whether actors are invoked in a read-only context
+    pub fn set_read_only(&self, read_only: bool) {
+        self.state.borrow_mut().read_only = read_only;
+    }
+
+    /// Sets the depth of nested calls past which sends fail with [`ErrorNumber::LimitExceeded`]
+    pub fn set_max_depth(&self, max_depth: u32) {
+        self.state.borrow_mut().max_depth = max_depth;
+    }
+
+    /// Returns the state root of an actor, if it has one
+    pub fn root(&self, actor_id: ActorID) -> Option<Cid> {
+        self.state.borrow().roots.get(&actor_id).copied()
+    }
+
+    /// Returns the events emitted by calls that didn't abort, with the ID of the emitting actor
+    pub fn events(&self) -> Vec<(ActorID, ActorEvent)> {
+        self.state.borrow().events.clone()
+    }
+
+    pub fn blockstore(&self) -> &SharedMemoryBlockstore {
+        &self.blockstore
+    }
+
+    /// Returns a runtime as seen by `actor_id` when called by `caller`, for setting up state or
+    /// inspecting it outside of a call
+    pub fn runtime(&self, actor_id: ActorID, caller: ActorID) -> TestRuntime {
+        self.runtime_with_value(actor_id, caller, TokenAmount::default())
+    }
+
+    fn runtime_with_value(
+        &self,
+        actor_id: ActorID,
+        caller: ActorID,
+        value: TokenAmount,
+    ) -> TestRuntime {
+        ActorRuntime::new(
+            TestSyscalls { env: self.clone(), receiver: actor_id, caller, value_received: value },
+            self.blockstore.clone(),
+        )
+    }
+
+    /// Sends a top-level message from `from`, as if it were included in a block
+    ///
+    /// If the called actor aborts, its exit code is returned in the response and all state changes
+    /// made during the call are reverted.
+    pub fn call(
+        &self,
+        from: ActorID,
+        to: &Address,
+        method: MethodNum,
+        params: Option<IpldBlock>,
+        value: TokenAmount,
+    ) -> Result<Response, ErrorNumber> {
+        self.send(from, to, method, params, value)
+    }
+
+    fn send(
+        &self,
+        from: ActorID,
+        to: &Address,
+        method: MethodNum,
+        params: Option<IpldBlock>,
+        value: TokenAmount,
+    ) -> Result<Response, ErrorNumber> {
+        let to = self.resolve_or_create(to)?;
+        let (actor, snapshot) = {
+            let mut state = self.state.borrow_mut();
+            if state.depth >= state.max_depth {
+                return Err(ErrorNumber::LimitExceeded);
+            }
+            let snapshot = Snapshot {
+                roots: state.roots.clone(),
+                balances: state.balances.clone(),
+                events: state.events.len(),
+            };
+            let from_balance = state.balances.get(&from).cloned().unwrap_or_default();
+            if from_balance < value {
+                return Err(ErrorNumber::InsufficientFunds);
+            }
+            if from != to {
+                let to_balance = state.balances.get(&to).cloned().unwrap_or_default();
+                state.balances.insert(from, from_balance - value.clone());
+                state.balances.insert(to, to_balance + value.clone());
+            }
+            (state.actors.get(&to).cloned(), snapshot)
+        };
+
+        // the state must not be borrowed while the actor runs, as it may send further messages
+        let result = match actor {
+            _ if method == METHOD_SEND => Ok(None),
+            Some(actor) => {
+                self.state.borrow_mut().depth += 1;
+                let runtime = self.runtime_with_value(to, from, value);
+                let result = actor.invoke(&runtime, method, params);
+                self.state.borrow_mut().depth -= 1;
+                result
+            }
+            None => Err(ExitCode::USR_UNHANDLED_MESSAGE),
+        };
+
+        match result {
+            Ok(return_data) => Ok(Response { exit_code: ExitCode::OK, return_data }),
+            Err(exit_code) => {
+                let mut state = self.state.borrow_mut();
+                state.roots = snapshot.roots;
+                state.balances = snapshot.balances;
+                state.events.truncate(snapshot.events);
+                Ok(Response { exit_code, return_data: None })
+            }
+        }
+    }
+
+    fn resolve(&self, address: &Address) -> Option<ActorID> {
+        let state = self.state.borrow();
+        match address.payload() {
+            Payload::ID(id) => state.exists(*id).then_some(*id),
+            _ => state.addresses.get(address).copied(),
+        }
+    }
+
+    /// Resolves an address, creating an account if it is a new key address
+    fn resolve_or_create(&self, address: &Address) -> Result<ActorID, ErrorNumber> {
+        if let Some(id) = self.resolve(address) {
+            return Ok(id);
+        }
+        match address.payload() {
+            Payload::Secp256k1(_) | Payload::BLS(_) | Payload::Delegated(_) => {
+                let id = self.create_account();
+                self.assign_address(*address, id);
+                Ok(id)
+            }
+            Payload::ID(_) | Payload::Actor(_) => Err(ErrorNumber::NotFound),
+        }
+    }
+}
+
+/// Syscalls for an actor running in a [`TestEnv`]
+#[derive(Clone)]
+pub struct TestSyscalls {
+    env: TestEnv,
+    receiver: ActorID,
+    caller: ActorID,
+    value_received: TokenAmount,
+}
+
+impl Syscalls for TestSyscalls {
+    fn root(&self) -> Result<Cid, NoStateError> {
+        self.env.root(self.receiver).ok_or(NoStateError)
+    }
+
+    fn set_root(&self, cid: &Cid) -> Result<(), NoStateError> {
+        self.env.state.borrow_mut().roots.insert(self.receiver, *cid);
+        Ok(())
+    }
+
+    fn receiver(&self) -> ActorID {
+        self.receiver
+    }
+
+    fn caller(&self) -> ActorID {
+        self.caller
+    }
+
+    fn curr_epoch(&self) -> ChainEpoch {
+        self.env.state.borrow().curr_epoch
+    }
+
+    fn tipset_timestamp(&self) -> u64 {
+        fake_tipset_timestamp(self.curr_epoch())
+    }
+
+    fn base_fee(&self) -> TokenAmount {
+        self.env.state.borrow().base_fee.clone()
+    }
+
+    fn network_version(&self) -> NetworkVersion {
+        self.env.state.borrow().network_version
+    }
+
+    fn chain_id(&self) -> u64 {
+        FAKE_CHAIN_ID
+    }
+
+    fn get_chain_randomness(
+        &self,
+        epoch: ChainEpoch,
+    ) -> Result<[u8; RANDOMNESS_LENGTH], ErrorNumber> {
+        fake_chain_randomness(epoch, self.curr_epoch())
+    }
+
+    fn value_received(&self) -> TokenAmount {
+        self.value_received.clone()
+    }
+
+    fn current_balance(&self) -> TokenAmount {
+        self.env.balance(self.receiver)
+    }
+
+    fn balance_of(&self, actor_id: ActorID) -> Option<TokenAmount> {
+        let state = self.env.state.borrow();
+        state.exists(actor_id).then(|| state.balances.get(&actor_id).cloned().unwrap_or_default())
+    }
+
+    fn send(
+        &self,
+        to: &Address,
+        method: MethodNum,
+        params: Option<IpldBlock>,
+        value: TokenAmount,
+    ) -> Result<Response, ErrorNumber> {
+        self.env.send(self.receiver, to, method, params, value)
+    }
+
+    fn resolve_address(&self, addr: &Address) -> Option<ActorID> {
+        match addr.payload() {
+            Payload::ID(id) => Some(*id),
+            _ => self.env.resolve(addr),
+        }
+    }
+
+    fn lookup_delegated_address(&self, actor_id: ActorID) -> Option<Address> {
+        self.env.state.borrow().addresses.iter().find_map(|(addr, id)| {
+            (*id == actor_id && addr.protocol() == Protocol::Delegated).then_some(*addr)
+        })
+    }
+
+    fn emit_event(&self, event: &ActorEvent) -> Result<(), ErrorNumber> {
+        self.env.state.borrow_mut().events.push((self.receiver, event.clone()));
+        Ok(())
+    }
+
+    fn recover_secp_public_key(
+        &self,
+        digest: &[u8; 32],
+        signature: &[u8; SECP_SIG_LEN],
+    ) -> Result<[u8; SECP_PUB_LEN], ErrorNumber> {
+        fake_recover_secp_public_key(digest, signature)
+    }
+
+    fn verify_bls(
+        &self,
+        signature: &[u8; BLS_SIG_LEN],
+        public_key: &[u8; BLS_PUB_LEN],
+        message: &[u8],
+    ) -> Result<bool, ErrorNumber> {
+        Ok(fake_verify_bls(signature, public_key, message))
+    }
+
+    fn read_only(&self) -> bool {
+        self.env.state.borrow().read_only
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_ipld_encoding::CborStore;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::{ErrorNumber, ExitCode};
+    use fvm_shared::{ActorID, MethodNum, METHOD_SEND};
+
+    use super::{NativeActor, TestEnv, TestRuntime};
+    use crate::events::EventBuilder;
+
+    const INCREMENT: MethodNum = 2;
+    const INCREMENT_AND_ABORT: MethodNum = 3;
+    const CALL_SELF: MethodNum = 4;
+
+    /// Keeps a counter as its state and forwards calls to another counter
+    struct Counter {
+        forward_to: Option<ActorID>,
+    }
+
+    fn count(runtime: &TestRuntime) -> u64 {
+        match runtime.root_cid() {
+            Ok(root) => runtime.blockstore.get_cbor(&root).unwrap().unwrap(),
+            Err(_) => 0,
+        }
+    }
+
+    impl NativeActor for Counter {
+        fn invoke(
+            &self,
+            runtime: &TestRuntime,
+            method: MethodNum,
+            _params: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ExitCode> {
+            match method {
+                INCREMENT | INCREMENT_AND_ABORT => {
+                    let count = count(runtime) + 1;
+                    let root = runtime.blockstore.put_cbor(&count, Code::Blake2b256).unwrap();
+                    runtime.set_root(&root).unwrap();
+                    runtime
+                        .emit_event(&EventBuilder::new().typ("increment").build().unwrap())
+                        .unwrap();
+                    if let Some(to) = self.forward_to {
+                        let res = runtime
+                            .send(&Address::new_id(to), method, None, TokenAmount::default())
+                            .unwrap();
+                        if !res.exit_code.is_success() {
+                            return Err(res.exit_code);
+                        }
+                    }
+                    if method == INCREMENT_AND_ABORT && self.forward_to.is_none() {
+                        return Err(ExitCode::USR_ILLEGAL_STATE);
+                    }
+                    Ok(IpldBlock::serialize_cbor(&count).unwrap())
+                }
+                CALL_SELF => {
+                    let res = runtime.send(
+                        &Address::new_id(runtime.actor_id()),
+                        CALL_SELF,
+                        None,
+                        TokenAmount::default(),
+                    );
+                    match res {
+                        Ok(res) if res.exit_code.is_success() => Ok(res.return_data),
+                        Ok(res) => Err(res.exit_code),
+                        Err(ErrorNumber::LimitExceeded) => {
+                            Ok(IpldBlock::serialize_cbor(&"limit").unwrap())
+                        }
+                        Err(_) => Err(ExitCode::USR_ASSERTION_FAILED),
+                    }
+                }
+                _ => Err(ExitCode::USR_UNHANDLED_MESSAGE),
+            }
+        }
+    }
+
+    #[test]
+    fn it_routes_messages_between_actors() {
+        let env = TestEnv::new();
+        let alice = env.create_account();
+        let inner = env.register(Counter { forward_to: None });
+        let outer = env.register(Counter { forward_to: Some(inner) });
+
+        let res = env
+            .call(alice, &Address::new_id(outer), INCREMENT, None, TokenAmount::default())
+            .unwrap();
+        assert_eq!(res.exit_code, ExitCode::OK);
+        assert_eq!(count(&env.runtime(outer, alice)), 1);
+        assert_eq!(count(&env.runtime(inner, alice)), 1);
+        assert_eq!(env.events().len(), 2);
+
+        // the inner call aborts, reverting the changes made by both actors
+        let res = env
+            .call(alice, &Address::new_id(outer), INCREMENT_AND_ABORT, None, TokenAmount::default())
+            .unwrap();
+        assert_eq!(res.exit_code, ExitCode::USR_ILLEGAL_STATE);
+        assert_eq!(count(&env.runtime(outer, alice)), 1);
+        assert_eq!(count(&env.runtime(inner, alice)), 1);
+        assert_eq!(env.events().len(), 2);
+
+        // unknown actors and methods
+        let err = env.call(alice, &Address::new_id(999), INCREMENT, None, TokenAmount::default());
+        assert_eq!(err.unwrap_err(), ErrorNumber::NotFound);
+        let res =
+            env.call(alice, &Address::new_id(outer), 99, None, TokenAmount::default()).unwrap();
+        assert_eq!(res.exit_code, ExitCode::USR_UNHANDLED_MESSAGE);
+    }
+
+    #[test]
+    fn it_transfers_value() {
+        let env = TestEnv::new();
+        let alice = env.create_account();
+        let counter = env.register(Counter { forward_to: None });
+        env.set_balance(alice, TokenAmount::from_atto(100));
+
+        let to = Address::new_id(counter);
+        env.call(alice, &to, INCREMENT, None, TokenAmount::from_atto(30)).unwrap();
+        assert_eq!(env.balance(alice), TokenAmount::from_atto(70));
+        assert_eq!(env.balance(counter), TokenAmount::from_atto(30));
+
+        // value sent with an aborted call is returned
+        env.call(alice, &to, INCREMENT_AND_ABORT, None, TokenAmount::from_atto(30)).unwrap();
+        assert_eq!(env.balance(alice), TokenAmount::from_atto(70));
+
+        let err = env.call(alice, &to, INCREMENT, None, TokenAmount::from_atto(71)).unwrap_err();
+        assert_eq!(err, ErrorNumber::InsufficientFunds);
+
+        // sending to a new key address creates an account for it
+        let bob = Address::new_secp256k1(&[1; 65]).unwrap();
+        env.call(alice, &bob, METHOD_SEND, None, TokenAmount::from_atto(10)).unwrap();
+        let bob_id = env.runtime(alice, alice).resolve_id(&bob).unwrap();
+        assert_eq!(env.balance(bob_id), TokenAmount::from_atto(10));
+    }
+
+    #[test]
+    fn it_limits_call_depth() {
+        let env = TestEnv::new();
+        let alice = env.create_account();
+        let counter = env.register(Counter { forward_to: None });
+        env.set_max_depth(16);
+
+        let res = env
+            .call(alice, &Address::new_id(counter), CALL_SELF, None, TokenAmount::default())
+            .unwrap();
+        assert_eq!(res.return_data.unwrap().deserialize::<String>().unwrap(), "limit");
+        assert_eq!(env.state.borrow().depth, 0);
+    }
+}
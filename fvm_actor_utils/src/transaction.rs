@@ -0,0 +1,134 @@
+//! Atomic modification of actor state
+//!
+//! State that implements [`Transactional`] can be changed through a copy which replaces the
+//! original only once all changes have succeeded, so that a failure part-way through an operation
+//! leaves no partial changes behind. State held in a blockstore (such as HAMT roots) is covered as
+//! long as the state only refers to it by CID.
+use std::ops::{Deref, DerefMut};
+
+/// State that can be modified atomically
+///
+/// Implementing types only need to be [`Clone`]; cloning should be cheap, which is the case for
+/// state that keeps its collections in the blockstore.
+pub trait Transactional: Clone {
+    /// Runs `f` on a copy of the state, replacing the state with the copy only if `f` succeeds
+    ///
+    /// Transactions may be nested by opening another transaction within `f`, in which case an
+    /// error from the inner transaction is discarded by it alone unless it is propagated.
+    fn transaction<F, R, E>(&mut self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut Self) -> Result<R, E>,
+    {
+        let mut tx = self.begin();
+        let res = f(&mut tx)?;
+        tx.commit();
+        Ok(res)
+    }
+
+    /// Begins a transaction which is committed or aborted explicitly
+    ///
+    /// The transaction is aborted if it is dropped without being committed.
+    fn begin(&mut self) -> Transaction<'_, Self> {
+        Transaction { working: self.clone(), target: self }
+    }
+}
+
+/// An open transaction on some state, see [`Transactional::begin`]
+///
+/// Dereferences to the working copy of the state. Nested transactions are opened by calling
+/// [`begin`](Transactional::begin) or [`transaction`](Transactional::transaction) through it.
+#[must_use = "a transaction is aborted unless it is committed"]
+pub struct Transaction<'a, T: Transactional> {
+    target: &'a mut T,
+    working: T,
+}
+
+impl<T: Transactional> Transaction<'_, T> {
+    /// Replaces the original state with the working copy
+    pub fn commit(self) {
+        *self.target = self.working;
+    }
+
+    /// Discards the working copy, leaving the original state unchanged
+    pub fn abort(self) {}
+
+    /// Returns the state as it was when the transaction began
+    pub fn original(&self) -> &T {
+        self.target
+    }
+}
+
+impl<T: Transactional> Deref for Transaction<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.working
+    }
+}
+
+impl<T: Transactional> DerefMut for Transaction<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.working
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Transactional;
+
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    struct Counters {
+        a: u64,
+        b: u64,
+    }
+
+    impl Transactional for Counters {}
+
+    #[test]
+    fn it_commits_only_on_success() {
+        let mut state = Counters::default();
+        state
+            .transaction(|s| {
+                s.a += 1;
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(state, Counters { a: 1, b: 0 });
+
+        state
+            .transaction(|s| {
+                s.a += 1;
+                Err(())
+            })
+            .unwrap_err();
+        assert_eq!(state, Counters { a: 1, b: 0 });
+    }
+
+    #[test]
+    fn it_supports_explicit_and_nested_transactions() {
+        let mut state = Counters::default();
+
+        let mut outer = state.begin();
+        outer.a = 1;
+        {
+            // the failed inner transaction is discarded without aborting the outer one
+            let res: Result<(), ()> = outer.transaction(|s| {
+                s.b = 1;
+                Err(())
+            });
+            assert!(res.is_err());
+            let mut inner = outer.begin();
+            inner.b = 2;
+            assert_eq!(inner.original().a, 1);
+            inner.commit();
+        }
+        assert_eq!(outer.original(), &Counters::default());
+        outer.commit();
+        assert_eq!(state, Counters { a: 1, b: 2 });
+
+        let mut tx = state.begin();
+        tx.a = 5;
+        tx.abort();
+        assert_eq!(state, Counters { a: 1, b: 2 });
+    }
+}
@@ -1,15 +1,33 @@
+use std::rc::Rc;
+
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_ipld_encoding::de::DeserializeOwned;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::ser::Serialize;
+use fvm_ipld_encoding::RawBytes;
 use fvm_shared::METHOD_SEND;
-use fvm_shared::{address::Address, econ::TokenAmount, error::ExitCode, ActorID};
-use fvm_shared::{MethodNum, Response};
+use fvm_shared::{
+    address::{Address, Payload},
+    clock::ChainEpoch,
+    econ::TokenAmount,
+    error::{ErrorNumber, ExitCode},
+    randomness::RANDOMNESS_LENGTH,
+    version::NetworkVersion,
+    ActorID,
+};
+use fvm_shared::{event::ActorEvent, MethodNum, Response};
 use num_traits::Zero;
 use thiserror::Error;
 
+use crate::crypto::{self, Digest};
+use crate::gas::{GasMeter, MeteredBlockstore, PriceTable};
+use crate::init::{
+    Exec4Params, ExecParams, ExecReturn, EXEC4_METHOD, EXEC_METHOD, INIT_ACTOR_ADDR,
+};
 use crate::messaging::{Messaging, MessagingError, Result as MessagingResult};
-use crate::shared_blockstore::SharedMemoryBlockstore;
+use crate::shared_blockstore::{BlockstoreSnapshot, SharedMemoryBlockstore};
 use crate::syscalls::fake_syscalls::FakeSyscalls;
 use crate::syscalls::NoStateError;
 use crate::syscalls::Syscalls;
@@ -18,6 +36,8 @@ use crate::syscalls::Syscalls;
 pub enum ActorError {
     #[error("root state not found {0}")]
     NoState(#[from] NoStateError),
+    #[error("actor state cannot be modified in a read-only context")]
+    ReadOnly,
 }
 
 type ActorResult<T> = std::result::Result<T, ActorError>;
@@ -26,6 +46,7 @@ impl From<&ActorError> for ExitCode {
     fn from(error: &ActorError) -> Self {
         match error {
             ActorError::NoState(_) => ExitCode::USR_NOT_FOUND,
+            ActorError::ReadOnly => ExitCode::USR_READ_ONLY,
         }
     }
 }
@@ -59,6 +80,27 @@ impl<S: Syscalls, BS: Blockstore> ActorRuntime<S, BS> {
         }
     }
 
+    /// Creates a runtime for tests which charges gas for block operations, sends and events
+    ///
+    /// The syscalls and blockstore share a [`GasMeter`], which can be reached through either.
+    pub fn new_metered_test_runtime(
+        prices: PriceTable,
+    ) -> ActorRuntime<FakeSyscalls, MeteredBlockstore<MemoryBlockstore>> {
+        let meter = Rc::new(GasMeter::new(prices));
+        ActorRuntime {
+            syscalls: FakeSyscalls { gas_meter: Some(meter.clone()), ..Default::default() },
+            blockstore: MeteredBlockstore::new(MemoryBlockstore::default(), meter),
+        }
+    }
+
+    /// Borrows this runtime as a runtime over references to the same syscalls and blockstore
+    ///
+    /// Useful when a handle which takes its runtime by value (such as an NFT) is only needed briefly
+    /// by a wrapper that keeps ownership of the runtime
+    pub fn by_ref(&self) -> ActorRuntime<&S, &BS> {
+        ActorRuntime { syscalls: &self.syscalls, blockstore: &self.blockstore }
+    }
+
     /// Returns the address of the current actor as an ActorID
     pub fn actor_id(&self) -> ActorID {
         self.syscalls.receiver()
@@ -68,6 +110,101 @@ impl<S: Syscalls, BS: Blockstore> ActorRuntime<S, BS> {
         self.syscalls.caller()
     }
 
+    /// Returns the current epoch of the chain
+    pub fn curr_epoch(&self) -> ChainEpoch {
+        self.syscalls.curr_epoch()
+    }
+
+    /// Returns the value sent with the current message
+    pub fn value_received(&self) -> TokenAmount {
+        self.syscalls.value_received()
+    }
+
+    /// Returns the balance of the current actor
+    pub fn current_balance(&self) -> TokenAmount {
+        self.syscalls.current_balance()
+    }
+
+    /// Returns the timestamp of the current tipset, in seconds since the Unix epoch
+    pub fn tipset_timestamp(&self) -> u64 {
+        self.syscalls.tipset_timestamp()
+    }
+
+    /// Returns the base fee of the current tipset
+    pub fn base_fee(&self) -> TokenAmount {
+        self.syscalls.base_fee()
+    }
+
+    /// Returns the network version the message is being executed under
+    pub fn network_version(&self) -> NetworkVersion {
+        self.syscalls.network_version()
+    }
+
+    /// Returns the chain ID of the network, which EIP-712 signatures commit to
+    pub fn chain_id(&self) -> u64 {
+        self.syscalls.chain_id()
+    }
+
+    /// Returns randomness drawn from the ticket chain at the given epoch
+    pub fn chain_randomness(&self, epoch: ChainEpoch) -> MessagingResult<[u8; RANDOMNESS_LENGTH]> {
+        Ok(self.syscalls.get_chain_randomness(epoch)?)
+    }
+
+    /// Returns the value of FIL sent with the current message
+    ///
+    /// Equivalent to [`value_received`](Self::value_received), named to distinguish it from token
+    /// amounts in actors which handle both.
+    pub fn message_value_received(&self) -> TokenAmount {
+        self.syscalls.value_received()
+    }
+
+    /// Returns the FIL balance of the current actor
+    pub fn balance(&self) -> TokenAmount {
+        self.syscalls.current_balance()
+    }
+
+    /// Sends FIL from the current actor to an address, creating an account there if needed
+    ///
+    /// This is a plain value transfer: the recipient's code is not invoked.
+    pub fn transfer_fil(&self, to: &Address, amount: &TokenAmount) -> MessagingResult<()> {
+        self.send(to, METHOD_SEND, None, amount.clone())?;
+        Ok(())
+    }
+
+    /// Creates a new actor running `code_cid` by calling the init actor
+    ///
+    /// If `delegated_address` is given, the actor is also assigned that address. It must be an f4
+    /// address in the namespace of the current actor, as only the caller's namespace may be used.
+    pub fn create_actor(
+        &self,
+        code_cid: Cid,
+        constructor_params: RawBytes,
+        delegated_address: Option<&Address>,
+    ) -> MessagingResult<ExecReturn> {
+        match delegated_address.map(Address::payload) {
+            None => self.send_typed(
+                &INIT_ACTOR_ADDR,
+                EXEC_METHOD,
+                &ExecParams { code_cid, constructor_params },
+                TokenAmount::zero(),
+            ),
+            Some(Payload::Delegated(delegated)) if delegated.namespace() == self.actor_id() => {
+                let params = Exec4Params {
+                    code_cid,
+                    constructor_params,
+                    subaddress: RawBytes::new(delegated.subaddress().to_vec()),
+                };
+                self.send_typed(&INIT_ACTOR_ADDR, EXEC4_METHOD, &params, TokenAmount::zero())
+            }
+            Some(_) => Err(MessagingError::Syscall(ErrorNumber::IllegalArgument)),
+        }
+    }
+
+    /// Returns the balance of an actor, or None if it doesn't exist
+    pub fn balance_of(&self, actor_id: ActorID) -> Option<TokenAmount> {
+        self.syscalls.balance_of(actor_id)
+    }
+
     /// Sends a message to an actor
     pub fn send(
         &self,
@@ -79,6 +216,46 @@ impl<S: Syscalls, BS: Blockstore> ActorRuntime<S, BS> {
         Ok(self.syscalls.send(to, method, params, value)?)
     }
 
+    /// Sends a message with CBOR encoded parameters and decodes its return value, see
+    /// [`Messaging::send_typed`]
+    pub fn send_typed<P: Serialize, R: DeserializeOwned>(
+        &self,
+        to: &Address,
+        method: MethodNum,
+        params: &P,
+        value: TokenAmount,
+    ) -> MessagingResult<R> {
+        Messaging::send_typed(self, to, method, params, value)
+    }
+
+    /// Emits an actor event, see [`EventBuilder`](crate::events::EventBuilder) for building events
+    /// from typed fields
+    pub fn emit_event(&self, event: &ActorEvent) -> MessagingResult<()> {
+        Ok(self.syscalls.emit_event(event)?)
+    }
+
+    /// Checks that `signature` was made over `plaintext` by the key behind `signer`, see
+    /// [`crypto::verify_signature`]
+    pub fn verify_signature(
+        &self,
+        signer: &Address,
+        signature: &[u8],
+        plaintext: &[u8],
+    ) -> MessagingResult<bool> {
+        Ok(crypto::verify_signature(&self.syscalls, signer, signature, plaintext)?)
+    }
+
+    /// Checks that `signature` was made over `digest` by the key behind `signer`, see
+    /// [`crypto::verify_digest`]
+    pub fn verify_digest(
+        &self,
+        signer: &Address,
+        signature: &[u8],
+        digest: &Digest,
+    ) -> MessagingResult<bool> {
+        Ok(crypto::verify_digest(&self.syscalls, signer, signature, digest)?)
+    }
+
     /// Attempts to resolve the given address to its ID address form
     ///
     /// Returns MessagingError::AddressNotResolved if the address could not be resolved
@@ -86,9 +263,26 @@ impl<S: Syscalls, BS: Blockstore> ActorRuntime<S, BS> {
         self.syscalls.resolve_address(address).ok_or(MessagingError::AddressNotResolved(*address))
     }
 
+    /// Looks up the delegated (f4) address of an actor, such as the Ethereum-style address of an
+    /// EVM account
+    pub fn lookup_delegated_address(&self, actor_id: ActorID) -> Option<Address> {
+        self.syscalls.lookup_delegated_address(actor_id)
+    }
+
+    /// Returns the address an actor should be reported as in return values and events
+    ///
+    /// This is the delegated address if the actor has one, as that is the address EVM callers
+    /// know it by, and the ID address otherwise.
+    pub fn display_address(&self, actor_id: ActorID) -> Address {
+        self.lookup_delegated_address(actor_id).unwrap_or_else(|| Address::new_id(actor_id))
+    }
+
     /// Resolves an address to an ID address, sending a message to initialize an account there if
     /// it doesn't exist
     ///
+    /// Sending to a new delegated (f410) address creates a placeholder actor for it, which becomes
+    /// an EVM account once the key holder sends a message from it.
+    ///
     /// If the account cannot be created, this function returns MessagingError::AddressNotInitialized
     pub fn resolve_or_init(&self, address: &Address) -> MessagingResult<ActorID> {
         let id = match self.resolve_id(address) {
@@ -117,10 +311,46 @@ impl<S: Syscalls, BS: Blockstore> ActorRuntime<S, BS> {
     }
 
     /// Set the root cid of the actor's state
+    ///
+    /// Returns ActorError::ReadOnly without attempting the change if executing read-only.
     pub fn set_root(&self, cid: &Cid) -> ActorResult<()> {
+        self.assert_mutable()?;
         Ok(self.syscalls.set_root(cid).map_err(|_err| NoStateError)?)
     }
 
+    /// Commits `root` as the actor's state before running `calls`, which may call back into the
+    /// actor
+    ///
+    /// Returns the result of `calls` along with the state root afterwards if it was changed by a
+    /// call back into the actor, in which case state held in memory is stale and must be reloaded.
+    /// See [`SendQueue`](crate::send_queue::SendQueue) for deferring sends until this point.
+    pub fn call_after_commit<R, E: From<ActorError>>(
+        &self,
+        root: &Cid,
+        calls: impl FnOnce() -> Result<R, E>,
+    ) -> Result<(R, Option<Cid>), E> {
+        self.set_root(root)?;
+        let res = calls()?;
+        let current = self.root_cid()?;
+        Ok((res, (current != *root).then_some(current)))
+    }
+
+    /// Returns true if the actor is executing in a read-only context
+    pub fn read_only(&self) -> bool {
+        self.syscalls.read_only()
+    }
+
+    /// Returns ActorError::ReadOnly if the actor is executing in a read-only context
+    ///
+    /// Methods that modify state can call this before doing any work, to fail with a clear error
+    /// rather than when the change is finally committed.
+    pub fn assert_mutable(&self) -> ActorResult<()> {
+        if self.read_only() {
+            return Err(ActorError::ReadOnly);
+        }
+        Ok(())
+    }
+
     /// Attempts to compare two addresses, seeing if they would resolve to the same Actor without
     /// actually instantiating accounts for them
     ///
@@ -150,13 +380,40 @@ impl<S: Syscalls, BS: Blockstore> ActorRuntime<S, BS> {
     }
 }
 
+/// The state of a shared test runtime at some point in time, see [`ActorRuntime::snapshot`]
+#[derive(Clone, Debug)]
+pub struct RuntimeSnapshot {
+    syscalls: FakeSyscalls,
+    blockstore: BlockstoreSnapshot,
+}
+
+impl ActorRuntime<FakeSyscalls, SharedMemoryBlockstore> {
+    /// Captures the blockstore contents and fake syscall state (root, balances, addresses and so
+    /// on) so that tests can explore an operation and roll back afterwards
+    pub fn snapshot(&self) -> RuntimeSnapshot {
+        RuntimeSnapshot { syscalls: self.syscalls.clone(), blockstore: self.blockstore.snapshot() }
+    }
+
+    /// Rolls the runtime back to a snapshot
+    ///
+    /// The blockstore is shared, so clones of this runtime see the restored blocks, but their
+    /// syscalls are left unchanged.
+    pub fn restore(&mut self, snapshot: RuntimeSnapshot) {
+        self.syscalls = snapshot.syscalls;
+        self.blockstore.restore(snapshot.blockstore);
+    }
+}
+
 /// Convenience impl encapsulating the blockstore functionality
 impl<S: Syscalls, BS: Blockstore> Blockstore for ActorRuntime<S, BS> {
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
         self.blockstore.get(k)
     }
 
+    /// Fails with ActorError::ReadOnly if executing read-only, as blocks written then can never be
+    /// referenced by the actor's state
     fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.assert_mutable()?;
         self.blockstore.put_keyed(k, block)
     }
 }
@@ -0,0 +1,89 @@
+//! Addresses of every class, for testing how actors handle the addresses passed to them
+//!
+//! Actors resolve addresses with `resolve_or_init`, which behaves differently for each class. Any
+//! address of an existing actor resolves to its ID. Unused secp256k1 (f1) and BLS (f3) addresses
+//! have an account created for them, and unused delegated (f410) addresses a placeholder, while
+//! unused ID (f0) and actor (f2) addresses can't be initialized and fail to resolve.
+use fvm_shared::{
+    address::{Address, BLS_PUB_LEN, SECP_PUB_LEN},
+    ActorID,
+};
+
+/// The Ethereum Address Manager, whose namespace holds Ethereum-style (f410) addresses
+pub const EAM_ACTOR_ID: ActorID = 10;
+
+/// IDs from here on are never assigned by a harness
+const UNUSED_ID_BASE: ActorID = 1 << 40;
+
+/// The classes of Filecoin address
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressClass {
+    /// An actor ID (f0)
+    Id,
+    /// The hash of a secp256k1 public key (f1)
+    Secp256k1,
+    /// The hash of the message that created an actor (f2)
+    Actor,
+    /// A BLS public key (f3)
+    Bls,
+    /// An Ethereum-style address delegated by the Ethereum Address Manager (f410)
+    Delegated,
+}
+
+impl AddressClass {
+    /// Every class of address, in order of their protocol
+    pub const ALL: [AddressClass; 5] = [
+        AddressClass::Id,
+        AddressClass::Secp256k1,
+        AddressClass::Actor,
+        AddressClass::Bls,
+        AddressClass::Delegated,
+    ];
+
+    /// Returns true if sending to an unused address of this class creates an actor there
+    pub fn is_initializable(self) -> bool {
+        matches!(self, AddressClass::Secp256k1 | AddressClass::Bls | AddressClass::Delegated)
+    }
+
+    /// Returns an address of this class that no actor uses, which is distinct for each seed
+    ///
+    /// The key material is made up, so the addresses of accounts created here can send messages in
+    /// a harness, which doesn't check signatures, but not on a real network.
+    pub fn unused_address(self, seed: u8) -> Address {
+        match self {
+            AddressClass::Id => Address::new_id(UNUSED_ID_BASE + ActorID::from(seed)),
+            AddressClass::Secp256k1 => Address::new_secp256k1(&[seed; SECP_PUB_LEN]).unwrap(),
+            AddressClass::Actor => Address::new_actor(&[seed]),
+            AddressClass::Bls => Address::new_bls(&[seed; BLS_PUB_LEN]).unwrap(),
+            AddressClass::Delegated => Address::new_delegated(EAM_ACTOR_ID, &[seed; 20]).unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use fvm_shared::address::Protocol;
+
+    use super::AddressClass;
+
+    #[test]
+    fn it_makes_unused_addresses_of_each_class() {
+        let protocols = [
+            Protocol::ID,
+            Protocol::Secp256k1,
+            Protocol::Actor,
+            Protocol::BLS,
+            Protocol::Delegated,
+        ];
+        let mut addresses = HashSet::new();
+        for (class, protocol) in AddressClass::ALL.into_iter().zip(protocols) {
+            for seed in [1, 2] {
+                let address = class.unused_address(seed);
+                assert_eq!(address.protocol(), protocol);
+                assert!(addresses.insert(address), "{address} is not unique");
+            }
+        }
+    }
+}
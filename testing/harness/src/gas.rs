@@ -0,0 +1,97 @@
+//! Gas used by the calls made through a harness, grouped by actor and method
+//!
+//! Every message sent by a [`TestHarness`](crate::TestHarness), including the constructor calls of
+//! deployed actors, is recorded in its [`GasReport`]. If the `HELIX_GAS_REPORT_DIR` environment
+//! variable is set, the harness writes its report to a JSON file named after the test in that
+//! directory when it is dropped, so gas usage can be compared between runs:
+//!
+//! ```json
+//! {
+//!   "basic_token": {
+//!     "Mint": { "calls": 2, "total": 5204811, "min": 2481306, "max": 2723505 }
+//!   }
+//! }
+//! ```
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use serde::Serialize;
+
+/// Environment variable naming the directory in which harnesses write their gas reports
+pub const GAS_REPORT_DIR_VAR: &str = "HELIX_GAS_REPORT_DIR";
+
+/// Gas used by the calls to one method of an actor
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MethodGas {
+    pub calls: u64,
+    pub total: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl MethodGas {
+    /// Mean gas used per call
+    pub fn mean(&self) -> u64 {
+        self.total / self.calls
+    }
+}
+
+/// Gas used by calls, keyed by actor label and then by method name
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct GasReport {
+    actors: BTreeMap<String, BTreeMap<String, MethodGas>>,
+}
+
+impl GasReport {
+    /// Records the gas used by a call
+    pub fn record(&mut self, actor: &str, method: &str, gas_used: u64) {
+        let methods = self.actors.entry(actor.into()).or_default();
+        methods
+            .entry(method.into())
+            .and_modify(|gas| {
+                gas.calls += 1;
+                gas.total += gas_used;
+                gas.min = gas.min.min(gas_used);
+                gas.max = gas.max.max(gas_used);
+            })
+            .or_insert(MethodGas { calls: 1, total: gas_used, min: gas_used, max: gas_used });
+    }
+
+    /// Returns the gas used by calls to a method of an actor, if any were made
+    pub fn method(&self, actor: &str, method: &str) -> Option<MethodGas> {
+        self.actors.get(actor)?.get(method).copied()
+    }
+
+    /// Serializes the report as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("failed to serialize gas report")
+    }
+
+    /// Writes the report to a JSON file
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GasReport, MethodGas};
+
+    #[test]
+    fn it_groups_calls_by_actor_and_method() {
+        let mut report = GasReport::default();
+        report.record("token", "Mint", 300);
+        report.record("token", "Mint", 100);
+        report.record("token", "Transfer", 50);
+        report.record("receiver", "Constructor", 10);
+
+        let mint = report.method("token", "Mint").unwrap();
+        assert_eq!(mint, MethodGas { calls: 2, total: 400, min: 100, max: 300 });
+        assert_eq!(mint.mean(), 200);
+        assert_eq!(report.method("receiver", "Mint"), None);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["token"]["Transfer"]["total"], 50);
+        assert_eq!(json["receiver"]["Constructor"]["calls"], 1);
+    }
+}
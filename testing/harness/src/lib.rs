@@ -0,0 +1,311 @@
+//! Typed helpers for deploying and calling actors in integration tests
+//!
+//! [`TestHarness`] wraps the integration [`Tester`] so tests deal in actor addresses, method names
+//! and their own param and return types rather than method numbers and [`RawBytes`]. Method names
+//! are resolved to FRC-0042 method numbers, and each account's message sequence is tracked so any
+//! account can send messages. The gas used by each call is recorded in a [`GasReport`].
+use std::{collections::HashMap, env, fs, io::Write, path::Path, thread};
+
+use cid::Cid;
+use frc42_dispatch::hash::method_number;
+use fvm::executor::{ApplyKind, ApplyRet, Executor};
+use fvm::machine::Machine;
+use fvm_actor_utils::car::export_car;
+use fvm_integration_tests::{
+    bundle,
+    dummy::DummyExterns,
+    tester::{ExecutionOptions, Tester},
+};
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_ipld_encoding::{de::DeserializeOwned, ser::Serialize, RawBytes};
+use fvm_shared::{
+    address::Address, bigint::Zero, econ::TokenAmount, message::Message, state::StateTreeVersion,
+    version::NetworkVersion, ActorID, BLOCK_GAS_LIMIT,
+};
+
+pub mod addresses;
+pub mod gas;
+pub mod scenario;
+pub use addresses::AddressClass;
+pub use gas::{GasReport, MethodGas, GAS_REPORT_DIR_VAR};
+pub use scenario::{NFTScenario, TokenScenario};
+
+/// ID of the first actor deployed by a harness, with later actors numbered sequentially
+pub const FIRST_ACTOR_ID: ActorID = 10000;
+
+/// The CBOR encoding of null, which `()` serializes to
+const CBOR_NULL: &[u8] = &[0xf6];
+
+/// An integration tester with typed helpers for deploying and calling actors
+///
+/// Accounts and actors are installed in the initial state tree, so they must all be created before
+/// the first call. The first call starts the machine and then runs the constructors of the
+/// deployed actors, in the order they were deployed.
+pub struct TestHarness {
+    /// The underlying tester, for anything the harness doesn't cover
+    pub tester: Tester<MemoryBlockstore, DummyExterns>,
+    blockstore: MemoryBlockstore,
+    /// Account which constructs deployed actors and sends messages for [`call`](Self::call)
+    deployer: Address,
+    /// Constructor params of the deployed actors, awaiting the start of the machine
+    constructors: Vec<(Address, RawBytes)>,
+    /// The next message sequence number of each account that has sent a message
+    sequences: HashMap<Address, u64>,
+    next_actor_id: ActorID,
+    /// Names of actors in the gas report, which otherwise uses their address
+    labels: HashMap<Address, String>,
+    gas: GasReport,
+}
+
+impl Default for TestHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestHarness {
+    /// Creates a harness with the builtin actors bundle installed and a deployer account
+    pub fn new() -> Self {
+        let blockstore = MemoryBlockstore::default();
+        let bundle_root = bundle::import_bundle(&blockstore, actors_v12::BUNDLE_CAR).unwrap();
+        let mut tester =
+            Tester::new(NetworkVersion::V21, StateTreeVersion::V5, bundle_root, blockstore.clone())
+                .unwrap();
+        let [(_, deployer)] = tester.create_accounts().unwrap();
+
+        Self {
+            tester,
+            blockstore,
+            deployer,
+            constructors: Vec::new(),
+            sequences: HashMap::new(),
+            next_actor_id: FIRST_ACTOR_ID,
+            labels: HashMap::new(),
+            gas: GasReport::default(),
+        }
+    }
+
+    /// The blockstore backing the state tree, for inspecting actor state
+    pub fn blockstore(&self) -> &MemoryBlockstore {
+        &self.blockstore
+    }
+
+    /// The account which constructs deployed actors and sends messages for [`call`](Self::call)
+    pub fn deployer(&self) -> Address {
+        self.deployer
+    }
+
+    /// The gas used by the calls made so far
+    pub fn gas_report(&self) -> &GasReport {
+        &self.gas
+    }
+
+    /// Names an actor in the gas report, in place of its address
+    pub fn label_actor(&mut self, actor: Address, label: &str) {
+        self.labels.insert(actor, label.into());
+    }
+
+    /// Records the execution trace of every message in [`ApplyRet::exec_trace`], which is otherwise
+    /// empty
+    ///
+    /// Tracing is configured when the machine starts, so this must be called before the first call
+    /// unless tracing is already enabled.
+    pub fn enable_tracing(&mut self) {
+        if self.tester.options.as_ref().map_or(false, |options| options.trace) {
+            return;
+        }
+        assert!(self.tester.executor.is_none(), "tracing must be enabled before the first call");
+        self.tester.options = Some(ExecutionOptions { debug: false, trace: true, events: true });
+    }
+
+    /// Creates `N` accounts, returning their ID addresses
+    pub fn create_accounts<const N: usize>(&mut self) -> [Address; N] {
+        self.assert_not_started("accounts");
+        let accounts: [_; N] = self.tester.create_accounts().unwrap();
+        accounts.map(|(_, address)| address)
+    }
+
+    /// Deploys an actor from its wasm binary, returning its ID address
+    ///
+    /// The actor's constructor is called with `constructor_params` by the deployer account when the
+    /// machine starts, and must succeed. Actors whose constructor takes no params can be passed
+    /// `&()`.
+    pub fn deploy_actor<T: Serialize>(&mut self, wasm: &[u8], constructor_params: &T) -> Address {
+        let address = Address::new_id(self.next_actor_id);
+        self.next_actor_id += 1;
+        self.deploy_actor_at(wasm, address, constructor_params)
+    }
+
+    /// Deploys an actor from its wasm binary at the given address, such as an actor (f2) address
+    ///
+    /// The actor is constructed as in [`deploy_actor`](Self::deploy_actor), and is called at the
+    /// returned address. Use [`resolve`](Self::resolve) to find its ID once the machine has started.
+    pub fn deploy_actor_at<T: Serialize>(
+        &mut self,
+        wasm: &[u8],
+        address: Address,
+        constructor_params: &T,
+    ) -> Address {
+        self.assert_not_started("actors");
+        self.tester.set_actor_from_bin(wasm, Cid::default(), address, TokenAmount::zero()).unwrap();
+        self.constructors.push((address, encode_params(constructor_params)));
+        address
+    }
+
+    /// Resolves an address of any class to the ID of the actor using it, if there is one
+    ///
+    /// This starts the machine if it isn't already started.
+    pub fn resolve(&mut self, address: &Address) -> Option<ActorID> {
+        self.start();
+        self.tester.executor.as_ref().unwrap().state_tree().lookup_id(address).unwrap()
+    }
+
+    /// Writes the state tree of an actor to a CAR archive, returning the number of blocks written
+    ///
+    /// The archive's root is the actor's state root. Snapshots taken in a failing test can be
+    /// imported with `fvm_actor_utils::car::import_car_to_memory` to reproduce the state. Blocks
+    /// linked from the state but missing from the store are left out, see `export_car`.
+    pub fn export_state<W: Write>(&mut self, actor: &Address, writer: W) -> usize {
+        let id = self.resolve(actor).unwrap_or_else(|| panic!("{actor} does not exist"));
+        let state_tree = self.tester.executor.as_ref().unwrap().state_tree();
+        let state = state_tree.get_actor(id).unwrap().expect("actor not found").state;
+        export_car(state_tree.store(), &state, writer).unwrap().blocks
+    }
+
+    /// Calls a method on an actor from the deployer account, returning its decoded return value
+    ///
+    /// Panics if the call fails. Methods that take no params can be passed `&()`, and `()` can be
+    /// used as the return type of methods that return nothing.
+    pub fn call<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        actor: Address,
+        method: &str,
+        params: &P,
+    ) -> R {
+        self.call_from(self.deployer, actor, method, params)
+    }
+
+    /// Calls a method on an actor from the given account, returning its decoded return value
+    ///
+    /// Panics if the call fails.
+    pub fn call_from<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        from: Address,
+        actor: Address,
+        method: &str,
+        params: &P,
+    ) -> R {
+        let ret = self.apply(from, actor, method, params);
+        assert!(ret.msg_receipt.exit_code.is_success(), "call to {method} failed: {ret:#?}");
+        decode_return(&ret)
+    }
+
+    /// Calls a method on an actor from the given account, returning the result whether or not the
+    /// call succeeded
+    pub fn apply<P: Serialize>(
+        &mut self,
+        from: Address,
+        actor: Address,
+        method: &str,
+        params: &P,
+    ) -> ApplyRet {
+        self.start();
+        self.send(from, actor, method, encode_params(params))
+    }
+
+    /// Starts the machine and constructs the deployed actors, if not already started
+    fn start(&mut self) {
+        if self.tester.executor.is_some() {
+            return;
+        }
+        self.tester.instantiate_machine(DummyExterns).unwrap();
+        for (actor, params) in std::mem::take(&mut self.constructors) {
+            let ret = self.send(self.deployer, actor, "Constructor", params);
+            assert!(
+                ret.msg_receipt.exit_code.is_success(),
+                "constructing {actor} failed: {ret:#?}"
+            );
+        }
+    }
+
+    fn send(&mut self, from: Address, to: Address, method: &str, params: RawBytes) -> ApplyRet {
+        let sequence = self.sequences.entry(from).or_default();
+        let message = Message {
+            from,
+            to,
+            gas_limit: BLOCK_GAS_LIMIT,
+            method_num: method_number(method),
+            sequence: *sequence,
+            params,
+            ..Message::default()
+        };
+        *sequence += 1;
+        let ret = self
+            .tester
+            .executor
+            .as_mut()
+            .unwrap()
+            .execute_message(message, ApplyKind::Explicit, 100)
+            .unwrap();
+        let actor = self.labels.get(&to).cloned().unwrap_or_else(|| to.to_string());
+        self.gas.record(&actor, method, ret.msg_receipt.gas_used);
+        ret
+    }
+
+    fn assert_not_started(&self, what: &str) {
+        assert!(self.tester.executor.is_none(), "{what} must be created before the first call");
+    }
+}
+
+impl Drop for TestHarness {
+    /// Writes the gas report to the directory named by `HELIX_GAS_REPORT_DIR`, if set
+    ///
+    /// The report is named after the test running the harness. Nothing is written for failed tests.
+    fn drop(&mut self) {
+        let Some(dir) = env::var_os(GAS_REPORT_DIR_VAR) else { return };
+        if thread::panicking() {
+            return;
+        }
+        let test = thread::current().name().unwrap_or("harness").replace("::", "-");
+        let path = Path::new(&dir).join(format!("{test}.json"));
+        if let Err(e) = fs::create_dir_all(&dir).and_then(|_| self.gas.write_json(&path)) {
+            eprintln!("failed to write gas report to {}: {e}", path.display());
+        }
+    }
+}
+
+/// Serializes params for a message, sending `()` as no params at all
+fn encode_params<P: Serialize>(params: &P) -> RawBytes {
+    let bytes = fvm_ipld_encoding::to_vec(params).expect("failed to serialize params");
+    if bytes == CBOR_NULL {
+        RawBytes::default()
+    } else {
+        RawBytes::new(bytes)
+    }
+}
+
+/// Deserializes the return value of a successful call, reading no data as `()`
+fn decode_return<R: DeserializeOwned>(ret: &ApplyRet) -> R {
+    let data = ret.msg_receipt.return_data.bytes();
+    let data = if data.is_empty() { CBOR_NULL } else { data };
+    fvm_ipld_encoding::from_slice(data)
+        .unwrap_or_else(|e| panic!("failed to deserialize return value: {e}"))
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, econ::TokenAmount};
+
+    use super::encode_params;
+
+    #[test]
+    fn it_encodes_unit_params_as_no_params() {
+        assert!(encode_params(&()).is_empty());
+
+        let address = Address::new_id(1);
+        assert_eq!(encode_params(&address), RawBytes::serialize(address).unwrap());
+        let amount = TokenAmount::from_whole(1);
+        assert_eq!(encode_params(&amount), RawBytes::serialize(amount).unwrap());
+    }
+}
@@ -0,0 +1,472 @@
+//! Declarative scenarios of token and NFT flows between several actors
+//!
+//! A scenario is a list of steps, each sent as a message from the acting account, followed by
+//! expectations of the final state. Running it asserts that every step succeeded (or failed, for
+//! steps marked with `fails`), checks the expected balances, allowances and owners, and compares
+//! the receiver hooks called along the way with those expected:
+//!
+//! ```ignore
+//! TokenScenario::new(token, minter)
+//!     .mint(alice, 100)
+//!     .approve(alice, bob, 50)
+//!     .transfer_from(bob, alice, carol, 30)
+//!     .expect_balance(alice, 70)
+//!     .expect_balance(carol, 30)
+//!     .expect_allowance(alice, bob, 20)
+//!     .expect_hook(alice, 100)
+//!     .expect_hook(carol, 30)
+//!     .run(&mut harness);
+//! ```
+//!
+//! Scenarios call the methods of the FRC-46 and FRC-53 test actors by name, so they work with any
+//! actor exposing the same methods. Hooks are read from the execution trace of each message, which
+//! the harness must record from its first call, so scenarios expecting hooks must run before any
+//! other calls are made or after [`TestHarness::enable_tracing`].
+use frc46_token::receiver::{FRC46TokenReceived, FRC46_TOKEN_TYPE};
+use frc46_token::token::types::{
+    BurnFromParams, BurnParams, GetAllowanceParams, IncreaseAllowanceParams, RevokeAllowanceParams,
+    TransferFromParams, TransferParams,
+};
+use frc53_nft::receiver::{FRC53TokenReceived, FRC53_TOKEN_TYPE};
+use frc53_nft::types::{
+    ApproveForAllParams, ApproveParams, TokenID, TransferFromParams as NFTTransferFromParams,
+    TransferParams as NFTTransferParams,
+};
+use fvm::executor::ApplyRet;
+use fvm::trace::ExecutionEvent;
+use fvm_actor_utils::messaging::RECEIVER_HOOK_METHOD_NUM;
+use fvm_actor_utils::receiver::{ReceiverType, UniversalReceiverParams};
+use fvm_ipld_encoding::de::DeserializeOwned;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::{address::Address, econ::TokenAmount, ActorID};
+use serde_tuple::{Deserialize_tuple, Serialize_tuple};
+
+use crate::TestHarness;
+
+// The mint params of the test actors are duplicated here, as they can't be depended on from here
+
+/// Params of the `Mint` method of the FRC-46 test token actors
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+struct TokenMintParams {
+    initial_owner: Address,
+    amount: TokenAmount,
+    operator_data: RawBytes,
+}
+
+/// Params of the `Mint` method of the FRC-53 test NFT actors
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+struct NFTMintParams {
+    initial_owner: Address,
+    metadata: Vec<String>,
+    operator_data: RawBytes,
+}
+
+/// An action of a scenario, and whether it should succeed
+#[derive(Debug)]
+struct Step<A> {
+    action: A,
+    succeeds: bool,
+}
+
+/// Asserts that a step succeeded or failed as expected
+fn assert_outcome<A: std::fmt::Debug>(step: &Step<A>, ret: &ApplyRet) {
+    let outcome = if step.succeeds { "failed" } else { "succeeded" };
+    assert_eq!(
+        ret.msg_receipt.exit_code.is_success(),
+        step.succeeds,
+        "step {:?} unexpectedly {outcome}: {ret:#?}",
+        step.action
+    );
+}
+
+/// Returns the payloads of the receiver hooks of the given type called while applying a message
+fn hooks<T: DeserializeOwned>(ret: &ApplyRet, type_: ReceiverType) -> Vec<T> {
+    ret.exec_trace
+        .iter()
+        .filter_map(|event| match event {
+            ExecutionEvent::Call { method, params: Some(params), .. }
+                if *method == RECEIVER_HOOK_METHOD_NUM =>
+            {
+                let params: UniversalReceiverParams = params.deserialize().ok()?;
+                (params.type_ == type_).then(|| params.payload.deserialize().unwrap())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Resolves the addresses in a scenario's expectations to the IDs that hooks report
+fn resolve(harness: &mut TestHarness, address: &Address) -> ActorID {
+    harness.resolve(address).unwrap_or_else(|| panic!("{address} does not exist"))
+}
+
+#[derive(Debug)]
+enum TokenAction {
+    Mint { to: Address, amount: u64 },
+    Transfer { from: Address, to: Address, amount: u64 },
+    TransferFrom { operator: Address, from: Address, to: Address, amount: u64 },
+    Approve { owner: Address, operator: Address, amount: u64 },
+    Revoke { owner: Address, operator: Address },
+    Burn { owner: Address, amount: u64 },
+    BurnFrom { operator: Address, owner: Address, amount: u64 },
+}
+
+/// A scenario of FRC-46 token transfers, with amounts given in atto
+pub struct TokenScenario {
+    token: Address,
+    minter: Address,
+    steps: Vec<Step<TokenAction>>,
+    balances: Vec<(Address, u64)>,
+    allowances: Vec<(Address, Address, u64)>,
+    supply: Option<u64>,
+    hooks: Option<Vec<(Address, u64)>>,
+}
+
+impl TokenScenario {
+    /// Starts a scenario with a token, which mints tokens when called by `minter`
+    pub fn new(token: Address, minter: Address) -> Self {
+        Self {
+            token,
+            minter,
+            steps: Vec::new(),
+            balances: Vec::new(),
+            allowances: Vec::new(),
+            supply: None,
+            hooks: None,
+        }
+    }
+
+    fn step(mut self, action: TokenAction) -> Self {
+        self.steps.push(Step { action, succeeds: true });
+        self
+    }
+
+    /// The minter mints tokens to an address
+    pub fn mint(self, to: Address, amount: u64) -> Self {
+        self.step(TokenAction::Mint { to, amount })
+    }
+
+    /// An address transfers tokens from its own balance
+    pub fn transfer(self, from: Address, to: Address, amount: u64) -> Self {
+        self.step(TokenAction::Transfer { from, to, amount })
+    }
+
+    /// An operator transfers tokens from another address, using its allowance
+    pub fn transfer_from(self, operator: Address, from: Address, to: Address, amount: u64) -> Self {
+        self.step(TokenAction::TransferFrom { operator, from, to, amount })
+    }
+
+    /// An owner increases the allowance of an operator
+    pub fn approve(self, owner: Address, operator: Address, amount: u64) -> Self {
+        self.step(TokenAction::Approve { owner, operator, amount })
+    }
+
+    /// An owner revokes the allowance of an operator
+    pub fn revoke(self, owner: Address, operator: Address) -> Self {
+        self.step(TokenAction::Revoke { owner, operator })
+    }
+
+    /// An address burns tokens from its own balance
+    pub fn burn(self, owner: Address, amount: u64) -> Self {
+        self.step(TokenAction::Burn { owner, amount })
+    }
+
+    /// An operator burns tokens from another address, using its allowance
+    pub fn burn_from(self, operator: Address, owner: Address, amount: u64) -> Self {
+        self.step(TokenAction::BurnFrom { operator, owner, amount })
+    }
+
+    /// Expects the previous step to fail, leaving the state unchanged
+    pub fn fails(mut self) -> Self {
+        self.steps.last_mut().expect("no step to fail").succeeds = false;
+        self
+    }
+
+    /// Expects an address to hold a balance at the end of the scenario
+    pub fn expect_balance(mut self, holder: Address, amount: u64) -> Self {
+        self.balances.push((holder, amount));
+        self
+    }
+
+    /// Expects an operator to have an allowance from an owner at the end of the scenario
+    pub fn expect_allowance(mut self, owner: Address, operator: Address, amount: u64) -> Self {
+        self.allowances.push((owner, operator, amount));
+        self
+    }
+
+    /// Expects the total supply at the end of the scenario
+    pub fn expect_supply(mut self, amount: u64) -> Self {
+        self.supply = Some(amount);
+        self
+    }
+
+    /// Expects the next receiver hook called to be for an amount sent to an address
+    ///
+    /// Once a hook is expected, the hooks called must match those expected exactly and in order,
+    /// including those called by steps which fail.
+    pub fn expect_hook(mut self, to: Address, amount: u64) -> Self {
+        self.hooks.get_or_insert_with(Vec::new).push((to, amount));
+        self
+    }
+
+    /// Sends the message of each step, and then checks the expectations
+    pub fn run(self, harness: &mut TestHarness) {
+        let atto = TokenAmount::from_atto;
+        if self.hooks.is_some() {
+            harness.enable_tracing();
+        }
+
+        let mut called_hooks: Vec<FRC46TokenReceived> = Vec::new();
+        for step in &self.steps {
+            let token = self.token;
+            let ret = match step.action {
+                TokenAction::Mint { to, amount } => {
+                    let params = TokenMintParams {
+                        initial_owner: to,
+                        amount: atto(amount),
+                        operator_data: RawBytes::default(),
+                    };
+                    harness.apply(self.minter, token, "Mint", &params)
+                }
+                TokenAction::Transfer { from, to, amount } => {
+                    let params = TransferParams {
+                        to,
+                        amount: atto(amount),
+                        operator_data: RawBytes::default(),
+                    };
+                    harness.apply(from, token, "Transfer", &params)
+                }
+                TokenAction::TransferFrom { operator, from, to, amount } => {
+                    let params = TransferFromParams {
+                        from,
+                        to,
+                        amount: atto(amount),
+                        operator_data: RawBytes::default(),
+                    };
+                    harness.apply(operator, token, "TransferFrom", &params)
+                }
+                TokenAction::Approve { owner, operator, amount } => {
+                    let params = IncreaseAllowanceParams { operator, increase: atto(amount) };
+                    harness.apply(owner, token, "IncreaseAllowance", &params)
+                }
+                TokenAction::Revoke { owner, operator } => {
+                    let params = RevokeAllowanceParams { operator };
+                    harness.apply(owner, token, "RevokeAllowance", &params)
+                }
+                TokenAction::Burn { owner, amount } => {
+                    harness.apply(owner, token, "Burn", &BurnParams { amount: atto(amount) })
+                }
+                TokenAction::BurnFrom { operator, owner, amount } => {
+                    let params = BurnFromParams { owner, amount: atto(amount) };
+                    harness.apply(operator, token, "BurnFrom", &params)
+                }
+            };
+            assert_outcome(step, &ret);
+            called_hooks.extend(hooks(&ret, FRC46_TOKEN_TYPE));
+        }
+
+        for (holder, amount) in self.balances {
+            let balance: TokenAmount = harness.call(self.token, "BalanceOf", &holder);
+            assert_eq!(balance, atto(amount), "balance of {holder}");
+        }
+        for (owner, operator, amount) in self.allowances {
+            let params = GetAllowanceParams { owner, operator };
+            let allowance: TokenAmount = harness.call(self.token, "Allowance", &params);
+            assert_eq!(allowance, atto(amount), "allowance of {operator} from {owner}");
+        }
+        if let Some(amount) = self.supply {
+            let supply: TokenAmount = harness.call(self.token, "TotalSupply", &());
+            assert_eq!(supply, atto(amount), "total supply");
+        }
+        if let Some(expected) = self.hooks {
+            let expected: Vec<_> =
+                expected.iter().map(|(to, amount)| (resolve(harness, to), atto(*amount))).collect();
+            let called: Vec<_> = called_hooks.into_iter().map(|h| (h.to, h.amount)).collect();
+            assert_eq!(called, expected, "receiver hooks called");
+        }
+    }
+}
+
+#[derive(Debug)]
+enum NFTAction {
+    Mint { to: Address, count: usize },
+    Transfer { from: Address, to: Address, token_ids: Vec<TokenID> },
+    TransferFrom { operator: Address, from: Address, to: Address, token_ids: Vec<TokenID> },
+    Approve { owner: Address, operator: Address, token_ids: Vec<TokenID> },
+    ApproveForAll { owner: Address, operator: Address },
+    Burn { owner: Address, token_ids: Vec<TokenID> },
+}
+
+/// A scenario of FRC-53 NFT transfers
+pub struct NFTScenario {
+    nft: Address,
+    minter: Address,
+    steps: Vec<Step<NFTAction>>,
+    owners: Vec<(TokenID, Address)>,
+    balances: Vec<(Address, u64)>,
+    supply: Option<u64>,
+    hooks: Option<Vec<(Address, Vec<TokenID>)>>,
+}
+
+impl NFTScenario {
+    /// Starts a scenario with an NFT collection, which mints tokens when called by `minter`
+    pub fn new(nft: Address, minter: Address) -> Self {
+        Self {
+            nft,
+            minter,
+            steps: Vec::new(),
+            owners: Vec::new(),
+            balances: Vec::new(),
+            supply: None,
+            hooks: None,
+        }
+    }
+
+    fn step(mut self, action: NFTAction) -> Self {
+        self.steps.push(Step { action, succeeds: true });
+        self
+    }
+
+    /// The minter mints a number of tokens to an address, which get the next token IDs
+    pub fn mint(self, to: Address, count: usize) -> Self {
+        self.step(NFTAction::Mint { to, count })
+    }
+
+    /// An owner transfers its tokens
+    pub fn transfer(self, from: Address, to: Address, token_ids: &[TokenID]) -> Self {
+        self.step(NFTAction::Transfer { from, to, token_ids: token_ids.to_vec() })
+    }
+
+    /// An operator transfers tokens from their owner
+    pub fn transfer_from(
+        self,
+        operator: Address,
+        from: Address,
+        to: Address,
+        token_ids: &[TokenID],
+    ) -> Self {
+        self.step(NFTAction::TransferFrom { operator, from, to, token_ids: token_ids.to_vec() })
+    }
+
+    /// An owner approves an operator for some of its tokens
+    pub fn approve(self, owner: Address, operator: Address, token_ids: &[TokenID]) -> Self {
+        self.step(NFTAction::Approve { owner, operator, token_ids: token_ids.to_vec() })
+    }
+
+    /// An owner approves an operator for all of its tokens
+    pub fn approve_for_all(self, owner: Address, operator: Address) -> Self {
+        self.step(NFTAction::ApproveForAll { owner, operator })
+    }
+
+    /// An owner burns its tokens
+    pub fn burn(self, owner: Address, token_ids: &[TokenID]) -> Self {
+        self.step(NFTAction::Burn { owner, token_ids: token_ids.to_vec() })
+    }
+
+    /// Expects the previous step to fail, leaving the state unchanged
+    pub fn fails(mut self) -> Self {
+        self.steps.last_mut().expect("no step to fail").succeeds = false;
+        self
+    }
+
+    /// Expects a token to be owned by an address at the end of the scenario
+    pub fn expect_owner(mut self, token_id: TokenID, owner: Address) -> Self {
+        self.owners.push((token_id, owner));
+        self
+    }
+
+    /// Expects an address to own a number of tokens at the end of the scenario
+    pub fn expect_balance(mut self, owner: Address, balance: u64) -> Self {
+        self.balances.push((owner, balance));
+        self
+    }
+
+    /// Expects the total supply at the end of the scenario
+    pub fn expect_supply(mut self, supply: u64) -> Self {
+        self.supply = Some(supply);
+        self
+    }
+
+    /// Expects the next receiver hook called to be for tokens sent to an address
+    ///
+    /// Once a hook is expected, the hooks called must match those expected exactly and in order,
+    /// including those called by steps which fail.
+    pub fn expect_hook(mut self, to: Address, token_ids: &[TokenID]) -> Self {
+        self.hooks.get_or_insert_with(Vec::new).push((to, token_ids.to_vec()));
+        self
+    }
+
+    /// Sends the message of each step, and then checks the expectations
+    pub fn run(self, harness: &mut TestHarness) {
+        if self.hooks.is_some() {
+            harness.enable_tracing();
+        }
+
+        let mut called_hooks: Vec<FRC53TokenReceived> = Vec::new();
+        for step in &self.steps {
+            let nft = self.nft;
+            let ret = match step.action {
+                NFTAction::Mint { to, count } => {
+                    let params = NFTMintParams {
+                        initial_owner: to,
+                        metadata: vec![String::default(); count],
+                        operator_data: RawBytes::default(),
+                    };
+                    harness.apply(self.minter, nft, "Mint", &params)
+                }
+                NFTAction::Transfer { from, to, ref token_ids } => {
+                    let params = NFTTransferParams {
+                        to,
+                        token_ids: token_ids.clone(),
+                        operator_data: RawBytes::default(),
+                        per_token_data: vec![],
+                    };
+                    harness.apply(from, nft, "Transfer", &params)
+                }
+                NFTAction::TransferFrom { operator, from, to, ref token_ids } => {
+                    let params = NFTTransferFromParams {
+                        from,
+                        to,
+                        token_ids: token_ids.clone(),
+                        operator_data: RawBytes::default(),
+                        per_token_data: vec![],
+                    };
+                    harness.apply(operator, nft, "TransferFrom", &params)
+                }
+                NFTAction::Approve { owner, operator, ref token_ids } => {
+                    let params = ApproveParams { operator, token_ids: token_ids.clone() };
+                    harness.apply(owner, nft, "Approve", &params)
+                }
+                NFTAction::ApproveForAll { owner, operator } => {
+                    harness.apply(owner, nft, "ApproveForAll", &ApproveForAllParams { operator })
+                }
+                NFTAction::Burn { owner, ref token_ids } => {
+                    harness.apply(owner, nft, "Burn", token_ids)
+                }
+            };
+            assert_outcome(step, &ret);
+            called_hooks.extend(hooks(&ret, FRC53_TOKEN_TYPE));
+        }
+
+        for (token_id, owner) in self.owners {
+            let owner_id: ActorID = harness.call(self.nft, "OwnerOf", &token_id);
+            assert_eq!(owner_id, resolve(harness, &owner), "owner of token {token_id}");
+        }
+        for (owner, expected) in self.balances {
+            let balance: u64 = harness.call(self.nft, "BalanceOf", &owner);
+            assert_eq!(balance, expected, "balance of {owner}");
+        }
+        if let Some(expected) = self.supply {
+            let supply: u64 = harness.call(self.nft, "TotalSupply", &());
+            assert_eq!(supply, expected, "total supply");
+        }
+        if let Some(expected) = self.hooks {
+            let expected: Vec<_> = expected
+                .into_iter()
+                .map(|(to, token_ids)| (resolve(harness, &to), token_ids))
+                .collect();
+            let called: Vec<_> = called_hooks.into_iter().map(|h| (h.to, h.token_ids)).collect();
+            assert_eq!(called, expected, "receiver hooks called");
+        }
+    }
+}
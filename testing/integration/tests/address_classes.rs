@@ -0,0 +1,203 @@
+//! Tests of tokens and NFTs sent to and from every class of address
+//!
+//! Holders with ID (f0) and actor (f2) addresses are deployed receiver actors, which send tokens on
+//! from their receiver hook. Holders with secp256k1 (f1), BLS (f3) and delegated (f410) addresses
+//! start out unused, are created by the first transfer to them, and then send messages themselves.
+use frc46_token::token::types::{MintReturn, TransferParams, TransferReturn};
+use frc53_nft::types::{
+    MintReturn as NFTMintReturn, TokenID, TransferParams as NFTTransferParams,
+    TransferReturn as NFTTransferReturn,
+};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::{address::Address, econ::TokenAmount, receipt::Receipt, ActorID};
+use helix_test_actors::{
+    BASIC_NFT_ACTOR_BINARY, FRC46_FACTORY_TOKEN_ACTOR_BINARY, REENTRANT_RECEIVER_ACTOR_BINARY,
+};
+use helix_test_harness::{AddressClass, TestHarness};
+use serde::{Deserialize, Serialize};
+use serde_tuple::{Deserialize_tuple, Serialize_tuple};
+use token_impl::{self_transfer::SelfTransferPolicy, ConstructorParams, MintParams};
+
+/// Creates a holder of each class of address
+///
+/// Actors must be deployed before the harness starts, so this is called before any other calls.
+fn holders(harness: &mut TestHarness) -> Vec<(AddressClass, Address)> {
+    AddressClass::ALL
+        .into_iter()
+        .map(|class| {
+            let holder = match class {
+                AddressClass::Id => harness.deploy_actor(REENTRANT_RECEIVER_ACTOR_BINARY, &()),
+                AddressClass::Actor => harness.deploy_actor_at(
+                    REENTRANT_RECEIVER_ACTOR_BINARY,
+                    class.unused_address(1),
+                    &(),
+                ),
+                _ => class.unused_address(1),
+            };
+            (class, holder)
+        })
+        .collect()
+}
+
+/// Returns true if the holder is an actor which sends tokens on from its receiver hook, rather
+/// than an account which sends messages
+fn is_receiver_actor(class: AddressClass) -> bool {
+    !class.is_initializable()
+}
+
+#[test]
+fn frc46_address_classes() {
+    let mut harness = TestHarness::new();
+    let owner = harness.deployer();
+    let params = ConstructorParams {
+        name: "Test Token".into(),
+        symbol: "TEST".into(),
+        granularity: 1,
+        description: String::new(),
+        icon: None,
+        decimals: 18,
+        fil_backed: false,
+        self_transfer: SelfTransferPolicy::Reject,
+        minter: owner,
+    };
+    let token = harness.deploy_actor(FRC46_FACTORY_TOKEN_ACTOR_BINARY, &params);
+    let holders = holders(&mut harness);
+
+    let balance_of = |harness: &mut TestHarness, holder: Address| {
+        harness.call::<_, TokenAmount>(token, "BalanceOf", &holder)
+    };
+
+    for (class, holder) in holders {
+        // TEST: tokens are minted to the holder, which is created if it doesn't exist yet
+        let attack = TokenAttack::Transfer { to: owner, amount: TokenAmount::from_atto(40) };
+        let params = MintParams {
+            initial_owner: holder,
+            amount: TokenAmount::from_atto(100),
+            operator_data: RawBytes::serialize(attack).unwrap(),
+        };
+        let ret: MintReturn = harness.call(token, "Mint", &params);
+        let id = harness.resolve(&holder).unwrap_or_else(|| panic!("{class:?} holder not found"));
+        assert_eq!(balance_of(&mut harness, Address::new_id(id)), balance_of(&mut harness, holder));
+
+        // TEST: the holder sends tokens back to the owner
+        if is_receiver_actor(class) {
+            // the receiver actor sent them from its hook
+            let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+            assert!(receipt.exit_code.is_success(), "{class:?} holder failed to transfer");
+        } else {
+            let params = TransferParams {
+                to: owner,
+                amount: TokenAmount::from_atto(40),
+                operator_data: RawBytes::default(),
+            };
+            harness.call_from::<_, TransferReturn>(holder, token, "Transfer", &params);
+        }
+        assert_eq!(balance_of(&mut harness, holder), TokenAmount::from_atto(60), "{class:?}");
+
+        // TEST: the holder receives a transfer, and its hook is called
+        let params = TransferParams {
+            to: holder,
+            amount: TokenAmount::from_atto(10),
+            operator_data: RawBytes::default(),
+        };
+        let ret: TransferReturn = harness.call(token, "Transfer", &params);
+        assert_eq!(ret.to_balance, TokenAmount::from_atto(70), "{class:?}");
+    }
+
+    // TEST: minting to unused addresses which can't be initialized fails
+    for class in AddressClass::ALL.into_iter().filter(|c| !c.is_initializable()) {
+        let params = MintParams {
+            initial_owner: class.unused_address(2),
+            amount: TokenAmount::from_atto(100),
+            operator_data: RawBytes::default(),
+        };
+        let ret = harness.apply(owner, token, "Mint", &params);
+        assert!(!ret.msg_receipt.exit_code.is_success(), "{class:?} mint succeeded");
+    }
+}
+
+#[test]
+fn frc53_address_classes() {
+    let mut harness = TestHarness::new();
+    let owner = harness.deployer();
+    let nft = harness.deploy_actor(BASIC_NFT_ACTOR_BINARY, &());
+    let holders = holders(&mut harness);
+
+    let owner_of = |harness: &mut TestHarness, token_id: TokenID| {
+        harness.call::<_, ActorID>(nft, "OwnerOf", &token_id)
+    };
+    let owner_id = harness.resolve(&owner).unwrap();
+
+    for (next_token, (class, holder)) in (0..).step_by(2).zip(holders) {
+        // TEST: tokens are minted to the holder, which is created if it doesn't exist yet
+        let (kept, sent) = (next_token, next_token + 1);
+        let attack = NFTAttack::Transfer { to: owner, token_ids: vec![sent] };
+        let params = NFTMintParams {
+            initial_owner: holder,
+            metadata: vec![String::default(); 2],
+            operator_data: RawBytes::serialize(attack).unwrap(),
+        };
+        let ret: NFTMintReturn = harness.call(nft, "Mint", &params);
+        assert_eq!(ret.token_ids, vec![kept, sent]);
+        let id = harness.resolve(&holder).unwrap_or_else(|| panic!("{class:?} holder not found"));
+        assert_eq!(owner_of(&mut harness, kept), id);
+
+        // TEST: the holder sends a token back to the owner
+        if is_receiver_actor(class) {
+            // the receiver actor sent it from its hook
+            let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+            assert!(receipt.exit_code.is_success(), "{class:?} holder failed to transfer");
+        } else {
+            let params = NFTTransferParams {
+                to: owner,
+                token_ids: vec![sent],
+                operator_data: RawBytes::default(),
+                per_token_data: vec![],
+            };
+            harness.call_from::<_, NFTTransferReturn>(holder, nft, "Transfer", &params);
+        }
+        assert_eq!(owner_of(&mut harness, sent), owner_id, "{class:?}");
+
+        // TEST: the holder receives a transfer, and its hook is called
+        let params = NFTTransferParams {
+            to: holder,
+            token_ids: vec![sent],
+            operator_data: RawBytes::default(),
+            per_token_data: vec![],
+        };
+        harness.call::<_, NFTTransferReturn>(nft, "Transfer", &params);
+        assert_eq!(owner_of(&mut harness, sent), id, "{class:?}");
+        let balance: u64 = harness.call(nft, "BalanceOf", &holder);
+        assert_eq!(balance, 2, "{class:?}");
+    }
+
+    // TEST: minting to unused addresses which can't be initialized fails
+    for class in AddressClass::ALL.into_iter().filter(|c| !c.is_initializable()) {
+        let params = NFTMintParams {
+            initial_owner: class.unused_address(2),
+            metadata: vec![String::default()],
+            operator_data: RawBytes::default(),
+        };
+        let ret = harness.apply(owner, nft, "Mint", &params);
+        assert!(!ret.msg_receipt.exit_code.is_success(), "{class:?} mint succeeded");
+    }
+}
+
+// These types have been duplicated from reentrant_receiver_actor and basic_nft_actor as they can't
+// be included into rust code from a cdylib
+#[derive(Serialize, Deserialize, Debug)]
+pub enum TokenAttack {
+    Transfer { to: Address, amount: TokenAmount },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum NFTAttack {
+    Transfer { to: Address, token_ids: Vec<TokenID> },
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct NFTMintParams {
+    pub initial_owner: Address,
+    pub metadata: Vec<String>,
+    pub operator_data: RawBytes,
+}
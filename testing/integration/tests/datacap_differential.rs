@@ -0,0 +1,406 @@
+//! Differential tests of `frc46_token` against the semantics of the builtin datacap actor
+//!
+//! The datacap actor wraps `frc46_token` with whole-token granularity and a governor (the verified
+//! registry) that alone may mint and destroy datacap. Minting also grants an effectively infinite
+//! allowance over the new tokens to a list of operators. Transfers are restricted to those sent to
+//! or from the governor, and operator transfers to those sent to the governor. Burns and allowance
+//! changes behave as in any FRC-46 token.
+//!
+//! Random sequences of datacap operations are run through a `Token` wrapped the way the datacap
+//! actor wraps it, and through an independent model of those semantics. After every step both must
+//! agree on the exit code of the operation, every balance and allowance, and the total supply.
+use std::collections::BTreeMap;
+
+use frc46_token::token::{state::TokenState, Token, TokenError, TOKEN_PRECISION};
+use fvm_actor_utils::syscalls::fake_syscalls::FakeSyscalls;
+use fvm_actor_utils::util::ActorRuntime;
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::{address::Address, econ::TokenAmount, error::ExitCode, ActorID};
+use num_traits::Zero;
+use proptest::prelude::*;
+
+/// The verified registry actor, which governs datacap
+const GOVERNOR: ActorID = 6;
+/// Verified clients and other holders of datacap
+const CLIENTS: [ActorID; 3] = [100, 101, 102];
+/// Amounts are drawn as multiples of half a token, so that some are not whole tokens
+const HALF_TOKEN: i128 = TOKEN_PRECISION as i128 / 2;
+/// The largest amount drawn, in half tokens
+const MAX_HALVES: i64 = 10;
+
+/// The allowance granted to operators when datacap is minted, as defined by the datacap actor
+fn infinite_allowance() -> TokenAmount {
+    TokenAmount::from_whole(10i128.pow(21))
+}
+
+fn halves(halves: i64) -> TokenAmount {
+    TokenAmount::from_atto(halves as i128 * HALF_TOKEN)
+}
+
+fn id(actor: ActorID) -> Address {
+    Address::new_id(actor)
+}
+
+/// A datacap actor method, invoked by the first account in each variant
+#[derive(Clone, Debug)]
+enum Op {
+    Mint { caller: ActorID, to: ActorID, amount: i64, operators: Vec<ActorID> },
+    Destroy { caller: ActorID, owner: ActorID, amount: i64 },
+    Transfer { from: ActorID, to: ActorID, amount: i64 },
+    TransferFrom { operator: ActorID, from: ActorID, to: ActorID, amount: i64 },
+    Burn { owner: ActorID, amount: i64 },
+    BurnFrom { operator: ActorID, owner: ActorID, amount: i64 },
+    IncreaseAllowance { owner: ActorID, operator: ActorID, delta: i64 },
+    DecreaseAllowance { owner: ActorID, operator: ActorID, delta: i64 },
+    RevokeAllowance { owner: ActorID, operator: ActorID },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    // the governor is drawn as often as any client, so that governor-only methods often succeed
+    let account = || prop_oneof![Just(GOVERNOR), prop::sample::select(CLIENTS.to_vec())];
+    let amount = || -1..=MAX_HALVES;
+    prop_oneof![
+        (account(), account(), amount(), prop::collection::vec(account(), 0..=2))
+            .prop_map(|(caller, to, amount, operators)| Op::Mint { caller, to, amount, operators }),
+        (account(), account(), amount()).prop_map(|(caller, owner, amount)| Op::Destroy {
+            caller,
+            owner,
+            amount
+        }),
+        (account(), account(), amount()).prop_map(|(from, to, amount)| Op::Transfer {
+            from,
+            to,
+            amount
+        }),
+        (account(), account(), account(), amount()).prop_map(|(operator, from, to, amount)| {
+            Op::TransferFrom { operator, from, to, amount }
+        }),
+        (account(), amount()).prop_map(|(owner, amount)| Op::Burn { owner, amount }),
+        (account(), account(), amount()).prop_map(|(operator, owner, amount)| Op::BurnFrom {
+            operator,
+            owner,
+            amount
+        }),
+        (account(), account(), amount()).prop_map(|(owner, operator, delta)| {
+            Op::IncreaseAllowance { owner, operator, delta }
+        }),
+        (account(), account(), amount()).prop_map(|(owner, operator, delta)| {
+            Op::DecreaseAllowance { owner, operator, delta }
+        }),
+        (account(), account())
+            .prop_map(|(owner, operator)| Op::RevokeAllowance { owner, operator }),
+    ]
+}
+
+/// Runs datacap methods through `frc46_token`, the way the datacap actor does
+struct Datacap<'a> {
+    runtime: &'a ActorRuntime<FakeSyscalls, MemoryBlockstore>,
+    state: TokenState,
+}
+
+impl<'a> Datacap<'a> {
+    fn new(runtime: &'a ActorRuntime<FakeSyscalls, MemoryBlockstore>) -> Self {
+        Self { runtime, state: TokenState::new(runtime.bs()).unwrap() }
+    }
+
+    fn token(&mut self) -> Token<'_, FakeSyscalls, MemoryBlockstore> {
+        Token::wrap(self.runtime, TOKEN_PRECISION, &mut self.state)
+    }
+
+    /// Invokes a method, returning its exit code
+    ///
+    /// Methods that fail leave the state unchanged, as the datacap actor runs each one in a state
+    /// transaction.
+    fn invoke(&mut self, op: &Op) -> Result<(), ExitCode> {
+        let prior = self.state.clone();
+        let res = self.try_invoke(op);
+        if res.is_err() {
+            self.state = prior;
+        }
+        res
+    }
+
+    fn try_invoke(&mut self, op: &Op) -> Result<(), ExitCode> {
+        let exit_code = |e: TokenError| ExitCode::from(&e);
+        let mut token = self.token();
+        match *op {
+            Op::Mint { caller, to, amount, ref operators } => {
+                if caller != GOVERNOR {
+                    return Err(ExitCode::USR_FORBIDDEN);
+                }
+                let mut hook = token
+                    .mint(
+                        &id(caller),
+                        &id(to),
+                        &halves(amount),
+                        RawBytes::default(),
+                        RawBytes::default(),
+                    )
+                    .map_err(exit_code)?;
+                for &operator in operators {
+                    token
+                        .set_allowance(&id(to), &id(operator), &infinite_allowance())
+                        .map_err(exit_code)?;
+                }
+                hook.call(token.runtime()).map_err(|e| ExitCode::from(&e))?;
+            }
+            Op::Destroy { caller, owner, amount } => {
+                if caller != GOVERNOR {
+                    return Err(ExitCode::USR_FORBIDDEN);
+                }
+                token.burn(&id(owner), &halves(amount)).map_err(exit_code)?;
+            }
+            Op::Transfer { from, to, amount } => {
+                if from != GOVERNOR && to != GOVERNOR {
+                    return Err(ExitCode::USR_FORBIDDEN);
+                }
+                let mut hook = token
+                    .transfer(
+                        &id(from),
+                        &id(to),
+                        &halves(amount),
+                        RawBytes::default(),
+                        RawBytes::default(),
+                    )
+                    .map_err(exit_code)?;
+                hook.call(token.runtime()).map_err(|e| ExitCode::from(&e))?;
+            }
+            Op::TransferFrom { operator, from, to, amount } => {
+                if to != GOVERNOR {
+                    return Err(ExitCode::USR_FORBIDDEN);
+                }
+                let mut hook = token
+                    .transfer_from(
+                        &id(operator),
+                        &id(from),
+                        &id(to),
+                        &halves(amount),
+                        RawBytes::default(),
+                        RawBytes::default(),
+                    )
+                    .map_err(exit_code)?;
+                hook.call(token.runtime()).map_err(|e| ExitCode::from(&e))?;
+            }
+            Op::Burn { owner, amount } => {
+                token.burn(&id(owner), &halves(amount)).map_err(exit_code)?;
+            }
+            Op::BurnFrom { operator, owner, amount } => {
+                token.burn_from(&id(operator), &id(owner), &halves(amount)).map_err(exit_code)?;
+            }
+            Op::IncreaseAllowance { owner, operator, delta } => {
+                token
+                    .increase_allowance(&id(owner), &id(operator), &halves(delta))
+                    .map_err(exit_code)?;
+            }
+            Op::DecreaseAllowance { owner, operator, delta } => {
+                token
+                    .decrease_allowance(&id(owner), &id(operator), &halves(delta))
+                    .map_err(exit_code)?;
+            }
+            Op::RevokeAllowance { owner, operator } => {
+                token.revoke_allowance(&id(owner), &id(operator)).map_err(exit_code)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reference implementation of the datacap actor, omitting zero balances and allowances
+#[derive(Clone, Default)]
+struct Model {
+    supply: TokenAmount,
+    balances: BTreeMap<ActorID, TokenAmount>,
+    allowances: BTreeMap<(ActorID, ActorID), TokenAmount>,
+}
+
+impl Model {
+    fn balance(&self, owner: ActorID) -> TokenAmount {
+        self.balances.get(&owner).cloned().unwrap_or_default()
+    }
+
+    fn allowance(&self, owner: ActorID, operator: ActorID) -> TokenAmount {
+        self.allowances.get(&(owner, operator)).cloned().unwrap_or_default()
+    }
+
+    fn set_balance(&mut self, owner: ActorID, balance: TokenAmount) {
+        if balance.is_zero() {
+            self.balances.remove(&owner);
+        } else {
+            self.balances.insert(owner, balance);
+        }
+    }
+
+    fn set_allowance(&mut self, owner: ActorID, operator: ActorID, allowance: TokenAmount) {
+        if allowance.is_zero() {
+            self.allowances.remove(&(owner, operator));
+        } else {
+            self.allowances.insert((owner, operator), allowance);
+        }
+    }
+
+    /// Amounts of datacap must be non-negative whole tokens
+    fn validate_amount(amount: i64) -> Result<TokenAmount, ExitCode> {
+        if amount < 0 || amount % 2 != 0 {
+            return Err(ExitCode::USR_ILLEGAL_ARGUMENT);
+        }
+        Ok(halves(amount))
+    }
+
+    /// Allowance deltas must be non-negative, but need not be whole tokens
+    fn validate_delta(delta: i64) -> Result<TokenAmount, ExitCode> {
+        if delta < 0 {
+            return Err(ExitCode::USR_ILLEGAL_ARGUMENT);
+        }
+        Ok(halves(delta))
+    }
+
+    fn burn(&mut self, owner: ActorID, amount: &TokenAmount) -> Result<(), ExitCode> {
+        let balance = self.balance(owner);
+        if &balance < amount {
+            return Err(ExitCode::USR_INSUFFICIENT_FUNDS);
+        }
+        self.set_balance(owner, &balance - amount);
+        self.supply = &self.supply - amount;
+        Ok(())
+    }
+
+    fn transfer(
+        &mut self,
+        from: ActorID,
+        to: ActorID,
+        amount: &TokenAmount,
+    ) -> Result<(), ExitCode> {
+        if &self.balance(from) < amount {
+            return Err(ExitCode::USR_INSUFFICIENT_FUNDS);
+        }
+        self.set_balance(from, &self.balance(from) - amount);
+        self.set_balance(to, &self.balance(to) + amount);
+        Ok(())
+    }
+
+    /// Spends an operator's allowance, which must be non-zero even to spend nothing
+    fn use_allowance(
+        &mut self,
+        operator: ActorID,
+        owner: ActorID,
+        amount: &TokenAmount,
+    ) -> Result<(), ExitCode> {
+        if operator == owner {
+            return Err(ExitCode::USR_ILLEGAL_ARGUMENT);
+        }
+        let allowance = self.allowance(owner, operator);
+        if allowance.is_zero() || &allowance < amount {
+            return Err(ExitCode::USR_INSUFFICIENT_FUNDS);
+        }
+        self.set_allowance(owner, operator, &allowance - amount);
+        Ok(())
+    }
+
+    /// Applies an operation, returning its exit code
+    ///
+    /// Operations that fail leave the model unchanged.
+    fn apply(&mut self, op: &Op) -> Result<(), ExitCode> {
+        let mut model = self.clone();
+        let res = model.try_apply(op);
+        if res.is_ok() {
+            *self = model;
+        }
+        res
+    }
+
+    fn try_apply(&mut self, op: &Op) -> Result<(), ExitCode> {
+        match *op {
+            Op::Mint { caller, to, amount, ref operators } => {
+                if caller != GOVERNOR {
+                    return Err(ExitCode::USR_FORBIDDEN);
+                }
+                let amount = Self::validate_amount(amount)?;
+                self.set_balance(to, &self.balance(to) + &amount);
+                self.supply = &self.supply + &amount;
+                for &operator in operators {
+                    self.set_allowance(to, operator, infinite_allowance());
+                }
+                Ok(())
+            }
+            Op::Destroy { caller, owner, amount } => {
+                if caller != GOVERNOR {
+                    return Err(ExitCode::USR_FORBIDDEN);
+                }
+                self.burn(owner, &Self::validate_amount(amount)?)
+            }
+            Op::Transfer { from, to, amount } => {
+                if from != GOVERNOR && to != GOVERNOR {
+                    return Err(ExitCode::USR_FORBIDDEN);
+                }
+                self.transfer(from, to, &Self::validate_amount(amount)?)
+            }
+            Op::TransferFrom { operator, from, to, amount } => {
+                if to != GOVERNOR {
+                    return Err(ExitCode::USR_FORBIDDEN);
+                }
+                let amount = Self::validate_amount(amount)?;
+                self.use_allowance(operator, from, &amount)?;
+                self.transfer(from, to, &amount)
+            }
+            Op::Burn { owner, amount } => self.burn(owner, &Self::validate_amount(amount)?),
+            Op::BurnFrom { operator, owner, amount } => {
+                let amount = Self::validate_amount(amount)?;
+                self.use_allowance(operator, owner, &amount)?;
+                self.burn(owner, &amount)
+            }
+            Op::IncreaseAllowance { owner, operator, delta } => {
+                let delta = Self::validate_delta(delta)?;
+                self.set_allowance(owner, operator, &self.allowance(owner, operator) + &delta);
+                Ok(())
+            }
+            Op::DecreaseAllowance { owner, operator, delta } => {
+                let delta = Self::validate_delta(delta)?;
+                let allowance = &self.allowance(owner, operator) - &delta;
+                // allowances saturate at zero rather than failing
+                self.set_allowance(owner, operator, allowance.max(TokenAmount::zero()));
+                Ok(())
+            }
+            Op::RevokeAllowance { owner, operator } => {
+                self.set_allowance(owner, operator, TokenAmount::zero());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Compares every balance, allowance and the supply of the token with the model
+fn check_state(datacap: &mut Datacap, model: &Model) {
+    let token = datacap.token();
+    assert_eq!(token.total_supply(), model.supply);
+    let accounts: Vec<_> = [GOVERNOR].into_iter().chain(CLIENTS).collect();
+    for &owner in &accounts {
+        assert_eq!(
+            token.balance_of(&id(owner)).unwrap(),
+            model.balance(owner),
+            "balance of {owner}"
+        );
+        for &operator in &accounts {
+            assert_eq!(
+                token.allowance(&id(owner), &id(operator)).unwrap(),
+                model.allowance(owner, operator),
+                "allowance of {operator} from {owner}"
+            );
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn it_matches_datacap_semantics(ops in prop::collection::vec(op(), 1..64)) {
+        let runtime = ActorRuntime::<FakeSyscalls, MemoryBlockstore>::new_test_runtime();
+        let mut datacap = Datacap::new(&runtime);
+        let mut model = Model::default();
+
+        for op in &ops {
+            let exit_code = datacap.invoke(op);
+            prop_assert_eq!(exit_code, model.apply(op), "exit code of {:?} differs", op);
+            check_state(&mut datacap, &model);
+        }
+    }
+}
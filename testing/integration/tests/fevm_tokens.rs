@@ -0,0 +1,186 @@
+//! Tests of FRC-46 tokens sent to and from EVM contracts and accounts
+//!
+//! Contracts are emulated by the mock FEVM actor at delegated (f410) addresses, which are called
+//! with Solidity calldata, while unused f410 addresses stand in for EVM accounts.
+use frc46_token::token::types::{
+    MintReturn, TransferFromParams, TransferFromReturn, TransferParams,
+};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::{Address, Payload};
+use fvm_shared::{bigint::Zero, econ::TokenAmount, error::ExitCode};
+use helix_test_actors::{FRC46_FACTORY_TOKEN_ACTOR_BINARY, MOCK_FEVM_ACTOR_BINARY};
+use helix_test_harness::{AddressClass, TestHarness};
+use serde_tuple::{Deserialize_tuple, Serialize_tuple};
+use token_impl::{self_transfer::SelfTransferPolicy, ConstructorParams, MintParams};
+
+/// The exit code of an EVM contract that reverted
+const EVM_CONTRACT_REVERTED: ExitCode = ExitCode::new(33);
+
+// Selectors of the ERC-20 style functions of the mock contract
+const TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const TRANSFER_FROM: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+const INCREASE_ALLOWANCE: [u8; 4] = [0x39, 0x50, 0x93, 0x51];
+const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// Returns the Ethereum address standing for an ID or delegated address
+fn eth_address(address: Address) -> [u8; 20] {
+    match address.payload() {
+        Payload::ID(id) => {
+            let mut eth_address = [0; 20];
+            eth_address[0] = 0xff;
+            eth_address[12..].copy_from_slice(&id.to_be_bytes());
+            eth_address
+        }
+        Payload::Delegated(delegated) => delegated.subaddress().try_into().unwrap(),
+        _ => panic!("{address} has no Ethereum address"),
+    }
+}
+
+fn address_arg(address: Address) -> Vec<u8> {
+    let mut word = vec![0; 12];
+    word.extend(eth_address(address));
+    word
+}
+
+fn amount_arg(amount: u64) -> Vec<u8> {
+    let mut word = vec![0; 24];
+    word.extend(amount.to_be_bytes());
+    word
+}
+
+fn calldata(selector: [u8; 4], args: &[Vec<u8>]) -> RawBytes {
+    RawBytes::new([&selector[..], &args.concat()].concat())
+}
+
+fn setup(handles_filecoin_methods: bool) -> (TestHarness, Address, Address) {
+    let mut harness = TestHarness::new();
+    let params = ConstructorParams {
+        name: "Test Token".into(),
+        symbol: "TEST".into(),
+        granularity: 1,
+        description: String::new(),
+        icon: None,
+        decimals: 18,
+        fil_backed: false,
+        self_transfer: SelfTransferPolicy::Reject,
+        minter: harness.deployer(),
+    };
+    let token = harness.deploy_actor(FRC46_FACTORY_TOKEN_ACTOR_BINARY, &params);
+    let params = MockContract { token, handles_filecoin_methods };
+    let contract = harness.deploy_actor_at(
+        MOCK_FEVM_ACTOR_BINARY,
+        AddressClass::Delegated.unused_address(1),
+        &params,
+    );
+    (harness, token, contract)
+}
+
+fn mint(harness: &mut TestHarness, token: Address, to: Address, amount: u64) -> ExitCode {
+    let params = MintParams {
+        initial_owner: to,
+        amount: TokenAmount::from_atto(amount),
+        operator_data: RawBytes::default(),
+    };
+    harness.apply(harness.deployer(), token, "Mint", &params).msg_receipt.exit_code
+}
+
+fn balance(harness: &mut TestHarness, token: Address, holder: Address) -> TokenAmount {
+    harness.call(token, "BalanceOf", &holder)
+}
+
+#[test]
+fn frc46_transfers_with_evm_contracts() {
+    let (mut harness, token, contract) = setup(true);
+    let operator = harness.deployer();
+    let eth_account = AddressClass::Delegated.unused_address(2);
+
+    // TEST: a contract handling Filecoin methods accepts minted tokens from the receiver hook
+    assert_eq!(mint(&mut harness, token, contract, 100), ExitCode::OK);
+    assert_eq!(balance(&mut harness, token, contract), TokenAmount::from_atto(100));
+
+    // TEST: the contract transfers to an EVM account, which is created by the transfer
+    let transfer = calldata(TRANSFER, &[address_arg(eth_account), amount_arg(40)]);
+    let ret: RawBytes = harness.call(contract, "InvokeEVM", &transfer);
+    assert_eq!(ret.to_vec(), amount_arg(1), "transfer didn't return true");
+    assert_eq!(balance(&mut harness, token, eth_account), TokenAmount::from_atto(40));
+
+    // TEST: the contract reads balances through the token
+    let balance_of = calldata(BALANCE_OF, &[address_arg(eth_account)]);
+    let ret: RawBytes = harness.call(contract, "InvokeEVM", &balance_of);
+    assert_eq!(ret.to_vec(), amount_arg(40));
+
+    // TEST: the contract approves an operator given by its masked ID address, which then
+    // transfers from the contract
+    let operator_id = Address::new_id(harness.resolve(&operator).unwrap());
+    let approve = calldata(INCREASE_ALLOWANCE, &[address_arg(operator_id), amount_arg(20)]);
+    harness.call::<_, RawBytes>(contract, "InvokeEVM", &approve);
+    let params = TransferFromParams {
+        from: contract,
+        to: eth_account,
+        amount: TokenAmount::from_atto(20),
+        operator_data: RawBytes::default(),
+    };
+    harness.call::<_, TransferFromReturn>(token, "TransferFrom", &params);
+    assert_eq!(balance(&mut harness, token, contract), TokenAmount::from_atto(40));
+    assert_eq!(balance(&mut harness, token, eth_account), TokenAmount::from_atto(60));
+
+    // TEST: an EVM account receives minted tokens directly
+    assert_eq!(mint(&mut harness, token, eth_account, 10), ExitCode::OK);
+    assert_eq!(balance(&mut harness, token, eth_account), TokenAmount::from_atto(70));
+
+    // TEST: a transfer the token rejects makes the contract revert
+    let transfer = calldata(TRANSFER, &[address_arg(eth_account), amount_arg(41)]);
+    let ret = harness.apply(operator, contract, "InvokeEVM", &transfer);
+    assert_eq!(ret.msg_receipt.exit_code, EVM_CONTRACT_REVERTED);
+    let transfer_from =
+        calldata(TRANSFER_FROM, &[address_arg(eth_account), address_arg(contract), amount_arg(1)]);
+    let ret = harness.apply(operator, contract, "InvokeEVM", &transfer_from);
+    assert_eq!(ret.msg_receipt.exit_code, EVM_CONTRACT_REVERTED);
+    assert_eq!(balance(&mut harness, token, contract), TokenAmount::from_atto(40));
+
+    // TEST: calldata selecting no function reverts
+    let ret = harness.apply(operator, contract, "InvokeEVM", &calldata([0; 4], &[]));
+    assert_eq!(ret.msg_receipt.exit_code, EVM_CONTRACT_REVERTED);
+}
+
+#[test]
+fn frc46_transfers_to_evm_contracts_without_hooks() {
+    let (mut harness, token, contract) = setup(false);
+    let holder = harness.deployer();
+
+    // TEST: a contract without handle_filecoin_method reverts in the receiver hook, so minting to
+    // it fails
+    assert_ne!(mint(&mut harness, token, contract, 100), ExitCode::OK);
+    assert_eq!(balance(&mut harness, token, contract), TokenAmount::zero());
+
+    // TEST: transfers to the contract fail the same way, leaving the sender's balance unchanged
+    let params = MintParams {
+        initial_owner: holder,
+        amount: TokenAmount::from_atto(100),
+        operator_data: RawBytes::default(),
+    };
+    let ret: MintReturn = harness.call(token, "Mint", &params);
+    assert_eq!(ret.balance, TokenAmount::from_atto(100));
+    let params = TransferParams {
+        to: contract,
+        amount: TokenAmount::from_atto(50),
+        operator_data: RawBytes::default(),
+    };
+    let ret = harness.apply(holder, token, "Transfer", &params);
+    assert!(!ret.msg_receipt.exit_code.is_success());
+    assert_eq!(balance(&mut harness, token, holder), TokenAmount::from_atto(100));
+    assert_eq!(balance(&mut harness, token, contract), TokenAmount::zero());
+
+    // TEST: the contract can still be called through InvokeEVM
+    let balance_of = calldata(BALANCE_OF, &[address_arg(holder)]);
+    let ret: RawBytes = harness.call(contract, "InvokeEVM", &balance_of);
+    assert_eq!(ret.to_vec(), amount_arg(100));
+}
+
+// This type has been duplicated from mock_fevm_actor as it can't be included into rust code from a
+// cdylib
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct MockContract {
+    pub token: Address,
+    pub handles_filecoin_methods: bool,
+}
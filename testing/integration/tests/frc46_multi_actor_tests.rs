@@ -1,10 +1,10 @@
 use frc42_dispatch::method_hash;
-use frc46_token::token::types::TransferReturn;
+use frc46_token::token::types::{MintReturn, TransferReturn};
 use fvm_integration_tests::{dummy::DummyExterns, tester::Account};
 use fvm_ipld_blockstore::MemoryBlockstore;
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::{
-    address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode, receipt::Receipt,
+    address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode, receipt::Receipt, MethodNum,
 };
 
 mod common;
@@ -13,7 +13,7 @@ use common::{construct_tester, TestHelpers};
 use helix_test_actors::{FRC46_FACTORY_TOKEN_ACTOR_BINARY, FRC46_TEST_ACTOR_BINARY};
 use serde::{Deserialize, Serialize};
 use serde_tuple::{Deserialize_tuple, Serialize_tuple};
-use token_impl::ConstructorParams;
+use token_impl::{self_transfer::SelfTransferPolicy, ConstructorParams};
 
 #[test]
 fn frc46_multi_actor_tests() {
@@ -38,6 +38,11 @@ fn frc46_multi_actor_tests() {
             name: "Test Token".into(),
             symbol: "TEST".into(),
             granularity: 1,
+            description: String::new(),
+            icon: None,
+            decimals: 18,
+            fil_backed: false,
+            self_transfer: SelfTransferPolicy::Reject,
             minter: operator[0].1,
         };
         let params = RawBytes::serialize(params).unwrap();
@@ -372,6 +377,58 @@ fn frc46_multi_actor_tests() {
         // total supply should remain at 500
         tester.assert_total_supply(operator[0].1, token_actor, TokenAmount::from_atto(500));
     }
+
+    // TEST: mint to alice who rejects with a specific exit code, which the token passes on
+    {
+        let reject_code = ExitCode::new(42);
+        let ret_val = tester.mint_tokens(
+            operator[0].1,
+            token_actor,
+            alice,
+            TokenAmount::from_atto(100),
+            action(TestAction::RejectWith(reject_code)),
+        );
+        assert_eq!(ret_val.msg_receipt.exit_code, reject_code);
+        tester.assert_token_balance_zero(operator[0].1, token_actor, alice);
+        tester.assert_total_supply(operator[0].1, token_actor, TokenAmount::from_atto(500));
+    }
+
+    // TEST: carol is configured to burn incoming tokens, which applies to mints without operator_data
+    {
+        let params = RawBytes::serialize(TestAction::Burn).unwrap();
+        tester.call_method_ok(operator[0].1, carol, method_hash!("Configure"), Some(params));
+        tester.mint_tokens_ok(
+            operator[0].1,
+            token_actor,
+            carol,
+            TokenAmount::from_atto(100),
+            RawBytes::default(),
+        );
+        // carol burned what was minted, keeping the 100 from earlier tests
+        tester.assert_token_balance(operator[0].1, token_actor, carol, TokenAmount::from_atto(100));
+        tester.assert_total_supply(operator[0].1, token_actor, TokenAmount::from_atto(500));
+    }
+
+    // TEST: mint to alice who re-enters the token to check her balance from inside the hook
+    {
+        let ret_val = tester.mint_tokens_ok(
+            operator[0].1,
+            token_actor,
+            alice,
+            TokenAmount::from_atto(100),
+            action(TestAction::ReEnter {
+                method: method_hash!("BalanceOf"),
+                params: RawBytes::serialize(alice).unwrap(),
+            }),
+        );
+        let mint_return: MintReturn = ret_val.msg_receipt.return_data.deserialize().unwrap();
+        let receipt: Receipt = mint_return.recipient_data.deserialize().unwrap();
+        assert!(receipt.exit_code.is_success());
+        // the minted tokens were already credited when the hook was called
+        let balance: TokenAmount = receipt.return_data.deserialize().unwrap();
+        assert_eq!(balance, TokenAmount::from_atto(100));
+        tester.assert_total_supply(operator[0].1, token_actor, TokenAmount::from_atto(600));
+    }
 }
 
 // These types have been copied from frc46_test_actor as they can't be included into rust code from a cdylib
@@ -383,6 +440,8 @@ pub enum TestAction {
     Burn,
     ActionThenAbort(RawBytes),
     TransferWithFallback { to: Address, instructions: RawBytes, fallback: RawBytes },
+    RejectWith(ExitCode),
+    ReEnter { method: MethodNum, params: RawBytes },
 }
 
 #[derive(Serialize_tuple, Deserialize_tuple, Debug)]
@@ -14,7 +14,7 @@ use common::{construct_tester, TestHelpers};
 use helix_test_actors::{FRC46_FACTORY_TOKEN_ACTOR_BINARY, FRC46_TEST_ACTOR_BINARY};
 use serde::{Deserialize, Serialize};
 use serde_tuple::{Deserialize_tuple, Serialize_tuple};
-use token_impl::ConstructorParams;
+use token_impl::{self_transfer::SelfTransferPolicy, ConstructorParams};
 
 /// This covers several simpler tests, which all involve a single receiving actor
 /// They're combined because these integration tests take a long time to build and run
@@ -53,6 +53,11 @@ fn frc46_single_actor_tests() {
             name: "Test Token".into(),
             symbol: "TEST".into(),
             granularity: 1,
+            description: String::new(),
+            icon: None,
+            decimals: 18,
+            fil_backed: false,
+            self_transfer: SelfTransferPolicy::Reject,
             minter: operator[0].1,
         };
         let params = RawBytes::serialize(params).unwrap();
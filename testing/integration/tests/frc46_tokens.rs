@@ -1,23 +1,23 @@
-use cid::Cid;
-use frc42_dispatch::method_hash;
-use frc46_token::token::{state::TokenState, types::MintReturn};
-use fvm::executor::{ApplyKind, Executor};
-use fvm_integration_tests::bundle;
-use fvm_integration_tests::dummy::DummyExterns;
-use fvm_integration_tests::tester::{Account, Tester};
-use fvm_ipld_blockstore::MemoryBlockstore;
+use frc46_token::interop::selectors;
+use frc46_token::token::types::MintReturn;
+use fvm_actor_utils::abi::{encode_address, encode_bool, encode_uint64, Word};
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::address::Address;
-use fvm_shared::bigint::Zero;
 use fvm_shared::econ::TokenAmount;
-use fvm_shared::message::Message;
-use fvm_shared::state::StateTreeVersion;
-use fvm_shared::version::NetworkVersion;
 use helix_test_actors::BASIC_RECEIVING_ACTOR_BINARY;
 use helix_test_actors::BASIC_TOKEN_ACTOR_BINARY;
+use helix_test_harness::TestHarness;
 use serde_tuple::{Deserialize_tuple, Serialize_tuple};
 
-// Duplicated type from basic_token_actor
+// Duplicated types from basic_token_actor
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct ConstructorParams {
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    pub owner: Address,
+}
+
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
 pub struct MintParams {
     pub initial_owner: Address,
@@ -27,62 +27,22 @@ pub struct MintParams {
 
 #[test]
 fn it_mints_tokens() {
-    let blockstore = MemoryBlockstore::default();
-    let bundle_root = bundle::import_bundle(&blockstore, actors_v12::BUNDLE_CAR).unwrap();
-    let mut tester =
-        Tester::new(NetworkVersion::V21, StateTreeVersion::V5, bundle_root, blockstore.clone())
-            .unwrap();
-
-    let minter: [Account; 1] = tester.create_accounts().unwrap();
-
-    // Set actor state
-    let actor_state = TokenState::new(&blockstore).unwrap(); // TODO: this should probably not be exported from the package
-    let state_cid = tester.set_state(&actor_state).unwrap();
-
-    let actor_address = Address::new_id(10000);
-    let receive_address = Address::new_id(10010);
-    tester
-        .set_actor_from_bin(BASIC_TOKEN_ACTOR_BINARY, state_cid, actor_address, TokenAmount::zero())
-        .unwrap();
-    tester
-        .set_actor_from_bin(
-            BASIC_RECEIVING_ACTOR_BINARY,
-            Cid::default(),
-            receive_address,
-            TokenAmount::zero(),
-        )
-        .unwrap();
-
-    // Instantiate machine
-    tester.instantiate_machine(DummyExterns).unwrap();
-
-    // Helper to simplify sending messages
-    let mut sequence = 0u64;
-    let mut call_method = |from, to, method_num, params| {
-        let message = Message {
-            from,
-            to,
-            gas_limit: 99999999,
-            method_num,
-            sequence,
-            params: if let Some(params) = params { params } else { RawBytes::default() },
-            ..Message::default()
-        };
-        sequence += 1;
-        tester
-            .executor
-            .as_mut()
-            .unwrap()
-            .execute_message(message, ApplyKind::Explicit, 100)
-            .unwrap()
-    };
+    let mut harness = TestHarness::new();
+    let [minter] = harness.create_accounts();
 
-    // Construct the token actor
-    let ret_val = call_method(minter[0].1, actor_address, method_hash!("Constructor"), None);
-    println!("token actor constructor return data: {:#?}", &ret_val);
+    let params = ConstructorParams {
+        name: "Test Token".into(),
+        symbol: "TEST".into(),
+        granularity: 1,
+        owner: minter,
+    };
+    let actor_address = harness.deploy_actor(BASIC_TOKEN_ACTOR_BINARY, &params);
+    let receive_address = harness.deploy_actor(BASIC_RECEIVING_ACTOR_BINARY, &());
+    harness.label_actor(actor_address, "basic_token");
+    harness.label_actor(receive_address, "basic_receiver");
 
-    let ret_val = call_method(minter[0].1, receive_address, method_hash!("Constructor"), None);
-    println!("receiving actor constructor return data: {:#?}", &ret_val);
+    let symbol: String = harness.call(actor_address, "Symbol", &());
+    assert_eq!(symbol, "TEST");
 
     // Mint some tokens
     let mint_params = MintParams {
@@ -90,23 +50,80 @@ fn it_mints_tokens() {
         amount: TokenAmount::from_atto(100),
         operator_data: RawBytes::default(),
     };
-    let params = RawBytes::serialize(mint_params).unwrap();
-    let ret_val = call_method(minter[0].1, actor_address, method_hash!("Mint"), Some(params));
-    println!("mint return data {:#?}", &ret_val);
-    let return_data = ret_val.msg_receipt.return_data;
-    if return_data.is_empty() {
-        println!("return data was empty");
-    } else {
-        let mint_result: MintReturn = return_data.deserialize().unwrap();
-        println!("new total supply: {:?}", &mint_result.supply);
-    }
+    let mint_result: MintReturn = harness.call_from(minter, actor_address, "Mint", &mint_params);
+    println!("new total supply: {:?}", &mint_result.supply);
 
-    // Check balance
-    let params = RawBytes::serialize(receive_address).unwrap();
-    let ret_val = call_method(minter[0].1, actor_address, method_hash!("BalanceOf"), Some(params));
-    println!("balance return data {:#?}", &ret_val);
+    // only the owner may mint
+    let ret_val = harness.apply(harness.deployer(), actor_address, "Mint", &mint_params);
+    assert!(!ret_val.msg_receipt.exit_code.is_success());
 
-    let return_data = ret_val.msg_receipt.return_data;
-    let balance: TokenAmount = return_data.deserialize().unwrap();
+    // Check balance
+    let balance: TokenAmount = harness.call(actor_address, "BalanceOf", &receive_address);
     println!("balance: {balance:?}");
+    assert_eq!(balance, TokenAmount::from_atto(100));
+
+    // both mints are in the gas report, whether or not they succeeded
+    let mint_gas = harness.gas_report().method("basic_token", "Mint").unwrap();
+    assert_eq!(mint_gas.calls, 2);
+    assert!(mint_gas.min > 0);
+}
+
+fn calldata(selector: u32, args: &[Word]) -> RawBytes {
+    RawBytes::new([&selector.to_be_bytes()[..], &args.concat()].concat())
+}
+
+#[test]
+fn it_serves_erc20_calls() {
+    let mut harness = TestHarness::new();
+    let [alice, bob] = harness.create_accounts();
+
+    // a granularity of 10^12 is presented to Solidity callers as 6 decimals
+    let params = ConstructorParams {
+        name: "Test Token".into(),
+        symbol: "TEST".into(),
+        granularity: 1_000_000_000_000,
+        owner: alice,
+    };
+    let token = harness.deploy_actor(BASIC_TOKEN_ACTOR_BINARY, &params);
+    let mint_params = MintParams {
+        initial_owner: alice,
+        amount: TokenAmount::from_atto(100_000_000_000_000u64),
+        operator_data: RawBytes::default(),
+    };
+    harness.call_from::<_, MintReturn>(alice, token, "Mint", &mint_params);
+
+    let decimals: RawBytes = harness.call(token, "InvokeEVM", &calldata(selectors::DECIMALS, &[]));
+    assert_eq!(decimals.to_vec(), encode_uint64(6));
+    let balance_of = calldata(selectors::BALANCE_OF, &[encode_address(&alice).unwrap()]);
+    let balance: RawBytes = harness.call(token, "InvokeEVM", &balance_of);
+    assert_eq!(balance.to_vec(), encode_uint64(100));
+
+    // ERC-20 transfers move the same balances native callers see
+    let transfer =
+        calldata(selectors::TRANSFER, &[encode_address(&bob).unwrap(), encode_uint64(40)]);
+    let ret: RawBytes = harness.call_from(alice, token, "InvokeEVM", &transfer);
+    assert_eq!(ret.to_vec(), encode_bool(true));
+    let balance: TokenAmount = harness.call(token, "BalanceOf", &bob);
+    assert_eq!(balance, TokenAmount::from_atto(40_000_000_000_000u64));
+
+    // an ERC-20 approval can be spent by an ERC-20 transferFrom
+    let approve = calldata(selectors::APPROVE, &[encode_address(&bob).unwrap(), encode_uint64(25)]);
+    harness.call_from::<_, RawBytes>(alice, token, "InvokeEVM", &approve);
+    let transfer_from = calldata(
+        selectors::TRANSFER_FROM,
+        &[encode_address(&alice).unwrap(), encode_address(&bob).unwrap(), encode_uint64(30)],
+    );
+    let ret = harness.apply(bob, token, "InvokeEVM", &transfer_from);
+    assert!(!ret.msg_receipt.exit_code.is_success());
+    let transfer_from = calldata(
+        selectors::TRANSFER_FROM,
+        &[encode_address(&alice).unwrap(), encode_address(&bob).unwrap(), encode_uint64(25)],
+    );
+    harness.call_from::<_, RawBytes>(bob, token, "InvokeEVM", &transfer_from);
+    let balance: TokenAmount = harness.call(token, "BalanceOf", &bob);
+    assert_eq!(balance, TokenAmount::from_atto(65_000_000_000_000u64));
+
+    // calldata for functions the facade doesn't serve is rejected
+    let ret = harness.apply(alice, token, "InvokeEVM", &calldata(0x12345678, &[]));
+    assert!(!ret.msg_receipt.exit_code.is_success());
 }
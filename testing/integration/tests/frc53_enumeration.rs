@@ -35,18 +35,19 @@ pub fn test_nft_enumerations() {
                 String::from("alice2"),
                 String::from("alice3"),
             ],
+            0,
         )
         .unwrap();
     // Burn alice's first token
     state.burn_tokens(&blockstore, alice.0, &[0], |_token_data, _token_id| Ok(())).unwrap();
     // Mint a token for bob
-    state.mint_tokens(&blockstore, bob.0, vec![String::from("bob4")]).unwrap();
+    state.mint_tokens(&blockstore, bob.0, vec![String::from("bob4")], 0).unwrap();
     // Set the operator as an operator for one out of alice's three tokens
     state
-        .approve_for_tokens(&blockstore, operator.0, &[1], |_token_data, _token_id| Ok(()))
+        .approve_for_tokens(&blockstore, operator.0, &[1], None, 0, |_token_data, _token_id| Ok(()))
         .unwrap();
     // Set the operator as an account-level operator for bob
-    state.approve_for_owner(&blockstore, bob.0, operator.0).unwrap();
+    state.approve_for_owner(&blockstore, bob.0, operator.0, None, 0).unwrap();
 
     // Install the actor with the seeded state
     let actor_address = tester.install_actor_with_state(BASIC_NFT_ACTOR_BINARY, 10_000, state);
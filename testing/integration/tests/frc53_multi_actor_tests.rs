@@ -5,7 +5,7 @@ use frc53_nft::types::{MintReturn, TransferReturn};
 use fvm_integration_tests::{dummy::DummyExterns, tester::Account};
 use fvm_ipld_blockstore::MemoryBlockstore;
 use fvm_ipld_encoding::RawBytes;
-use fvm_shared::{address::Address, receipt::Receipt};
+use fvm_shared::{address::Address, error::ExitCode, receipt::Receipt, MethodNum};
 
 mod common;
 use common::frc53_nft_helpers::{MintParams, NFTHelper};
@@ -439,6 +439,38 @@ fn frc53_multi_actor_tests() {
         // Bob: [1, 2, 5]
         // Next ID: 6
     }
+
+    // TEST: carol is configured to reject with a specific exit code, which applies to mints without
+    // operator_data
+    {
+        let params = RawBytes::serialize(TestAction::RejectWith(ExitCode::new(42))).unwrap();
+        tester.call_method_ok(op_addr, carol, method_hash!("Configure"), Some(params));
+        let ret_val = tester.mint_nfts(op_addr, token_actor, carol, 1, RawBytes::default());
+        assert!(!ret_val.msg_receipt.exit_code.is_success());
+        tester.assert_nft_balance(op_addr, token_actor, carol, 0);
+        tester.assert_nft_total_supply(op_addr, token_actor, 3);
+    }
+
+    // TEST: mint to carol who re-enters the token to check her balance from inside the hook
+    {
+        let reenter = TestAction::ReEnter {
+            method: method_hash!("BalanceOf"),
+            params: RawBytes::serialize(carol).unwrap(),
+        };
+        let ret_val = tester.mint_nfts_ok(op_addr, token_actor, carol, 1, action(reenter));
+        let mint_return: MintReturn = ret_val.msg_receipt.return_data.deserialize().unwrap();
+        let receipt: Receipt = mint_return.recipient_data.deserialize().unwrap();
+        assert!(receipt.exit_code.is_success());
+        // the minted token was already credited when the hook was called
+        assert_eq!(receipt.return_data.deserialize::<u64>().unwrap(), 1);
+        tester.assert_nft_total_supply(op_addr, token_actor, 4);
+
+        // Total Supply: 4
+        // Alice: []
+        // Bob: [1, 2, 5]
+        // Carol: [6]
+        // Next ID: 7
+    }
 }
 
 /// These types have been duplicated from frc53_test_actor as we can't import into rust from a cdylib
@@ -448,6 +480,8 @@ pub enum TestAction {
     Reject,
     Transfer(Address, Vec<TokenID>, RawBytes),
     Burn(Vec<TokenID>),
+    RejectWith(ExitCode),
+    ReEnter { method: MethodNum, params: RawBytes },
 }
 
 #[derive(Serialize_tuple, Deserialize_tuple, Debug)]
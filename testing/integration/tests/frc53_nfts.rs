@@ -1,15 +1,19 @@
 use frc42_dispatch::method_hash;
+use frc53_nft::interop::selectors;
 use frc53_nft::types::{ListTokensParams, ListTokensReturn};
 use frc53_nft::{types::MintReturn, types::TokenID};
+use fvm_actor_utils::abi::{encode_address, encode_bool, encode_uint64, Word};
 use fvm_integration_tests::{dummy::DummyExterns, tester::Account};
 use fvm_ipld_bitfield::bitfield;
 use fvm_ipld_blockstore::MemoryBlockstore;
 use fvm_ipld_encoding::RawBytes;
+use fvm_shared::ActorID;
 
 mod common;
 use common::frc53_nft_helpers::{MintParams, NFTHelper};
 use common::{construct_tester, TestHelpers};
 use helix_test_actors::{BASIC_NFT_ACTOR_BINARY, BASIC_RECEIVING_ACTOR_BINARY};
+use helix_test_harness::TestHarness;
 
 #[test]
 fn test_nft_actor() {
@@ -172,3 +176,52 @@ fn test_nft_actor() {
         assert_eq!(list_tokens_result.tokens, bitfield![1, 1, 1, 1]);
     }
 }
+
+fn calldata(selector: u32, args: &[Word]) -> RawBytes {
+    RawBytes::new([&selector.to_be_bytes()[..], &args.concat()].concat())
+}
+
+#[test]
+fn it_serves_erc721_calls() {
+    let mut harness = TestHarness::new();
+    let [alice, bob] = harness.create_accounts();
+    let nft = harness.deploy_actor(BASIC_NFT_ACTOR_BINARY, &());
+    let receiver = harness.deploy_actor(BASIC_RECEIVING_ACTOR_BINARY, &());
+    let params = MintParams {
+        initial_owner: alice,
+        metadata: vec!["ipfs://a".into(), "ipfs://b".into()],
+        operator_data: RawBytes::default(),
+    };
+    harness.call_from::<_, MintReturn>(alice, nft, "Mint", &params);
+
+    let owner_of = |token_id| calldata(selectors::OWNER_OF, &[encode_uint64(token_id)]);
+    let owner: RawBytes = harness.call(nft, "InvokeEVM", &owner_of(0));
+    assert_eq!(owner.to_vec(), encode_address(&alice).unwrap());
+
+    // safeTransferFrom to a native actor calls its receiver hook in place of onERC721Received
+    let transfer = calldata(
+        selectors::SAFE_TRANSFER_FROM,
+        &[encode_address(&alice).unwrap(), encode_address(&receiver).unwrap(), encode_uint64(0)],
+    );
+    harness.call_from::<_, RawBytes>(alice, nft, "InvokeEVM", &transfer);
+    let owner: ActorID = harness.call(nft, "OwnerOf", &0u64);
+    assert_eq!(owner, receiver.id().unwrap());
+
+    // an operator approved for all of Alice's tokens may transfer them
+    let transfer_from = calldata(
+        selectors::TRANSFER_FROM,
+        &[encode_address(&alice).unwrap(), encode_address(&bob).unwrap(), encode_uint64(1)],
+    );
+    let ret = harness.apply(bob, nft, "InvokeEVM", &transfer_from);
+    assert!(!ret.msg_receipt.exit_code.is_success());
+    let approve = calldata(
+        selectors::SET_APPROVAL_FOR_ALL,
+        &[encode_address(&bob).unwrap(), encode_bool(true)],
+    );
+    harness.call_from::<_, RawBytes>(alice, nft, "InvokeEVM", &approve);
+    harness.call_from::<_, RawBytes>(bob, nft, "InvokeEVM", &transfer_from);
+    let owner: RawBytes = harness.call(nft, "InvokeEVM", &owner_of(1));
+    assert_eq!(owner.to_vec(), encode_address(&bob).unwrap());
+    let balance: u64 = harness.call(nft, "BalanceOf", &alice);
+    assert_eq!(balance, 0);
+}
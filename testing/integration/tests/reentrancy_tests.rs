@@ -0,0 +1,261 @@
+use frc46_token::token::types::{
+    GetAllowanceParams, IncreaseAllowanceParams, MintReturn, TransferParams, TransferReturn,
+};
+use frc53_nft::types::{ApproveParams, MintReturn as NFTMintReturn, TokenID};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::{address::Address, econ::TokenAmount, receipt::Receipt, ActorID};
+use helix_test_actors::{
+    BASIC_NFT_ACTOR_BINARY, BASIC_RECEIVING_ACTOR_BINARY, FRC46_FACTORY_TOKEN_ACTOR_BINARY,
+    REENTRANT_RECEIVER_ACTOR_BINARY,
+};
+use helix_test_harness::TestHarness;
+use serde::{Deserialize, Serialize};
+use serde_tuple::{Deserialize_tuple, Serialize_tuple};
+use token_impl::{self_transfer::SelfTransferPolicy, ConstructorParams, MintParams};
+
+/// Asserts that the balances of all holders of a token add up to its total supply
+fn assert_token_supply(harness: &mut TestHarness, token: Address, holders: &[Address]) {
+    let supply: TokenAmount = harness.call(token, "TotalSupply", &());
+    let balances: TokenAmount = holders
+        .iter()
+        .map(|holder| harness.call::<_, TokenAmount>(token, "BalanceOf", holder))
+        .sum();
+    assert_eq!(balances, supply);
+}
+
+/// Asserts that the balances of all holders of an NFT add up to its total supply
+fn assert_nft_supply(harness: &mut TestHarness, nft: Address, holders: &[Address]) {
+    let supply: u64 = harness.call(nft, "TotalSupply", &());
+    let balances: u64 =
+        holders.iter().map(|holder| harness.call::<_, u64>(nft, "BalanceOf", holder)).sum();
+    assert_eq!(balances, supply);
+}
+
+#[test]
+fn frc46_reentrant_hooks() {
+    let mut harness = TestHarness::new();
+    let owner = harness.deployer();
+    let params = ConstructorParams {
+        name: "Test Token".into(),
+        symbol: "TEST".into(),
+        granularity: 1,
+        description: String::new(),
+        icon: None,
+        decimals: 18,
+        fil_backed: false,
+        self_transfer: SelfTransferPolicy::Reject,
+        minter: owner,
+    };
+    let token = harness.deploy_actor(FRC46_FACTORY_TOKEN_ACTOR_BINARY, &params);
+    let attacker = harness.deploy_actor(REENTRANT_RECEIVER_ACTOR_BINARY, &());
+    let sink = harness.deploy_actor(BASIC_RECEIVING_ACTOR_BINARY, &());
+    let holders = [owner, attacker, sink];
+
+    let mint =
+        |harness: &mut TestHarness, to: Address, amount: u64, attack: Option<TokenAttack>| {
+            let params = MintParams {
+                initial_owner: to,
+                amount: TokenAmount::from_atto(amount),
+                operator_data: attack.map(|a| RawBytes::serialize(a).unwrap()).unwrap_or_default(),
+            };
+            harness.call::<_, MintReturn>(token, "Mint", &params)
+        };
+
+    // TEST: the attacker transfers the minted tokens away before the mint returns
+    {
+        let attack = TokenAttack::Transfer { to: sink, amount: TokenAmount::from_atto(100) };
+        let ret = mint(&mut harness, attacker, 100, Some(attack));
+        let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+        assert!(receipt.exit_code.is_success());
+        // the mint return reflects the state after the hook, rather than overwriting it
+        assert_eq!(ret.balance, TokenAmount::from_atto(0));
+        assert_eq!(ret.supply, TokenAmount::from_atto(100));
+        let sink_balance: TokenAmount = harness.call(token, "BalanceOf", &sink);
+        assert_eq!(sink_balance, TokenAmount::from_atto(100));
+        assert_token_supply(&mut harness, token, &holders);
+    }
+
+    // TEST: the attacker tries to spend more than it was minted
+    {
+        let attack = TokenAttack::Transfer { to: sink, amount: TokenAmount::from_atto(200) };
+        let ret = mint(&mut harness, attacker, 100, Some(attack));
+        let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+        assert!(!receipt.exit_code.is_success());
+        assert_eq!(ret.balance, TokenAmount::from_atto(100));
+        assert_eq!(ret.supply, TokenAmount::from_atto(200));
+        assert_token_supply(&mut harness, token, &holders);
+
+        // Attacker: 100
+        // Sink: 100
+        // Supply: 200
+    }
+
+    // TEST: the attacker burns tokens while they are being transferred to it
+    {
+        mint(&mut harness, owner, 100, None);
+        let attack = TokenAttack::Burn { amount: TokenAmount::from_atto(50) };
+        let params = TransferParams {
+            to: attacker,
+            amount: TokenAmount::from_atto(50),
+            operator_data: RawBytes::serialize(attack).unwrap(),
+        };
+        let ret: TransferReturn = harness.call(token, "Transfer", &params);
+        let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+        assert!(receipt.exit_code.is_success());
+        assert_eq!(ret.from_balance, TokenAmount::from_atto(50));
+        assert_eq!(ret.to_balance, TokenAmount::from_atto(100));
+        let supply: TokenAmount = harness.call(token, "TotalSupply", &());
+        assert_eq!(supply, TokenAmount::from_atto(250));
+        assert_token_supply(&mut harness, token, &holders);
+
+        // Owner: 50
+        // Attacker: 100
+        // Sink: 100
+        // Supply: 250
+    }
+
+    // TEST: the attacker spends its allowance from the owner while receiving tokens from the owner,
+    // and can't spend it twice
+    {
+        let params =
+            IncreaseAllowanceParams { operator: attacker, increase: TokenAmount::from_atto(30) };
+        harness.call::<_, TokenAmount>(token, "IncreaseAllowance", &params);
+        let attack = || TokenAttack::TransferFrom {
+            from: owner,
+            to: sink,
+            amount: TokenAmount::from_atto(30),
+        };
+        for (attack_succeeds, owner_balance) in [(true, 10), (false, 0)] {
+            let params = TransferParams {
+                to: attacker,
+                amount: TokenAmount::from_atto(10),
+                operator_data: RawBytes::serialize(attack()).unwrap(),
+            };
+            let ret: TransferReturn = harness.call(token, "Transfer", &params);
+            let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+            assert_eq!(receipt.exit_code.is_success(), attack_succeeds);
+            assert_eq!(ret.from_balance, TokenAmount::from_atto(owner_balance));
+        }
+        let params = GetAllowanceParams { owner, operator: attacker };
+        let allowance: TokenAmount = harness.call(token, "Allowance", &params);
+        assert_eq!(allowance, TokenAmount::from_atto(0));
+        let sink_balance: TokenAmount = harness.call(token, "BalanceOf", &sink);
+        assert_eq!(sink_balance, TokenAmount::from_atto(130));
+        assert_token_supply(&mut harness, token, &holders);
+
+        // Owner: 0
+        // Attacker: 120
+        // Sink: 130
+        // Supply: 250
+    }
+}
+
+#[test]
+fn frc53_reentrant_hooks() {
+    let mut harness = TestHarness::new();
+    let owner = harness.deployer();
+    let nft = harness.deploy_actor(BASIC_NFT_ACTOR_BINARY, &());
+    let attacker = harness.deploy_actor(REENTRANT_RECEIVER_ACTOR_BINARY, &());
+    let sink = harness.deploy_actor(BASIC_RECEIVING_ACTOR_BINARY, &());
+    let holders = [owner, attacker, sink];
+
+    let mint =
+        |harness: &mut TestHarness, to: Address, amount: usize, attack: Option<NFTAttack>| {
+            let params = NFTMintParams {
+                initial_owner: to,
+                metadata: vec![String::default(); amount],
+                operator_data: attack.map(|a| RawBytes::serialize(a).unwrap()).unwrap_or_default(),
+            };
+            harness.call::<_, NFTMintReturn>(nft, "Mint", &params)
+        };
+    let owner_of = |harness: &mut TestHarness, token_id: TokenID| {
+        harness.call::<_, ActorID>(nft, "OwnerOf", &token_id)
+    };
+
+    // TEST: the attacker transfers the minted tokens away before the mint returns
+    {
+        let attack = NFTAttack::Transfer { to: sink, token_ids: vec![0, 1] };
+        let ret = mint(&mut harness, attacker, 2, Some(attack));
+        let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+        assert!(receipt.exit_code.is_success());
+        // the mint return reflects the state after the hook, rather than overwriting it
+        assert_eq!(ret.balance, 0);
+        assert_eq!(ret.supply, 2);
+        assert_eq!(owner_of(&mut harness, 0), sink.id().unwrap());
+        assert_nft_supply(&mut harness, nft, &holders);
+    }
+
+    // TEST: the attacker burns a token while it is being minted
+    {
+        let ret = mint(&mut harness, attacker, 1, Some(NFTAttack::Burn { token_ids: vec![2] }));
+        let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+        assert!(receipt.exit_code.is_success());
+        assert_eq!(ret.balance, 0);
+        assert_eq!(ret.supply, 2);
+        assert_nft_supply(&mut harness, nft, &holders);
+
+        // Supply: 2
+        // Sink: [0, 1]
+        // Next ID: 3
+    }
+
+    // TEST: the attacker tries to transfer tokens it doesn't own along with those it was minted
+    {
+        let attack = NFTAttack::Transfer { to: sink, token_ids: vec![3, 0] };
+        let ret = mint(&mut harness, attacker, 1, Some(attack));
+        let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+        assert!(!receipt.exit_code.is_success());
+        assert_eq!(ret.balance, 1);
+        assert_eq!(owner_of(&mut harness, 3), attacker.id().unwrap());
+        assert_nft_supply(&mut harness, nft, &holders);
+
+        // Supply: 3
+        // Attacker: [3]
+        // Sink: [0, 1]
+        // Next ID: 4
+    }
+
+    // TEST: the attacker transfers a token it was approved for while receiving another, and can't
+    // transfer it twice
+    {
+        mint(&mut harness, owner, 1, None);
+        let params = ApproveParams { operator: attacker, token_ids: vec![4] };
+        harness.call::<_, ()>(nft, "Approve", &params);
+        let attack = || NFTAttack::TransferFrom { from: owner, to: sink, token_ids: vec![4] };
+        for attack_succeeds in [true, false] {
+            let ret = mint(&mut harness, attacker, 1, Some(attack()));
+            let receipt: Receipt = ret.recipient_data.deserialize().unwrap();
+            assert_eq!(receipt.exit_code.is_success(), attack_succeeds);
+        }
+        assert_eq!(owner_of(&mut harness, 4), sink.id().unwrap());
+        assert_nft_supply(&mut harness, nft, &holders);
+
+        // Supply: 6
+        // Attacker: [3, 5, 6]
+        // Sink: [0, 1, 4]
+        // Next ID: 7
+    }
+}
+
+// These types have been duplicated from reentrant_receiver_actor and basic_nft_actor as they can't
+// be included into rust code from a cdylib
+#[derive(Serialize, Deserialize, Debug)]
+pub enum TokenAttack {
+    Transfer { to: Address, amount: TokenAmount },
+    TransferFrom { from: Address, to: Address, amount: TokenAmount },
+    Burn { amount: TokenAmount },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum NFTAttack {
+    Transfer { to: Address, token_ids: Vec<TokenID> },
+    TransferFrom { from: Address, to: Address, token_ids: Vec<TokenID> },
+    Burn { token_ids: Vec<TokenID> },
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct NFTMintParams {
+    pub initial_owner: Address,
+    pub metadata: Vec<String>,
+    pub operator_data: RawBytes,
+}
@@ -0,0 +1,112 @@
+//! Flows between several accounts, written as scenarios run against the test actors
+use helix_test_actors::{BASIC_NFT_ACTOR_BINARY, FRC46_FACTORY_TOKEN_ACTOR_BINARY};
+use helix_test_harness::{NFTScenario, TestHarness, TokenScenario};
+use token_impl::{self_transfer::SelfTransferPolicy, ConstructorParams};
+
+#[test]
+fn frc46_allowance_flow() {
+    let mut harness = TestHarness::new();
+    let minter = harness.deployer();
+    let [alice, bob, carol] = harness.create_accounts();
+    let params = ConstructorParams {
+        name: "Test Token".into(),
+        symbol: "TEST".into(),
+        granularity: 1,
+        description: String::new(),
+        icon: None,
+        decimals: 18,
+        fil_backed: false,
+        self_transfer: SelfTransferPolicy::Reject,
+        minter,
+    };
+    let token = harness.deploy_actor(FRC46_FACTORY_TOKEN_ACTOR_BINARY, &params);
+
+    TokenScenario::new(token, minter)
+        .mint(alice, 100)
+        .approve(alice, bob, 50)
+        .transfer_from(bob, alice, carol, 30)
+        // the remaining allowance doesn't cover a second transfer
+        .transfer_from(bob, alice, carol, 30)
+        .fails()
+        .transfer(carol, bob, 10)
+        .revoke(alice, bob)
+        .transfer_from(bob, alice, bob, 10)
+        .fails()
+        .burn(bob, 5)
+        .expect_balance(alice, 70)
+        .expect_balance(bob, 5)
+        .expect_balance(carol, 20)
+        .expect_allowance(alice, bob, 0)
+        .expect_supply(95)
+        .expect_hook(alice, 100)
+        .expect_hook(carol, 30)
+        .expect_hook(bob, 10)
+        .run(&mut harness);
+}
+
+#[test]
+fn frc46_burn_from_flow() {
+    let mut harness = TestHarness::new();
+    let minter = harness.deployer();
+    let [alice, bob] = harness.create_accounts();
+    let params = ConstructorParams {
+        name: "Test Token".into(),
+        symbol: "TEST".into(),
+        granularity: 1,
+        description: String::new(),
+        icon: None,
+        decimals: 18,
+        fil_backed: false,
+        self_transfer: SelfTransferPolicy::Reject,
+        minter,
+    };
+    let token = harness.deploy_actor(FRC46_FACTORY_TOKEN_ACTOR_BINARY, &params);
+
+    TokenScenario::new(token, minter)
+        .mint(alice, 100)
+        // bob has no allowance yet
+        .burn_from(bob, alice, 10)
+        .fails()
+        .approve(alice, bob, 30)
+        .burn_from(bob, alice, 20)
+        // the remaining allowance doesn't cover a second burn
+        .burn_from(bob, alice, 20)
+        .fails()
+        .expect_balance(alice, 80)
+        .expect_allowance(alice, bob, 10)
+        .expect_supply(80)
+        .run(&mut harness);
+}
+
+#[test]
+fn frc53_operator_flow() {
+    let mut harness = TestHarness::new();
+    let minter = harness.deployer();
+    let [alice, bob, carol] = harness.create_accounts();
+    let nft = harness.deploy_actor(BASIC_NFT_ACTOR_BINARY, &());
+
+    NFTScenario::new(nft, minter)
+        .mint(alice, 3)
+        .approve(alice, bob, &[1])
+        .transfer_from(bob, alice, carol, &[1])
+        // bob was only approved for token 1
+        .transfer_from(bob, alice, carol, &[2])
+        .fails()
+        .approve_for_all(alice, bob)
+        .transfer_from(bob, alice, carol, &[2])
+        .transfer(alice, carol, &[0])
+        .burn(carol, &[2])
+        // alice no longer owns any tokens
+        .burn(alice, &[0])
+        .fails()
+        .expect_owner(0, carol)
+        .expect_owner(1, carol)
+        .expect_balance(alice, 0)
+        .expect_balance(carol, 2)
+        .expect_supply(2)
+        .expect_hook(alice, &[0, 1, 2])
+        .expect_hook(carol, &[1])
+        .expect_hook(carol, &[2])
+        .expect_hook(carol, &[0])
+        .run(&mut harness);
+}
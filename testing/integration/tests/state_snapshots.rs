@@ -0,0 +1,34 @@
+//! Snapshots of actor state exported to CAR archives and imported again
+use frc53_nft::state::NFTState;
+use fvm_actor_utils::car::import_car_to_memory;
+use helix_test_actors::BASIC_NFT_ACTOR_BINARY;
+use helix_test_harness::{NFTScenario, TestHarness};
+
+#[test]
+fn frc53_state_snapshot_round_trip() {
+    let mut harness = TestHarness::new();
+    let minter = harness.deployer();
+    let [alice, bob] = harness.create_accounts();
+    let nft = harness.deploy_actor(BASIC_NFT_ACTOR_BINARY, &());
+
+    NFTScenario::new(nft, minter)
+        .mint(alice, 3)
+        .approve_for_all(alice, bob)
+        .transfer_from(bob, alice, bob, &[1])
+        .run(&mut harness);
+
+    let mut car = Vec::new();
+    let blocks = harness.export_state(&nft, &mut car);
+    assert!(blocks > 1);
+
+    // TEST: the imported snapshot holds the whole state tree
+    let (bs, roots) = import_car_to_memory(car.as_slice()).unwrap();
+    assert_eq!(roots.len(), 1);
+    let state = NFTState::load(&bs, &roots[0]).unwrap();
+    assert!(state.check_invariants(&bs).is_ok());
+    let alice = harness.resolve(&alice).unwrap();
+    let bob = harness.resolve(&bob).unwrap();
+    assert_eq!(state.total_supply, 3);
+    assert_eq!(state.get_owner(&bs, 0).unwrap(), alice);
+    assert_eq!(state.get_owner(&bs, 1).unwrap(), bob);
+}
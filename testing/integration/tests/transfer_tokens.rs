@@ -1,82 +1,72 @@
-use frc42_dispatch::method_hash;
-use frc46_token::token::{state::TokenState, types::MintReturn};
-use fvm_integration_tests::{dummy::DummyExterns, tester::Account};
-use fvm_ipld_blockstore::MemoryBlockstore;
+use frc46_token::token::types::MintReturn;
 use fvm_ipld_encoding::{
     tuple::{Deserialize_tuple, Serialize_tuple},
     RawBytes,
 };
 use fvm_shared::address::Address;
 use fvm_shared::econ::TokenAmount;
-
-mod common;
-use common::frc46_token_helpers::TokenHelper;
-use common::{construct_tester, TestHelpers};
 use helix_test_actors::{
     BASIC_RECEIVING_ACTOR_BINARY, BASIC_TOKEN_ACTOR_BINARY, BASIC_TRANSFER_ACTOR_BINARY,
 };
+use helix_test_harness::TestHarness;
+
+// Duplicated types from basic_token_actor
+#[derive(Serialize_tuple, Deserialize_tuple)]
+struct ConstructorParams {
+    name: String,
+    symbol: String,
+    granularity: u64,
+    owner: Address,
+}
 
 #[derive(Serialize_tuple, Deserialize_tuple)]
-struct TransferActorState {
-    operator_address: Option<Address>,
-    token_address: Option<Address>,
+struct MintParams {
+    initial_owner: Address,
+    amount: TokenAmount,
+    operator_data: RawBytes,
 }
 
 #[test]
 fn transfer_tokens() {
-    let blockstore = MemoryBlockstore::default();
-    let mut tester = construct_tester(&blockstore);
-
-    let operator: [Account; 1] = tester.create_accounts().unwrap();
-
-    let token_state = TokenState::new(&blockstore).unwrap();
-    let transfer_state = TransferActorState { operator_address: None, token_address: None };
-
-    let token_address =
-        tester.install_actor_with_state(BASIC_TOKEN_ACTOR_BINARY, 10000, token_state);
-    let transfer_address =
-        tester.install_actor_with_state(BASIC_TRANSFER_ACTOR_BINARY, 10010, transfer_state);
-    let receiver_address = tester.install_actor_stateless(BASIC_RECEIVING_ACTOR_BINARY, 10020);
-
-    // Instantiate machine
-    tester.instantiate_machine(DummyExterns).unwrap();
+    let mut harness = TestHarness::new();
 
-    // construct actors
-    for actor in [token_address, transfer_address, receiver_address] {
-        let ret_val = tester.call_method(operator[0].1, actor, method_hash!("Constructor"), None);
-        assert!(ret_val.msg_receipt.exit_code.is_success());
-    }
+    let params = ConstructorParams {
+        name: "Test Token".into(),
+        symbol: "TEST".into(),
+        granularity: 1,
+        owner: harness.deployer(),
+    };
+    let token_address = harness.deploy_actor(BASIC_TOKEN_ACTOR_BINARY, &params);
+    let transfer_address = harness.deploy_actor(BASIC_TRANSFER_ACTOR_BINARY, &());
+    let receiver_address = harness.deploy_actor(BASIC_RECEIVING_ACTOR_BINARY, &());
+    harness.label_actor(token_address, "basic_token");
+    harness.label_actor(transfer_address, "basic_transfer");
+    harness.label_actor(receiver_address, "basic_receiver");
 
     // mint some tokens
-    let ret_val = tester.mint_tokens(
-        operator[0].1,
-        token_address,
-        transfer_address,
-        TokenAmount::from_atto(100),
-        RawBytes::default(),
-    );
-    println!("minting return data {:#?}", &ret_val);
-    let mint_result: MintReturn = ret_val.msg_receipt.return_data.deserialize().unwrap();
+    let params = MintParams {
+        initial_owner: transfer_address,
+        amount: TokenAmount::from_atto(100),
+        operator_data: RawBytes::default(),
+    };
+    let mint_result: MintReturn = harness.call(token_address, "Mint", &params);
     println!("minted - total supply: {:?}", &mint_result.supply);
     assert_eq!(mint_result.supply, TokenAmount::from_atto(100));
 
     // check balance of transfer actor
-    let balance = tester.token_balance(operator[0].1, token_address, transfer_address);
+    let balance: TokenAmount = harness.call(token_address, "BalanceOf", &transfer_address);
     println!("balance held by transfer actor: {balance:?}");
     assert_eq!(balance, TokenAmount::from_atto(100));
 
     // forward from transfer to receiving actor
-    let params = RawBytes::serialize(receiver_address).unwrap();
-    let ret_val =
-        tester.call_method(operator[0].1, transfer_address, method_hash!("Forward"), Some(params));
-    println!("forwarding return data {:#?}", &ret_val);
+    harness.call::<_, ()>(transfer_address, "Forward", &receiver_address);
 
     // check balance of receiver actor
-    let balance = tester.token_balance(operator[0].1, token_address, transfer_address);
+    let balance: TokenAmount = harness.call(token_address, "BalanceOf", &transfer_address);
     println!("balance held by transfer actor: {balance:?}");
     assert_eq!(balance, TokenAmount::from_atto(0));
 
-    let balance = tester.token_balance(operator[0].1, token_address, receiver_address);
+    let balance: TokenAmount = harness.call(token_address, "BalanceOf", &receiver_address);
     println!("balance held by receiver actor: {balance:?}");
     assert_eq!(balance, TokenAmount::from_atto(100));
 }
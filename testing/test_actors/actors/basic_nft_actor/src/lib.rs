@@ -1,11 +1,12 @@
-use frc42_dispatch::match_method;
+use frc42_dispatch::{match_method, match_method::abort_unhandled};
 use frc53_nft::{
+    interop::{Erc721Call, Erc721Return},
     state::NFTState,
     types::{
-        ApproveForAllParams, ApproveParams, BurnFromParams, ListAccountOperatorsParams,
-        ListOperatorTokensParams, ListOwnedTokensParams, ListTokenOperatorsParams,
-        ListTokensParams, RevokeForAllParams, RevokeParams, TokenID, TransferFromParams,
-        TransferParams,
+        ApproveForAllParams, ApproveParams, BurnFromParams, IsApprovedForAllParams,
+        ListAccountOperatorsParams, ListOperatorTokensParams, ListOwnedTokensParams,
+        ListTokenOperatorsParams, ListTokensParams, RevokeForAllParams, RevokeParams, TokenID,
+        TransferFromParams, TransferParams,
     },
     NFT,
 };
@@ -21,7 +22,6 @@ use fvm_ipld_encoding::{
 };
 use fvm_sdk as sdk;
 use fvm_shared::address::Address;
-use fvm_shared::error::ExitCode;
 use sdk::{sys::ErrorNumber, NO_DATA_BLOCK_ID};
 use thiserror::Error;
 
@@ -42,6 +42,18 @@ fn invoke(params: u32) -> u32 {
     let mut handle = NFT::wrap(helpers, &mut state);
 
     match_method!(method_num,{
+        "Name" => {
+            let res = handle.name();
+            return_ipld(&res).unwrap()
+        }
+        "Symbol" => {
+            let res = handle.symbol();
+            return_ipld(&res).unwrap()
+        }
+        "CollectionMetadata" => {
+            let res = handle.collection_metadata();
+            return_ipld(&res).unwrap()
+        }
         "BalanceOf" => {
             let params = deserialize_params::<Address>(params);
             let res = handle.balance_of(&params).unwrap();
@@ -61,6 +73,16 @@ fn invoke(params: u32) -> u32 {
             let res = handle.metadata(params).unwrap();
             return_ipld(&res).unwrap()
         }
+        "GetApproved" => {
+            let params = deserialize_params::<TokenID>(params);
+            let res = handle.approved_operators(params).unwrap();
+            return_ipld(&res).unwrap()
+        }
+        "IsApprovedForAll" => {
+            let params = deserialize_params::<IsApprovedForAllParams>(params);
+            let res = handle.is_account_operator(&params.owner, &params.operator).unwrap();
+            return_ipld(&res).unwrap()
+        }
         "Mint" => {
             let params = deserialize_params::<MintParams>(params);
             let caller = Address::new_id(sdk::message::caller());
@@ -76,12 +98,13 @@ fn invoke(params: u32) -> u32 {
         }
         "Transfer" => {
             let params = deserialize_params::<TransferParams>(params);
-            let mut hook = handle.transfer(
+            let mut hook = handle.transfer_with_token_data(
                 &caller_address(),
                 &params.to,
                 &params.token_ids,
                 params.operator_data,
-                RawBytes::default()
+                RawBytes::default(),
+                params.per_token_data
             ).unwrap();
 
             let cid = handle.flush().unwrap();
@@ -94,13 +117,14 @@ fn invoke(params: u32) -> u32 {
         }
         "TransferFrom" => {
             let params = deserialize_params::<TransferFromParams>(params);
-            let mut hook = handle.transfer_from(
+            let mut hook = handle.transfer_from_with_token_data(
                 &caller_address(),
                 &params.from,
                 &params.to,
                 &params.token_ids,
                 params.operator_data,
-                RawBytes::default()
+                RawBytes::default(),
+                params.per_token_data
             ).unwrap();
 
             let cid = handle.flush().unwrap();
@@ -114,7 +138,7 @@ fn invoke(params: u32) -> u32 {
         "Burn" => {
             let params = deserialize_params::<Vec<TokenID>>(params);
             let caller = sdk::message::caller();
-            let ret_val = handle.burn(&Address::new_id(caller), &params).unwrap();
+            let ret_val = handle.burn(&Address::new_id(caller), &params).unwrap().balance;
 
             let cid = handle.flush().unwrap();
             sdk::sself::set_root(&cid).unwrap();
@@ -182,8 +206,32 @@ fn invoke(params: u32) -> u32 {
             let res = handle.list_account_operators(&params.owner, params.cursor, params.limit).unwrap();
             return_ipld(&res).unwrap()
         }
+        "InvokeEVM" => {
+            let calldata = deserialize_params::<RawBytes>(params);
+            let call = Erc721Call::decode(&calldata).unwrap();
+            let read_only = call.is_read_only();
+            let ret = match call.invoke(&mut handle, &caller_address()).unwrap() {
+                Erc721Return::Data(ret) => {
+                    if !read_only {
+                        let cid = handle.flush().unwrap();
+                        sdk::sself::set_root(&cid).unwrap();
+                    }
+                    ret
+                }
+                Erc721Return::Transfer(mut hook) => {
+                    let cid = handle.flush().unwrap();
+                    sdk::sself::set_root(&cid).unwrap();
+
+                    let hook_res = hook.call(&messenger).unwrap();
+
+                    handle.transfer_return(hook_res, cid).unwrap();
+                    Vec::new()
+                }
+            };
+            return_ipld(&RawBytes::new(ret)).unwrap()
+        }
         _ => {
-            sdk::vm::abort(ExitCode::USR_ILLEGAL_ARGUMENT.value(), Some(&format!("Unknown method number {method_num:?} was invoked")));
+            abort_unhandled(method_num)
         }
     })
 }
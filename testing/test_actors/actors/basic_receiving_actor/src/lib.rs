@@ -1,7 +1,8 @@
 use frc42_dispatch::match_method;
-use frc46_token::receiver::{FRC46TokenReceived, FRC46_TOKEN_TYPE};
-use frc53_nft::receiver::{FRC53TokenReceived, FRC53_TOKEN_TYPE};
-use fvm_actor_utils::receiver::UniversalReceiverParams;
+use frc42_dispatch::match_method::{abort_reserved, abort_unhandled};
+use frc46_token::receiver::FRC46TokenReceived;
+use frc53_nft::receiver::FRC53TokenReceived;
+use fvm_actor_utils::receiver::{UniversalReceiver, UniversalReceiverParams};
 use fvm_ipld_encoding::{de::DeserializeOwned, RawBytes};
 use fvm_sdk as sdk;
 use fvm_shared::error::ExitCode;
@@ -14,6 +15,22 @@ pub fn deserialize_params<O: DeserializeOwned>(params: u32) -> O {
     params.deserialize().unwrap()
 }
 
+struct BasicReceiver;
+
+impl UniversalReceiver for BasicReceiver {
+    fn on_frc46_received(&mut self, _params: FRC46TokenReceived) -> Result<(), ExitCode> {
+        // TODO: inspect params and decide if we'll accept the transfer
+        // to reject it, return an error
+        Ok(())
+    }
+
+    fn on_frc53_received(&mut self, _params: FRC53TokenReceived) -> Result<(), ExitCode> {
+        // TODO: inspect params and decide if we'll accept the transfer
+        // to reject it, return an error
+        Ok(())
+    }
+}
+
 #[no_mangle]
 fn invoke(input: u32) -> u32 {
     std::panic::set_hook(Box::new(|info| {
@@ -29,33 +46,15 @@ fn invoke(input: u32) -> u32 {
             // Receive is passed a UniversalReceiverParams
             let params: UniversalReceiverParams = deserialize_params(input);
 
-            // reject if not an FRC46 token or an FRC53 NFT
-            // we don't know how to inspect other payloads in this example
-            match params.type_ {
-                FRC46_TOKEN_TYPE => {
-                    // get token transfer data
-                    let _token_params: FRC46TokenReceived = params.payload.deserialize().unwrap();
-                    // TODO: inspect token_params and decide if we'll accept the transfer
-                    // to reject it, we just abort (or panic, which does the same thing)
-                }
-                FRC53_TOKEN_TYPE => {
-                    // get token transfer data
-                    let _token_params: FRC53TokenReceived = params.payload.deserialize().unwrap();
-                    // TODO: inspect token_params and decide if we'll accept the transfer
-                    // to reject it, we just abort (or panic, which does the same thing)
-                }
-                _ => {
-                    panic!("invalid token type, rejecting transfer");
-                }
+            // accept FRC46 tokens and FRC53 NFTs, rejecting other payloads as we don't know how
+            // to inspect them in this example
+            if let Err(exit_code) = BasicReceiver.receive(params) {
+                sdk::vm::abort(exit_code.value(), Some("rejecting transfer"));
             }
 
             NO_DATA_BLOCK_ID
         },
-        _ => {
-            sdk::vm::abort(
-                ExitCode::USR_UNHANDLED_MESSAGE.value(),
-                Some("Unknown method number"),
-            );
-        }
+        reserved => abort_reserved(method_num),
+        _ => abort_unhandled(method_num),
     })
 }
@@ -1,61 +1,91 @@
 mod util;
 
-use cid::Cid;
+use cid::{multihash::Code, Cid};
+use frc42_dispatch::match_method;
+use frc42_dispatch::match_method::abort_unhandled;
+use frc46_token::interop::Erc20Call;
+use frc46_token::token::state::TokenState;
 use frc46_token::token::types::{
-    AllowanceReturn, BalanceReturn, BurnFromReturn, BurnParams, BurnReturn,
+    AllowanceReturn, BalanceReturn, BurnFromParams, BurnFromReturn, BurnParams, BurnReturn,
     DecreaseAllowanceParams, FRC46Token, GetAllowanceParams, GranularityReturn,
     IncreaseAllowanceParams, MintReturn, RevokeAllowanceParams, TotalSupplyReturn,
-    TransferFromReturn, TransferParams, TransferReturn,
+    TransferFromParams, TransferFromReturn, TransferParams, TransferReturn,
 };
 use frc46_token::token::Token;
 use fvm_actor_utils::blockstore::Blockstore;
 use fvm_actor_utils::syscalls::fvm_syscalls::FvmSyscalls;
 use fvm_actor_utils::util::ActorRuntime;
 use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
-use fvm_ipld_encoding::{RawBytes, DAG_CBOR};
+use fvm_ipld_encoding::{CborStore, RawBytes};
 use fvm_sdk as sdk;
 use fvm_shared::address::Address;
 use fvm_shared::econ::TokenAmount;
 use fvm_shared::error::ExitCode;
-use sdk::sys::ErrorNumber;
+use fvm_shared::ActorID;
 use sdk::NO_DATA_BLOCK_ID;
-use serde::ser;
-use thiserror::Error;
-use util::{caller_address, deserialize_params, RuntimeError};
+use util::{deserialize_params, return_ipld, RuntimeError};
 
-struct BasicToken<'state> {
+/// Configures a new token
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct ConstructorParams {
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    /// The only account allowed to mint tokens
+    pub owner: Address,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct MintParams {
+    pub initial_owner: Address,
+    pub amount: TokenAmount,
+    pub operator_data: RawBytes,
+}
+
+/// State of the token actor, which carries its identity alongside the `TokenState`
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct BasicTokenState {
     /// Default token helper impl
-    util: Token<'state, FvmSyscalls, Blockstore>,
+    pub token: TokenState,
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    pub owner: ActorID,
+}
+
+struct BasicToken {
+    runtime: ActorRuntime<FvmSyscalls, Blockstore>,
+    state: BasicTokenState,
 }
 
 /// Implementation of the token API in a FVM actor
 ///
 /// Here the Ipld parameter structs are marshalled and passed to the underlying library functions
-impl FRC46Token for BasicToken<'_> {
+impl FRC46Token for BasicToken {
     type TokenError = RuntimeError;
     fn name(&self) -> String {
-        String::from("FRC-0046 Token")
+        self.state.name.clone()
     }
 
     fn symbol(&self) -> String {
-        String::from("FRC46")
+        self.state.symbol.clone()
     }
 
     fn granularity(&self) -> GranularityReturn {
-        1
+        self.state.granularity
     }
 
     fn total_supply(&mut self) -> TotalSupplyReturn {
-        self.util.total_supply()
+        self.token().total_supply()
     }
 
     fn balance_of(&mut self, params: Address) -> Result<BalanceReturn, RuntimeError> {
-        Ok(self.util.balance_of(&params)?)
+        Ok(self.token().balance_of(&params)?)
     }
 
     fn transfer(&mut self, params: TransferParams) -> Result<TransferReturn, RuntimeError> {
-        let operator = caller_address();
-        let mut hook = self.util.transfer(
+        let operator = self.caller_address();
+        let mut hook = self.token().transfer(
             &operator,
             &params.to,
             &params.amount,
@@ -63,23 +93,22 @@ impl FRC46Token for BasicToken<'_> {
             RawBytes::default(),
         )?;
 
-        let cid = self.util.flush()?;
-        sdk::sself::set_root(&cid).unwrap();
-
-        let hook_ret = hook.call(self.util.runtime())?;
-
-        self.reload(&cid)?;
-        let ret = self.util.transfer_return(hook_ret)?;
+        let cid = self.save()?;
+        let (hook_ret, new_root) = self
+            .runtime
+            .call_after_commit(&cid, || hook.call(&self.runtime).map_err(RuntimeError::from))?;
+        self.reload(new_root)?;
+        let ret = self.token().transfer_return(hook_ret)?;
 
         Ok(ret)
     }
 
     fn transfer_from(
         &mut self,
-        params: frc46_token::token::types::TransferFromParams,
+        params: TransferFromParams,
     ) -> Result<TransferFromReturn, RuntimeError> {
-        let operator = caller_address();
-        let mut hook = self.util.transfer_from(
+        let operator = self.caller_address();
+        let mut hook = self.token().transfer_from(
             &operator,
             &params.from,
             &params.to,
@@ -88,13 +117,12 @@ impl FRC46Token for BasicToken<'_> {
             RawBytes::default(),
         )?;
 
-        let cid = self.util.flush()?;
-        sdk::sself::set_root(&cid).unwrap();
-
-        let hook_ret = hook.call(self.util.runtime())?;
-
-        self.reload(&cid)?;
-        let ret = self.util.transfer_from_return(hook_ret)?;
+        let cid = self.save()?;
+        let (hook_ret, new_root) = self
+            .runtime
+            .call_after_commit(&cid, || hook.call(&self.runtime).map_err(RuntimeError::from))?;
+        self.reload(new_root)?;
+        let ret = self.token().transfer_from_return(hook_ret)?;
 
         Ok(ret)
     }
@@ -103,9 +131,9 @@ impl FRC46Token for BasicToken<'_> {
         &mut self,
         params: IncreaseAllowanceParams,
     ) -> Result<AllowanceReturn, RuntimeError> {
-        let owner = caller_address();
+        let owner = self.caller_address();
         let new_allowance =
-            self.util.increase_allowance(&owner, &params.operator, &params.increase)?;
+            self.token().increase_allowance(&owner, &params.operator, &params.increase)?;
         Ok(new_allowance)
     }
 
@@ -113,91 +141,221 @@ impl FRC46Token for BasicToken<'_> {
         &mut self,
         params: DecreaseAllowanceParams,
     ) -> Result<AllowanceReturn, RuntimeError> {
-        let owner = caller_address();
+        let owner = self.caller_address();
         let new_allowance =
-            self.util.decrease_allowance(&owner, &params.operator, &params.decrease)?;
+            self.token().decrease_allowance(&owner, &params.operator, &params.decrease)?;
         Ok(new_allowance)
     }
 
     fn revoke_allowance(&mut self, params: RevokeAllowanceParams) -> Result<(), RuntimeError> {
-        let owner = caller_address();
-        self.util.revoke_allowance(&owner, &params.operator)?;
+        let owner = self.caller_address();
+        self.token().revoke_allowance(&owner, &params.operator)?;
         Ok(())
     }
 
     fn allowance(&mut self, params: GetAllowanceParams) -> Result<AllowanceReturn, RuntimeError> {
-        let allowance = self.util.allowance(&params.owner, &params.operator)?;
+        let allowance = self.token().allowance(&params.owner, &params.operator)?;
         Ok(allowance)
     }
 
     fn burn(&mut self, params: BurnParams) -> Result<BurnReturn, RuntimeError> {
-        let caller = caller_address();
-        let res = self.util.burn(&caller, &params.amount)?;
+        let caller = self.caller_address();
+        let res = self.token().burn(&caller, &params.amount)?;
         Ok(res)
     }
 
-    fn burn_from(
-        &mut self,
-        params: frc46_token::token::types::BurnFromParams,
-    ) -> Result<BurnFromReturn, RuntimeError> {
-        let caller = caller_address();
-        let res = self.util.burn_from(&caller, &params.owner, &params.amount)?;
+    fn burn_from(&mut self, params: BurnFromParams) -> Result<BurnFromReturn, RuntimeError> {
+        let caller = self.caller_address();
+        let res = self.token().burn_from(&caller, &params.owner, &params.amount)?;
         Ok(res)
     }
 }
 
-#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
-pub struct MintParams {
-    pub initial_owner: Address,
-    pub amount: TokenAmount,
-    pub operator_data: RawBytes,
-}
+impl BasicToken {
+    /// Creates a new token, validating its configuration
+    fn new(
+        runtime: ActorRuntime<FvmSyscalls, Blockstore>,
+        params: ConstructorParams,
+    ) -> Result<Self, RuntimeError> {
+        if params.name.trim().is_empty() || params.symbol.trim().is_empty() {
+            return Err(RuntimeError::InvalidParams("name and symbol must not be empty".into()));
+        }
+        if params.granularity == 0 {
+            return Err(RuntimeError::InvalidParams("granularity must be at least 1".into()));
+        }
+        let owner = runtime
+            .resolve_id(&params.owner)
+            .map_err(|_| RuntimeError::InvalidParams("owner address can't be resolved".into()))?;
+        let state = BasicTokenState {
+            token: TokenState::new(&runtime)?,
+            name: params.name,
+            symbol: params.symbol,
+            granularity: params.granularity,
+            owner,
+        };
+        Ok(BasicToken { runtime, state })
+    }
+
+    fn load(runtime: ActorRuntime<FvmSyscalls, Blockstore>) -> Result<Self, RuntimeError> {
+        let root = runtime.root_cid()?;
+        let state = load_state(&runtime, &root)?;
+        Ok(BasicToken { runtime, state })
+    }
+
+    fn save(&self) -> Result<Cid, RuntimeError> {
+        self.runtime
+            .put_cbor(&self.state, Code::Blake2b256)
+            .map_err(|err| RuntimeError::Serialization(err.to_string()))
+    }
+
+    /// Saves the state and sets it as the actor's root
+    fn flush(&self) -> Result<(), RuntimeError> {
+        let cid = self.save()?;
+        Ok(self.runtime.set_root(&cid)?)
+    }
 
-impl BasicToken<'_> {
-    fn reload(&mut self, initial_cid: &Cid) -> Result<(), RuntimeError> {
-        // todo: revise error type here so it plays nice with the result and doesn't need unwrap
-        let new_cid = sdk::sself::root().unwrap();
-        if new_cid != *initial_cid {
-            self.util.load_replace(&new_cid)?;
+    /// Replaces the state with that at `new_root`, if a receiver hook changed it
+    fn reload(&mut self, new_root: Option<Cid>) -> Result<(), RuntimeError> {
+        if let Some(new_root) = new_root {
+            self.state = load_state(&self.runtime, &new_root)?;
         }
         Ok(())
     }
 
+    fn token(&mut self) -> Token<'_, FvmSyscalls, Blockstore> {
+        Token::wrap(&self.runtime, self.state.granularity, &mut self.state.token)
+    }
+
+    fn caller_address(&self) -> Address {
+        Address::new_id(self.runtime.caller())
+    }
+
+    /// Mints new tokens, which only the owner may do
     fn mint(&mut self, params: MintParams) -> Result<MintReturn, RuntimeError> {
-        let mut hook = self.util.mint(
-            &caller_address(),
+        let caller = self.runtime.caller();
+        if caller != self.state.owner {
+            return Err(RuntimeError::AddressNotAuthorized);
+        }
+        let mut hook = self.token().mint(
+            &Address::new_id(caller),
             &params.initial_owner,
             &params.amount,
             params.operator_data,
             Default::default(),
         )?;
 
-        let cid = self.util.flush()?;
-        sdk::sself::set_root(&cid).unwrap();
-
-        let hook_ret = hook.call(self.util.runtime())?;
-
-        self.reload(&cid)?;
-        let ret = self.util.mint_return(hook_ret)?;
+        let cid = self.save()?;
+        let (hook_ret, new_root) = self
+            .runtime
+            .call_after_commit(&cid, || hook.call(&self.runtime).map_err(RuntimeError::from))?;
+        self.reload(new_root)?;
+        let ret = self.token().mint_return(hook_ret)?;
 
         Ok(ret)
     }
 }
 
-#[derive(Error, Debug)]
-enum IpldError {
-    #[error("ipld encoding error: {0}")]
-    Encoding(#[from] fvm_ipld_encoding::Error),
-    #[error("ipld blockstore error: {0}")]
-    Blockstore(#[from] ErrorNumber),
+fn load_state(bs: &impl CborStore, cid: &Cid) -> Result<BasicTokenState, RuntimeError> {
+    match bs.get_cbor(cid) {
+        Ok(Some(state)) => Ok(state),
+        Ok(None) => Err(RuntimeError::Deserialization("no data found".into())),
+        Err(e) => Err(RuntimeError::Deserialization(e.to_string())),
+    }
 }
 
-fn return_ipld<T>(value: &T) -> std::result::Result<u32, IpldError>
-where
-    T: ser::Serialize + ?Sized,
-{
-    let bytes = fvm_ipld_encoding::to_vec(value)?;
-    Ok(sdk::ipld::put_block(DAG_CBOR, bytes.as_slice())?)
+fn token_invoke(method_num: u64, params: u32) -> Result<u32, RuntimeError> {
+    let runtime = ActorRuntime::<FvmSyscalls, Blockstore>::new_fvm_runtime();
+    match_method!(method_num, {
+        "Constructor" => {
+            let token = BasicToken::new(runtime, deserialize_params(params)?)?;
+            token.flush()?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        "Name" => {
+            return_ipld(&BasicToken::load(runtime)?.name())
+        }
+        "Symbol" => {
+            return_ipld(&BasicToken::load(runtime)?.symbol())
+        }
+        "Granularity" => {
+            return_ipld(&BasicToken::load(runtime)?.granularity())
+        }
+        "TotalSupply" => {
+            return_ipld(&BasicToken::load(runtime)?.total_supply())
+        }
+        "BalanceOf" => {
+            let mut token = BasicToken::load(runtime)?;
+            return_ipld(&token.balance_of(deserialize_params(params)?)?)
+        }
+        "Allowance" => {
+            let mut token = BasicToken::load(runtime)?;
+            return_ipld(&token.allowance(deserialize_params(params)?)?)
+        }
+        "IncreaseAllowance" => {
+            let mut token = BasicToken::load(runtime)?;
+            let res = token.increase_allowance(deserialize_params(params)?)?;
+            token.flush()?;
+            return_ipld(&res)
+        }
+        "DecreaseAllowance" => {
+            let mut token = BasicToken::load(runtime)?;
+            let res = token.decrease_allowance(deserialize_params(params)?)?;
+            token.flush()?;
+            return_ipld(&res)
+        }
+        "RevokeAllowance" => {
+            let mut token = BasicToken::load(runtime)?;
+            token.revoke_allowance(deserialize_params(params)?)?;
+            token.flush()?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        "Burn" => {
+            let mut token = BasicToken::load(runtime)?;
+            let res = token.burn(deserialize_params(params)?)?;
+            token.flush()?;
+            return_ipld(&res)
+        }
+        "BurnFrom" => {
+            let mut token = BasicToken::load(runtime)?;
+            let res = token.burn_from(deserialize_params(params)?)?;
+            token.flush()?;
+            return_ipld(&res)
+        }
+        "TransferFrom" => {
+            // state is flushed before the receiver hook is called
+            let mut token = BasicToken::load(runtime)?;
+            return_ipld(&token.transfer_from(deserialize_params(params)?)?)
+        }
+        "Transfer" => {
+            // state is flushed before the receiver hook is called
+            let mut token = BasicToken::load(runtime)?;
+            return_ipld(&token.transfer(deserialize_params(params)?)?)
+        }
+        // Custom actor interface, these are author-defined methods that extend beyond the
+        // FRC46 Token standard
+        "Mint" => {
+            // state is flushed before the receiver hook is called
+            let mut token = BasicToken::load(runtime)?;
+            return_ipld(&token.mint(deserialize_params(params)?)?)
+        }
+        // ERC-20 facade for EVM contracts, which send calldata as a CBOR byte string and expect
+        // the same in return
+        "InvokeEVM" => {
+            let calldata: RawBytes = deserialize_params(params)?;
+            let call = Erc20Call::decode(&calldata)?;
+            let read_only = call.is_read_only();
+            let mut token = BasicToken::load(runtime)?;
+            let caller = token.caller_address();
+            let ret = call.invoke(&mut token, &caller)?;
+            if !read_only {
+                token.flush()?;
+            }
+            return_ipld(&RawBytes::new(ret))
+        }
+        _ => {
+            abort_unhandled(method_num)
+        }
+    })
 }
 
 /// Conduct method dispatch. Handle input parameters and return data.
@@ -208,120 +366,8 @@ pub fn invoke(params: u32) -> u32 {
     }));
 
     let method_num = sdk::message::method_number();
-
-    match method_num {
-        // Actor constructor
-        1 => constructor(),
-
-        // Standard token interface
-        rest => {
-            let root_cid = sdk::sself::root().unwrap();
-
-            let helper = ActorRuntime::<FvmSyscalls, Blockstore>::new_fvm_runtime();
-            let mut token_state =
-                Token::<FvmSyscalls, Blockstore>::load_state(helper.bs(), &root_cid).unwrap();
-
-            let mut token_actor = BasicToken { util: Token::wrap(&helper, 1, &mut token_state) };
-
-            // Method numbers calculated via fvm_dispatch_tools using CamelCase names derived from
-            // the corresponding FRC46Token trait methods.
-            match rest {
-                0x02ea015c => {
-                    // Name
-                    let name = token_actor.name();
-                    return_ipld(&name).unwrap()
-                }
-                0x7adab63e => {
-                    // Symbol
-                    let symbol = token_actor.symbol();
-                    return_ipld(&symbol).unwrap()
-                }
-                0x06da7a35 => {
-                    // TotalSupply
-                    let total_supply = token_actor.total_supply();
-                    return_ipld(&total_supply).unwrap()
-                }
-                0x8710e1ac => {
-                    //BalanceOf
-                    let params = deserialize_params(params);
-                    let res = token_actor.balance_of(params).unwrap();
-                    return_ipld(&res).unwrap()
-                }
-                0xfaa45236 => {
-                    // Allowance
-                    let params = deserialize_params(params);
-                    let res = token_actor.allowance(params).unwrap();
-                    return_ipld(&res).unwrap()
-                }
-                0x69ecb918 => {
-                    // IncreaseAllowance
-                    let params = deserialize_params(params);
-                    let res = token_actor.increase_allowance(params).unwrap();
-                    let cid = token_actor.util.flush().unwrap();
-                    sdk::sself::set_root(&cid).unwrap();
-                    return_ipld(&res).unwrap()
-                }
-                0x5b286f21 => {
-                    // DecreaseAllowance
-                    let params = deserialize_params(params);
-                    let res = token_actor.decrease_allowance(params).unwrap();
-                    let cid = token_actor.util.flush().unwrap();
-                    sdk::sself::set_root(&cid).unwrap();
-                    return_ipld(&res).unwrap()
-                }
-                0xa4d840b1 => {
-                    // RevokeAllowance
-                    let params = deserialize_params(params);
-                    token_actor.revoke_allowance(params).unwrap();
-                    let cid = token_actor.util.flush().unwrap();
-                    sdk::sself::set_root(&cid).unwrap();
-                    NO_DATA_BLOCK_ID
-                }
-                0x5584159a => {
-                    // Burn
-                    let params = deserialize_params(params);
-                    let res = token_actor.burn(params).unwrap();
-                    let cid = token_actor.util.flush().unwrap();
-                    sdk::sself::set_root(&cid).unwrap();
-                    return_ipld(&res).unwrap()
-                }
-                0xd7d4deed => {
-                    // TransferFrom
-                    let params = deserialize_params(params);
-                    let res = token_actor.transfer_from(params).unwrap();
-                    return_ipld(&res).unwrap()
-                }
-                0x04cbf732 => {
-                    // Transfer
-                    let params = deserialize_params(params);
-                    let res = token_actor.transfer(params).unwrap();
-                    return_ipld(&res).unwrap()
-                }
-
-                // Custom actor interface, these are author-defined methods that extend beyond the
-                // FRC46 Token standard
-                0x06f84ab2 => {
-                    // Mint
-                    let params: MintParams = deserialize_params(params);
-                    let res = token_actor.mint(params).unwrap();
-                    return_ipld(&res).unwrap()
-                }
-                _ => {
-                    sdk::vm::abort(
-                        ExitCode::USR_UNHANDLED_MESSAGE.value(),
-                        Some("Unknown method number"),
-                    );
-                }
-            }
-        }
+    match token_invoke(method_num, params) {
+        Ok(ret) => ret,
+        Err(err) => sdk::vm::abort(ExitCode::from(&err).value(), Some(&err.to_string())),
     }
 }
-
-fn constructor() -> u32 {
-    let helper = ActorRuntime::<FvmSyscalls, Blockstore>::new_fvm_runtime();
-    let mut token_state = Token::<FvmSyscalls, Blockstore>::create_state(helper.bs()).unwrap();
-    let mut token = Token::wrap(&helper, 1, &mut token_state);
-    let cid = token.flush().unwrap();
-    sdk::sself::set_root(&cid).unwrap();
-    NO_DATA_BLOCK_ID
-}
@@ -1,8 +1,10 @@
-use frc46_token::token::TokenError;
-use fvm_actor_utils::receiver::ReceiverHookError;
-use fvm_ipld_encoding::{de::DeserializeOwned, RawBytes};
+use frc46_token::token::{state::StateError, TokenError};
+use fvm_actor_utils::abi::AbiError;
+use fvm_actor_utils::{messaging::MessagingError, receiver::ReceiverHookError, util::ActorError};
+use fvm_ipld_encoding::{de::DeserializeOwned, ser::Serialize, DAG_CBOR};
 use fvm_sdk as sdk;
-use fvm_shared::address::Address;
+use fvm_shared::error::ExitCode;
+use sdk::sys::ErrorNumber;
 use thiserror::Error;
 
 /// Errors that can occur during the execution of this actor
@@ -14,16 +16,62 @@ pub enum RuntimeError {
     /// Error from the underlying universal receiver hook library
     #[error("error calling receiver hook: {0}")]
     Receiver(#[from] ReceiverHookError),
+    #[error("underlying state error {0}")]
+    State(#[from] StateError),
+    #[error("ipld encoding error: {0}")]
+    Encoding(#[from] fvm_ipld_encoding::Error),
+    #[error("ipld blockstore error: {0}")]
+    Blockstore(#[from] ErrorNumber),
+    #[error("actor runtime error: {0}")]
+    ActorRuntime(#[from] ActorError),
+    #[error("actor messaging error {0}")]
+    Messaging(#[from] MessagingError),
+    #[error("invalid EVM call: {0}")]
+    Abi(#[from] AbiError),
+    #[error("error loading state {0}")]
+    Deserialization(String),
+    #[error("error saving state {0}")]
+    Serialization(String),
+    #[error("missing parameters")]
+    MissingParams,
+    #[error("invalid constructor params: {0}")]
+    InvalidParams(String),
+    #[error("address not authorized")]
+    AddressNotAuthorized,
 }
 
-pub fn caller_address() -> Address {
-    let caller = sdk::message::caller();
-    Address::new_id(caller)
+impl From<&RuntimeError> for ExitCode {
+    fn from(error: &RuntimeError) -> Self {
+        match error {
+            RuntimeError::Token(e) => e.into(),
+            RuntimeError::Receiver(e) => e.into(),
+            RuntimeError::State(e) => e.into(),
+            RuntimeError::Encoding(_)
+            | RuntimeError::Blockstore(_)
+            | RuntimeError::Deserialization(_)
+            | RuntimeError::Serialization(_) => ExitCode::USR_SERIALIZATION,
+            RuntimeError::ActorRuntime(e) => e.into(),
+            RuntimeError::Messaging(e) => e.into(),
+            RuntimeError::Abi(e) => e.into(),
+            RuntimeError::MissingParams | RuntimeError::InvalidParams(_) => {
+                ExitCode::USR_ILLEGAL_ARGUMENT
+            }
+            RuntimeError::AddressNotAuthorized => ExitCode::USR_FORBIDDEN,
+        }
+    }
 }
 
 /// Grab the incoming parameters and convert from RawBytes to deserialized struct
-pub fn deserialize_params<O: DeserializeOwned>(params: u32) -> O {
-    let params = sdk::message::params_raw(params).unwrap().unwrap();
-    let params = RawBytes::new(params.data);
-    params.deserialize().unwrap()
+pub fn deserialize_params<O: DeserializeOwned>(params: u32) -> Result<O, RuntimeError> {
+    let params = sdk::message::params_raw(params)?.ok_or(RuntimeError::MissingParams)?;
+    Ok(params.deserialize()?)
+}
+
+/// Serialize a return value and put it in a block for the caller
+pub fn return_ipld<T>(value: &T) -> Result<u32, RuntimeError>
+where
+    T: Serialize + ?Sized,
+{
+    let bytes = fvm_ipld_encoding::to_vec(value)?;
+    Ok(sdk::ipld::put_block(DAG_CBOR, bytes.as_slice())?)
 }
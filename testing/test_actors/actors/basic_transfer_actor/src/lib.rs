@@ -1,4 +1,5 @@
 use cid::{multihash::Code, Cid};
+use frc42_dispatch::match_method::{abort_reserved, abort_unhandled};
 use frc42_dispatch::{match_method, method_hash};
 use frc46_token::receiver::{FRC46TokenReceived, FRC46_TOKEN_TYPE};
 use frc46_token::token::types::TransferParams;
@@ -144,11 +145,7 @@ fn invoke(input: u32) -> u32 {
             // but the test we run from is checking that already so no need to do it here
             NO_DATA_BLOCK_ID
         }
-        _ => {
-            sdk::vm::abort(
-                ExitCode::USR_UNHANDLED_MESSAGE.value(),
-                Some("Unknown method number"),
-            );
-        }
+        reserved => abort_reserved(method_num),
+        _ => abort_unhandled(method_num),
     })
 }
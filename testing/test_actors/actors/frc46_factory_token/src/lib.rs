@@ -1,14 +1,23 @@
-use frc42_dispatch::match_method;
+use frc42_dispatch::{fallback::InvokeFallback, match_method};
 use fvm_actor_utils::{
-    blockstore::Blockstore, syscalls::fvm_syscalls::FvmSyscalls, util::ActorRuntime,
+    blockstore::Blockstore, receiver::UniversalReceiverParams, syscalls::fvm_syscalls::FvmSyscalls,
+    util::ActorRuntime,
 };
 use fvm_sdk::NO_DATA_BLOCK_ID;
 use fvm_shared::error::ExitCode;
 use token_impl::{
-    construct_token, deserialize_params, frc46_invoke, return_ipld, FactoryToken, MintParams,
-    RuntimeError,
+    blocklist::blocklist_invoke, construct_token, deserialize_params, frc46_invoke,
+    permit::permit_invoke, return_ipld, roles::roles_invoke, self_transfer::SweepParams,
+    FactoryToken, MintBatchParams, MintParams, RedeemParams, RuntimeError, SetMintLimitParams,
+    TransferMinterParams, UpdateMetadataParams,
 };
 
+fn save_state(token: &mut FactoryToken<FvmSyscalls, Blockstore>) -> Result<(), RuntimeError> {
+    let cid = token.save()?;
+    token.runtime().set_root(&cid)?;
+    Ok(())
+}
+
 fn token_invoke(method_num: u64, params: u32) -> Result<u32, RuntimeError> {
     let runtime = ActorRuntime::<FvmSyscalls, Blockstore>::new_fvm_runtime();
     match_method!(method_num, {
@@ -23,6 +32,41 @@ fn token_invoke(method_num: u64, params: u32) -> Result<u32, RuntimeError> {
             let res = token_actor.mint(params)?;
             return_ipld(&res)
         }
+        "MintBatch" => {
+            let root_cid = runtime.root_cid()?;
+            let params: MintBatchParams = deserialize_params(params);
+            let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
+            let res = token_actor.mint_batch(params)?;
+            return_ipld(&res)
+        }
+        "Redeem" => {
+            let root_cid = runtime.root_cid()?;
+            let params: RedeemParams = deserialize_params(params);
+            let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
+            let res = token_actor.redeem(params)?;
+            return_ipld(&res)
+        }
+        "Receive" => {
+            let root_cid = runtime.root_cid()?;
+            let params: UniversalReceiverParams = deserialize_params(params);
+            let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
+            token_actor.token_received(params)?;
+            save_state(&mut token_actor)?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        "Sweep" => {
+            let root_cid = runtime.root_cid()?;
+            let params: SweepParams = deserialize_params(params);
+            let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
+            // state is flushed before the receiver hook is called
+            let res = token_actor.sweep(params)?;
+            return_ipld(&res)
+        }
+        "SelfTransferPolicy" => {
+            let root_cid = runtime.root_cid()?;
+            let token_actor = FactoryToken::load(runtime, &root_cid)?;
+            return_ipld(&token_actor.self_transfer_policy())
+        }
         "DisableMint" => {
             let root_cid = runtime.root_cid()?;
             let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
@@ -34,28 +78,69 @@ fn token_invoke(method_num: u64, params: u32) -> Result<u32, RuntimeError> {
             // no return
             Ok(NO_DATA_BLOCK_ID)
         }
+        "Description" => {
+            let root_cid = runtime.root_cid()?;
+            let token_actor = FactoryToken::load(runtime, &root_cid)?;
+            return_ipld(&token_actor.description())
+        }
+        "Icon" => {
+            let root_cid = runtime.root_cid()?;
+            let token_actor = FactoryToken::load(runtime, &root_cid)?;
+            return_ipld(&token_actor.icon())
+        }
+        "Decimals" => {
+            let root_cid = runtime.root_cid()?;
+            let token_actor = FactoryToken::load(runtime, &root_cid)?;
+            return_ipld(&token_actor.decimals())
+        }
+        "Metadata" => {
+            let root_cid = runtime.root_cid()?;
+            let token_actor = FactoryToken::load(runtime, &root_cid)?;
+            return_ipld(&token_actor.metadata())
+        }
+        "UpdateMetadata" => {
+            let root_cid = runtime.root_cid()?;
+            let params: UpdateMetadataParams = deserialize_params(params);
+            let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
+            token_actor.update_metadata(params)?;
+            save_state(&mut token_actor)?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        "SetMintLimit" => {
+            let root_cid = runtime.root_cid()?;
+            let params: SetMintLimitParams = deserialize_params(params);
+            let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
+            token_actor.set_mint_limit(params)?;
+            save_state(&mut token_actor)?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        "TransferMinter" => {
+            let root_cid = runtime.root_cid()?;
+            let params: TransferMinterParams = deserialize_params(params);
+            let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
+            token_actor.transfer_minter(params)?;
+            save_state(&mut token_actor)?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        "AcceptMinter" => {
+            let root_cid = runtime.root_cid()?;
+            let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
+            token_actor.accept_minter()?;
+            save_state(&mut token_actor)?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
         _ => {
             let root_cid = runtime.root_cid()?;
             let mut token_actor = FactoryToken::load(runtime, &root_cid)?;
 
-            let res = frc46_invoke(method_num, params, &mut token_actor, |token| {
-                // `token` is passed through from the original token provided in the function call
-                // so it won't break mutable borrow rules when used here (trying to use token_actor directly won't work)
-                let cid = token.save()?;
-                token.runtime().set_root(&cid)?;
-                Ok(())
-            })?;
-            match res {
-                // handled by frc46_invoke, return result
-                Some(r) => Ok(r),
-                // method not found
-                None => {
-                    fvm_sdk::vm::abort(
-                        ExitCode::USR_UNHANDLED_MESSAGE.value(),
-                        Some("Unknown method number"),
-                    )
-                }
-            }
+            // `token` is passed through to save_state from the original token provided in the function call
+            // so it won't break mutable borrow rules when used here (trying to use token_actor directly won't work)
+            // each handler passes through methods it doesn't handle to the next
+            roles_invoke(method_num, params, &mut token_actor, save_state)
+                .or_invoke(|| blocklist_invoke(method_num, params, &mut token_actor, save_state))
+                .or_invoke(|| permit_invoke(method_num, params, &mut token_actor, save_state))
+                .or_invoke(|| frc46_invoke(method_num, params, &mut token_actor, save_state))
+                .or_abort_unhandled(method_num)
         }
     })
 }
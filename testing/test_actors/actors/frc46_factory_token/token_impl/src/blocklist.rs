@@ -0,0 +1,275 @@
+//! Blocklist for the factory token
+//!
+//! Admins may add accounts to a blocklist kept in the token state. Blocklisted accounts can't
+//! transfer or receive tokens, whether as the owner, operator or recipient, and can't mint or be
+//! minted to. They can still burn their tokens and remain subject to `ForceBurn`.
+//!
+//! Each change to the blocklist emits an actor event with the affected account.
+use frc42_dispatch::match_method;
+use fvm_actor_utils::{events::EventBuilder, messaging::MessagingError, syscalls::Syscalls};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_sdk::NO_DATA_BLOCK_ID;
+use fvm_shared::{address::Address, ActorID};
+
+use crate::{
+    frc46_return_block, frc46_unpack_params, roles::ADMIN_ROLE, FactoryToken, FactoryTokenState,
+    RuntimeError,
+};
+
+/// Type of the event emitted when an account is added to the blocklist
+pub const BLOCKLIST_ADDED_EVENT: &str = "blocklist_added";
+/// Type of the event emitted when an account is removed from the blocklist
+pub const BLOCKLIST_REMOVED_EVENT: &str = "blocklist_removed";
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct BlocklistParams {
+    pub account: Address,
+}
+
+impl FactoryTokenState {
+    /// Checks if an actor is blocklisted
+    pub fn is_blocklisted(&self, actor: ActorID) -> bool {
+        self.blocklist.binary_search(&actor).is_ok()
+    }
+
+    /// Adds an actor to the blocklist, returning false if it was already present
+    ///
+    /// It is the caller's responsibility to check that the actor using this method is permitted to
+    /// do so.
+    pub fn add_to_blocklist(&mut self, actor: ActorID) -> bool {
+        match self.blocklist.binary_search(&actor) {
+            Ok(_) => false,
+            Err(i) => {
+                self.blocklist.insert(i, actor);
+                true
+            }
+        }
+    }
+
+    /// Removes an actor from the blocklist, returning false if it wasn't present
+    ///
+    /// It is the caller's responsibility to check that the actor using this method is permitted to
+    /// do so.
+    pub fn remove_from_blocklist(&mut self, actor: ActorID) -> bool {
+        match self.blocklist.binary_search(&actor) {
+            Ok(i) => {
+                self.blocklist.remove(i);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
+    /// Blocks an account from transferring, receiving or minting tokens
+    ///
+    /// The caller must hold the `ADMIN_ROLE`
+    pub fn add_to_blocklist(&mut self, params: BlocklistParams) -> Result<(), RuntimeError> {
+        self.state.assert_role(ADMIN_ROLE, self.runtime.caller())?;
+        let account = self.runtime.resolve_or_init(&params.account)?;
+        if self.state.add_to_blocklist(account) {
+            self.emit_blocklist_event(BLOCKLIST_ADDED_EVENT, account)?;
+        }
+        Ok(())
+    }
+
+    /// Lifts the block on an account
+    ///
+    /// The caller must hold the `ADMIN_ROLE`
+    pub fn remove_from_blocklist(&mut self, params: BlocklistParams) -> Result<(), RuntimeError> {
+        self.state.assert_role(ADMIN_ROLE, self.runtime.caller())?;
+        let account = match self.runtime.resolve_id(&params.account) {
+            Ok(id) => id,
+            Err(MessagingError::AddressNotResolved(_)) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if self.state.remove_from_blocklist(account) {
+            self.emit_blocklist_event(BLOCKLIST_REMOVED_EVENT, account)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether an account is blocklisted
+    pub fn is_blocklisted(&self, params: BlocklistParams) -> Result<bool, RuntimeError> {
+        match self.runtime.resolve_id(&params.account) {
+            Ok(id) => Ok(self.state.is_blocklisted(id)),
+            Err(MessagingError::AddressNotResolved(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Fails with `RuntimeError::Blocklisted` if any of the accounts are blocklisted
+    ///
+    /// Addresses that don't resolve to an actor yet can't have been blocklisted.
+    pub(crate) fn assert_not_blocklisted(&self, accounts: &[&Address]) -> Result<(), RuntimeError> {
+        for account in accounts {
+            match self.runtime.resolve_id(account) {
+                Ok(id) if self.state.is_blocklisted(id) => {
+                    return Err(RuntimeError::Blocklisted(**account))
+                }
+                Ok(_) | Err(MessagingError::AddressNotResolved(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_blocklist_event(&self, typ: &str, account: ActorID) -> Result<(), RuntimeError> {
+        let event = EventBuilder::new().typ(typ).field_indexed("account", &account).build()?;
+        Ok(self.runtime.emit_event(&event)?)
+    }
+}
+
+/// Generic invoke for the blocklist methods of the factory token
+///
+/// Works the same way as [`frc46_invoke`](crate::frc46_invoke), calling `flush_state` once after
+/// methods that change the state and returning `Ok(None)` for methods it doesn't handle.
+pub fn blocklist_invoke<S, BS, F>(
+    method_num: u64,
+    params: u32,
+    token: &mut FactoryToken<S, BS>,
+    flush_state: F,
+) -> Result<Option<u32>, RuntimeError>
+where
+    S: Syscalls,
+    BS: Blockstore,
+    F: FnOnce(&mut FactoryToken<S, BS>) -> Result<(), RuntimeError>,
+{
+    let (ret, needs_flush) = match_method!(method_num, {
+        "AddToBlocklist" => {
+            token.add_to_blocklist(frc46_unpack_params(params))?;
+            (Some(NO_DATA_BLOCK_ID), true)
+        }
+        "RemoveFromBlocklist" => {
+            token.remove_from_blocklist(frc46_unpack_params(params))?;
+            (Some(NO_DATA_BLOCK_ID), true)
+        }
+        "IsBlocklisted" => {
+            let res = token.is_blocklisted(frc46_unpack_params(params))?;
+            (frc46_return_block(&res), false)
+        }
+        _ => {
+            (None, false)
+        }
+    });
+
+    if needs_flush {
+        flush_state(token)?;
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use frc46_token::token::types::{
+        FRC46Token, IncreaseAllowanceParams, TransferFromParams, TransferParams,
+    };
+    use fvm_actor_utils::{
+        events::EVENT_TYPE_KEY, shared_blockstore::SharedMemoryBlockstore,
+        syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime,
+    };
+    use fvm_ipld_encoding::{from_slice, RawBytes};
+    use fvm_shared::{address::Address, econ::TokenAmount, ActorID};
+
+    use super::{BlocklistParams, BLOCKLIST_ADDED_EVENT, BLOCKLIST_REMOVED_EVENT};
+    use crate::{FactoryToken, MintParams, RuntimeError};
+
+    const ALICE_ID: ActorID = 1;
+    const BOB_ID: ActorID = 2;
+    const CAROL_ID: ActorID = 3;
+    const BOB: Address = Address::new_id(BOB_ID);
+    const CAROL: Address = Address::new_id(CAROL_ID);
+
+    fn mint(to: Address) -> MintParams {
+        MintParams {
+            initial_owner: to,
+            amount: TokenAmount::from_whole(10),
+            operator_data: RawBytes::default(),
+        }
+    }
+
+    fn transfer(to: Address) -> TransferParams {
+        TransferParams {
+            to,
+            amount: TokenAmount::from_whole(1),
+            operator_data: RawBytes::default(),
+        }
+    }
+
+    #[test]
+    fn it_blocks_listed_accounts() {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        runtime.syscalls.set_caller_id(ALICE_ID);
+        let mut token =
+            FactoryToken::new(runtime, "Test Token".into(), "TEST".into(), 1, Some(ALICE_ID))
+                .unwrap();
+        token.mint(mint(BOB)).unwrap();
+        token.mint(mint(CAROL)).unwrap();
+
+        // only admins can manage the blocklist
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        let err = token.add_to_blocklist(BlocklistParams { account: CAROL }).unwrap_err();
+        assert!(matches!(err, RuntimeError::AddressNotAuthorized));
+        token.runtime.syscalls.set_caller_id(CAROL_ID);
+        token
+            .increase_allowance(IncreaseAllowanceParams {
+                operator: BOB,
+                increase: TokenAmount::from_whole(5),
+            })
+            .unwrap();
+        token.runtime.syscalls.set_caller_id(ALICE_ID);
+        token.add_to_blocklist(BlocklistParams { account: BOB }).unwrap();
+        // adding twice changes nothing
+        token.add_to_blocklist(BlocklistParams { account: BOB }).unwrap();
+        assert!(token.is_blocklisted(BlocklistParams { account: BOB }).unwrap());
+        assert!(!token.is_blocklisted(BlocklistParams { account: CAROL }).unwrap());
+
+        // a blocklisted account can't be minted to, send, receive or act as an operator
+        let err = token.mint(mint(BOB)).unwrap_err();
+        assert!(matches!(err, RuntimeError::Blocklisted(a) if a == BOB));
+        token.runtime.syscalls.set_caller_id(CAROL_ID);
+        let err = token.transfer(transfer(BOB)).unwrap_err();
+        assert!(matches!(err, RuntimeError::Blocklisted(_)));
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        let err = token.transfer(transfer(CAROL)).unwrap_err();
+        assert!(matches!(err, RuntimeError::Blocklisted(_)));
+        let err = token
+            .transfer_from(TransferFromParams {
+                from: CAROL,
+                to: Address::new_id(ALICE_ID),
+                amount: TokenAmount::from_whole(1),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap_err();
+        assert!(matches!(err, RuntimeError::Blocklisted(_)));
+
+        // until it is removed from the blocklist
+        token.runtime.syscalls.set_caller_id(ALICE_ID);
+        token.remove_from_blocklist(BlocklistParams { account: BOB }).unwrap();
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        token.transfer(transfer(CAROL)).unwrap();
+        assert_eq!(token.balance_of(BOB).unwrap(), TokenAmount::from_whole(9));
+
+        // one event was emitted for each change to the blocklist
+        let events: Vec<(String, ActorID)> = token
+            .runtime
+            .syscalls
+            .take_events()
+            .iter()
+            .filter_map(|e| {
+                assert_eq!(e.entries[0].key, EVENT_TYPE_KEY);
+                let typ: String = from_slice(&e.entries[0].value).unwrap();
+                let account = e.entries.iter().find(|e| e.key == "account")?;
+                Some((typ, from_slice(&account.value).unwrap()))
+            })
+            .filter(|(typ, _)| typ.starts_with("blocklist"))
+            .collect();
+        assert_eq!(
+            events,
+            vec![(BLOCKLIST_ADDED_EVENT.into(), BOB_ID), (BLOCKLIST_REMOVED_EVENT.into(), BOB_ID)]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Actor events emitted by the factory token
+//!
+//! Every method that changes balances, allowances or roles emits an event, so explorers can index
+//! tokens deployed from this actor without knowing its methods. Accounts are given by their ActorID
+//! in indexed fields, while amounts are unindexed:
+//!
+//! - `mint`: `operator`, `to`, `amount`
+//! - `transfer`: `operator`, `from`, `to`, `amount`, for both `Transfer` and `TransferFrom`
+//! - `burn`: `operator`, `owner`, `amount`, for every way tokens are burned
+//! - `allowance`: `owner`, `operator`, and the new `allowance`
+//! - `role_granted` and `role_revoked`: `role`, `account`
+use fvm_actor_utils::{events::EventBuilder, messaging::MessagingError, syscalls::Syscalls};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::{address::Address, econ::TokenAmount, ActorID};
+
+use crate::{FactoryToken, RuntimeError};
+
+/// Type of the event emitted when tokens are minted
+pub const MINT_EVENT: &str = "mint";
+/// Type of the event emitted when tokens are transferred
+pub const TRANSFER_EVENT: &str = "transfer";
+/// Type of the event emitted when tokens are burned
+pub const BURN_EVENT: &str = "burn";
+/// Type of the event emitted when an allowance is changed
+pub const ALLOWANCE_EVENT: &str = "allowance";
+/// Type of the event emitted when an account is granted a role
+pub const ROLE_GRANTED_EVENT: &str = "role_granted";
+/// Type of the event emitted when an account loses a role
+pub const ROLE_REVOKED_EVENT: &str = "role_revoked";
+
+impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
+    pub(crate) fn emit_mint_event(
+        &self,
+        operator: ActorID,
+        to: &Address,
+        amount: &TokenAmount,
+    ) -> Result<(), RuntimeError> {
+        let Some(to) = self.event_account(to)? else { return Ok(()) };
+        self.emit(
+            EventBuilder::new()
+                .typ(MINT_EVENT)
+                .field_indexed("operator", &operator)
+                .field_indexed("to", &to)
+                .field("amount", amount),
+        )
+    }
+
+    pub(crate) fn emit_transfer_event(
+        &self,
+        operator: ActorID,
+        from: &Address,
+        to: &Address,
+        amount: &TokenAmount,
+    ) -> Result<(), RuntimeError> {
+        let (Some(from), Some(to)) = (self.event_account(from)?, self.event_account(to)?) else {
+            return Ok(());
+        };
+        self.emit(
+            EventBuilder::new()
+                .typ(TRANSFER_EVENT)
+                .field_indexed("operator", &operator)
+                .field_indexed("from", &from)
+                .field_indexed("to", &to)
+                .field("amount", amount),
+        )
+    }
+
+    pub(crate) fn emit_burn_event(
+        &self,
+        operator: ActorID,
+        owner: &Address,
+        amount: &TokenAmount,
+    ) -> Result<(), RuntimeError> {
+        let Some(owner) = self.event_account(owner)? else { return Ok(()) };
+        self.emit(
+            EventBuilder::new()
+                .typ(BURN_EVENT)
+                .field_indexed("operator", &operator)
+                .field_indexed("owner", &owner)
+                .field("amount", amount),
+        )
+    }
+
+    pub(crate) fn emit_allowance_event(
+        &self,
+        owner: &Address,
+        operator: &Address,
+        allowance: &TokenAmount,
+    ) -> Result<(), RuntimeError> {
+        let (Some(owner), Some(operator)) =
+            (self.event_account(owner)?, self.event_account(operator)?)
+        else {
+            return Ok(());
+        };
+        self.emit(
+            EventBuilder::new()
+                .typ(ALLOWANCE_EVENT)
+                .field_indexed("owner", &owner)
+                .field_indexed("operator", &operator)
+                .field("allowance", allowance),
+        )
+    }
+
+    /// Emits a `role_granted` or `role_revoked` event, depending on `typ`
+    pub(crate) fn emit_role_event(
+        &self,
+        typ: &str,
+        role: &str,
+        account: ActorID,
+    ) -> Result<(), RuntimeError> {
+        self.emit(
+            EventBuilder::new()
+                .typ(typ)
+                .field_indexed("role", role)
+                .field_indexed("account", &account),
+        )
+    }
+
+    /// Resolves an account named in an event
+    ///
+    /// Returns `None` for addresses that don't resolve to an actor, which can only be named by
+    /// operations that changed nothing, so no event is emitted for them.
+    fn event_account(&self, address: &Address) -> Result<Option<ActorID>, RuntimeError> {
+        match self.runtime.resolve_id(address) {
+            Ok(id) => Ok(Some(id)),
+            Err(MessagingError::AddressNotResolved(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn emit(&self, event: EventBuilder) -> Result<(), RuntimeError> {
+        Ok(self.runtime.emit_event(&event.build()?)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use frc46_token::token::types::{
+        BurnParams, FRC46Token, IncreaseAllowanceParams, RevokeAllowanceParams, TransferFromParams,
+        TransferParams,
+    };
+    use fvm_actor_utils::{
+        events::EVENT_TYPE_KEY, shared_blockstore::SharedMemoryBlockstore,
+        syscalls::fake_syscalls::FakeSyscalls, util::ActorRuntime,
+    };
+    use fvm_ipld_encoding::{from_slice, RawBytes};
+    use fvm_shared::{
+        address::Address, bigint::Zero, econ::TokenAmount, event::ActorEvent, ActorID,
+    };
+
+    use super::{
+        ALLOWANCE_EVENT, BURN_EVENT, MINT_EVENT, ROLE_GRANTED_EVENT, ROLE_REVOKED_EVENT,
+        TRANSFER_EVENT,
+    };
+    use crate::{
+        roles::{RoleParams, PAUSER_ROLE},
+        FactoryToken, MintParams,
+    };
+
+    const ALICE_ID: ActorID = 1;
+    const BOB_ID: ActorID = 2;
+    const CAROL_ID: ActorID = 3;
+    const ALICE: Address = Address::new_id(ALICE_ID);
+    const BOB: Address = Address::new_id(BOB_ID);
+    const CAROL: Address = Address::new_id(CAROL_ID);
+
+    fn event_type(event: &ActorEvent) -> String {
+        assert_eq!(event.entries[0].key, EVENT_TYPE_KEY);
+        from_slice(&event.entries[0].value).unwrap()
+    }
+
+    fn field<T: serde::de::DeserializeOwned>(event: &ActorEvent, key: &str) -> T {
+        from_slice(&event.entries.iter().find(|e| e.key == key).unwrap().value).unwrap()
+    }
+
+    #[test]
+    fn it_emits_events_for_each_change() {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        runtime.syscalls.set_caller_id(ALICE_ID);
+        let mut token =
+            FactoryToken::new(runtime, "Test Token".into(), "TEST".into(), 1, Some(ALICE_ID))
+                .unwrap();
+
+        token
+            .mint(MintParams {
+                initial_owner: BOB,
+                amount: TokenAmount::from_whole(10),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+        let events = token.runtime.syscalls.take_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(event_type(&events[0]), MINT_EVENT);
+        assert_eq!(field::<ActorID>(&events[0], "operator"), ALICE_ID);
+        assert_eq!(field::<ActorID>(&events[0], "to"), BOB_ID);
+        assert_eq!(field::<TokenAmount>(&events[0], "amount"), TokenAmount::from_whole(10));
+
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        token
+            .increase_allowance(IncreaseAllowanceParams {
+                operator: CAROL,
+                increase: TokenAmount::from_whole(5),
+            })
+            .unwrap();
+        token
+            .transfer(TransferParams {
+                to: ALICE,
+                amount: TokenAmount::from_whole(1),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+        token.burn(BurnParams { amount: TokenAmount::from_whole(1) }).unwrap();
+        token.runtime.syscalls.set_caller_id(CAROL_ID);
+        token
+            .transfer_from(TransferFromParams {
+                from: BOB,
+                to: CAROL,
+                amount: TokenAmount::from_whole(2),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        token.revoke_allowance(RevokeAllowanceParams { operator: CAROL }).unwrap();
+
+        let events = token.runtime.syscalls.take_events();
+        let types: Vec<_> = events.iter().map(event_type).collect();
+        assert_eq!(
+            types,
+            [ALLOWANCE_EVENT, TRANSFER_EVENT, BURN_EVENT, TRANSFER_EVENT, ALLOWANCE_EVENT]
+        );
+        assert_eq!(field::<TokenAmount>(&events[0], "allowance"), TokenAmount::from_whole(5));
+        // an operator transfer names the owner and operator separately
+        assert_eq!(field::<ActorID>(&events[3], "operator"), CAROL_ID);
+        assert_eq!(field::<ActorID>(&events[3], "from"), BOB_ID);
+        assert_eq!(field::<ActorID>(&events[3], "to"), CAROL_ID);
+        assert_eq!(field::<TokenAmount>(&events[4], "allowance"), TokenAmount::zero());
+
+        // role events are only emitted when membership changes
+        token.runtime.syscalls.set_caller_id(ALICE_ID);
+        let pauser = || RoleParams { role: PAUSER_ROLE.into(), account: CAROL };
+        token.grant_role(pauser()).unwrap();
+        token.grant_role(pauser()).unwrap();
+        token.revoke_role(pauser()).unwrap();
+        token.revoke_role(pauser()).unwrap();
+        let events = token.runtime.syscalls.take_events();
+        let roles: Vec<_> = events
+            .iter()
+            .map(|e| (event_type(e), field::<String>(e, "role"), field::<ActorID>(e, "account")))
+            .collect();
+        assert_eq!(
+            roles,
+            [
+                (ROLE_GRANTED_EVENT.into(), PAUSER_ROLE.into(), CAROL_ID),
+                (ROLE_REVOKED_EVENT.into(), PAUSER_ROLE.into(), CAROL_ID)
+            ]
+        );
+    }
+}
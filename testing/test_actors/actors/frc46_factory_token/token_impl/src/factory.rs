@@ -0,0 +1,182 @@
+//! A factory actor which deploys new instances of the factory token
+//!
+//! Each token is created through the init actor with a delegated (f4) address in the factory's
+//! namespace, derived from the number of tokens deployed before it. The factory keeps a registry of
+//! the tokens it has deployed, mapping each token's ActorID to the actor that requested it.
+use cid::{multihash::Code, Cid};
+use frc46_token::token::state::{actor_id_key, DEFAULT_HAMT_BIT_WIDTH};
+use fvm_actor_utils::{syscalls::Syscalls, util::ActorRuntime};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{
+    tuple::{Deserialize_tuple, Serialize_tuple},
+    CborStore, RawBytes,
+};
+use fvm_ipld_hamt::Hamt;
+use fvm_shared::{address::Address, ActorID};
+
+use crate::{ConstructorParams, RuntimeError};
+
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct FactoryConstructorParams {
+    /// Code CID of the token actor to deploy
+    pub token_code: Cid,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct CreateTokenReturn {
+    pub id_address: Address,
+    pub robust_address: Address,
+    pub delegated_address: Address,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct TokenFactoryState {
+    /// Code CID of the token actor to deploy
+    pub token_code: Cid,
+    /// Map<ActorID, ActorID> of deployed tokens to the actor which created them
+    pub tokens: Cid,
+    /// Number of tokens deployed so far, used to derive the next token's delegated address
+    pub token_count: u64,
+}
+
+pub struct TokenFactory<S: Syscalls, BS: Blockstore> {
+    runtime: ActorRuntime<S, BS>,
+    state: TokenFactoryState,
+}
+
+impl<S: Syscalls, BS: Blockstore> TokenFactory<S, BS> {
+    pub fn new(runtime: ActorRuntime<S, BS>, token_code: Cid) -> Result<Self, RuntimeError> {
+        let tokens =
+            Hamt::<_, ActorID>::new_with_bit_width(&runtime, DEFAULT_HAMT_BIT_WIDTH).flush()?;
+        Ok(TokenFactory {
+            state: TokenFactoryState { token_code, tokens, token_count: 0 },
+            runtime,
+        })
+    }
+
+    pub fn load(runtime: ActorRuntime<S, BS>, cid: &Cid) -> Result<Self, RuntimeError> {
+        let state = match runtime.get_cbor::<TokenFactoryState>(cid) {
+            Ok(Some(s)) => s,
+            Ok(None) => return Err(RuntimeError::Deserialization("no data found".into())),
+            Err(e) => return Err(RuntimeError::Deserialization(e.to_string())),
+        };
+        Ok(TokenFactory { runtime, state })
+    }
+
+    pub fn save(&self) -> Result<Cid, RuntimeError> {
+        self.runtime
+            .put_cbor(&self.state, Code::Blake2b256)
+            .map_err(|err| RuntimeError::Serialization(err.to_string()))
+    }
+
+    pub fn runtime(&self) -> &ActorRuntime<S, BS> {
+        &self.runtime
+    }
+
+    pub fn state(&self) -> &TokenFactoryState {
+        &self.state
+    }
+
+    /// Deploys a new token, recording the caller as its creator
+    pub fn create_token(
+        &mut self,
+        params: ConstructorParams,
+    ) -> Result<CreateTokenReturn, RuntimeError> {
+        // an eight byte subaddress is always within the allowed length
+        let delegated_address =
+            Address::new_delegated(self.runtime.actor_id(), &self.state.token_count.to_be_bytes())
+                .unwrap();
+        let ret = self.runtime.create_actor(
+            self.state.token_code,
+            RawBytes::serialize(&params)?,
+            Some(&delegated_address),
+        )?;
+        let token_id = self.runtime.resolve_id(&ret.id_address)?;
+
+        let mut tokens = Hamt::<_, ActorID>::load_with_bit_width(
+            &self.state.tokens,
+            &self.runtime,
+            DEFAULT_HAMT_BIT_WIDTH,
+        )?;
+        tokens.set(actor_id_key(token_id), self.runtime.caller())?;
+        self.state.tokens = tokens.flush()?;
+        self.state.token_count += 1;
+
+        Ok(CreateTokenReturn {
+            id_address: ret.id_address,
+            robust_address: ret.robust_address,
+            delegated_address,
+        })
+    }
+
+    /// Returns the actor that created `token`, or None if it wasn't deployed by this factory
+    pub fn creator_of(&self, token: ActorID) -> Result<Option<ActorID>, RuntimeError> {
+        let tokens = Hamt::<_, ActorID>::load_with_bit_width(
+            &self.state.tokens,
+            &self.runtime,
+            DEFAULT_HAMT_BIT_WIDTH,
+        )?;
+        Ok(tokens.get(&actor_id_key(token))?.copied())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::Cid;
+    use fvm_actor_utils::{
+        init::{Exec4Params, EXEC4_METHOD},
+        shared_blockstore::SharedMemoryBlockstore,
+        syscalls::fake_syscalls::FakeSyscalls,
+        util::ActorRuntime,
+    };
+    use fvm_shared::address::Address;
+
+    use super::TokenFactory;
+    use crate::ConstructorParams;
+
+    #[test]
+    fn it_deploys_and_registers_tokens() {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let factory_id = runtime.actor_id();
+        let token_code = Cid::default();
+        let mut factory = TokenFactory::new(runtime, token_code).unwrap();
+        factory.runtime().syscalls.set_caller_id(100);
+
+        let params = || ConstructorParams {
+            name: String::from("Test Token"),
+            symbol: String::from("TEST"),
+            granularity: 1,
+            description: String::new(),
+            icon: None,
+            decimals: 18,
+            fil_backed: false,
+            self_transfer: SelfTransferPolicy::Reject,
+            minter: Address::new_id(100),
+        };
+        let first = factory.create_token(params()).unwrap();
+        let second = factory.create_token(params()).unwrap();
+        assert_ne!(first.id_address, second.id_address);
+        assert_eq!(
+            first.delegated_address,
+            Address::new_delegated(factory_id, &0u64.to_be_bytes()).unwrap()
+        );
+
+        // the token was constructed with the params given, and can be found by its f4 address
+        let message = factory.runtime().syscalls.last_message.borrow().clone().unwrap();
+        assert_eq!(message.method, EXEC4_METHOD);
+        let exec: Exec4Params = message.params.unwrap().deserialize().unwrap();
+        assert_eq!(exec.code_cid, token_code);
+        let constructed: ConstructorParams = exec.constructor_params.deserialize().unwrap();
+        assert_eq!(constructed.symbol, "TEST");
+        let second_id = factory.runtime().resolve_id(&second.delegated_address).unwrap();
+        assert_eq!(Address::new_id(second_id), second.id_address);
+
+        // the registry survives a save and load
+        let cid = factory.save().unwrap();
+        let factory = TokenFactory::load(factory.runtime, &cid).unwrap();
+        assert_eq!(factory.state().token_count, 2);
+        assert_eq!(factory.creator_of(second_id).unwrap(), Some(100));
+        assert_eq!(factory.creator_of(factory_id).unwrap(), None);
+    }
+}
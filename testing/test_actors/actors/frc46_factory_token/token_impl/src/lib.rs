@@ -11,22 +11,42 @@ use frc46_token::token::{
     Token, TokenError,
 };
 use fvm_actor_utils::{
+    abi::AbiError,
     messaging::MessagingError,
-    receiver::ReceiverHookError,
+    receiver::{
+        universal::{FRC46TokenReceived, FRC46_TOKEN_TYPE},
+        ReceiverHookBatch, ReceiverHookError, UniversalReceiverParams,
+    },
     syscalls::Syscalls,
+    transaction::Transactional,
     util::{ActorError, ActorRuntime},
 };
 use fvm_ipld_blockstore::{Block, Blockstore};
 use fvm_ipld_encoding::{
     tuple::{Deserialize_tuple, Serialize_tuple},
-    CborStore, RawBytes, DAG_CBOR,
+    RawBytes, DAG_CBOR,
 };
 use fvm_sdk::error::{StateReadError, StateUpdateError};
 use fvm_sdk::{self as sdk, sys::ErrorNumber, NO_DATA_BLOCK_ID};
-use fvm_shared::{address::Address, econ::TokenAmount, error::ExitCode, ActorID};
+use fvm_shared::{
+    address::Address, clock::ChainEpoch, econ::TokenAmount, error::ExitCode, ActorID,
+};
 use serde::{de::DeserializeOwned, ser::Serialize};
 use thiserror::Error;
 
+pub mod blocklist;
+pub mod events;
+pub mod factory;
+pub mod migration;
+pub mod permit;
+pub mod roles;
+pub mod self_transfer;
+
+use events::{ROLE_GRANTED_EVENT, ROLE_REVOKED_EVENT};
+use permit::PermitNonce;
+use roles::{RoleMembers, ADMIN_ROLE, MINTER_ROLE};
+use self_transfer::SelfTransferPolicy;
+
 /// Errors that can occur during the execution of this actor
 #[derive(Error, Debug)]
 pub enum RuntimeError {
@@ -57,10 +77,49 @@ pub enum RuntimeError {
     State(#[from] StateError),
     #[error("actor messaging error {0}")]
     Messaging(#[from] MessagingError),
+    #[error("error in token registry {0}")]
+    Registry(#[from] fvm_ipld_hamt::Error),
     #[error("address not authorized")]
     AddressNotAuthorized,
     #[error("minting has been permanently disabled")]
     MintingDisabled,
+    #[error("token is paused")]
+    Paused,
+    #[error("mint of {amount} exceeds the remaining budget of {remaining} for this window")]
+    MintLimitExceeded { amount: TokenAmount, remaining: TokenAmount },
+    #[error("expected {expected} FIL to be sent with the message but received {received}")]
+    IncorrectValue { expected: TokenAmount, received: TokenAmount },
+    #[error("token is not backed by FIL")]
+    NotFilBacked,
+    #[error("token does not accept these assets")]
+    UnexpectedAssets,
+    #[error("account {0} is blocklisted")]
+    Blocklisted(Address),
+    #[error("permit can't be encoded for signing: {0}")]
+    Abi(#[from] AbiError),
+    #[error("signature is not valid for this permit")]
+    InvalidSignature,
+    #[error("permit expired at epoch {deadline}, current epoch is {epoch}")]
+    PermitExpired { deadline: ChainEpoch, epoch: ChainEpoch },
+    #[error("invalid constructor params: {0}")]
+    InvalidConstructorParams(#[from] ConstructorParamsError),
+}
+
+/// Reasons the parameters a token is constructed with may be rejected
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConstructorParamsError {
+    #[error("name must not be empty")]
+    EmptyName,
+    #[error("symbol must not be empty")]
+    EmptySymbol,
+    #[error("symbol must be at most {MAX_SYMBOL_LENGTH} characters")]
+    SymbolTooLong,
+    #[error("symbol may only contain ASCII letters, digits, '.', '-' and '_', found {0:?}")]
+    InvalidSymbolChar(char),
+    #[error("granularity must be at least 1")]
+    ZeroGranularity,
+    #[error("minter address {0} could not be resolved")]
+    UnresolvableMinter(Address),
 }
 
 impl From<&RuntimeError> for ExitCode {
@@ -94,9 +153,19 @@ impl From<&RuntimeError> for ExitCode {
             }
             RuntimeError::State(e) => e.into(),
             RuntimeError::Messaging(e) => e.into(),
-            RuntimeError::AddressNotAuthorized | RuntimeError::MintingDisabled => {
-                ExitCode::USR_FORBIDDEN
-            }
+            RuntimeError::Registry(_) => ExitCode::USR_SERIALIZATION,
+            RuntimeError::AddressNotAuthorized
+            | RuntimeError::MintingDisabled
+            | RuntimeError::Paused
+            | RuntimeError::MintLimitExceeded { .. }
+            | RuntimeError::NotFilBacked
+            | RuntimeError::UnexpectedAssets
+            | RuntimeError::Blocklisted(_)
+            | RuntimeError::InvalidSignature
+            | RuntimeError::PermitExpired { .. } => ExitCode::USR_FORBIDDEN,
+            RuntimeError::Abi(_)
+            | RuntimeError::IncorrectValue { .. }
+            | RuntimeError::InvalidConstructorParams(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
         }
     }
 }
@@ -106,18 +175,68 @@ pub struct ConstructorParams {
     pub name: String,
     pub symbol: String,
     pub granularity: u64,
-    /// authorised mint operator
-    /// only this address can mint tokens or remove themselves to permanently disable minting
+    /// human-readable description of the token, for display by wallets
+    pub description: String,
+    /// CID of an image to display for the token
+    pub icon: Option<Cid>,
+    /// number of decimal places wallets should display amounts with
+    pub decimals: u8,
+    /// whether each token is backed by one unit of FIL, which anyone can deposit to mint
+    pub fil_backed: bool,
+    /// what to do with tokens transferred to the token actor itself
+    pub self_transfer: SelfTransferPolicy,
+    /// initial admin and mint operator
+    /// this address can grant further roles, mint tokens or permanently disable minting
     pub minter: Address,
 }
 
+/// Maximum length of a token symbol
+pub const MAX_SYMBOL_LENGTH: usize = 32;
+
+/// Checks the identifying parameters of a new token, so it can't be constructed in a state wallets
+/// can't display or in which no amount can be minted
+pub fn validate_token_params(
+    name: &str,
+    symbol: &str,
+    granularity: u64,
+) -> Result<(), ConstructorParamsError> {
+    if name.trim().is_empty() {
+        return Err(ConstructorParamsError::EmptyName);
+    }
+    if symbol.is_empty() {
+        return Err(ConstructorParamsError::EmptySymbol);
+    }
+    if symbol.len() > MAX_SYMBOL_LENGTH {
+        return Err(ConstructorParamsError::SymbolTooLong);
+    }
+    if let Some(c) =
+        symbol.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')))
+    {
+        return Err(ConstructorParamsError::InvalidSymbolChar(c));
+    }
+    if granularity == 0 {
+        return Err(ConstructorParamsError::ZeroGranularity);
+    }
+    Ok(())
+}
+
 pub fn construct_token<S: Syscalls, BS: Blockstore>(
     runtime: ActorRuntime<S, BS>,
     params: ConstructorParams,
 ) -> Result<u32, RuntimeError> {
-    let minter = runtime.resolve_id(&params.minter)?;
-    let token =
-        FactoryToken::new(runtime, params.name, params.symbol, params.granularity, Some(minter));
+    let minter = runtime
+        .resolve_id(&params.minter)
+        .map_err(|_| ConstructorParamsError::UnresolvableMinter(params.minter))?;
+    let mut token =
+        FactoryToken::new(runtime, params.name, params.symbol, params.granularity, Some(minter))?;
+    token.state.description = params.description;
+    token.state.icon = params.icon;
+    token.state.decimals = params.decimals;
+    token.state.fil_backed = params.fil_backed;
+    token.state.self_transfer = params.self_transfer;
+    for role in [ADMIN_ROLE, MINTER_ROLE] {
+        token.emit_role_event(ROLE_GRANTED_EVENT, role, minter)?;
+    }
 
     let cid = token.save()?;
     token.runtime.set_root(&cid)?;
@@ -125,16 +244,81 @@ pub fn construct_token<S: Syscalls, BS: Blockstore>(
     Ok(NO_DATA_BLOCK_ID)
 }
 
-#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
 pub struct FactoryTokenState {
+    /// Version of the state layout, see [`migration`]
+    pub version: u64,
     /// Default token helper impl
     pub token: TokenState,
     /// basic token identifier stuff, should it go here or store separately alongside the state
     pub name: String,
     pub symbol: String,
     pub granularity: u64,
-    /// address of authorised minting operator
-    pub minter: Option<ActorID>,
+    pub description: String,
+    pub icon: Option<Cid>,
+    pub decimals: u8,
+    /// Actors holding each role, sorted by role name
+    pub roles: Vec<RoleMembers>,
+    /// Whether transfers, minting and burning are paused
+    pub paused: bool,
+    /// Whether minting has been permanently disabled
+    pub minting_disabled: bool,
+    /// A transfer of the minter role waiting to be accepted
+    pub pending_minter: Option<PendingMinter>,
+    /// Maximum amount that may be minted per window of epochs, if limited
+    pub mint_limit: Option<MintLimit>,
+    /// Whether each token is backed by one unit of FIL held by this actor
+    pub fil_backed: bool,
+    /// Sorted list of accounts which may not transfer, receive or mint tokens
+    pub blocklist: Vec<ActorID>,
+    /// Next permit nonce of each owner that has used a permit, sorted by owner
+    pub permit_nonces: Vec<PermitNonce>,
+    /// What to do with tokens transferred to the token actor itself
+    pub self_transfer: SelfTransferPolicy,
+}
+
+/// A budget for minting which is replenished at the start of each window of epochs
+///
+/// Bounds how much a compromised minter key can mint before the role is revoked.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct MintLimit {
+    /// Maximum total amount minted in a single window
+    pub max_amount: TokenAmount,
+    /// Length of a window in epochs
+    pub window: ChainEpoch,
+    /// First epoch of the current window
+    pub window_start: ChainEpoch,
+    /// Amount minted so far in the current window
+    pub minted: TokenAmount,
+}
+
+impl MintLimit {
+    pub fn new(max_amount: TokenAmount, window: ChainEpoch, epoch: ChainEpoch) -> Self {
+        MintLimit { max_amount, window, window_start: epoch, minted: TokenAmount::default() }
+    }
+
+    /// Records `amount` as minted at `epoch`, failing if it exceeds what is left of the budget
+    pub fn consume(&mut self, epoch: ChainEpoch, amount: &TokenAmount) -> Result<(), RuntimeError> {
+        let window = self.window.max(1);
+        if epoch >= self.window_start.saturating_add(window) {
+            // start the window that `epoch` falls in, so windows stay aligned to the first one
+            self.window_start += (epoch - self.window_start) / window * window;
+            self.minted = TokenAmount::default();
+        }
+        let remaining = &self.max_amount - &self.minted;
+        if amount > &remaining {
+            return Err(RuntimeError::MintLimitExceeded { amount: amount.clone(), remaining });
+        }
+        self.minted = &self.minted + amount;
+        Ok(())
+    }
+}
+
+/// A minter role transfer nominated by `from`, which completes when `to` accepts it
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingMinter {
+    pub from: ActorID,
+    pub to: ActorID,
 }
 
 pub struct FactoryToken<S: Syscalls, BS: Blockstore> {
@@ -142,16 +326,13 @@ pub struct FactoryToken<S: Syscalls, BS: Blockstore> {
     state: FactoryTokenState,
 }
 
+impl Transactional for FactoryTokenState {}
+
 impl FactoryTokenState {
-    /// Load token state from the blockstore provided in `runtime`
+    /// Load token state from the blockstore provided in `runtime`, migrating older layouts
     /// This is for internal use only as part of FactoryToken::load
     fn load<BS: Blockstore>(runtime: &BS, cid: &Cid) -> Result<Self, RuntimeError> {
-        match runtime.get_cbor::<Self>(cid) {
-            Ok(Some(s)) => Ok(s),
-            // TODO: improve on these errors?
-            Ok(None) => Err(RuntimeError::Deserialization("no data found".into())),
-            Err(e) => Err(RuntimeError::Deserialization(e.to_string())),
-        }
+        migration::load_state(runtime, cid)
     }
 }
 
@@ -181,7 +362,9 @@ impl<SC: Syscalls, BS: Blockstore> FRC46Token for FactoryToken<SC, BS> {
     }
 
     fn transfer(&mut self, params: TransferParams) -> Result<TransferReturn, RuntimeError> {
+        self.assert_not_paused()?;
         let operator = self.caller_address();
+        self.assert_not_blocklisted(&[&operator, &params.to])?;
         let mut hook = self.token().transfer(
             &operator,
             &params.to,
@@ -189,13 +372,13 @@ impl<SC: Syscalls, BS: Blockstore> FRC46Token for FactoryToken<SC, BS> {
             params.operator_data,
             RawBytes::default(),
         )?;
+        self.emit_transfer_event(self.runtime.caller(), &operator, &params.to, &params.amount)?;
 
         let cid = self.save()?;
-        self.runtime.set_root(&cid)?;
-
-        let hook_ret = hook.call(self.token().runtime())?;
-
-        self.reload(&cid)?;
+        let (hook_ret, new_root) = self
+            .runtime
+            .call_after_commit(&cid, || hook.call(&self.runtime).map_err(RuntimeError::from))?;
+        self.reload(new_root)?;
         let ret = self.token().transfer_return(hook_ret)?;
 
         Ok(ret)
@@ -205,7 +388,9 @@ impl<SC: Syscalls, BS: Blockstore> FRC46Token for FactoryToken<SC, BS> {
         &mut self,
         params: TransferFromParams,
     ) -> Result<TransferFromReturn, RuntimeError> {
+        self.assert_not_paused()?;
         let operator = self.caller_address();
+        self.assert_not_blocklisted(&[&operator, &params.from, &params.to])?;
         let mut hook = self.token().transfer_from(
             &operator,
             &params.from,
@@ -214,13 +399,13 @@ impl<SC: Syscalls, BS: Blockstore> FRC46Token for FactoryToken<SC, BS> {
             params.operator_data,
             RawBytes::default(),
         )?;
+        self.emit_transfer_event(self.runtime.caller(), &params.from, &params.to, &params.amount)?;
 
         let cid = self.save()?;
-        self.runtime.set_root(&cid)?;
-
-        let hook_ret = hook.call(self.token().runtime())?;
-
-        self.reload(&cid)?;
+        let (hook_ret, new_root) = self
+            .runtime
+            .call_after_commit(&cid, || hook.call(&self.runtime).map_err(RuntimeError::from))?;
+        self.reload(new_root)?;
         let ret = self.token().transfer_from_return(hook_ret)?;
 
         Ok(ret)
@@ -233,6 +418,7 @@ impl<SC: Syscalls, BS: Blockstore> FRC46Token for FactoryToken<SC, BS> {
         let owner = self.caller_address();
         let new_allowance =
             self.token().increase_allowance(&owner, &params.operator, &params.increase)?;
+        self.emit_allowance_event(&owner, &params.operator, &new_allowance)?;
         Ok(new_allowance)
     }
 
@@ -243,12 +429,14 @@ impl<SC: Syscalls, BS: Blockstore> FRC46Token for FactoryToken<SC, BS> {
         let owner = self.caller_address();
         let new_allowance =
             self.token().decrease_allowance(&owner, &params.operator, &params.decrease)?;
+        self.emit_allowance_event(&owner, &params.operator, &new_allowance)?;
         Ok(new_allowance)
     }
 
     fn revoke_allowance(&mut self, params: RevokeAllowanceParams) -> Result<(), RuntimeError> {
         let owner = self.caller_address();
         self.token().revoke_allowance(&owner, &params.operator)?;
+        self.emit_allowance_event(&owner, &params.operator, &TokenAmount::default())?;
         Ok(())
     }
 
@@ -258,8 +446,10 @@ impl<SC: Syscalls, BS: Blockstore> FRC46Token for FactoryToken<SC, BS> {
     }
 
     fn burn(&mut self, params: BurnParams) -> Result<BurnReturn, RuntimeError> {
+        self.assert_not_paused()?;
         let caller = self.caller_address();
         let res = self.token().burn(&caller, &params.amount)?;
+        self.emit_burn_event(self.runtime.caller(), &caller, &params.amount)?;
         Ok(res)
     }
 
@@ -267,8 +457,10 @@ impl<SC: Syscalls, BS: Blockstore> FRC46Token for FactoryToken<SC, BS> {
         &mut self,
         params: frc46_token::token::types::BurnFromParams,
     ) -> Result<BurnFromReturn, RuntimeError> {
+        self.assert_not_paused()?;
         let caller = self.caller_address();
         let res = self.token().burn_from(&caller, &params.owner, &params.amount)?;
+        self.emit_burn_event(self.runtime.caller(), &params.owner, &params.amount)?;
         Ok(res)
     }
 }
@@ -280,24 +472,98 @@ pub struct MintParams {
     pub operator_data: RawBytes,
 }
 
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct RedeemParams {
+    pub amount: TokenAmount,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct MintBatchParams {
+    pub mints: Vec<MintParams>,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct MintBatchReturn {
+    /// The result of each mint, in the order given
+    pub results: Vec<MintReturn>,
+}
+
+/// Number of decimals used by tokens which don't specify otherwise, matching FIL
+pub const DEFAULT_DECIMALS: u8 = 18;
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct MetadataReturn {
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    pub description: String,
+    pub icon: Option<Cid>,
+    pub decimals: u8,
+}
+
+/// Replaces the description and icon of the token
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct UpdateMetadataParams {
+    pub description: String,
+    pub icon: Option<Cid>,
+}
+
+/// Sets the mint budget, or removes it if `None`
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct SetMintLimitParams {
+    pub limit: Option<MintLimitParams>,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct MintLimitParams {
+    pub max_amount: TokenAmount,
+    pub window: ChainEpoch,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct TransferMinterParams {
+    pub new_minter: Address,
+}
+
 impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
+    /// Creates a new token, granting the admin and minter roles to `minter` if given
+    ///
+    /// If no minter is given, minting is disabled from the start. The token has no description or
+    /// icon and uses [`DEFAULT_DECIMALS`].
+    ///
+    /// Fails if the name, symbol or granularity are invalid, see [`validate_token_params`].
     pub fn new(
         runtime: ActorRuntime<S, BS>,
         name: String,
         symbol: String,
         granularity: u64,
         minter: Option<ActorID>,
-    ) -> Self {
-        FactoryToken {
-            state: FactoryTokenState {
-                token: TokenState::new(&runtime).unwrap(),
-                name,
-                symbol,
-                granularity,
-                minter,
-            },
-            runtime,
+    ) -> Result<Self, RuntimeError> {
+        validate_token_params(&name, &symbol, granularity)?;
+        let mut state = FactoryTokenState {
+            version: migration::STATE_VERSION,
+            token: TokenState::new(&runtime)?,
+            name,
+            symbol,
+            granularity,
+            description: String::new(),
+            icon: None,
+            decimals: DEFAULT_DECIMALS,
+            roles: Vec::new(),
+            paused: false,
+            minting_disabled: minter.is_none(),
+            pending_minter: None,
+            mint_limit: None,
+            fil_backed: false,
+            blocklist: Vec::new(),
+            permit_nonces: Vec::new(),
+            self_transfer: SelfTransferPolicy::default(),
+        };
+        if let Some(minter) = minter {
+            state.grant_role(ADMIN_ROLE, minter);
+            state.grant_role(MINTER_ROLE, minter);
         }
+        Ok(FactoryToken { state, runtime })
     }
 
     pub fn caller_address(&self) -> Address {
@@ -322,11 +588,10 @@ impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
             .map_err(|err| RuntimeError::Serialization(err.to_string()))
     }
 
-    fn reload(&mut self, initial_cid: &Cid) -> Result<(), RuntimeError> {
-        let new_cid = self.runtime.root_cid()?;
-        if new_cid != *initial_cid {
-            let new_state = FactoryTokenState::load(&self.runtime, &new_cid)?;
-            let _old = std::mem::replace(&mut self.state, new_state);
+    /// Replaces the state with that at `new_root`, if a receiver hook changed it
+    fn reload(&mut self, new_root: Option<Cid>) -> Result<(), RuntimeError> {
+        if let Some(new_root) = new_root {
+            self.state = FactoryTokenState::load(&self.runtime, &new_root)?;
         }
         Ok(())
     }
@@ -336,13 +601,8 @@ impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
     }
 
     pub fn mint(&mut self, params: MintParams) -> Result<MintReturn, RuntimeError> {
-        // check if the caller matches our authorise mint operator
-        // no minter address means minting has been permanently disabled
-        let minter = self.state.minter.ok_or(RuntimeError::MintingDisabled)?;
+        let mint_limit = self.authorize_mint(std::slice::from_ref(&params))?;
         let caller_id = self.runtime.caller();
-        if caller_id != minter {
-            return Err(RuntimeError::AddressNotAuthorized);
-        }
 
         let mut hook = self.token().mint(
             &Address::new_id(caller_id),
@@ -351,30 +611,280 @@ impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
             params.operator_data,
             Default::default(),
         )?;
+        self.emit_mint_event(caller_id, &params.initial_owner, &params.amount)?;
+        self.state.mint_limit = mint_limit;
 
         let cid = self.save()?;
-        self.runtime.set_root(&cid)?;
+        let (hook_ret, new_root) = self
+            .runtime
+            .call_after_commit(&cid, || hook.call(&self.runtime).map_err(RuntimeError::from))?;
+        self.reload(new_root)?;
+        let ret = self.token().mint_return(hook_ret)?;
 
-        let hook_ret = hook.call(self.token().runtime())?;
+        Ok(ret)
+    }
 
-        self.reload(&cid)?;
-        let ret = self.token().mint_return(hook_ret)?;
+    /// Mints to several recipients at once
+    ///
+    /// All recipients are credited together, so that none are if any mint fails, and the state is
+    /// saved once before calling each recipient's receiver hook in turn. A recipient may change the
+    /// state from its hook, in which case the state is reloaded before calling the next one.
+    pub fn mint_batch(&mut self, params: MintBatchParams) -> Result<MintBatchReturn, RuntimeError> {
+        let mint_limit = self.authorize_mint(&params.mints)?;
+        let caller_id = self.runtime.caller();
+
+        let operator = Address::new_id(caller_id);
+        let minted: Vec<_> =
+            params.mints.iter().map(|m| (m.initial_owner, m.amount.clone())).collect();
+        let (runtime, granularity) = (&self.runtime, self.state.granularity);
+        let mut hooks = self.state.transaction(|state| {
+            let mut hooks = ReceiverHookBatch::new();
+            for mint in params.mints {
+                let mut token = Token::wrap(runtime, granularity, &mut state.token);
+                match token.mint(
+                    &operator,
+                    &mint.initial_owner,
+                    &mint.amount,
+                    mint.operator_data,
+                    Default::default(),
+                ) {
+                    Ok(hook) => hooks.push(hook),
+                    Err(e) => {
+                        // the credits are rolled back, so no recipient may be notified
+                        hooks.discard();
+                        return Err(RuntimeError::from(e));
+                    }
+                }
+            }
+            Ok(hooks)
+        })?;
+        for (to, amount) in &minted {
+            self.emit_mint_event(caller_id, to, amount)?;
+        }
+        self.state.mint_limit = mint_limit;
+
+        let mut cid = self.save()?;
+        let hook_rets = hooks.try_call_all(|hook| {
+            let (hook_ret, new_root) = self
+                .runtime
+                .call_after_commit(&cid, || hook.call(&self.runtime).map_err(RuntimeError::from))?;
+            if let Some(new_root) = new_root {
+                self.reload(Some(new_root))?;
+                cid = new_root;
+            }
+            Ok::<_, RuntimeError>(hook_ret)
+        })?;
+        let results = hook_rets
+            .into_iter()
+            .map(|hook_ret| self.token().mint_return(hook_ret))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MintBatchReturn { results })
+    }
+
+    /// Checks that the caller may make `mints`, returning the mint limit updated to include them
+    ///
+    /// FIL-backed tokens may be minted by anyone sending exactly the total amount in FIL, and
+    /// neither the minter role nor the mint limit apply. Other tokens may only be minted by
+    /// minters, within the mint limit.
+    fn authorize_mint(&self, mints: &[MintParams]) -> Result<Option<MintLimit>, RuntimeError> {
+        self.assert_not_blocklisted(&[&self.caller_address()])?;
+        for mint in mints {
+            self.assert_not_blocklisted(&[&mint.initial_owner])?;
+        }
+
+        if self.state.fil_backed {
+            let expected = mints.iter().fold(TokenAmount::default(), |acc, m| &acc + &m.amount);
+            let received = self.runtime.message_value_received();
+            if received != expected {
+                return Err(RuntimeError::IncorrectValue { expected, received });
+            }
+            self.assert_not_paused()?;
+            return Ok(self.state.mint_limit.clone());
+        }
+
+        if self.state.minting_disabled {
+            return Err(RuntimeError::MintingDisabled);
+        }
+        self.state.assert_role(MINTER_ROLE, self.runtime.caller())?;
+        self.assert_not_paused()?;
+        let mut mint_limit = self.state.mint_limit.clone();
+        if let Some(limit) = &mut mint_limit {
+            for mint in mints {
+                limit.consume(self.runtime.curr_epoch(), &mint.amount)?;
+            }
+        }
+        Ok(mint_limit)
+    }
 
+    /// Burns the caller's tokens and sends them the same amount of FIL
+    ///
+    /// Only FIL-backed tokens can be redeemed.
+    pub fn redeem(&mut self, params: RedeemParams) -> Result<BurnReturn, RuntimeError> {
+        if !self.state.fil_backed {
+            return Err(RuntimeError::NotFilBacked);
+        }
+        self.assert_not_paused()?;
+        let caller = self.caller_address();
+        let ret = self.token().burn(&caller, &params.amount)?;
+        self.emit_burn_event(self.runtime.caller(), &caller, &params.amount)?;
+        self.refund(&caller, &params.amount)?;
         Ok(ret)
     }
 
+    /// Handles tokens sent to this actor, through its receiver hook
+    ///
+    /// Tokens transferred to the token actor itself are handled according to its
+    /// [`SelfTransferPolicy`]. When burned, the FIL backing FIL-backed tokens is refunded to the
+    /// sender, as an alternative to [`redeem`](Self::redeem). Anything else is rejected.
+    ///
+    /// The state must be flushed afterwards, as tokens may have been burned.
+    pub fn token_received(&mut self, params: UniversalReceiverParams) -> Result<(), RuntimeError> {
+        let actor_id = self.runtime.actor_id();
+        // only this token calls the hook with itself as the caller
+        if params.type_ != FRC46_TOKEN_TYPE || self.runtime.caller() != actor_id {
+            return Err(RuntimeError::UnexpectedAssets);
+        }
+        let received: FRC46TokenReceived = params.payload.deserialize()?;
+        if received.to != actor_id {
+            return Err(RuntimeError::UnexpectedAssets);
+        }
+
+        match self.state.self_transfer {
+            SelfTransferPolicy::Reject => Err(RuntimeError::UnexpectedAssets),
+            // the tokens are already credited to this actor
+            SelfTransferPolicy::Vault => Ok(()),
+            SelfTransferPolicy::Burn => {
+                self.token().burn(&Address::new_id(actor_id), &received.amount)?;
+                self.emit_burn_event(actor_id, &Address::new_id(actor_id), &received.amount)?;
+                if self.state.fil_backed {
+                    self.refund(&Address::new_id(received.from), &received.amount)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Commits the state then sends FIL backing redeemed tokens
+    fn refund(&mut self, to: &Address, amount: &TokenAmount) -> Result<(), RuntimeError> {
+        let cid = self.save()?;
+        let ((), new_root) = self.runtime.call_after_commit(&cid, || {
+            self.runtime.transfer_fil(to, amount).map_err(RuntimeError::from)
+        })?;
+        self.reload(new_root)
+    }
+
     /// Permanently disable minting
-    /// Only the authorised mint operator can do this
+    /// Only holders of the minter role can do this, and the role is cleared as it has no further use
     pub fn disable_mint(&mut self) -> Result<(), RuntimeError> {
-        // no minter means minting has already been permanently disabled
-        // we return this if already disabled because it will make more sense than failing the address check below
-        let minter = self.state.minter.ok_or(RuntimeError::MintingDisabled)?;
+        // we return this if already disabled because it will make more sense than failing the role check below
+        if self.state.minting_disabled {
+            return Err(RuntimeError::MintingDisabled);
+        }
+        self.state.assert_role(MINTER_ROLE, self.runtime.caller())?;
+
+        self.state.minting_disabled = true;
+        for minter in self.state.role_members(MINTER_ROLE).to_vec() {
+            self.state.revoke_role(MINTER_ROLE, minter);
+            self.emit_role_event(ROLE_REVOKED_EVENT, MINTER_ROLE, minter)?;
+        }
+        self.state.pending_minter = None;
+        Ok(())
+    }
+
+    pub fn description(&self) -> String {
+        self.state.description.clone()
+    }
+
+    pub fn icon(&self) -> Option<Cid> {
+        self.state.icon
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.state.decimals
+    }
+
+    /// Returns all the descriptive metadata of the token at once
+    pub fn metadata(&self) -> MetadataReturn {
+        MetadataReturn {
+            name: self.state.name.clone(),
+            symbol: self.state.symbol.clone(),
+            granularity: self.state.granularity,
+            description: self.state.description.clone(),
+            icon: self.state.icon,
+            decimals: self.state.decimals,
+        }
+    }
+
+    /// Replaces the description and icon of the token
+    ///
+    /// The caller must hold the `ADMIN_ROLE`. The name, symbol, granularity and decimals can't be
+    /// changed, as holders and wallets rely on them staying the same.
+    pub fn update_metadata(&mut self, params: UpdateMetadataParams) -> Result<(), RuntimeError> {
+        self.state.assert_role(ADMIN_ROLE, self.runtime.caller())?;
+        self.state.description = params.description;
+        self.state.icon = params.icon;
+        Ok(())
+    }
+
+    /// Sets or removes the budget limiting how much can be minted per window of epochs
+    ///
+    /// The caller must hold the `ADMIN_ROLE`. Setting a limit starts a new window at the current
+    /// epoch with the full budget available.
+    pub fn set_mint_limit(&mut self, params: SetMintLimitParams) -> Result<(), RuntimeError> {
+        self.state.assert_role(ADMIN_ROLE, self.runtime.caller())?;
+        let epoch = self.runtime.curr_epoch();
+        self.state.mint_limit =
+            params.limit.map(|limit| MintLimit::new(limit.max_amount, limit.window, epoch));
+        Ok(())
+    }
+
+    /// Nominates a new account to take over the caller's minter role
+    ///
+    /// The role only moves once the nominee calls [`accept_minter`](Self::accept_minter), so a
+    /// mistyped address can't leave the token without a minter. A later nomination replaces any
+    /// pending one, which can be used to correct a mistake.
+    pub fn transfer_minter(&mut self, params: TransferMinterParams) -> Result<(), RuntimeError> {
+        if self.state.minting_disabled {
+            return Err(RuntimeError::MintingDisabled);
+        }
+        let caller_id = self.runtime.caller();
+        self.state.assert_role(MINTER_ROLE, caller_id)?;
+        let new_minter = self.runtime.resolve_or_init(&params.new_minter)?;
+
+        self.state.pending_minter = Some(PendingMinter { from: caller_id, to: new_minter });
+        Ok(())
+    }
+
+    /// Completes a minter role transfer nominating the caller
+    ///
+    /// Fails if the caller isn't the pending minter, or if the nominating account no longer holds
+    /// the minter role.
+    pub fn accept_minter(&mut self) -> Result<(), RuntimeError> {
+        if self.state.minting_disabled {
+            return Err(RuntimeError::MintingDisabled);
+        }
         let caller_id = self.runtime.caller();
-        if caller_id != minter {
-            return Err(RuntimeError::AddressNotAuthorized);
+        let pending = match self.state.pending_minter {
+            Some(pending) if pending.to == caller_id => pending,
+            _ => return Err(RuntimeError::AddressNotAuthorized),
+        };
+        self.state.assert_role(MINTER_ROLE, pending.from)?;
+
+        if self.state.revoke_role(MINTER_ROLE, pending.from) {
+            self.emit_role_event(ROLE_REVOKED_EVENT, MINTER_ROLE, pending.from)?;
         }
+        if self.state.grant_role(MINTER_ROLE, pending.to) {
+            self.emit_role_event(ROLE_GRANTED_EVENT, MINTER_ROLE, pending.to)?;
+        }
+        self.state.pending_minter = None;
+        Ok(())
+    }
 
-        self.state.minter = None;
+    fn assert_not_paused(&self) -> Result<(), RuntimeError> {
+        if self.state.paused {
+            return Err(RuntimeError::Paused);
+        }
         Ok(())
     }
 }
@@ -397,10 +907,11 @@ where
 /// Given a method number and parameter block id, invokes the appropriate method on the FRC46Token interface
 ///
 /// The flush_state function passed into this must flush current state to the blockstore and update the root cid
-/// This is called after operations which mutate the state, such as changing an allowance or burning tokens.
+/// It is called once, after the method returns, for operations which mutate the state without calling a receiver
+/// hook, such as changing an allowance or burning tokens.
 ///
 /// Transfer and TransferFrom operations invoke the receiver hook which will require flushing state before calling the hook
-/// This must be done inside the FRC46Token::transfer/transfer_from functions
+/// This must be done inside the FRC46Token::transfer/transfer_from functions, so flush_state is not called again for them
 ///
 /// Possible returns:
 /// - Ok(None) - method not found
@@ -417,66 +928,74 @@ where
     T: FRC46Token<TokenError = E>,
     F: FnOnce(&mut T) -> Result<(), E>,
 {
-    match_method!(method_num, {
+    // each arm returns its result along with whether the state still needs to be flushed
+    let (ret, needs_flush) = match_method!(method_num, {
         "Name" => {
-            Ok(frc46_return_block(&token.name()))
+            (frc46_return_block(&token.name()), false)
         }
         "Symbol" => {
-            Ok(frc46_return_block(&token.symbol()))
+            (frc46_return_block(&token.symbol()), false)
         }
         "TotalSupply" => {
-            Ok(frc46_return_block(&token.total_supply()))
+            (frc46_return_block(&token.total_supply()), false)
         }
         "BalanceOf" => {
             let params = frc46_unpack_params(params);
             let res = token.balance_of(params)?;
-            Ok(frc46_return_block(&res))
+            (frc46_return_block(&res), false)
         }
         "Allowance" => {
             let params = frc46_unpack_params(params);
             let res = token.allowance(params)?;
-            Ok(frc46_return_block(&res))
+            (frc46_return_block(&res), false)
         }
         "IncreaseAllowance" => {
             let params = frc46_unpack_params(params);
             let res = token.increase_allowance(params)?;
-            flush_state(token)?;
-            Ok(frc46_return_block(&res))
+            (frc46_return_block(&res), true)
         }
         "DecreaseAllowance" => {
             let params = frc46_unpack_params(params);
             let res = token.decrease_allowance(params)?;
-            flush_state(token)?;
-            Ok(frc46_return_block(&res))
+            (frc46_return_block(&res), true)
         }
         "RevokeAllowance" => {
             let params = frc46_unpack_params(params);
             token.revoke_allowance(params)?;
-            flush_state(token)?;
-            Ok(Some(NO_DATA_BLOCK_ID))
+            (Some(NO_DATA_BLOCK_ID), true)
         }
         "Burn" => {
             let params = frc46_unpack_params(params);
             let res = token.burn(params)?;
-            flush_state(token)?;
-            Ok(frc46_return_block(&res))
-
+            (frc46_return_block(&res), true)
+        }
+        "BurnFrom" => {
+            let params = frc46_unpack_params(params);
+            let res = token.burn_from(params)?;
+            (frc46_return_block(&res), true)
         }
         "TransferFrom" => {
+            // state is flushed before the receiver hook is called
             let params = frc46_unpack_params(params);
             let res = token.transfer_from(params)?;
-            Ok(frc46_return_block(&res))
+            (frc46_return_block(&res), false)
         }
         "Transfer" => {
+            // state is flushed before the receiver hook is called
             let params = frc46_unpack_params(params);
             let res = token.transfer(params)?;
-            Ok(frc46_return_block(&res))
+            (frc46_return_block(&res), false)
         }
         _ => {
             // no method found - it's not considered an error here, but an upstream caller may choose to treat it as one
-            Ok(None)
+            (None, false)
         }
-    })
+    });
+
+    if needs_flush {
+        flush_state(token)?;
+    }
+    Ok(ret)
 }
 
 // deserialise params for passing to token methods
@@ -535,6 +1054,10 @@ where
 
 #[cfg(test)]
 mod test {
+    use cid::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
     use frc46_token::token::{
         types::{
             BurnFromParams, BurnParams, DecreaseAllowanceParams, FRC46Token, GetAllowanceParams,
@@ -546,10 +1069,15 @@ mod test {
         shared_blockstore::SharedMemoryBlockstore, syscalls::fake_syscalls::FakeSyscalls,
         util::ActorRuntime,
     };
-    use fvm_ipld_encoding::RawBytes;
-    use fvm_shared::{address::Address, bigint::Zero, econ::TokenAmount};
-
-    use crate::{FactoryToken, MintParams, RuntimeError};
+    use fvm_ipld_encoding::{CborStore, RawBytes, DAG_CBOR};
+    use fvm_shared::{address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode};
+
+    use crate::{
+        construct_token, migration, roles::MINTER_ROLE, self_transfer::SelfTransferPolicy,
+        ConstructorParams, ConstructorParamsError, FactoryToken, MintBatchParams, MintLimitParams,
+        MintParams, RedeemParams, RuntimeError, SetMintLimitParams, TransferMinterParams,
+        UpdateMetadataParams, DEFAULT_DECIMALS,
+    };
 
     const ALICE: Address = Address::new_id(1);
     const BOB: Address = Address::new_id(2);
@@ -571,6 +1099,7 @@ mod test {
             1,
             Some(actor_id),
         )
+        .unwrap()
     }
 
     #[test]
@@ -668,6 +1197,176 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_wraps_fil() {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let token_id = runtime.actor_id();
+        let mut token =
+            FactoryToken::new(runtime, "Wrapped FIL".into(), "WFIL".into(), 1, None).unwrap();
+        token.state.fil_backed = true;
+        token.state.self_transfer = SelfTransferPolicy::Burn;
+        let syscalls = &token.runtime.syscalls;
+        syscalls.set_balance(token_id, TokenAmount::default());
+        syscalls.set_balance(100, TokenAmount::default());
+
+        // anyone can mint by sending the same amount of FIL
+        syscalls.set_message(100, TokenAmount::from_whole(3));
+        let mint = |amount| MintParams {
+            initial_owner: Address::new_id(100),
+            amount: TokenAmount::from_whole(amount),
+            operator_data: RawBytes::default(),
+        };
+        match token.mint(mint(2)).unwrap_err() {
+            RuntimeError::IncorrectValue { received, .. } => {
+                assert_eq!(received, TokenAmount::from_whole(3))
+            }
+            e => panic!("unexpected error {e}"),
+        }
+        token.mint(mint(3)).unwrap();
+        token.runtime.syscalls.set_balance(token_id, TokenAmount::from_whole(3));
+
+        // tokens can be redeemed for FIL
+        token.runtime.syscalls.set_value_received(TokenAmount::default());
+        let ret = token.redeem(RedeemParams { amount: TokenAmount::from_whole(1) }).unwrap();
+        assert_eq!(ret.balance, TokenAmount::from_whole(2));
+        assert_eq!(token.runtime.balance_of(100), Some(TokenAmount::from_whole(1)));
+
+        // or transferred to the token itself, whose hook burns them and refunds the FIL
+        let bs = token.runtime.blockstore.clone();
+        token.runtime.syscalls.set_on_send(move |syscalls, to, _| {
+            if *to != Address::new_id(token_id) {
+                return ExitCode::OK;
+            }
+            let message = syscalls.last_message.borrow().clone().unwrap();
+            let caller = syscalls.caller_id.replace(token_id);
+            let root = *syscalls.root.borrow();
+            let mut nested =
+                FactoryToken::load(ActorRuntime::new(syscalls, bs.clone()), &root).unwrap();
+            let res = nested.token_received(message.params.unwrap().deserialize().unwrap());
+            syscalls.caller_id.replace(caller);
+            res.as_ref().map_or_else(ExitCode::from, |_| ExitCode::OK)
+        });
+        let ret = token
+            .transfer(TransferParams {
+                to: Address::new_id(token_id),
+                amount: TokenAmount::from_whole(2),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+        assert_eq!(ret.from_balance, TokenAmount::zero());
+        assert_eq!(ret.to_balance, TokenAmount::zero());
+        assert_eq!(token.total_supply(), TokenAmount::zero());
+        assert_eq!(token.runtime.balance_of(100), Some(TokenAmount::from_whole(3)));
+        assert_eq!(token.runtime.balance_of(token_id), Some(TokenAmount::zero()));
+    }
+
+    #[test]
+    fn it_mints_in_batches() {
+        let mut token = setup_token(&ALICE);
+        let mint = |owner, amount| MintParams {
+            initial_owner: owner,
+            amount: TokenAmount::from_whole(amount),
+            operator_data: RawBytes::default(),
+        };
+
+        // a recipient changing the state from its hook is seen by later hooks
+        let bs = token.runtime.blockstore.clone();
+        token.runtime.syscalls.set_on_send(move |syscalls, _, _| {
+            let root = *syscalls.root.borrow();
+            let mut state = migration::load_state(&bs, &root).unwrap();
+            state.description.push('!');
+            syscalls.root.replace(bs.put_cbor(&state, Code::Blake2b256).unwrap());
+            ExitCode::OK
+        });
+        let ret = token.mint_batch(MintBatchParams { mints: vec![mint(ALICE, 1), mint(BOB, 2)] });
+        let ret = ret.unwrap();
+        assert_eq!(ret.results.len(), 2);
+        assert_eq!(ret.results[0].balance, TokenAmount::from_whole(1));
+        assert_eq!(ret.results[1].balance, TokenAmount::from_whole(2));
+        assert_eq!(ret.results[1].supply, TokenAmount::from_whole(3));
+        assert_eq!(token.description(), "!!");
+
+        // nobody is credited if any mint fails
+        let bad = MintParams { amount: TokenAmount::from_atto(-1), ..mint(BOB, 0) };
+        token.mint_batch(MintBatchParams { mints: vec![mint(ALICE, 1), bad] }).unwrap_err();
+        assert_eq!(token.balance_of(ALICE).unwrap(), TokenAmount::from_whole(1));
+        assert_eq!(token.total_supply(), TokenAmount::from_whole(3));
+    }
+
+    #[test]
+    fn it_limits_minting_per_window() {
+        let mut token = setup_token(&ALICE);
+        token.runtime.syscalls.set_curr_epoch(5);
+        token
+            .set_mint_limit(SetMintLimitParams {
+                limit: Some(MintLimitParams {
+                    max_amount: TokenAmount::from_whole(10),
+                    window: 10,
+                }),
+            })
+            .unwrap();
+        let mint = |amount| MintParams {
+            initial_owner: BOB,
+            amount: TokenAmount::from_whole(amount),
+            operator_data: RawBytes::default(),
+        };
+
+        token.mint(mint(6)).unwrap();
+        token.runtime.syscalls.set_curr_epoch(14);
+        match token.mint(mint(5)).unwrap_err() {
+            RuntimeError::MintLimitExceeded { remaining, .. } => {
+                assert_eq!(remaining, TokenAmount::from_whole(4))
+            }
+            _ => panic!("unexpected error"),
+        }
+        token.mint(mint(4)).unwrap();
+
+        // the budget is replenished in the next window
+        token.runtime.syscalls.set_curr_epoch(27);
+        token.mint(mint(10)).unwrap();
+        assert_eq!(token.state.mint_limit.as_ref().unwrap().window_start, 25);
+        assert_eq!(token.total_supply(), TokenAmount::from_whole(20));
+
+        // removing the limit allows unbounded minting
+        token.set_mint_limit(SetMintLimitParams { limit: None }).unwrap();
+        token.mint(mint(100)).unwrap();
+    }
+
+    #[test]
+    fn it_hands_over_the_minter_role() {
+        let mut token = setup_token(&ALICE);
+        let alice = token.runtime.resolve_id(&ALICE).unwrap();
+        let bob = token.runtime.resolve_id(&BOB).unwrap();
+
+        token.transfer_minter(TransferMinterParams { new_minter: BOB }).unwrap();
+        // the role doesn't move until accepted, and only the nominee can accept
+        assert_eq!(token.state.role_members(MINTER_ROLE), &[alice]);
+        match token.accept_minter().unwrap_err() {
+            RuntimeError::AddressNotAuthorized => {}
+            _ => panic!("unexpected error"),
+        }
+
+        token.runtime.syscalls.set_caller_id(bob);
+        token.accept_minter().unwrap();
+        assert_eq!(token.state.role_members(MINTER_ROLE), &[bob]);
+        assert_eq!(token.state.pending_minter, None);
+        token
+            .mint(MintParams {
+                initial_owner: BOB,
+                amount: TokenAmount::from_whole(1),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+
+        // the previous minter can no longer nominate anyone
+        token.runtime.syscalls.set_caller_id(alice);
+        match token.transfer_minter(TransferMinterParams { new_minter: ALICE }).unwrap_err() {
+            RuntimeError::AddressNotAuthorized => {}
+            _ => panic!("unexpected error"),
+        }
+    }
+
     #[test]
     fn it_has_name_and_symbol() {
         let token = setup_token(&ALICE);
@@ -675,6 +1374,94 @@ mod test {
         assert_eq!(token.symbol(), "TEST");
     }
 
+    #[test]
+    fn it_validates_constructor_params() {
+        let params =
+            |name: &str, symbol: &str, granularity: u64, minter: Address| ConstructorParams {
+                name: name.into(),
+                symbol: symbol.into(),
+                granularity,
+                description: String::new(),
+                icon: None,
+                decimals: DEFAULT_DECIMALS,
+                fil_backed: false,
+                self_transfer: SelfTransferPolicy::Reject,
+                minter,
+            };
+        let construct = |params: ConstructorParams| {
+            let runtime =
+                ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+            match construct_token(runtime, params) {
+                Ok(_) => Ok(()),
+                Err(RuntimeError::InvalidConstructorParams(e)) => Err(e),
+                Err(e) => panic!("unexpected error {e}"),
+            }
+        };
+
+        construct(params("Test Token", "TEST.e", 1, ALICE)).unwrap();
+        assert_eq!(
+            construct(params(" ", "TEST", 1, ALICE)),
+            Err(ConstructorParamsError::EmptyName)
+        );
+        assert_eq!(
+            construct(params("Test Token", "", 1, ALICE)),
+            Err(ConstructorParamsError::EmptySymbol)
+        );
+        assert_eq!(
+            construct(params("Test Token", &"T".repeat(33), 1, ALICE)),
+            Err(ConstructorParamsError::SymbolTooLong)
+        );
+        assert_eq!(
+            construct(params("Test Token", "TE ST", 1, ALICE)),
+            Err(ConstructorParamsError::InvalidSymbolChar(' '))
+        );
+        assert_eq!(
+            construct(params("Test Token", "TEST", 0, ALICE)),
+            Err(ConstructorParamsError::ZeroGranularity)
+        );
+        let unknown = Address::new_secp256k1(&[1; 65]).unwrap();
+        assert_eq!(
+            construct(params("Test Token", "TEST", 1, unknown)),
+            Err(ConstructorParamsError::UnresolvableMinter(unknown))
+        );
+
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let err =
+            FactoryToken::new(runtime, "Test Token".into(), "TEST".into(), 0, None).err().unwrap();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_ILLEGAL_ARGUMENT);
+    }
+
+    #[test]
+    fn it_updates_metadata() {
+        let mut token = setup_token(&ALICE);
+        assert_eq!(token.decimals(), DEFAULT_DECIMALS);
+        assert_eq!(token.icon(), None);
+
+        let icon = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(b"icon"));
+        token
+            .update_metadata(UpdateMetadataParams {
+                description: String::from("A token for testing"),
+                icon: Some(icon),
+            })
+            .unwrap();
+        let metadata = token.metadata();
+        assert_eq!(metadata.description, "A token for testing");
+        assert_eq!(metadata.icon, Some(icon));
+        assert_eq!(metadata.symbol, "TEST");
+
+        // only admins can update metadata
+        token.runtime.syscalls.set_caller_id(token.runtime.resolve_id(&BOB).unwrap());
+        let err = token
+            .update_metadata(UpdateMetadataParams { description: String::new(), icon: None })
+            .unwrap_err();
+        match err {
+            RuntimeError::AddressNotAuthorized => {}
+            _ => panic!("unexpected error"),
+        }
+        assert_eq!(token.description(), "A token for testing");
+    }
+
     #[test]
     fn it_enforces_granularity() {
         // set up a token with granularity of 10
@@ -688,7 +1475,8 @@ mod test {
             String::from("TEST"),
             10,
             Some(actor_id),
-        );
+        )
+        .unwrap();
 
         {
             let ret = token
@@ -0,0 +1,400 @@
+//! Versioning of the factory token state
+//!
+//! The state is encoded as a tuple whose first element is the layout version. Layouts from before
+//! versioning was introduced start with the token state instead, and are treated as version 0.
+//! Loading reads the version first and upgrades older layouts to the current one in memory, so the
+//! state is migrated the next time it is saved.
+//!
+//! To change the layout: copy the current `FactoryTokenState` here as the previous version, bump
+//! [`STATE_VERSION`] and add a conversion from the previous version to `load_state`.
+use std::fmt;
+
+use cid::Cid;
+use frc46_token::token::state::TokenState;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{
+    tuple::{Deserialize_tuple, Serialize_tuple},
+    CborStore,
+};
+use fvm_shared::ActorID;
+use serde::de::{self, DeserializeOwned, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::permit::PermitNonce;
+use crate::roles::{RoleMembers, ADMIN_ROLE, MINTER_ROLE};
+use crate::self_transfer::SelfTransferPolicy;
+use crate::{FactoryTokenState, MintLimit, PendingMinter, RuntimeError, DEFAULT_DECIMALS};
+
+/// Version of the state layout written by this code
+pub const STATE_VERSION: u64 = 5;
+
+/// The state before the self-transfer policy was introduced
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct FactoryTokenStateV4 {
+    pub version: u64,
+    pub token: TokenState,
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    pub description: String,
+    pub icon: Option<Cid>,
+    pub decimals: u8,
+    pub roles: Vec<RoleMembers>,
+    pub paused: bool,
+    pub minting_disabled: bool,
+    pub pending_minter: Option<PendingMinter>,
+    pub mint_limit: Option<MintLimit>,
+    pub fil_backed: bool,
+    pub blocklist: Vec<ActorID>,
+    pub permit_nonces: Vec<PermitNonce>,
+}
+
+impl From<FactoryTokenStateV4> for FactoryTokenState {
+    fn from(old: FactoryTokenStateV4) -> Self {
+        FactoryTokenState {
+            version: STATE_VERSION,
+            token: old.token,
+            name: old.name,
+            symbol: old.symbol,
+            granularity: old.granularity,
+            description: old.description,
+            icon: old.icon,
+            decimals: old.decimals,
+            roles: old.roles,
+            paused: old.paused,
+            minting_disabled: old.minting_disabled,
+            pending_minter: old.pending_minter,
+            mint_limit: old.mint_limit,
+            fil_backed: old.fil_backed,
+            blocklist: old.blocklist,
+            permit_nonces: old.permit_nonces,
+            // FIL-backed tokens were always redeemed when sent to the token, others were rejected
+            self_transfer: if old.fil_backed {
+                SelfTransferPolicy::Burn
+            } else {
+                SelfTransferPolicy::Reject
+            },
+        }
+    }
+}
+
+/// The state before permits were introduced
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct FactoryTokenStateV3 {
+    pub version: u64,
+    pub token: TokenState,
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    pub description: String,
+    pub icon: Option<Cid>,
+    pub decimals: u8,
+    pub roles: Vec<RoleMembers>,
+    pub paused: bool,
+    pub minting_disabled: bool,
+    pub pending_minter: Option<PendingMinter>,
+    pub mint_limit: Option<MintLimit>,
+    pub fil_backed: bool,
+    pub blocklist: Vec<ActorID>,
+}
+
+impl From<FactoryTokenStateV3> for FactoryTokenStateV4 {
+    fn from(old: FactoryTokenStateV3) -> Self {
+        FactoryTokenStateV4 {
+            version: 4,
+            token: old.token,
+            name: old.name,
+            symbol: old.symbol,
+            granularity: old.granularity,
+            description: old.description,
+            icon: old.icon,
+            decimals: old.decimals,
+            roles: old.roles,
+            paused: old.paused,
+            minting_disabled: old.minting_disabled,
+            pending_minter: old.pending_minter,
+            mint_limit: old.mint_limit,
+            fil_backed: old.fil_backed,
+            blocklist: old.blocklist,
+            permit_nonces: Vec::new(),
+        }
+    }
+}
+
+/// The state before the blocklist was introduced
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct FactoryTokenStateV2 {
+    pub version: u64,
+    pub token: TokenState,
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    pub description: String,
+    pub icon: Option<Cid>,
+    pub decimals: u8,
+    pub roles: Vec<RoleMembers>,
+    pub paused: bool,
+    pub minting_disabled: bool,
+    pub pending_minter: Option<PendingMinter>,
+    pub mint_limit: Option<MintLimit>,
+    pub fil_backed: bool,
+}
+
+impl From<FactoryTokenStateV2> for FactoryTokenStateV3 {
+    fn from(old: FactoryTokenStateV2) -> Self {
+        FactoryTokenStateV3 {
+            version: 3,
+            token: old.token,
+            name: old.name,
+            symbol: old.symbol,
+            granularity: old.granularity,
+            description: old.description,
+            icon: old.icon,
+            decimals: old.decimals,
+            roles: old.roles,
+            paused: old.paused,
+            minting_disabled: old.minting_disabled,
+            pending_minter: old.pending_minter,
+            mint_limit: old.mint_limit,
+            fil_backed: old.fil_backed,
+            blocklist: Vec::new(),
+        }
+    }
+}
+
+/// The state before FIL-backed tokens were introduced
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct FactoryTokenStateV1 {
+    pub version: u64,
+    pub token: TokenState,
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    pub description: String,
+    pub icon: Option<Cid>,
+    pub decimals: u8,
+    pub roles: Vec<RoleMembers>,
+    pub paused: bool,
+    pub minting_disabled: bool,
+    pub pending_minter: Option<PendingMinter>,
+    pub mint_limit: Option<MintLimit>,
+}
+
+impl From<FactoryTokenStateV1> for FactoryTokenStateV2 {
+    fn from(old: FactoryTokenStateV1) -> Self {
+        FactoryTokenStateV2 {
+            version: 2,
+            token: old.token,
+            name: old.name,
+            symbol: old.symbol,
+            granularity: old.granularity,
+            description: old.description,
+            icon: old.icon,
+            decimals: old.decimals,
+            roles: old.roles,
+            paused: old.paused,
+            minting_disabled: old.minting_disabled,
+            pending_minter: old.pending_minter,
+            mint_limit: old.mint_limit,
+            fil_backed: false,
+        }
+    }
+}
+
+/// The state before versioning and role-based access control were introduced
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct FactoryTokenStateV0 {
+    pub token: TokenState,
+    pub name: String,
+    pub symbol: String,
+    pub granularity: u64,
+    pub minter: Option<ActorID>,
+}
+
+impl From<FactoryTokenStateV0> for FactoryTokenStateV1 {
+    fn from(old: FactoryTokenStateV0) -> Self {
+        let mut state = FactoryTokenStateV1 {
+            version: 1,
+            token: old.token,
+            name: old.name,
+            symbol: old.symbol,
+            granularity: old.granularity,
+            description: String::new(),
+            icon: None,
+            decimals: DEFAULT_DECIMALS,
+            roles: Vec::new(),
+            paused: false,
+            // the minter was cleared to disable minting
+            minting_disabled: old.minter.is_none(),
+            pending_minter: None,
+            mint_limit: None,
+        };
+        if let Some(minter) = old.minter {
+            // the minter was the only privileged account, so it becomes the admin too
+            for role in [ADMIN_ROLE, MINTER_ROLE] {
+                state.roles.push(RoleMembers { role: role.into(), members: vec![minter] });
+            }
+        }
+        state
+    }
+}
+
+/// Loads the state at `cid`, upgrading it from older layouts
+pub fn load_state<BS: Blockstore>(bs: &BS, cid: &Cid) -> Result<FactoryTokenState, RuntimeError> {
+    let StoredVersion(version) = get(bs, cid)?;
+    match version {
+        0 => {
+            let v1 = FactoryTokenStateV1::from(get::<_, FactoryTokenStateV0>(bs, cid)?);
+            let v3 = FactoryTokenStateV3::from(FactoryTokenStateV2::from(v1));
+            Ok(FactoryTokenStateV4::from(v3).into())
+        }
+        1 => {
+            let v2 = FactoryTokenStateV2::from(get::<_, FactoryTokenStateV1>(bs, cid)?);
+            Ok(FactoryTokenStateV4::from(FactoryTokenStateV3::from(v2)).into())
+        }
+        2 => {
+            let v3 = FactoryTokenStateV3::from(get::<_, FactoryTokenStateV2>(bs, cid)?);
+            Ok(FactoryTokenStateV4::from(v3).into())
+        }
+        3 => Ok(FactoryTokenStateV4::from(get::<_, FactoryTokenStateV3>(bs, cid)?).into()),
+        4 => Ok(get::<_, FactoryTokenStateV4>(bs, cid)?.into()),
+        STATE_VERSION => get(bs, cid),
+        v => Err(RuntimeError::Deserialization(format!("unsupported state version {v}"))),
+    }
+}
+
+fn get<BS: Blockstore, T: DeserializeOwned>(bs: &BS, cid: &Cid) -> Result<T, RuntimeError> {
+    match bs.get_cbor::<T>(cid) {
+        Ok(Some(s)) => Ok(s),
+        Ok(None) => Err(RuntimeError::Deserialization("no data found".into())),
+        Err(e) => Err(RuntimeError::Deserialization(e.to_string())),
+    }
+}
+
+/// The version of a stored state, read from the first element of its tuple encoding
+struct StoredVersion(u64);
+
+impl<'de> Deserialize<'de> for StoredVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(StoredVersionVisitor)
+    }
+}
+
+struct StoredVersionVisitor;
+
+impl<'de> Visitor<'de> for StoredVersionVisitor {
+    type Value = StoredVersion;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a state tuple")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let first = seq
+            .next_element::<FirstElement>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(StoredVersion(match first {
+            FirstElement::Version(v) => v,
+            FirstElement::Unversioned => 0,
+        }))
+    }
+}
+
+/// The first element of a state tuple: a version number, or anything else in unversioned layouts
+enum FirstElement {
+    Version(u64),
+    Unversioned,
+}
+
+impl<'de> Deserialize<'de> for FirstElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FirstElementVisitor;
+
+        impl<'de> Visitor<'de> for FirstElementVisitor {
+            type Value = FirstElement;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a version number or the token state")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(FirstElement::Version(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(FirstElement::Unversioned)
+            }
+        }
+
+        deserializer.deserialize_any(FirstElementVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Code;
+    use frc46_token::token::state::TokenState;
+    use fvm_actor_utils::{
+        shared_blockstore::SharedMemoryBlockstore, syscalls::fake_syscalls::FakeSyscalls,
+        util::ActorRuntime,
+    };
+    use fvm_ipld_encoding::{tuple::*, CborStore};
+
+    use super::{load_state, FactoryTokenStateV0, STATE_VERSION};
+    use crate::roles::{ADMIN_ROLE, MINTER_ROLE};
+    use crate::{FactoryToken, RuntimeError};
+
+    #[test]
+    fn it_migrates_unversioned_state() {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let old = FactoryTokenStateV0 {
+            token: TokenState::new(&runtime).unwrap(),
+            name: String::from("Test Token"),
+            symbol: String::from("TEST"),
+            granularity: 1,
+            minter: Some(5),
+        };
+        let cid = runtime.put_cbor(&old, Code::Blake2b256).unwrap();
+
+        let token = FactoryToken::load(runtime, &cid).unwrap();
+        assert_eq!(token.state.version, STATE_VERSION);
+        assert_eq!(token.state.symbol, "TEST");
+        assert_eq!(token.state.role_members(ADMIN_ROLE), &[5]);
+        assert_eq!(token.state.role_members(MINTER_ROLE), &[5]);
+        assert!(!token.state.minting_disabled);
+
+        // once saved, the state loads as the current version
+        let cid = token.save().unwrap();
+        let state = load_state(&token.runtime, &cid).unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.roles, token.state.roles);
+
+        // a cleared minter meant minting was disabled
+        let cid =
+            token.runtime.put_cbor(&FactoryTokenStateV0 { minter: None, ..old }, Code::Blake2b256);
+        let state = load_state(&token.runtime, &cid.unwrap()).unwrap();
+        assert!(state.minting_disabled);
+        assert!(state.roles.is_empty());
+    }
+
+    #[test]
+    fn it_rejects_unknown_versions() {
+        #[derive(Serialize_tuple)]
+        struct FutureState {
+            version: u64,
+            data: String,
+        }
+
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let future = FutureState { version: STATE_VERSION + 1, data: String::new() };
+        let cid = runtime.put_cbor(&future, Code::Blake2b256).unwrap();
+        match load_state(&runtime, &cid).unwrap_err() {
+            RuntimeError::Deserialization(msg) => assert!(msg.contains("unsupported")),
+            e => panic!("unexpected error {e}"),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Signed allowance approvals for the factory token
+//!
+//! A `Permit` sets an operator's allowance on behalf of an owner who signed the approval off-chain,
+//! so the owner needn't send a message themselves. Anyone can submit a permit. The owner must be
+//! given by the address of the key that signed it: a secp256k1 (f1), BLS (f3) or Ethereum-style
+//! (f410) address.
+//!
+//! The owner signs the EIP-712 digest of a [`PermitMessage`] in the domain of this token, so a
+//! permit can't be replayed against another token or on another network. The message includes the
+//! owner's current nonce so it can't be replayed against this token either, and the nonce is
+//! incremented each time a permit is used.
+use frc42_dispatch::match_method;
+use frc46_token::token::types::AllowanceReturn;
+use fvm_actor_utils::{
+    abi::{encode_uint256, encode_uint64, AbiError},
+    crypto::{eip712_digest, keccak256, struct_hash, Digest, Eip712Domain},
+    messaging::MessagingError,
+    syscalls::Syscalls,
+};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{
+    tuple::{Deserialize_tuple, Serialize_tuple},
+    RawBytes,
+};
+use fvm_shared::{address::Address, bigint::BigInt, clock::ChainEpoch, econ::TokenAmount, ActorID};
+
+use crate::{
+    frc46_return_block, frc46_unpack_params, FactoryToken, FactoryTokenState, RuntimeError,
+};
+
+/// Name of the EIP-712 domain in which permits are signed
+pub const PERMIT_DOMAIN: &str = "frc46_factory_token/permit";
+
+/// Version of the EIP-712 domain in which permits are signed
+pub const PERMIT_VERSION: &str = "1";
+
+/// The EIP-712 type of a [`PermitMessage`]
+pub const PERMIT_TYPE: &str =
+    "Permit(bytes owner,bytes operator,uint256 amount,uint256 nonce,uint256 deadline)";
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct PermitParams {
+    /// Signer of the permit, which must be an f1, f3 or f410 address
+    pub owner: Address,
+    pub operator: Address,
+    /// The allowance to set
+    pub amount: TokenAmount,
+    /// Last epoch at which the permit may be used
+    pub deadline: ChainEpoch,
+    /// Signature by the owner over the digest of the permit, see [`FactoryToken::permit_digest`]
+    pub signature: RawBytes,
+}
+
+/// The typed message signed by the owner of a permit
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct PermitMessage {
+    pub owner: Address,
+    pub operator: Address,
+    pub amount: TokenAmount,
+    pub nonce: u64,
+    pub deadline: ChainEpoch,
+}
+
+impl PermitMessage {
+    /// Returns the EIP-712 hash of the message, of type [`PERMIT_TYPE`]
+    ///
+    /// Addresses are encoded as `bytes` holding their Filecoin encoding, since f1 and f3 owners
+    /// have no Ethereum address.
+    pub fn struct_hash(&self) -> Result<Digest, AbiError> {
+        Ok(struct_hash(
+            PERMIT_TYPE,
+            &[
+                keccak256(&self.owner.to_bytes()),
+                keccak256(&self.operator.to_bytes()),
+                encode_uint256(self.amount.atto())?,
+                encode_uint64(self.nonce),
+                encode_uint256(&BigInt::from(self.deadline))?,
+            ],
+        ))
+    }
+}
+
+/// The next nonce expected in a permit signed by an owner
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct PermitNonce {
+    pub owner: ActorID,
+    pub nonce: u64,
+}
+
+impl FactoryTokenState {
+    /// Returns the nonce the next permit signed by `owner` must use
+    pub fn permit_nonce(&self, owner: ActorID) -> u64 {
+        match self.permit_nonces.binary_search_by_key(&owner, |n| n.owner) {
+            Ok(pos) => self.permit_nonces[pos].nonce,
+            Err(_) => 0,
+        }
+    }
+
+    fn increment_permit_nonce(&mut self, owner: ActorID) {
+        match self.permit_nonces.binary_search_by_key(&owner, |n| n.owner) {
+            Ok(pos) => self.permit_nonces[pos].nonce += 1,
+            Err(pos) => self.permit_nonces.insert(pos, PermitNonce { owner, nonce: 1 }),
+        }
+    }
+}
+
+impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
+    /// Returns the EIP-712 domain in which owners sign permits for this token
+    pub fn permit_domain(&self) -> Eip712Domain {
+        Eip712Domain {
+            name: PERMIT_DOMAIN.into(),
+            version: PERMIT_VERSION.into(),
+            chain_id: self.runtime.chain_id(),
+            verifying_contract: Address::new_id(self.runtime.actor_id()),
+        }
+    }
+
+    /// Returns the digest an owner signs to permit `params.amount` to be set as an allowance
+    ///
+    /// The signature in `params` is ignored.
+    pub fn permit_digest(&self, params: &PermitParams) -> Result<Digest, RuntimeError> {
+        let message = self.permit_message(params)?;
+        Ok(eip712_digest(&self.permit_domain().separator()?, &message.struct_hash()?))
+    }
+
+    /// Returns the typed message whose digest an owner signs to permit `params.amount` to be set
+    /// as an allowance
+    ///
+    /// The signature in `params` is ignored.
+    pub fn permit_message(&self, params: &PermitParams) -> Result<PermitMessage, RuntimeError> {
+        Ok(PermitMessage {
+            owner: params.owner,
+            operator: params.operator,
+            amount: params.amount.clone(),
+            nonce: self.owner_nonce(&params.owner)?,
+            deadline: params.deadline,
+        })
+    }
+
+    /// Sets an allowance approved by the owner's signature
+    pub fn permit(&mut self, params: PermitParams) -> Result<AllowanceReturn, RuntimeError> {
+        let epoch = self.runtime.curr_epoch();
+        if epoch > params.deadline {
+            return Err(RuntimeError::PermitExpired { deadline: params.deadline, epoch });
+        }
+        let digest = self.permit_digest(&params)?;
+        if !self.runtime.verify_digest(&params.owner, params.signature.bytes(), &digest)? {
+            return Err(RuntimeError::InvalidSignature);
+        }
+
+        self.token().set_allowance(&params.owner, &params.operator, &params.amount)?;
+        self.emit_allowance_event(&params.owner, &params.operator, &params.amount)?;
+        // setting the allowance initialized the owner if it didn't exist yet
+        let owner = self.runtime.resolve_id(&params.owner)?;
+        self.state.increment_permit_nonce(owner);
+        Ok(params.amount)
+    }
+
+    /// Returns the nonce the next permit signed by an owner must use
+    pub fn permit_nonce(&self, owner: Address) -> Result<u64, RuntimeError> {
+        self.owner_nonce(&owner)
+    }
+
+    fn owner_nonce(&self, owner: &Address) -> Result<u64, RuntimeError> {
+        match self.runtime.resolve_id(owner) {
+            Ok(id) => Ok(self.state.permit_nonce(id)),
+            Err(MessagingError::AddressNotResolved(_)) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Generic invoke for the permit methods of the factory token
+///
+/// Works the same way as [`frc46_invoke`](crate::frc46_invoke), calling `flush_state` once after
+/// methods that change the state and returning `Ok(None)` for methods it doesn't handle.
+pub fn permit_invoke<S, BS, F>(
+    method_num: u64,
+    params: u32,
+    token: &mut FactoryToken<S, BS>,
+    flush_state: F,
+) -> Result<Option<u32>, RuntimeError>
+where
+    S: Syscalls,
+    BS: Blockstore,
+    F: FnOnce(&mut FactoryToken<S, BS>) -> Result<(), RuntimeError>,
+{
+    let (ret, needs_flush) = match_method!(method_num, {
+        "Permit" => {
+            let res = token.permit(frc46_unpack_params(params))?;
+            (frc46_return_block(&res), true)
+        }
+        "PermitNonce" => {
+            let res = token.permit_nonce(frc46_unpack_params(params))?;
+            (frc46_return_block(&res), false)
+        }
+        _ => {
+            (None, false)
+        }
+    });
+
+    if needs_flush {
+        flush_state(token)?;
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use frc46_token::token::types::{FRC46Token, GetAllowanceParams};
+    use fvm_actor_utils::{
+        shared_blockstore::SharedMemoryBlockstore,
+        syscalls::fake_syscalls::{FakeKey, FakeSyscalls},
+        util::ActorRuntime,
+    };
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{
+        address::{Address, Protocol},
+        econ::TokenAmount,
+    };
+
+    use super::PermitParams;
+    use crate::{FactoryToken, RuntimeError};
+
+    const OPERATOR: Address = Address::new_id(2);
+
+    /// Signs a permit with the key of the given address
+    fn sign(
+        token: &FactoryToken<FakeSyscalls, SharedMemoryBlockstore>,
+        key: &FakeKey,
+        signer: &Address,
+        mut params: PermitParams,
+    ) -> PermitParams {
+        let digest = token.permit_digest(&params).unwrap();
+        let signature = match signer.protocol() {
+            Protocol::BLS => key.sign_bls(&digest).to_vec(),
+            _ => key.sign_secp(&digest).to_vec(),
+        };
+        params.signature = RawBytes::new(signature);
+        params
+    }
+
+    #[test]
+    fn it_sets_allowances_from_permits() {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        runtime.syscalls.set_caller_id(1);
+        let mut token =
+            FactoryToken::new(runtime, "Test Token".into(), "TEST".into(), 1, Some(1)).unwrap();
+        token.runtime.syscalls.set_curr_epoch(10);
+
+        // f1, f3 and f410 owners can all sign permits, which anyone can submit
+        let key = FakeKey::new(b"owner");
+        let eth = key.eth_address();
+        for owner in [key.secp_address(), key.bls_address(), eth] {
+            let params = PermitParams {
+                owner,
+                operator: OPERATOR,
+                amount: TokenAmount::from_whole(5),
+                deadline: 10,
+                signature: RawBytes::default(),
+            };
+            let permit = sign(&token, &key, &owner, params.clone());
+            assert_eq!(token.permit(permit.clone()).unwrap(), TokenAmount::from_whole(5));
+            let allowance = token.allowance(GetAllowanceParams { owner, operator: OPERATOR });
+            assert_eq!(allowance.unwrap(), TokenAmount::from_whole(5));
+            assert_eq!(token.permit_nonce(owner).unwrap(), 1);
+
+            // a permit can't be replayed
+            let err = token.permit(permit).unwrap_err();
+            assert!(matches!(err, RuntimeError::InvalidSignature));
+
+            // later permits replace the allowance
+            let params = PermitParams { amount: TokenAmount::from_whole(1), ..params };
+            token.permit(sign(&token, &key, &owner, params)).unwrap();
+            let allowance = token.allowance(GetAllowanceParams { owner, operator: OPERATOR });
+            assert_eq!(allowance.unwrap(), TokenAmount::from_whole(1));
+            assert_eq!(token.permit_nonce(owner).unwrap(), 2);
+        }
+
+        // permits must be signed by the owner
+        let params = PermitParams {
+            owner: eth,
+            operator: OPERATOR,
+            amount: TokenAmount::from_whole(5),
+            deadline: 10,
+            signature: RawBytes::default(),
+        };
+        let mallory = FakeKey::new(b"mallory");
+        let err = token.permit(sign(&token, &mallory, &eth, params.clone())).unwrap_err();
+        assert!(matches!(err, RuntimeError::InvalidSignature));
+
+        // and can't be used after their deadline
+        let params = PermitParams { deadline: 9, ..params };
+        let err = token.permit(sign(&token, &key, &eth, params)).unwrap_err();
+        assert!(matches!(err, RuntimeError::PermitExpired { deadline: 9, epoch: 10 }));
+
+        // nonces survive a save and load
+        let cid = token.save().unwrap();
+        let token = FactoryToken::load(token.runtime, &cid).unwrap();
+        assert_eq!(token.permit_nonce(eth).unwrap(), 2);
+    }
+}
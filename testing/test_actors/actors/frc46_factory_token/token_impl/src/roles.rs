@@ -0,0 +1,361 @@
+//! Role-based access control for the factory token
+//!
+//! Roles are named sets of actors stored in the token state, following the same scheme as the
+//! roles of `frc53_nft`. Members of the `ADMIN_ROLE` may grant and revoke any role, and any member
+//! may renounce a role it holds. The actor that constructs the token is its first admin and minter.
+use frc42_dispatch::match_method;
+use frc46_token::token::types::BurnReturn;
+use fvm_actor_utils::{messaging::MessagingError, syscalls::Syscalls};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_sdk::NO_DATA_BLOCK_ID;
+use fvm_shared::{address::Address, econ::TokenAmount, ActorID};
+
+use crate::{
+    events::{ROLE_GRANTED_EVENT, ROLE_REVOKED_EVENT},
+    frc46_return_block, frc46_unpack_params, FactoryToken, FactoryTokenState, RuntimeError,
+};
+
+/// Members may grant and revoke every role, including this one
+pub const ADMIN_ROLE: &str = "admin";
+/// Members may mint new tokens and permanently disable minting
+pub const MINTER_ROLE: &str = "minter";
+/// Members may burn tokens from any account without an allowance
+pub const BURNER_ROLE: &str = "burner";
+/// Members may pause and unpause transfers, minting and burning
+pub const PAUSER_ROLE: &str = "pauser";
+
+/// The actors holding a role
+#[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Eq, Clone, Debug)]
+pub struct RoleMembers {
+    pub role: String,
+    /// Sorted list of the members of the role
+    pub members: Vec<ActorID>,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct RoleParams {
+    pub role: String,
+    pub account: Address,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct RenounceRoleParams {
+    pub role: String,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct ForceBurnParams {
+    pub owner: Address,
+    pub amount: TokenAmount,
+}
+
+impl FactoryTokenState {
+    /// Adds an actor to a role, returning false if it already held the role
+    ///
+    /// It is the caller's responsibility to check that the actor using this method is permitted to
+    /// do so.
+    pub fn grant_role(&mut self, role: &str, actor: ActorID) -> bool {
+        match self.roles.binary_search_by(|r| r.role.as_str().cmp(role)) {
+            Ok(pos) => {
+                let members = &mut self.roles[pos].members;
+                match members.binary_search(&actor) {
+                    Ok(_) => false,
+                    Err(i) => {
+                        members.insert(i, actor);
+                        true
+                    }
+                }
+            }
+            Err(pos) => {
+                self.roles.insert(pos, RoleMembers { role: role.into(), members: vec![actor] });
+                true
+            }
+        }
+    }
+
+    /// Removes an actor from a role, returning false if it didn't hold the role
+    ///
+    /// It is the caller's responsibility to check that the actor using this method is permitted to
+    /// do so.
+    pub fn revoke_role(&mut self, role: &str, actor: ActorID) -> bool {
+        let Ok(pos) = self.roles.binary_search_by(|r| r.role.as_str().cmp(role)) else {
+            return false;
+        };
+        let members = &mut self.roles[pos].members;
+        let Ok(i) = members.binary_search(&actor) else {
+            return false;
+        };
+        members.remove(i);
+        if members.is_empty() {
+            self.roles.remove(pos);
+        }
+        true
+    }
+
+    /// Returns the members of a role
+    pub fn role_members(&self, role: &str) -> &[ActorID] {
+        match self.roles.binary_search_by(|r| r.role.as_str().cmp(role)) {
+            Ok(pos) => &self.roles[pos].members,
+            Err(_) => &[],
+        }
+    }
+
+    /// Checks if an actor holds a role
+    pub fn has_role(&self, role: &str, actor: ActorID) -> bool {
+        self.role_members(role).binary_search(&actor).is_ok()
+    }
+
+    /// Fails with `RuntimeError::AddressNotAuthorized` unless the actor holds the role
+    pub fn assert_role(&self, role: &str, actor: ActorID) -> Result<(), RuntimeError> {
+        if self.has_role(role, actor) {
+            Ok(())
+        } else {
+            Err(RuntimeError::AddressNotAuthorized)
+        }
+    }
+}
+
+impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
+    /// Grants a role to an account
+    ///
+    /// The caller must hold the `ADMIN_ROLE`
+    pub fn grant_role(&mut self, params: RoleParams) -> Result<(), RuntimeError> {
+        self.state.assert_role(ADMIN_ROLE, self.runtime.caller())?;
+        let account = self.runtime.resolve_or_init(&params.account)?;
+        if self.state.grant_role(&params.role, account) {
+            self.emit_role_event(ROLE_GRANTED_EVENT, &params.role, account)?;
+        }
+        Ok(())
+    }
+
+    /// Revokes a role from an account
+    ///
+    /// The caller must hold the `ADMIN_ROLE`
+    pub fn revoke_role(&mut self, params: RoleParams) -> Result<(), RuntimeError> {
+        self.state.assert_role(ADMIN_ROLE, self.runtime.caller())?;
+        let account = match self.runtime.resolve_id(&params.account) {
+            Ok(id) => id,
+            Err(MessagingError::AddressNotResolved(_)) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if self.state.revoke_role(&params.role, account) {
+            self.emit_role_event(ROLE_REVOKED_EVENT, &params.role, account)?;
+        }
+        Ok(())
+    }
+
+    /// Gives up a role held by the caller
+    pub fn renounce_role(&mut self, params: RenounceRoleParams) -> Result<(), RuntimeError> {
+        let caller = self.runtime.caller();
+        if self.state.revoke_role(&params.role, caller) {
+            self.emit_role_event(ROLE_REVOKED_EVENT, &params.role, caller)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether an account holds a role
+    pub fn has_role(&self, params: RoleParams) -> Result<bool, RuntimeError> {
+        match self.runtime.resolve_id(&params.account) {
+            Ok(id) => Ok(self.state.has_role(&params.role, id)),
+            Err(MessagingError::AddressNotResolved(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Pauses transfers, minting and burning
+    ///
+    /// The caller must hold the `PAUSER_ROLE`
+    pub fn pause(&mut self) -> Result<(), RuntimeError> {
+        self.state.assert_role(PAUSER_ROLE, self.runtime.caller())?;
+        self.state.paused = true;
+        Ok(())
+    }
+
+    /// Resumes transfers, minting and burning
+    ///
+    /// The caller must hold the `PAUSER_ROLE`
+    pub fn unpause(&mut self) -> Result<(), RuntimeError> {
+        self.state.assert_role(PAUSER_ROLE, self.runtime.caller())?;
+        self.state.paused = false;
+        Ok(())
+    }
+
+    /// Burns tokens from any account, without needing an allowance
+    ///
+    /// The caller must hold the `BURNER_ROLE`. This is permitted while the token is paused.
+    pub fn force_burn(&mut self, params: ForceBurnParams) -> Result<BurnReturn, RuntimeError> {
+        self.state.assert_role(BURNER_ROLE, self.runtime.caller())?;
+        let res = self.token().burn(&params.owner, &params.amount)?;
+        self.emit_burn_event(self.runtime.caller(), &params.owner, &params.amount)?;
+        Ok(res)
+    }
+}
+
+/// Generic invoke for the access control methods of the factory token
+///
+/// Works the same way as [`frc46_invoke`](crate::frc46_invoke), calling `flush_state` once after
+/// methods that change the state and returning `Ok(None)` for methods it doesn't handle.
+pub fn roles_invoke<S, BS, F>(
+    method_num: u64,
+    params: u32,
+    token: &mut FactoryToken<S, BS>,
+    flush_state: F,
+) -> Result<Option<u32>, RuntimeError>
+where
+    S: Syscalls,
+    BS: Blockstore,
+    F: FnOnce(&mut FactoryToken<S, BS>) -> Result<(), RuntimeError>,
+{
+    let (ret, needs_flush) = match_method!(method_num, {
+        "GrantRole" => {
+            token.grant_role(frc46_unpack_params(params))?;
+            (Some(NO_DATA_BLOCK_ID), true)
+        }
+        "RevokeRole" => {
+            token.revoke_role(frc46_unpack_params(params))?;
+            (Some(NO_DATA_BLOCK_ID), true)
+        }
+        "RenounceRole" => {
+            token.renounce_role(frc46_unpack_params(params))?;
+            (Some(NO_DATA_BLOCK_ID), true)
+        }
+        "HasRole" => {
+            let res = token.has_role(frc46_unpack_params(params))?;
+            (frc46_return_block(&res), false)
+        }
+        "Pause" => {
+            token.pause()?;
+            (Some(NO_DATA_BLOCK_ID), true)
+        }
+        "Unpause" => {
+            token.unpause()?;
+            (Some(NO_DATA_BLOCK_ID), true)
+        }
+        "ForceBurn" => {
+            let res = token.force_burn(frc46_unpack_params(params))?;
+            (frc46_return_block(&res), true)
+        }
+        _ => {
+            (None, false)
+        }
+    });
+
+    if needs_flush {
+        flush_state(token)?;
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use frc46_token::token::types::{FRC46Token, TransferParams};
+    use fvm_actor_utils::{
+        shared_blockstore::SharedMemoryBlockstore, syscalls::fake_syscalls::FakeSyscalls,
+        util::ActorRuntime,
+    };
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, econ::TokenAmount, ActorID};
+
+    use super::{
+        ForceBurnParams, RenounceRoleParams, RoleParams, ADMIN_ROLE, BURNER_ROLE, MINTER_ROLE,
+        PAUSER_ROLE,
+    };
+    use crate::{FactoryToken, MintParams, RuntimeError};
+
+    const ALICE_ID: ActorID = 1;
+    const BOB_ID: ActorID = 2;
+    const BOB: Address = Address::new_id(BOB_ID);
+
+    fn setup_token() -> FactoryToken<FakeSyscalls, SharedMemoryBlockstore> {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        runtime.syscalls.set_caller_id(ALICE_ID);
+        FactoryToken::new(runtime, "Test Token".into(), "TEST".into(), 1, Some(ALICE_ID)).unwrap()
+    }
+
+    fn role(role: &str, account: Address) -> RoleParams {
+        RoleParams { role: role.into(), account }
+    }
+
+    #[test]
+    fn it_manages_roles() {
+        let mut token = setup_token();
+        assert!(token.has_role(role(ADMIN_ROLE, Address::new_id(ALICE_ID))).unwrap());
+        assert_eq!(token.state.role_members(MINTER_ROLE), &[ALICE_ID]);
+
+        // only admins can grant and revoke roles
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        let err = token.grant_role(role(MINTER_ROLE, BOB)).unwrap_err();
+        assert!(matches!(err, RuntimeError::AddressNotAuthorized));
+        token.runtime.syscalls.set_caller_id(ALICE_ID);
+        token.grant_role(role(MINTER_ROLE, BOB)).unwrap();
+        token.grant_role(role(PAUSER_ROLE, BOB)).unwrap();
+        assert_eq!(token.state.role_members(MINTER_ROLE), &[ALICE_ID, BOB_ID]);
+
+        // a granted minter can mint
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        token
+            .mint(MintParams {
+                initial_owner: BOB,
+                amount: TokenAmount::from_whole(1),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+
+        // members can renounce their roles, and admins can revoke them
+        token.renounce_role(RenounceRoleParams { role: PAUSER_ROLE.into() }).unwrap();
+        assert!(!token.has_role(role(PAUSER_ROLE, BOB)).unwrap());
+        token.runtime.syscalls.set_caller_id(ALICE_ID);
+        token.revoke_role(role(MINTER_ROLE, BOB)).unwrap();
+        assert!(!token.has_role(role(MINTER_ROLE, BOB)).unwrap());
+    }
+
+    #[test]
+    fn it_pauses_and_force_burns() {
+        let mut token = setup_token();
+        token
+            .mint(MintParams {
+                initial_owner: BOB,
+                amount: TokenAmount::from_whole(10),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+
+        // pausing needs the pauser role, even for admins
+        assert!(matches!(token.pause().unwrap_err(), RuntimeError::AddressNotAuthorized));
+        token.grant_role(role(PAUSER_ROLE, Address::new_id(ALICE_ID))).unwrap();
+        token.pause().unwrap();
+
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        let err = token
+            .transfer(TransferParams {
+                to: Address::new_id(ALICE_ID),
+                amount: TokenAmount::from_whole(1),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap_err();
+        assert!(matches!(err, RuntimeError::Paused));
+
+        // burners can burn from any account while paused
+        let burn = || ForceBurnParams { owner: BOB, amount: TokenAmount::from_whole(4) };
+        assert!(matches!(
+            token.force_burn(burn()).unwrap_err(),
+            RuntimeError::AddressNotAuthorized
+        ));
+        token.runtime.syscalls.set_caller_id(ALICE_ID);
+        token.grant_role(role(BURNER_ROLE, Address::new_id(ALICE_ID))).unwrap();
+        assert_eq!(token.force_burn(burn()).unwrap().balance, TokenAmount::from_whole(6));
+
+        token.unpause().unwrap();
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        token
+            .transfer(TransferParams {
+                to: Address::new_id(ALICE_ID),
+                amount: TokenAmount::from_whole(1),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+        assert_eq!(token.total_supply(), TokenAmount::from_whole(6));
+    }
+}
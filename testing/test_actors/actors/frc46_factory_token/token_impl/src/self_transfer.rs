@@ -0,0 +1,179 @@
+//! Handling of tokens transferred to the factory token actor itself
+//!
+//! What happens to tokens sent to the token's own address is chosen at construction and applied
+//! by its receiver hook, [`token_received`](FactoryToken::token_received). They can be rejected,
+//! burned, or held in a vault: the token actor's own balance, from which an admin can `Sweep` them
+//! to another account. This recovers tokens sent to the token by mistake.
+use frc46_token::token::types::TransferReturn;
+use fvm_actor_utils::syscalls::Syscalls;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{
+    tuple::{Deserialize_tuple, Serialize_tuple},
+    RawBytes,
+};
+use fvm_shared::{address::Address, econ::TokenAmount};
+use serde::{Deserialize, Serialize};
+
+use crate::{roles::ADMIN_ROLE, FactoryToken, RuntimeError};
+
+/// What to do with tokens transferred to the token actor's own address
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelfTransferPolicy {
+    /// Abort the transfer
+    #[default]
+    Reject,
+    /// Burn the tokens, refunding the FIL backing them to the sender for FIL-backed tokens
+    Burn,
+    /// Hold the tokens in the vault until an admin sweeps them
+    Vault,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct SweepParams {
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub operator_data: RawBytes,
+}
+
+impl<S: Syscalls, BS: Blockstore> FactoryToken<S, BS> {
+    /// Returns the policy for tokens transferred to the token actor itself
+    pub fn self_transfer_policy(&self) -> SelfTransferPolicy {
+        self.state.self_transfer
+    }
+
+    /// Transfers tokens held in the vault to another account, calling its receiver hook
+    ///
+    /// The caller must hold the `ADMIN_ROLE`. Like any other transfer, this fails while the token
+    /// is paused or if the recipient is blocklisted.
+    pub fn sweep(&mut self, params: SweepParams) -> Result<TransferReturn, RuntimeError> {
+        self.state.assert_role(ADMIN_ROLE, self.runtime.caller())?;
+        self.assert_not_paused()?;
+        self.assert_not_blocklisted(&[&params.to])?;
+        let vault = Address::new_id(self.runtime.actor_id());
+        let mut hook = self.token().transfer(
+            &vault,
+            &params.to,
+            &params.amount,
+            params.operator_data,
+            RawBytes::default(),
+        )?;
+        self.emit_transfer_event(self.runtime.caller(), &vault, &params.to, &params.amount)?;
+
+        let cid = self.save()?;
+        let (hook_ret, new_root) = self
+            .runtime
+            .call_after_commit(&cid, || hook.call(&self.runtime).map_err(RuntimeError::from))?;
+        self.reload(new_root)?;
+        Ok(self.token().transfer_return(hook_ret)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use frc46_token::token::types::{FRC46Token, TransferParams};
+    use fvm_actor_utils::{
+        shared_blockstore::SharedMemoryBlockstore, syscalls::fake_syscalls::FakeSyscalls,
+        util::ActorRuntime,
+    };
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{address::Address, econ::TokenAmount, error::ExitCode, ActorID};
+
+    use super::{SelfTransferPolicy, SweepParams};
+    use crate::{FactoryToken, MintParams, RuntimeError};
+
+    const ALICE_ID: ActorID = 1;
+    const BOB_ID: ActorID = 2;
+    const BOB: Address = Address::new_id(BOB_ID);
+    const CAROL: Address = Address::new_id(3);
+
+    /// Sets up a token with BOB holding 10 tokens, which handles its own receiver hook calls
+    fn setup_token(
+        policy: SelfTransferPolicy,
+    ) -> FactoryToken<FakeSyscalls, SharedMemoryBlockstore> {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        runtime.syscalls.set_caller_id(ALICE_ID);
+        let mut token =
+            FactoryToken::new(runtime, "Test Token".into(), "TEST".into(), 1, Some(ALICE_ID))
+                .unwrap();
+        token.state.self_transfer = policy;
+        token
+            .mint(MintParams {
+                initial_owner: BOB,
+                amount: TokenAmount::from_whole(10),
+                operator_data: RawBytes::default(),
+            })
+            .unwrap();
+
+        let token_id = token.runtime.actor_id();
+        let bs = token.runtime.blockstore.clone();
+        token.runtime.syscalls.set_on_send(move |syscalls, to, _| {
+            if *to != Address::new_id(token_id) {
+                return ExitCode::OK;
+            }
+            let message = syscalls.last_message.borrow().clone().unwrap();
+            let caller = syscalls.caller_id.replace(token_id);
+            let root = *syscalls.root.borrow();
+            let mut nested =
+                FactoryToken::load(ActorRuntime::new(syscalls, bs.clone()), &root).unwrap();
+            let res = nested
+                .token_received(message.params.unwrap().deserialize().unwrap())
+                .and_then(|_| nested.save());
+            syscalls.caller_id.replace(caller);
+            match res {
+                Ok(cid) => {
+                    syscalls.root.replace(cid);
+                    ExitCode::OK
+                }
+                Err(e) => ExitCode::from(&e),
+            }
+        });
+        token
+    }
+
+    fn transfer_to_token(
+        token: &mut FactoryToken<FakeSyscalls, SharedMemoryBlockstore>,
+    ) -> Result<(), RuntimeError> {
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        let to = Address::new_id(token.runtime.actor_id());
+        let params = TransferParams {
+            to,
+            amount: TokenAmount::from_whole(4),
+            operator_data: RawBytes::default(),
+        };
+        token.transfer(params)?;
+        token.runtime.syscalls.set_caller_id(ALICE_ID);
+        Ok(())
+    }
+
+    #[test]
+    fn it_applies_the_self_transfer_policy() {
+        let mut token = setup_token(SelfTransferPolicy::Reject);
+        let err = transfer_to_token(&mut token).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+
+        let mut token = setup_token(SelfTransferPolicy::Burn);
+        transfer_to_token(&mut token).unwrap();
+        assert_eq!(token.total_supply(), TokenAmount::from_whole(6));
+        assert_eq!(token.balance_of(BOB).unwrap(), TokenAmount::from_whole(6));
+
+        let mut token = setup_token(SelfTransferPolicy::Vault);
+        transfer_to_token(&mut token).unwrap();
+        let vault = Address::new_id(token.runtime.actor_id());
+        assert_eq!(token.balance_of(vault).unwrap(), TokenAmount::from_whole(4));
+        assert_eq!(token.total_supply(), TokenAmount::from_whole(10));
+
+        // only admins can sweep the vault
+        let sweep = || SweepParams {
+            to: CAROL,
+            amount: TokenAmount::from_whole(3),
+            operator_data: RawBytes::default(),
+        };
+        token.runtime.syscalls.set_caller_id(BOB_ID);
+        assert!(matches!(token.sweep(sweep()).unwrap_err(), RuntimeError::AddressNotAuthorized));
+        token.runtime.syscalls.set_caller_id(ALICE_ID);
+        let ret = token.sweep(sweep()).unwrap();
+        assert_eq!(ret.from_balance, TokenAmount::from_whole(1));
+        assert_eq!(token.balance_of(CAROL).unwrap(), TokenAmount::from_whole(3));
+    }
+}
@@ -1,3 +1,5 @@
+use cid::multihash::Code;
+use frc42_dispatch::match_method::{abort_reserved, abort_unhandled};
 use frc42_dispatch::{match_method, method_hash};
 use frc46_token::{
     receiver::{FRC46TokenReceived, FRC46_TOKEN_TYPE},
@@ -13,7 +15,7 @@ use fvm_ipld_encoding::{
 use fvm_sdk as sdk;
 use fvm_shared::receipt::Receipt;
 use fvm_shared::sys::SendFlags;
-use fvm_shared::{address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode};
+use fvm_shared::{address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode, MethodNum};
 use sdk::NO_DATA_BLOCK_ID;
 use serde::{Deserialize, Serialize};
 
@@ -33,7 +35,8 @@ where
 }
 
 /// Action to take in receiver hook or Action method
-/// This gets serialized and sent along as operator_data
+/// This gets serialized and sent along as operator_data. Transfers without operator_data get the
+/// action set by the Configure method instead, which is Accept until configured otherwise.
 #[derive(Serialize, Deserialize, Debug)]
 pub enum TestAction {
     /// Accept the tokens
@@ -48,6 +51,10 @@ pub enum TestAction {
     ActionThenAbort(RawBytes),
     /// Transfer to another address (with instructions for recipient), but take alternative action if rejected
     TransferWithFallback { to: Address, instructions: RawBytes, fallback: RawBytes },
+    /// Reject the tokens, aborting with the given exit code, which must be a user exit code
+    RejectWith(ExitCode),
+    /// Call a method on the token actor, returning the receipt of the call
+    ReEnter { method: MethodNum, params: RawBytes },
 }
 
 /// Params for Action method call
@@ -71,19 +78,32 @@ pub fn action(action: TestAction) -> RawBytes {
     RawBytes::serialize(action).unwrap()
 }
 
+/// Returns the action configured for transfers without operator_data
+fn configured_action() -> TestAction {
+    let data = sdk::ipld::get(&sdk::sself::root().unwrap()).unwrap();
+    fvm_ipld_encoding::from_slice(&data).unwrap()
+}
+
+/// Sets the action for transfers without operator_data
+fn configure(action: &TestAction) {
+    let data = fvm_ipld_encoding::to_vec(action).unwrap();
+    let cid = sdk::ipld::put(Code::Blake2b256.into(), 32, DAG_CBOR, &data).unwrap();
+    sdk::sself::set_root(&cid).unwrap();
+}
+
 /// Execute the Transfer action
 fn transfer(token: Address, to: Address, amount: TokenAmount, operator_data: RawBytes) -> Receipt {
     let transfer_params = TransferParams { to, amount, operator_data };
-    let ret = sdk::send::send(
-        &token,
-        method_hash!("Transfer"),
-        IpldBlock::serialize_cbor(&transfer_params).unwrap(),
-        TokenAmount::zero(),
-        None,
-        SendFlags::empty(),
-    )
-    .unwrap();
-    // ignore failures at this level and return the transfer call receipt so caller can decide what to do
+    call_token(token, method_hash!("Transfer"), RawBytes::serialize(transfer_params).unwrap())
+}
+
+/// Call a method on the token actor, for the Transfer and ReEnter actions
+fn call_token(token: Address, method: MethodNum, params: RawBytes) -> Receipt {
+    let params = (!params.is_empty()).then(|| IpldBlock { codec: DAG_CBOR, data: params.to_vec() });
+    let ret =
+        sdk::send::send(&token, method, params, TokenAmount::zero(), None, SendFlags::empty())
+            .unwrap();
+    // ignore failures at this level and return the call receipt so caller can decide what to do
     Receipt {
         exit_code: ret.exit_code,
         return_data: ret.return_data.map_or(RawBytes::default(), |b| RawBytes::new(b.data)),
@@ -131,7 +151,7 @@ fn handle_action(action: TestAction, token_address: Address) -> u32 {
     };
 
     match action {
-        TestAction::Accept | TestAction::Reject => {
+        TestAction::Accept | TestAction::Reject | TestAction::RejectWith(_) => {
             sdk::vm::abort(ExitCode::USR_ILLEGAL_ARGUMENT.value(), Some("invalid argument"));
         }
         TestAction::Transfer(to, operator_data) => {
@@ -161,6 +181,10 @@ fn handle_action(action: TestAction, token_address: Address) -> u32 {
                 return_ipld(&receipt)
             }
         }
+        TestAction::ReEnter { method, params } => {
+            let receipt = call_token(token_address, method, params);
+            return_ipld(&receipt)
+        }
     }
 }
 
@@ -174,6 +198,9 @@ fn handle_receive_action(action: TestAction, token_address: Address, amount: Tok
             // abort to reject transfer
             sdk::vm::abort(ExitCode::USR_FORBIDDEN.value(), Some("rejecting transfer"));
         }
+        TestAction::RejectWith(exit_code) => {
+            sdk::vm::abort(exit_code.value(), Some("rejecting transfer"));
+        }
         TestAction::Transfer(to, operator_data) => {
             // transfer to a target address
             let receipt = transfer(token_address, to, amount, operator_data);
@@ -198,6 +225,11 @@ fn handle_receive_action(action: TestAction, token_address: Address, amount: Tok
                 return_ipld(&receipt)
             }
         }
+        TestAction::ReEnter { method, params } => {
+            // call back into the token while the transfer is still in progress
+            let receipt = call_token(token_address, method, params);
+            return_ipld(&receipt)
+        }
     }
 }
 
@@ -210,6 +242,7 @@ fn invoke(input: u32) -> u32 {
     let method_num = sdk::message::method_number();
     match_method!(method_num, {
         "Constructor" => {
+            configure(&TestAction::Accept);
             NO_DATA_BLOCK_ID
         },
         "Receive" => {
@@ -225,8 +258,12 @@ fn invoke(input: u32) -> u32 {
             // get token transfer data
             let token_params: FRC46TokenReceived = params.payload.deserialize().unwrap();
 
-            // todo: examine the operator_data to determine our next move
-            let action: TestAction = token_params.operator_data.deserialize().unwrap();
+            // operator_data determines our next move, falling back to the configured action
+            let action = if token_params.operator_data.is_empty() {
+                configured_action()
+            } else {
+                token_params.operator_data.deserialize().unwrap()
+            };
             handle_receive_action(action, Address::new_id(sdk::message::caller()), token_params.amount)
         },
         "Action" => {
@@ -235,11 +272,13 @@ fn invoke(input: u32) -> u32 {
 
             handle_action(params.action, params.token_address)
         }
-        _ => {
-            sdk::vm::abort(
-                ExitCode::USR_UNHANDLED_MESSAGE.value(),
-                Some("Unknown method number"),
-            );
+        "Configure" => {
+            // set the action for transfers without operator_data
+            let action: TestAction = deserialize_params(input);
+            configure(&action);
+            NO_DATA_BLOCK_ID
         }
+        reserved => abort_reserved(method_num),
+        _ => abort_unhandled(method_num),
     })
 }
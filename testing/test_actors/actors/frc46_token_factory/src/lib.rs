@@ -0,0 +1,49 @@
+use frc42_dispatch::{match_method, match_method::abort_unhandled};
+use fvm_actor_utils::{
+    blockstore::Blockstore, syscalls::fvm_syscalls::FvmSyscalls, util::ActorRuntime,
+};
+use fvm_sdk::NO_DATA_BLOCK_ID;
+use fvm_shared::error::ExitCode;
+use token_impl::{
+    deserialize_params,
+    factory::{FactoryConstructorParams, TokenFactory},
+    return_ipld, ConstructorParams, RuntimeError,
+};
+
+fn factory_invoke(method_num: u64, params: u32) -> Result<u32, RuntimeError> {
+    let runtime = ActorRuntime::<FvmSyscalls, Blockstore>::new_fvm_runtime();
+    match_method!(method_num, {
+        "Constructor" => {
+            let params: FactoryConstructorParams = deserialize_params(params);
+            let factory = TokenFactory::new(runtime, params.token_code)?;
+            let cid = factory.save()?;
+            factory.runtime().set_root(&cid)?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        "CreateToken" => {
+            let root_cid = runtime.root_cid()?;
+            let params: ConstructorParams = deserialize_params(params);
+            let mut factory = TokenFactory::load(runtime, &root_cid)?;
+            let res = factory.create_token(params)?;
+            let cid = factory.save()?;
+            factory.runtime().set_root(&cid)?;
+            return_ipld(&res)
+        }
+        _ => {
+            abort_unhandled(method_num)
+        }
+    })
+}
+
+#[no_mangle]
+pub fn invoke(params: u32) -> u32 {
+    std::panic::set_hook(Box::new(|info| {
+        fvm_sdk::vm::abort(ExitCode::USR_ASSERTION_FAILED.value(), Some(&format!("{info}")))
+    }));
+
+    let method_num = fvm_sdk::message::method_number();
+    match factory_invoke(method_num, params) {
+        Ok(ret) => ret,
+        Err(err) => fvm_sdk::vm::abort(ExitCode::from(&err).value(), Some(&err.to_string())),
+    }
+}
@@ -0,0 +1,419 @@
+//! A registry of deployed FRC-46 tokens
+//!
+//! Token deployers register the name, symbol and address of their tokens here, giving wallets a
+//! single on-chain place to discover tokens. Entries are stored in registration order in an AMT so
+//! they can be listed a page at a time, and indexed by token ActorID (to reject duplicate
+//! registrations) and by symbol.
+//!
+//! Symbols are not unique: anyone may register any token, so the registrant of each entry is
+//! recorded for wallets to decide which registrations to trust.
+use cid::{multihash::Code, Cid};
+use frc42_dispatch::client;
+use frc46_token::token::state::{actor_id_key, DEFAULT_HAMT_BIT_WIDTH};
+use fvm_actor_utils::{
+    messaging::MessagingError,
+    syscalls::Syscalls,
+    util::{ActorError, ActorRuntime},
+};
+use fvm_ipld_amt::Amt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{
+    tuple::{Deserialize_tuple, Serialize_tuple},
+    CborStore,
+};
+use fvm_ipld_hamt::{BytesKey, Hamt};
+use fvm_shared::{
+    address::Address,
+    clock::ChainEpoch,
+    error::{ErrorNumber, ExitCode},
+    ActorID,
+};
+use thiserror::Error;
+
+/// Bit width of the AMT holding registry entries
+pub const ENTRIES_BIT_WIDTH: u32 = 5;
+/// Maximum number of entries returned by a single `ListTokens` call
+pub const MAX_PAGE_SIZE: u64 = 100;
+/// Maximum length in bytes of a registered token name
+pub const MAX_NAME_LENGTH: usize = 128;
+/// Maximum length in bytes of a registered token symbol
+pub const MAX_SYMBOL_LENGTH: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("ipld encoding error: {0}")]
+    Encoding(#[from] fvm_ipld_encoding::Error),
+    #[error("ipld blockstore error: {0}")]
+    Blockstore(#[from] ErrorNumber),
+    #[error("actor runtime error: {0}")]
+    ActorRuntime(#[from] ActorError),
+    #[error("actor messaging error {0}")]
+    Messaging(#[from] MessagingError),
+    #[error("error in registry index {0}")]
+    Hamt(#[from] fvm_ipld_hamt::Error),
+    #[error("error in registry entries {0}")]
+    Amt(#[from] fvm_ipld_amt::Error),
+    // deserialisation error when loading state
+    #[error("error loading state {0}")]
+    Deserialization(String),
+    // serialisation error when saving state
+    #[error("error saving state {0}")]
+    Serialization(String),
+    #[error("token {0} is already registered")]
+    AlreadyRegistered(ActorID),
+    #[error("invalid parameters: {0}")]
+    InvalidParams(String),
+}
+
+impl From<&RegistryError> for ExitCode {
+    fn from(error: &RegistryError) -> Self {
+        match error {
+            RegistryError::Encoding(_)
+            | RegistryError::Hamt(_)
+            | RegistryError::Amt(_)
+            | RegistryError::Deserialization(_)
+            | RegistryError::Serialization(_) => ExitCode::USR_SERIALIZATION,
+            RegistryError::Blockstore(_) => ExitCode::USR_UNSPECIFIED,
+            RegistryError::ActorRuntime(e) => e.into(),
+            RegistryError::Messaging(e) => e.into(),
+            RegistryError::AlreadyRegistered(_) => ExitCode::USR_FORBIDDEN,
+            RegistryError::InvalidParams(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
+        }
+    }
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterParams {
+    /// Address of the token actor, which must already exist
+    pub token: Address,
+    pub name: String,
+    pub symbol: String,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterReturn {
+    /// Position of the new entry in the registry, usable as a `ListTokens` cursor
+    pub index: u64,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct RegistryEntry {
+    /// Address of the token as given at registration
+    pub address: Address,
+    /// ActorID the token's address resolved to at registration
+    pub id: ActorID,
+    pub name: String,
+    pub symbol: String,
+    /// Actor which registered the token
+    pub registrant: ActorID,
+    /// Epoch at which the token was registered
+    pub registered_at: ChainEpoch,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ListTokensParams {
+    /// Index to start listing from, with None meaning the start of the registry
+    pub cursor: Option<u64>,
+    /// Maximum number of entries to return, capped at MAX_PAGE_SIZE
+    pub limit: u64,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ListTokensReturn {
+    pub entries: Vec<RegistryEntry>,
+    /// Cursor to pass to the next call, with None meaning there are no more entries
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct LookupBySymbolParams {
+    pub symbol: String,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct LookupBySymbolReturn {
+    /// Every token registered with the symbol, in registration order
+    pub entries: Vec<RegistryEntry>,
+}
+
+/// Interface of the registry actor, for calling it from other actors through
+/// [`TokenRegistryClient`]
+#[client(TokenRegistryClient)]
+pub trait TokenRegistry {
+    fn register(&self, params: RegisterParams) -> RegisterReturn;
+    fn list_tokens(&self, params: ListTokensParams) -> ListTokensReturn;
+    fn lookup_by_symbol(&self, params: LookupBySymbolParams) -> LookupBySymbolReturn;
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct RegistryState {
+    /// Amt<RegistryEntry> of registered tokens, in registration order
+    pub entries: Cid,
+    /// Map<ActorID, u64> of registered tokens to their index in `entries`
+    pub tokens: Cid,
+    /// Map<String, Vec<u64>> of symbols to the indices of the tokens registered with them
+    pub symbols: Cid,
+    /// Index of the next entry to be registered
+    pub next_index: u64,
+}
+
+pub struct Registry<S: Syscalls, BS: Blockstore> {
+    runtime: ActorRuntime<S, BS>,
+    state: RegistryState,
+}
+
+impl<S: Syscalls, BS: Blockstore> Registry<S, BS> {
+    pub fn new(runtime: ActorRuntime<S, BS>) -> Result<Self, RegistryError> {
+        let entries =
+            Amt::<RegistryEntry, _>::new_with_bit_width(&runtime, ENTRIES_BIT_WIDTH).flush()?;
+        let tokens =
+            Hamt::<_, u64>::new_with_bit_width(&runtime, DEFAULT_HAMT_BIT_WIDTH).flush()?;
+        let symbols =
+            Hamt::<_, Vec<u64>>::new_with_bit_width(&runtime, DEFAULT_HAMT_BIT_WIDTH).flush()?;
+        Ok(Registry { state: RegistryState { entries, tokens, symbols, next_index: 0 }, runtime })
+    }
+
+    pub fn load(runtime: ActorRuntime<S, BS>, cid: &Cid) -> Result<Self, RegistryError> {
+        let state = match runtime.get_cbor::<RegistryState>(cid) {
+            Ok(Some(s)) => s,
+            Ok(None) => return Err(RegistryError::Deserialization("no data found".into())),
+            Err(e) => return Err(RegistryError::Deserialization(e.to_string())),
+        };
+        Ok(Registry { runtime, state })
+    }
+
+    pub fn save(&self) -> Result<Cid, RegistryError> {
+        self.runtime
+            .put_cbor(&self.state, Code::Blake2b256)
+            .map_err(|err| RegistryError::Serialization(err.to_string()))
+    }
+
+    pub fn runtime(&self) -> &ActorRuntime<S, BS> {
+        &self.runtime
+    }
+
+    pub fn state(&self) -> &RegistryState {
+        &self.state
+    }
+
+    /// Registers a token, recording the caller as its registrant
+    ///
+    /// Each token can only be registered once, by whoever registers it first.
+    pub fn register(&mut self, params: RegisterParams) -> Result<RegisterReturn, RegistryError> {
+        if params.name.is_empty() || params.name.len() > MAX_NAME_LENGTH {
+            return Err(RegistryError::InvalidParams(format!(
+                "name must be between 1 and {MAX_NAME_LENGTH} bytes"
+            )));
+        }
+        if params.symbol.is_empty() || params.symbol.len() > MAX_SYMBOL_LENGTH {
+            return Err(RegistryError::InvalidParams(format!(
+                "symbol must be between 1 and {MAX_SYMBOL_LENGTH} bytes"
+            )));
+        }
+        let id = self.runtime.resolve_id(&params.token)?;
+
+        let mut tokens = Hamt::<_, u64>::load_with_bit_width(
+            &self.state.tokens,
+            &self.runtime,
+            DEFAULT_HAMT_BIT_WIDTH,
+        )?;
+        if tokens.contains_key(&actor_id_key(id))? {
+            return Err(RegistryError::AlreadyRegistered(id));
+        }
+        let index = self.state.next_index;
+        tokens.set(actor_id_key(id), index)?;
+
+        let mut symbols = Hamt::<_, Vec<u64>>::load_with_bit_width(
+            &self.state.symbols,
+            &self.runtime,
+            DEFAULT_HAMT_BIT_WIDTH,
+        )?;
+        let key = BytesKey::from(params.symbol.as_bytes());
+        let mut indices = symbols.get(&key)?.cloned().unwrap_or_default();
+        indices.push(index);
+        symbols.set(key, indices)?;
+
+        let mut entries = Amt::<RegistryEntry, _>::load(&self.state.entries, &self.runtime)?;
+        entries.set(
+            index,
+            RegistryEntry {
+                address: params.token,
+                id,
+                name: params.name,
+                symbol: params.symbol,
+                registrant: self.runtime.caller(),
+                registered_at: self.runtime.curr_epoch(),
+            },
+        )?;
+
+        self.state.entries = entries.flush()?;
+        self.state.tokens = tokens.flush()?;
+        self.state.symbols = symbols.flush()?;
+        self.state.next_index += 1;
+        Ok(RegisterReturn { index })
+    }
+
+    /// Lists up to `limit` entries in registration order, starting from `cursor`
+    pub fn list_tokens(&self, params: ListTokensParams) -> Result<ListTokensReturn, RegistryError> {
+        if params.limit == 0 {
+            return Err(RegistryError::InvalidParams("limit must be positive".into()));
+        }
+        let limit = params.limit.min(MAX_PAGE_SIZE) as usize;
+
+        let entries = Amt::<RegistryEntry, _>::load(&self.state.entries, &self.runtime)?;
+        let mut page = Vec::new();
+        let (_, next_cursor) =
+            entries.for_each_ranged(params.cursor, Some(limit), |_, entry| {
+                page.push(entry.clone());
+                Ok(())
+            })?;
+        Ok(ListTokensReturn { entries: page, next_cursor })
+    }
+
+    /// Returns every token registered with `symbol`, in registration order
+    ///
+    /// Symbols are matched exactly, including case.
+    pub fn lookup_by_symbol(
+        &self,
+        params: LookupBySymbolParams,
+    ) -> Result<LookupBySymbolReturn, RegistryError> {
+        let symbols = Hamt::<_, Vec<u64>>::load_with_bit_width(
+            &self.state.symbols,
+            &self.runtime,
+            DEFAULT_HAMT_BIT_WIDTH,
+        )?;
+        let indices = match symbols.get(&BytesKey::from(params.symbol.as_bytes()))? {
+            Some(indices) => indices,
+            None => return Ok(LookupBySymbolReturn { entries: vec![] }),
+        };
+
+        let entries = Amt::<RegistryEntry, _>::load(&self.state.entries, &self.runtime)?;
+        let entries = indices
+            .iter()
+            .map(|index| {
+                entries.get(*index)?.cloned().ok_or_else(|| {
+                    RegistryError::Deserialization(format!("missing registry entry {index}"))
+                })
+            })
+            .collect::<Result<_, RegistryError>>()?;
+        Ok(LookupBySymbolReturn { entries })
+    }
+
+    /// Returns the entry for `token`, or None if it hasn't been registered
+    pub fn get(&self, token: ActorID) -> Result<Option<RegistryEntry>, RegistryError> {
+        let tokens = Hamt::<_, u64>::load_with_bit_width(
+            &self.state.tokens,
+            &self.runtime,
+            DEFAULT_HAMT_BIT_WIDTH,
+        )?;
+        let index = match tokens.get(&actor_id_key(token))? {
+            Some(index) => *index,
+            None => return Ok(None),
+        };
+        let entries = Amt::<RegistryEntry, _>::load(&self.state.entries, &self.runtime)?;
+        Ok(entries.get(index)?.cloned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_actor_utils::{
+        shared_blockstore::SharedMemoryBlockstore, syscalls::fake_syscalls::FakeSyscalls,
+        util::ActorRuntime,
+    };
+    use fvm_shared::{address::Address, error::ExitCode};
+
+    use super::{
+        ListTokensParams, LookupBySymbolParams, RegisterParams, Registry, RegistryError,
+        MAX_PAGE_SIZE,
+    };
+
+    fn register_params(token: u64, symbol: &str) -> RegisterParams {
+        RegisterParams {
+            token: Address::new_id(token),
+            name: format!("Token {token}"),
+            symbol: symbol.into(),
+        }
+    }
+
+    #[test]
+    fn it_registers_and_looks_up_tokens() {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let mut registry = Registry::new(runtime).unwrap();
+        registry.runtime().syscalls.set_caller_id(100);
+        registry.runtime().syscalls.set_curr_epoch(7);
+
+        assert_eq!(registry.register(register_params(1000, "AAA")).unwrap().index, 0);
+        assert_eq!(registry.register(register_params(1001, "BBB")).unwrap().index, 1);
+        // symbols aren't unique, but tokens are
+        registry.runtime().syscalls.set_caller_id(101);
+        assert_eq!(registry.register(register_params(1002, "AAA")).unwrap().index, 2);
+        let err = registry.register(register_params(1000, "CCC")).unwrap_err();
+        assert!(matches!(err, RegistryError::AlreadyRegistered(1000)));
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_FORBIDDEN);
+
+        let err = registry.register(register_params(1003, "")).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_ILLEGAL_ARGUMENT);
+        // tokens must exist to be registered
+        let params = RegisterParams {
+            token: Address::new_secp256k1(&[1; 65]).unwrap(),
+            ..register_params(1003, "DDD")
+        };
+        let err = registry.register(params).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_NOT_FOUND);
+
+        // the registry survives a save and load
+        let cid = registry.save().unwrap();
+        let registry = Registry::load(registry.runtime, &cid).unwrap();
+
+        let found = registry
+            .lookup_by_symbol(LookupBySymbolParams { symbol: "AAA".into() })
+            .unwrap()
+            .entries;
+        assert_eq!(found.iter().map(|e| e.id).collect::<Vec<_>>(), vec![1000, 1002]);
+        assert_eq!(found[0].registrant, 100);
+        assert_eq!(found[0].registered_at, 7);
+        assert_eq!(found[1].registrant, 101);
+        let found = registry
+            .lookup_by_symbol(LookupBySymbolParams { symbol: "aaa".into() })
+            .unwrap()
+            .entries;
+        assert!(found.is_empty());
+
+        assert_eq!(registry.get(1001).unwrap().unwrap().symbol, "BBB");
+        assert_eq!(registry.get(1003).unwrap(), None);
+    }
+
+    #[test]
+    fn it_lists_tokens_in_pages() {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let mut registry = Registry::new(runtime).unwrap();
+        for token in 1000..1005 {
+            registry.register(register_params(token, "TKN")).unwrap();
+        }
+
+        let mut cursor = None;
+        let mut listed = vec![];
+        loop {
+            let page = registry.list_tokens(ListTokensParams { cursor, limit: 2 }).unwrap();
+            assert!(page.entries.len() <= 2);
+            listed.extend(page.entries.into_iter().map(|e| e.id));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(listed, vec![1000, 1001, 1002, 1003, 1004]);
+
+        // large pages are capped and zero-sized ones rejected
+        let page =
+            registry.list_tokens(ListTokensParams { cursor: None, limit: u64::MAX }).unwrap();
+        assert_eq!(page.entries.len(), 5);
+        assert!(page.entries.len() as u64 <= MAX_PAGE_SIZE);
+        assert_eq!(page.next_cursor, None);
+        let err = registry.list_tokens(ListTokensParams { cursor: None, limit: 0 }).unwrap_err();
+        assert_eq!(ExitCode::from(&err), ExitCode::USR_ILLEGAL_ARGUMENT);
+    }
+}
@@ -0,0 +1,69 @@
+use frc42_dispatch::{match_method, match_method::abort_unhandled};
+use fvm_actor_utils::{
+    blockstore::Blockstore, syscalls::fvm_syscalls::FvmSyscalls, util::ActorRuntime,
+};
+use fvm_ipld_encoding::DAG_CBOR;
+use fvm_sdk::NO_DATA_BLOCK_ID;
+use fvm_shared::error::ExitCode;
+use registry_impl::{Registry, RegistryError};
+use serde::{de::DeserializeOwned, ser::Serialize};
+
+fn deserialize_params<O: DeserializeOwned>(params: u32) -> O {
+    let params = fvm_sdk::message::params_raw(params).unwrap();
+    let params = params.unwrap();
+    params.deserialize().unwrap()
+}
+
+fn return_ipld<T>(value: &T) -> Result<u32, RegistryError>
+where
+    T: Serialize + ?Sized,
+{
+    let bytes = fvm_ipld_encoding::to_vec(value)?;
+    Ok(fvm_sdk::ipld::put_block(DAG_CBOR, bytes.as_slice())?)
+}
+
+fn registry_invoke(method_num: u64, params: u32) -> Result<u32, RegistryError> {
+    let runtime = ActorRuntime::<FvmSyscalls, Blockstore>::new_fvm_runtime();
+    match_method!(method_num, {
+        "Constructor" => {
+            let registry = Registry::new(runtime)?;
+            let cid = registry.save()?;
+            registry.runtime().set_root(&cid)?;
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        "Register" => {
+            let root_cid = runtime.root_cid()?;
+            let mut registry = Registry::load(runtime, &root_cid)?;
+            let res = registry.register(deserialize_params(params))?;
+            let cid = registry.save()?;
+            registry.runtime().set_root(&cid)?;
+            return_ipld(&res)
+        }
+        "ListTokens" => {
+            let root_cid = runtime.root_cid()?;
+            let registry = Registry::load(runtime, &root_cid)?;
+            return_ipld(&registry.list_tokens(deserialize_params(params))?)
+        }
+        "LookupBySymbol" => {
+            let root_cid = runtime.root_cid()?;
+            let registry = Registry::load(runtime, &root_cid)?;
+            return_ipld(&registry.lookup_by_symbol(deserialize_params(params))?)
+        }
+        _ => {
+            abort_unhandled(method_num)
+        }
+    })
+}
+
+#[no_mangle]
+pub fn invoke(params: u32) -> u32 {
+    std::panic::set_hook(Box::new(|info| {
+        fvm_sdk::vm::abort(ExitCode::USR_ASSERTION_FAILED.value(), Some(&format!("{info}")))
+    }));
+
+    let method_num = fvm_sdk::message::method_number();
+    match registry_invoke(method_num, params) {
+        Ok(ret) => ret,
+        Err(err) => fvm_sdk::vm::abort(ExitCode::from(&err).value(), Some(&err.to_string())),
+    }
+}
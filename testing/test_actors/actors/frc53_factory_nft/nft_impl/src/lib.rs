@@ -0,0 +1,622 @@
+use cid::{multihash::Code, Cid};
+use frc53_nft::{
+    state::{NFTState, StateError},
+    types::{
+        ActorIDSet, ApproveForAllParams, ApproveParams, BurnFromParams, BurnReturn,
+        CollectionMetadata, IsApprovedForAllParams, ListAccountOperatorsParams,
+        ListAccountOperatorsReturn, ListOperatorTokensParams, ListOperatorTokensReturn,
+        ListOwnedTokensParams, ListOwnedTokensReturn, ListTokenOperatorsParams,
+        ListTokenOperatorsReturn, ListTokensParams, ListTokensReturn, MintReturn,
+        RevokeForAllParams, RevokeParams, TokenID, TransferFromParams, TransferParams,
+        TransferReturn, FRC53NFT,
+    },
+    NFTError, NFT,
+};
+use fvm_actor_utils::{
+    messaging::MessagingError,
+    receiver::ReceiverHookError,
+    syscalls::Syscalls,
+    util::{ActorError, ActorRuntime},
+};
+use fvm_ipld_blockstore::{Block, Blockstore};
+use fvm_ipld_encoding::{
+    tuple::{Deserialize_tuple, Serialize_tuple},
+    CborStore, RawBytes, DAG_CBOR,
+};
+use fvm_sdk::error::{StateReadError, StateUpdateError};
+use fvm_sdk::{self as sdk, sys::ErrorNumber, NO_DATA_BLOCK_ID};
+use fvm_shared::{address::Address, error::ExitCode, ActorID};
+use serde::{de::DeserializeOwned, ser::Serialize};
+use thiserror::Error;
+
+/// Errors that can occur during the execution of this actor
+#[derive(Error, Debug)]
+pub enum RuntimeError {
+    /// Error from the underlying NFT library
+    #[error("error in nft: {0}")]
+    NFT(#[from] NFTError),
+    /// Error from the underlying universal receiver hook library
+    #[error("error calling receiver hook: {0}")]
+    Receiver(#[from] ReceiverHookError),
+    /// Error from serialising data to RawBytes
+    #[error("ipld encoding error: {0}")]
+    Encoding(#[from] fvm_ipld_encoding::Error),
+    #[error("ipld blockstore error: {0}")]
+    Blockstore(#[from] ErrorNumber),
+    #[error("actor state not found {0}")]
+    StateRead(#[from] StateReadError),
+    #[error("failed to update actor state {0}")]
+    StateUpdate(#[from] StateUpdateError),
+    #[error("actor runtime error: {0}")]
+    ActorRuntime(#[from] ActorError),
+    // deserialisation error when loading state
+    #[error("error loading state {0}")]
+    Deserialization(String),
+    // serialisation error when saving state
+    #[error("error saving state {0}")]
+    Serialization(String),
+    #[error("underlying state error {0}")]
+    State(#[from] StateError),
+    #[error("actor messaging error {0}")]
+    Messaging(#[from] MessagingError),
+    #[error("address not authorized")]
+    AddressNotAuthorized,
+    #[error("minting has been permanently disabled")]
+    MintingDisabled,
+}
+
+impl From<&RuntimeError> for ExitCode {
+    fn from(error: &RuntimeError) -> Self {
+        match error {
+            RuntimeError::NFT(e) => e.into(),
+            RuntimeError::Receiver(e) => e.into(),
+            RuntimeError::Encoding(_) => ExitCode::USR_SERIALIZATION,
+            RuntimeError::Blockstore(e) => match e {
+                ErrorNumber::IllegalArgument => ExitCode::USR_ILLEGAL_ARGUMENT,
+                ErrorNumber::Forbidden | ErrorNumber::IllegalOperation => ExitCode::USR_FORBIDDEN,
+                ErrorNumber::AssertionFailed => ExitCode::USR_ASSERTION_FAILED,
+                ErrorNumber::InsufficientFunds => ExitCode::USR_INSUFFICIENT_FUNDS,
+                ErrorNumber::IllegalCid | ErrorNumber::NotFound | ErrorNumber::InvalidHandle => {
+                    ExitCode::USR_NOT_FOUND
+                }
+                ErrorNumber::Serialization | ErrorNumber::IllegalCodec => {
+                    ExitCode::USR_SERIALIZATION
+                }
+                _ => ExitCode::USR_UNSPECIFIED,
+            },
+            RuntimeError::StateRead(_) => ExitCode::USR_NOT_FOUND,
+            RuntimeError::StateUpdate(e) => match e {
+                StateUpdateError::ActorDeleted => ExitCode::USR_ILLEGAL_STATE,
+                StateUpdateError::ReadOnly => ExitCode::USR_READ_ONLY,
+            },
+            RuntimeError::ActorRuntime(e) => e.into(),
+            RuntimeError::Deserialization(_) | RuntimeError::Serialization(_) => {
+                ExitCode::USR_SERIALIZATION
+            }
+            RuntimeError::State(e) => e.into(),
+            RuntimeError::Messaging(e) => e.into(),
+            RuntimeError::AddressNotAuthorized | RuntimeError::MintingDisabled => {
+                ExitCode::USR_FORBIDDEN
+            }
+        }
+    }
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct ConstructorParams {
+    pub name: String,
+    pub symbol: String,
+    /// authorised mint operator
+    /// only this address can mint NFTs or remove themselves to permanently disable minting
+    pub minter: Address,
+}
+
+pub fn construct_nft<S: Syscalls, BS: Blockstore>(
+    runtime: ActorRuntime<S, BS>,
+    params: ConstructorParams,
+) -> Result<u32, RuntimeError> {
+    let minter = runtime.resolve_id(&params.minter)?;
+    let nft = FactoryNFT::new(runtime, params.name, params.symbol, Some(minter));
+
+    let cid = nft.save()?;
+    nft.runtime.set_root(&cid)?;
+
+    Ok(NO_DATA_BLOCK_ID)
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct FactoryNFTState {
+    /// Default NFT helper impl, which also holds the collection's name and symbol
+    pub nft: NFTState,
+    /// address of authorised minting operator
+    pub minter: Option<ActorID>,
+}
+
+pub struct FactoryNFT<S: Syscalls, BS: Blockstore> {
+    runtime: ActorRuntime<S, BS>,
+    state: FactoryNFTState,
+}
+
+impl FactoryNFTState {
+    /// Load NFT state from the blockstore provided in `runtime`
+    /// This is for internal use only as part of FactoryNFT::load
+    fn load<BS: Blockstore>(runtime: &BS, cid: &Cid) -> Result<Self, RuntimeError> {
+        match runtime.get_cbor::<Self>(cid) {
+            Ok(Some(s)) => Ok(s),
+            Ok(None) => Err(RuntimeError::Deserialization("no data found".into())),
+            Err(e) => Err(RuntimeError::Deserialization(e.to_string())),
+        }
+    }
+}
+
+/// Implementation of the NFT API in a FVM actor
+///
+/// Here the Ipld parameter structs are marshalled and passed to the underlying library functions
+impl<SC: Syscalls, BS: Blockstore> FRC53NFT for FactoryNFT<SC, BS> {
+    type NFTError = RuntimeError;
+
+    fn name(&self) -> String {
+        self.state.nft.collection_metadata.name.clone()
+    }
+
+    fn symbol(&self) -> String {
+        self.state.nft.collection_metadata.symbol.clone()
+    }
+
+    fn collection_metadata(&self) -> CollectionMetadata {
+        self.state.nft.collection_metadata.clone()
+    }
+
+    fn metadata(&mut self, params: TokenID) -> Result<String, RuntimeError> {
+        Ok(self.nft().metadata(params)?)
+    }
+
+    fn total_supply(&mut self) -> u64 {
+        self.nft().total_supply()
+    }
+
+    fn burn(&mut self, params: Vec<TokenID>) -> Result<BurnReturn, RuntimeError> {
+        let caller = self.caller_address();
+        Ok(self.nft().burn(&caller, &params)?)
+    }
+
+    fn burn_from(&mut self, params: BurnFromParams) -> Result<BurnReturn, RuntimeError> {
+        let caller = self.caller_address();
+        Ok(self.nft().burn_from(&params.from, &caller, &params.token_ids)?)
+    }
+
+    fn list_tokens(&mut self, params: ListTokensParams) -> Result<ListTokensReturn, RuntimeError> {
+        Ok(self.nft().list_tokens(params.cursor, params.limit)?)
+    }
+
+    fn list_owned_tokens(
+        &mut self,
+        params: ListOwnedTokensParams,
+    ) -> Result<ListOwnedTokensReturn, RuntimeError> {
+        let res = self.nft().list_owned_tokens(&params.owner, params.cursor, params.limit)?;
+        Ok(ListOwnedTokensReturn { tokens: res.tokens, next_cursor: res.next_cursor })
+    }
+
+    fn list_token_operators(
+        &mut self,
+        params: ListTokenOperatorsParams,
+    ) -> Result<ListTokenOperatorsReturn, RuntimeError> {
+        Ok(self.nft().list_token_operators(params.token_id, params.cursor, params.limit)?)
+    }
+
+    fn list_operator_tokens(
+        &mut self,
+        params: ListOperatorTokensParams,
+    ) -> Result<ListOperatorTokensReturn, RuntimeError> {
+        Ok(self.nft().list_operator_tokens(&params.operator, params.cursor, params.limit)?)
+    }
+
+    fn list_account_operators(
+        &mut self,
+        params: ListAccountOperatorsParams,
+    ) -> Result<ListAccountOperatorsReturn, RuntimeError> {
+        Ok(self.nft().list_account_operators(&params.owner, params.cursor, params.limit)?)
+    }
+
+    fn balance_of(&mut self, params: Address) -> Result<u64, RuntimeError> {
+        Ok(self.nft().balance_of(&params)?)
+    }
+
+    fn owner_of(&mut self, params: TokenID) -> Result<ActorID, RuntimeError> {
+        Ok(self.nft().owner_of(params)?)
+    }
+
+    fn transfer(&mut self, params: TransferParams) -> Result<TransferReturn, RuntimeError> {
+        let caller = self.caller_address();
+        let mut hook = self.nft().transfer_with_token_data(
+            &caller,
+            &params.to,
+            &params.token_ids,
+            params.operator_data,
+            RawBytes::default(),
+            params.per_token_data,
+        )?;
+
+        let cid = self.save()?;
+        self.runtime.set_root(&cid)?;
+
+        let hook_ret = hook.call(&self.runtime)?;
+
+        self.reload(&cid)?;
+        let ret = self.state.nft.transfer_return(&self.runtime, hook_ret)?;
+
+        Ok(ret)
+    }
+
+    fn transfer_from(
+        &mut self,
+        params: TransferFromParams,
+    ) -> Result<TransferReturn, RuntimeError> {
+        let caller = self.caller_address();
+        let mut hook = self.nft().transfer_from_with_token_data(
+            &params.from,
+            &caller,
+            &params.to,
+            &params.token_ids,
+            params.operator_data,
+            RawBytes::default(),
+            params.per_token_data,
+        )?;
+
+        let cid = self.save()?;
+        self.runtime.set_root(&cid)?;
+
+        let hook_ret = hook.call(&self.runtime)?;
+
+        self.reload(&cid)?;
+        let ret = self.state.nft.transfer_return(&self.runtime, hook_ret)?;
+
+        Ok(ret)
+    }
+
+    fn approve(&mut self, params: ApproveParams) -> Result<(), RuntimeError> {
+        let caller = self.caller_address();
+        self.nft().approve(&caller, &params.operator, &params.token_ids)?;
+        Ok(())
+    }
+
+    fn revoke(&mut self, params: RevokeParams) -> Result<(), RuntimeError> {
+        let caller = self.caller_address();
+        self.nft().revoke(&caller, &params.operator, &params.token_ids)?;
+        Ok(())
+    }
+
+    fn approve_for_all(&mut self, params: ApproveForAllParams) -> Result<(), RuntimeError> {
+        let caller = self.caller_address();
+        self.nft().approve_for_owner(&caller, &params.operator)?;
+        Ok(())
+    }
+
+    fn revoke_for_all(&mut self, params: RevokeForAllParams) -> Result<(), RuntimeError> {
+        let caller = self.caller_address();
+        self.nft().revoke_for_all(&caller, &params.operator)?;
+        Ok(())
+    }
+
+    fn get_approved(&mut self, params: TokenID) -> Result<ActorIDSet, RuntimeError> {
+        Ok(self.nft().approved_operators(params)?)
+    }
+
+    fn is_approved_for_all(
+        &mut self,
+        params: IsApprovedForAllParams,
+    ) -> Result<bool, RuntimeError> {
+        Ok(self.nft().is_account_operator(&params.owner, &params.operator)?)
+    }
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct MintParams {
+    pub initial_owner: Address,
+    pub metadata: Vec<String>,
+    pub operator_data: RawBytes,
+}
+
+impl<S: Syscalls, BS: Blockstore> FactoryNFT<S, BS> {
+    pub fn new(
+        runtime: ActorRuntime<S, BS>,
+        name: String,
+        symbol: String,
+        minter: Option<ActorID>,
+    ) -> Self {
+        let metadata = CollectionMetadata { name, symbol, ..Default::default() };
+        FactoryNFT {
+            state: FactoryNFTState {
+                nft: NFTState::new_with_metadata(&runtime, metadata).unwrap(),
+                minter,
+            },
+            runtime,
+        }
+    }
+
+    pub fn caller_address(&self) -> Address {
+        let caller = self.runtime.caller();
+        Address::new_id(caller)
+    }
+
+    pub fn nft(&mut self) -> NFT<'_, &S, &BS> {
+        NFT::wrap(self.runtime.by_ref(), &mut self.state.nft)
+    }
+
+    pub fn load(runtime: ActorRuntime<S, BS>, cid: &Cid) -> Result<Self, RuntimeError> {
+        Ok(FactoryNFT { state: FactoryNFTState::load(&runtime, cid)?, runtime })
+    }
+
+    pub fn save(&self) -> Result<Cid, RuntimeError> {
+        let serialized = fvm_ipld_encoding::to_vec(&self.state)
+            .map_err(|err| RuntimeError::Serialization(err.to_string()))?;
+        let block = Block { codec: DAG_CBOR, data: serialized };
+        self.runtime
+            .put(Code::Blake2b256, &block)
+            .map_err(|err| RuntimeError::Serialization(err.to_string()))
+    }
+
+    fn reload(&mut self, initial_cid: &Cid) -> Result<(), RuntimeError> {
+        let new_cid = self.runtime.root_cid()?;
+        if new_cid != *initial_cid {
+            let new_state = FactoryNFTState::load(&self.runtime, &new_cid)?;
+            let _old = std::mem::replace(&mut self.state, new_state);
+        }
+        Ok(())
+    }
+
+    pub fn runtime(&self) -> &ActorRuntime<S, BS> {
+        &self.runtime
+    }
+
+    pub fn mint(&mut self, params: MintParams) -> Result<MintReturn, RuntimeError> {
+        // check if the caller matches our authorise mint operator
+        // no minter address means minting has been permanently disabled
+        let minter = self.state.minter.ok_or(RuntimeError::MintingDisabled)?;
+        let caller_id = self.runtime.caller();
+        if caller_id != minter {
+            return Err(RuntimeError::AddressNotAuthorized);
+        }
+
+        let mut hook = self.nft().mint(
+            &Address::new_id(caller_id),
+            &params.initial_owner,
+            params.metadata,
+            params.operator_data,
+            RawBytes::default(),
+        )?;
+
+        let cid = self.save()?;
+        self.runtime.set_root(&cid)?;
+
+        let hook_ret = hook.call(&self.runtime)?;
+
+        self.reload(&cid)?;
+        let ret = self.state.nft.mint_return(&self.runtime, hook_ret)?;
+
+        Ok(ret)
+    }
+
+    /// Permanently disable minting
+    /// Only the authorised mint operator can do this
+    pub fn disable_mint(&mut self) -> Result<(), RuntimeError> {
+        // no minter means minting has already been permanently disabled
+        // we return this if already disabled because it will make more sense than failing the address check below
+        let minter = self.state.minter.ok_or(RuntimeError::MintingDisabled)?;
+        let caller_id = self.runtime.caller();
+        if caller_id != minter {
+            return Err(RuntimeError::AddressNotAuthorized);
+        }
+
+        self.state.minter = None;
+        Ok(())
+    }
+}
+
+pub fn deserialize_params<O: DeserializeOwned>(params: u32) -> O {
+    let params = sdk::message::params_raw(params).unwrap();
+    let params = params.unwrap();
+    params.deserialize().unwrap()
+}
+
+pub fn return_ipld<T>(value: &T) -> std::result::Result<u32, RuntimeError>
+where
+    T: Serialize + ?Sized,
+{
+    let bytes = fvm_ipld_encoding::to_vec(value)?;
+    Ok(sdk::ipld::put_block(DAG_CBOR, bytes.as_slice())?)
+}
+
+#[cfg(test)]
+mod test {
+    use frc53_nft::{
+        state::StateError,
+        types::{
+            ApproveForAllParams, ApproveParams, BurnFromParams, TransferFromParams, TransferParams,
+            FRC53NFT,
+        },
+        NFTError,
+    };
+    use fvm_actor_utils::{
+        shared_blockstore::SharedMemoryBlockstore, syscalls::fake_syscalls::FakeSyscalls,
+        util::ActorRuntime,
+    };
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+
+    use crate::{FactoryNFT, MintParams, RuntimeError};
+
+    const ALICE: Address = Address::new_id(1);
+    const BOB: Address = Address::new_id(2);
+    const CHARLIE: Address = Address::new_id(3);
+
+    // set up an NFT instance for testing
+    // fake syscalls allow us to set the ActorID of the caller, which we'll normally set to the minter
+    fn setup_nft(minter: &Address) -> FactoryNFT<FakeSyscalls, SharedMemoryBlockstore> {
+        let runtime =
+            ActorRuntime::<FakeSyscalls, SharedMemoryBlockstore>::new_shared_test_runtime();
+        let actor_id = runtime.resolve_id(minter).unwrap();
+
+        // set the minter as the message caller so calls to mint() will succeed
+        runtime.syscalls.set_caller_id(actor_id);
+
+        FactoryNFT::new(runtime, String::from("Test NFT"), String::from("TNFT"), Some(actor_id))
+    }
+
+    fn mint_params(initial_owner: Address, count: usize) -> MintParams {
+        MintParams {
+            initial_owner,
+            metadata: vec![String::new(); count],
+            operator_data: RawBytes::default(),
+        }
+    }
+
+    #[test]
+    fn it_mints() {
+        let mut nft = setup_nft(&ALICE);
+
+        let ret = nft.mint(mint_params(BOB, 2)).unwrap();
+
+        assert_eq!(ret.balance, 2);
+        assert_eq!(ret.token_ids, vec![0, 1]);
+        assert_eq!(nft.balance_of(BOB).unwrap(), 2);
+        assert_eq!(nft.owner_of(1).unwrap(), 2);
+        assert_eq!(nft.total_supply(), 2);
+    }
+
+    #[test]
+    fn it_denies_unauthorised_minter() {
+        let mut nft = setup_nft(&BOB);
+
+        nft.runtime.syscalls.set_caller_id(nft.runtime.resolve_id(&ALICE).unwrap());
+        let err = nft.mint(mint_params(ALICE, 1)).unwrap_err();
+
+        match err {
+            RuntimeError::AddressNotAuthorized => {}
+            _ => panic!("unexpected error"),
+        }
+    }
+
+    #[test]
+    fn it_disables_minting() {
+        let mut nft = setup_nft(&ALICE);
+
+        // first, we mint successfully
+        nft.mint(mint_params(BOB, 1)).unwrap();
+        assert_eq!(nft.total_supply(), 1);
+
+        // now disable minting
+        nft.disable_mint().unwrap();
+
+        // and try minting again (should fail)
+        let err = nft.mint(mint_params(BOB, 1)).unwrap_err();
+        assert_eq!(nft.total_supply(), 1);
+        match err {
+            RuntimeError::MintingDisabled => {}
+            _ => panic!("unexpected error"),
+        }
+
+        // disabling again fails in the same way
+        let err = nft.disable_mint().unwrap_err();
+        match err {
+            RuntimeError::MintingDisabled => {}
+            _ => panic!("unexpected error"),
+        }
+    }
+
+    #[test]
+    fn it_denies_unauthorised_caller_from_disabling_mint() {
+        let mut nft = setup_nft(&BOB);
+
+        nft.runtime.syscalls.set_caller_id(nft.runtime.resolve_id(&ALICE).unwrap());
+        let err = nft.disable_mint().unwrap_err();
+
+        match err {
+            RuntimeError::AddressNotAuthorized => {}
+            _ => panic!("unexpected error"),
+        }
+    }
+
+    #[test]
+    fn it_has_name_and_symbol() {
+        let nft = setup_nft(&ALICE);
+        assert_eq!(nft.name(), "Test NFT");
+        assert_eq!(nft.symbol(), "TNFT");
+        assert_eq!(nft.collection_metadata().name, "Test NFT");
+    }
+
+    #[test]
+    fn it_transfers() {
+        let mut nft = setup_nft(&ALICE);
+        nft.mint(mint_params(ALICE, 3)).unwrap();
+
+        let ret = nft
+            .transfer(TransferParams {
+                to: BOB,
+                token_ids: vec![0, 2],
+                operator_data: RawBytes::default(),
+                per_token_data: vec![],
+            })
+            .unwrap();
+
+        assert_eq!(ret.from_balance, 1);
+        assert_eq!(ret.to_balance, 2);
+        assert_eq!(nft.owner_of(0).unwrap(), 2);
+        assert_eq!(nft.owner_of(1).unwrap(), 1);
+        assert_eq!(nft.total_supply(), 3);
+    }
+
+    #[test]
+    fn it_transfers_from_approvals() {
+        let mut nft = setup_nft(&ALICE);
+        nft.mint(mint_params(ALICE, 3)).unwrap();
+
+        // alice approves bob for token 0 and charlie for all her tokens
+        nft.approve(ApproveParams { operator: BOB, token_ids: vec![0] }).unwrap();
+        nft.approve_for_all(ApproveForAllParams { operator: CHARLIE }).unwrap();
+        assert!(nft.get_approved(0).unwrap().get(2));
+
+        // bob can transfer token 0 but not token 1
+        nft.runtime.syscalls.set_caller_id(nft.runtime.resolve_id(&BOB).unwrap());
+        let err = nft
+            .transfer_from(TransferFromParams {
+                from: ALICE,
+                to: BOB,
+                token_ids: vec![1],
+                operator_data: RawBytes::default(),
+                per_token_data: vec![],
+            })
+            .unwrap_err();
+        match err {
+            RuntimeError::NFT(NFTError::NFTState(StateError::NotAuthorized { .. })) => {}
+            e => panic!("unexpected error {e:?}"),
+        }
+        nft.transfer_from(TransferFromParams {
+            from: ALICE,
+            to: BOB,
+            token_ids: vec![0],
+            operator_data: RawBytes::default(),
+            per_token_data: vec![],
+        })
+        .unwrap();
+        assert_eq!(nft.owner_of(0).unwrap(), 2);
+
+        // charlie can burn alice's remaining tokens
+        nft.runtime.syscalls.set_caller_id(nft.runtime.resolve_id(&CHARLIE).unwrap());
+        let ret = nft.burn_from(BurnFromParams { from: ALICE, token_ids: vec![1, 2] }).unwrap();
+        assert_eq!(ret.balance, 0);
+        assert_eq!(nft.total_supply(), 1);
+    }
+
+    #[test]
+    fn it_burns() {
+        let mut nft = setup_nft(&ALICE);
+        nft.mint(mint_params(ALICE, 2)).unwrap();
+
+        let ret = nft.burn(vec![1]).unwrap();
+        assert_eq!(ret.balance, 1);
+        assert_eq!(ret.supply, 1);
+        assert_eq!(nft.balance_of(ALICE).unwrap(), 1);
+        assert_eq!(nft.total_supply(), 1);
+    }
+}
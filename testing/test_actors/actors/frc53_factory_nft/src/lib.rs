@@ -0,0 +1,64 @@
+use frc42_dispatch::{fallback::InvokeFallback, match_method};
+use frc53_nft::dispatch::frc53_invoke;
+use fvm_actor_utils::{
+    blockstore::Blockstore, syscalls::fvm_syscalls::FvmSyscalls, util::ActorRuntime,
+};
+use fvm_sdk::NO_DATA_BLOCK_ID;
+use fvm_shared::error::ExitCode;
+use nft_impl::{
+    construct_nft, deserialize_params, return_ipld, FactoryNFT, MintParams, RuntimeError,
+};
+
+fn nft_invoke(method_num: u64, params: u32) -> Result<u32, RuntimeError> {
+    let runtime = ActorRuntime::<FvmSyscalls, Blockstore>::new_fvm_runtime();
+    match_method!(method_num, {
+        "Constructor" => {
+            let params = deserialize_params(params);
+            construct_nft(runtime, params)
+        }
+        "Mint" => {
+            let root_cid = runtime.root_cid()?;
+            let params: MintParams = deserialize_params(params);
+            let mut nft_actor = FactoryNFT::load(runtime, &root_cid)?;
+            let res = nft_actor.mint(params)?;
+            return_ipld(&res)
+        }
+        "DisableMint" => {
+            let root_cid = runtime.root_cid()?;
+            let mut nft_actor = FactoryNFT::load(runtime, &root_cid)?;
+            // disable minting forever
+            nft_actor.disable_mint()?;
+            // save state
+            let cid = nft_actor.save()?;
+            nft_actor.runtime().set_root(&cid)?;
+            // no return
+            Ok(NO_DATA_BLOCK_ID)
+        }
+        _ => {
+            let root_cid = runtime.root_cid()?;
+            let mut nft_actor = FactoryNFT::load(runtime, &root_cid)?;
+
+            frc53_invoke(method_num, params, &mut nft_actor, |nft| {
+                // `nft` is passed through from the original handle provided in the function call
+                // so it won't break mutable borrow rules when used here
+                let cid = nft.save()?;
+                nft.runtime().set_root(&cid)?;
+                Ok(())
+            })
+            .or_abort_unhandled(method_num)
+        }
+    })
+}
+
+#[no_mangle]
+pub fn invoke(params: u32) -> u32 {
+    std::panic::set_hook(Box::new(|info| {
+        fvm_sdk::vm::abort(ExitCode::USR_ASSERTION_FAILED.value(), Some(&format!("{info}")))
+    }));
+
+    let method_num = fvm_sdk::message::method_number();
+    match nft_invoke(method_num, params) {
+        Ok(ret) => ret,
+        Err(err) => fvm_sdk::vm::abort(ExitCode::from(&err).value(), Some(&err.to_string())),
+    }
+}
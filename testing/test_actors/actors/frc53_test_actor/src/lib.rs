@@ -1,3 +1,5 @@
+use cid::multihash::Code;
+use frc42_dispatch::match_method::{abort_reserved, abort_unhandled};
 use frc42_dispatch::{match_method, method_hash};
 use frc53_nft::receiver::FRC53TokenReceived;
 use frc53_nft::receiver::FRC53_TOKEN_TYPE;
@@ -13,7 +15,7 @@ use fvm_ipld_encoding::{
 use fvm_sdk as sdk;
 use fvm_shared::receipt::Receipt;
 use fvm_shared::sys::SendFlags;
-use fvm_shared::{address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode};
+use fvm_shared::{address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode, MethodNum};
 use sdk::NO_DATA_BLOCK_ID;
 use serde::{Deserialize, Serialize};
 
@@ -33,7 +35,8 @@ where
 }
 
 /// Action to take in receiver hook or Action method
-/// This gets serialized and sent along as operator_data
+/// This gets serialized and sent along as operator_data. Transfers without operator_data get the
+/// action set by the Configure method instead, which is Accept until configured otherwise.
 #[derive(Serialize, Deserialize, Debug)]
 pub enum TestAction {
     /// Accept the tokens
@@ -44,6 +47,10 @@ pub enum TestAction {
     Transfer(Address, Vec<TokenID>, RawBytes),
     /// Burn incoming tokens
     Burn(Vec<TokenID>),
+    /// Reject the tokens, aborting with the given exit code, which must be a user exit code
+    RejectWith(ExitCode),
+    /// Call a method on the token actor, returning the receipt of the call
+    ReEnter { method: MethodNum, params: RawBytes },
 }
 
 /// Params for Action method call
@@ -67,19 +74,32 @@ pub fn action(action: TestAction) -> RawBytes {
     RawBytes::serialize(action).unwrap()
 }
 
+/// Returns the action configured for transfers without operator_data
+fn configured_action() -> TestAction {
+    let data = sdk::ipld::get(&sdk::sself::root().unwrap()).unwrap();
+    fvm_ipld_encoding::from_slice(&data).unwrap()
+}
+
+/// Sets the action for transfers without operator_data
+fn configure(action: &TestAction) {
+    let data = fvm_ipld_encoding::to_vec(action).unwrap();
+    let cid = sdk::ipld::put(Code::Blake2b256.into(), 32, DAG_CBOR, &data).unwrap();
+    sdk::sself::set_root(&cid).unwrap();
+}
+
 /// Execute the Transfer action
 fn transfer(token: Address, to: Address, token_ids: Vec<TokenID>, operator_data: RawBytes) -> u32 {
-    let transfer_params = TransferParams { to, token_ids, operator_data };
-    let ret = sdk::send::send(
-        &token,
-        method_hash!("Transfer"),
-        IpldBlock::serialize_cbor(&transfer_params).unwrap(),
-        TokenAmount::zero(),
-        None,
-        SendFlags::empty(),
-    )
-    .unwrap();
-    // ignore failures at this level and return the transfer call receipt so caller can decide what to do
+    let transfer_params = TransferParams { to, token_ids, operator_data, per_token_data: vec![] };
+    call_token(token, method_hash!("Transfer"), RawBytes::serialize(transfer_params).unwrap())
+}
+
+/// Call a method on the token actor, for the Transfer and ReEnter actions
+fn call_token(token: Address, method: MethodNum, params: RawBytes) -> u32 {
+    let params = (!params.is_empty()).then(|| IpldBlock { codec: DAG_CBOR, data: params.to_vec() });
+    let ret =
+        sdk::send::send(&token, method, params, TokenAmount::zero(), None, SendFlags::empty())
+            .unwrap();
+    // ignore failures at this level and return the call receipt so caller can decide what to do
     return_ipld(&Receipt {
         exit_code: ret.exit_code,
         return_data: ret.return_data.map_or(RawBytes::default(), |b| RawBytes::new(b.data)),
@@ -114,6 +134,7 @@ fn invoke(input: u32) -> u32 {
     let method_num = sdk::message::method_number();
     match_method!(method_num, {
         "Constructor" => {
+            configure(&TestAction::Accept);
             NO_DATA_BLOCK_ID
         },
         "Receive" => {
@@ -129,8 +150,12 @@ fn invoke(input: u32) -> u32 {
             // get token transfer data
             let token_params: FRC53TokenReceived = params.payload.deserialize().unwrap();
 
-            // todo: examine the operator_data to determine our next move
-            let action: TestAction = token_params.operator_data.deserialize().unwrap();
+            // operator_data determines our next move, falling back to the configured action
+            let action = if token_params.operator_data.is_empty() {
+                configured_action()
+            } else {
+                token_params.operator_data.deserialize().unwrap()
+            };
             match action {
                 TestAction::Accept => {
                     // do nothing, return success
@@ -143,6 +168,9 @@ fn invoke(input: u32) -> u32 {
                         Some("rejecting transfer"),
                     );
                 }
+                TestAction::RejectWith(exit_code) => {
+                    sdk::vm::abort(exit_code.value(), Some("rejecting transfer"));
+                }
                 TestAction::Transfer(to, token_ids, operator_data) => {
                     // transfer to a target address
                     transfer(Address::new_id(sdk::message::caller()), to, token_ids, operator_data)
@@ -151,6 +179,10 @@ fn invoke(input: u32) -> u32 {
                     // burn the tokens
                     burn(Address::new_id(sdk::message::caller()), token_ids)
                 }
+                TestAction::ReEnter { method, params } => {
+                    // call back into the token while the transfer is still in progress
+                    call_token(Address::new_id(sdk::message::caller()), method, params)
+                }
             }
         },
         "Action" => {
@@ -158,7 +190,7 @@ fn invoke(input: u32) -> u32 {
             let params: ActionParams = deserialize_params(input);
 
             match params.action {
-                TestAction::Accept | TestAction::Reject => {
+                TestAction::Accept | TestAction::Reject | TestAction::RejectWith(_) => {
                     sdk::vm::abort(
                         ExitCode::USR_ILLEGAL_ARGUMENT.value(),
                         Some("invalid argument"),
@@ -172,13 +204,18 @@ fn invoke(input: u32) -> u32 {
                     // burn the tokens
                     burn(params.token_address, token_ids)
                 }
+                TestAction::ReEnter { method, params: call_params } => {
+                    call_token(params.token_address, method, call_params)
+                }
             }
         }
-        _ => {
-            sdk::vm::abort(
-                ExitCode::USR_UNHANDLED_MESSAGE.value(),
-                Some("Unknown method number"),
-            );
+        "Configure" => {
+            // set the action for transfers without operator_data
+            let action: TestAction = deserialize_params(input);
+            configure(&action);
+            NO_DATA_BLOCK_ID
         }
+        reserved => abort_reserved(method_num),
+        _ => abort_unhandled(method_num),
     })
 }
@@ -0,0 +1,273 @@
+//! An actor standing in for an EVM contract which holds FRC-46 tokens
+//!
+//! EVM contracts live at delegated (f410) addresses and are called through the `InvokeEVM` method
+//! with Solidity calldata, a 4-byte selector (the start of the keccak256 hash of the function
+//! signature) followed by ABI-encoded arguments. Any other exported method, such as the receiver
+//! hook, is passed to the contract as a call to `handle_filecoin_method`, which reverts if the
+//! contract doesn't implement it. This actor emulates both, routing calldata through a keccak
+//! dispatch shim to a few ERC-20 style functions that call an FRC-46 token, so tests can send tokens
+//! to and from an EVM-side caller without deploying EVM bytecode.
+use cid::multihash::Code;
+use frc42_dispatch::match_method::abort_reserved;
+use frc42_dispatch::{match_method, method_hash};
+use frc46_token::receiver::FRC46_TOKEN_TYPE;
+use frc46_token::token::types::{IncreaseAllowanceParams, TransferFromParams, TransferParams};
+use fvm_actor_utils::messaging::RECEIVER_HOOK_METHOD_NUM;
+use fvm_actor_utils::receiver::UniversalReceiverParams;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::{RawBytes, DAG_CBOR};
+use fvm_sdk as sdk;
+use fvm_shared::bigint::{BigInt, Sign};
+use fvm_shared::crypto::hash::SupportedHashes;
+use fvm_shared::sys::SendFlags;
+use fvm_shared::{
+    address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode, ActorID, MethodNum,
+};
+use sdk::NO_DATA_BLOCK_ID;
+use serde_tuple::{Deserialize_tuple, Serialize_tuple};
+
+/// The exit code of an EVM contract that reverted
+pub const EVM_CONTRACT_REVERTED: ExitCode = ExitCode::new(33);
+
+/// The namespace of Ethereum addresses in delegated (f410) addresses
+const EAM_ACTOR_ID: ActorID = 10;
+
+/// The prefix of an Ethereum address which holds an actor ID in its last 8 bytes
+const ID_ADDRESS_PREFIX: [u8; 12] = [0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+/// Size of an ABI-encoded word
+const WORD: usize = 32;
+
+/// Signature of the function EVM contracts implement to handle Filecoin methods
+const HANDLE_FILECOIN_METHOD: &str = "handle_filecoin_method(uint64,uint64,bytes)";
+
+/// Constructor params, which are also the state of the actor
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct MockContract {
+    /// The token called by the ERC-20 style functions
+    pub token: Address,
+    /// Whether the contract implements `handle_filecoin_method`, without which it rejects receiver
+    /// hooks as most contracts do
+    pub handles_filecoin_methods: bool,
+}
+
+type Function = fn(&MockContract, &[u8]) -> Vec<u8>;
+
+/// The functions of the contract, by Solidity signature
+const FUNCTIONS: &[(&str, Function)] = &[
+    ("transfer(address,uint256)", transfer),
+    ("transferFrom(address,address,uint256)", transfer_from),
+    ("increaseAllowance(address,uint256)", increase_allowance),
+    ("balanceOf(address)", balance_of),
+    (HANDLE_FILECOIN_METHOD, handle_filecoin_method),
+];
+
+fn load() -> MockContract {
+    let data = sdk::ipld::get(&sdk::sself::root().unwrap()).unwrap();
+    fvm_ipld_encoding::from_slice(&data).unwrap()
+}
+
+fn save(contract: &MockContract) {
+    let data = fvm_ipld_encoding::to_vec(contract).unwrap();
+    let cid = sdk::ipld::put(Code::Blake2b256.into(), 32, DAG_CBOR, &data).unwrap();
+    sdk::sself::set_root(&cid).unwrap();
+}
+
+fn revert(message: &str) -> ! {
+    sdk::vm::abort(EVM_CONTRACT_REVERTED.value(), Some(message))
+}
+
+/// Returns the 4-byte selector of a Solidity function signature
+fn selector(signature: &str) -> [u8; 4] {
+    let hash = sdk::crypto::hash_owned(SupportedHashes::Keccak256, signature.as_bytes());
+    hash[..4].try_into().unwrap()
+}
+
+/// Calls the contract function selected by the calldata, reverting if there is none
+fn dispatch(contract: &MockContract, calldata: &[u8]) -> Vec<u8> {
+    if calldata.len() < 4 {
+        revert("calldata has no selector");
+    }
+    let (called, args) = calldata.split_at(4);
+    let function = FUNCTIONS
+        .iter()
+        .filter(|(signature, _)| {
+            *signature != HANDLE_FILECOIN_METHOD || contract.handles_filecoin_methods
+        })
+        .find(|(signature, _)| selector(signature) == called)
+        .map(|(_, function)| function)
+        .unwrap_or_else(|| revert("no function matches the selector"));
+    function(contract, args)
+}
+
+/// Returns the ABI-encoded argument at an index
+fn arg(args: &[u8], index: usize) -> &[u8] {
+    args.get(index * WORD..(index + 1) * WORD).unwrap_or_else(|| revert("missing argument"))
+}
+
+fn decode_u64(word: &[u8]) -> u64 {
+    u64::from_be_bytes(word[WORD - 8..].try_into().unwrap())
+}
+
+/// Decodes an Ethereum address to the Filecoin address it stands for
+fn decode_address(word: &[u8]) -> Address {
+    let eth_address = &word[WORD - 20..];
+    if eth_address[..12] == ID_ADDRESS_PREFIX {
+        Address::new_id(decode_u64(word))
+    } else {
+        Address::new_delegated(EAM_ACTOR_ID, eth_address).unwrap()
+    }
+}
+
+fn decode_amount(word: &[u8]) -> TokenAmount {
+    TokenAmount::from_atto(BigInt::from_bytes_be(Sign::Plus, word))
+}
+
+/// Decodes a dynamic `bytes` argument, which is found at the offset given by its head
+fn decode_bytes(args: &[u8], index: usize) -> &[u8] {
+    let offset = decode_u64(arg(args, index)) as usize;
+    let len = decode_u64(args.get(offset..offset + WORD).unwrap_or_else(|| revert("bad offset")));
+    let start = offset + WORD;
+    args.get(start..start + len as usize).unwrap_or_else(|| revert("bytes out of bounds"))
+}
+
+fn encode_u64(value: u64) -> Vec<u8> {
+    let mut word = vec![0; WORD];
+    word[WORD - 8..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn encode_amount(amount: &TokenAmount) -> Vec<u8> {
+    let (_, bytes) = amount.atto().to_bytes_be();
+    let mut word = vec![0; WORD - bytes.len()];
+    word.extend(bytes);
+    word
+}
+
+/// Encodes a dynamic `bytes` value as the tail of a call, padded to a whole number of words
+fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = encode_u64(bytes.len() as u64);
+    encoded.extend(bytes);
+    encoded.resize(WORD + bytes.len().div_ceil(WORD) * WORD, 0);
+    encoded
+}
+
+/// Calls a method on the token, reverting if it fails as a Solidity call would
+fn call_token<P: serde::Serialize>(
+    contract: &MockContract,
+    method: MethodNum,
+    params: &P,
+) -> Option<IpldBlock> {
+    let ret = sdk::send::send(
+        &contract.token,
+        method,
+        IpldBlock::serialize_cbor(params).unwrap(),
+        TokenAmount::zero(),
+        None,
+        SendFlags::empty(),
+    )
+    .unwrap();
+    if !ret.exit_code.is_success() {
+        revert(&format!("token call failed with {}", ret.exit_code));
+    }
+    ret.return_data
+}
+
+fn transfer(contract: &MockContract, args: &[u8]) -> Vec<u8> {
+    let params = TransferParams {
+        to: decode_address(arg(args, 0)),
+        amount: decode_amount(arg(args, 1)),
+        operator_data: RawBytes::default(),
+    };
+    call_token(contract, method_hash!("Transfer"), &params);
+    encode_u64(true as u64)
+}
+
+fn transfer_from(contract: &MockContract, args: &[u8]) -> Vec<u8> {
+    let params = TransferFromParams {
+        from: decode_address(arg(args, 0)),
+        to: decode_address(arg(args, 1)),
+        amount: decode_amount(arg(args, 2)),
+        operator_data: RawBytes::default(),
+    };
+    call_token(contract, method_hash!("TransferFrom"), &params);
+    encode_u64(true as u64)
+}
+
+fn increase_allowance(contract: &MockContract, args: &[u8]) -> Vec<u8> {
+    let params = IncreaseAllowanceParams {
+        operator: decode_address(arg(args, 0)),
+        increase: decode_amount(arg(args, 1)),
+    };
+    call_token(contract, method_hash!("IncreaseAllowance"), &params);
+    encode_u64(true as u64)
+}
+
+fn balance_of(contract: &MockContract, args: &[u8]) -> Vec<u8> {
+    let owner = decode_address(arg(args, 0));
+    let balance: TokenAmount =
+        call_token(contract, method_hash!("BalanceOf"), &owner).unwrap().deserialize().unwrap();
+    encode_amount(&balance)
+}
+
+/// Accepts FRC-46 tokens from the receiver hook, returning `(exit_code, codec, return_data)`
+fn handle_filecoin_method(_: &MockContract, args: &[u8]) -> Vec<u8> {
+    let method = decode_u64(arg(args, 0));
+    let params = decode_bytes(args, 2);
+    let accepted = method == RECEIVER_HOOK_METHOD_NUM
+        && fvm_ipld_encoding::from_slice::<UniversalReceiverParams>(params)
+            .map_or(false, |params| params.type_ == FRC46_TOKEN_TYPE);
+    let exit_code = if accepted { ExitCode::OK } else { ExitCode::USR_UNHANDLED_MESSAGE };
+
+    let mut ret = encode_u64(exit_code.value().into());
+    ret.extend(encode_u64(0));
+    ret.extend(encode_u64(3 * WORD as u64));
+    ret.extend(encode_bytes(&[]));
+    ret
+}
+
+/// Passes an exported method to the contract as a call to `handle_filecoin_method`
+fn invoke_filecoin_method(method: MethodNum, input: u32) -> u32 {
+    let (codec, params) = match sdk::message::params_raw(input).unwrap() {
+        Some(block) => (block.codec, block.data),
+        None => (0, Vec::new()),
+    };
+    let mut calldata = selector(HANDLE_FILECOIN_METHOD).to_vec();
+    calldata.extend(encode_u64(method));
+    calldata.extend(encode_u64(codec));
+    calldata.extend(encode_u64(3 * WORD as u64));
+    calldata.extend(encode_bytes(&params));
+
+    let ret = dispatch(&load(), &calldata);
+    let exit_code = decode_u64(arg(&ret, 0)) as u32;
+    if exit_code != 0 {
+        sdk::vm::abort(exit_code, Some("contract rejected the method"));
+    }
+    NO_DATA_BLOCK_ID
+}
+
+#[no_mangle]
+fn invoke(input: u32) -> u32 {
+    std::panic::set_hook(Box::new(|info| {
+        sdk::vm::abort(ExitCode::USR_ASSERTION_FAILED.value(), Some(&format!("{info}")))
+    }));
+
+    let method_num = sdk::message::method_number();
+    match_method!(method_num, {
+        "Constructor" => {
+            let params = sdk::message::params_raw(input).unwrap().unwrap();
+            save(&fvm_ipld_encoding::from_slice(&params.data).unwrap());
+            NO_DATA_BLOCK_ID
+        },
+        "InvokeEVM" => {
+            // calldata is sent as a CBOR byte string, and so is the return data
+            let params = sdk::message::params_raw(input).unwrap().unwrap();
+            let calldata: RawBytes = fvm_ipld_encoding::from_slice(&params.data).unwrap();
+            let ret = RawBytes::new(dispatch(&load(), &calldata));
+            let bytes = fvm_ipld_encoding::to_vec(&ret).unwrap();
+            sdk::ipld::put_block(DAG_CBOR, &bytes).unwrap()
+        },
+        reserved => abort_reserved(method_num),
+        other => invoke_filecoin_method(other, input),
+    })
+}
@@ -0,0 +1,154 @@
+use frc42_dispatch::match_method::{abort_reserved, abort_unhandled};
+use frc42_dispatch::{match_method, method_hash};
+use frc46_token::receiver::{FRC46TokenReceived, FRC46_TOKEN_TYPE};
+use frc46_token::token::types::{BurnParams, TransferFromParams, TransferParams};
+use frc53_nft::receiver::{FRC53TokenReceived, FRC53_TOKEN_TYPE};
+use frc53_nft::types::{self as nft, TokenID};
+use fvm_actor_utils::receiver::UniversalReceiverParams;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::{de::DeserializeOwned, RawBytes, DAG_CBOR};
+use fvm_sdk as sdk;
+use fvm_shared::receipt::Receipt;
+use fvm_shared::sys::SendFlags;
+use fvm_shared::{address::Address, bigint::Zero, econ::TokenAmount, error::ExitCode, MethodNum};
+use sdk::NO_DATA_BLOCK_ID;
+use serde::{Deserialize, Serialize};
+
+/// Grab the incoming parameters and convert from RawBytes to deserialized struct
+pub fn deserialize_params<O: DeserializeOwned>(params: u32) -> O {
+    let params = sdk::message::params_raw(params).unwrap().unwrap();
+    let params = RawBytes::new(params.data);
+    params.deserialize().unwrap()
+}
+
+fn return_ipld<T>(value: &T) -> u32
+where
+    T: Serialize + ?Sized,
+{
+    let bytes = fvm_ipld_encoding::to_vec(value).unwrap();
+    sdk::ipld::put_block(DAG_CBOR, &bytes).unwrap()
+}
+
+/// Re-entrant call to make from the hook when receiving FRC-0046 tokens
+/// This gets serialized and sent along as operator_data
+#[derive(Serialize, Deserialize, Debug)]
+pub enum TokenAttack {
+    /// Transfer from our own balance
+    Transfer { to: Address, amount: TokenAmount },
+    /// Transfer from another account, using an allowance it gave us
+    TransferFrom { from: Address, to: Address, amount: TokenAmount },
+    /// Burn from our own balance
+    Burn { amount: TokenAmount },
+}
+
+/// Re-entrant call to make from the hook when receiving FRC-0053 NFTs
+/// This gets serialized and sent along as operator_data
+#[derive(Serialize, Deserialize, Debug)]
+pub enum NFTAttack {
+    /// Transfer tokens we own
+    Transfer { to: Address, token_ids: Vec<TokenID> },
+    /// Transfer tokens owned by another account, which approved us as an operator
+    TransferFrom { from: Address, to: Address, token_ids: Vec<TokenID> },
+    /// Burn tokens we own
+    Burn { token_ids: Vec<TokenID> },
+}
+
+/// Call a method on the token actor, returning its receipt whether or not it succeeded
+fn call_token<P: Serialize>(method: MethodNum, params: &P) -> Receipt {
+    let token = Address::new_id(sdk::message::caller());
+    let ret = sdk::send::send(
+        &token,
+        method,
+        IpldBlock::serialize_cbor(params).unwrap(),
+        TokenAmount::zero(),
+        None,
+        SendFlags::empty(),
+    )
+    .unwrap();
+    Receipt {
+        exit_code: ret.exit_code,
+        return_data: ret.return_data.map_or(RawBytes::default(), |b| RawBytes::new(b.data)),
+        gas_used: 0,
+        events_root: None,
+    }
+}
+
+fn token_attack(attack: TokenAttack) -> Receipt {
+    match attack {
+        TokenAttack::Transfer { to, amount } => {
+            let params = TransferParams { to, amount, operator_data: RawBytes::default() };
+            call_token(method_hash!("Transfer"), &params)
+        }
+        TokenAttack::TransferFrom { from, to, amount } => {
+            let params =
+                TransferFromParams { from, to, amount, operator_data: RawBytes::default() };
+            call_token(method_hash!("TransferFrom"), &params)
+        }
+        TokenAttack::Burn { amount } => call_token(method_hash!("Burn"), &BurnParams { amount }),
+    }
+}
+
+fn nft_attack(attack: NFTAttack) -> Receipt {
+    match attack {
+        NFTAttack::Transfer { to, token_ids } => {
+            let params = nft::TransferParams {
+                to,
+                token_ids,
+                operator_data: RawBytes::default(),
+                per_token_data: vec![],
+            };
+            call_token(method_hash!("Transfer"), &params)
+        }
+        NFTAttack::TransferFrom { from, to, token_ids } => {
+            let params = nft::TransferFromParams {
+                from,
+                to,
+                token_ids,
+                operator_data: RawBytes::default(),
+                per_token_data: vec![],
+            };
+            call_token(method_hash!("TransferFrom"), &params)
+        }
+        NFTAttack::Burn { token_ids } => call_token(method_hash!("Burn"), &token_ids),
+    }
+}
+
+#[no_mangle]
+fn invoke(input: u32) -> u32 {
+    std::panic::set_hook(Box::new(|info| {
+        sdk::vm::abort(ExitCode::USR_ASSERTION_FAILED.value(), Some(&format!("{info}")))
+    }));
+
+    let method_num = sdk::message::method_number();
+    match_method!(method_num, {
+        "Constructor" => {
+            NO_DATA_BLOCK_ID
+        },
+        "Receive" => {
+            // Receive is passed a UniversalReceiverParams
+            let params: UniversalReceiverParams = deserialize_params(input);
+
+            // operator_data carries the attack, while transfers without one are accepted
+            let receipt = match params.type_ {
+                FRC46_TOKEN_TYPE => {
+                    let received: FRC46TokenReceived = params.payload.deserialize().unwrap();
+                    if received.operator_data.is_empty() {
+                        return NO_DATA_BLOCK_ID;
+                    }
+                    token_attack(received.operator_data.deserialize().unwrap())
+                }
+                FRC53_TOKEN_TYPE => {
+                    let received: FRC53TokenReceived = params.payload.deserialize().unwrap();
+                    if received.operator_data.is_empty() {
+                        return NO_DATA_BLOCK_ID;
+                    }
+                    nft_attack(received.operator_data.deserialize().unwrap())
+                }
+                _ => panic!("invalid token type, rejecting transfer"),
+            };
+            return_ipld(&receipt)
+        },
+        reserved => abort_reserved(method_num),
+        _ => abort_unhandled(method_num),
+    })
+}
@@ -13,8 +13,13 @@ const ACTORS: &[&str] = &[
     "basic_transfer_actor",
     "frc46_test_actor",
     "frc53_test_actor",
+    "mock_fevm_actor",
+    "reentrant_receiver_actor",
     "greeter",
     "frc46_factory_token",
+    "frc46_token_factory",
+    "frc46_token_registry",
+    "frc53_factory_nft",
 ];
 
 fn main() -> Result<(), Box<dyn Error>> {
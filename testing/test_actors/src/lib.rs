@@ -12,5 +12,13 @@ pub const BASIC_TOKEN_ACTOR_BINARY: &[u8] = include_bytes!(wasm_bin!("basic_toke
 pub const BASIC_TRANSFER_ACTOR_BINARY: &[u8] = include_bytes!(wasm_bin!("basic_transfer_actor"));
 pub const FRC46_TEST_ACTOR_BINARY: &[u8] = include_bytes!(wasm_bin!("frc46_test_actor"));
 pub const FRC53_TEST_ACTOR_BINARY: &[u8] = include_bytes!(wasm_bin!("frc53_test_actor"));
+pub const MOCK_FEVM_ACTOR_BINARY: &[u8] = include_bytes!(wasm_bin!("mock_fevm_actor"));
+pub const REENTRANT_RECEIVER_ACTOR_BINARY: &[u8] =
+    include_bytes!(wasm_bin!("reentrant_receiver_actor"));
 pub const FRC46_FACTORY_TOKEN_ACTOR_BINARY: &[u8] =
     include_bytes!(wasm_bin!("frc46_factory_token"));
+pub const FRC46_TOKEN_FACTORY_ACTOR_BINARY: &[u8] =
+    include_bytes!(wasm_bin!("frc46_token_factory"));
+pub const FRC46_TOKEN_REGISTRY_ACTOR_BINARY: &[u8] =
+    include_bytes!(wasm_bin!("frc46_token_registry"));
+pub const FRC53_FACTORY_NFT_ACTOR_BINARY: &[u8] = include_bytes!(wasm_bin!("frc53_factory_nft"));